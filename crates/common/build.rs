@@ -23,6 +23,7 @@ pub fn main() {
     *config.git_mut().commit_count_mut() = false;
     *config.git_mut().commit_message_mut() = false;
     *config.git_mut().commit_timestamp_mut() = false;
-    *config.git_mut().sha_mut() = false;
+    *config.git_mut().sha_mut() = true;
+    *config.git_mut().sha_kind_mut() = vergen::ShaKind::Normal;
     vergen::vergen(config).expect("vergen failed; this is probably due to missing .git directory");
 }
@@ -0,0 +1,436 @@
+//! Accounting of the bytes downloaded from the feeder gateway and the Ethereum endpoint, for
+//! operators on metered connections.
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const METRIC_TOTAL: &str = "bandwidth_received_bytes_total";
+const METRIC_DAY: &str = "bandwidth_received_bytes_today";
+const METRIC_MONTH: &str = "bandwidth_received_bytes_this_month";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Gateway,
+    Ethereum,
+}
+
+impl Source {
+    const ALL: [Source; 2] = [Source::Gateway, Source::Ethereum];
+
+    /// The `source` label of the metrics.
+    pub fn label(self) -> &'static str {
+        match self {
+            Source::Gateway => "gateway",
+            Source::Ethereum => "ethereum",
+        }
+    }
+}
+
+/// A calendar month in UTC, displayed as `YYYY-MM`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Month(u64);
+
+impl std::fmt::Display for Month {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}", self.0 / 12, self.0 % 12 + 1)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("expected a month as YYYY-MM, got {0}")]
+pub struct ParseMonthError(String);
+
+impl FromStr for Month {
+    type Err = ParseMonthError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseMonthError(s.to_owned());
+        let (year, month) = s.split_once('-').ok_or_else(error)?;
+        let year: u64 = year.parse().map_err(|_| error())?;
+        let month: u64 = month.parse().map_err(|_| error())?;
+        if !(1..=12).contains(&month) {
+            return Err(error());
+        }
+
+        Ok(Self(year * 12 + month - 1))
+    }
+}
+
+/// Bytes received from a [Source] during a [Month] which have not been persisted yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MonthlyBytes {
+    pub source: Source,
+    pub month: Month,
+    pub bytes: u64,
+}
+
+/// Tallies the response bytes received from each [Source] per UTC day and month.
+///
+/// Every recorded response increments the `bandwidth_received_bytes_total` counter of its source,
+/// and updates the `bandwidth_received_bytes_today` and `bandwidth_received_bytes_this_month`
+/// gauges. The monthly tally, and with it the quota, survives restarts if the bytes
+/// [taken](Bandwidth::take_unpersisted) are persisted and [restored](Bandwidth::restore) at
+/// startup. The daily tally starts at zero on startup.
+///
+/// Cheap to clone, with all clones sharing the same tally.
+#[derive(Clone, Debug)]
+pub struct Bandwidth(Arc<Mutex<Usage>>);
+
+#[derive(Debug)]
+struct Usage {
+    monthly_quota: Option<u64>,
+    /// Days since the UNIX epoch.
+    day: u64,
+    /// Months since year zero.
+    month: u64,
+    /// Bytes received today and this month, indexed like [Source::ALL].
+    received: [(u64, u64); 2],
+    /// Bytes received since startup, indexed like [Source::ALL].
+    total: [u64; 2],
+    /// Bytes received since they were last [taken](Bandwidth::take_unpersisted).
+    unpersisted: Vec<MonthlyBytes>,
+}
+
+impl Bandwidth {
+    /// Share of the monthly quota, in percent, above which the quota counts as near.
+    const QUOTA_NEAR_PERCENT: u64 = 90;
+
+    pub fn new(monthly_quota: Option<u64>) -> Self {
+        Self::new_at(monthly_quota, SystemTime::now())
+    }
+
+    fn new_at(monthly_quota: Option<u64>, now: SystemTime) -> Self {
+        let day = days_since_epoch(now);
+        Self(Arc::new(Mutex::new(Usage {
+            monthly_quota,
+            day,
+            month: month_of(day),
+            received: Default::default(),
+            total: Default::default(),
+            unpersisted: Vec::new(),
+        })))
+    }
+
+    /// Records a response of `bytes` received from `source`.
+    pub fn record(&self, source: Source, bytes: u64) {
+        self.record_at(source, bytes, SystemTime::now());
+    }
+
+    fn record_at(&self, source: Source, bytes: u64, now: SystemTime) {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.roll_over(now);
+
+        let received = &mut usage.received[source as usize];
+        received.0 += bytes;
+        received.1 += bytes;
+        let (day, month) = *received;
+        usage.total[source as usize] += bytes;
+        let unpersisted = MonthlyBytes {
+            source,
+            month: Month(usage.month),
+            bytes,
+        };
+        usage.add_unpersisted(unpersisted);
+        drop(usage);
+
+        let label = source.label();
+        metrics::counter!(METRIC_TOTAL, bytes, "source" => label);
+        metrics::gauge!(METRIC_DAY, day as f64, "source" => label);
+        metrics::gauge!(METRIC_MONTH, month as f64, "source" => label);
+    }
+
+    /// Adds the `bytes` received from `source` during `month` before startup, as persisted from
+    /// [Bandwidth::take_unpersisted], to the tally. Bytes of past months are ignored.
+    pub fn restore(&self, source: Source, month: Month, bytes: u64) {
+        self.restore_at(source, month, bytes, SystemTime::now());
+    }
+
+    fn restore_at(&self, source: Source, month: Month, bytes: u64, now: SystemTime) {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.roll_over(now);
+        if month.0 != usage.month {
+            return;
+        }
+
+        let received = &mut usage.received[source as usize];
+        received.1 += bytes;
+        let month = received.1;
+        drop(usage);
+
+        metrics::gauge!(METRIC_MONTH, month as f64, "source" => source.label());
+    }
+
+    /// Takes the bytes received since the last call, per source and month, so that they can be
+    /// persisted.
+    pub fn take_unpersisted(&self) -> Vec<MonthlyBytes> {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut usage.unpersisted)
+    }
+
+    /// Hands back bytes [taken](Bandwidth::take_unpersisted) which could not be persisted, so
+    /// that they are taken again by the next call.
+    pub fn return_unpersisted(&self, unpersisted: Vec<MonthlyBytes>) {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        for bytes in unpersisted {
+            usage.add_unpersisted(bytes);
+        }
+    }
+
+    /// Bytes received from `source` since startup.
+    pub fn received_since_startup(&self, source: Source) -> u64 {
+        let usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.total[source as usize]
+    }
+
+    /// Bytes received from all sources during the current month.
+    pub fn received_this_month(&self) -> u64 {
+        self.received_this_month_at(SystemTime::now())
+    }
+
+    fn received_this_month_at(&self, now: SystemTime) -> u64 {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.roll_over(now);
+        usage.received_this_month()
+    }
+
+    /// Whether 90% of the monthly quota has been used, in which case non-essential downloads,
+    /// such as deferred classes and the pending block, should pause until the next month. Always
+    /// false without a quota.
+    pub fn quota_near(&self) -> bool {
+        self.quota_used_at(Self::QUOTA_NEAR_PERCENT, SystemTime::now())
+    }
+
+    /// Whether the monthly quota has been used up, in which case no more requests should be sent
+    /// until the next month. Always false without a quota.
+    pub fn quota_exceeded(&self) -> bool {
+        self.quota_used_at(100, SystemTime::now())
+    }
+
+    #[cfg(test)]
+    fn quota_near_at(&self, now: SystemTime) -> bool {
+        self.quota_used_at(Self::QUOTA_NEAR_PERCENT, now)
+    }
+
+    /// Whether `percent` of the monthly quota has been used.
+    fn quota_used_at(&self, percent: u64, now: SystemTime) -> bool {
+        let mut usage = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        usage.roll_over(now);
+
+        match usage.monthly_quota {
+            Some(quota) => usage.received_this_month() >= quota / 100 * percent,
+            None => false,
+        }
+    }
+}
+
+impl Usage {
+    fn received_this_month(&self) -> u64 {
+        self.received.iter().map(|(_, month)| month).sum()
+    }
+
+    fn add_unpersisted(&mut self, bytes: MonthlyBytes) {
+        let existing = self
+            .unpersisted
+            .iter_mut()
+            .find(|u| u.source == bytes.source && u.month == bytes.month);
+        match existing {
+            Some(existing) => existing.bytes += bytes.bytes,
+            None => self.unpersisted.push(bytes),
+        }
+    }
+
+    /// Resets the tallies of a day or month which has passed.
+    fn roll_over(&mut self, now: SystemTime) {
+        let day = days_since_epoch(now);
+        if day == self.day {
+            return;
+        }
+
+        let month = month_of(day);
+        let new_month = month != self.month;
+        for (source, received) in Source::ALL.iter().zip(self.received.iter_mut()) {
+            received.0 = 0;
+            metrics::gauge!(METRIC_DAY, 0.0, "source" => source.label());
+            if new_month {
+                received.1 = 0;
+                metrics::gauge!(METRIC_MONTH, 0.0, "source" => source.label());
+            }
+        }
+
+        self.day = day;
+        self.month = month;
+    }
+}
+
+fn days_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() / (24 * 60 * 60))
+        .unwrap_or_default()
+}
+
+/// Converts days since the UNIX epoch into months since year zero, using the proleptic Gregorian
+/// calendar.
+///
+/// Based on `civil_from_days` from <http://howardhinnant.github.io/date_algorithms.html>.
+fn month_of(days: u64) -> u64 {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months starting from March, so that the leap day is last.
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let (month, year) = match shifted_month {
+        0..=9 => (shifted_month + 2, year_of_era + era * 400),
+        _ => (shifted_month - 10, year_of_era + era * 400 + 1),
+    };
+
+    year * 12 + month
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Midnight UTC of the given day.
+    fn date(year: u64, month: u64, day: u64) -> SystemTime {
+        // Days from 1970-01-01 to the first of each month of a non-leap year.
+        const MONTH_STARTS: [u64; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+        let leap_days = (1970..year)
+            .filter(|y| (y % 4 == 0 && y % 100 != 0) || y % 400 == 0)
+            .count() as u64;
+        let leap_day = match (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 {
+            true if month > 2 => 1,
+            _ => 0,
+        };
+        let days = (year - 1970) * 365 + leap_days + MONTH_STARTS[month as usize - 1] + day - 1;
+
+        UNIX_EPOCH + Duration::from_secs((days + leap_day) * 24 * 60 * 60)
+    }
+
+    #[test]
+    fn month_of() {
+        let month = |time| super::month_of(days_since_epoch(time));
+
+        assert_eq!(month(UNIX_EPOCH), 1970 * 12);
+        assert_eq!(month(date(2000, 2, 29)), 2000 * 12 + 1);
+        assert_eq!(month(date(2000, 3, 1)), 2000 * 12 + 2);
+        assert_eq!(month(date(2023, 12, 31)), 2023 * 12 + 11);
+        assert_eq!(month(date(2024, 1, 1)), 2024 * 12);
+        assert_eq!(month(date(2024, 2, 29)), 2024 * 12 + 1);
+    }
+
+    #[test]
+    fn tally_rolls_over() {
+        let start = date(2023, 4, 30);
+        let bandwidth = Bandwidth::new_at(None, start);
+
+        bandwidth.record_at(Source::Gateway, 100, start);
+        bandwidth.record_at(Source::Ethereum, 10, start);
+        assert_eq!(bandwidth.received_this_month_at(start), 110);
+
+        // A new day in a new month.
+        let next = date(2023, 5, 1);
+        assert_eq!(bandwidth.received_this_month_at(next), 0);
+
+        bandwidth.record_at(Source::Gateway, 100, next);
+        let usage = bandwidth.0.lock().unwrap();
+        assert_eq!(usage.received, [(100, 100), (0, 0)]);
+        drop(usage);
+
+        // A new day in the same month.
+        let later = date(2023, 5, 2);
+        bandwidth.record_at(Source::Gateway, 50, later);
+        let usage = bandwidth.0.lock().unwrap();
+        assert_eq!(usage.received, [(50, 150), (0, 0)]);
+        drop(usage);
+        assert_eq!(bandwidth.received_since_startup(Source::Gateway), 250);
+        assert_eq!(bandwidth.received_since_startup(Source::Ethereum), 10);
+    }
+
+    #[test]
+    fn quota() {
+        let start = date(2023, 4, 1);
+        let bandwidth = Bandwidth::new_at(Some(1000), start);
+        assert!(!bandwidth.quota_near_at(start));
+
+        bandwidth.record_at(Source::Gateway, 899, start);
+        assert!(!bandwidth.quota_near_at(start));
+
+        bandwidth.record_at(Source::Ethereum, 1, start);
+        assert!(bandwidth.quota_near_at(start));
+        assert!(!bandwidth.quota_near_at(date(2023, 5, 1)));
+
+        let unlimited = Bandwidth::new_at(None, start);
+        unlimited.record_at(Source::Gateway, u32::MAX as u64, start);
+        assert!(!unlimited.quota_near_at(start));
+    }
+
+    #[test]
+    fn quota_exceeded() {
+        let start = date(2023, 4, 1);
+        let bandwidth = Bandwidth::new_at(Some(1000), start);
+
+        bandwidth.record_at(Source::Gateway, 999, start);
+        assert!(!bandwidth.quota_used_at(100, start));
+
+        bandwidth.record_at(Source::Gateway, 1, start);
+        assert!(bandwidth.quota_used_at(100, start));
+        assert!(!bandwidth.quota_used_at(100, date(2023, 5, 1)));
+    }
+
+    #[test]
+    fn month_display() {
+        let month = Month(super::month_of(days_since_epoch(date(2023, 4, 30))));
+        assert_eq!(month.to_string(), "2023-04");
+        assert_eq!("2023-04".parse::<Month>().unwrap(), month);
+        assert_eq!("2023-12".parse::<Month>().unwrap(), Month(2023 * 12 + 11));
+        assert!("2023-13".parse::<Month>().is_err());
+        assert!("2023".parse::<Month>().is_err());
+    }
+
+    #[test]
+    fn persistence() {
+        let start = date(2023, 4, 30);
+        let april = Month(2023 * 12 + 3);
+        let bandwidth = Bandwidth::new_at(Some(1000), start);
+
+        bandwidth.record_at(Source::Gateway, 100, start);
+        bandwidth.record_at(Source::Gateway, 50, start);
+        bandwidth.record_at(Source::Ethereum, 10, start);
+        let unpersisted = bandwidth.take_unpersisted();
+        assert_eq!(
+            unpersisted,
+            vec![
+                MonthlyBytes {
+                    source: Source::Gateway,
+                    month: april,
+                    bytes: 150
+                },
+                MonthlyBytes {
+                    source: Source::Ethereum,
+                    month: april,
+                    bytes: 10
+                },
+            ]
+        );
+        assert_eq!(bandwidth.take_unpersisted(), vec![]);
+
+        // Returned bytes are merged with those received since.
+        bandwidth.record_at(Source::Gateway, 1, start);
+        bandwidth.return_unpersisted(unpersisted);
+        assert_eq!(bandwidth.take_unpersisted()[0].bytes, 151);
+
+        // A restart restores this month's tally, but not past months'.
+        let restarted = Bandwidth::new_at(Some(1000), start);
+        restarted.restore_at(Source::Gateway, april, 900, start);
+        restarted.restore_at(Source::Gateway, Month(2023 * 12 + 2), 900, start);
+        assert_eq!(restarted.received_this_month_at(start), 900);
+        assert!(restarted.quota_near_at(start));
+        // Restored bytes are already persisted.
+        assert_eq!(restarted.take_unpersisted(), vec![]);
+    }
+}
@@ -0,0 +1,104 @@
+//! The constants which tell one StarkNet-like chain apart from another.
+//!
+//! Pathfinder itself only follows the StarkNet networks, for which [ChainParameters::starknet]
+//! and [ChainParameters::for_chain] give the parameters. Appchains which reuse the StarkNet stack
+//! with their own chain id or hashing domains describe themselves with their own
+//! [ChainParameters] instead.
+//!
+//! Only hashing is parameterized. Execution always pays fees in StarkNet's fee token.
+use stark_hash::Felt;
+
+use crate::{felt_bytes, Chain, ChainId, ClassHash, ContractAddress, ContractAddressSalt};
+
+/// The chain-specific constants used when hashing on a chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainParameters {
+    /// The chain id which transactions and typed data messages are signed for.
+    pub chain_id: ChainId,
+    /// Hashed ahead of the deployment parameters of a [contract address](Self::contract_address).
+    pub contract_address_prefix: Felt,
+    /// Hashed ahead of the domain of a typed data message.
+    pub typed_data_prefix: Felt,
+}
+
+impl ChainParameters {
+    pub const STARKNET_CONTRACT_ADDRESS_PREFIX: Felt = felt_bytes!(b"STARKNET_CONTRACT_ADDRESS");
+    pub const STARKNET_TYPED_DATA_PREFIX: Felt = felt_bytes!(b"StarkNet Message");
+
+    /// The parameters of a StarkNet network with `chain_id`.
+    pub const fn starknet(chain_id: ChainId) -> Self {
+        Self {
+            chain_id,
+            contract_address_prefix: Self::STARKNET_CONTRACT_ADDRESS_PREFIX,
+            typed_data_prefix: Self::STARKNET_TYPED_DATA_PREFIX,
+        }
+    }
+
+    /// The parameters of a known StarkNet network, [None] for [Chain::Custom] whose chain id is
+    /// not known.
+    pub fn for_chain(chain: Chain) -> Option<Self> {
+        let chain_id = match chain {
+            Chain::Mainnet => ChainId::MAINNET,
+            Chain::Testnet => ChainId::TESTNET,
+            Chain::Integration => ChainId::INTEGRATION,
+            Chain::Testnet2 => ChainId::TESTNET2,
+            Chain::Custom => return None,
+        };
+
+        Some(Self::starknet(chain_id))
+    }
+
+    /// Calculates the address of a contract deployed on this chain, see
+    /// [calculate_contract_address](crate::calculate_contract_address).
+    pub fn contract_address(
+        &self,
+        class_hash: ClassHash,
+        contract_address_salt: ContractAddressSalt,
+        constructor_calldata: impl IntoIterator<Item = Felt>,
+        deployer_address: ContractAddress,
+    ) -> ContractAddress {
+        crate::contract_address_with_prefix(
+            self.contract_address_prefix,
+            class_hash,
+            contract_address_salt,
+            constructor_calldata,
+            deployer_address,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::felt;
+
+    #[test]
+    fn appchain_contract_address() {
+        let starknet = ChainParameters::starknet(ChainId::TESTNET);
+        let appchain = ChainParameters {
+            chain_id: ChainId(felt_bytes!(b"APPCHAIN")),
+            contract_address_prefix: felt_bytes!(b"APPCHAIN_CONTRACT_ADDRESS"),
+            ..starknet
+        };
+
+        let address = |parameters: &ChainParameters| {
+            parameters.contract_address(
+                ClassHash(felt!("0x1")),
+                ContractAddressSalt(felt!("0x2")),
+                [felt!("0x3")],
+                ContractAddress::ZERO,
+            )
+        };
+
+        assert_eq!(
+            address(&starknet),
+            crate::calculate_contract_address(
+                ClassHash(felt!("0x1")),
+                ContractAddressSalt(felt!("0x2")),
+                [felt!("0x3")],
+                ContractAddress::ZERO,
+            )
+        );
+        assert_ne!(address(&appchain), address(&starknet));
+    }
+}
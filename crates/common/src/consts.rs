@@ -5,6 +5,9 @@ use crate::{felt, StarknetBlockHash};
 /// Vergen string
 pub const VERGEN_GIT_SEMVER_LIGHTWEIGHT: &str = env!("VERGEN_GIT_SEMVER_LIGHTWEIGHT");
 
+/// Git commit hash of the build, [None] if the version was forced with `PATHFINDER_FORCE_VERSION`
+pub const GIT_COMMIT: Option<&str> = option_env!("VERGEN_GIT_SHA");
+
 /// User agent used in http clients
 pub const USER_AGENT: &str = concat!(
     "starknet-pathfinder/",
@@ -6,10 +6,15 @@ use ethers::types::{H160, H256};
 use serde::{Deserialize, Serialize};
 use stark_hash::Felt;
 
+pub use chain_parameters::ChainParameters;
+
+pub mod bandwidth;
+mod chain_parameters;
 pub mod consts;
 mod macros;
-#[cfg(feature = "test-utils")]
+#[cfg(any(test, feature = "test-utils"))]
 pub mod test_utils;
+pub mod typed_data;
 
 /// The address of a StarkNet contract.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, PartialOrd, Ord)]
@@ -18,6 +23,10 @@ pub struct ContractAddress(Felt);
 macros::starkhash251::newtype!(ContractAddress);
 macros::starkhash251::deserialization!(ContractAddress);
 
+impl ContractAddress {
+    pub const ZERO: Self = Self(Felt::ZERO);
+}
+
 /// A nonce that is associated with a particular deployed StarkNet contract
 /// distinguishing it from other contracts that use the same contract class.
 #[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -63,7 +72,7 @@ macros::starkhash251::deserialization!(ClassCommitment);
 
 /// A Cairo 1.0 class' leaf hash. This is the value stored
 /// in the class commitment tree.
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClassCommitmentLeafHash(pub Felt);
 
 macros::starkhash::to_from_sql!(ClassCommitmentLeafHash);
@@ -184,7 +193,7 @@ impl StorageCommitment {
 }
 
 /// A StarkNet block hash.
-#[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct StarknetBlockHash(pub Felt);
 
 /// A StarkNet block number.
@@ -249,6 +258,11 @@ pub struct L1ToL2MessageNonce(pub Felt);
 #[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct L1ToL2MessagePayloadElem(pub Felt);
 
+/// The hash of an L1 to L2 message, under which the StarkNet core contract on L1 tracks the
+/// message until it is consumed by an L1 handler transaction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct L1ToL2MessageHash(pub H256);
+
 /// A single element of the payload of an L2 to L1 message in a StarkNet transaction.
 #[derive(Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct L2ToL1MessagePayloadElem(pub Felt);
@@ -349,8 +363,7 @@ pub struct EthereumTransactionIndex(pub u64);
 pub struct EthereumLogIndex(pub u64);
 
 /// A way of identifying a specific block.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
-#[cfg_attr(any(test, feature = "full-serde"), derive(Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub enum BlockId {
     #[serde(rename = "block_number")]
@@ -526,6 +539,9 @@ macros::fmt::thin_debug!(CasmHash);
 macros::fmt::thin_display!(CasmHash);
 macros::starkhash::to_from_sql!(CasmHash);
 
+macros::fmt::thin_debug!(ClassCommitmentLeafHash);
+macros::fmt::thin_display!(ClassCommitmentLeafHash);
+
 macros::fmt::thin_debug!(ClassCommitment);
 macros::fmt::thin_display!(ClassCommitment);
 macros::starkhash::to_from_sql!(ClassCommitment);
@@ -585,8 +601,123 @@ pub fn calculate_class_commitment_leaf_hash(
     )
 }
 
+/// Calculates the address of a contract deployed by `deployer_address`, which is zero for
+/// `DEPLOY` and `DEPLOY_ACCOUNT` transactions.
+///
+/// Since the address only depends on the deployment parameters, it is known before the contract
+/// is deployed.
+///
+/// Uses StarkNet's prefix, other chains use [ChainParameters::contract_address] instead.
+///
+/// See: <https://github.com/starkware-libs/cairo-lang/blob/v0.11.0/src/starkware/starknet/core/os/contract_address/contract_address.py>
+pub fn calculate_contract_address(
+    class_hash: ClassHash,
+    contract_address_salt: ContractAddressSalt,
+    constructor_calldata: impl IntoIterator<Item = Felt>,
+    deployer_address: ContractAddress,
+) -> ContractAddress {
+    contract_address_with_prefix(
+        ChainParameters::STARKNET_CONTRACT_ADDRESS_PREFIX,
+        class_hash,
+        contract_address_salt,
+        constructor_calldata,
+        deployer_address,
+    )
+}
+
+fn contract_address_with_prefix(
+    prefix: Felt,
+    class_hash: ClassHash,
+    contract_address_salt: ContractAddressSalt,
+    constructor_calldata: impl IntoIterator<Item = Felt>,
+    deployer_address: ContractAddress,
+) -> ContractAddress {
+    use ethers::types::U256;
+
+    let mut calldata_hash = stark_hash::HashChain::default();
+    for param in constructor_calldata {
+        calldata_hash.update(param);
+    }
+
+    let mut hash = stark_hash::HashChain::default();
+    hash.update(prefix);
+    hash.update(deployer_address.0);
+    hash.update(contract_address_salt.0);
+    hash.update(class_hash.0);
+    hash.update(calldata_hash.finalize());
+    let hash = hash.finalize();
+
+    // Addresses are reduced modulo 2^251 - 256. The hash is less than twice that, so at most a
+    // single subtraction is needed.
+    let upper_bound = (U256::one() << 251) - 256;
+    let mut address = U256::from_big_endian(hash.as_be_bytes());
+    if address >= upper_bound {
+        address -= upper_bound;
+    }
+
+    let mut bytes = [0u8; 32];
+    address.to_big_endian(&mut bytes);
+    ContractAddress(Felt::from_be_bytes(bytes).expect("cannot overflow: smaller than hash"))
+}
+
 #[cfg(test)]
 mod tests {
+    mod calculate_contract_address {
+        use super::super::{
+            calculate_contract_address, ClassHash, ContractAddress, ContractAddressSalt,
+        };
+        use crate::felt;
+
+        #[test]
+        fn deploy() {
+            // The first deploy transaction of the testnet genesis block.
+            let address = calculate_contract_address(
+                ClassHash(felt!(
+                    "0x10455c752b86932ce552f2b0fe81a880746649b9aee7e0d842bf3f52378f9f8"
+                )),
+                ContractAddressSalt(felt!(
+                    "0x7284a0367fdd636434f76da25532785690d5f27db40ba38b0cfcbc89a472507"
+                )),
+                [
+                    felt!("0x635b73abaa9efff71570cb08f3e5014424788470c3b972b952368fb3fc27cc3"),
+                    felt!("0x7e92479a573a24241ee6f3e4ade742ff37bae4a60bacef5be1caaff5e7e04f3"),
+                ],
+                ContractAddress::ZERO,
+            );
+
+            assert_eq!(
+                address,
+                ContractAddress::new_or_panic(felt!(
+                    "0x2f40faa63fdd5871415b2dcfb1a5e3e1ca06435b3dda6e2ba9df3f726fd3251"
+                ))
+            );
+        }
+
+        #[test]
+        fn deploy_account() {
+            // Integration block 228457.
+            let address = calculate_contract_address(
+                ClassHash(felt!(
+                    "0x1fac3074c9d5282f0acc5c69a4781a1c711efea5e73c550c5d9fb253cf7fd3d"
+                )),
+                ContractAddressSalt(felt!(
+                    "0x6ccdf35bc37d9c5030215e2fb39b97de8f755c8ed43dd52a4c30e5ab96e61b"
+                )),
+                [felt!(
+                    "0x539f848975104309396ee8ebaee0a45acb0238e0b13da9df0f855c082a3b7f3"
+                )],
+                ContractAddress::ZERO,
+            );
+
+            assert_eq!(
+                address,
+                ContractAddress::new_or_panic(felt!(
+                    "0x39696a0665e4c9806a709c7872030c63ca82b551925b2f517ed1985066217b8"
+                ))
+            );
+        }
+    }
+
     mod block_id_serde {
         use super::super::BlockId;
 
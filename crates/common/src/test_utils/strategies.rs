@@ -0,0 +1,210 @@
+//! [proptest] strategies for the types of this crate, for use in property tests throughout the
+//! workspace.
+//!
+//! All types implement [Arbitrary] and only generate valid values, so that for example
+//! `any::<ContractAddress>()` never exceeds 251 bits.
+use ethers::types::{H160, H256};
+use proptest::prelude::*;
+use stark_hash::Felt;
+
+use crate::*;
+
+/// Generates any [Felt] below 2^251, which is below the field modulus and therefore also valid for
+/// the 251 bit types such as [ContractAddress] and [StorageAddress].
+pub fn felt() -> impl Strategy<Value = Felt> {
+    any::<[u8; 32]>().prop_map(|mut bytes| {
+        bytes[0] &= 0x07;
+        Felt::from_be_bytes(bytes).expect("Value is below the modulus")
+    })
+}
+
+/// Generates transaction versions which are in use, i.e. zero to two, with or without the query
+/// version bit.
+pub fn transaction_version() -> impl Strategy<Value = TransactionVersion> {
+    (0..=2u64, any::<bool>()).prop_map(|(version, query)| {
+        let version = TransactionVersion(H256::from_low_u64_be(version));
+        match query {
+            true => version.with_query_version(),
+            false => version,
+        }
+    })
+}
+
+/// Implements [Arbitrary] for newtypes of [Felt] which accept any value.
+macro_rules! felt_newtype {
+    ($($target:ident),+ $(,)?) => {
+        $(
+            impl Arbitrary for $target {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    felt().prop_map($target).boxed()
+                }
+            }
+        )+
+    };
+}
+
+/// Implements [Arbitrary] for newtypes of [Felt] limited to 251 bits.
+macro_rules! felt251_newtype {
+    ($($target:ident),+ $(,)?) => {
+        $(
+            impl Arbitrary for $target {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    felt().prop_map($target::new_or_panic).boxed()
+                }
+            }
+        )+
+    };
+}
+
+/// Implements [Arbitrary] for newtypes of [u64] which are stored as [i64] in the database.
+macro_rules! i64_backed_u64_newtype {
+    ($($target:ident),+ $(,)?) => {
+        $(
+            impl Arbitrary for $target {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<Self>;
+
+                fn arbitrary_with(_: ()) -> Self::Strategy {
+                    (0..=i64::MAX as u64).prop_map($target::new_or_panic).boxed()
+                }
+            }
+        )+
+    };
+}
+
+felt_newtype!(
+    ContractNonce,
+    ContractAddressSalt,
+    ClassHash,
+    SierraHash,
+    CasmHash,
+    ClassCommitment,
+    ClassCommitmentLeafHash,
+    ContractStateHash,
+    ContractRoot,
+    EntryPoint,
+    ByteCodeOffset,
+    CallParam,
+    ConstructorParam,
+    CallResultValue,
+    StorageValue,
+    StateCommitment,
+    StorageCommitment,
+    StarknetBlockHash,
+    EventCommitment,
+    TransactionCommitment,
+    StarknetTransactionHash,
+    TransactionSignatureElem,
+    L1ToL2MessageNonce,
+    L1ToL2MessagePayloadElem,
+    L2ToL1MessagePayloadElem,
+    EventData,
+    EventKey,
+    SequencerAddress,
+    Fee,
+    TransactionNonce,
+    ChainId,
+);
+
+felt251_newtype!(ContractAddress, StorageAddress);
+
+i64_backed_u64_newtype!(
+    StarknetBlockNumber,
+    StarknetBlockTimestamp,
+    StarknetTransactionIndex,
+);
+
+impl Arbitrary for GasPrice {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<u128>().prop_map(GasPrice).boxed()
+    }
+}
+
+impl Arbitrary for TransactionVersion {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        transaction_version().boxed()
+    }
+}
+
+impl Arbitrary for EthereumAddress {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<[u8; 20]>()
+            .prop_map(|bytes| EthereumAddress(H160(bytes)))
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generates a serialization round-trip property test for each type.
+    macro_rules! round_trip {
+        ($($name:ident: $target:ty),+ $(,)?) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(value in any::<$target>()) {
+                        let json = serde_json::to_value(value).unwrap();
+                        let decoded = serde_json::from_value::<$target>(json).unwrap();
+                        prop_assert_eq!(decoded, value);
+                    }
+                )+
+            }
+        };
+    }
+
+    round_trip!(
+        contract_address: ContractAddress,
+        contract_nonce: ContractNonce,
+        contract_address_salt: ContractAddressSalt,
+        class_hash: ClassHash,
+        sierra_hash: SierraHash,
+        casm_hash: CasmHash,
+        class_commitment: ClassCommitment,
+        entry_point: EntryPoint,
+        call_param: CallParam,
+        constructor_param: ConstructorParam,
+        storage_address: StorageAddress,
+        storage_value: StorageValue,
+        state_commitment: StateCommitment,
+        block_hash: StarknetBlockHash,
+        block_number: StarknetBlockNumber,
+        block_timestamp: StarknetBlockTimestamp,
+        transaction_hash: StarknetTransactionHash,
+        transaction_index: StarknetTransactionIndex,
+        transaction_signature_elem: TransactionSignatureElem,
+        l1_to_l2_message_nonce: L1ToL2MessageNonce,
+        event_data: EventData,
+        event_key: EventKey,
+        sequencer_address: SequencerAddress,
+        fee: Fee,
+        gas_price: GasPrice,
+        transaction_nonce: TransactionNonce,
+        transaction_version: TransactionVersion,
+        ethereum_address: EthereumAddress,
+        chain_id: ChainId,
+    );
+
+    proptest! {
+        #[test]
+        fn felts_are_valid_addresses(felt in felt()) {
+            prop_assert!(ContractAddress::new(felt).is_some());
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Hashing of typed data messages for off-chain signatures, following revision 0 of
+//! [SNIP-12](https://github.com/starknet-io/SNIPs/blob/main/SNIPS/snip-12.md), StarkNet's
+//! counterpart of Ethereum's EIP-712.
+//!
+//! The message hash binds the message to the account signing it and to the chain of the
+//! `StarkNetDomain`, so that a signature cannot be replayed on another chain.
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use stark_hash::{Felt, HashChain};
+
+use crate::{ChainId, ChainParameters, ContractAddress, EntryPoint};
+
+/// A typed data message, in the JSON format used by wallets and `starknet.js`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    /// The struct types of the message, which must include `StarkNetDomain`.
+    pub types: BTreeMap<String, Vec<TypedDataMember>>,
+    /// The type of the [message](Self::message).
+    pub primary_type: String,
+    pub domain: Map<String, Value>,
+    pub message: Map<String, Value>,
+}
+
+/// A member of a [TypedData] struct type.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TypedDataMember {
+    pub name: String,
+    /// One of `felt`, `string`, `selector`, a struct type, or an array of these marked by a
+    /// trailing `*`.
+    #[serde(rename = "type")]
+    pub r#type: String,
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum TypedDataError {
+    #[error("Type {0} is not defined")]
+    UnknownType(String),
+    #[error("Value of {0} is missing")]
+    MissingValue(String),
+    #[error("Value of {0} is invalid")]
+    InvalidValue(String),
+    #[error(
+        "Domain is for chain {} instead of {}",
+        .domain.to_hex_str(),
+        .expected.to_hex_str()
+    )]
+    ChainIdMismatch { expected: ChainId, domain: ChainId },
+}
+
+impl TypedData {
+    const DOMAIN_TYPE: &'static str = "StarkNetDomain";
+
+    /// The chain id of the [domain](Self::domain).
+    pub fn chain_id(&self) -> Result<ChainId, TypedDataError> {
+        let chain_id = self
+            .domain
+            .get("chainId")
+            .ok_or_else(|| TypedDataError::MissingValue("chainId".to_owned()))?;
+
+        encode_felt(chain_id)
+            .map(ChainId)
+            .ok_or_else(|| TypedDataError::InvalidValue("chainId".to_owned()))
+    }
+
+    /// Calculates the hash which `account` signs for this message on the StarkNet network with
+    /// `chain_id`.
+    ///
+    /// Fails if the message's domain is not for `chain_id`.
+    pub fn message_hash(
+        &self,
+        account: ContractAddress,
+        chain_id: ChainId,
+    ) -> Result<Felt, TypedDataError> {
+        self.message_hash_on(account, &ChainParameters::starknet(chain_id))
+    }
+
+    /// Like [message_hash](Self::message_hash), for the chain described by `parameters`.
+    pub fn message_hash_on(
+        &self,
+        account: ContractAddress,
+        parameters: &ChainParameters,
+    ) -> Result<Felt, TypedDataError> {
+        let domain_chain_id = self.chain_id()?;
+        if domain_chain_id != parameters.chain_id {
+            return Err(TypedDataError::ChainIdMismatch {
+                expected: parameters.chain_id,
+                domain: domain_chain_id,
+            });
+        }
+
+        let mut hash = HashChain::default();
+        hash.update(parameters.typed_data_prefix);
+        hash.update(self.struct_hash(Self::DOMAIN_TYPE, &self.domain)?);
+        hash.update(*account.get());
+        hash.update(self.struct_hash(&self.primary_type, &self.message)?);
+
+        Ok(hash.finalize())
+    }
+
+    /// Calculates the hash of `value` as an instance of the struct type `name`.
+    pub fn struct_hash(
+        &self,
+        name: &str,
+        value: &Map<String, Value>,
+    ) -> Result<Felt, TypedDataError> {
+        let mut hash = HashChain::default();
+        hash.update(self.type_hash(name)?);
+
+        for member in self.members(name)? {
+            let field = value
+                .get(&member.name)
+                .ok_or_else(|| TypedDataError::MissingValue(member.name.clone()))?;
+            hash.update(self.encode_value(&member.r#type, field, &member.name)?);
+        }
+
+        Ok(hash.finalize())
+    }
+
+    /// The StarkNet keccak of the [encoded type](Self::encode_type).
+    pub fn type_hash(&self, name: &str) -> Result<Felt, TypedDataError> {
+        let encoded = self.encode_type(name)?;
+        Ok(EntryPoint::hashed(encoded.as_bytes()).0)
+    }
+
+    /// Encodes the struct type `name` and all the struct types it references, such as
+    /// `Mail(from:Person,contents:felt)Person(name:felt,wallet:felt)`.
+    ///
+    /// The referenced types follow `name` in alphabetical order.
+    pub fn encode_type(&self, name: &str) -> Result<String, TypedDataError> {
+        let mut dependencies = BTreeSet::new();
+        self.collect_dependencies(name, &mut dependencies)?;
+        dependencies.remove(name);
+
+        std::iter::once(name)
+            .chain(dependencies)
+            .map(|name| {
+                let members = self
+                    .members(name)?
+                    .iter()
+                    .map(|member| format!("{}:{}", member.name, member.r#type))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Ok(format!("{name}({members})"))
+            })
+            .collect()
+    }
+
+    fn members(&self, name: &str) -> Result<&[TypedDataMember], TypedDataError> {
+        self.types
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| TypedDataError::UnknownType(name.to_owned()))
+    }
+
+    fn collect_dependencies<'a>(
+        &'a self,
+        name: &'a str,
+        dependencies: &mut BTreeSet<&'a str>,
+    ) -> Result<(), TypedDataError> {
+        let members = self.members(name)?;
+        if !dependencies.insert(name) {
+            return Ok(());
+        }
+
+        for member in members {
+            let r#type = member.r#type.trim_end_matches('*');
+            if self.types.contains_key(r#type) {
+                self.collect_dependencies(r#type, dependencies)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn encode_value(
+        &self,
+        r#type: &str,
+        value: &Value,
+        field: &str,
+    ) -> Result<Felt, TypedDataError> {
+        let invalid = || TypedDataError::InvalidValue(field.to_owned());
+
+        if self.types.contains_key(r#type) {
+            let value = value.as_object().ok_or_else(invalid)?;
+            return self.struct_hash(r#type, value);
+        }
+
+        if let Some(element_type) = r#type.strip_suffix('*') {
+            let elements = value.as_array().ok_or_else(invalid)?;
+            let mut hash = HashChain::default();
+            for element in elements {
+                hash.update(self.encode_value(element_type, element, field)?);
+            }
+            return Ok(hash.finalize());
+        }
+
+        match r#type {
+            "felt" | "string" => encode_felt(value).ok_or_else(invalid),
+            "selector" => value
+                .as_str()
+                .map(|name| EntryPoint::hashed(name.as_bytes()).0)
+                .ok_or_else(invalid),
+            other => Err(TypedDataError::UnknownType(other.to_owned())),
+        }
+    }
+}
+
+/// Encodes a number, a hex or decimal string, or otherwise a short string of at most 31 ASCII
+/// characters.
+fn encode_felt(value: &Value) -> Option<Felt> {
+    let decimal = |s: &str| {
+        let value = ethers::types::U256::from_dec_str(s).ok()?;
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        Felt::from_be_bytes(bytes).ok()
+    };
+
+    match value {
+        Value::Number(number) => decimal(&number.to_string()),
+        Value::String(s) if s.starts_with("0x") => Felt::from_hex_str(s).ok(),
+        Value::String(s) if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => decimal(s),
+        Value::String(s) if s.is_ascii() && s.len() <= 31 => Felt::from_be_slice(s.as_bytes()).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::felt;
+
+    /// The example of `starknet.js`.
+    fn mail() -> TypedData {
+        serde_json::from_value(serde_json::json!({
+            "types": {
+                "StarkNetDomain": [
+                    { "name": "name", "type": "felt" },
+                    { "name": "version", "type": "felt" },
+                    { "name": "chainId", "type": "felt" }
+                ],
+                "Person": [
+                    { "name": "name", "type": "felt" },
+                    { "name": "wallet", "type": "felt" }
+                ],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "felt" }
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "StarkNet Mail",
+                "version": "1",
+                "chainId": 1
+            },
+            "message": {
+                "from": {
+                    "name": "Cow",
+                    "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"
+                },
+                "to": {
+                    "name": "Bob",
+                    "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"
+                },
+                "contents": "Hello, Bob!"
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn encode_type() {
+        assert_eq!(
+            mail().encode_type("Mail").unwrap(),
+            "Mail(from:Person,to:Person,contents:felt)Person(name:felt,wallet:felt)"
+        );
+    }
+
+    #[test]
+    fn type_hash() {
+        let typed_data = mail();
+
+        assert_eq!(
+            typed_data.type_hash("StarkNetDomain").unwrap(),
+            felt!("0x1bfc207425a47a5dfa1a50a4f5241203f50624ca5fdf5e18755765416b8e288")
+        );
+        assert_eq!(
+            typed_data.type_hash("Mail").unwrap(),
+            felt!("0x13d89452df9512bf750f539ba3001b945576243288137ddb6c788457d4b2f79")
+        );
+    }
+
+    #[test]
+    fn message_hash() {
+        let account =
+            ContractAddress::new_or_panic(felt!("0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826"));
+        let chain_id = ChainId(felt!("0x1"));
+
+        assert_eq!(
+            mail().message_hash(account, chain_id).unwrap(),
+            felt!("0x6fcff244f63e38b9d88b9e3378d44757710d1b244282b435cb472053c8d78d0")
+        );
+    }
+
+    #[test]
+    fn chain_id_mismatch() {
+        let account = ContractAddress::new_or_panic(felt!("0x1"));
+
+        assert_eq!(
+            mail().message_hash(account, ChainId::MAINNET).unwrap_err(),
+            TypedDataError::ChainIdMismatch {
+                expected: ChainId::MAINNET,
+                domain: ChainId(felt!("0x1")),
+            }
+        );
+    }
+
+    #[test]
+    fn felt_encoding() {
+        use serde_json::json;
+
+        assert_eq!(encode_felt(&json!(10)), Some(felt!("0xa")));
+        assert_eq!(encode_felt(&json!("10")), Some(felt!("0xa")));
+        assert_eq!(encode_felt(&json!("0x10")), Some(felt!("0x10")));
+        assert_eq!(encode_felt(&json!("SN_GOERLI")), Some(ChainId::TESTNET.0));
+        // Too long for a short string.
+        assert_eq!(encode_felt(&json!("a".repeat(32))), None);
+        assert_eq!(encode_felt(&json!(true)), None);
+    }
+
+    #[test]
+    fn unknown_types() {
+        let mut typed_data = mail();
+        typed_data.primary_type = "Letter".to_owned();
+        let account = ContractAddress::new_or_panic(felt!("0x1"));
+
+        assert_eq!(
+            typed_data
+                .message_hash(account, ChainId(felt!("0x1")))
+                .unwrap_err(),
+            TypedDataError::UnknownType("Letter".to_owned())
+        );
+    }
+}
@@ -0,0 +1,706 @@
+//! Retrieval of the state diffs which StarkNet posts to L1 as [EIP-4844] blobs, which allows
+//! validating the state updates of the gateway against L1.
+//!
+//! The blobs of a state update transaction are fetched from the `blob_sidecars` endpoint of the
+//! beacon API. Consensus clients prune blobs after about 18 days, so older blobs have to come from
+//! a blob archive serving the same endpoint.
+//!
+//! Blobs hold the evaluations of a polynomial whose coefficients are the StarkNet data, which is
+//! the [StateDiff] of all L2 blocks covered by the state update.
+//!
+//! [EIP-4844]: https://eips.ethereum.org/EIPS/eip-4844
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use ethers::types::{BlockId, H256, U256};
+use pathfinder_common::{
+    CasmHash, ClassHash, ContractAddress, ContractNonce, EthereumChain, SierraHash, StorageAddress,
+    StorageValue,
+};
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use stark_hash::Felt;
+
+use crate::log::StateUpdateLog;
+use crate::provider::EthereumTransport;
+
+pub const FIELD_ELEMENTS_PER_BLOB: usize = 4096;
+pub const BYTES_PER_BLOB: usize = 32 * FIELD_ELEMENTS_PER_BLOB;
+
+const VERSIONED_HASH_VERSION_KZG: u8 = 0x01;
+const SECONDS_PER_SLOT: u64 = 12;
+
+/// The blobs posted by a StarkNet state update transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobTransaction {
+    /// The beacon chain slot of the transaction's block.
+    pub slot: u64,
+    /// The versioned hashes of the transaction's blobs, in order.
+    pub versioned_hashes: Vec<H256>,
+}
+
+impl BlobTransaction {
+    /// Looks up the blobs of the transaction which emitted `log`.
+    ///
+    /// Returns [None] if the state diff of the update was posted as calldata instead.
+    pub async fn fetch(
+        transport: &impl EthereumTransport,
+        log: &StateUpdateLog,
+    ) -> anyhow::Result<Option<Self>> {
+        let transaction = transport
+            .transaction(log.origin.transaction.hash.0)
+            .await?
+            .context("State update transaction not found")?;
+
+        let versioned_hashes: Vec<H256> =
+            match transaction.other.get_deserialized("blobVersionedHashes") {
+                Some(hashes) => hashes.context("Parsing blob versioned hashes")?,
+                None => return Ok(None),
+            };
+        if versioned_hashes.is_empty() {
+            return Ok(None);
+        }
+
+        let block = transport
+            .block(BlockId::Hash(log.origin.block.hash.0))
+            .await?
+            .context("State update block not found")?;
+        let genesis_time = beacon_genesis_time(transport.chain().await?)?;
+        let slot = block
+            .timestamp
+            .as_u64()
+            .checked_sub(genesis_time)
+            .context("Block predates the beacon chain")?
+            / SECONDS_PER_SLOT;
+
+        Ok(Some(Self {
+            slot,
+            versioned_hashes,
+        }))
+    }
+}
+
+/// Unix timestamp of the first beacon chain slot.
+fn beacon_genesis_time(chain: EthereumChain) -> anyhow::Result<u64> {
+    match chain {
+        EthereumChain::Mainnet => Ok(1_606_824_023),
+        EthereumChain::Goerli => Ok(1_616_508_000),
+        EthereumChain::Other(id) => anyhow::bail!("Beacon chain genesis of chain {id} is unknown"),
+    }
+}
+
+/// Fetches blobs from the beacon API of a consensus client or from a blob archive.
+#[derive(Clone, Debug)]
+pub struct BlobClient {
+    client: reqwest::Client,
+    url: Url,
+}
+
+#[derive(serde::Deserialize)]
+struct Sidecars {
+    data: Vec<Sidecar>,
+}
+
+#[derive(serde::Deserialize)]
+struct Sidecar {
+    blob: ethers::types::Bytes,
+    kzg_commitment: ethers::types::Bytes,
+}
+
+impl BlobClient {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    /// Fetches the blobs of `transaction`, in the order of its versioned hashes.
+    ///
+    /// Only the versioned hash of each blob's KZG commitment is checked, not the KZG proof that the
+    /// blob matches the commitment. A forged blob is instead caught by [StateDiff::verify].
+    pub async fn blobs(&self, transaction: &BlobTransaction) -> anyhow::Result<Vec<Blob>> {
+        let url = format!(
+            "{}/eth/v1/beacon/blob_sidecars/{}",
+            self.url.as_str().trim_end_matches('/'),
+            transaction.slot
+        );
+        let sidecars: Sidecars = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("Requesting blob sidecars")?
+            .error_for_status()
+            .context("Requesting blob sidecars")?
+            .json()
+            .await
+            .context("Parsing blob sidecars")?;
+
+        transaction
+            .versioned_hashes
+            .iter()
+            .map(|hash| {
+                let sidecar = sidecars
+                    .data
+                    .iter()
+                    .find(|sidecar| versioned_hash(&sidecar.kzg_commitment) == *hash)
+                    .with_context(|| format!("Blob {hash:?} is missing"))?;
+                Blob::new(sidecar.blob.to_vec())
+            })
+            .collect()
+    }
+}
+
+/// The versioned hash of a KZG commitment, by which transactions refer to their blobs.
+pub fn versioned_hash(kzg_commitment: &[u8]) -> H256 {
+    let mut hash: [u8; 32] = Sha256::digest(kzg_commitment).into();
+    hash[0] = VERSIONED_HASH_VERSION_KZG;
+    H256(hash)
+}
+
+/// The scalar field of BLS12-381, which blobs consist of.
+mod bls_field {
+    use ethers::types::{U256, U512};
+
+    /// The modulus of the field.
+    pub const MODULUS: U256 = U256([
+        0xffffffff00000001,
+        0x53bda402fffe5bfe,
+        0x3339d80809a1d805,
+        0x73eda753299d7d48,
+    ]);
+
+    /// Generator of the field's multiplicative group, used by EIP-4844 for the roots of unity.
+    const PRIMITIVE_ROOT: u64 = 7;
+
+    pub fn add(a: U256, b: U256) -> U256 {
+        // Both are less than the modulus, which is less than 2^255, so this cannot overflow.
+        let sum = a + b;
+        if sum >= MODULUS {
+            sum - MODULUS
+        } else {
+            sum
+        }
+    }
+
+    pub fn sub(a: U256, b: U256) -> U256 {
+        if a >= b {
+            a - b
+        } else {
+            MODULUS - (b - a)
+        }
+    }
+
+    pub fn mul(a: U256, b: U256) -> U256 {
+        let product = a.full_mul(b) % U512::from(MODULUS);
+        U256::try_from(product).expect("Less than the modulus")
+    }
+
+    pub fn pow(mut base: U256, mut exponent: U256) -> U256 {
+        let mut result = U256::one();
+        while !exponent.is_zero() {
+            if exponent.bit(0) {
+                result = mul(result, base);
+            }
+            base = mul(base, base);
+            exponent >>= 1;
+        }
+        result
+    }
+
+    pub fn inverse(a: U256) -> U256 {
+        pow(a, MODULUS - 2)
+    }
+
+    /// A primitive `n`-th root of unity, for `n` a power of two.
+    pub fn root_of_unity(n: usize) -> U256 {
+        pow(U256::from(PRIMITIVE_ROOT), (MODULUS - 1) / n)
+    }
+
+    /// The index of `i` with its `bits` least significant bits reversed.
+    pub fn reverse_bits(i: usize, bits: u32) -> usize {
+        i.reverse_bits() >> (usize::BITS - bits)
+    }
+
+    /// Evaluates the polynomial with the given coefficients at the powers of `root`, a primitive
+    /// `values.len()`-th root of unity, in place.
+    pub fn fft(values: &mut [U256], root: U256) {
+        let n = values.len();
+        let bits = n.trailing_zeros();
+        for i in 0..n {
+            let j = reverse_bits(i, bits);
+            if i < j {
+                values.swap(i, j);
+            }
+        }
+
+        let mut size = 2;
+        while size <= n {
+            let step = pow(root, U256::from(n / size));
+            for start in (0..n).step_by(size) {
+                let mut twiddle = U256::one();
+                for i in start..start + size / 2 {
+                    let even = values[i];
+                    let odd = mul(values[i + size / 2], twiddle);
+                    values[i] = add(even, odd);
+                    values[i + size / 2] = sub(even, odd);
+                    twiddle = mul(twiddle, step);
+                }
+            }
+            size *= 2;
+        }
+    }
+}
+
+/// A blob of [BYTES_PER_BLOB] bytes.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Blob(Vec<u8>);
+
+impl std::fmt::Debug for Blob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Blob({} bytes)", self.0.len())
+    }
+}
+
+impl Blob {
+    pub fn new(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            bytes.len() == BYTES_PER_BLOB,
+            "Blob has {} bytes instead of {BYTES_PER_BLOB}",
+            bytes.len()
+        );
+        Ok(Self(bytes))
+    }
+
+    /// Recovers the StarkNet data of the blob, which are the coefficients of the polynomial the
+    /// blob evaluates.
+    pub fn data(&self) -> anyhow::Result<Vec<Felt>> {
+        let evaluations = self
+            .0
+            .chunks_exact(32)
+            .map(|bytes| {
+                let value = U256::from_big_endian(bytes);
+                anyhow::ensure!(value < bls_field::MODULUS, "Blob element out of range");
+                Ok(value)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // The blob evaluates the polynomial at the roots of unity in bit-reversed order.
+        let bits = FIELD_ELEMENTS_PER_BLOB.trailing_zeros();
+        let mut values = (0..FIELD_ELEMENTS_PER_BLOB)
+            .map(|i| evaluations[bls_field::reverse_bits(i, bits)])
+            .collect::<Vec<_>>();
+
+        let root = bls_field::root_of_unity(FIELD_ELEMENTS_PER_BLOB);
+        bls_field::fft(&mut values, bls_field::inverse(root));
+        let scale = bls_field::inverse(U256::from(FIELD_ELEMENTS_PER_BLOB));
+
+        values
+            .into_iter()
+            .map(|value| {
+                let mut bytes = [0u8; 32];
+                bls_field::mul(value, scale).to_big_endian(&mut bytes);
+                Felt::from_be_bytes(bytes).context("Blob data is not a field element")
+            })
+            .collect()
+    }
+}
+
+/// The changes to a contract in a [StateDiff].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContractDiff {
+    /// Always present in state diffs posted to L1, even if unchanged.
+    pub nonce: Option<ContractNonce>,
+    /// The class of a deployed contract, or the new class of a replaced one.
+    pub class_hash: Option<ClassHash>,
+    pub storage: BTreeMap<StorageAddress, StorageValue>,
+}
+
+/// The state diff of one or more L2 blocks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub contracts: BTreeMap<ContractAddress, ContractDiff>,
+    /// Declared Sierra classes. Cairo 0 classes are not part of state diffs posted to L1.
+    pub declared_classes: BTreeMap<SierraHash, CasmHash>,
+}
+
+/// A difference between the [StateDiff] posted to L1 and the expected one.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum StateDiffMismatch {
+    #[error("Storage of {contract:?} at {key:?} is {l1:?} on L1 instead of {expected:?}")]
+    Storage {
+        contract: ContractAddress,
+        key: StorageAddress,
+        l1: StorageValue,
+        expected: Option<StorageValue>,
+    },
+    #[error("Nonce of {contract:?} is {l1:?} on L1 instead of {expected:?}")]
+    Nonce {
+        contract: ContractAddress,
+        l1: Option<ContractNonce>,
+        expected: ContractNonce,
+    },
+    #[error("Class of {contract:?} is {l1:?} on L1 instead of {expected:?}")]
+    ClassHash {
+        contract: ContractAddress,
+        l1: Option<ClassHash>,
+        expected: Option<ClassHash>,
+    },
+    #[error("Compiled class hash of {class_hash:?} is {l1:?} on L1 instead of {expected:?}")]
+    DeclaredClass {
+        class_hash: SierraHash,
+        l1: Option<CasmHash>,
+        expected: Option<CasmHash>,
+    },
+}
+
+impl StateDiff {
+    /// Decodes the state diff posted in `blobs`.
+    pub fn from_blobs(blobs: &[Blob]) -> anyhow::Result<Self> {
+        let mut data = Vec::with_capacity(blobs.len() * FIELD_ELEMENTS_PER_BLOB);
+        for blob in blobs {
+            data.extend(blob.data()?);
+        }
+        Self::parse(&data)
+    }
+
+    /// Parses the encoding of a state diff in the StarkNet data of blobs. Data following the
+    /// last declared class is padding.
+    pub fn parse(data: &[Felt]) -> anyhow::Result<Self> {
+        let mut data = data.iter().copied();
+        let mut next = |what: &str| {
+            data.next()
+                .with_context(|| format!("State diff ends before {what}"))
+        };
+
+        let mut state_diff = StateDiff::default();
+
+        let contract_count = to_u64(next("contract count")?).context("Contract count")?;
+        for _ in 0..contract_count {
+            let address = ContractAddress::new(next("contract address")?)
+                .context("Contract address out of range")?;
+
+            // class_flag * 2^128 + nonce * 2^64 + storage_update_count
+            let info = next("contract info")?.to_be_bytes();
+            anyhow::ensure!(
+                info[..15].iter().all(|b| *b == 0) && info[15] <= 1,
+                "Invalid info of contract {address:?}"
+            );
+            let nonce = u64::from_be_bytes(info[16..24].try_into().unwrap());
+            let storage_update_count = u64::from_be_bytes(info[24..].try_into().unwrap());
+
+            let mut contract = ContractDiff {
+                nonce: Some(ContractNonce(Felt::from(nonce))),
+                ..Default::default()
+            };
+            if info[15] == 1 {
+                contract.class_hash = Some(ClassHash(next("class hash")?));
+            }
+            for _ in 0..storage_update_count {
+                let key = StorageAddress::new(next("storage key")?)
+                    .context("Storage key out of range")?;
+                let value = StorageValue(next("storage value")?);
+                contract.storage.insert(key, value);
+            }
+
+            state_diff.contracts.insert(address, contract);
+        }
+
+        let class_count = to_u64(next("declared class count")?).context("Class count")?;
+        for _ in 0..class_count {
+            let class_hash = SierraHash(next("class hash")?);
+            let compiled_class_hash = CasmHash(next("compiled class hash")?);
+            state_diff
+                .declared_classes
+                .insert(class_hash, compiled_class_hash);
+        }
+
+        Ok(state_diff)
+    }
+
+    /// Checks this state diff, as posted to L1, against the state diff `expected` from the
+    /// gateway.
+    ///
+    /// Storage writes which leave a value unchanged are not posted to L1, so storage entries of
+    /// `expected` which are missing on L1 are not reported. The same goes for nonces, which are
+    /// posted for all contracts with changes.
+    pub fn verify(&self, expected: &StateDiff) -> Vec<StateDiffMismatch> {
+        let mut mismatches = Vec::new();
+        let empty = ContractDiff::default();
+
+        for (address, contract) in &self.contracts {
+            let expected_contract = expected.contracts.get(address).unwrap_or(&empty);
+            for (key, value) in &contract.storage {
+                let expected_value = expected_contract.storage.get(key).copied();
+                if expected_value != Some(*value) {
+                    mismatches.push(StateDiffMismatch::Storage {
+                        contract: *address,
+                        key: *key,
+                        l1: *value,
+                        expected: expected_value,
+                    });
+                }
+            }
+        }
+
+        for (address, expected_contract) in &expected.contracts {
+            let contract = self.contracts.get(address).unwrap_or(&empty);
+            if let Some(expected_nonce) = expected_contract.nonce {
+                if contract.nonce != Some(expected_nonce) {
+                    mismatches.push(StateDiffMismatch::Nonce {
+                        contract: *address,
+                        l1: contract.nonce,
+                        expected: expected_nonce,
+                    });
+                }
+            }
+        }
+
+        for address in self.contracts.keys().chain(expected.contracts.keys()) {
+            let l1 = self.contracts.get(address).and_then(|c| c.class_hash);
+            let expected_class_hash = expected.contracts.get(address).and_then(|c| c.class_hash);
+            let mismatch = StateDiffMismatch::ClassHash {
+                contract: *address,
+                l1,
+                expected: expected_class_hash,
+            };
+            if l1 != expected_class_hash && !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+
+        for class_hash in self
+            .declared_classes
+            .keys()
+            .chain(expected.declared_classes.keys())
+        {
+            let l1 = self.declared_classes.get(class_hash).copied();
+            let expected_compiled_class_hash = expected.declared_classes.get(class_hash).copied();
+            let mismatch = StateDiffMismatch::DeclaredClass {
+                class_hash: *class_hash,
+                l1,
+                expected: expected_compiled_class_hash,
+            };
+            if l1 != expected_compiled_class_hash && !mismatches.contains(&mismatch) {
+                mismatches.push(mismatch);
+            }
+        }
+
+        mismatches
+    }
+}
+
+fn to_u64(felt: Felt) -> anyhow::Result<u64> {
+    let bytes = felt.to_be_bytes();
+    anyhow::ensure!(bytes[..24].iter().all(|b| *b == 0), "Out of range");
+    Ok(u64::from_be_bytes(bytes[24..].try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    /// Encodes `data` as a blob, the way StarkNet does.
+    fn encode(data: &[Felt]) -> Blob {
+        let mut values = vec![U256::zero(); FIELD_ELEMENTS_PER_BLOB];
+        for (value, felt) in values.iter_mut().zip(data) {
+            *value = U256::from_big_endian(felt.as_be_bytes());
+        }
+        bls_field::fft(
+            &mut values,
+            bls_field::root_of_unity(FIELD_ELEMENTS_PER_BLOB),
+        );
+
+        let bits = FIELD_ELEMENTS_PER_BLOB.trailing_zeros();
+        let mut bytes = vec![0u8; BYTES_PER_BLOB];
+        for (i, chunk) in bytes.chunks_exact_mut(32).enumerate() {
+            values[bls_field::reverse_bits(i, bits)].to_big_endian(chunk);
+        }
+        Blob::new(bytes).unwrap()
+    }
+
+    fn encoded_state_diff() -> Vec<Felt> {
+        vec![
+            // Two contracts.
+            felt!("0x2"),
+            felt!("0x1234"),
+            // Nonce 5, one storage update.
+            felt!("0x50000000000000001"),
+            felt!("0x10"),
+            felt!("0x11"),
+            felt!("0x5678"),
+            // Deployed, nonce 0 and no storage updates.
+            felt!("0x100000000000000000000000000000000"),
+            felt!("0xc1a55"),
+            // One declared class.
+            felt!("0x1"),
+            felt!("0xabc"),
+            felt!("0xdef"),
+        ]
+    }
+
+    fn state_diff() -> StateDiff {
+        StateDiff {
+            contracts: [
+                (
+                    ContractAddress::new_or_panic(felt!("0x1234")),
+                    ContractDiff {
+                        nonce: Some(ContractNonce(felt!("0x5"))),
+                        class_hash: None,
+                        storage: [(
+                            StorageAddress::new_or_panic(felt!("0x10")),
+                            StorageValue(felt!("0x11")),
+                        )]
+                        .into(),
+                    },
+                ),
+                (
+                    ContractAddress::new_or_panic(felt!("0x5678")),
+                    ContractDiff {
+                        nonce: Some(ContractNonce::ZERO),
+                        class_hash: Some(ClassHash(felt!("0xc1a55"))),
+                        storage: BTreeMap::new(),
+                    },
+                ),
+            ]
+            .into(),
+            declared_classes: [(SierraHash(felt!("0xabc")), CasmHash(felt!("0xdef")))].into(),
+        }
+    }
+
+    #[test]
+    fn blob_roundtrip() {
+        let data = (1..=100u64).map(Felt::from).collect::<Vec<_>>();
+
+        let decoded = encode(&data).data().unwrap();
+
+        assert_eq!(&decoded[..100], &data[..]);
+        assert!(decoded[100..].iter().all(|felt| *felt == Felt::ZERO));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            StateDiff::parse(&encoded_state_diff()).unwrap(),
+            state_diff()
+        );
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let data = encoded_state_diff();
+        StateDiff::parse(&data[..data.len() - 1]).unwrap_err();
+    }
+
+    #[test]
+    fn from_blobs() {
+        let blob = encode(&encoded_state_diff());
+        assert_eq!(StateDiff::from_blobs(&[blob]).unwrap(), state_diff());
+    }
+
+    #[test]
+    fn versioned_hash_of_commitment() {
+        assert_eq!(
+            versioned_hash(&[0u8; 48]),
+            "0x01b0761f87b081d5cf10757ccc89f12be355c70e2e29df288b65b30710dcbcd1"
+                .parse::<H256>()
+                .unwrap()
+        );
+    }
+
+    mod verify {
+        use super::*;
+
+        #[test]
+        fn matching() {
+            let l1 = state_diff();
+            let mut expected = state_diff();
+            // Not posted to L1 as the value is unchanged.
+            expected
+                .contracts
+                .get_mut(&ContractAddress::new_or_panic(felt!("0x1234")))
+                .unwrap()
+                .storage
+                .insert(
+                    StorageAddress::new_or_panic(felt!("0x20")),
+                    StorageValue(felt!("0x1")),
+                );
+            // Only posted to L1 as the nonce is unchanged.
+            expected
+                .contracts
+                .get_mut(&ContractAddress::new_or_panic(felt!("0x5678")))
+                .unwrap()
+                .nonce = None;
+
+            assert_eq!(l1.verify(&expected), vec![]);
+        }
+
+        #[test]
+        fn storage() {
+            let l1 = state_diff();
+            let mut expected = state_diff();
+            let contract = ContractAddress::new_or_panic(felt!("0x1234"));
+            let key = StorageAddress::new_or_panic(felt!("0x10"));
+            expected
+                .contracts
+                .get_mut(&contract)
+                .unwrap()
+                .storage
+                .insert(key, StorageValue(felt!("0x12")));
+
+            assert_eq!(
+                l1.verify(&expected),
+                vec![StateDiffMismatch::Storage {
+                    contract,
+                    key,
+                    l1: StorageValue(felt!("0x11")),
+                    expected: Some(StorageValue(felt!("0x12"))),
+                }]
+            );
+        }
+
+        #[test]
+        fn nonce() {
+            let l1 = state_diff();
+            let mut expected = state_diff();
+            let contract = ContractAddress::new_or_panic(felt!("0x1234"));
+            expected.contracts.get_mut(&contract).unwrap().nonce =
+                Some(ContractNonce(felt!("0x6")));
+
+            assert_eq!(
+                l1.verify(&expected),
+                vec![StateDiffMismatch::Nonce {
+                    contract,
+                    l1: Some(ContractNonce(felt!("0x5"))),
+                    expected: ContractNonce(felt!("0x6")),
+                }]
+            );
+        }
+
+        #[test]
+        fn missing_class() {
+            let mut l1 = state_diff();
+            let contract = ContractAddress::new_or_panic(felt!("0x5678"));
+            l1.contracts.get_mut(&contract).unwrap().class_hash = None;
+            l1.declared_classes.clear();
+
+            assert_eq!(
+                l1.verify(&state_diff()),
+                vec![
+                    StateDiffMismatch::ClassHash {
+                        contract,
+                        l1: None,
+                        expected: Some(ClassHash(felt!("0xc1a55"))),
+                    },
+                    StateDiffMismatch::DeclaredClass {
+                        class_hash: SierraHash(felt!("0xabc")),
+                        l1: None,
+                        expected: Some(CasmHash(felt!("0xdef"))),
+                    }
+                ]
+            );
+        }
+    }
+}
@@ -7,6 +7,7 @@ use pathfinder_common::{
     EthereumTransactionIndex,
 };
 
+pub mod blob;
 pub mod contract;
 pub mod log;
 pub mod provider;
@@ -1,11 +1,15 @@
+use anyhow::Context;
 use ethers::providers::Middleware;
 use ethers::types::{Block, BlockId, Filter, Log, Transaction, TxHash, H256, U256};
 use futures::TryFutureExt;
+use pathfinder_common::bandwidth::{Bandwidth, Source};
 use pathfinder_common::EthereumChain;
 use pathfinder_retry::Retry;
 use reqwest::Url;
 use std::future::Future;
 use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::error;
 
@@ -34,6 +38,27 @@ pub trait EthereumTransport {
     async fn gas_price(&self) -> anyhow::Result<U256>;
 }
 
+/// Set while the requests of an [HttpProvider] fail and are being retried, and cleared by the next
+/// request which succeeds.
+///
+/// Cheap to clone, with all clones sharing the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct Reachability(Arc<AtomicBool>);
+
+impl Reachability {
+    pub fn is_unreachable(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set_unreachable(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    fn set_reachable(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
 /// An implementation of [`EthereumTransport`] wrapped with a [exponential backoff retry utility](Retry).
 ///
 /// Initial backoff time is 30 seconds and saturates at 10 minutes:
@@ -42,25 +67,73 @@ pub trait EthereumTransport {
 ///
 /// where `N` is the consecutive retry iteration number `{1, 2, ...}`.
 #[derive(Clone, Debug)]
-pub struct HttpProvider(ethers::providers::Provider<ethers::providers::Http>);
+pub struct HttpProvider {
+    provider: ethers::providers::Provider<ethers::providers::Http>,
+    /// Tallies the bytes received from the endpoint, if enabled.
+    bandwidth: Option<Bandwidth>,
+    reachability: Reachability,
+}
 
 impl HttpProvider {
     /// Creates new [`HttpProvider`].
     pub fn new(http: ethers::providers::Provider<ethers::providers::Http>) -> Self {
-        Self(http)
+        Self {
+            provider: http,
+            bandwidth: None,
+            reachability: Reachability::default(),
+        }
+    }
+
+    /// Whether the endpoint currently fails the requests of this provider, and of its clones.
+    pub fn reachability(&self) -> &Reachability {
+        &self.reachability
+    }
+
+    /// Records the size of every response in the given [Bandwidth] tally.
+    ///
+    /// The underlying client does not expose the raw responses, so their size is estimated from
+    /// the JSON encoding of the decoded result.
+    pub fn with_bandwidth_accounting(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Called for each successful response.
+    fn record<T: serde::Serialize>(&self, response: &T) {
+        self.reachability.set_reachable();
+        if let Some(bandwidth) = &self.bandwidth {
+            let bytes = serde_json::to_vec(response).map_or(0, |json| json.len());
+            bandwidth.record(Source::Ethereum, bytes as u64);
+        }
     }
 
-    /// Creates new [`HttpProvider`] from url and optional password
+    /// Creates new [`HttpProvider`] from url and optional password and proxy
     ///
     /// This includes setting:
     /// - the [Url](reqwest::Url)
     /// - the password (if provided)
-    pub fn from_config(url: Url, password: Option<String>) -> anyhow::Result<Self> {
+    /// - the `http`, `https` or `socks5` proxy URL (if provided), which otherwise is taken from
+    ///   the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` environment variables if they are set
+    pub fn from_config(
+        url: Url,
+        password: Option<String>,
+        proxy: Option<Url>,
+    ) -> anyhow::Result<Self> {
         let mut url = url;
         url.set_password(password.as_deref())
             .map_err(|_| anyhow::anyhow!("Setting password"))?;
 
-        let provider = ethers::providers::Http::new(url);
+        let provider = match proxy {
+            Some(proxy) => {
+                let proxy = reqwest::Proxy::all(proxy).context("Invalid proxy URL")?;
+                let client = reqwest::Client::builder()
+                    .proxy(proxy)
+                    .build()
+                    .context("Creating HTTP client")?;
+                ethers::providers::Http::new_with_client(url, client)
+            }
+            None => ethers::providers::Http::new(url),
+        };
         let provider = ethers::providers::Provider::new(provider);
 
         Ok(Self::new(provider))
@@ -97,27 +170,40 @@ impl HttpProvider {
 
         let url = url.parse::<reqwest::Url>().expect("Bad Ethereum URL");
 
-        Self::from_config(url, password).unwrap()
+        Self::from_config(url, password, None).unwrap()
+    }
+
+    /// Logs a failed request, and marks the endpoint as unreachable until a request succeeds.
+    /// Always yields __true__.
+    fn retrying(&self, error: &ethers::providers::ProviderError) -> bool {
+        self.reachability.set_unreachable();
+        error!(reason=%error, "L1 request failed, retrying");
+
+        true
     }
 }
 
 #[async_trait::async_trait]
 impl EthereumTransport for HttpProvider {
     async fn block(&self, block: BlockId) -> anyhow::Result<Option<Block<H256>>> {
-        Ok(retry(|| self.0.get_block(block), log_and_always_retry).await?)
+        let block = retry(|| self.provider.get_block(block), |e| self.retrying(e)).await?;
+        self.record(&block);
+        Ok(block)
     }
 
     async fn block_number(&self) -> anyhow::Result<u64> {
-        Ok(retry(|| self.0.get_block_number(), log_and_always_retry)
-            .await
-            .map(|n| n.as_u64())?)
+        let number = retry(|| self.provider.get_block_number(), |e| self.retrying(e)).await?;
+        self.record(&number);
+        Ok(number.as_u64())
     }
 
     /// Identifies the [EthereumChain] behind the given Ethereum transport.
     ///
     /// Will error if it's not one of the valid Starknet [EthereumChain] variants.
     async fn chain(&self) -> anyhow::Result<EthereumChain> {
-        match retry(|| self.0.get_chainid(), log_and_always_retry).await? {
+        let id = retry(|| self.provider.get_chainid(), |e| self.retrying(e)).await?;
+        self.record(&id);
+        match id {
             id if id == U256::from(1u32) => Ok(EthereumChain::Mainnet),
             id if id == U256::from(5u32) => Ok(EthereumChain::Goerli),
             other => anyhow::bail!("Unsupported chain ID: {}", other),
@@ -130,12 +216,12 @@ impl EthereumTransport for HttpProvider {
         const INVALID_INPUT: i64 = -32000;
         const RESOURCE_NOT_FOUND: i64 = -32001;
 
-        retry(
+        let logs = retry(
             || {
                 use ethers::providers::ProviderError;
                 use ethers::providers::HttpClientError;
 
-                self.0.get_logs(&filter).map_err(|err| {
+                self.provider.get_logs(&filter).map_err(|err| {
                     let rpc_err = match err {
                         ProviderError::JsonRpcClientError(inner) => inner,
                         other => return LogsError::Other(other),
@@ -170,19 +256,60 @@ impl EthereumTransport for HttpProvider {
                 })
             },
             |e| match e {
-                LogsError::Other(error) => log_and_always_retry(error),
+                LogsError::Other(error) => self.retrying(error),
                 _ => false,
             },
         )
-        .await
+        .await?;
+        self.record(&logs);
+        Ok(logs)
     }
 
     async fn transaction(&self, id: TxHash) -> anyhow::Result<Option<Transaction>> {
-        Ok(retry(|| self.0.get_transaction(id), log_and_always_retry).await?)
+        let transaction = retry(|| self.provider.get_transaction(id), |e| self.retrying(e)).await?;
+        self.record(&transaction);
+        Ok(transaction)
     }
 
     async fn gas_price(&self) -> anyhow::Result<U256> {
-        Ok(retry(|| self.0.get_gas_price(), log_and_always_retry).await?)
+        let gas_price = retry(|| self.provider.get_gas_price(), |e| self.retrying(e)).await?;
+        self.record(&gas_price);
+        Ok(gas_price)
+    }
+}
+
+/// An [EthereumTransport] for running without an Ethereum endpoint, where every request fails.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DisabledTransport;
+
+const ETHEREUM_DISABLED: &str = "Ethereum is disabled";
+
+#[async_trait::async_trait]
+impl EthereumTransport for DisabledTransport {
+    async fn block(&self, _block: BlockId) -> anyhow::Result<Option<Block<H256>>> {
+        anyhow::bail!(ETHEREUM_DISABLED)
+    }
+
+    async fn block_number(&self) -> anyhow::Result<u64> {
+        anyhow::bail!(ETHEREUM_DISABLED)
+    }
+
+    async fn chain(&self) -> anyhow::Result<EthereumChain> {
+        anyhow::bail!(ETHEREUM_DISABLED)
+    }
+
+    async fn logs(&self, _filter: Filter) -> std::result::Result<Vec<Log>, LogsError> {
+        Err(LogsError::Other(
+            ethers::providers::ProviderError::CustomError(ETHEREUM_DISABLED.to_owned()),
+        ))
+    }
+
+    async fn transaction(&self, _id: TxHash) -> anyhow::Result<Option<Transaction>> {
+        anyhow::bail!(ETHEREUM_DISABLED)
+    }
+
+    async fn gas_price(&self) -> anyhow::Result<U256> {
+        anyhow::bail!(ETHEREUM_DISABLED)
     }
 }
 
@@ -203,19 +330,12 @@ where
         .await
 }
 
-/// A helper function to log API errors. Always yields __true__.
-fn log_and_always_retry(error: &ethers::providers::ProviderError) -> bool {
-    error!(reason=%error, "L1 request failed, retrying");
-
-    true
-}
-
 #[cfg(test)]
 impl std::ops::Deref for HttpProvider {
     type Target = ethers::providers::Provider<ethers::providers::Http>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.provider
     }
 }
 
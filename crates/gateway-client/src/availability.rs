@@ -0,0 +1,71 @@
+//! Tracks whether the gateway is down for maintenance.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use reqwest::StatusCode;
+use starknet_gateway_types::error::SequencerError;
+
+pub(crate) const METRIC_UNAVAILABLE: &str = "gateway_unavailable";
+
+/// Set while the gateway answers with maintenance responses, i.e. `502 Bad Gateway`,
+/// `503 Service Unavailable` or `504 Gateway Timeout`.
+///
+/// The flag is mirrored by the `gateway_unavailable` gauge, and each change is logged once
+/// rather than for every failed request.
+///
+/// Cheap to clone, with all clones sharing the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct Availability(Arc<AtomicBool>);
+
+impl Availability {
+    pub fn is_unavailable(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_unavailable(&self, reason: &SequencerError) {
+        if !self.0.swap(true, Ordering::Relaxed) {
+            metrics::gauge!(METRIC_UNAVAILABLE, 1.0);
+            tracing::warn!(%reason, "Gateway is unavailable, pausing until it is back");
+        }
+    }
+
+    pub(crate) fn set_available(&self) {
+        if self.0.swap(false, Ordering::Relaxed) {
+            metrics::gauge!(METRIC_UNAVAILABLE, 0.0);
+            tracing::info!("Gateway is available again");
+        }
+    }
+}
+
+/// Whether `e` is a response of a gateway which is down for maintenance.
+pub(crate) fn is_maintenance(e: &SequencerError) -> bool {
+    match e {
+        SequencerError::ReqwestError(e) => matches!(
+            e.status(),
+            Some(
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            )
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_is_shared() {
+        let availability = Availability::default();
+        let clone = availability.clone();
+        assert!(!clone.is_unavailable());
+
+        availability.set_unavailable(&SequencerError::InvalidStarknetErrorVariant);
+        assert!(clone.is_unavailable());
+
+        availability.set_available();
+        assert!(!clone.is_unavailable());
+    }
+}
@@ -9,24 +9,124 @@
 //!   2. [Method](stage::Method) where you select the REST API method.
 //!   3. [Params](stage::Params) where you select the retry behavior.
 //!   4. [Final](stage::Final) where you select the REST operation type, which is then executed.
-use crate::metrics::{with_metrics, BlockTag, RequestMetadata};
+use crate::availability::{is_maintenance, Availability};
+use crate::failover::Endpoints;
+use crate::metrics::{with_metrics, BlockTag, RequestMetadata, METRIC_RETRIED_REQUESTS};
+use crate::rate_limit::RateLimit;
+use crate::unknown_fields::UnknownFields;
+use pathfinder_common::bandwidth::{Bandwidth, Source};
 use pathfinder_common::{
     BlockId, ClassHash, ContractAddress, StarknetTransactionHash, StorageAddress,
 };
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 use starknet_gateway_types::error::SequencerError;
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
 /// A Sequencer Request builder.
 pub struct Request<'a, S: RequestState> {
     state: S,
     url: reqwest::Url,
     client: &'a reqwest::Client,
+    unknown_fields: Option<&'a UnknownFields>,
+    bandwidth: Option<&'a Bandwidth>,
+    availability: Option<&'a Availability>,
+    rate_limit: Option<&'a RateLimit>,
+    failover: Option<&'a Endpoints>,
+    retry_policy: Option<&'a RetryPolicy>,
+    headers: Option<&'a HeaderMap>,
 }
 
 /// Describes the retry behavior of a [Request] and is specified using
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
 pub enum Retry {
     Enabled,
     Disabled,
+    /// Retries requests which could not connect to the gateway a few times with a short backoff,
+    /// for requests which must not be sent twice, such as submitting transactions.
+    ConnectOnly,
+}
+
+/// How a request with [Retry::Enabled] is retried.
+///
+/// The backoff starts at `base_delay` and doubles with each further retry, saturating at
+/// `max_delay`. The default retries forever, starting at 30 seconds and saturating at 10
+/// minutes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts per request including the first, [None] if unlimited.
+    pub max_attempts: Option<NonZeroUsize>,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Randomizes each backoff to between half and all of its delay, so that many clients do not
+    /// retry in lockstep.
+    pub jitter: bool,
+    /// Statuses of failed responses which are retried, [None] if all of them are. Failures
+    /// without a response, such as timeouts, are always retried.
+    pub retryable_statuses: Option<Vec<StatusCode>>,
+}
+
+/// The [RetryPolicy] of requests which do not specify one.
+static DEFAULT_RETRY_POLICY: RetryPolicy = RetryPolicy::DEFAULT;
+
+/// Backs off for 2 and 4 seconds, so that users are not kept waiting.
+static CONNECT_ONLY_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_attempts: NonZeroUsize::new(3),
+    base_delay: Duration::from_secs(2),
+    ..RetryPolicy::DEFAULT
+};
+
+impl RetryPolicy {
+    const DEFAULT: Self = Self {
+        max_attempts: None,
+        base_delay: Duration::from_secs(30),
+        max_delay: Duration::from_secs(10 * 60),
+        jitter: false,
+        retryable_statuses: None,
+    };
+
+    /// Whether the policy allows retrying a failure with the status of `e`, if it has one.
+    fn allows(&self, e: &SequencerError) -> bool {
+        let status = match e {
+            SequencerError::ReqwestError(e) => e.status(),
+            _ => None,
+        };
+        match (&self.retryable_statuses, status) {
+            (Some(retryable), Some(status)) => retryable.contains(&status),
+            _ => true,
+        }
+    }
+
+    /// Whether the policy allows another attempt after `attempts` attempts.
+    fn allows_attempt(&self, attempts: usize) -> bool {
+        self.max_attempts
+            .map(|max| attempts < max.get())
+            .unwrap_or(true)
+    }
+
+    /// The backoff before the `retry`th retry, starting at 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .unwrap_or(Duration::MAX)
+            .min(self.max_delay);
+
+        if self.jitter {
+            use rand::Rng;
+            rand::thread_rng().gen_range(delay / 2..=delay)
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
 }
 
 pub mod stage {
@@ -81,6 +181,13 @@ impl<'a> Request<'a, stage::Init> {
         Request {
             url,
             client,
+            unknown_fields: None,
+            bandwidth: None,
+            availability: None,
+            rate_limit: None,
+            failover: None,
+            retry_policy: None,
+            headers: None,
             state: stage::Method,
         }
     }
@@ -143,8 +250,57 @@ impl<'a> Request<'a, stage::Method> {
         get_state_update,
         get_contract_addresses,
         get_compiled_class_by_class_hash,
+        get_nonce,
+        get_class_hash_at,
+        get_block_traces,
     );
 
+    /// Reports unknown fields in the response instead of silently ignoring them.
+    pub fn with_unknown_fields(mut self, unknown_fields: Option<&'a UnknownFields>) -> Self {
+        self.unknown_fields = unknown_fields;
+        self
+    }
+
+    /// Records the size of the response in the [Bandwidth] tally.
+    pub fn with_bandwidth(mut self, bandwidth: Option<&'a Bandwidth>) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    /// Records maintenance responses of the gateway in the shared [Availability] flag.
+    pub fn with_availability(mut self, availability: Option<&'a Availability>) -> Self {
+        self.availability = availability;
+        self
+    }
+
+    /// Counts the rate limited attempts of the request in the shared [RateLimit].
+    pub fn with_rate_limit(mut self, rate_limit: Option<&'a RateLimit>) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// Fails the request over between the given endpoints, whose primary must be the request url.
+    pub fn with_failover(mut self, endpoints: Option<&'a Endpoints>) -> Self {
+        self.failover = endpoints;
+        self
+    }
+
+    /// Retries the request according to the given [RetryPolicy] instead of the default one, if
+    /// [retry is enabled](Request::with_retry).
+    pub fn with_retry_policy(mut self, retry_policy: Option<&'a RetryPolicy>) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Attaches the given headers to the request, such as the API key of a hosted gateway.
+    ///
+    /// They are only sent to the origin of the request url, and not to the
+    /// [failover](Request::with_failover) endpoints, which are operated by someone else.
+    pub fn with_headers(mut self, headers: Option<&'a HeaderMap>) -> Self {
+        self.headers = headers;
+        self
+    }
+
     /// Appends the given method to the request url.
     fn with_method(mut self, method: &'static str) -> Request<'a, stage::Params> {
         self.url
@@ -155,6 +311,13 @@ impl<'a> Request<'a, stage::Method> {
         Request {
             url: self.url,
             client: self.client,
+            unknown_fields: self.unknown_fields,
+            bandwidth: self.bandwidth,
+            availability: self.availability,
+            rate_limit: self.rate_limit,
+            failover: self.failover,
+            retry_policy: self.retry_policy,
+            headers: self.headers,
             state: stage::Params {
                 meta: RequestMetadata::new(method),
             },
@@ -221,6 +384,13 @@ impl<'a> Request<'a, stage::Params> {
         Request {
             url: self.url,
             client: self.client,
+            unknown_fields: self.unknown_fields,
+            bandwidth: self.bandwidth,
+            availability: self.availability,
+            rate_limit: self.rate_limit,
+            failover: self.failover,
+            retry_policy: self.retry_policy,
+            headers: self.headers,
             state: stage::Final {
                 meta: self.state.meta,
                 retry,
@@ -236,61 +406,59 @@ impl<'a> Request<'a, stage::Final> {
         T: serde::de::DeserializeOwned,
     {
         async fn send_request<T: serde::de::DeserializeOwned>(
-            url: reqwest::Url,
-            client: &reqwest::Client,
+            request: reqwest::RequestBuilder,
             meta: RequestMetadata,
+            unknown_fields: Option<&UnknownFields>,
+            bandwidth: Option<&Bandwidth>,
         ) -> Result<T, SequencerError> {
             with_metrics(meta, async move {
-                let response = client.get(url).send().await?;
-                parse::<T>(response).await
+                let response = request.send().await?;
+                parse::<T>(response, meta, unknown_fields, bandwidth).await
             })
             .await
         }
 
-        match self.state.retry {
-            Retry::Disabled => send_request(self.url, self.client, self.state.meta).await,
-            Retry::Enabled => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        send_request(clone_url, self.client, self.state.meta).await
-                    },
-                    retry_condition,
+        self.retry(|| async {
+            self.send(|url| {
+                send_request(
+                    self.request(reqwest::Method::GET, url),
+                    self.state.meta,
+                    self.unknown_fields,
+                    self.bandwidth,
                 )
-                .await
-            }
-        }
+            })
+            .await
+        })
+        .await
     }
 
     /// Sends the Sequencer request as a REST `GET` operation and returns the response's bytes.
     pub async fn get_as_bytes(self) -> Result<bytes::Bytes, SequencerError> {
         async fn get_as_bytes_inner(
-            url: reqwest::Url,
-            client: &reqwest::Client,
+            request: reqwest::RequestBuilder,
             meta: RequestMetadata,
+            bandwidth: Option<&Bandwidth>,
         ) -> Result<bytes::Bytes, SequencerError> {
             with_metrics(meta, async {
-                let response = client.get(url).send().await?;
+                let response = request.send().await?;
                 let response = parse_raw(response).await?;
                 let bytes = response.bytes().await?;
+                if let Some(bandwidth) = bandwidth {
+                    bandwidth.record(Source::Gateway, bytes.len() as u64);
+                }
                 Ok(bytes)
             })
             .await
         }
 
-        match self.state.retry {
-            Retry::Disabled => get_as_bytes_inner(self.url, self.client, self.state.meta).await,
-            Retry::Enabled => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        get_as_bytes_inner(clone_url, self.client, self.state.meta).await
-                    },
-                    retry_condition,
-                )
-                .await
-            }
-        }
+        self.retry(|| async {
+            self.send(|url| {
+                let request = self.request(reqwest::Method::GET, url);
+                get_as_bytes_inner(request, self.state.meta, self.bandwidth)
+            })
+            .await
+        })
+        .await
     }
 
     /// Sends the Sequencer request as a REST `POST` operation, in addition to the specified
@@ -301,9 +469,10 @@ impl<'a> Request<'a, stage::Final> {
         J: serde::Serialize + ?Sized,
     {
         async fn post_with_json_inner<T, J>(
-            url: reqwest::Url,
-            client: &reqwest::Client,
+            request: reqwest::RequestBuilder,
             meta: RequestMetadata,
+            unknown_fields: Option<&UnknownFields>,
+            bandwidth: Option<&Bandwidth>,
             json: &J,
         ) -> Result<T, SequencerError>
         where
@@ -311,36 +480,150 @@ impl<'a> Request<'a, stage::Final> {
             J: serde::Serialize + ?Sized,
         {
             with_metrics(meta, async {
-                let response = client.post(url).json(json).send().await?;
-                parse::<T>(response).await
+                let response = request.json(json).send().await?;
+                parse::<T>(response, meta, unknown_fields, bandwidth).await
             })
             .await
         }
 
+        self.retry(|| async {
+            self.send(|url| {
+                post_with_json_inner(
+                    self.request(reqwest::Method::POST, url),
+                    self.state.meta,
+                    self.unknown_fields,
+                    self.bandwidth,
+                    json,
+                )
+            })
+            .await
+        })
+        .await
+    }
+
+    /// Starts a request to `url` with the [headers](Request::with_headers) of this request, if
+    /// `url` has the origin of the request url.
+    fn request(&self, method: reqwest::Method, url: reqwest::Url) -> reqwest::RequestBuilder {
+        let headers = self.headers.filter(|_| url.origin() == self.url.origin());
+        let request = self.client.request(method, url);
+        match headers {
+            Some(headers) => request.headers(headers.clone()),
+            None => request,
+        }
+    }
+
+    /// Sends the request built by `future_factory` with the [retry behavior](Request::with_retry)
+    /// of this request.
+    async fn retry<T, Fut, FutureFactory>(
+        &self,
+        mut future_factory: FutureFactory,
+    ) -> Result<T, SequencerError>
+    where
+        Fut: futures::Future<Output = Result<T, SequencerError>>,
+        FutureFactory: FnMut() -> Fut,
+    {
+        self.check_quota().await?;
+
+        let policy = self.retry_policy.unwrap_or(&DEFAULT_RETRY_POLICY);
+        let rate_limit = self.rate_limit;
+        retry0(
+            self.state.retry,
+            policy,
+            self.availability,
+            self.state.meta.method,
+            || {
+                let attempt = future_factory();
+                async move {
+                    let result = attempt.await;
+                    if let (Some(rate_limit), Err(e)) = (rate_limit, &result) {
+                        rate_limit.record(e);
+                    }
+                    result
+                }
+            },
+        )
+        .await
+    }
+
+    /// Holds back the request while the monthly [Bandwidth] quota is used.
+    ///
+    /// Requests for the pending block are not essential, and fail once the quota is near. Other
+    /// requests with [Retry::Enabled] wait until the next month once the quota is used up, which
+    /// pauses sync, while the rest fail.
+    async fn check_quota(&self) -> Result<(), SequencerError> {
+        let bandwidth = match self.bandwidth {
+            Some(bandwidth) => bandwidth,
+            None => return Ok(()),
+        };
+
+        if matches!(self.state.meta.tag, BlockTag::Pending) && bandwidth.quota_near() {
+            return Err(SequencerError::QuotaExceeded);
+        }
+        if !bandwidth.quota_exceeded() {
+            return Ok(());
+        }
+
         match self.state.retry {
-            Retry::Disabled => {
-                post_with_json_inner(self.url, self.client, self.state.meta, json).await
-            }
             Retry::Enabled => {
-                retry0(
-                    || async {
-                        let clone_url = self.url.clone();
-                        post_with_json_inner(clone_url, self.client, self.state.meta, json).await
-                    },
-                    retry_condition,
-                )
-                .await
+                tracing::warn!(
+                    method=%self.state.meta.method,
+                    "Monthly bandwidth quota is used, waiting for the next month"
+                );
+                while bandwidth.quota_exceeded() {
+                    tokio::time::sleep(QUOTA_POLL_INTERVAL).await;
+                }
+                Ok(())
             }
+            Retry::Disabled | Retry::ConnectOnly => Err(SequencerError::QuotaExceeded),
+        }
+    }
+
+    /// Sends the request once, to each of the [failover](Request::with_failover) endpoints in
+    /// turn if there are any.
+    async fn send<T, Fut>(
+        &self,
+        mut send: impl FnMut(reqwest::Url) -> Fut,
+    ) -> Result<T, SequencerError>
+    where
+        Fut: futures::Future<Output = Result<T, SequencerError>>,
+    {
+        match self.failover {
+            Some(endpoints) => endpoints.send(&self.url, send).await,
+            None => send(self.url.clone()).await,
         }
     }
 }
 
-async fn parse<T>(response: reqwest::Response) -> Result<T, SequencerError>
+async fn parse<T>(
+    response: reqwest::Response,
+    meta: RequestMetadata,
+    unknown_fields: Option<&UnknownFields>,
+    bandwidth: Option<&Bandwidth>,
+) -> Result<T, SequencerError>
 where
     T: ::serde::de::DeserializeOwned,
 {
     let response = parse_raw(response).await?;
-    // Attempt to deserialize the actual data we are looking for
+
+    if unknown_fields.is_none() && bandwidth.is_none() {
+        // Attempt to deserialize the actual data we are looking for
+        let response = response.json::<T>().await?;
+        return Ok(response);
+    }
+
+    let bytes = response.bytes().await?;
+    if let Some(bandwidth) = bandwidth {
+        bandwidth.record(Source::Gateway, bytes.len() as u64);
+    }
+
+    if let Some(unknown_fields) = unknown_fields {
+        if let Ok(response) = unknown_fields.decode::<T>(meta.method, &bytes) {
+            return Ok(response);
+        }
+    }
+
+    // Decode using reqwest, so that any error is the same as when decoding the response directly.
+    let response = reqwest::Response::from(http::Response::new(bytes));
     let response = response.json::<T>().await?;
     Ok(response)
 }
@@ -366,27 +649,125 @@ async fn parse_raw(response: reqwest::Response) -> Result<reqwest::Response, Seq
 
 pub trait RequestState {}
 
+/// Initial and maximum delay between requests while the gateway is down for maintenance.
+///
+/// The delay is short compared to the backoff of other failures, so that sync resumes soon after
+/// the maintenance ends.
+const MAINTENANCE_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+const MAX_MAINTENANCE_DELAY: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Interval between checks whether a used monthly bandwidth quota has been reset.
+const QUOTA_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// Wrapper function to allow retrying sequencer queries in an exponential manner.
-async fn retry0<T, Fut, FutureFactory, Ret>(
-    future_factory: FutureFactory,
-    retry_condition: Ret,
+///
+/// Maintenance responses are retried separately from other failures, so that a maintenance
+/// window neither escalates the exponential backoff nor logs every failed request. Instead, the
+/// `availability` flag is set for the duration of the maintenance. They are retried regardless
+/// of the [RetryPolicy], and do not count towards its attempts.
+///
+/// Every retry increments the `gateway_requests_retried_total` counter of the `method`.
+async fn retry0<T, Fut, FutureFactory>(
+    retry: Retry,
+    policy: &RetryPolicy,
+    availability: Option<&Availability>,
+    method: &'static str,
+    mut future_factory: FutureFactory,
 ) -> Result<T, SequencerError>
 where
     Fut: futures::Future<Output = Result<T, SequencerError>>,
     FutureFactory: FnMut() -> Fut,
-    Ret: FnMut(&SequencerError) -> bool,
 {
-    use pathfinder_retry::Retry;
-    use std::num::NonZeroU64;
+    match retry {
+        Retry::Disabled => future_factory().await,
+        Retry::Enabled => {
+            // Maintenance counts towards the attempts allowed by the policy.
+            let mut attempts = 0;
+            let mut maintenance_delay = MAINTENANCE_DELAY;
+            loop {
+                let result = backoff(policy, method, &mut attempts, &mut future_factory, |e| {
+                    !is_maintenance(e) && policy.allows(e) && retry_condition(e)
+                })
+                .await;
+
+                match result {
+                    Err(e)
+                        if is_maintenance(&e)
+                            && policy.allows(&e)
+                            && policy.allows_attempt(attempts) =>
+                    {
+                        match availability {
+                            Some(availability) => availability.set_unavailable(&e),
+                            None => tracing::debug!(reason=%e, "Gateway is under maintenance"),
+                        }
+                        tokio::time::sleep(maintenance_delay).await;
+                        maintenance_delay = (maintenance_delay * 2).min(MAX_MAINTENANCE_DELAY);
+                    }
+                    // Out of attempts while the gateway is still under maintenance.
+                    Err(e) if is_maintenance(&e) => return Err(e),
+                    result => {
+                        if let Some(availability) = availability {
+                            availability.set_available();
+                        }
+                        return result;
+                    }
+                }
+            }
+        }
+        Retry::ConnectOnly => {
+            backoff(
+                &CONNECT_ONLY_RETRY_POLICY,
+                method,
+                &mut 0,
+                &mut future_factory,
+                connect_retry_condition,
+            )
+            .await
+        }
+    }
+}
 
-    Retry::exponential(future_factory, NonZeroU64::new(2).unwrap())
-        .factor(NonZeroU64::new(15).unwrap())
-        .max_delay(std::time::Duration::from_secs(10 * 60))
-        .when(retry_condition)
-        .await
+/// Retries the request for as long as it fails with errors which meet `retry_condition`, and
+/// `policy` allows another attempt, counting each request in `attempts`.
+async fn backoff<T, Fut, FutureFactory>(
+    policy: &RetryPolicy,
+    method: &'static str,
+    attempts: &mut usize,
+    future_factory: &mut FutureFactory,
+    mut retry_condition: impl FnMut(&SequencerError) -> bool,
+) -> Result<T, SequencerError>
+where
+    Fut: futures::Future<Output = Result<T, SequencerError>>,
+    FutureFactory: FnMut() -> Fut,
+{
+    let mut retries = 0;
+    loop {
+        *attempts += 1;
+        match future_factory().await {
+            Err(e) if policy.allows_attempt(*attempts) && retry_condition(&e) => {
+                metrics::increment_counter!(METRIC_RETRIED_REQUESTS, "method" => method);
+                retries += 1;
+                tokio::time::sleep(policy.backoff(retries)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Determines if a request failed before reaching the gateway, so that it is safe to retry.
+fn connect_retry_condition(e: &SequencerError) -> bool {
+    match e {
+        SequencerError::ReqwestError(e) if e.is_connect() => {
+            tracing::info!(reason=%e, "Request failed to connect, retrying");
+            true
+        }
+        _ => false,
+    }
 }
 
 /// Determines if an error is retryable or not.
+///
+/// Maintenance responses are retryable, but [retry0] retries them with its own delay.
 fn retry_condition(e: &SequencerError) -> bool {
     use reqwest::StatusCode;
     use tracing::{debug, error, info, warn};
@@ -420,7 +801,7 @@ fn retry_condition(e: &SequencerError) -> bool {
 
             true
         }
-        SequencerError::StarknetError(_) => false,
+        SequencerError::StarknetError(_) | SequencerError::QuotaExceeded => false,
         SequencerError::InvalidStarknetErrorVariant => {
             error!(reason=%e, "Request failed, retrying");
             true
@@ -438,7 +819,8 @@ mod tests {
         use tokio::{sync::Mutex, task::JoinHandle};
         use warp::Filter;
 
-        use crate::builder::{retry0, retry_condition};
+        use crate::builder::{retry0, Retry, RetryPolicy};
+        use crate::metrics::RequestMetadata;
 
         // A test helper
         fn status_queue_server(
@@ -491,13 +873,17 @@ mod tests {
 
             let (_jh, addr) = status_queue_server(statuses);
             let result = retry0(
+                Retry::Enabled,
+                &RetryPolicy::default(),
+                None,
+                "test",
                 || async {
                     let mut url = reqwest::Url::parse("http://localhost/").unwrap();
                     url.set_port(Some(addr.port())).unwrap();
                     let response = reqwest::get(url).await?;
-                    builder::parse::<String>(response).await
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
                 },
-                retry_condition,
             )
             .await
             .unwrap();
@@ -527,13 +913,17 @@ mod tests {
 
             let (_jh, addr) = status_queue_server(statuses);
             let error = retry0(
+                Retry::Enabled,
+                &RetryPolicy::default(),
+                None,
+                "test",
                 || async {
                     let mut url = reqwest::Url::parse("http://localhost/").unwrap();
                     url.set_port(Some(addr.port())).unwrap();
                     let response = reqwest::get(url).await?;
-                    builder::parse::<String>(response).await
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
                 },
-                retry_condition,
             )
             .await
             .unwrap_err();
@@ -543,6 +933,158 @@ mod tests {
             );
         }
 
+        #[test_log::test(tokio::test)]
+        async fn policy_limits_attempts_and_statuses() {
+            use crate::builder;
+            use starknet_gateway_types::error::SequencerError;
+
+            tokio::time::pause();
+
+            let statuses = VecDeque::from([
+                (StatusCode::TOO_MANY_REQUESTS, ""),
+                (StatusCode::TOO_MANY_REQUESTS, ""),
+                (StatusCode::TOO_MANY_REQUESTS, ""),
+                (StatusCode::INTERNAL_SERVER_ERROR, ""),
+                (StatusCode::OK, r#""Not reached""#),
+            ]);
+            let (_jh, addr) = status_queue_server(statuses);
+            let policy = RetryPolicy {
+                max_attempts: std::num::NonZeroUsize::new(2),
+                retryable_statuses: Some(vec![StatusCode::TOO_MANY_REQUESTS]),
+                ..Default::default()
+            };
+            let get = || async {
+                let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+                let response = reqwest::get(url).await?;
+                builder::parse::<String>(response, RequestMetadata::new("test"), None, None).await
+            };
+
+            // Gives up after the second attempt.
+            let error = retry0(Retry::Enabled, &policy, None, "test", get)
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::ReqwestError(e) => assert_eq!(e.status(), Some(StatusCode::TOO_MANY_REQUESTS))
+            );
+
+            // Does not retry a status which is not listed.
+            let error = retry0(Retry::Enabled, &policy, None, "test", get)
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::ReqwestError(e) => assert_eq!(e.status(), Some(StatusCode::INTERNAL_SERVER_ERROR))
+            );
+        }
+
+        #[test]
+        fn backoff() {
+            let policy = RetryPolicy {
+                base_delay: Duration::from_secs(5),
+                max_delay: Duration::from_secs(30),
+                ..Default::default()
+            };
+            let delays = (1..=5).map(|retry| policy.backoff(retry).as_secs());
+            assert_eq!(delays.collect::<Vec<_>>(), vec![5, 10, 20, 30, 30]);
+
+            let policy = RetryPolicy {
+                jitter: true,
+                ..policy
+            };
+            for _ in 0..100 {
+                let delay = policy.backoff(3);
+                assert!(delay >= Duration::from_secs(10) && delay <= Duration::from_secs(20));
+            }
+        }
+
+        #[test_log::test(tokio::test)]
+        async fn maintenance_pauses_without_escalating_backoff() {
+            use crate::availability::Availability;
+            use crate::builder;
+
+            tokio::time::pause();
+
+            let mut statuses = VecDeque::from([(StatusCode::SERVICE_UNAVAILABLE, ""); 6]);
+            statuses.push_back((StatusCode::OK, r#""Back""#));
+
+            let (_jh, addr) = status_queue_server(statuses);
+            let availability = Availability::default();
+            let seen = Mutex::new(Vec::new());
+            let fut = retry0(
+                Retry::Enabled,
+                &RetryPolicy::default(),
+                Some(&availability),
+                "test",
+                || async {
+                    seen.lock().await.push(availability.is_unavailable());
+                    let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+                    let response = reqwest::get(url).await?;
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
+                },
+            );
+
+            // Maintenance delays of 5, 10, 20, 40, 60 and 60 seconds, whereas the exponential
+            // backoff of other failures would have reached 10 minutes.
+            let result = tokio::time::timeout(Duration::from_secs(200), fut)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(result, "Back");
+            assert_eq!(
+                *seen.lock().await,
+                [false, true, true, true, true, true, true]
+            );
+            assert!(!availability.is_unavailable());
+        }
+
+        #[test_log::test(tokio::test)]
+        async fn maintenance_is_limited_by_max_attempts() {
+            use crate::availability::Availability;
+            use crate::builder;
+            use starknet_gateway_types::error::SequencerError;
+            use std::num::NonZeroUsize;
+
+            tokio::time::pause();
+
+            let statuses = VecDeque::from([(StatusCode::SERVICE_UNAVAILABLE, ""); 4]);
+            let (_jh, addr) = status_queue_server(statuses);
+            let policy = RetryPolicy {
+                max_attempts: NonZeroUsize::new(3),
+                ..Default::default()
+            };
+            let availability = Availability::default();
+            let attempts = Mutex::new(0);
+            let fut = retry0(
+                Retry::Enabled,
+                &policy,
+                Some(&availability),
+                "test",
+                || async {
+                    *attempts.lock().await += 1;
+                    let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+                    let response = reqwest::get(url).await?;
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
+                },
+            );
+
+            let error = tokio::time::timeout(Duration::from_secs(200), fut)
+                .await
+                .unwrap()
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::ReqwestError(e) => {
+                    assert_eq!(e.status(), Some(StatusCode::SERVICE_UNAVAILABLE))
+                }
+            );
+            assert_eq!(*attempts.lock().await, 3);
+            // The gateway is still under maintenance.
+            assert!(availability.is_unavailable());
+        }
+
         #[tokio::test(flavor = "current_thread")]
         async fn request_timeout() {
             use crate::builder;
@@ -555,6 +1097,10 @@ mod tests {
             static CNT: AtomicUsize = AtomicUsize::new(0);
 
             let fut = retry0(
+                Retry::Enabled,
+                &RetryPolicy::default(),
+                None,
+                "test",
                 || async {
                     let mut url = reqwest::Url::parse("http://localhost/").unwrap();
                     url.set_port(Some(addr.port())).unwrap();
@@ -569,9 +1115,9 @@ mod tests {
                         .timeout(Duration::from_millis(1))
                         .send()
                         .await?;
-                    builder::parse::<String>(response).await
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
                 },
-                retry_condition,
             );
 
             // The retry loops forever, so wrap it in a timeout and check the counter.
@@ -584,6 +1130,73 @@ mod tests {
             // 5th try should have timedout if this is really exponential backoff
             assert_eq!(CNT.load(Ordering::Relaxed), 5);
         }
+
+        #[tokio::test(flavor = "current_thread")]
+        async fn connect_only_retries_connection_failures() {
+            use starknet_gateway_types::error::SequencerError;
+            use std::sync::atomic::{AtomicUsize, Ordering};
+
+            tokio::time::pause();
+
+            // Nothing listens on the port once the listener is dropped.
+            let addr = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap();
+            let attempts = AtomicUsize::new(0);
+
+            let error = retry0(
+                Retry::ConnectOnly,
+                &RetryPolicy::default(),
+                None,
+                "test",
+                || async {
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+                    let response = reqwest::get(url).await?;
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
+                },
+            )
+            .await
+            .unwrap_err();
+
+            assert_matches!(error, SequencerError::ReqwestError(e) => assert!(e.is_connect()));
+            assert_eq!(attempts.load(Ordering::Relaxed), 3);
+        }
+
+        #[test_log::test(tokio::test)]
+        async fn connect_only_does_not_retry_responses() {
+            use starknet_gateway_types::error::SequencerError;
+
+            tokio::time::pause();
+
+            let statuses = VecDeque::from([
+                (StatusCode::SERVICE_UNAVAILABLE, ""),
+                (StatusCode::OK, r#""Sent twice""#),
+            ]);
+
+            let (_jh, addr) = status_queue_server(statuses);
+            let error = retry0(
+                Retry::ConnectOnly,
+                &RetryPolicy::default(),
+                None,
+                "test",
+                || async {
+                    let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+                    let response = reqwest::get(url).await?;
+                    builder::parse::<String>(response, RequestMetadata::new("test"), None, None)
+                        .await
+                },
+            )
+            .await
+            .unwrap_err();
+
+            assert_matches!(
+                error,
+                SequencerError::ReqwestError(e) => assert_eq!(e.status(), Some(StatusCode::SERVICE_UNAVAILABLE))
+            );
+        }
     }
 
     mod invalid_starknet_error_variant {
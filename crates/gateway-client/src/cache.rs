@@ -0,0 +1,188 @@
+//! Persists feeder gateway responses which can never change, so that they are not downloaded
+//! again after a restart.
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::metrics::{METRIC_CACHE_HITS, METRIC_CACHE_MISSES};
+
+/// Storage for the responses cached by [Client::with_response_store](crate::Client::with_response_store).
+///
+/// Responses are keyed by the feeder gateway method and the hash identifying the requested item.
+/// The methods block the thread, and are called from a blocking task.
+pub trait ResponseStore: Send + Sync {
+    /// Returns the stored response of `method` for the item identified by `hash`.
+    fn get(&self, method: &'static str, hash: &[u8]) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Stores the response of `method` for the item identified by `hash`.
+    fn insert(&self, method: &'static str, hash: &[u8], response: &[u8]) -> anyhow::Result<()>;
+}
+
+/// Caches responses in a [ResponseStore], counting hits and misses per method.
+///
+/// Failures of the store are logged and treated as misses, so that they never fail a request, as
+/// are stored responses which can no longer be parsed.
+#[derive(Clone)]
+pub(crate) struct ResponseCache(Arc<dyn ResponseStore>);
+
+impl ResponseCache {
+    pub fn new(store: Arc<dyn ResponseStore>) -> Self {
+        Self(store)
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        hash: &[u8],
+    ) -> Option<T> {
+        self.get(method, hash, |response| {
+            serde_json::from_slice(&response).ok()
+        })
+        .await
+    }
+
+    async fn get<T>(
+        &self,
+        method: &'static str,
+        hash: &[u8],
+        parse: impl FnOnce(Vec<u8>) -> Option<T>,
+    ) -> Option<T> {
+        let store = self.0.clone();
+        let key = hash.to_vec();
+        let response = tokio::task::spawn_blocking(move || store.get(method, &key)).await;
+
+        let response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(error)) => {
+                tracing::warn!(%method, %error, "Reading cached gateway response failed");
+                None
+            }
+            Err(error) => {
+                tracing::warn!(%method, %error, "Reading cached gateway response panicked");
+                None
+            }
+        };
+        let response = response.and_then(parse);
+
+        match response {
+            Some(_) => metrics::increment_counter!(METRIC_CACHE_HITS, "method" => method),
+            None => metrics::increment_counter!(METRIC_CACHE_MISSES, "method" => method),
+        }
+        response
+    }
+
+    pub fn insert_json<T: Serialize>(&self, method: &'static str, hash: &[u8], response: &T) {
+        match serde_json::to_vec(response) {
+            Ok(response) => self.insert(method, hash, response),
+            Err(error) => tracing::warn!(%method, %error, "Serializing gateway response failed"),
+        }
+    }
+
+    /// Stores the response in the background.
+    fn insert(&self, method: &'static str, hash: &[u8], response: Vec<u8>) {
+        let store = self.0.clone();
+        let key = hash.to_vec();
+        tokio::task::spawn_blocking(move || {
+            if let Err(error) = store.insert(method, &key, &response) {
+                tracing::warn!(%method, %error, "Caching gateway response failed");
+            }
+        });
+    }
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ResponseCache").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, ClientApi};
+    use pathfinder_common::{BlockId, StarknetBlockHash};
+    use stark_hash::Felt;
+    use starknet_gateway_types::reply::{MaybePendingBlock, Status};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use warp::Filter;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<(&'static str, Vec<u8>), Vec<u8>>>);
+
+    impl ResponseStore for MemoryStore {
+        fn get(&self, method: &'static str, hash: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .get(&(method, hash.to_vec()))
+                .cloned())
+        }
+
+        fn insert(&self, method: &'static str, hash: &[u8], response: &[u8]) -> anyhow::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert((method, hash.to_vec()), response.to_vec());
+            Ok(())
+        }
+    }
+
+    /// Serves the genesis block with the given status, counting the requests.
+    fn server(status: Status) -> (Client, Arc<Mutex<usize>>) {
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let filter = warp::path!("feeder_gateway" / "get_block").map(move || {
+            *counter.lock().unwrap() += 1;
+            let block = starknet_gateway_test_fixtures::v0_9_0::block::GENESIS;
+            let mut block: serde_json::Value = serde_json::from_str(block).unwrap();
+            block["status"] = serde_json::to_value(status).unwrap();
+            warp::reply::json(&block)
+        });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = reqwest::Url::parse(&format!("http://{addr}/")).unwrap();
+        (Client::with_base_url(url).unwrap(), requests)
+    }
+
+    #[tokio::test]
+    async fn blocks_accepted_on_l1_are_cached() {
+        let store = Arc::new(MemoryStore::default());
+        let (client, requests) = server(Status::AcceptedOnL1);
+        let client = client.with_response_store(store.clone());
+        let hash = BlockId::Hash(StarknetBlockHash(Felt::from_hex_str("0x7").unwrap()));
+
+        let first = client.block(hash).await.unwrap();
+        assert!(matches!(first, MaybePendingBlock::Block(_)));
+        // Stored in the background.
+        while store.0.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        // A client created after a restart is served from the store.
+        let (restarted, restarted_requests) = server(Status::AcceptedOnL1);
+        let restarted = restarted.with_response_store(store);
+        assert_eq!(restarted.block(hash).await.unwrap(), first);
+        assert_eq!(*restarted_requests.lock().unwrap(), 0);
+        assert_eq!(*requests.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn blocks_which_may_change_are_not_cached() {
+        let store = Arc::new(MemoryStore::default());
+        let (client, requests) = server(Status::AcceptedOnL2);
+        let client = client.with_response_store(store.clone());
+        let hash = BlockId::Hash(StarknetBlockHash(Felt::from_hex_str("0x7").unwrap()));
+
+        client.block(hash).await.unwrap();
+        client.block(hash).await.unwrap();
+        // Blocks requested by number are never cached.
+        client.block(BlockId::Latest).await.unwrap();
+
+        assert_eq!(*requests.lock().unwrap(), 3);
+        assert!(store.0.lock().unwrap().is_empty());
+    }
+}
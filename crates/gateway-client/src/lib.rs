@@ -1,9 +1,13 @@
 //! StarkNet L2 sequencer client.
+use anyhow::Context;
+use pathfinder_common::bandwidth::Bandwidth;
 use pathfinder_common::{
     BlockId, CallParam, CasmHash, Chain, ClassHash, ContractAddress, ContractAddressSalt,
-    EntryPoint, Fee, SierraHash, StarknetBlockNumber, StarknetTransactionHash, StorageAddress,
-    StorageValue, TransactionNonce, TransactionSignatureElem, TransactionVersion,
+    ContractNonce, EntryPoint, Fee, SierraHash, StarknetBlockHash, StarknetBlockNumber,
+    StarknetTransactionHash, StorageAddress, StorageValue, TransactionNonce,
+    TransactionSignatureElem, TransactionVersion,
 };
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Url;
 use starknet_gateway_types::{
     error::SequencerError,
@@ -15,10 +19,23 @@ use starknet_gateway_types::{
         BlockHashOrTag,
     },
 };
-use std::{fmt::Debug, result::Result, time::Duration};
+use std::{fmt::Debug, result::Result, sync::Arc, time::Duration};
 
+mod availability;
 mod builder;
+mod cache;
+mod failover;
 mod metrics;
+mod rate_limit;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod unknown_fields;
+
+pub use availability::Availability;
+pub use builder::RetryPolicy;
+pub use cache::ResponseStore;
+pub use metrics::{METRIC_REQUEST_DURATION, REQUEST_DURATION_BUCKETS};
+pub use rate_limit::RateLimit;
 
 #[cfg_attr(feature = "test-utils", mockall::automock)]
 #[async_trait::async_trait]
@@ -41,6 +58,18 @@ pub trait ClientApi {
         block_hash: BlockHashOrTag,
     ) -> Result<StorageValue, SequencerError>;
 
+    async fn nonce(
+        &self,
+        contract_addr: ContractAddress,
+        block_hash: BlockHashOrTag,
+    ) -> Result<ContractNonce, SequencerError>;
+
+    async fn class_hash_at(
+        &self,
+        contract_addr: ContractAddress,
+        block_hash: BlockHashOrTag,
+    ) -> Result<ClassHash, SequencerError>;
+
     async fn transaction(
         &self,
         transaction_hash: StarknetTransactionHash,
@@ -53,6 +82,8 @@ pub trait ClientApi {
 
     async fn eth_contract_addresses(&self) -> Result<reply::EthContractAddresses, SequencerError>;
 
+    async fn block_traces(&self, block: BlockId) -> Result<bytes::Bytes, SequencerError>;
+
     #[allow(clippy::too_many_arguments)]
     async fn add_invoke_transaction(
         &self,
@@ -96,19 +127,56 @@ pub trait ClientApi {
 /// Retry is performed on __all__ types of errors __except for__
 /// [StarkNet specific errors](starknet_gateway_types::error::StarknetError).
 ///
-/// Initial backoff time is 30 seconds and saturates at 10 minutes:
+/// By default, the initial backoff time is 30 seconds and saturates at 10 minutes:
 ///
 /// `backoff [secs] = min((2 ^ N) * 15, 600) [secs]`
 ///
-/// where `N` is the consecutive retry iteration number `{1, 2, ...}`.
+/// where `N` is the consecutive retry iteration number `{1, 2, ...}`. This can be changed with
+/// a [RetryPolicy], and every retry is counted by the `gateway_requests_retried_total` metric.
+///
+/// Maintenance responses (`502`, `503` and `504`) are instead retried every 5 seconds, backing
+/// off to once a minute, and set the shared [Availability] flag until the gateway is back.
+///
+/// Feeder gateway requests fail over to the
+/// [fallback feeder gateways](Client::with_feeder_gateway_fallbacks) on outages, before any
+/// retry. Only a request which fails on every endpoint is retried.
+///
+/// Transactions are submitted to the gateway over a separate connection pool with a shorter
+/// timeout, so that they are never queued behind sync downloads. Submissions are only retried
+/// if the connection could not be established, since otherwise the gateway may already have
+/// accepted the transaction.
+///
+/// Responses which can never change are cached persistently, if a
+/// [ResponseStore](Client::with_response_store) is configured.
 #[derive(Debug, Clone)]
 pub struct Client {
     /// This client is internally refcounted
     inner: reqwest::Client,
+    /// Separate connection pool for submitting transactions, so that they are not queued
+    /// behind sync downloads.
+    write: reqwest::Client,
     /// StarkNet gateway URL.
     gateway: Url,
-    /// StarkNet feeder gateway URL.
-    feeder_gateway: Url,
+    /// StarkNet feeder gateway URLs, the first of which is the primary.
+    feeder_gateway: failover::Endpoints,
+    /// Collects unknown fields in responses, if enabled.
+    unknown_fields: Option<unknown_fields::UnknownFields>,
+    /// Tallies the bytes received from the gateway, if enabled.
+    bandwidth: Option<Bandwidth>,
+    /// Set while the feeder gateway is down for maintenance.
+    availability: Availability,
+    /// Counts the feeder gateway requests which were rate limited.
+    rate_limit: RateLimit,
+    /// Whether to retry failed feeder gateway requests.
+    retry: builder::Retry,
+    /// How failed feeder gateway requests are retried.
+    retry_policy: RetryPolicy,
+    /// Whether to retry failed transaction submissions.
+    write_retry: builder::Retry,
+    /// Caches immutable responses, if enabled.
+    response_cache: Option<cache::ResponseCache>,
+    /// Attached to every request, with values which are redacted from debug output.
+    headers: Option<HeaderMap>,
 }
 
 impl Client {
@@ -116,6 +184,10 @@ impl Client {
     const RETRY: builder::Retry = builder::Retry::Enabled;
     #[cfg(test)]
     const RETRY: builder::Retry = builder::Retry::Disabled;
+    #[cfg(not(test))]
+    const WRITE_RETRY: builder::Retry = builder::Retry::ConnectOnly;
+    #[cfg(test)]
+    const WRITE_RETRY: builder::Retry = builder::Retry::Disabled;
 
     /// Creates a new Sequencer client for the given chain.
     #[cfg(any(test, feature = "test-utils"))]
@@ -162,21 +234,201 @@ impl Client {
         metrics::register();
 
         Ok(Self {
-            inner: reqwest::Client::builder()
-                .timeout(Duration::from_secs(120))
-                .user_agent(pathfinder_common::consts::USER_AGENT)
-                .build()?,
+            inner: Self::read_client(None)?,
+            write: Self::write_client(None)?,
             gateway,
-            feeder_gateway,
+            feeder_gateway: failover::Endpoints::new(feeder_gateway, Vec::new()),
+            unknown_fields: None,
+            bandwidth: None,
+            availability: Availability::default(),
+            rate_limit: RateLimit::default(),
+            retry: Self::RETRY,
+            retry_policy: RetryPolicy::default(),
+            write_retry: Self::WRITE_RETRY,
+            response_cache: None,
+            headers: None,
         })
     }
 
+    fn read_client(proxy: Option<&reqwest::Proxy>) -> anyhow::Result<reqwest::Client> {
+        let builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(120))
+            .user_agent(pathfinder_common::consts::USER_AGENT);
+        let builder = match proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        };
+        Ok(builder.build()?)
+    }
+
+    fn write_client(proxy: Option<&reqwest::Proxy>) -> anyhow::Result<reqwest::Client> {
+        let builder = reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(10))
+            .timeout(Duration::from_secs(30))
+            .user_agent(pathfinder_common::consts::USER_AGENT);
+        let builder = match proxy {
+            Some(proxy) => builder.proxy(proxy.clone()),
+            None => builder,
+        };
+        Ok(builder.build()?)
+    }
+
+    /// Sends all requests through the proxy at the given `http`, `https` or `socks5` URL.
+    ///
+    /// Without one, the proxy is taken from the `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY`
+    /// environment variables, if they are set.
+    pub fn with_proxy(mut self, proxy: Url) -> anyhow::Result<Self> {
+        let proxy = reqwest::Proxy::all(proxy).context("Invalid proxy URL")?;
+        self.inner = Self::read_client(Some(&proxy))?;
+        self.write = Self::write_client(Some(&proxy))?;
+        Ok(self)
+    }
+
+    /// Attaches the given headers to every request, such as the API key required by a hosted
+    /// gateway.
+    ///
+    /// The values are marked as sensitive, so that they are redacted wherever the client or its
+    /// requests are logged.
+    pub fn with_headers(
+        mut self,
+        headers: impl IntoIterator<Item = (HeaderName, HeaderValue)>,
+    ) -> Self {
+        let map = self.headers.get_or_insert_with(HeaderMap::new);
+        for (name, mut value) in headers {
+            value.set_sensitive(true);
+            map.append(name, value);
+        }
+        self
+    }
+
+    /// Disables retrying of failed requests, so that tests see failures immediately.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn disable_retry_for_tests(self) -> Self {
+        Self {
+            retry: builder::Retry::Disabled,
+            write_retry: builder::Retry::Disabled,
+            ..self
+        }
+    }
+
+    /// Reports fields in responses which are unknown to pathfinder, instead of silently ignoring
+    /// them. Unknown fields are counted by the `gateway_unknown_fields_total` metric and
+    /// summarized in an hourly warning, which is logged by a task spawned on the current tokio
+    /// runtime.
+    pub fn with_unknown_field_reporting(mut self) -> Self {
+        self.unknown_fields = Some(Default::default());
+        self
+    }
+
+    /// Fails feeder gateway requests over to the given feeder gateway URLs, in order, while the
+    /// primary feeder gateway has an outage. The health of each URL is tracked separately.
+    pub fn with_feeder_gateway_fallbacks(mut self, fallbacks: Vec<Url>) -> Self {
+        let primary = self.feeder_gateway.primary().clone();
+        self.feeder_gateway = failover::Endpoints::new(primary, fallbacks);
+        self
+    }
+
+    /// Checks that every [fallback feeder gateway](Self::with_feeder_gateway_fallbacks) serves
+    /// the same chain as the primary feeder gateway, by comparing their genesis blocks.
+    ///
+    /// Fails if a fallback has a different genesis block, or if any genesis block cannot be
+    /// fetched within this client's [RetryPolicy].
+    pub async fn verify_feeder_gateway_fallbacks(&self) -> anyhow::Result<()> {
+        let primary = self.feeder_gateway.primary();
+        let expected = self
+            .genesis_hash_at(primary.clone(), self.headers.clone())
+            .await
+            .context("Fetching genesis block from the feeder gateway")?;
+
+        for fallback in self.feeder_gateway.fallbacks() {
+            let label = fallback.origin().ascii_serialization();
+            // Like failover, this never sends the headers of the primary to a fallback.
+            let genesis = self
+                .genesis_hash_at(fallback.clone(), None)
+                .await
+                .with_context(|| format!("Fetching genesis block from fallback {label}"))?;
+            anyhow::ensure!(
+                genesis == expected,
+                "Fallback feeder gateway {label} serves a different chain. Its genesis block is {genesis} instead of {expected}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the genesis block hash from the feeder gateway at `url` alone, without failover
+    /// or caching.
+    async fn genesis_hash_at(
+        &self,
+        url: Url,
+        headers: Option<HeaderMap>,
+    ) -> anyhow::Result<StarknetBlockHash> {
+        let client = Self {
+            feeder_gateway: failover::Endpoints::new(url, Vec::new()),
+            availability: Availability::default(),
+            response_cache: None,
+            headers,
+            ..self.clone()
+        };
+
+        let block = client
+            .block(StarknetBlockNumber::GENESIS.into())
+            .await?
+            .as_block()
+            .context("Genesis block should not be pending")?;
+        Ok(block.block_hash)
+    }
+
+    /// Retries failed feeder gateway requests according to the given [RetryPolicy].
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Caches the responses which can never change in the given [ResponseStore], so that they
+    /// are served from it instead of the feeder gateway, even after a restart.
+    ///
+    /// These are blocks by hash and transactions once they are accepted on L1, before which their
+    /// status can still change. Classes are not cached, as sync stores them in the database
+    /// anyway. Cache hits and misses are counted by the `gateway_cache_hits_total` and
+    /// `gateway_cache_misses_total` metrics.
+    pub fn with_response_store(mut self, store: Arc<dyn ResponseStore>) -> Self {
+        self.response_cache = Some(cache::ResponseCache::new(store));
+        self
+    }
+
+    /// Records the size of every response in the given [Bandwidth] tally.
+    pub fn with_bandwidth_accounting(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = Some(bandwidth);
+        self
+    }
+
+    /// Whether the feeder gateway is down for maintenance, shared by all clones of this client.
+    pub fn availability(&self) -> &Availability {
+        &self.availability
+    }
+
+    /// How often feeder gateway requests were rate limited, shared by all clones of this client.
+    pub fn rate_limit(&self) -> &RateLimit {
+        &self.rate_limit
+    }
+
     fn gateway_request(&self) -> builder::Request<'_, builder::stage::Method> {
-        builder::Request::builder(&self.inner, self.gateway.clone())
+        builder::Request::builder(&self.write, self.gateway.clone())
+            .with_unknown_fields(self.unknown_fields.as_ref())
+            .with_bandwidth(self.bandwidth.as_ref())
+            .with_headers(self.headers.as_ref())
     }
 
     fn feeder_gateway_request(&self) -> builder::Request<'_, builder::stage::Method> {
-        builder::Request::builder(&self.inner, self.feeder_gateway.clone())
+        builder::Request::builder(&self.inner, self.feeder_gateway.primary().clone())
+            .with_unknown_fields(self.unknown_fields.as_ref())
+            .with_bandwidth(self.bandwidth.as_ref())
+            .with_availability(Some(&self.availability))
+            .with_rate_limit(Some(&self.rate_limit))
+            .with_failover(Some(&self.feeder_gateway))
+            .with_retry_policy(Some(&self.retry_policy))
+            .with_headers(self.headers.as_ref())
     }
 
     /// Returns the [network chain](Chain) this client is operating on.
@@ -207,12 +459,33 @@ impl Client {
 impl ClientApi for Client {
     #[tracing::instrument(skip(self))]
     async fn block(&self, block: BlockId) -> Result<reply::MaybePendingBlock, SequencerError> {
-        self.feeder_gateway_request()
+        const METHOD: &str = "get_block";
+        let cache = match (block, &self.response_cache) {
+            (BlockId::Hash(hash), Some(cache)) => Some((cache, hash)),
+            _ => None,
+        };
+
+        if let Some((cache, hash)) = cache {
+            if let Some(block) = cache.get_json(METHOD, hash.0.as_be_bytes()).await {
+                return Ok(reply::MaybePendingBlock::Block(block));
+            }
+        }
+
+        let reply = self
+            .feeder_gateway_request()
             .get_block()
             .with_block(block)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get()
-            .await
+            .await?;
+
+        if let (Some((cache, hash)), reply::MaybePendingBlock::Block(block)) = (cache, &reply) {
+            if block.status == reply::Status::AcceptedOnL1 {
+                cache.insert_json(METHOD, hash.0.as_be_bytes(), block);
+            }
+        }
+
+        Ok(reply)
     }
 
     #[tracing::instrument(skip(self))]
@@ -222,7 +495,7 @@ impl ClientApi for Client {
             // Let's not introduce an equivalent of `with_class_hash` for `SierraHash`
             // which is conceptually the same thing here
             .with_class_hash(ClassHash(hash.0))
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get_as_bytes()
             .await
     }
@@ -233,7 +506,7 @@ impl ClientApi for Client {
         self.feeder_gateway_request()
             .get_class_by_hash()
             .with_class_hash(class_hash)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get_as_bytes()
             .await
     }
@@ -248,7 +521,7 @@ impl ClientApi for Client {
             .get_class_by_hash()
             .with_class_hash(class_hash)
             .with_block(BlockId::Pending)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get_as_bytes()
             .await
     }
@@ -266,7 +539,39 @@ impl ClientApi for Client {
             .with_contract_address(contract_addr)
             .with_storage_address(key)
             .with_block(block_hash)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
+            .get()
+            .await
+    }
+
+    /// Gets the nonce of a contract.
+    #[tracing::instrument(skip(self))]
+    async fn nonce(
+        &self,
+        contract_addr: ContractAddress,
+        block_hash: BlockHashOrTag,
+    ) -> Result<ContractNonce, SequencerError> {
+        self.feeder_gateway_request()
+            .get_nonce()
+            .with_contract_address(contract_addr)
+            .with_block(block_hash)
+            .with_retry(self.retry)
+            .get()
+            .await
+    }
+
+    /// Gets the class hash of a contract, failing for contracts which are not deployed.
+    #[tracing::instrument(skip(self))]
+    async fn class_hash_at(
+        &self,
+        contract_addr: ContractAddress,
+        block_hash: BlockHashOrTag,
+    ) -> Result<ClassHash, SequencerError> {
+        self.feeder_gateway_request()
+            .get_class_hash_at()
+            .with_contract_address(contract_addr)
+            .with_block(block_hash)
+            .with_retry(self.retry)
             .get()
             .await
     }
@@ -277,12 +582,30 @@ impl ClientApi for Client {
         &self,
         transaction_hash: StarknetTransactionHash,
     ) -> Result<reply::Transaction, SequencerError> {
-        self.feeder_gateway_request()
+        const METHOD: &str = "get_transaction";
+        let key = transaction_hash.0.as_be_bytes();
+
+        if let Some(cache) = &self.response_cache {
+            if let Some(transaction) = cache.get_json(METHOD, key).await {
+                return Ok(transaction);
+            }
+        }
+
+        let transaction: reply::Transaction = self
+            .feeder_gateway_request()
             .get_transaction()
             .with_transaction_hash(transaction_hash)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get()
-            .await
+            .await?;
+
+        if let Some(cache) = &self.response_cache {
+            if transaction.status == reply::Status::AcceptedOnL1 {
+                cache.insert_json(METHOD, key, &transaction);
+            }
+        }
+
+        Ok(transaction)
     }
 
     #[tracing::instrument(skip(self))]
@@ -293,7 +616,7 @@ impl ClientApi for Client {
         self.feeder_gateway_request()
             .get_state_update()
             .with_block(block)
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get()
             .await
     }
@@ -303,11 +626,23 @@ impl ClientApi for Client {
     async fn eth_contract_addresses(&self) -> Result<reply::EthContractAddresses, SequencerError> {
         self.feeder_gateway_request()
             .get_contract_addresses()
-            .with_retry(Self::RETRY)
+            .with_retry(self.retry)
             .get()
             .await
     }
 
+    /// Gets the execution traces of a block's transactions as JSON, which is left to the RPC API
+    /// to parse into its trace types.
+    #[tracing::instrument(skip(self))]
+    async fn block_traces(&self, block: BlockId) -> Result<bytes::Bytes, SequencerError> {
+        self.feeder_gateway_request()
+            .get_block_traces()
+            .with_block(block)
+            .with_retry(self.retry)
+            .get_as_bytes()
+            .await
+    }
+
     /// Adds a transaction invoking a contract.
     #[tracing::instrument(skip(self))]
     async fn add_invoke_transaction(
@@ -330,13 +665,13 @@ impl ClientApi for Client {
             nonce,
         });
 
-        // Note that we don't do retries here.
+        // Note that we only retry if the connection could not be established.
         // This method is used to proxy an add transaction operation from the JSON-RPC
-        // API to the sequencer. Retries should be implemented in the JSON-RPC
+        // API to the sequencer. Any other retries should be implemented in the JSON-RPC
         // client instead.
         self.gateway_request()
             .add_transaction()
-            .with_retry(builder::Retry::Disabled)
+            .with_retry(self.write_retry)
             .post_with_json(&req)
             .await
     }
@@ -364,15 +699,15 @@ impl ClientApi for Client {
             compiled_class_hash,
         });
 
-        // Note that we don't do retries here.
+        // Note that we only retry if the connection could not be established.
         // This method is used to proxy an add transaction operation from the JSON-RPC
-        // API to the sequencer. Retries should be implemented in the JSON-RPC
+        // API to the sequencer. Any other retries should be implemented in the JSON-RPC
         // client instead.
         self.gateway_request()
             .add_transaction()
             // mainnet requires a token (but testnet does not so its optional).
             .with_optional_token(token.as_deref())
-            .with_retry(builder::Retry::Disabled)
+            .with_retry(self.write_retry)
             .post_with_json(&req)
             .await
     }
@@ -398,14 +733,13 @@ impl ClientApi for Client {
             constructor_calldata: calldata,
         });
 
-        // Note that we don't do retries here.
+        // Note that we only retry if the connection could not be established.
         // This method is used to proxy an add transaction operation from the JSON-RPC
-        // API to the sequencer. Retries should be implemented in the JSON-RPC
+        // API to the sequencer. Any other retries should be implemented in the JSON-RPC
         // client instead.
-
         self.gateway_request()
             .add_transaction()
-            .with_retry(builder::Retry::Disabled)
+            .with_retry(self.write_retry)
             .post_with_json(&req)
             .await
     }
@@ -632,6 +966,57 @@ mod tests {
         server_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn requests_go_through_proxy() {
+        use warp::Filter;
+
+        // A plain HTTP proxy receives the full URL of the target as the request target.
+        let filter = warp::path!("feeder_gateway" / "get_contract_addresses")
+            .and(warp::header::<String>("host"))
+            .map(|host: String| {
+                assert_eq!(host, "gateway.invalid");
+                r#"{
+                    "Starknet": "0x0000000000000000000000000000000000000001",
+                    "GpsStatementVerifier": "0x0000000000000000000000000000000000000002"
+                }"#
+            });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = Client::with_base_url(Url::parse("http://gateway.invalid/").unwrap())
+            .unwrap()
+            .with_proxy(Url::parse(&format!("http://{addr}")).unwrap())
+            .unwrap();
+        client.eth_contract_addresses().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn requests_include_headers() {
+        use warp::Filter;
+
+        let filter = warp::path!("feeder_gateway" / "get_contract_addresses")
+            .and(warp::header::<String>("x-api-key"))
+            .map(|key: String| {
+                assert_eq!(key, "secret");
+                r#"{
+                    "Starknet": "0x0000000000000000000000000000000000000001",
+                    "GpsStatementVerifier": "0x0000000000000000000000000000000000000002"
+                }"#
+            });
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let client = Client::with_base_url(Url::parse(&format!("http://{addr}")).unwrap())
+            .unwrap()
+            .with_headers([(
+                HeaderName::from_static("x-api-key"),
+                HeaderValue::from_static("secret"),
+            )]);
+        client.eth_contract_addresses().await.unwrap();
+
+        assert!(!format!("{client:?}").contains("secret"));
+    }
+
     mod block_matches_by_hash_on {
         use super::*;
         use pathfinder_common::{felt, test_utils::metrics::RecorderGuard};
@@ -660,6 +1045,64 @@ mod tests {
             assert_eq!(by_hash, by_number);
         }
 
+        #[tokio::test]
+        async fn records_bandwidth() {
+            let _guard = RecorderGuard::lock_as_noop();
+            let (_jh, client) = setup([(
+                format!("/feeder_gateway/get_block?blockNumber={GENESIS_BLOCK_NUMBER}"),
+                (v0_9_0::block::GENESIS, 200),
+            )]);
+            let bandwidth = Bandwidth::new(None);
+            let client = client.with_bandwidth_accounting(bandwidth.clone());
+
+            client
+                .block(BlockId::from(GENESIS_BLOCK_NUMBER))
+                .await
+                .unwrap();
+            assert_eq!(
+                bandwidth.received_this_month(),
+                v0_9_0::block::GENESIS.len() as u64
+            );
+        }
+
+        #[tokio::test]
+        async fn bandwidth_quota() {
+            use pathfinder_common::bandwidth::Source;
+
+            let _guard = RecorderGuard::lock_as_noop();
+            let (_jh, client) = setup([(
+                format!("/feeder_gateway/get_block?blockNumber={GENESIS_BLOCK_NUMBER}"),
+                (v0_9_0::block::GENESIS, 200),
+            )]);
+            let quota = 20 * v0_9_0::block::GENESIS.len() as u64;
+            let bandwidth = Bandwidth::new(Some(quota));
+            let client = client.with_bandwidth_accounting(bandwidth.clone());
+
+            // Near the quota, only the pending block is held back.
+            bandwidth.record(
+                Source::Ethereum,
+                quota - 2 * v0_9_0::block::GENESIS.len() as u64,
+            );
+            let error = client.block(BlockId::Pending).await.unwrap_err();
+            assert_matches!(error, SequencerError::QuotaExceeded);
+            client
+                .block(BlockId::from(GENESIS_BLOCK_NUMBER))
+                .await
+                .unwrap();
+            client
+                .block(BlockId::from(GENESIS_BLOCK_NUMBER))
+                .await
+                .unwrap();
+
+            // Once the quota is used, requests which are not retried fail without being sent.
+            let error = client
+                .block(BlockId::from(GENESIS_BLOCK_NUMBER))
+                .await
+                .unwrap_err();
+            assert_matches!(error, SequencerError::QuotaExceeded);
+            assert_eq!(bandwidth.received_this_month(), quota);
+        }
+
         #[tokio::test]
         async fn specific_block() {
             let _guard = RecorderGuard::lock_as_noop();
@@ -994,6 +1437,69 @@ mod tests {
         }
     }
 
+    mod nonce {
+        use super::*;
+        use pathfinder_common::felt;
+        use pretty_assertions::assert_eq;
+
+        #[tokio::test]
+        async fn success() {
+            let (_jh, client) = setup([(
+                format!(
+                    "/feeder_gateway/get_nonce?contractAddress={}&blockNumber=latest",
+                    VALID_CONTRACT_ADDR.get().to_hex_str(),
+                ),
+                (r#""0x5""#, 200),
+            )]);
+            let result = client
+                .nonce(VALID_CONTRACT_ADDR, BlockHashOrTag::Tag(Tag::Latest))
+                .await
+                .unwrap();
+            assert_eq!(result, ContractNonce(felt!("0x5")));
+        }
+    }
+
+    mod class_hash_at {
+        use super::*;
+        use pathfinder_common::felt;
+        use pretty_assertions::assert_eq;
+
+        #[tokio::test]
+        async fn success() {
+            let (_jh, client) = setup([(
+                format!(
+                    "/feeder_gateway/get_class_hash_at?contractAddress={}&blockNumber=latest",
+                    VALID_CONTRACT_ADDR.get().to_hex_str(),
+                ),
+                (r#""0x123""#, 200),
+            )]);
+            let result = client
+                .class_hash_at(VALID_CONTRACT_ADDR, BlockHashOrTag::Tag(Tag::Latest))
+                .await
+                .unwrap();
+            assert_eq!(result, ClassHash(felt!("0x123")));
+        }
+
+        #[tokio::test]
+        async fn uninitialized_contract() {
+            let (_jh, client) = setup([(
+                format!(
+                    "/feeder_gateway/get_class_hash_at?contractAddress={}&blockNumber=latest",
+                    INVALID_CONTRACT_ADDR.get().to_hex_str(),
+                ),
+                response_from(StarknetErrorCode::UninitializedContract),
+            )]);
+            let error = client
+                .class_hash_at(INVALID_CONTRACT_ADDR, BlockHashOrTag::Tag(Tag::Latest))
+                .await
+                .unwrap_err();
+            assert_matches!(
+                error,
+                SequencerError::StarknetError(e) => assert_eq!(e.code, StarknetErrorCode::UninitializedContract)
+            );
+        }
+    }
+
     mod transaction {
         use super::{reply::Status, *};
         use pathfinder_common::felt;
@@ -1843,6 +2349,23 @@ mod tests {
                     ),
                 }
             });
+
+            // Every request is timed, whether it succeeded or not.
+            let histogram = "sequencer_request_duration_seconds";
+            assert_eq!(
+                handle.get_histogram_count_by_label(histogram, [("method", method_name)]),
+                21
+            );
+            for tag in ["latest", "pending"] {
+                assert_eq!(
+                    handle.get_histogram_count_by_label(
+                        histogram,
+                        [("method", method_name), ("tag", tag)]
+                    ),
+                    7,
+                    "histogram: {histogram}, method: {method_name}, tag: {tag}"
+                );
+            }
         }
     }
 
@@ -9,6 +9,16 @@ use pathfinder_common::BlockId;
 const METRIC_REQUESTS: &str = "gateway_requests_total";
 const METRIC_FAILED_REQUESTS: &str = "gateway_requests_failed_total";
 const METRICS: [&str; 2] = [METRIC_REQUESTS, METRIC_FAILED_REQUESTS];
+/// Duration of each request attempt, including the download and parsing of the response.
+pub const METRIC_REQUEST_DURATION: &str = "sequencer_request_duration_seconds";
+/// Buckets of [METRIC_REQUEST_DURATION], in seconds.
+pub const REQUEST_DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+/// Counts the retries of failed requests, see [retry0](crate::builder).
+pub(crate) const METRIC_RETRIED_REQUESTS: &str = "gateway_requests_retried_total";
+/// Count the lookups of the persistent response cache, see [ResponseStore](crate::ResponseStore).
+pub(crate) const METRIC_CACHE_HITS: &str = "gateway_cache_hits_total";
+pub(crate) const METRIC_CACHE_MISSES: &str = "gateway_cache_misses_total";
+const CACHED_METHODS: [&str; 2] = ["get_block", "get_transaction"];
 const TAG_LATEST: &str = "latest";
 const TAG_PENDING: &str = "pending";
 const TAGS: &[&str] = &[TAG_LATEST, TAG_PENDING];
@@ -19,6 +29,8 @@ const REASONS: [&str; 3] = [REASON_DECODE, REASON_RATE_LIMITING, REASON_STARKNET
 
 /// Register all sequencer related metrics
 pub fn register() {
+    metrics::register_gauge!(crate::availability::METRIC_UNAVAILABLE);
+
     let methods_with_tags = ["get_block", "get_state_update"].into_iter();
 
     // Requests and failed requests
@@ -36,6 +48,21 @@ pub fn register() {
         })
     });
 
+    Request::<'_, Method>::METHODS.iter().for_each(|&method| {
+        metrics::register_counter!(METRIC_RETRIED_REQUESTS, "method" => method);
+        metrics::register_histogram!(METRIC_REQUEST_DURATION, "method" => method);
+    });
+    methods_with_tags.clone().for_each(|method| {
+        TAGS.iter().for_each(|&tag| {
+            metrics::register_histogram!(METRIC_REQUEST_DURATION, "method" => method, "tag" => tag);
+        })
+    });
+
+    CACHED_METHODS.iter().for_each(|&method| {
+        metrics::register_counter!(METRIC_CACHE_HITS, "method" => method);
+        metrics::register_counter!(METRIC_CACHE_MISSES, "method" => method);
+    });
+
     // Failed requests for specific failure reasons
     REASONS.iter().for_each(|&reason| {
         // For all methods
@@ -104,9 +131,12 @@ impl RequestMetadata {
 /// - `gateway_requests_total`,
 /// - `gateway_requests_failed_total` if the future returns the `Err()` variant.
 ///
+/// The time taken by `f` is recorded in the `sequencer_request_duration_seconds` histogram,
+/// whether it succeeds or not.
+///
 /// # Additional counter labels
 ///
-/// 1. All the above counters are also duplicated for the special cases of:
+/// 1. All the above counters and the histogram are also duplicated for the special cases of:
 /// `("get_block" | "get_state_update") AND ("latest" | "pending")`.
 ///
 /// 2. `gateway_requests_failed_total` is also duplicated for the specific failure reasons:
@@ -141,9 +171,24 @@ pub async fn with_metrics<T>(
         }
     }
 
+    /// Records the duration of a request and its block tag specific variant if it exists
+    fn record_duration(meta: RequestMetadata, duration: std::time::Duration) {
+        let method = meta.method;
+        let seconds = duration.as_secs_f64();
+        metrics::histogram!(METRIC_REQUEST_DURATION, seconds, "method" => method);
+
+        if let ("get_block" | "get_state_update", Some(tag)) = (method, meta.tag.as_str()) {
+            metrics::histogram!(METRIC_REQUEST_DURATION, seconds, "method" => method, "tag" => tag);
+        }
+    }
+
     increment(METRIC_REQUESTS, meta);
 
-    f.await.map_err(|e| {
+    let started_at = std::time::Instant::now();
+    let result = f.await;
+    record_duration(meta, started_at.elapsed());
+
+    result.map_err(|e| {
         increment(METRIC_FAILED_REQUESTS, meta);
 
         match &e {
@@ -156,14 +201,10 @@ pub async fn with_metrics<T>(
             SequencerError::ReqwestError(e) if e.is_decode() => {
                 increment_failed(meta, REASON_DECODE);
             }
-            SequencerError::ReqwestError(e)
-                if e.is_status()
-                    && e.status().expect("error kind should be status")
-                        == reqwest::StatusCode::TOO_MANY_REQUESTS =>
-            {
+            SequencerError::ReqwestError(_) if e.is_rate_limited() => {
                 increment_failed(meta, REASON_RATE_LIMITING);
             }
-            SequencerError::ReqwestError(_) => {}
+            SequencerError::ReqwestError(_) | SequencerError::QuotaExceeded => {}
         }
 
         e
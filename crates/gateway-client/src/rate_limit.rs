@@ -0,0 +1,56 @@
+//! Tracks how often the gateway rate limits requests.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use starknet_gateway_types::error::SequencerError;
+
+/// Counts the requests which the gateway rejected with `429 Too Many Requests`, i.e. the failures
+/// which the `gateway_requests_failed_total` metric attributes to `rate_limiting`.
+///
+/// Each attempt of a retried request is counted, so that callers which poll the gateway can tell
+/// whether they were rate limited since their previous poll even though the request eventually
+/// succeeded.
+///
+/// Cheap to clone, with all clones sharing the same count.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimit(Arc<AtomicU64>);
+
+impl RateLimit {
+    /// The number of rate limited requests so far.
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record(&self, error: &SequencerError) {
+        if error.is_rate_limited() {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::Filter;
+
+    #[tokio::test]
+    async fn counts_only_rate_limiting() {
+        let any = warp::any()
+            .map(|| warp::reply::with_status("", warp::http::StatusCode::TOO_MANY_REQUESTS));
+        let (addr, server) = warp::serve(any).bind_ephemeral(([127, 0, 0, 1], 0));
+        let _jh = tokio::spawn(server);
+
+        let rejected = reqwest::get(format!("http://{addr}/"))
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap_err();
+
+        let rate_limit = RateLimit::default();
+        let clone = rate_limit.clone();
+
+        rate_limit.record(&SequencerError::from(rejected));
+        rate_limit.record(&SequencerError::InvalidStarknetErrorVariant);
+        assert_eq!(clone.count(), 1);
+    }
+}
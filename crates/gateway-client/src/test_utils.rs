@@ -0,0 +1,468 @@
+//! An in-process mock of the feeder gateway, serving a scripted chain.
+//!
+//! This allows running the sync pipeline against a real [Client] in tests, including reorgs
+//! and failing requests, without setting up the expected requests one by one.
+//!
+//! ```ignore
+//! let mut chain = ScriptedChain::default();
+//! chain.push_empty_blocks(3);
+//! let gateway = MockGateway::spawn(chain);
+//!
+//! // ... start syncing from `gateway.client()` ...
+//!
+//! gateway.update(|chain| {
+//!     chain.reorg(StarknetBlockNumber::new_or_panic(1));
+//!     chain.push_empty_blocks(2);
+//!     chain.inject_error("get_block", InjectedError::Status(503));
+//! });
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use pathfinder_common::{
+    ClassHash, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
+    StarknetBlockTimestamp, StateCommitment,
+};
+use stark_hash::Felt;
+use starknet_gateway_types::error::{StarknetError, StarknetErrorCode};
+use starknet_gateway_types::reply::{state_update::StateDiff, Block, StateUpdate, Status};
+
+use crate::Client;
+
+/// The chain served by a [MockGateway].
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedChain {
+    blocks: Vec<(Block, StateUpdate)>,
+    classes: HashMap<ClassHash, bytes::Bytes>,
+    compiled_classes: HashMap<ClassHash, bytes::Bytes>,
+    traces: HashMap<StarknetBlockHash, serde_json::Value>,
+    /// Errors returned instead of the next responses, keyed by feeder gateway method.
+    errors: HashMap<String, VecDeque<InjectedError>>,
+    /// Incremented on each reorg, so that replacement blocks get new hashes.
+    fork: u64,
+}
+
+/// An error returned by the [MockGateway] instead of a response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InjectedError {
+    /// A response with this HTTP status code and no body.
+    Status(u16),
+    /// A StarkNet error, which the gateway reports with a 500 status code.
+    Starknet(StarknetErrorCode),
+    /// The regular response, sent after a delay. Exceeding the client's timeout results in a
+    /// timeout error.
+    Delay(Duration),
+    /// The regular response, with only the first half of its body.
+    Truncated,
+}
+
+impl ScriptedChain {
+    /// The latest block of the chain.
+    pub fn head(&self) -> Option<&Block> {
+        self.blocks.last().map(|(block, _)| block)
+    }
+
+    pub fn block(&self, number: StarknetBlockNumber) -> Option<&Block> {
+        self.blocks
+            .get(number.get() as usize)
+            .map(|(block, _)| block)
+    }
+
+    /// Appends a block and its state update to the chain.
+    ///
+    /// Panics if the block does not follow the current head.
+    pub fn push_block(&mut self, block: Block, state_update: StateUpdate) {
+        let expected = self
+            .head()
+            .map(|head| (head.block_number + 1, head.block_hash))
+            .unwrap_or((StarknetBlockNumber::GENESIS, StarknetBlockHash(Felt::ZERO)));
+        assert_eq!(
+            (block.block_number, block.parent_block_hash),
+            expected,
+            "Block does not follow the head of the chain"
+        );
+        assert_eq!(
+            block.block_hash, state_update.block_hash,
+            "State update is for a different block"
+        );
+
+        self.blocks.push((block, state_update));
+    }
+
+    /// Appends `count` blocks without transactions or state changes.
+    ///
+    /// Their hashes are not valid, so these blocks are only accepted by sync with
+    /// `BlockValidationMode::AllowMismatch`.
+    pub fn push_empty_blocks(&mut self, count: usize) {
+        for _ in 0..count {
+            let (number, parent_hash) = self
+                .head()
+                .map(|head| (head.block_number + 1, head.block_hash))
+                .unwrap_or((StarknetBlockNumber::GENESIS, StarknetBlockHash(Felt::ZERO)));
+
+            let block = Block {
+                block_hash: self.block_hash(number),
+                block_number: number,
+                gas_price: Some(GasPrice::ZERO),
+                parent_block_hash: parent_hash,
+                sequencer_address: Some(SequencerAddress(Felt::ZERO)),
+                // The state commitment of an empty state.
+                state_commitment: StateCommitment::ZERO,
+                status: Status::AcceptedOnL2,
+                timestamp: StarknetBlockTimestamp::new_or_panic(number.get()),
+                transaction_receipts: vec![],
+                transactions: vec![],
+                starknet_version: Some("0.11.0".to_owned()),
+            };
+            let state_update = StateUpdate {
+                block_hash: block.block_hash,
+                new_root: StateCommitment::ZERO,
+                old_root: StateCommitment::ZERO,
+                state_diff: StateDiff {
+                    storage_diffs: Default::default(),
+                    deployed_contracts: vec![],
+                    old_declared_contracts: vec![],
+                    declared_classes: vec![],
+                    nonces: Default::default(),
+                    replaced_classes: vec![],
+                },
+            };
+
+            self.blocks.push((block, state_update));
+        }
+    }
+
+    /// Removes all blocks after `new_head`. Empty blocks pushed afterwards get different
+    /// hashes than the removed ones.
+    pub fn reorg(&mut self, new_head: StarknetBlockNumber) {
+        self.blocks.truncate(new_head.get() as usize + 1);
+        self.fork += 1;
+    }
+
+    /// Serves the definition of a class for `get_class_by_hash`.
+    pub fn add_class(&mut self, class_hash: ClassHash, definition: bytes::Bytes) {
+        self.classes.insert(class_hash, definition);
+    }
+
+    /// Serves the definition of a compiled class for `get_compiled_class_by_class_hash`.
+    pub fn add_compiled_class(&mut self, class_hash: ClassHash, definition: bytes::Bytes) {
+        self.compiled_classes.insert(class_hash, definition);
+    }
+
+    /// Serves the `traces` of a block for `get_block_traces`.
+    pub fn add_block_traces(&mut self, block_hash: StarknetBlockHash, traces: serde_json::Value) {
+        self.traces.insert(block_hash, traces);
+    }
+
+    /// Fails the next request to the feeder gateway `method`, such as `get_block`.
+    ///
+    /// Multiple errors for the same method are returned in order.
+    pub fn inject_error(&mut self, method: &str, error: InjectedError) {
+        self.errors
+            .entry(method.to_owned())
+            .or_default()
+            .push_back(error);
+    }
+
+    /// Removes all injected errors which have not been returned yet.
+    pub fn clear_errors(&mut self) {
+        self.errors.clear();
+    }
+
+    fn block_hash(&self, number: StarknetBlockNumber) -> StarknetBlockHash {
+        let mut bytes = [0u8; 32];
+        bytes[16..24].copy_from_slice(&(self.fork + 1).to_be_bytes());
+        bytes[24..].copy_from_slice(&number.get().to_be_bytes());
+        StarknetBlockHash(Felt::from_be_bytes(bytes).expect("Hash is below the modulus"))
+    }
+
+    /// Looks up the block given by the `blockNumber` or `blockHash` query parameter.
+    ///
+    /// Like the gateway, `pending` returns the latest block while there is no pending block.
+    fn find(&self, params: &HashMap<String, String>) -> Option<&(Block, StateUpdate)> {
+        match (params.get("blockNumber"), params.get("blockHash")) {
+            (Some(tag), None) if tag == "latest" || tag == "pending" => self.blocks.last(),
+            (Some(number), None) => {
+                let number = number.parse::<usize>().ok()?;
+                self.blocks.get(number)
+            }
+            (None, Some(hash)) => {
+                let hash = StarknetBlockHash(Felt::from_hex_str(hash).ok()?);
+                self.blocks
+                    .iter()
+                    .find(|(block, _)| block.block_hash == hash)
+            }
+            _ => None,
+        }
+    }
+
+    /// Responds to a request, returning the delay before the response should be sent.
+    fn respond(
+        &mut self,
+        method: &str,
+        params: &HashMap<String, String>,
+    ) -> (Duration, http::Response<String>) {
+        match self.errors.get_mut(method).and_then(VecDeque::pop_front) {
+            Some(InjectedError::Status(status)) => (Duration::ZERO, status_response(status)),
+            Some(InjectedError::Starknet(code)) => (Duration::ZERO, starknet_error_response(code)),
+            Some(InjectedError::Delay(delay)) => (delay, self.serve(method, params)),
+            Some(InjectedError::Truncated) => {
+                let mut response = self.serve(method, params);
+                let body = response.body_mut();
+                let mut len = body.len() / 2;
+                while !body.is_char_boundary(len) {
+                    len -= 1;
+                }
+                body.truncate(len);
+                (Duration::ZERO, response)
+            }
+            None => (Duration::ZERO, self.serve(method, params)),
+        }
+    }
+
+    fn serve(&self, method: &str, params: &HashMap<String, String>) -> http::Response<String> {
+        let class_hash = || {
+            params
+                .get("classHash")
+                .and_then(|hash| Felt::from_hex_str(hash).ok())
+                .map(ClassHash)
+        };
+
+        let body = match method {
+            "get_block" => self.find(params).map(|(block, _)| to_json(block)),
+            "get_state_update" => self
+                .find(params)
+                .map(|(_, state_update)| to_json(state_update)),
+            "get_class_by_hash" => class_hash()
+                .and_then(|hash| self.classes.get(&hash))
+                .map(|definition| String::from_utf8_lossy(definition).into_owned()),
+            "get_compiled_class_by_class_hash" => class_hash()
+                .and_then(|hash| self.compiled_classes.get(&hash))
+                .map(|definition| String::from_utf8_lossy(definition).into_owned()),
+            "get_block_traces" => params
+                .get("blockHash")
+                .and_then(|hash| Felt::from_hex_str(hash).ok())
+                .and_then(|hash| self.traces.get(&StarknetBlockHash(hash)))
+                .map(|traces| to_json(&serde_json::json!({ "traces": traces }))),
+            _ => return status_response(404),
+        };
+
+        match body {
+            Some(body) => http::Response::new(body),
+            None if method.contains("class") => {
+                starknet_error_response(StarknetErrorCode::UndeclaredClass)
+            }
+            None => starknet_error_response(StarknetErrorCode::BlockNotFound),
+        }
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("Replies are serializable")
+}
+
+fn status_response(status: u16) -> http::Response<String> {
+    http::Response::builder()
+        .status(status)
+        .body(String::new())
+        .expect("Response is valid")
+}
+
+fn starknet_error_response(code: StarknetErrorCode) -> http::Response<String> {
+    let error = StarknetError {
+        code,
+        message: "Injected by the mock gateway".to_owned(),
+    };
+
+    http::Response::builder()
+        .status(500)
+        .body(to_json(&error))
+        .expect("Response is valid")
+}
+
+/// A feeder gateway serving a [ScriptedChain], which can be changed while it is running.
+///
+/// The server is stopped once this is dropped.
+pub struct MockGateway {
+    chain: Arc<Mutex<ScriptedChain>>,
+    url: reqwest::Url,
+    server: tokio::task::JoinHandle<()>,
+}
+
+impl MockGateway {
+    /// Starts serving `chain` on a local port. Must be called from within a tokio runtime.
+    pub fn spawn(chain: ScriptedChain) -> Self {
+        use warp::Filter;
+
+        let chain = Arc::new(Mutex::new(chain));
+
+        let filter = {
+            let chain = chain.clone();
+            warp::path("feeder_gateway")
+                .and(warp::path::param::<String>())
+                .and(warp::path::end())
+                .and(warp::query::<HashMap<String, String>>())
+                .then(move |method: String, params: HashMap<String, String>| {
+                    let (delay, response) = chain.lock().unwrap().respond(&method, &params);
+                    async move {
+                        tokio::time::sleep(delay).await;
+                        response
+                    }
+                })
+        };
+
+        let (addr, server) = warp::serve(filter).bind_ephemeral(([127, 0, 0, 1], 0));
+        let server = tokio::spawn(server);
+        let url = reqwest::Url::parse(&format!("http://{addr}/")).expect("Valid URL");
+
+        Self { chain, url, server }
+    }
+
+    /// A client for this gateway, which does not retry failed requests.
+    pub fn client(&self) -> Client {
+        Client::with_base_url(self.url.clone())
+            .expect("Valid URL")
+            .disable_retry_for_tests()
+    }
+
+    pub fn url(&self) -> &reqwest::Url {
+        &self.url
+    }
+
+    /// Changes the served chain, for example to add new blocks or to reorg.
+    pub fn update<T>(&self, f: impl FnOnce(&mut ScriptedChain) -> T) -> T {
+        f(&mut self.chain.lock().unwrap())
+    }
+}
+
+impl Drop for MockGateway {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientApi;
+    use assert_matches::assert_matches;
+    use pathfinder_common::BlockId;
+    use starknet_gateway_types::error::SequencerError;
+    use starknet_gateway_types::reply::{MaybePendingBlock, MaybePendingStateUpdate};
+
+    #[tokio::test]
+    async fn serves_scripted_chain() {
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(3);
+        let expected = chain
+            .block(StarknetBlockNumber::new_or_panic(1))
+            .unwrap()
+            .clone();
+        let gateway = MockGateway::spawn(chain);
+        let client = gateway.client();
+
+        let block = client.block(expected.block_number.into()).await.unwrap();
+        assert_eq!(block, MaybePendingBlock::Block(expected.clone()));
+
+        let block = client.block(expected.block_hash.into()).await.unwrap();
+        assert_eq!(block, MaybePendingBlock::Block(expected.clone()));
+
+        let state_update = client
+            .state_update(expected.block_hash.into())
+            .await
+            .unwrap();
+        assert_matches!(
+            state_update,
+            MaybePendingStateUpdate::StateUpdate(s) if s.block_hash == expected.block_hash
+        );
+
+        let latest = client.block(BlockId::Latest).await.unwrap();
+        assert_eq!(latest.as_block().unwrap().block_number.get(), 2);
+
+        let error = client
+            .block(StarknetBlockNumber::new_or_panic(3).into())
+            .await
+            .unwrap_err();
+        assert_matches!(
+            error,
+            SequencerError::StarknetError(e) if e.code == StarknetErrorCode::BlockNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn reorg() {
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(3);
+        let gateway = MockGateway::spawn(chain);
+        let client = gateway.client();
+
+        let number = StarknetBlockNumber::new_or_panic(2);
+        let before = client
+            .block(number.into())
+            .await
+            .unwrap()
+            .as_block()
+            .unwrap();
+
+        let parent = gateway.update(|chain| {
+            chain.reorg(StarknetBlockNumber::new_or_panic(1));
+            chain.push_empty_blocks(2);
+            chain
+                .block(StarknetBlockNumber::new_or_panic(1))
+                .unwrap()
+                .block_hash
+        });
+
+        let after = client
+            .block(number.into())
+            .await
+            .unwrap()
+            .as_block()
+            .unwrap();
+        assert_ne!(before.block_hash, after.block_hash);
+        assert_eq!(after.parent_block_hash, parent);
+
+        let latest = client.block(BlockId::Latest).await.unwrap();
+        assert_eq!(latest.as_block().unwrap().block_number.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn injected_errors() {
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(1);
+        chain.inject_error("get_block", InjectedError::Status(503));
+        chain.inject_error(
+            "get_block",
+            InjectedError::Starknet(StarknetErrorCode::OutOfRangeBlockHash),
+        );
+        chain.inject_error("get_block", InjectedError::Truncated);
+        chain.inject_error(
+            "get_block",
+            InjectedError::Delay(Duration::from_millis(100)),
+        );
+        let gateway = MockGateway::spawn(chain);
+        let client = gateway.client();
+
+        let error = client.block(BlockId::Latest).await.unwrap_err();
+        assert_matches!(
+            error,
+            SequencerError::ReqwestError(e) if e.status() == Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        );
+
+        let error = client.block(BlockId::Latest).await.unwrap_err();
+        assert_matches!(
+            error,
+            SequencerError::StarknetError(e) if e.code == StarknetErrorCode::OutOfRangeBlockHash
+        );
+
+        let error = client.block(BlockId::Latest).await.unwrap_err();
+        assert_matches!(error, SequencerError::ReqwestError(e) if e.is_decode());
+
+        let start = std::time::Instant::now();
+        client.block(BlockId::Latest).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        client.block(BlockId::Latest).await.unwrap();
+    }
+}
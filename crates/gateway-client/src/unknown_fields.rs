@@ -0,0 +1,209 @@
+//! Reporting of unknown fields in gateway responses.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const METRIC_UNKNOWN_FIELDS: &str = "gateway_unknown_fields_total";
+
+/// Collects the fields in gateway responses which are unknown to our types, and which would
+/// otherwise be silently ignored while decoding.
+///
+/// Each unknown field increments the `gateway_unknown_fields_total` counter of the request method,
+/// and a summary of the fields is logged periodically. This gives early warning of additions to
+/// the feeder gateway's format, before they turn into decoding failures.
+///
+/// Fields within internally tagged or untagged enums are buffered by serde before decoding and can
+/// therefore not be reported.
+///
+/// Cheap to clone, with all clones sharing the same summary.
+#[derive(Clone, Debug)]
+pub struct UnknownFields(Arc<Mutex<Summary>>);
+
+#[derive(Debug, Default)]
+struct Summary {
+    /// Occurrences of each unknown field, keyed by request method and field path.
+    fields: HashMap<(&'static str, String), u64>,
+}
+
+impl UnknownFields {
+    const SUMMARY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    /// Must be called within a tokio runtime, which runs the task logging the summaries until
+    /// all clones are dropped.
+    pub fn new() -> Self {
+        let summary = Arc::new(Mutex::new(Summary::default()));
+        tokio::spawn(log_summaries(Arc::downgrade(&summary)));
+        Self(summary)
+    }
+
+    /// Decodes the JSON response of `method`, recording any unknown fields.
+    pub(crate) fn decode<T>(&self, method: &'static str, bytes: &[u8]) -> serde_json::Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let mut unknown = Vec::new();
+        let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+        let value =
+            serde_ignored::deserialize(&mut deserializer, |path| unknown.push(field_path(&path)))?;
+        deserializer.end()?;
+
+        self.record(method, unknown);
+
+        Ok(value)
+    }
+
+    fn record(&self, method: &'static str, unknown: Vec<String>) {
+        if unknown.is_empty() {
+            return;
+        }
+
+        metrics::counter!(METRIC_UNKNOWN_FIELDS, unknown.len() as u64, "method" => method);
+
+        let mut summary = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        for field in unknown {
+            *summary.fields.entry((method, field)).or_default() += 1;
+        }
+    }
+}
+
+/// Logs the summary of every [interval](UnknownFields::SUMMARY_INTERVAL) in which unknown fields
+/// were recorded.
+async fn log_summaries(summary: std::sync::Weak<Mutex<Summary>>) {
+    let mut interval = tokio::time::interval(UnknownFields::SUMMARY_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let fields = match summary.upgrade() {
+            Some(summary) => summary.lock().unwrap_or_else(|e| e.into_inner()).take(),
+            None => return,
+        };
+        if let Some(fields) = fields {
+            tracing::warn!(
+                %fields,
+                "Gateway responses contained unknown fields, the gateway format may have changed"
+            );
+        }
+    }
+}
+
+impl Default for UnknownFields {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Summary {
+    /// Returns the formatted summary and starts a new one, unless no unknown fields were
+    /// recorded.
+    fn take(&mut self) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+
+        let mut fields = std::mem::take(&mut self.fields)
+            .into_iter()
+            .map(|((method, field), count)| format!("{method}: {field} ({count}x)"))
+            .collect::<Vec<_>>();
+        fields.sort();
+
+        Some(fields.join(", "))
+    }
+}
+
+/// Formats the path of an unknown field, without sequence indices so that the same field is
+/// only counted once across all items.
+fn field_path(path: &serde_ignored::Path<'_>) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, .. } => format!("{}[]", field_path(parent)),
+        Path::Map { parent, key } => match field_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{parent}.{key}"),
+        },
+        Path::Some { parent }
+        | Path::NewtypeStruct { parent }
+        | Path::NewtypeVariant { parent } => field_path(parent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Reply {
+        number: u64,
+        items: Vec<Item>,
+        nested: Option<Item>,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Item {
+        value: u64,
+    }
+
+    #[tokio::test]
+    async fn collects_unknown_fields() {
+        let unknown_fields = UnknownFields::new();
+        let json = br#"{
+            "number": 1,
+            "new_field": "a",
+            "items": [{ "value": 2, "extra": 3 }, { "value": 4, "extra": 5 }],
+            "nested": { "value": 6, "extra": {} }
+        }"#;
+
+        let reply = unknown_fields.decode::<Reply>("get_block", json).unwrap();
+        assert_eq!(
+            reply,
+            Reply {
+                number: 1,
+                items: vec![Item { value: 2 }, Item { value: 4 }],
+                nested: Some(Item { value: 6 }),
+            }
+        );
+
+        let mut summary = unknown_fields.0.lock().unwrap();
+        assert_eq!(
+            summary.take().unwrap(),
+            "get_block: items[].extra (2x), get_block: nested.extra (1x), get_block: new_field (1x)"
+        );
+        // A new summary is started.
+        assert_eq!(summary.take(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn logs_summary_periodically() {
+        let unknown_fields = UnknownFields::new();
+        // Lets the summary task start its interval.
+        tokio::task::yield_now().await;
+
+        for _ in 0..2 {
+            unknown_fields
+                .decode::<Item>("get_block", br#"{ "value": 1, "extra": 2 }"#)
+                .unwrap();
+            assert!(!unknown_fields.0.lock().unwrap().fields.is_empty());
+
+            tokio::time::sleep(UnknownFields::SUMMARY_INTERVAL).await;
+            tokio::task::yield_now().await;
+            assert!(unknown_fields.0.lock().unwrap().fields.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn decoding_is_unchanged() {
+        let unknown_fields = UnknownFields::new();
+
+        unknown_fields
+            .decode::<Reply>("get_block", br#"{ "number": 1 }"#)
+            .unwrap_err();
+        unknown_fields
+            .decode::<Reply>("get_block", br#"{ "number": 1, "items": [] } trailing"#)
+            .unwrap_err();
+    }
+}
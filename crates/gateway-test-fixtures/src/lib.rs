@@ -1,3 +1,7 @@
+//! Sequencer responses for use in tests.
+//!
+//! New fixtures can be downloaded using the `capture_fixtures` example of the `pathfinder` crate.
+
 macro_rules! str_fixture {
     ($file_name:literal) => {
         include_str!(concat!("../fixtures/", $file_name))
@@ -119,6 +123,9 @@ pub mod zstd_compressed_contracts {
     // modified: "abi" has been converted to a string and debug info is removed
     pub const CAIRO_1_0_0_ALPHA5_SIERRA: &[u8] =
         bytes_fixture!("contracts/sierra-1.0.0.alpha5-starknet-format.json.zst");
+    // CAIRO_1_0_0_ALPHA5_SIERRA compiled to CASM
+    pub const CAIRO_1_0_0_ALPHA5_CASM: &[u8] =
+        bytes_fixture!("contracts/sierra-1.0.0.alpha5-starknet-format-compiled-casm.json.zst");
     // https://external.integration.starknet.io/feeder_gateway/get_class_by_hash?classHash=0x4d7d2ddf396736d7cdba26e178e30e3388d488984a94e03bc4af4841e222920
     pub const CAIRO_1_0_0_ALPHA6_SIERRA: &[u8] =
         bytes_fixture!("contracts/sierra-1.0.0.alpha6.json.zst");
@@ -1,6 +1,6 @@
 use crate::request::contract::EntryPointType;
 use anyhow::{Context, Error, Result};
-use pathfinder_common::{felt_bytes, ClassHash};
+use pathfinder_common::{felt_bytes, CasmHash, ClassHash};
 use serde::Serialize;
 use sha3::Digest;
 use stark_hash::{Felt, HashChain};
@@ -362,6 +362,59 @@ fn compute_sierra_class_hash(
     Ok(ClassHash(hash.finish().into()))
 }
 
+/// Computes the compiled class hash of a CASM class definition JSON blob, as produced by the
+/// Sierra compiler.
+///
+/// This is the hash declared for a Sierra class, so it can be used to verify CASM definitions
+/// which were not compiled locally. See the [cairo-lang implementation][compiled_class].
+///
+/// [compiled_class]: https://github.com/starkware-libs/cairo-lang/blob/v0.11.0.2/src/starkware/starknet/core/os/contract_class/compiled_class.cairo
+pub fn compute_casm_class_hash(casm_definition: &[u8]) -> Result<CasmHash> {
+    use EntryPointType::*;
+
+    let casm_definition =
+        serde_json::from_slice::<json::CasmContractDefinition<'_>>(casm_definition)
+            .context("Failed to parse CASM definition")?;
+
+    let mut hash = PoseidonHasher::default();
+
+    const COMPILED_CLASS_VERSION: Felt = felt_bytes!(b"COMPILED_CLASS_V1");
+    hash.write(COMPILED_CLASS_VERSION.into());
+
+    // As for Sierra classes, the entry point lists are hashed in this order. Each entry point
+    // is flattened to (selector, offset, hash of its builtin names).
+    for key in [External, L1Handler, Constructor] {
+        let mut entry_points_hash = PoseidonHasher::default();
+
+        for entry_point in casm_definition
+            .entry_points_by_type
+            .get(&key)
+            .unwrap_or(&Vec::new())
+        {
+            let mut builtins_hash = PoseidonHasher::default();
+            for builtin in &entry_point.builtins {
+                let builtin = Felt::from_be_slice(builtin.as_bytes())
+                    .with_context(|| format!("Builtin name {builtin} is too long"))?;
+                builtins_hash.write(builtin.into());
+            }
+
+            entry_points_hash.write(entry_point.selector.into());
+            entry_points_hash.write(Felt::from(entry_point.offset).into());
+            entry_points_hash.write(builtins_hash.finish());
+        }
+
+        hash.write(entry_points_hash.finish());
+    }
+
+    let mut bytecode_hash = PoseidonHasher::default();
+    for felt in &casm_definition.bytecode {
+        bytecode_hash.write((*felt).into());
+    }
+    hash.write(bytecode_hash.finish());
+
+    Ok(CasmHash(hash.finish().into()))
+}
+
 /// See:
 /// <https://github.com/starkware-libs/cairo-lang/blob/64a7f6aed9757d3d8d6c28bd972df73272b0cb0a/src/starkware/starknet/public/abi.py#L21-L26>
 pub(crate) fn truncated_keccak(mut plain: [u8; 32]) -> Felt {
@@ -454,6 +507,25 @@ mod json {
         pub entry_points_by_type: HashMap<EntryPointType, Vec<SelectorAndFunctionIndex>>,
     }
 
+    /// The parts of a CASM class definition which its compiled class hash commits to.
+    #[derive(serde::Deserialize)]
+    pub struct CasmContractDefinition<'a> {
+        pub bytecode: Vec<stark_hash::Felt>,
+
+        #[serde(borrow)]
+        pub entry_points_by_type: HashMap<EntryPointType, Vec<CasmEntryPoint<'a>>>,
+    }
+
+    #[derive(serde::Deserialize)]
+    pub struct CasmEntryPoint<'a> {
+        pub selector: stark_hash::Felt,
+
+        pub offset: u64,
+
+        #[serde(borrow)]
+        pub builtins: Vec<Cow<'a, str>>,
+    }
+
     /// Our version of the cairo contract definition used to deserialize and re-serialize a
     /// modified version for a hash of the contract definition.
     ///
@@ -730,4 +802,30 @@ mod tests {
             felt!("0x1c584056064687e149968cbab758a3376d22aedc6a55823d1b3ecbee81b8fb9")
         );
     }
+
+    #[test]
+    fn casm_class_hash_commits_to_bytecode_and_entry_points() {
+        use super::compute_casm_class_hash;
+        use starknet_gateway_test_fixtures::zstd_compressed_contracts::CAIRO_1_0_0_ALPHA5_CASM;
+
+        let casm_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_CASM).unwrap();
+        let hash = compute_casm_class_hash(&casm_definition).unwrap();
+
+        // Hints and other metadata are not part of the hash.
+        let mut casm: serde_json::Value = serde_json::from_slice(&casm_definition).unwrap();
+        casm["hints"] = serde_json::json!([]);
+        casm["compiler_version"] = serde_json::json!("other");
+        let stripped = serde_json::to_vec(&casm).unwrap();
+        assert_eq!(compute_casm_class_hash(&stripped).unwrap(), hash);
+
+        let mut tampered = casm.clone();
+        tampered["bytecode"][0] = serde_json::json!("0x1");
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert_ne!(compute_casm_class_hash(&tampered).unwrap(), hash);
+
+        let mut tampered = casm;
+        tampered["entry_points_by_type"]["EXTERNAL"][0]["offset"] = serde_json::json!(1_000_000);
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert_ne!(compute_casm_class_hash(&tampered).unwrap(), hash);
+    }
 }
@@ -14,6 +14,21 @@ pub enum SequencerError {
     /// not informative enough or bloated
     #[error("error decoding response body: invalid error variant")]
     InvalidStarknetErrorVariant,
+    /// The request was not sent, because the monthly bandwidth quota has been used.
+    #[error("monthly bandwidth quota has been used")]
+    QuotaExceeded,
+}
+
+impl SequencerError {
+    /// Whether the gateway rejected the request with `429 Too Many Requests`.
+    pub fn is_rate_limited(&self) -> bool {
+        match self {
+            SequencerError::ReqwestError(e) => {
+                e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Used for deserializing specific Starknet sequencer error data.
@@ -80,4 +95,14 @@ pub enum StarknetErrorCode {
     InvalidCompiledClassHash,
     #[serde(rename = "StarknetErrorCode.COMPILATION_FAILED")]
     CompilationFailed,
+    #[serde(rename = "StarknetErrorCode.CLASS_ALREADY_DECLARED")]
+    ClassAlreadyDeclared,
+    #[serde(rename = "StarknetErrorCode.DUPLICATED_TRANSACTION")]
+    DuplicatedTransaction,
+    #[serde(rename = "StarknetErrorCode.INSUFFICIENT_MAX_FEE")]
+    InsufficientMaxFee,
+    #[serde(rename = "StarknetErrorCode.INSUFFICIENT_ACCOUNT_BALANCE")]
+    InsufficientAccountBalance,
+    #[serde(rename = "StarknetErrorCode.VALIDATE_FAILURE")]
+    ValidateFailure,
 }
@@ -3,3 +3,6 @@ pub mod error;
 pub mod pending;
 pub mod reply;
 pub mod request;
+pub mod sync_state;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
@@ -1,30 +1,192 @@
 use crate::reply::{PendingBlock, PendingStateUpdate};
 use pathfinder_common::{StarknetBlockHash, StarknetBlockTimestamp};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 struct PendingInner {
     pub block: Arc<PendingBlock>,
     pub state_update: Arc<PendingStateUpdate>,
+    pub version: PendingStateVersion,
+    /// The [PendingData::generation] the pending data was set in.
+    pub generation: u64,
 }
 
+impl PendingInner {
+    fn new(
+        block: Arc<PendingBlock>,
+        state_update: Arc<PendingStateUpdate>,
+        generation: u64,
+    ) -> Self {
+        let version = PendingStateVersion::of(&block);
+        Self {
+            block,
+            state_update,
+            version,
+            generation,
+        }
+    }
+}
+
+/// Identifies the contents of the pending block, so that clients can detect whether it changed
+/// between their queries.
+///
+/// The version is derived from the pending block's parent and transactions, so polling the same
+/// pending block again does not change it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingStateVersion(pub u64);
+
+impl PendingStateVersion {
+    pub fn of(block: &PendingBlock) -> Self {
+        let mut hasher = DefaultHasher::new();
+        block.parent_hash.0.hash(&mut hasher);
+        for transaction in &block.transactions {
+            transaction.hash().0.hash(&mut hasher);
+        }
+        Self(hasher.finish())
+    }
+}
+
+impl std::fmt::Display for PendingStateVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:016x}", self.0)
+    }
+}
+
+impl std::str::FromStr for PendingStateVersion {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        u64::from_str_radix(hex, 16).map(Self)
+    }
+}
+
+impl serde::Serialize for PendingStateVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for PendingStateVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The pending data served by the RPC API.
+///
+/// [PendingData] serves the pending data as sync polled it from the gateway. Other sources build
+/// on it, such as one which replaces the gateway's receipts with locally executed ones.
+#[async_trait::async_trait]
+pub trait PendingSource: Send + Sync {
+    /// See [PendingData::generation].
+    fn generation(&self) -> u64;
+
+    /// The pending block, if it was set in `generation`.
+    async fn block_at(&self, generation: u64) -> Option<Arc<PendingBlock>>;
+
+    async fn block(&self) -> Option<Arc<PendingBlock>>;
+
+    /// The pending block along with its [PendingStateVersion].
+    async fn block_with_version(&self) -> Option<(Arc<PendingBlock>, PendingStateVersion)>;
+
+    async fn version(&self) -> Option<PendingStateVersion>;
+
+    async fn state_update(&self) -> Option<Arc<PendingStateUpdate>>;
+
+    /// The pending state update along with the hash and timestamp of the pending block's parent,
+    /// which the state update applies to.
+    async fn state_update_on_parent_block(
+        &self,
+    ) -> Option<(
+        StarknetBlockHash,
+        StarknetBlockTimestamp,
+        Arc<PendingStateUpdate>,
+    )>;
+}
+
+/// The pending data which sync shares with the RPC API.
+///
+/// Every head block applied by sync starts a new generation, see [PendingData::advance_head].
+/// Pending data only belongs to the generation it was set in, so that it is never served on top
+/// of a newer head than the one it was built on:
+///
+/// - sync records the generation before it starts preparing pending data, and
+///   [PendingData::set_if_current] discards the data if a new head was applied meanwhile;
+/// - the RPC API records the generation before it reads the latest block from the database, and
+///   [PendingData::block_at] only returns pending data of that generation.
+///
+/// RPC state reads which fall back to the database do not rely on the generation. Instead they
+/// read the pending block's parent, see [PendingData::state_update_on_parent_block], which is
+/// consistent with the pending data regardless of the head sync applied since.
+///
+/// Cheap to clone, with all clones sharing the same data and generation.
 #[derive(Default, Clone)]
 pub struct PendingData {
     inner: Arc<RwLock<Option<PendingInner>>>,
+    /// Only advanced while holding the write lock of `inner`, so that the pending data and the
+    /// generation always change together.
+    generation: Arc<AtomicU64>,
 }
 
 impl PendingData {
     pub async fn set(&self, block: Arc<PendingBlock>, state_update: Arc<PendingStateUpdate>) {
-        *self.inner.write().await = Some(PendingInner {
-            block,
-            state_update,
-        });
+        let mut inner = self.inner.write().await;
+        let generation = self.generation.load(Ordering::Acquire);
+        *inner = Some(PendingInner::new(block, state_update, generation));
+    }
+
+    /// Sets the pending data unless a new head was applied since `generation`, in which case the
+    /// data is stale and discarded. Returns whether the data was set.
+    pub async fn set_if_current(
+        &self,
+        generation: u64,
+        block: Arc<PendingBlock>,
+        state_update: Arc<PendingStateUpdate>,
+    ) -> bool {
+        let mut inner = self.inner.write().await;
+        if self.generation.load(Ordering::Acquire) != generation {
+            return false;
+        }
+        *inner = Some(PendingInner::new(block, state_update, generation));
+        true
     }
 
     pub async fn clear(&self) {
         *self.inner.write().await = None;
     }
 
+    /// Clears the pending data and starts a new generation, once sync has applied a new head
+    /// block or a reorg.
+    ///
+    /// Sync also [clears](PendingData::clear) the pending data before it writes the new head, so
+    /// that there is no pending data while the database already has the new head but the
+    /// generation has not been advanced yet.
+    pub async fn advance_head(&self) {
+        let mut inner = self.inner.write().await;
+        *inner = None;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// The current generation, which is advanced by [PendingData::advance_head].
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// The pending block, if it was set in `generation`.
+    pub async fn block_at(&self, generation: u64) -> Option<Arc<PendingBlock>> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .filter(|inner| inner.generation == generation)
+            .map(|inner| inner.block.clone())
+    }
+
     pub async fn block(&self) -> Option<Arc<PendingBlock>> {
         self.inner
             .read()
@@ -33,6 +195,19 @@ impl PendingData {
             .map(|inner| inner.block.clone())
     }
 
+    /// The pending block along with its [PendingStateVersion].
+    pub async fn block_with_version(&self) -> Option<(Arc<PendingBlock>, PendingStateVersion)> {
+        self.inner
+            .read()
+            .await
+            .as_ref()
+            .map(|inner| (inner.block.clone(), inner.version))
+    }
+
+    pub async fn version(&self) -> Option<PendingStateVersion> {
+        self.inner.read().await.as_ref().map(|inner| inner.version)
+    }
+
     pub async fn state_update(&self) -> Option<Arc<PendingStateUpdate>> {
         self.inner
             .read()
@@ -58,3 +233,121 @@ impl PendingData {
         ))
     }
 }
+
+#[async_trait::async_trait]
+impl PendingSource for PendingData {
+    fn generation(&self) -> u64 {
+        PendingData::generation(self)
+    }
+
+    async fn block_at(&self, generation: u64) -> Option<Arc<PendingBlock>> {
+        PendingData::block_at(self, generation).await
+    }
+
+    async fn block(&self) -> Option<Arc<PendingBlock>> {
+        PendingData::block(self).await
+    }
+
+    async fn block_with_version(&self) -> Option<(Arc<PendingBlock>, PendingStateVersion)> {
+        PendingData::block_with_version(self).await
+    }
+
+    async fn version(&self) -> Option<PendingStateVersion> {
+        PendingData::version(self).await
+    }
+
+    async fn state_update(&self) -> Option<Arc<PendingStateUpdate>> {
+        PendingData::state_update(self).await
+    }
+
+    async fn state_update_on_parent_block(
+        &self,
+    ) -> Option<(
+        StarknetBlockHash,
+        StarknetBlockTimestamp,
+        Arc<PendingStateUpdate>,
+    )> {
+        PendingData::state_update_on_parent_block(self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_serde() {
+        let version = PendingStateVersion(0x1234);
+        let json = serde_json::to_value(version).unwrap();
+        assert_eq!(json, serde_json::json!("0x0000000000001234"));
+        assert_eq!(
+            serde_json::from_value::<PendingStateVersion>(json).unwrap(),
+            version
+        );
+        serde_json::from_value::<PendingStateVersion>(serde_json::json!("0xnope")).unwrap_err();
+    }
+
+    fn pending() -> (Arc<PendingBlock>, Arc<PendingStateUpdate>) {
+        use crate::reply::{state_update::StateDiff, Status};
+        use pathfinder_common::{GasPrice, SequencerAddress, StateCommitment};
+
+        let block = PendingBlock {
+            gas_price: GasPrice(1),
+            parent_hash: StarknetBlockHash(stark_hash::Felt::from(1u64)),
+            sequencer_address: SequencerAddress(stark_hash::Felt::ZERO),
+            status: Status::Pending,
+            timestamp: StarknetBlockTimestamp::new_or_panic(0),
+            transaction_receipts: vec![],
+            transactions: vec![],
+            starknet_version: None,
+        };
+        let state_update = PendingStateUpdate {
+            old_root: StateCommitment(stark_hash::Felt::ZERO),
+            state_diff: StateDiff {
+                storage_diffs: Default::default(),
+                deployed_contracts: vec![],
+                old_declared_contracts: vec![],
+                declared_classes: vec![],
+                nonces: Default::default(),
+                replaced_classes: vec![],
+            },
+        };
+
+        (Arc::new(block), Arc::new(state_update))
+    }
+
+    #[tokio::test]
+    async fn new_head_invalidates_pending_data() {
+        let pending_data = PendingData::default();
+        let (block, state_update) = pending();
+
+        let before = pending_data.generation();
+        assert!(
+            pending_data
+                .set_if_current(before, block.clone(), state_update.clone())
+                .await
+        );
+        assert_eq!(pending_data.block_at(before).await, Some(block.clone()));
+
+        pending_data.advance_head().await;
+        let after = pending_data.generation();
+        assert_ne!(after, before);
+        assert_eq!(pending_data.block().await, None);
+
+        // Pending data prepared on top of the previous head is discarded.
+        assert!(
+            !pending_data
+                .set_if_current(before, block.clone(), state_update.clone())
+                .await
+        );
+        assert_eq!(pending_data.block().await, None);
+
+        assert!(
+            pending_data
+                .set_if_current(after, block.clone(), state_update)
+                .await
+        );
+        assert_eq!(pending_data.block_at(before).await, None);
+        assert_eq!(pending_data.block_at(after).await, Some(block));
+    }
+}
@@ -52,13 +52,173 @@ pub struct PendingBlock {
     pub starknet_version: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(untagged)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MaybePendingBlock {
     Block(Block),
     Pending(PendingBlock),
 }
 
+impl<'de> Deserialize<'de> for MaybePendingBlock {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        const BLOCK_FIELDS: &[&str] = &[
+            "block_hash",
+            "block_number",
+            "gas_price",
+            "parent_block_hash",
+            "sequencer_address",
+            "state_commitment",
+            "status",
+            "timestamp",
+            "transaction_receipts",
+            "transactions",
+            "starknet_version",
+        ];
+
+        let block = AnyBlock::deserialize(deserializer)?;
+        let parent_block_hash = block
+            .parent_block_hash
+            .ok_or_else(|| D::Error::missing_field("parent_block_hash"))?;
+        let status = block
+            .status
+            .ok_or_else(|| D::Error::missing_field("status"))?;
+        let timestamp = block
+            .timestamp
+            .ok_or_else(|| D::Error::missing_field("timestamp"))?;
+        let transaction_receipts = block
+            .transaction_receipts
+            .ok_or_else(|| D::Error::missing_field("transaction_receipts"))?;
+        let transactions = block
+            .transactions
+            .ok_or_else(|| D::Error::missing_field("transactions"))?;
+
+        let block = match block.block_hash {
+            Some(block_hash) => {
+                if let Some(field) = block.unknown {
+                    return Err(D::Error::unknown_field(&field, BLOCK_FIELDS));
+                }
+                Self::Block(Block {
+                    block_hash,
+                    block_number: block
+                        .block_number
+                        .ok_or_else(|| D::Error::missing_field("block_number"))?,
+                    gas_price: block.gas_price,
+                    parent_block_hash,
+                    sequencer_address: block.sequencer_address,
+                    state_commitment: block
+                        .state_commitment
+                        .ok_or_else(|| D::Error::missing_field("state_commitment"))?,
+                    status,
+                    timestamp,
+                    transaction_receipts,
+                    transactions,
+                    starknet_version: block.starknet_version,
+                })
+            }
+            None => Self::Pending(PendingBlock {
+                gas_price: block
+                    .gas_price
+                    .ok_or_else(|| D::Error::missing_field("gas_price"))?,
+                parent_hash: parent_block_hash,
+                sequencer_address: block
+                    .sequencer_address
+                    .ok_or_else(|| D::Error::missing_field("sequencer_address"))?,
+                status,
+                timestamp,
+                transaction_receipts,
+                transactions,
+                starknet_version: block.starknet_version,
+            }),
+        };
+
+        Ok(block)
+    }
+}
+
+/// The fields of both [Block] and [PendingBlock], which are told apart by the presence of
+/// `block_hash`.
+///
+/// Unlike an untagged enum, this decodes the block in a single pass instead of buffering the
+/// whole response and then attempting each variant in turn. Unknown fields are skipped, and are
+/// then rejected for a [Block] but ignored for a [PendingBlock], as by their own decoding.
+#[derive(Default)]
+struct AnyBlock {
+    block_hash: Option<StarknetBlockHash>,
+    block_number: Option<StarknetBlockNumber>,
+    gas_price: Option<GasPrice>,
+    parent_block_hash: Option<StarknetBlockHash>,
+    sequencer_address: Option<SequencerAddress>,
+    state_commitment: Option<StateCommitment>,
+    status: Option<Status>,
+    timestamp: Option<StarknetBlockTimestamp>,
+    transaction_receipts: Option<Vec<transaction::Receipt>>,
+    transactions: Option<Vec<transaction::Transaction>>,
+    starknet_version: Option<String>,
+    /// The first unknown field.
+    unknown: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for AnyBlock {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::{IgnoredAny, MapAccess, Visitor};
+        use serde_with::de::DeserializeAsWrap;
+
+        struct AnyBlockVisitor;
+
+        impl<'de> Visitor<'de> for AnyBlockVisitor {
+            type Value = AnyBlock;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a block")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<AnyBlock, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut block = AnyBlock::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "block_hash" => block.block_hash = map.next_value()?,
+                        "block_number" => block.block_number = map.next_value()?,
+                        "gas_price" => {
+                            block.gas_price = map
+                                .next_value::<DeserializeAsWrap<_, Option<GasPriceAsHexStr>>>()?
+                                .into_inner()
+                        }
+                        "parent_block_hash" => block.parent_block_hash = map.next_value()?,
+                        "sequencer_address" => block.sequencer_address = map.next_value()?,
+                        // Historical blocks (pre v0.11) still use `state_root`.
+                        "state_commitment" | "state_root" => {
+                            block.state_commitment = map.next_value()?
+                        }
+                        "status" => block.status = map.next_value()?,
+                        "timestamp" => block.timestamp = map.next_value()?,
+                        "transaction_receipts" => block.transaction_receipts = map.next_value()?,
+                        "transactions" => block.transactions = map.next_value()?,
+                        "starknet_version" => block.starknet_version = map.next_value()?,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                            block.unknown.get_or_insert(key);
+                        }
+                    }
+                }
+
+                Ok(block)
+            }
+        }
+
+        deserializer.deserialize_map(AnyBlockVisitor)
+    }
+}
+
 impl From<Block> for MaybePendingBlock {
     fn from(block: Block) -> Self {
         MaybePendingBlock::Block(block)
@@ -156,7 +316,7 @@ pub mod call {
 
 /// Used to deserialize replies to StarkNet transaction requests.
 #[serde_as]
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, serde::Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Transaction {
     #[serde(default)]
@@ -186,16 +346,13 @@ pub struct TransactionStatus {
 pub mod transaction {
     use pathfinder_common::{
         CallParam, CasmHash, ClassHash, ConstructorParam, ContractAddress, ContractAddressSalt,
-        EntryPoint, EthereumAddress, EventData, EventKey, Fee, L1ToL2MessageNonce,
-        L1ToL2MessagePayloadElem, L2ToL1MessagePayloadElem, StarknetTransactionHash,
-        StarknetTransactionIndex, TransactionNonce, TransactionSignatureElem, TransactionVersion,
-    };
-    use pathfinder_serde::{
-        CallParamAsDecimalStr, ConstructorParamAsDecimalStr, EthereumAddressAsHexStr,
-        EventDataAsDecimalStr, EventKeyAsDecimalStr, L1ToL2MessagePayloadElemAsDecimalStr,
-        L2ToL1MessagePayloadElemAsDecimalStr, TransactionSignatureElemAsDecimalStr,
-        TransactionVersionAsHexStr,
+        EntryPoint, EthereumAddress, EventData, EventKey, Fee, L1ToL2MessageHash,
+        L1ToL2MessageNonce, L1ToL2MessagePayloadElem, L2ToL1MessagePayloadElem,
+        StarknetTransactionHash, StarknetTransactionIndex, TransactionNonce,
+        TransactionSignatureElem, TransactionVersion,
     };
+    use pathfinder_serde::policy::{Gateway, Numeric};
+    use pathfinder_serde::{EthereumAddressAsHexStr, TransactionVersionAsHexStr};
     use serde::{Deserialize, Serialize};
     use serde_with::serde_as;
 
@@ -253,7 +410,7 @@ pub mod transaction {
     pub struct L1ToL2Message {
         #[serde_as(as = "EthereumAddressAsHexStr")]
         pub from_address: EthereumAddress,
-        #[serde_as(as = "Vec<L1ToL2MessagePayloadElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub payload: Vec<L1ToL2MessagePayloadElem>,
         pub selector: EntryPoint,
         pub to_address: ContractAddress,
@@ -261,13 +418,39 @@ pub mod transaction {
         pub nonce: Option<L1ToL2MessageNonce>,
     }
 
+    impl L1ToL2Message {
+        /// Calculates the hash of the message the way the StarkNet core contract on L1 does.
+        ///
+        /// Messages sent before L1 to L2 messages had a nonce are hashed without one.
+        pub fn hash(&self) -> L1ToL2MessageHash {
+            use sha3::{Digest, Keccak256};
+
+            let mut hasher = Keccak256::new();
+            hasher.update([0u8; 12]);
+            hasher.update(self.from_address.0.as_bytes());
+            hasher.update(self.to_address.get().as_be_bytes());
+            if let Some(nonce) = self.nonce {
+                hasher.update(nonce.0.as_be_bytes());
+            }
+            hasher.update(self.selector.0.as_be_bytes());
+            hasher.update(ethers::types::H256::from_low_u64_be(
+                self.payload.len() as u64
+            ));
+            for element in &self.payload {
+                hasher.update(element.0.as_be_bytes());
+            }
+
+            L1ToL2MessageHash(ethers::types::H256(<[u8; 32]>::from(hasher.finalize())))
+        }
+    }
+
     /// Represents deserialized L2 to L1 message.
     #[serde_as]
     #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct L2ToL1Message {
         pub from_address: ContractAddress,
-        #[serde_as(as = "Vec<L2ToL1MessagePayloadElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub payload: Vec<L2ToL1MessagePayloadElem>,
         #[serde_as(as = "EthereumAddressAsHexStr")]
         pub to_address: EthereumAddress,
@@ -286,6 +469,29 @@ pub mod transaction {
         pub l2_to_l1_messages: Vec<L2ToL1Message>,
         pub transaction_hash: StarknetTransactionHash,
         pub transaction_index: StarknetTransactionIndex,
+        /// Excluded by the gateway prior to StarkNet 0.12.1, where every transaction succeeded.
+        #[serde(default, skip_serializing_if = "ExecutionStatus::is_succeeded")]
+        pub execution_status: ExecutionStatus,
+        /// The reason a [reverted](ExecutionStatus::Reverted) transaction failed.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub revert_error: Option<String>,
+    }
+
+    /// Whether the execution of a transaction succeeded. The changes of a reverted transaction
+    /// are discarded, but it is still included in its block and charged a fee.
+    #[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+    pub enum ExecutionStatus {
+        #[default]
+        #[serde(rename = "SUCCEEDED")]
+        Succeeded,
+        #[serde(rename = "REVERTED")]
+        Reverted,
+    }
+
+    impl ExecutionStatus {
+        pub fn is_succeeded(&self) -> bool {
+            matches!(self, Self::Succeeded)
+        }
     }
 
     /// Represents deserialized L2 transaction event data.
@@ -293,17 +499,16 @@ pub mod transaction {
     #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct Event {
-        #[serde_as(as = "Vec<EventDataAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub data: Vec<EventData>,
         pub from_address: ContractAddress,
-        #[serde_as(as = "Vec<EventKeyAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub keys: Vec<EventKey>,
     }
 
     /// Represents deserialized L2 transaction data.
-    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+    #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
     #[serde(tag = "type")]
-    #[serde(deny_unknown_fields)]
     pub enum Transaction {
         #[serde(rename = "DECLARE")]
         Declare(DeclareTransaction),
@@ -371,35 +576,9 @@ pub mod transaction {
         where
             D: serde::Deserializer<'de>,
         {
-            use ethers::types::H256;
-            use serde::de;
-
-            #[serde_as]
-            #[derive(Deserialize)]
-            struct Version {
-                #[serde_as(as = "TransactionVersionAsHexStr")]
-                #[serde(default = "transaction_version_zero")]
-                pub version: TransactionVersion,
-            }
-
-            let mut v = serde_json::Value::deserialize(deserializer)?;
-            let version = Version::deserialize(&v).map_err(de::Error::custom)?;
-            // remove "version", since v0 and v1 transactions use deny_unknown_fields
-            v.as_object_mut()
-                .expect("must be an object because deserializing version succeeded")
-                .remove("version");
-            match version.version {
-                TransactionVersion(x) if x == H256::from_low_u64_be(0) => Ok(Self::V0(
-                    DeclareTransactionV0V1::deserialize(&v).map_err(de::Error::custom)?,
-                )),
-                TransactionVersion(x) if x == H256::from_low_u64_be(1) => Ok(Self::V1(
-                    DeclareTransactionV0V1::deserialize(&v).map_err(de::Error::custom)?,
-                )),
-                TransactionVersion(x) if x == H256::from_low_u64_be(2) => Ok(Self::V2(
-                    DeclareTransactionV2::deserialize(&v).map_err(de::Error::custom)?,
-                )),
-                _v => Err(de::Error::custom("version must be 0, 1 or 2")),
-            }
+            AnyTransaction::deserialize(deserializer)?
+                .untagged(DECLARE_V0V1_FIELDS)?
+                .into_declare()
         }
     }
 
@@ -412,7 +591,7 @@ pub mod transaction {
         pub max_fee: Fee,
         pub nonce: TransactionNonce,
         pub sender_address: ContractAddress,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         #[serde(default)]
         pub signature: Vec<TransactionSignatureElem>,
         pub transaction_hash: StarknetTransactionHash,
@@ -427,7 +606,7 @@ pub mod transaction {
         pub max_fee: Fee,
         pub nonce: TransactionNonce,
         pub sender_address: ContractAddress,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         #[serde(default)]
         pub signature: Vec<TransactionSignatureElem>,
         pub transaction_hash: StarknetTransactionHash,
@@ -446,7 +625,7 @@ pub mod transaction {
         pub contract_address: ContractAddress,
         pub contract_address_salt: ContractAddressSalt,
         pub class_hash: ClassHash,
-        #[serde_as(as = "Vec<ConstructorParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub constructor_calldata: Vec<ConstructorParam>,
         pub transaction_hash: StarknetTransactionHash,
         #[serde_as(as = "TransactionVersionAsHexStr")]
@@ -464,11 +643,11 @@ pub mod transaction {
         pub max_fee: Fee,
         #[serde_as(as = "TransactionVersionAsHexStr")]
         pub version: TransactionVersion,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
         pub nonce: TransactionNonce,
         pub contract_address_salt: ContractAddressSalt,
-        #[serde_as(as = "Vec<CallParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub constructor_calldata: Vec<CallParam>,
         pub class_hash: ClassHash,
     }
@@ -487,32 +666,9 @@ pub mod transaction {
         where
             D: serde::Deserializer<'de>,
         {
-            use ethers::types::H256;
-            use serde::de;
-
-            #[serde_as]
-            #[derive(Deserialize)]
-            struct Version {
-                #[serde_as(as = "TransactionVersionAsHexStr")]
-                #[serde(default = "transaction_version_zero")]
-                pub version: TransactionVersion,
-            }
-
-            let mut v = serde_json::Value::deserialize(deserializer)?;
-            let version = Version::deserialize(&v).map_err(de::Error::custom)?;
-            // remove "version", since v0 and v1 transactions use deny_unknown_fields
-            v.as_object_mut()
-                .expect("must be an object because deserializing version succeeded")
-                .remove("version");
-            match version.version {
-                TransactionVersion(x) if x == H256::from_low_u64_be(0) => Ok(Self::V0(
-                    InvokeTransactionV0::deserialize(&v).map_err(de::Error::custom)?,
-                )),
-                TransactionVersion(x) if x == H256::from_low_u64_be(1) => Ok(Self::V1(
-                    InvokeTransactionV1::deserialize(&v).map_err(de::Error::custom)?,
-                )),
-                _v => Err(de::Error::custom("version must be 0 or 1")),
-            }
+            AnyTransaction::deserialize(deserializer)?
+                .untagged(INVOKE_V1_FIELDS)?
+                .into_invoke()
         }
     }
 
@@ -530,7 +686,7 @@ pub mod transaction {
     #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct InvokeTransactionV0 {
-        #[serde_as(as = "Vec<CallParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub calldata: Vec<CallParam>,
         // contract_address is the historic name for this field. sender_address was
         // introduced with starknet v0.11. Although the gateway no longer uses the historic
@@ -542,7 +698,7 @@ pub mod transaction {
         #[serde(skip_serializing_if = "Option::is_none")]
         pub entry_point_type: Option<EntryPointType>,
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
         pub transaction_hash: StarknetTransactionHash,
     }
@@ -552,7 +708,7 @@ pub mod transaction {
     #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct InvokeTransactionV1 {
-        #[serde_as(as = "Vec<CallParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub calldata: Vec<CallParam>,
         // contract_address is the historic name for this field. sender_address was
         // introduced with starknet v0.11. Although the gateway no longer uses the historic
@@ -561,7 +717,7 @@ pub mod transaction {
         #[serde(alias = "contract_address")]
         pub sender_address: ContractAddress,
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
         pub nonce: TransactionNonce,
         pub transaction_hash: StarknetTransactionHash,
@@ -587,6 +743,356 @@ pub mod transaction {
         TransactionNonce(stark_hash::Felt::ZERO)
     }
 
+    impl<'de> Deserialize<'de> for Transaction {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            AnyTransaction::deserialize(deserializer)?.into_transaction()
+        }
+    }
+
+    const DECLARE_V0V1_FIELDS: &[&str] = &[
+        "version",
+        "class_hash",
+        "max_fee",
+        "nonce",
+        "sender_address",
+        "signature",
+        "transaction_hash",
+    ];
+    const DECLARE_V2_FIELDS: &[&str] = &[
+        "version",
+        "class_hash",
+        "max_fee",
+        "nonce",
+        "sender_address",
+        "signature",
+        "transaction_hash",
+        "compiled_class_hash",
+    ];
+    const DEPLOY_FIELDS: &[&str] = &[
+        "contract_address",
+        "contract_address_salt",
+        "class_hash",
+        "constructor_calldata",
+        "transaction_hash",
+        "version",
+    ];
+    const DEPLOY_ACCOUNT_FIELDS: &[&str] = &[
+        "contract_address",
+        "transaction_hash",
+        "max_fee",
+        "version",
+        "signature",
+        "nonce",
+        "contract_address_salt",
+        "constructor_calldata",
+        "class_hash",
+    ];
+    const INVOKE_V0_FIELDS: &[&str] = &[
+        "version",
+        "calldata",
+        "sender_address",
+        "contract_address",
+        "entry_point_selector",
+        "entry_point_type",
+        "max_fee",
+        "signature",
+        "transaction_hash",
+    ];
+    const INVOKE_V1_FIELDS: &[&str] = &[
+        "version",
+        "calldata",
+        "sender_address",
+        "contract_address",
+        "max_fee",
+        "signature",
+        "nonce",
+        "transaction_hash",
+    ];
+    const L1_HANDLER_FIELDS: &[&str] = &[
+        "contract_address",
+        "entry_point_selector",
+        "nonce",
+        "calldata",
+        "transaction_hash",
+        "version",
+    ];
+
+    /// The `type` of a [Transaction].
+    #[derive(Copy, Clone, Deserialize)]
+    enum TransactionType {
+        #[serde(rename = "DECLARE")]
+        Declare,
+        #[serde(rename = "DEPLOY")]
+        Deploy,
+        #[serde(rename = "DEPLOY_ACCOUNT")]
+        DeployAccount,
+        #[serde(rename = "INVOKE_FUNCTION")]
+        Invoke,
+        #[serde(rename = "L1_HANDLER")]
+        L1Handler,
+    }
+
+    /// The fields of all transaction types and versions.
+    ///
+    /// Serde decodes an internally tagged enum by buffering each transaction until it has found
+    /// its `type`, and versions could only be dispatched on by buffering as well. Instead, the
+    /// transaction is decoded in a single pass into this struct, and then converted into its type
+    /// and version. The conversion rejects the fields which do not belong to them.
+    ///
+    /// All calldata is decoded as [Numeric<Gateway>], which accepts the hex encoded calldata of
+    /// L1 handlers as well.
+    #[serde_as]
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct AnyTransaction {
+        #[serde(default, rename = "type")]
+        transaction_type: Option<TransactionType>,
+        #[serde_as(as = "Option<TransactionVersionAsHexStr>")]
+        #[serde(default)]
+        version: Option<TransactionVersion>,
+        transaction_hash: StarknetTransactionHash,
+        #[serde_as(as = "Option<Vec<Numeric<Gateway>>>")]
+        #[serde(default)]
+        calldata: Option<Vec<CallParam>>,
+        #[serde(default)]
+        class_hash: Option<ClassHash>,
+        #[serde(default)]
+        compiled_class_hash: Option<CasmHash>,
+        #[serde_as(as = "Option<Vec<Numeric<Gateway>>>")]
+        #[serde(default)]
+        constructor_calldata: Option<Vec<CallParam>>,
+        #[serde(default)]
+        contract_address: Option<ContractAddress>,
+        #[serde(default)]
+        contract_address_salt: Option<ContractAddressSalt>,
+        #[serde(default)]
+        entry_point_selector: Option<EntryPoint>,
+        #[serde(default)]
+        entry_point_type: Option<EntryPointType>,
+        #[serde(default)]
+        max_fee: Option<Fee>,
+        #[serde(default)]
+        nonce: Option<TransactionNonce>,
+        #[serde(default)]
+        sender_address: Option<ContractAddress>,
+        #[serde_as(as = "Option<Vec<Numeric<Gateway>>>")]
+        #[serde(default)]
+        signature: Option<Vec<TransactionSignatureElem>>,
+    }
+
+    impl AnyTransaction {
+        /// Rejects the `type` field, for transactions which are decoded by their type.
+        fn untagged<E: serde::de::Error>(self, fields: &'static [&'static str]) -> Result<Self, E> {
+            match self.transaction_type {
+                Some(_) => Err(E::unknown_field("type", fields)),
+                None => Ok(self),
+            }
+        }
+
+        /// Rejects the fields which are set but are not among the `fields` of the transaction's
+        /// type and version.
+        fn only<E: serde::de::Error>(&self, fields: &'static [&'static str]) -> Result<(), E> {
+            let present = [
+                ("calldata", self.calldata.is_some()),
+                ("class_hash", self.class_hash.is_some()),
+                ("compiled_class_hash", self.compiled_class_hash.is_some()),
+                ("constructor_calldata", self.constructor_calldata.is_some()),
+                ("contract_address", self.contract_address.is_some()),
+                (
+                    "contract_address_salt",
+                    self.contract_address_salt.is_some(),
+                ),
+                ("entry_point_selector", self.entry_point_selector.is_some()),
+                ("entry_point_type", self.entry_point_type.is_some()),
+                ("max_fee", self.max_fee.is_some()),
+                ("nonce", self.nonce.is_some()),
+                ("sender_address", self.sender_address.is_some()),
+                ("signature", self.signature.is_some()),
+            ];
+            match present
+                .into_iter()
+                .find(|(field, set)| *set && !fields.contains(field))
+            {
+                Some((field, _)) => Err(E::unknown_field(field, fields)),
+                None => Ok(()),
+            }
+        }
+
+        fn version_or_zero(&self) -> TransactionVersion {
+            self.version.unwrap_or_else(transaction_version_zero)
+        }
+
+        fn into_transaction<E: serde::de::Error>(self) -> Result<Transaction, E> {
+            match self
+                .transaction_type
+                .ok_or_else(|| E::missing_field("type"))?
+            {
+                TransactionType::Declare => self.into_declare().map(Transaction::Declare),
+                TransactionType::Deploy => {
+                    self.only(DEPLOY_FIELDS)?;
+                    Ok(Transaction::Deploy(DeployTransaction {
+                        version: self.version_or_zero(),
+                        contract_address: self
+                            .contract_address
+                            .ok_or_else(|| E::missing_field("contract_address"))?,
+                        contract_address_salt: self
+                            .contract_address_salt
+                            .ok_or_else(|| E::missing_field("contract_address_salt"))?,
+                        class_hash: self
+                            .class_hash
+                            .ok_or_else(|| E::missing_field("class_hash"))?,
+                        constructor_calldata: self
+                            .constructor_calldata
+                            .ok_or_else(|| E::missing_field("constructor_calldata"))?
+                            .into_iter()
+                            .map(|param| ConstructorParam(param.0))
+                            .collect(),
+                        transaction_hash: self.transaction_hash,
+                    }))
+                }
+                TransactionType::DeployAccount => {
+                    self.only(DEPLOY_ACCOUNT_FIELDS)?;
+                    Ok(Transaction::DeployAccount(DeployAccountTransaction {
+                        contract_address: self
+                            .contract_address
+                            .ok_or_else(|| E::missing_field("contract_address"))?,
+                        transaction_hash: self.transaction_hash,
+                        max_fee: self.max_fee.ok_or_else(|| E::missing_field("max_fee"))?,
+                        version: self.version.ok_or_else(|| E::missing_field("version"))?,
+                        signature: self
+                            .signature
+                            .ok_or_else(|| E::missing_field("signature"))?,
+                        nonce: self.nonce.ok_or_else(|| E::missing_field("nonce"))?,
+                        contract_address_salt: self
+                            .contract_address_salt
+                            .ok_or_else(|| E::missing_field("contract_address_salt"))?,
+                        constructor_calldata: self
+                            .constructor_calldata
+                            .ok_or_else(|| E::missing_field("constructor_calldata"))?,
+                        class_hash: self
+                            .class_hash
+                            .ok_or_else(|| E::missing_field("class_hash"))?,
+                    }))
+                }
+                TransactionType::Invoke => self.into_invoke().map(Transaction::Invoke),
+                TransactionType::L1Handler => {
+                    self.only(L1_HANDLER_FIELDS)?;
+                    Ok(Transaction::L1Handler(L1HandlerTransaction {
+                        contract_address: self
+                            .contract_address
+                            .ok_or_else(|| E::missing_field("contract_address"))?,
+                        entry_point_selector: self
+                            .entry_point_selector
+                            .ok_or_else(|| E::missing_field("entry_point_selector"))?,
+                        nonce: self.nonce.unwrap_or_else(l1_handler_default_nonce),
+                        calldata: self.calldata.ok_or_else(|| E::missing_field("calldata"))?,
+                        transaction_hash: self.transaction_hash,
+                        version: self.version.ok_or_else(|| E::missing_field("version"))?,
+                    }))
+                }
+            }
+        }
+
+        fn into_declare<E: serde::de::Error>(self) -> Result<DeclareTransaction, E> {
+            use ethers::types::H256;
+
+            let version = self.version_or_zero();
+            let fields = match version {
+                TransactionVersion(x) if x == H256::from_low_u64_be(2) => DECLARE_V2_FIELDS,
+                _ => DECLARE_V0V1_FIELDS,
+            };
+            self.only(fields)?;
+
+            let v0v1 = |tx: Self| -> Result<_, E> {
+                Ok(DeclareTransactionV0V1 {
+                    class_hash: tx
+                        .class_hash
+                        .ok_or_else(|| E::missing_field("class_hash"))?,
+                    max_fee: tx.max_fee.ok_or_else(|| E::missing_field("max_fee"))?,
+                    nonce: tx.nonce.ok_or_else(|| E::missing_field("nonce"))?,
+                    sender_address: tx
+                        .sender_address
+                        .ok_or_else(|| E::missing_field("sender_address"))?,
+                    signature: tx.signature.unwrap_or_default(),
+                    transaction_hash: tx.transaction_hash,
+                })
+            };
+            match version {
+                TransactionVersion(x) if x == H256::from_low_u64_be(0) => {
+                    Ok(DeclareTransaction::V0(v0v1(self)?))
+                }
+                TransactionVersion(x) if x == H256::from_low_u64_be(1) => {
+                    Ok(DeclareTransaction::V1(v0v1(self)?))
+                }
+                TransactionVersion(x) if x == H256::from_low_u64_be(2) => {
+                    Ok(DeclareTransaction::V2(DeclareTransactionV2 {
+                        class_hash: self
+                            .class_hash
+                            .ok_or_else(|| E::missing_field("class_hash"))?,
+                        max_fee: self.max_fee.ok_or_else(|| E::missing_field("max_fee"))?,
+                        nonce: self.nonce.ok_or_else(|| E::missing_field("nonce"))?,
+                        sender_address: self
+                            .sender_address
+                            .ok_or_else(|| E::missing_field("sender_address"))?,
+                        signature: self.signature.unwrap_or_default(),
+                        transaction_hash: self.transaction_hash,
+                        compiled_class_hash: self
+                            .compiled_class_hash
+                            .ok_or_else(|| E::missing_field("compiled_class_hash"))?,
+                    }))
+                }
+                _v => Err(E::custom("version must be 0, 1 or 2")),
+            }
+        }
+
+        fn into_invoke<E: serde::de::Error>(self) -> Result<InvokeTransaction, E> {
+            use ethers::types::H256;
+
+            // contract_address is the historic name of sender_address, see InvokeTransactionV0.
+            let sender_address = match (self.sender_address, self.contract_address) {
+                (Some(address), None) | (None, Some(address)) => address,
+                (None, None) => return Err(E::missing_field("sender_address")),
+                (Some(_), Some(_)) => return Err(E::duplicate_field("sender_address")),
+            };
+
+            match self.version_or_zero() {
+                TransactionVersion(x) if x == H256::from_low_u64_be(0) => {
+                    self.only(INVOKE_V0_FIELDS)?;
+                    Ok(InvokeTransaction::V0(InvokeTransactionV0 {
+                        calldata: self.calldata.ok_or_else(|| E::missing_field("calldata"))?,
+                        sender_address,
+                        entry_point_selector: self
+                            .entry_point_selector
+                            .ok_or_else(|| E::missing_field("entry_point_selector"))?,
+                        entry_point_type: self.entry_point_type,
+                        max_fee: self.max_fee.ok_or_else(|| E::missing_field("max_fee"))?,
+                        signature: self
+                            .signature
+                            .ok_or_else(|| E::missing_field("signature"))?,
+                        transaction_hash: self.transaction_hash,
+                    }))
+                }
+                TransactionVersion(x) if x == H256::from_low_u64_be(1) => {
+                    self.only(INVOKE_V1_FIELDS)?;
+                    Ok(InvokeTransaction::V1(InvokeTransactionV1 {
+                        calldata: self.calldata.ok_or_else(|| E::missing_field("calldata"))?,
+                        sender_address,
+                        max_fee: self.max_fee.ok_or_else(|| E::missing_field("max_fee"))?,
+                        signature: self
+                            .signature
+                            .ok_or_else(|| E::missing_field("signature"))?,
+                        nonce: self.nonce.ok_or_else(|| E::missing_field("nonce"))?,
+                        transaction_hash: self.transaction_hash,
+                    }))
+                }
+                _v => Err(E::custom("version must be 0 or 1")),
+            }
+        }
+    }
+
     impl From<DeclareTransaction> for Transaction {
         fn from(tx: DeclareTransaction) -> Self {
             Self::Declare(tx)
@@ -624,7 +1130,7 @@ pub mod transaction {
     }
 
     /// Describes L2 transaction failure details.
-    #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+    #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
     #[serde(deny_unknown_fields)]
     pub struct Failure {
         pub code: String,
@@ -632,8 +1138,7 @@ pub mod transaction {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
-#[serde(untagged)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum MaybePendingStateUpdate {
     /// Always has a `block_hash` and a `new_root`
     StateUpdate(StateUpdate),
@@ -641,8 +1146,48 @@ pub enum MaybePendingStateUpdate {
     Pending(PendingStateUpdate),
 }
 
+impl<'de> Deserialize<'de> for MaybePendingStateUpdate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        /// The fields of both [StateUpdate] and [PendingStateUpdate], which are told apart by the
+        /// presence of `block_hash`, so that the state diff is decoded in a single pass.
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct AnyStateUpdate {
+            #[serde(default)]
+            block_hash: Option<StarknetBlockHash>,
+            #[serde(default)]
+            new_root: Option<StateCommitment>,
+            old_root: StateCommitment,
+            state_diff: state_update::StateDiff,
+        }
+
+        let update = AnyStateUpdate::deserialize(deserializer)?;
+        let update = match update.block_hash {
+            Some(block_hash) => Self::StateUpdate(StateUpdate {
+                block_hash,
+                new_root: update
+                    .new_root
+                    .ok_or_else(|| D::Error::missing_field("new_root"))?,
+                old_root: update.old_root,
+                state_diff: update.state_diff,
+            }),
+            None => Self::Pending(PendingStateUpdate {
+                old_root: update.old_root,
+                state_diff: update.state_diff,
+            }),
+        };
+
+        Ok(update)
+    }
+}
+
 /// Used to deserialize replies to StarkNet state update requests except for the pending one.
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, serde::Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct StateUpdate {
     pub block_hash: StarknetBlockHash,
@@ -688,13 +1233,13 @@ pub mod state_update {
         CasmHash, ClassHash, ContractAddress, ContractNonce, SierraHash, StorageAddress,
         StorageValue,
     };
-    use serde::Deserialize;
+    use serde::{Deserialize, Serialize};
     use serde_with::serde_as;
     use std::collections::HashMap;
 
     /// L2 state diff.
     #[serde_as]
-    #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct StateDiff {
         #[serde_as(as = "HashMap<_, Vec<_>>")]
@@ -707,7 +1252,7 @@ pub mod state_update {
     }
 
     /// L2 storage diff.
-    #[derive(Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
     #[serde(deny_unknown_fields)]
     pub struct StorageDiff {
         pub key: StorageAddress,
@@ -715,7 +1260,7 @@ pub mod state_update {
     }
 
     /// L2 contract data within state diff.
-    #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
     #[serde(deny_unknown_fields)]
     pub struct DeployedContract {
         pub address: ContractAddress,
@@ -726,7 +1271,7 @@ pub mod state_update {
     }
 
     /// Describes a newly declared class. Maps Sierra class hash to a Casm hash.
-    #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
     #[serde(deny_unknown_fields)]
     pub struct DeclaredSierraClass {
         pub class_hash: SierraHash,
@@ -734,7 +1279,7 @@ pub mod state_update {
     }
 
     /// Describes a newly replaced class. Maps contract address to a new class.
-    #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+    #[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
     #[serde(deny_unknown_fields)]
     pub struct ReplacedClass {
         pub address: ContractAddress,
@@ -904,4 +1449,148 @@ mod tests {
             serde_json::from_str::<Transaction>(v0_8_2::transaction::INVOKE).unwrap();
         }
     }
+
+    #[test]
+    fn l1_to_l2_message_hash() {
+        use super::Block;
+        use ethers::types::H256;
+        use pathfinder_common::L1ToL2MessageHash;
+
+        let block = serde_json::from_str::<Block>(
+            starknet_gateway_test_fixtures::integration::block::NUMBER_216171,
+        )
+        .unwrap();
+        let message = block
+            .transaction_receipts
+            .iter()
+            .find_map(|receipt| receipt.l1_to_l2_consumed_message.as_ref())
+            .unwrap();
+
+        let expected: H256 = "0x983362b305278e5d9be23bfae7108c6523131877c628adba0f2c32be5a9676ab"
+            .parse()
+            .unwrap();
+        assert_eq!(message.hash(), L1ToL2MessageHash(expected));
+    }
+
+    #[test]
+    fn pending_is_told_apart_by_block_hash() {
+        use super::{Block, MaybePendingBlock, MaybePendingStateUpdate, StateUpdate};
+        use starknet_gateway_test_fixtures::{v0_11_0, v0_9_0};
+
+        let block = serde_json::from_str::<MaybePendingBlock>(v0_9_0::block::NUMBER_231579);
+        let expected = serde_json::from_str::<Block>(v0_9_0::block::NUMBER_231579).unwrap();
+        assert_eq!(block.unwrap(), MaybePendingBlock::Block(expected));
+        let pending = serde_json::from_str::<MaybePendingBlock>(v0_9_0::block::PENDING);
+        assert!(matches!(pending.unwrap(), MaybePendingBlock::Pending(_)));
+
+        let update =
+            serde_json::from_str::<MaybePendingStateUpdate>(v0_11_0::state_update::NUMBER_315700);
+        let expected =
+            serde_json::from_str::<StateUpdate>(v0_11_0::state_update::NUMBER_315700).unwrap();
+        assert_eq!(
+            update.unwrap(),
+            MaybePendingStateUpdate::StateUpdate(expected)
+        );
+        let pending =
+            serde_json::from_str::<MaybePendingStateUpdate>(v0_11_0::state_update::PENDING);
+        assert!(matches!(
+            pending.unwrap(),
+            MaybePendingStateUpdate::Pending(_)
+        ));
+    }
+
+    #[test]
+    fn invoke_version_fields() {
+        use super::transaction::{InvokeTransaction, InvokeTransactionV1};
+        use pathfinder_common::{
+            felt, ContractAddress, Fee, StarknetTransactionHash, TransactionNonce,
+        };
+
+        let v1 = serde_json::json!({
+            "version": "0x1",
+            "calldata": ["1"],
+            "sender_address": "0x2",
+            "max_fee": "0x3",
+            "signature": [],
+            "nonce": "0x4",
+            "transaction_hash": "0x5",
+        });
+        let tx = serde_json::from_value::<InvokeTransaction>(v1.clone()).unwrap();
+        assert_eq!(
+            tx,
+            InvokeTransaction::V1(InvokeTransactionV1 {
+                calldata: vec![pathfinder_common::CallParam(felt!("0x1"))],
+                sender_address: ContractAddress::new_or_panic(felt!("0x2")),
+                max_fee: Fee(felt!("0x3")),
+                signature: vec![],
+                nonce: TransactionNonce(felt!("0x4")),
+                transaction_hash: StarknetTransactionHash(felt!("0x5")),
+            })
+        );
+
+        // Fields of another version are rejected, as are missing fields of this version.
+        let mut with_selector = v1.clone();
+        with_selector["entry_point_selector"] = serde_json::json!("0x6");
+        assert!(serde_json::from_value::<InvokeTransaction>(with_selector).is_err());
+        let mut v0 = v1;
+        v0["version"] = serde_json::json!("0x0");
+        assert!(serde_json::from_value::<InvokeTransaction>(v0).is_err());
+    }
+
+    #[test]
+    fn unknown_block_fields() {
+        use super::MaybePendingBlock;
+        use starknet_gateway_test_fixtures::v0_9_0;
+
+        // Full blocks reject fields we don't know about, pending blocks are best effort.
+        let mut block =
+            serde_json::from_str::<serde_json::Value>(v0_9_0::block::NUMBER_231579).unwrap();
+        block["unknown"] = serde_json::json!(1);
+        assert!(serde_json::from_value::<MaybePendingBlock>(block).is_err());
+
+        let mut pending =
+            serde_json::from_str::<serde_json::Value>(v0_9_0::block::PENDING).unwrap();
+        pending["unknown"] = serde_json::json!(1);
+        let pending = serde_json::from_value::<MaybePendingBlock>(pending).unwrap();
+        assert!(matches!(pending, MaybePendingBlock::Pending(_)));
+    }
+
+    #[test]
+    fn transaction_type_fields() {
+        use super::transaction::{L1HandlerTransaction, Transaction};
+        use pathfinder_common::{
+            felt, CallParam, ContractAddress, EntryPoint, StarknetTransactionHash,
+            TransactionNonce, TransactionVersion,
+        };
+
+        let l1_handler = serde_json::json!({
+            "type": "L1_HANDLER",
+            "contract_address": "0x1",
+            "entry_point_selector": "0x2",
+            "nonce": "0x3",
+            "calldata": ["0x4"],
+            "transaction_hash": "0x5",
+            "version": "0x0",
+        });
+        let tx = serde_json::from_value::<Transaction>(l1_handler.clone()).unwrap();
+        assert_eq!(
+            tx,
+            Transaction::L1Handler(L1HandlerTransaction {
+                contract_address: ContractAddress::new_or_panic(felt!("0x1")),
+                entry_point_selector: EntryPoint(felt!("0x2")),
+                nonce: TransactionNonce(felt!("0x3")),
+                calldata: vec![CallParam(felt!("0x4"))],
+                transaction_hash: StarknetTransactionHash(felt!("0x5")),
+                version: TransactionVersion(ethers::types::H256::zero()),
+            })
+        );
+
+        // Fields of another transaction type are rejected, as is a missing type.
+        let mut with_fee = l1_handler.clone();
+        with_fee["max_fee"] = serde_json::json!("0x6");
+        assert!(serde_json::from_value::<Transaction>(with_fee).is_err());
+        let mut untyped = l1_handler;
+        untyped.as_object_mut().unwrap().remove("type");
+        assert!(serde_json::from_value::<Transaction>(untyped).is_err());
+    }
 }
@@ -196,9 +196,8 @@ pub mod add_transaction {
     use pathfinder_common::{
         CasmHash, ClassHash, ContractAddressSalt, TransactionNonce, TransactionVersion,
     };
-    use pathfinder_serde::{
-        CallParamAsDecimalStr, TransactionSignatureElemAsDecimalStr, TransactionVersionAsHexStr,
-    };
+    use pathfinder_serde::policy::{Gateway, Numeric};
+    use pathfinder_serde::TransactionVersionAsHexStr;
     use serde_with::serde_as;
     use std::collections::HashMap;
 
@@ -240,13 +239,13 @@ pub mod add_transaction {
         pub version: TransactionVersion,
 
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
         pub nonce: TransactionNonce,
 
         pub class_hash: ClassHash,
         pub contract_address_salt: ContractAddressSalt,
-        #[serde_as(as = "Vec<CallParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub constructor_calldata: Vec<CallParam>,
     }
 
@@ -260,13 +259,13 @@ pub mod add_transaction {
 
         // AccountTransaction properties
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
         pub nonce: Option<TransactionNonce>,
 
         pub sender_address: ContractAddress,
         pub entry_point_selector: Option<EntryPoint>,
-        #[serde_as(as = "Vec<CallParamAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub calldata: Vec<CallParam>,
     }
 
@@ -280,7 +279,7 @@ pub mod add_transaction {
 
         // AccountTransaction properties -- except for nonce which is non-optional here
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
         pub signature: Vec<TransactionSignatureElem>,
 
         pub contract_class: ContractDefinition,
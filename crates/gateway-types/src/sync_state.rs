@@ -0,0 +1,64 @@
+//! The state of sync which is shared with the RPC API, such as the syncing status and the stream
+//! of changes made to the stored chain.
+pub mod milestones;
+pub mod syncing;
+
+use crate::reply::{Block, PendingBlock};
+use pathfinder_common::StarknetBlockNumber;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use syncing::Syncing;
+use tokio::sync::RwLock;
+
+pub struct SyncState {
+    pub status: RwLock<Syncing>,
+    /// Set while pathfinder is serving stored data only, because its upstream
+    /// endpoints were unreachable at startup and sync has not started yet.
+    pub degraded: AtomicBool,
+    /// Sync broadcasts each change to the stored chain, for RPC subscriptions.
+    pub chain_updates: tokio::sync::broadcast::Sender<ChainUpdate>,
+    /// Sync reports its progress here, which notifies subscribers of any milestones crossed.
+    pub milestones: milestones::SyncMilestones,
+    /// When sync's main loop last [beat](Self::beat), [None] until sync has started.
+    heartbeat: std::sync::Mutex<Option<std::time::Instant>>,
+}
+
+impl SyncState {
+    /// Number of chain updates a subscriber may fall behind before it is disconnected.
+    pub const CHAIN_UPDATES_CAPACITY: usize = 16;
+
+    /// Called periodically by sync's main loop, to show that it is still responsive.
+    pub fn beat(&self) {
+        *self.heartbeat.lock().unwrap() = Some(std::time::Instant::now());
+    }
+
+    /// The time since sync's main loop last [beat](Self::beat), [None] if sync has not started.
+    pub fn since_heartbeat(&self) -> Option<std::time::Duration> {
+        self.heartbeat.lock().unwrap().map(|beat| beat.elapsed())
+    }
+}
+
+/// A change made by sync to the stored chain, broadcast in the order in which sync made them.
+#[derive(Clone, Debug)]
+pub enum ChainUpdate {
+    /// A new block has been stored.
+    Block(Arc<Block>),
+    /// A reorg removed the stored blocks from this one onwards.
+    Reorg(StarknetBlockNumber),
+    /// The stored blocks up to and including this one have been accepted on L1.
+    AcceptedOnL1(StarknetBlockNumber),
+    /// The pending block has changed.
+    Pending(Arc<PendingBlock>),
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        Self {
+            status: RwLock::new(Syncing::False(false)),
+            degraded: AtomicBool::new(false),
+            chain_updates: tokio::sync::broadcast::channel(Self::CHAIN_UPDATES_CAPACITY).0,
+            milestones: Default::default(),
+            heartbeat: Default::default(),
+        }
+    }
+}
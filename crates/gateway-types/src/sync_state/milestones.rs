@@ -0,0 +1,173 @@
+//! Sync milestones which are broadcast to RPC subscribers, so that orchestration layers can
+//! shift traffic towards or away from a node depending on whether it is caught up.
+use std::sync::Mutex;
+
+use pathfinder_common::StarknetBlockNumber;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "milestone", rename_all = "snake_case")]
+pub enum SyncMilestone {
+    /// Sync has reached the latest block of the network.
+    CaughtUp { block_number: StarknetBlockNumber },
+    /// Sync is more than the configured number of blocks behind the network.
+    FellBehind {
+        current_block_number: StarknetBlockNumber,
+        highest_block_number: StarknetBlockNumber,
+    },
+}
+
+/// Detects [SyncMilestone]s from the sync status and broadcasts them.
+///
+/// A node counts as caught up once it has reached the latest block, and as behind once it trails
+/// by more than the threshold. In between it keeps its previous state, so that a node hovering a
+/// block or two behind the head does not flood subscribers.
+pub struct SyncMilestones {
+    sender: broadcast::Sender<SyncMilestone>,
+    tracker: Mutex<Tracker>,
+}
+
+struct Tracker {
+    behind_threshold: u64,
+    /// [None] until the first status update.
+    caught_up: Option<bool>,
+}
+
+impl SyncMilestones {
+    pub const DEFAULT_BEHIND_THRESHOLD: u64 = 10;
+    /// Number of milestones a subscriber may fall behind before it is disconnected.
+    pub const CAPACITY: usize = 16;
+
+    pub fn set_behind_threshold(&self, behind_threshold: u64) {
+        self.tracker.lock().unwrap().behind_threshold = behind_threshold;
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncMilestone> {
+        self.sender.subscribe()
+    }
+
+    /// Called by sync whenever its current or highest block changes.
+    pub fn update(&self, current: StarknetBlockNumber, highest: StarknetBlockNumber) {
+        let milestone = self.tracker.lock().unwrap().update(current, highest);
+
+        if let Some(milestone) = milestone {
+            tracing::debug!(?milestone, "Sync milestone reached");
+            // An error only means that there are no subscribers.
+            let _ = self.sender.send(milestone);
+        }
+    }
+
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn send(&self, milestone: SyncMilestone) {
+        self.sender.send(milestone).unwrap();
+    }
+}
+
+impl Default for SyncMilestones {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(Self::CAPACITY).0,
+            tracker: Mutex::new(Tracker {
+                behind_threshold: Self::DEFAULT_BEHIND_THRESHOLD,
+                caught_up: None,
+            }),
+        }
+    }
+}
+
+impl Tracker {
+    fn update(
+        &mut self,
+        current: StarknetBlockNumber,
+        highest: StarknetBlockNumber,
+    ) -> Option<SyncMilestone> {
+        let behind = highest.get().saturating_sub(current.get());
+
+        if behind == 0 && self.caught_up != Some(true) {
+            self.caught_up = Some(true);
+            Some(SyncMilestone::CaughtUp {
+                block_number: current,
+            })
+        } else if behind > self.behind_threshold && self.caught_up != Some(false) {
+            self.caught_up = Some(false);
+            Some(SyncMilestone::FellBehind {
+                current_block_number: current,
+                highest_block_number: highest,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(tracker: &mut Tracker, current: u64, highest: u64) -> Option<SyncMilestone> {
+        tracker.update(
+            StarknetBlockNumber::new_or_panic(current),
+            StarknetBlockNumber::new_or_panic(highest),
+        )
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut tracker = Tracker {
+            behind_threshold: 10,
+            caught_up: None,
+        };
+
+        assert_eq!(
+            update(&mut tracker, 0, 100),
+            Some(SyncMilestone::FellBehind {
+                current_block_number: StarknetBlockNumber::new_or_panic(0),
+                highest_block_number: StarknetBlockNumber::new_or_panic(100),
+            })
+        );
+        assert_eq!(update(&mut tracker, 50, 100), None);
+        // Within the threshold, but not yet caught up.
+        assert_eq!(update(&mut tracker, 95, 100), None);
+        assert_eq!(
+            update(&mut tracker, 100, 100),
+            Some(SyncMilestone::CaughtUp {
+                block_number: StarknetBlockNumber::new_or_panic(100)
+            })
+        );
+        assert_eq!(update(&mut tracker, 100, 101), None);
+        assert_eq!(update(&mut tracker, 101, 101), None);
+        assert_eq!(update(&mut tracker, 101, 111), None);
+        assert_eq!(
+            update(&mut tracker, 101, 112),
+            Some(SyncMilestone::FellBehind {
+                current_block_number: StarknetBlockNumber::new_or_panic(101),
+                highest_block_number: StarknetBlockNumber::new_or_panic(112),
+            })
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let caught_up = SyncMilestone::CaughtUp {
+            block_number: StarknetBlockNumber::new_or_panic(5),
+        };
+        assert_eq!(
+            serde_json::to_value(caught_up).unwrap(),
+            serde_json::json!({"milestone": "caught_up", "block_number": 5})
+        );
+
+        let fell_behind = SyncMilestone::FellBehind {
+            current_block_number: StarknetBlockNumber::new_or_panic(5),
+            highest_block_number: StarknetBlockNumber::new_or_panic(20),
+        };
+        assert_eq!(
+            serde_json::to_value(fell_behind).unwrap(),
+            serde_json::json!({
+                "milestone": "fell_behind",
+                "current_block_number": 5,
+                "highest_block_number": 20,
+            })
+        );
+    }
+}
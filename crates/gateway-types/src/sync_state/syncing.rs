@@ -1,11 +1,10 @@
 use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
 use pathfinder_serde::StarknetBlockNumberAsHexStr;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 /// Describes Starknet's syncing status RPC reply.
-#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
-#[cfg_attr(any(test, feature = "rpc-full-serde"), derive(serde::Deserialize))]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(untagged)]
 pub enum Syncing {
     False(bool),
@@ -24,8 +23,7 @@ impl std::fmt::Display for Syncing {
 }
 
 /// Represents Starknet node syncing status.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
-#[cfg_attr(any(test, feature = "rpc-full-serde"), derive(serde::Deserialize))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Status {
     #[serde(flatten, with = "prefix_starting")]
     pub starting: NumberedBlock,
@@ -51,8 +49,7 @@ impl std::fmt::Display for Status {
 
 /// Block hash and a number, for `starknet_syncing` response only.
 #[serde_as]
-#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
-#[cfg_attr(any(test, feature = "rpc-full-serde"), derive(serde::Deserialize))]
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct NumberedBlock {
     #[serde(rename = "block_hash")]
     pub hash: StarknetBlockHash,
@@ -74,7 +71,7 @@ impl From<(StarknetBlockHash, StarknetBlockNumber)> for NumberedBlock {
 }
 
 /// Helper to make it a bit less painful to write examples.
-#[cfg(test)]
+#[cfg(any(test, feature = "test-utils"))]
 impl<'a> From<(&'a str, u64)> for NumberedBlock {
     fn from((h, n): (&'a str, u64)) -> Self {
         use stark_hash::Felt;
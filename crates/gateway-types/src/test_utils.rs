@@ -0,0 +1,359 @@
+//! [proptest] strategies for the gateway's transaction and receipt types, building on
+//! [pathfinder_common::test_utils::strategies].
+use pathfinder_common::test_utils::strategies::transaction_version;
+use pathfinder_common::{
+    CallParam, CasmHash, ClassHash, ConstructorParam, ContractAddress, ContractAddressSalt,
+    EntryPoint, EthereumAddress, EventData, EventKey, Fee, L1ToL2MessageNonce,
+    L1ToL2MessagePayloadElem, L2ToL1MessagePayloadElem, StarknetTransactionHash,
+    StarknetTransactionIndex, TransactionNonce, TransactionSignatureElem,
+};
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use serde::Deserialize;
+
+use crate::reply::transaction::{
+    execution_resources::BuiltinInstanceCounter, DeclareTransaction, DeclareTransactionV0V1,
+    DeclareTransactionV2, DeployAccountTransaction, DeployTransaction, EntryPointType, Event,
+    ExecutionResources, ExecutionStatus, InvokeTransaction, InvokeTransactionV0,
+    InvokeTransactionV1, L1HandlerTransaction, L1ToL2Message, L2ToL1Message, Receipt, Transaction,
+};
+
+/// The maximum length of generated collections such as calldata and events.
+const MAX_LEN: usize = 4;
+
+pub fn transaction() -> impl Strategy<Value = Transaction> {
+    prop_oneof![
+        declare_transaction().prop_map(Transaction::Declare),
+        deploy_transaction().prop_map(Transaction::Deploy),
+        deploy_account_transaction().prop_map(Transaction::DeployAccount),
+        invoke_transaction().prop_map(Transaction::Invoke),
+        l1_handler_transaction().prop_map(Transaction::L1Handler),
+    ]
+}
+
+pub fn declare_transaction() -> impl Strategy<Value = DeclareTransaction> {
+    prop_oneof![
+        declare_transaction_v0_v1().prop_map(DeclareTransaction::V0),
+        declare_transaction_v0_v1().prop_map(DeclareTransaction::V1),
+        declare_transaction_v2().prop_map(DeclareTransaction::V2),
+    ]
+}
+
+fn declare_transaction_v0_v1() -> impl Strategy<Value = DeclareTransactionV0V1> {
+    (
+        any::<ClassHash>(),
+        any::<Fee>(),
+        any::<TransactionNonce>(),
+        any::<ContractAddress>(),
+        vec(any::<TransactionSignatureElem>(), 0..MAX_LEN),
+        any::<StarknetTransactionHash>(),
+    )
+        .prop_map(
+            |(class_hash, max_fee, nonce, sender_address, signature, transaction_hash)| {
+                DeclareTransactionV0V1 {
+                    class_hash,
+                    max_fee,
+                    nonce,
+                    sender_address,
+                    signature,
+                    transaction_hash,
+                }
+            },
+        )
+}
+
+fn declare_transaction_v2() -> impl Strategy<Value = DeclareTransactionV2> {
+    (declare_transaction_v0_v1(), any::<CasmHash>()).prop_map(|(tx, compiled_class_hash)| {
+        DeclareTransactionV2 {
+            class_hash: tx.class_hash,
+            max_fee: tx.max_fee,
+            nonce: tx.nonce,
+            sender_address: tx.sender_address,
+            signature: tx.signature,
+            transaction_hash: tx.transaction_hash,
+            compiled_class_hash,
+        }
+    })
+}
+
+pub fn deploy_transaction() -> impl Strategy<Value = DeployTransaction> {
+    (
+        any::<ContractAddress>(),
+        any::<ContractAddressSalt>(),
+        any::<ClassHash>(),
+        vec(any::<ConstructorParam>(), 0..MAX_LEN),
+        any::<StarknetTransactionHash>(),
+        transaction_version(),
+    )
+        .prop_map(
+            |(
+                contract_address,
+                contract_address_salt,
+                class_hash,
+                constructor_calldata,
+                transaction_hash,
+                version,
+            )| DeployTransaction {
+                contract_address,
+                contract_address_salt,
+                class_hash,
+                constructor_calldata,
+                transaction_hash,
+                version,
+            },
+        )
+}
+
+pub fn deploy_account_transaction() -> impl Strategy<Value = DeployAccountTransaction> {
+    (
+        any::<ContractAddress>(),
+        any::<StarknetTransactionHash>(),
+        any::<Fee>(),
+        transaction_version(),
+        vec(any::<TransactionSignatureElem>(), 0..MAX_LEN),
+        any::<TransactionNonce>(),
+        any::<ContractAddressSalt>(),
+        vec(any::<CallParam>(), 0..MAX_LEN),
+        any::<ClassHash>(),
+    )
+        .prop_map(
+            |(
+                contract_address,
+                transaction_hash,
+                max_fee,
+                version,
+                signature,
+                nonce,
+                contract_address_salt,
+                constructor_calldata,
+                class_hash,
+            )| DeployAccountTransaction {
+                contract_address,
+                transaction_hash,
+                max_fee,
+                version,
+                signature,
+                nonce,
+                contract_address_salt,
+                constructor_calldata,
+                class_hash,
+            },
+        )
+}
+
+pub fn invoke_transaction() -> impl Strategy<Value = InvokeTransaction> {
+    let v0 = (
+        vec(any::<CallParam>(), 0..MAX_LEN),
+        any::<ContractAddress>(),
+        any::<EntryPoint>(),
+        option::of(prop_oneof![
+            Just(EntryPointType::External),
+            Just(EntryPointType::L1Handler)
+        ]),
+        any::<Fee>(),
+        vec(any::<TransactionSignatureElem>(), 0..MAX_LEN),
+        any::<StarknetTransactionHash>(),
+    )
+        .prop_map(
+            |(
+                calldata,
+                sender_address,
+                entry_point_selector,
+                entry_point_type,
+                max_fee,
+                signature,
+                transaction_hash,
+            )| InvokeTransactionV0 {
+                calldata,
+                sender_address,
+                entry_point_selector,
+                entry_point_type,
+                max_fee,
+                signature,
+                transaction_hash,
+            },
+        );
+
+    let v1 = (
+        vec(any::<CallParam>(), 0..MAX_LEN),
+        any::<ContractAddress>(),
+        any::<Fee>(),
+        vec(any::<TransactionSignatureElem>(), 0..MAX_LEN),
+        any::<TransactionNonce>(),
+        any::<StarknetTransactionHash>(),
+    )
+        .prop_map(
+            |(calldata, sender_address, max_fee, signature, nonce, transaction_hash)| {
+                InvokeTransactionV1 {
+                    calldata,
+                    sender_address,
+                    max_fee,
+                    signature,
+                    nonce,
+                    transaction_hash,
+                }
+            },
+        );
+
+    prop_oneof![
+        v0.prop_map(InvokeTransaction::V0),
+        v1.prop_map(InvokeTransaction::V1),
+    ]
+}
+
+pub fn l1_handler_transaction() -> impl Strategy<Value = L1HandlerTransaction> {
+    (
+        any::<ContractAddress>(),
+        any::<EntryPoint>(),
+        any::<TransactionNonce>(),
+        vec(any::<CallParam>(), 0..MAX_LEN),
+        any::<StarknetTransactionHash>(),
+        transaction_version(),
+    )
+        .prop_map(
+            |(
+                contract_address,
+                entry_point_selector,
+                nonce,
+                calldata,
+                transaction_hash,
+                version,
+            )| L1HandlerTransaction {
+                contract_address,
+                entry_point_selector,
+                nonce,
+                calldata,
+                transaction_hash,
+                version,
+            },
+        )
+}
+
+pub fn receipt() -> impl Strategy<Value = Receipt> {
+    (
+        option::of(any::<Fee>()),
+        vec(event(), 0..MAX_LEN),
+        option::of(execution_resources()),
+        option::of(l1_to_l2_message()),
+        vec(l2_to_l1_message(), 0..MAX_LEN),
+        any::<StarknetTransactionHash>(),
+        any::<StarknetTransactionIndex>(),
+        prop_oneof![
+            Just((ExecutionStatus::Succeeded, None)),
+            any::<String>().prop_map(|error| (ExecutionStatus::Reverted, Some(error))),
+        ],
+    )
+        .prop_map(
+            |(
+                actual_fee,
+                events,
+                execution_resources,
+                l1_to_l2_consumed_message,
+                l2_to_l1_messages,
+                transaction_hash,
+                transaction_index,
+                (execution_status, revert_error),
+            )| Receipt {
+                actual_fee,
+                events,
+                execution_resources,
+                l1_to_l2_consumed_message,
+                l2_to_l1_messages,
+                transaction_hash,
+                transaction_index,
+                execution_status,
+                revert_error,
+            },
+        )
+}
+
+pub fn event() -> impl Strategy<Value = Event> {
+    (
+        vec(any::<EventData>(), 0..MAX_LEN),
+        any::<ContractAddress>(),
+        vec(any::<EventKey>(), 0..MAX_LEN),
+    )
+        .prop_map(|(data, from_address, keys)| Event {
+            data,
+            from_address,
+            keys,
+        })
+}
+
+pub fn execution_resources() -> impl Strategy<Value = ExecutionResources> {
+    // The counters are private, so they are created by deserialization instead.
+    let builtin_instance_counter = prop_oneof![
+        Just(serde_json::json!({})),
+        any::<[u64; 6]>().prop_map(|counters| serde_json::json!({
+            "bitwise_builtin": counters[0],
+            "ecdsa_builtin": counters[1],
+            "ec_op_builtin": counters[2],
+            "output_builtin": counters[3],
+            "pedersen_builtin": counters[4],
+            "range_check_builtin": counters[5],
+        })),
+    ]
+    .prop_map(|json| BuiltinInstanceCounter::deserialize(json).unwrap());
+
+    (builtin_instance_counter, any::<u64>(), any::<u64>()).prop_map(
+        |(builtin_instance_counter, n_steps, n_memory_holes)| ExecutionResources {
+            builtin_instance_counter,
+            n_steps,
+            n_memory_holes,
+        },
+    )
+}
+
+pub fn l1_to_l2_message() -> impl Strategy<Value = L1ToL2Message> {
+    (
+        any::<EthereumAddress>(),
+        vec(any::<L1ToL2MessagePayloadElem>(), 0..MAX_LEN),
+        any::<EntryPoint>(),
+        any::<ContractAddress>(),
+        option::of(any::<L1ToL2MessageNonce>()),
+    )
+        .prop_map(
+            |(from_address, payload, selector, to_address, nonce)| L1ToL2Message {
+                from_address,
+                payload,
+                selector,
+                to_address,
+                nonce,
+            },
+        )
+}
+
+pub fn l2_to_l1_message() -> impl Strategy<Value = L2ToL1Message> {
+    (
+        any::<ContractAddress>(),
+        vec(any::<L2ToL1MessagePayloadElem>(), 0..MAX_LEN),
+        any::<EthereumAddress>(),
+    )
+        .prop_map(|(from_address, payload, to_address)| L2ToL1Message {
+            from_address,
+            payload,
+            to_address,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn transaction_round_trip(transaction in transaction()) {
+            let json = serde_json::to_value(&transaction).unwrap();
+            let decoded = serde_json::from_value::<Transaction>(json).unwrap();
+            prop_assert_eq!(decoded, transaction);
+        }
+
+        #[test]
+        fn receipt_round_trip(receipt in receipt()) {
+            let json = serde_json::to_value(&receipt).unwrap();
+            let decoded = serde_json::from_value::<Receipt>(json).unwrap();
+            prop_assert_eq!(decoded, receipt);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! The trees which commit to the transactions and events of a block.
+use bitvec::prelude::BitView;
+use stark_hash::{stark_hash, Felt, HashChain};
+use starknet_gateway_types::reply::transaction::{Event, Transaction};
+
+use crate::merkle_tree::{verify_proof_of_height, Membership, MerkleTree, ProofNode};
+use crate::PedersenHash;
+
+/// A Patricia Merkle tree with height 64 used to compute transaction and event commitments.
+///
+/// According to the [documentation](https://docs.starknet.io/docs/Blocks/header/#block-header)
+/// the commitment trees are of height 64, because the key used is the 64 bit representation
+/// of the index of the transaction / event within the block.
+///
+/// The tree height is 64 in our case since our set operation takes u64 index values.
+pub struct CommitmentTree {
+    tree: MerkleTree<(), PedersenHash>,
+}
+
+impl Default for CommitmentTree {
+    fn default() -> Self {
+        Self {
+            tree: MerkleTree::empty((), Self::HEIGHT as u8),
+        }
+    }
+}
+
+impl CommitmentTree {
+    const HEIGHT: usize = 64;
+
+    pub fn set(&mut self, index: u64, value: Felt) -> anyhow::Result<()> {
+        let key = index.to_be_bytes();
+        self.tree.set(key.view_bits(), value)
+    }
+
+    pub fn commit(mut self) -> anyhow::Result<Felt> {
+        self.commit_mut()
+    }
+
+    /// Returns the root while keeping the tree, so that proofs can be generated from it.
+    pub fn commit_mut(&mut self) -> anyhow::Result<Felt> {
+        self.tree.commit_mut()
+    }
+
+    /// Generates a proof for the value at `index`. See [`MerkleTree::get_proof`].
+    ///
+    /// The tree must have been [committed](Self::commit_mut) since it was last changed.
+    pub fn get_proof(&self, index: u64) -> anyhow::Result<Vec<ProofNode>> {
+        let key = index.to_be_bytes();
+        self.tree.get_proof(key.view_bits())
+    }
+
+    /// Verifies a proof generated by [`Self::get_proof`] against the commitment `root`.
+    /// See [`verify_proof`](crate::merkle_tree::verify_proof) for details.
+    pub fn verify_proof(
+        root: Felt,
+        index: u64,
+        value: Felt,
+        proof: &[ProofNode],
+    ) -> Option<Membership> {
+        let key = index.to_be_bytes();
+        verify_proof_of_height::<PedersenHash>(root, key.view_bits(), value, proof, Self::HEIGHT)
+    }
+}
+
+/// Compute the combined hash of the transaction hash and the signature, which is the leaf of the
+/// transaction in the transaction commitment tree.
+///
+/// Since the transaction hash doesn't take the signature values as its input
+/// computing the transaction commitent uses a hash value that combines
+/// the transaction hash with the array of signature values.
+///
+/// Note that for non-invoke transactions we don't actually have signatures. The
+/// cairo-lang uses an empty list (whose hash is not the ZERO value!) in that
+/// case.
+pub fn calculate_transaction_hash_with_signature(tx: &Transaction) -> Felt {
+    lazy_static::lazy_static!(
+        static ref HASH_OF_EMPTY_LIST: Felt = HashChain::default().finalize();
+    );
+
+    let signature_hash = match tx {
+        Transaction::Invoke(tx) => {
+            let mut hash = HashChain::default();
+            for signature in tx.signature() {
+                hash.update(signature.0);
+            }
+            hash.finalize()
+        }
+        Transaction::Declare(_)
+        | Transaction::Deploy(_)
+        | Transaction::DeployAccount(_)
+        | Transaction::L1Handler(_) => *HASH_OF_EMPTY_LIST,
+    };
+
+    stark_hash(tx.hash().0, signature_hash)
+}
+
+/// Calculate the hash of an event, which is its leaf in the event commitment tree.
+///
+/// See the [documentation](https://docs.starknet.io/docs/Events/starknet-events#event-hash)
+/// for details.
+pub fn calculate_event_hash(event: &Event) -> Felt {
+    let mut keys_hash = HashChain::default();
+    for key in event.keys.iter() {
+        keys_hash.update(key.0);
+    }
+    let keys_hash = keys_hash.finalize();
+
+    let mut data_hash = HashChain::default();
+    for data in event.data.iter() {
+        data_hash.update(data.0);
+    }
+    let data_hash = data_hash.finalize();
+
+    let mut event_hash = HashChain::default();
+    event_hash.update(*event.from_address.get());
+    event_hash.update(keys_hash);
+    event_hash.update(data_hash);
+
+    event_hash.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{felt, EntryPoint, Fee};
+    use starknet_gateway_types::reply::transaction::{
+        EntryPointType, InvokeTransaction, InvokeTransactionV0,
+    };
+
+    #[test]
+    fn test_event_hash() {
+        use pathfinder_common::{ContractAddress, EventData, EventKey};
+
+        let event = Event {
+            from_address: ContractAddress::new_or_panic(felt!("0xdeadbeef")),
+            data: vec![
+                EventData(felt!("0x5")),
+                EventData(felt!("0x6")),
+                EventData(felt!("0x7")),
+                EventData(felt!("0x8")),
+                EventData(felt!("0x9")),
+            ],
+            keys: vec![
+                EventKey(felt!("0x1")),
+                EventKey(felt!("0x2")),
+                EventKey(felt!("0x3")),
+                EventKey(felt!("0x4")),
+            ],
+        };
+
+        // produced by the cairo-lang Python implementation:
+        // `hex(calculate_event_hash(0xdeadbeef, [1, 2, 3, 4], [5, 6, 7, 8, 9]))`
+        let expected_event_hash =
+            felt!("0xdb96455b3a61f9139f7921667188d31d1e1d49fb60a1aa3dbf3756dbe3a9b4");
+        let calculated_event_hash = calculate_event_hash(&event);
+        assert_eq!(expected_event_hash, calculated_event_hash);
+    }
+
+    #[test]
+    fn test_final_transaction_hash() {
+        use pathfinder_common::{
+            ContractAddress, StarknetTransactionHash, TransactionSignatureElem,
+        };
+
+        let transaction = Transaction::Invoke(InvokeTransaction::V0(InvokeTransactionV0 {
+            calldata: vec![],
+            sender_address: ContractAddress::new_or_panic(felt!("0xdeadbeef")),
+            entry_point_type: Some(EntryPointType::External),
+            entry_point_selector: EntryPoint(felt!("0xe")),
+            max_fee: Fee::ZERO,
+            signature: vec![
+                TransactionSignatureElem(felt!("0x2")),
+                TransactionSignatureElem(felt!("0x3")),
+            ],
+            transaction_hash: StarknetTransactionHash(felt!("0x1")),
+        }));
+
+        // produced by the cairo-lang Python implementation:
+        // `hex(calculate_single_tx_hash_with_signature(1, [2, 3], hash_function=pedersen_hash))`
+        let expected_final_hash =
+            Felt::from_hex_str("0x259c3bd5a1951eafb2f41e0b783eab92cfe4e108b2b1f071e3736f06b909431")
+                .unwrap();
+        let calculated_final_hash = calculate_transaction_hash_with_signature(&transaction);
+        assert_eq!(expected_final_hash, calculated_final_hash);
+    }
+
+    #[test]
+    fn test_commitment_merkle_tree() {
+        let mut tree = CommitmentTree::default();
+
+        for (idx, hash) in [1u64, 2, 3, 4].into_iter().enumerate() {
+            let hash = Felt::from(hash);
+            let idx: u64 = idx.try_into().unwrap();
+            tree.set(idx, hash).unwrap();
+        }
+
+        // produced by the cairo-lang Python implementation:
+        // `hex(asyncio.run(calculate_patricia_root([1, 2, 3, 4], height=64, ffc=ffc))))`
+        let expected_root_hash =
+            felt!("0x1a0e579b6b444769e4626331230b5ae39bd880f47e703b73fa56bf77e52e461");
+        let computed_root_hash = tree.commit().unwrap();
+
+        assert_eq!(expected_root_hash, computed_root_hash);
+    }
+
+    #[test]
+    fn proofs() {
+        let mut tree = CommitmentTree::default();
+        for index in 0..5u64 {
+            tree.set(index, Felt::from(index + 1)).unwrap();
+        }
+        let root = tree.commit_mut().unwrap();
+
+        for index in 0..5u64 {
+            let proof = tree.get_proof(index).unwrap();
+            let value = Felt::from(index + 1);
+            assert_eq!(
+                CommitmentTree::verify_proof(root, index, value, &proof),
+                Some(Membership::Member)
+            );
+            // A proof does not hold for another value or index.
+            assert_eq!(
+                CommitmentTree::verify_proof(root, index, Felt::from(10u64), &proof),
+                None
+            );
+            assert_ne!(
+                CommitmentTree::verify_proof(root, index + 1, value, &proof),
+                Some(Membership::Member)
+            );
+        }
+
+        let proof = tree.get_proof(7).unwrap();
+        assert_eq!(
+            CommitmentTree::verify_proof(root, 7, Felt::ZERO, &proof),
+            Some(Membership::NonMember)
+        );
+    }
+}
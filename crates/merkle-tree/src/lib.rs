@@ -1,8 +1,10 @@
 use stark_hash::Felt;
 
+pub mod commitment_tree;
 pub mod contract_state;
 pub mod merkle_node;
 pub mod merkle_tree;
+pub mod proof;
 pub mod state_tree;
 
 /// Hashing function used by a particular merkle tree implementation.
@@ -21,7 +23,8 @@ impl Hash for PedersenHash {
 }
 
 /// Implements [Hash] for the [StarkNet Poseidon hash](stark_poseidon::poseidon_hash).
-struct PoseidonHash;
+#[derive(Debug, Clone, Copy)]
+pub struct PoseidonHash;
 impl crate::Hash for PoseidonHash {
     fn hash(left: Felt, right: Felt) -> Felt {
         stark_poseidon::poseidon_hash(left.into(), right.into()).into()
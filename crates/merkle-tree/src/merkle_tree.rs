@@ -101,6 +101,154 @@ pub enum ProofNode {
     Edge(EdgeProofNode),
 }
 
+impl EdgeProofNode {
+    /// Calculates the hash of this node in the same way as its [EdgeNode] counterpart.
+    ///
+    /// Panics if the path is longer than 251 bits.
+    pub fn hash<H: Hash>(&self) -> Felt {
+        let child_hash = self.child_hash;
+
+        let path = Felt::from_bits(&self.path).unwrap();
+        let mut length = [0; 32];
+        // Safe as len() is guaranteed to be <= 251
+        length[31] = self.path.len() as u8;
+
+        // Length should be smaller than the maximum size of a stark hash.
+        let length = Felt::from_be_bytes(length).unwrap();
+
+        H::hash(child_hash, path) + length
+    }
+}
+
+impl BinaryProofNode {
+    /// Calculates the hash of this node in the same way as its [BinaryNode] counterpart.
+    pub fn hash<H: Hash>(&self) -> Felt {
+        H::hash(self.left_hash, self.right_hash)
+    }
+}
+
+impl ProofNode {
+    pub fn hash<H: Hash>(&self) -> Felt {
+        match self {
+            ProofNode::Binary(bin) => bin.hash::<H>(),
+            ProofNode::Edge(edge) => edge.hash::<H>(),
+        }
+    }
+}
+
+/// The outcome of a successful [verify_proof].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Membership {
+    Member,
+    NonMember,
+}
+
+/// Verifies that the key `key` with value `value` is indeed part of the MPT that has root
+/// `root`, given `proofs`.
+/// Supports proofs of non-membership as well as proof of membership: this function returns
+/// an enum corresponding to the membership of `value`, or returns `None` in case of a hash mismatch
+/// or an ill-formed proof.
+/// The algorithm follows this logic:
+/// 1. init expected_hash <- root hash
+/// 2. loop over nodes: current <- nodes[i]
+///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
+///    2. move towards the target - if current is:
+///       1. binary node then choose the child that moves towards the target, else if
+///       2. edge node then check the path against the target bits
+///          1. If it matches then proceed with the child, else
+///          2. if it does not match then we now have a proof that the target does not exist
+///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
+///       have reached the target and the child hash is the value you wanted and the proof is complete.
+///    4. set expected_hash <- to the child hash
+/// 3. check that the expected_hash is `value` (we should've reached the leaf)
+///
+/// `H` must be the hash function of the tree the proof was generated from.
+pub fn verify_proof<H: Hash>(
+    root: Felt,
+    key: &BitSlice<Msb0, u8>,
+    value: Felt,
+    proofs: &[ProofNode],
+) -> Option<Membership> {
+    verify_proof_of_height::<H>(root, key, value, proofs, 251)
+}
+
+/// Like [verify_proof], but for a tree of the given `height` instead of 251.
+pub(crate) fn verify_proof_of_height<H: Hash>(
+    root: Felt,
+    key: &BitSlice<Msb0, u8>,
+    value: Felt,
+    proofs: &[ProofNode],
+    height: usize,
+) -> Option<Membership> {
+    // Protect from ill-formed keys
+    if key.len() != height {
+        return None;
+    }
+
+    // An empty tree trivially does not contain the key.
+    if root == Felt::ZERO && proofs.is_empty() {
+        return Some(Membership::NonMember);
+    }
+
+    let mut expected_hash = root;
+    let mut remaining_path: &BitSlice<Msb0, u8> = key;
+
+    for proof_node in proofs.iter() {
+        // Protect from ill-formed proofs which would walk past the leaf.
+        match proof_node {
+            ProofNode::Binary(_) if remaining_path.is_empty() => return None,
+            ProofNode::Edge(edge) if edge.path.len() > remaining_path.len() => return None,
+            _ => {}
+        }
+
+        // Hash mismatch? Return None.
+        if proof_node.hash::<H>() != expected_hash {
+            return None;
+        }
+        match proof_node {
+            ProofNode::Binary(bin) => {
+                // Direction will always correspond to the 0th index
+                // because we're removing bits on every iteration.
+                let direction = Direction::from(remaining_path[0]);
+
+                // Set the next hash to be the left or right hash,
+                // depending on the direction
+                expected_hash = match direction {
+                    Direction::Left => bin.left_hash,
+                    Direction::Right => bin.right_hash,
+                };
+
+                // Advance by a single bit
+                remaining_path = &remaining_path[1..];
+            }
+            ProofNode::Edge(edge) => {
+                let path_matches = edge.path == remaining_path[..edge.path.len()];
+                if !path_matches {
+                    // If paths don't match, we've found a proof of non membership because we:
+                    // 1. Correctly moved towards the target insofar as is possible, and
+                    // 2. hashing all the nodes along the path does result in the root hash, which means
+                    // 3. the target definitely does not exist in this tree
+                    return Some(Membership::NonMember);
+                }
+
+                // Set the next hash to the child's hash
+                expected_hash = edge.child_hash;
+
+                // Advance by the whole edge path
+                remaining_path = &remaining_path[edge.path.len()..];
+            }
+        }
+    }
+
+    // At this point, we should reach `value` !
+    if remaining_path.is_empty() && expected_hash == value {
+        Some(Membership::Member)
+    } else {
+        // Hash mismatch or an incomplete proof. Return `None`.
+        None
+    }
+}
+
 /// A Starknet binary Merkle-Patricia tree with a specific root entry-point and storage.
 ///
 /// This is used to update, mutate and access global Starknet state as well as individual contract states.
@@ -733,6 +881,66 @@ impl<T: NodeStorage, H: Hash> MerkleTree<T, H> {
         Ok(None)
     }
 
+    /// Recomputes the hash of every stored node of the tree from the hashes of its children,
+    /// failing if a node does not match the hash it is stored under. `leaf` is called with the
+    /// key and value of every leaf, in ascending key order.
+    ///
+    /// As every node is reached from the root, a successful verification shows that the stored
+    /// nodes are exactly the tree committed to by the root the tree was loaded with.
+    pub fn verify<F>(&self, mut leaf: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&BitSlice<Msb0, u8>, Felt) -> anyhow::Result<()>,
+    {
+        let mut visitor = |node: &Node, path: &BitSlice<Msb0, u8>| {
+            let result = match node {
+                Node::Binary(binary) => {
+                    let left = binary.left.borrow().hash();
+                    let right = binary.right.borrow().hash();
+                    match (left, right, binary.hash) {
+                        (Some(left), Some(right), Some(hash)) if H::hash(left, right) == hash => {
+                            Ok(())
+                        }
+                        _ => Err(anyhow::anyhow!(
+                            "Hash of binary node at height {} does not match its children",
+                            path.len()
+                        )),
+                    }
+                }
+                Node::Edge(edge) => {
+                    let child = edge.child.borrow().hash();
+                    match (child, edge.hash) {
+                        (Some(child_hash), Some(hash))
+                            if EdgeProofNode {
+                                child_hash,
+                                path: edge.path.clone(),
+                            }
+                            .hash::<H>()
+                                == hash =>
+                        {
+                            Ok(())
+                        }
+                        _ => Err(anyhow::anyhow!(
+                            "Hash of edge node at height {} does not match its child",
+                            path.len()
+                        )),
+                    }
+                }
+                Node::Leaf(value) => leaf(path, *value),
+                Node::Unresolved(_) => Ok(()),
+            };
+
+            match result {
+                Ok(()) => ControlFlow::Continue(Visit::ContinueDeeper),
+                Err(e) => ControlFlow::Break(e),
+            }
+        };
+
+        match self.dfs(&mut visitor)? {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
     pub fn into_storage(self) -> T {
         self.storage
     }
@@ -1684,137 +1892,78 @@ mod tests {
         }
     }
 
-    mod proofs {
-        use crate::PedersenHash;
-
-        use super::{
-            BinaryProofNode, Direction, EdgeProofNode, MerkleTree, ProofNode, RcNodeStorage,
-        };
-        use bitvec::prelude::Msb0;
-        use bitvec::slice::BitSlice;
-        use pathfinder_common::felt;
-        use rusqlite::Transaction;
-        use stark_hash::Felt;
-
-        impl EdgeProofNode {
-            fn hash(&self) -> Felt {
-                // Code taken from [merkle_node::EdgeNode::calculate_hash]
-                let child_hash = self.child_hash;
-
-                // Path should be valid, so `unwrap()` is safe to use here.
-                let path = Felt::from_bits(&self.path).unwrap();
-                let mut length = [0; 32];
-                // Safe as len() is guaranteed to be <= 251
-                length[31] = self.path.len() as u8;
-
-                // Length should be smaller than the maximum size of a stark hash.
-                let length = Felt::from_be_bytes(length).unwrap();
-
-                stark_hash::stark_hash(child_hash, path) + length
-            }
-        }
+    mod verify {
+        use super::*;
 
-        impl BinaryProofNode {
-            fn hash(&self) -> Felt {
-                // Code taken from [merkle_node::EdgeNode::calculate_hash]
-                stark_hash::stark_hash(self.left_hash, self.right_hash)
-            }
-        }
+        fn committed_tree(transaction: &Transaction<'_>) -> (Felt, Vec<(Felt, Felt)>) {
+            let mut uut =
+                MerkleTree::<_, PedersenHash>::load("test", transaction, Felt::ZERO).unwrap();
 
-        impl ProofNode {
-            fn hash(&self) -> Felt {
-                match self {
-                    ProofNode::Binary(bin) => bin.hash(),
-                    ProofNode::Edge(edge) => edge.hash(),
-                }
+            let leaves = vec![
+                (felt!("0x8975"), felt!("0x3")),
+                (felt!("0x901823"), felt!("0x2")),
+                (felt!("0x99cadc82"), felt!("0x1")),
+            ];
+            for (key, value) in &leaves {
+                uut.set(key.view_bits(), *value).unwrap();
             }
-        }
 
-        #[derive(Debug, PartialEq, Eq)]
-        pub enum Membership {
-            Member,
-            NonMember,
+            (uut.commit().unwrap(), leaves)
         }
 
-        /// Verifies that the key `key` with value `value` is indeed part of the MPT that has root
-        /// `root`, given `proofs`.
-        /// Supports proofs of non-membership as well as proof of membership: this function returns
-        /// an enum corresponding to the membership of `value`, or returns `None` in case of a hash mismatch.
-        /// The algorithm follows this logic:
-        /// 1. init expected_hash <- root hash
-        /// 2. loop over nodes: current <- nodes[i]
-        ///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
-        ///    2. move towards the target - if current is:
-        ///       1. binary node then choose the child that moves towards the target, else if
-        ///       2. edge node then check the path against the target bits
-        ///          1. If it matches then proceed with the child, else
-        ///          2. if it does not match then we now have a proof that the target does not exist
-        ///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
-        ///       have reached the target and the child hash is the value you wanted and the proof is complete.
-        ///    4. set expected_hash <- to the child hash
-        /// 3. check that the expected_hash is `value` (we should've reached the leaf)
-        fn verify_proof(
-            root: Felt,
-            key: &BitSlice<Msb0, u8>,
-            value: Felt,
-            proofs: &[ProofNode],
-        ) -> Option<Membership> {
-            // Protect from ill-formed keys
-            if key.len() != 251 {
-                return None;
-            }
+        #[test]
+        fn visits_leaves_in_order() {
+            let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+            let transaction = conn.transaction().unwrap();
+            let (root, expected) = committed_tree(&transaction);
 
-            let mut expected_hash = root;
-            let mut remaining_path: &BitSlice<Msb0, u8> = key;
+            let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
+            let mut leaves = Vec::new();
+            uut.verify(|path, value| {
+                leaves.push((Felt::from_bits(path).unwrap(), value));
+                Ok(())
+            })
+            .unwrap();
 
-            for proof_node in proofs.iter() {
-                // Hash mismatch? Return None.
-                if proof_node.hash() != expected_hash {
-                    return None;
-                }
-                match proof_node {
-                    ProofNode::Binary(bin) => {
-                        // Direction will always correspond to the 0th index
-                        // because we're removing bits on every iteration.
-                        let direction = Direction::from(remaining_path[0]);
-
-                        // Set the next hash to be the left or right hash,
-                        // depending on the direction
-                        expected_hash = match direction {
-                            Direction::Left => bin.left_hash,
-                            Direction::Right => bin.right_hash,
-                        };
+            assert_eq!(leaves, expected);
+        }
 
-                        // Advance by a single bit
-                        remaining_path = &remaining_path[1..];
-                    }
-                    ProofNode::Edge(edge) => {
-                        let path_matches = edge.path == remaining_path[..edge.path.len()];
-                        if !path_matches {
-                            // If paths don't match, we've found a proof of non membership because we:
-                            // 1. Correctly moved towards the target insofar as is possible, and
-                            // 2. hashing all the nodes along the path does result in the root hash, which means
-                            // 3. the target definitely does not exist in this tree
-                            return Some(Membership::NonMember);
-                        }
+        #[test]
+        fn detects_tampered_node() {
+            let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+            let transaction = conn.transaction().unwrap();
+            let (root, _) = committed_tree(&transaction);
+
+            // Swaps the children of a binary node, which keeps the tree resolvable.
+            let (hash, data): (Vec<u8>, Vec<u8>) = transaction
+                .query_row(
+                    "SELECT hash, data FROM test WHERE length(data) = 64",
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .unwrap();
+            let swapped = [&data[32..], &data[..32]].concat();
+            transaction
+                .execute(
+                    "UPDATE test SET data = ? WHERE hash = ?",
+                    rusqlite::params![swapped, hash],
+                )
+                .unwrap();
 
-                        // Set the next hash to the child's hash
-                        expected_hash = edge.child_hash;
+            let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
+            uut.verify(|_, _| Ok(())).unwrap_err();
+        }
+    }
 
-                        // Advance by the whole edge path
-                        remaining_path = &remaining_path[edge.path.len()..];
-                    }
-                }
-            }
+    mod proofs {
+        use crate::PedersenHash;
 
-            // At this point, we should reach `value` !
-            if expected_hash == value {
-                Some(Membership::Member)
-            } else {
-                // Hash mismatch. Return `None`.
-                None
-            }
-        }
+        use super::{verify_proof, Membership, MerkleTree, ProofNode, RcNodeStorage};
+        use bitvec::prelude::Msb0;
+        use bitvec::slice::BitSlice;
+        use pathfinder_common::felt;
+        use rusqlite::Transaction;
+        use stark_hash::Felt;
 
         /// Structure representing a randomly generated tree.
         struct RandomTree<'tx, 'queries> {
@@ -1862,7 +2011,8 @@ mod tests {
                     .zip(self.values.iter())
                     .enumerate()
                     .for_each(|(i, (k, v))| {
-                        let verified = verify_proof(self.root, k, *v, &proofs[i]).unwrap();
+                        let verified =
+                            verify_proof::<PedersenHash>(self.root, k, *v, &proofs[i]).unwrap();
                         assert_eq!(verified, Membership::Member, "Failed to prove key");
                     });
             }
@@ -1907,11 +2057,55 @@ mod tests {
 
             let proofs = get_proofs(&keys, &uut).unwrap();
 
-            let verified_key1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_key1 =
+                verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
 
             assert_eq!(verified_key1, Membership::Member);
         }
 
+        #[test]
+        fn poseidon() {
+            use crate::PoseidonHash;
+
+            let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+            let transaction = conn.transaction().unwrap();
+            let mut uut =
+                MerkleTree::<_, PoseidonHash>::load("test", &transaction, Felt::ZERO).unwrap();
+
+            let key_1 = felt!("0x0");
+            let key_2 = felt!("0x1");
+            let key_3 = felt!("0x2");
+
+            let key1 = key_1.view_bits();
+            let key2 = key_2.view_bits();
+            let key3 = key_3.view_bits();
+            let keys = [key1, key2, key3];
+
+            let value_1 = felt!("0x2");
+            let value_2 = felt!("0x3");
+
+            uut.set(key1, value_1).unwrap();
+            uut.set(key2, value_2).unwrap();
+            let root = uut.commit().unwrap();
+
+            let uut = MerkleTree::<_, PoseidonHash>::load("test", &transaction, root).unwrap();
+
+            let proofs = get_proofs(&keys, &uut).unwrap();
+
+            let verified_1 = verify_proof::<PoseidonHash>(root, key1, value_1, &proofs[0]).unwrap();
+            assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
+
+            let verified_3 =
+                verify_proof::<PoseidonHash>(root, key3, Felt::ZERO, &proofs[2]).unwrap();
+            assert_eq!(verified_3, Membership::NonMember, "Failed to prove key3");
+
+            // Verifying with the wrong hash function must fail.
+            assert_eq!(
+                verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]),
+                None
+            );
+        }
+
         #[test]
         fn double_binary() {
             let mut conn = rusqlite::Connection::open_in_memory().unwrap();
@@ -1948,13 +2142,14 @@ mod tests {
             let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
 
             let proofs = get_proofs(&keys, &uut).unwrap();
-            let verified_1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_1 = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
             assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
 
-            let verified_2 = verify_proof(root, key2, value_2, &proofs[1]).unwrap();
+            let verified_2 = verify_proof::<PedersenHash>(root, key2, value_2, &proofs[1]).unwrap();
             assert_eq!(verified_2, Membership::Member, "Failed to prove key2");
 
-            let verified_key3 = verify_proof(root, key3, value_3, &proofs[2]).unwrap();
+            let verified_key3 =
+                verify_proof::<PedersenHash>(root, key3, value_3, &proofs[2]).unwrap();
             assert_eq!(verified_key3, Membership::Member, "Failed to prove key3");
         }
 
@@ -1983,7 +2178,7 @@ mod tests {
             let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
 
             let proofs = get_proofs(&keys, &uut).unwrap();
-            let verified_1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_1 = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
             assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
         }
 
@@ -2012,7 +2207,7 @@ mod tests {
             let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
 
             let proofs = get_proofs(&keys, &uut).unwrap();
-            let verified_1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_1 = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
             assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
         }
 
@@ -2041,7 +2236,7 @@ mod tests {
             let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
 
             let proofs = get_proofs(&keys, &uut).unwrap();
-            let verified_1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_1 = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
             assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
         }
 
@@ -2075,10 +2270,10 @@ mod tests {
             let uut = MerkleTree::<_, PedersenHash>::load("test", &transaction, root).unwrap();
 
             let proofs = get_proofs(&keys, &uut).unwrap();
-            let verified_1 = verify_proof(root, key1, value_1, &proofs[0]).unwrap();
+            let verified_1 = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]).unwrap();
             assert_eq!(verified_1, Membership::Member, "Failed to prove key1");
 
-            let verified_2 = verify_proof(root, key2, value_2, &proofs[1]).unwrap();
+            let verified_2 = verify_proof::<PedersenHash>(root, key2, value_2, &proofs[1]).unwrap();
             assert_eq!(verified_2, Membership::Member, "Failed to prove key2");
         }
 
@@ -2132,7 +2327,8 @@ mod tests {
                 .zip(random_tree.values.iter())
                 .enumerate()
                 .for_each(|(i, (k, v))| {
-                    let verified = verify_proof(random_tree.root, k, *v, &proofs[i]).unwrap();
+                    let verified =
+                        verify_proof::<PedersenHash>(random_tree.root, k, *v, &proofs[i]).unwrap();
                     assert_eq!(verified, Membership::NonMember);
                 });
         }
@@ -2162,7 +2358,8 @@ mod tests {
                 .zip(inexistent_values.iter())
                 .enumerate()
                 .for_each(|(i, (k, v))| {
-                    let verified = verify_proof(random_tree.root, k, *v, &proofs[i]);
+                    let verified =
+                        verify_proof::<PedersenHash>(random_tree.root, k, *v, &proofs[i]);
                     assert!(verified.is_none());
                 });
         }
@@ -2205,7 +2402,7 @@ mod tests {
                 _ => unreachable!(),
             };
 
-            let verified = verify_proof(root, key1, value_1, &proofs[0]);
+            let verified = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]);
             assert!(verified.is_none());
         }
 
@@ -2247,7 +2444,7 @@ mod tests {
                 _ => unreachable!(),
             };
 
-            let verified = verify_proof(root, key1, value_1, &proofs[0]);
+            let verified = verify_proof::<PedersenHash>(root, key1, value_1, &proofs[0]);
             assert!(verified.is_none());
         }
     }
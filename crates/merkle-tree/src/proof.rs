@@ -0,0 +1,233 @@
+//! Verification of the proofs generated by the [state trees](crate::state_tree) against a
+//! block's [StateCommitment].
+//!
+//! Since StarkNet 0.11.0 the state commitment is a combination of the storage and class commitments,
+//! so a proof in either tree has to be combined with the root of the other tree. Prior to that
+//! the state commitment is equal to the storage commitment and there is no class commitment tree.
+use crate::merkle_tree::{Membership, ProofNode};
+use crate::state_tree::{ClassCommitmentTree, StorageCommitmentTree};
+use crate::{Hash, PedersenHash, PoseidonHash};
+use pathfinder_common::{
+    calculate_class_commitment_leaf_hash, CasmHash, ClassCommitment, ClassCommitmentLeafHash,
+    ContractAddress, ContractStateHash, SierraHash, StateCommitment, StorageCommitment,
+};
+use stark_hash::Felt;
+
+/// Verifies the proof of a contract in the storage commitment tree against `state_commitment`.
+///
+/// `class_commitment` must be [ClassCommitment::ZERO] for blocks prior to StarkNet 0.11.0.
+/// `contract_state_hash` is the expected leaf value, or `None` if the proof is expected to be one
+/// of non-membership.
+///
+/// Returns `None` if the proof is invalid or does not match the expected membership.
+pub fn verify_contract_proof(
+    state_commitment: StateCommitment,
+    class_commitment: ClassCommitment,
+    contract_address: ContractAddress,
+    contract_state_hash: Option<ContractStateHash>,
+    contract_proof: &[ProofNode],
+) -> Option<Membership> {
+    let storage_commitment = if class_commitment == ClassCommitment::ZERO {
+        StorageCommitment(state_commitment.0)
+    } else {
+        let storage_commitment = StorageCommitment(proof_root::<PedersenHash>(contract_proof)?);
+        if StateCommitment::calculate(storage_commitment, class_commitment) != state_commitment {
+            return None;
+        }
+        storage_commitment
+    };
+
+    let value = contract_state_hash.unwrap_or(ContractStateHash(Felt::ZERO));
+    let membership = StorageCommitmentTree::verify_proof(
+        storage_commitment,
+        contract_address,
+        value,
+        contract_proof,
+    )?;
+
+    expected_membership(membership, contract_state_hash.is_some())
+}
+
+/// Verifies the proof of a class in the class commitment tree against `state_commitment`.
+///
+/// `compiled_class_hash` is the expected compiled class hash of the class, or `None` if the
+/// proof is expected to be one of non-membership.
+///
+/// Returns `None` if the proof is invalid or does not match the expected membership.
+pub fn verify_class_proof(
+    state_commitment: StateCommitment,
+    storage_commitment: StorageCommitment,
+    class: SierraHash,
+    compiled_class_hash: Option<CasmHash>,
+    class_proof: &[ProofNode],
+) -> Option<Membership> {
+    let class_commitment = ClassCommitment(proof_root::<PoseidonHash>(class_proof)?);
+    if StateCommitment::calculate(storage_commitment, class_commitment) != state_commitment {
+        return None;
+    }
+
+    let value = compiled_class_hash
+        .map(calculate_class_commitment_leaf_hash)
+        .unwrap_or(ClassCommitmentLeafHash(Felt::ZERO));
+    let membership =
+        ClassCommitmentTree::verify_proof(class_commitment, class, value, class_proof)?;
+
+    expected_membership(membership, compiled_class_hash.is_some())
+}
+
+/// Returns the root hash implied by the proof, which is the hash of its first node.
+///
+/// An empty proof implies an empty tree. Returns `None` if the proof is ill-formed.
+fn proof_root<H: Hash>(proof: &[ProofNode]) -> Option<Felt> {
+    match proof.first() {
+        Some(ProofNode::Edge(edge)) if edge.path.len() > 251 => None,
+        Some(node) => Some(node.hash::<H>()),
+        None => Some(Felt::ZERO),
+    }
+}
+
+fn expected_membership(membership: Membership, expect_member: bool) -> Option<Membership> {
+    match (&membership, expect_member) {
+        (Membership::Member, true) | (Membership::NonMember, false) => Some(membership),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_tree::{ClassCommitmentTree, StorageCommitmentTree};
+    use pathfinder_common::felt;
+
+    #[test]
+    fn contract() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+
+        let address = ContractAddress::new_or_panic(felt!("0x123"));
+        let missing = ContractAddress::new_or_panic(felt!("0x456"));
+        let state_hash = ContractStateHash(felt!("0xabc"));
+
+        let mut tree = StorageCommitmentTree::load(&tx, StorageCommitment::ZERO).unwrap();
+        tree.set(address, state_hash).unwrap();
+        let storage_commitment = tree.apply().unwrap();
+
+        let class_commitment = ClassCommitment(felt!("0xdef"));
+        let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
+
+        let tree = StorageCommitmentTree::load(&tx, storage_commitment).unwrap();
+        let proof = tree.get_proof(&address).unwrap();
+        let non_member_proof = tree.get_proof(&missing).unwrap();
+
+        assert_eq!(
+            verify_contract_proof(
+                state_commitment,
+                class_commitment,
+                address,
+                Some(state_hash),
+                &proof
+            ),
+            Some(Membership::Member)
+        );
+        assert_eq!(
+            verify_contract_proof(
+                state_commitment,
+                class_commitment,
+                missing,
+                None,
+                &non_member_proof
+            ),
+            Some(Membership::NonMember)
+        );
+        // Wrong leaf value
+        assert_eq!(
+            verify_contract_proof(
+                state_commitment,
+                class_commitment,
+                address,
+                Some(ContractStateHash(felt!("0x1"))),
+                &proof
+            ),
+            None
+        );
+        // Wrong class commitment
+        assert_eq!(
+            verify_contract_proof(
+                state_commitment,
+                ClassCommitment(felt!("0x1")),
+                address,
+                Some(state_hash),
+                &proof
+            ),
+            None
+        );
+        // Pre-0.11.0 blocks
+        assert_eq!(
+            verify_contract_proof(
+                StateCommitment(storage_commitment.0),
+                ClassCommitment::ZERO,
+                address,
+                Some(state_hash),
+                &proof
+            ),
+            Some(Membership::Member)
+        );
+    }
+
+    #[test]
+    fn class() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        let tx = conn.transaction().unwrap();
+
+        let class = SierraHash(felt!("0x123"));
+        let missing = SierraHash(felt!("0x456"));
+        let compiled_class_hash = CasmHash(felt!("0xabc"));
+
+        let mut tree = ClassCommitmentTree::load(&tx, ClassCommitment::ZERO).unwrap();
+        tree.set(
+            class,
+            calculate_class_commitment_leaf_hash(compiled_class_hash),
+        )
+        .unwrap();
+        let class_commitment = tree.apply().unwrap();
+
+        let storage_commitment = StorageCommitment(felt!("0xdef"));
+        let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
+
+        let tree = ClassCommitmentTree::load(&tx, class_commitment).unwrap();
+        let proof = tree.get_proof(&class).unwrap();
+        let non_member_proof = tree.get_proof(&missing).unwrap();
+
+        assert_eq!(
+            verify_class_proof(
+                state_commitment,
+                storage_commitment,
+                class,
+                Some(compiled_class_hash),
+                &proof
+            ),
+            Some(Membership::Member)
+        );
+        assert_eq!(
+            verify_class_proof(
+                state_commitment,
+                storage_commitment,
+                missing,
+                None,
+                &non_member_proof
+            ),
+            Some(Membership::NonMember)
+        );
+        // Claiming membership of a missing class
+        assert_eq!(
+            verify_class_proof(
+                state_commitment,
+                storage_commitment,
+                missing,
+                Some(compiled_class_hash),
+                &non_member_proof
+            ),
+            None
+        );
+    }
+}
@@ -1,13 +1,14 @@
-//! Contains the [StorageCommitmentTree] and [ContractsStateTree] trees, which combined
-//! store the total StarkNet state.
+//! Contains the [StorageCommitmentTree], [ContractsStateTree] and [ClassCommitmentTree] trees,
+//! which combined store the total StarkNet state.
 //!
 //! These are abstractions built-on the [Binary Merkle-Patricia Tree](MerkleTree).
 
 use crate::{
     merkle_node::Node,
-    merkle_tree::{MerkleTree, ProofNode, Visit},
+    merkle_tree::{verify_proof, Membership, MerkleTree, ProofNode, Visit},
 };
 use crate::{PedersenHash, PoseidonHash};
+use anyhow::Context;
 use bitvec::{prelude::Msb0, slice::BitSlice};
 use pathfinder_common::{
     ClassCommitment, ClassCommitmentLeafHash, ContractAddress, ContractRoot, ContractStateHash,
@@ -15,6 +16,7 @@ use pathfinder_common::{
 };
 use pathfinder_storage::merkle_tree::RcNodeStorage;
 use rusqlite::Transaction;
+use stark_hash::Felt;
 use std::ops::ControlFlow;
 
 /// A Binary Merkle-Patricia Tree which contains
@@ -42,10 +44,56 @@ impl<'tx> ContractsStateTree<'tx, '_> {
         self.tree.get_proof(key)
     }
 
+    /// Verifies a proof generated by [`Self::get_proof`] against the contract's `root`.
+    /// See [`verify_proof`] for details, `value` is ignored for proofs of non-membership.
+    pub fn verify_proof(
+        root: ContractRoot,
+        key: StorageAddress,
+        value: StorageValue,
+        proof: &[ProofNode],
+    ) -> Option<Membership> {
+        verify_proof::<PedersenHash>(root.0, key.view_bits(), value.0, proof)
+    }
+
     pub fn set(&mut self, address: StorageAddress, value: StorageValue) -> anyhow::Result<()> {
         self.tree.set(address.view_bits(), value.0)
     }
 
+    /// Returns up to `limit` storage entries in ascending key order, starting at `start`.
+    ///
+    /// Subtrees which lie entirely before `start` are skipped without being loaded.
+    pub fn entries(
+        &self,
+        start: StorageAddress,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(StorageAddress, StorageValue)>> {
+        let start = start.view_bits();
+        let mut entries = Vec::new();
+
+        if limit == 0 {
+            return Ok(entries);
+        }
+
+        let mut visitor = |node: &Node, path: &BitSlice<Msb0, u8>| match node {
+            Node::Leaf(value) => {
+                if path >= start {
+                    let key = Felt::from_bits(path).expect("Storage keys fit in a felt");
+                    entries.push((StorageAddress::new_or_panic(key), StorageValue(*value)));
+
+                    if entries.len() == limit {
+                        return ControlFlow::Break(());
+                    }
+                }
+                ControlFlow::Continue(Visit::ContinueDeeper)
+            }
+            _ if path < &start[..path.len()] => ControlFlow::Continue(Visit::StopSubtree),
+            _ => ControlFlow::Continue(Visit::ContinueDeeper),
+        };
+        self.tree.dfs(&mut visitor)?;
+
+        Ok(entries)
+    }
+
     /// Applies and persists any changes. Returns the new tree root.
     pub fn apply(self) -> anyhow::Result<ContractRoot> {
         let root = self.tree.commit()?;
@@ -59,6 +107,11 @@ impl<'tx> ContractsStateTree<'tx, '_> {
     ) -> anyhow::Result<Option<B>> {
         self.tree.dfs(f)
     }
+
+    /// Rehashes every node of the tree. See [`MerkleTree::verify`].
+    pub fn verify(&self) -> anyhow::Result<()> {
+        self.tree.verify(|_, _| Ok(()))
+    }
 }
 
 /// A Binary Merkle-Patricia Tree which contains StarkNet's storage commitment.
@@ -101,6 +154,17 @@ impl<'tx> StorageCommitmentTree<'tx, '_> {
         self.tree.get_proof(address.view_bits())
     }
 
+    /// Verifies a proof generated by [`Self::get_proof`] against the storage commitment `root`.
+    /// See [`verify_proof`] for details, `value` is ignored for proofs of non-membership.
+    pub fn verify_proof(
+        root: StorageCommitment,
+        address: ContractAddress,
+        value: ContractStateHash,
+        proof: &[ProofNode],
+    ) -> Option<Membership> {
+        verify_proof::<PedersenHash>(root.0, address.view_bits(), value.0, proof)
+    }
+
     /// See [`MerkleTree::dfs`]
     pub fn dfs<B, F: FnMut(&Node, &BitSlice<Msb0, u8>) -> ControlFlow<B, Visit>>(
         &self,
@@ -108,6 +172,21 @@ impl<'tx> StorageCommitmentTree<'tx, '_> {
     ) -> anyhow::Result<Option<B>> {
         self.tree.dfs(f)
     }
+
+    /// Rehashes every node of the tree, calling `contract` for each of its leaves. See
+    /// [`MerkleTree::verify`].
+    pub fn verify<F>(&self, mut contract: F) -> anyhow::Result<()>
+    where
+        F: FnMut(ContractAddress, ContractStateHash) -> anyhow::Result<()>,
+    {
+        self.tree.verify(|path, value| {
+            let address = Felt::from_bits(path).context("Contract address out of range")?;
+            contract(
+                ContractAddress::new_or_panic(address),
+                ContractStateHash(value),
+            )
+        })
+    }
 }
 
 /// Merkle tree which contains Starknet's class commitment.
@@ -133,9 +212,82 @@ impl<'tx> ClassCommitmentTree<'tx, '_> {
         self.tree.set(class.view_bits(), value.0)
     }
 
+    pub fn get(&self, class: SierraHash) -> anyhow::Result<Option<ClassCommitmentLeafHash>> {
+        let value = self.tree.get(class.view_bits())?;
+        Ok(value.map(ClassCommitmentLeafHash))
+    }
+
+    /// Generates a proof for the given `class`. See [`MerkleTree::get_proof`].
+    pub fn get_proof(&self, class: &SierraHash) -> anyhow::Result<Vec<ProofNode>> {
+        self.tree.get_proof(class.view_bits())
+    }
+
+    /// Verifies a proof generated by [`Self::get_proof`] against the class commitment `root`.
+    /// See [`verify_proof`] for details, `value` is ignored for proofs of non-membership.
+    pub fn verify_proof(
+        root: ClassCommitment,
+        class: SierraHash,
+        value: ClassCommitmentLeafHash,
+        proof: &[ProofNode],
+    ) -> Option<Membership> {
+        verify_proof::<PoseidonHash>(root.0, class.view_bits(), value.0, proof)
+    }
+
+    /// Rehashes every node of the tree, calling `class` for each of its leaves. See
+    /// [`MerkleTree::verify`].
+    pub fn verify<F>(&self, mut class: F) -> anyhow::Result<()>
+    where
+        F: FnMut(SierraHash, ClassCommitmentLeafHash) -> anyhow::Result<()>,
+    {
+        self.tree.verify(|path, value| {
+            let hash = Felt::from_bits(path).context("Class hash out of range")?;
+            class(SierraHash(hash), ClassCommitmentLeafHash(value))
+        })
+    }
+
     /// Applies and persists any changes. Returns the new global root.
     pub fn apply(self) -> anyhow::Result<ClassCommitment> {
         let root = self.tree.commit()?;
         Ok(ClassCommitment(root))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    #[test]
+    fn contract_storage_entries() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        let transaction = conn.transaction().unwrap();
+
+        let keys = [felt!("0x1"), felt!("0x5"), felt!("0x9"), felt!("0xa")]
+            .map(StorageAddress::new_or_panic);
+
+        let mut tree = ContractsStateTree::load(&transaction, ContractRoot(Felt::ZERO)).unwrap();
+        for key in keys {
+            tree.set(key, StorageValue(*key.get())).unwrap();
+        }
+        let root = tree.apply().unwrap();
+        let tree = ContractsStateTree::load(&transaction, root).unwrap();
+
+        let entries = |start: Felt, limit: usize| {
+            tree.entries(StorageAddress::new_or_panic(start), limit)
+                .unwrap()
+                .into_iter()
+                .map(|(key, value)| {
+                    assert_eq!(key.get(), &value.0);
+                    key
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(entries(Felt::ZERO, 10), keys);
+        assert_eq!(entries(Felt::ZERO, 2), keys[..2]);
+        assert_eq!(entries(felt!("0x5"), 2), keys[1..3]);
+        assert_eq!(entries(felt!("0x6"), 10), keys[2..]);
+        assert!(entries(felt!("0xb"), 10).is_empty());
+        assert!(entries(Felt::ZERO, 0).is_empty());
+    }
+}
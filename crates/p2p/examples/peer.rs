@@ -108,7 +108,9 @@ async fn main() -> anyhow::Result<()> {
 
                 let message = proto::propagation::Message::NewBlockHeader(
                     proto::propagation::NewBlockHeader {
+                        block_hash: Felt::ZERO,
                         header: Default::default(),
+                        signatures: Default::default(),
                     },
                 );
                 match client
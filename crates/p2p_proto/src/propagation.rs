@@ -65,7 +65,9 @@ impl ToProtobuf<proto::propagation::Message> for Message {
         let message = match self {
             Message::NewBlockHeader(h) => proto::propagation::message::Message::NewBlockHeader(
                 proto::propagation::NewBlockHeader {
+                    block_hash: Some(h.block_hash.to_protobuf()),
                     header: Some(h.header.to_protobuf()),
+                    signatures: h.signatures.to_protobuf(),
                 },
             ),
             Message::NewBlockBody(h) => proto::propagation::message::Message::NewBlockBody(
@@ -91,7 +93,29 @@ impl ToProtobuf<proto::propagation::Message> for Message {
 #[cfg_attr(feature = "test-utils", derive(Dummy))]
 #[protobuf(name = "crate::proto::propagation::NewBlockHeader")]
 pub struct NewBlockHeader {
+    pub block_hash: Felt,
     pub header: BlockHeader,
+    pub signatures: Vec<ConsensusSignature>,
+}
+
+/// A signature over a block hash, in the encoding of the network's consensus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "test-utils", derive(Dummy))]
+pub struct ConsensusSignature(pub Vec<u8>);
+
+impl ToProtobuf<Vec<u8>> for ConsensusSignature {
+    fn to_protobuf(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl TryFromProtobuf<Vec<u8>> for ConsensusSignature {
+    fn try_from_protobuf(
+        input: Vec<u8>,
+        _field_name: &'static str,
+    ) -> Result<Self, std::io::Error> {
+        Ok(Self(input))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, ToProtobuf, TryFromProtobuf)]
@@ -0,0 +1,263 @@
+//! Benchmarks of the paths which limit sync throughput: decoding gateway responses, persisting
+//! blocks and querying the persisted events.
+//!
+//! Blocks and state updates are the recorded gateway responses of `starknet-gateway-test-fixtures`,
+//! which can be extended using the `capture_fixtures` example. Merkle tree insertion is covered by
+//! the `merkle_tree` benchmark.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use pathfinder_common::{
+    ClassCommitment, ContractAddress, EventData, EventKey, Fee, GasPrice, SequencerAddress,
+    StarknetBlockHash, StarknetBlockNumber, StarknetBlockTimestamp, StarknetTransactionHash,
+    StarknetTransactionIndex, StateCommitment, StorageCommitment, TransactionNonce,
+};
+use pathfinder_storage::{
+    CanonicalBlocksTable, StarknetBlock, StarknetBlocksTable, StarknetEventFilter,
+    StarknetEventsTable, StarknetStateUpdatesTable, StarknetTransactionsTable, Storage,
+    V02KeyFilter, V03KeyFilter,
+};
+use stark_hash::Felt;
+use starknet_gateway_test_fixtures::{v0_11_0, v0_9_0};
+use starknet_gateway_types::reply::{
+    transaction::{Event, InvokeTransaction, InvokeTransactionV1, Receipt, Transaction},
+    Block, StateUpdate,
+};
+
+/// Number of blocks in the event query benchmarks.
+const EVENT_BLOCKS: u64 = 1000;
+const TRANSACTIONS_PER_BLOCK: u64 = 10;
+const EVENTS_PER_TRANSACTION: u64 = 5;
+/// Number of distinct event keys and emitting contracts.
+const EVENT_VARIANTS: u64 = 20;
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+
+    let block = v0_9_0::block::NUMBER_231579;
+    group.throughput(criterion::Throughput::Bytes(block.len() as u64));
+    group.bench_function("block", |b| {
+        b.iter(|| serde_json::from_str::<Block>(black_box(block)).unwrap())
+    });
+
+    let state_update = v0_11_0::state_update::NUMBER_315700;
+    group.throughput(criterion::Throughput::Bytes(state_update.len() as u64));
+    group.bench_function("state update", |b| {
+        b.iter(|| serde_json::from_str::<StateUpdate>(black_box(state_update)).unwrap())
+    });
+
+    group.finish();
+}
+
+/// Persists a block the way L2 sync does, minus the class downloads.
+fn persist(c: &mut Criterion) {
+    // The block and state update are from different blocks, which does not matter here as nothing
+    // is verified.
+    let block = serde_json::from_str::<Block>(v0_9_0::block::NUMBER_231579).unwrap();
+    let state_update =
+        serde_json::from_str::<StateUpdate>(v0_11_0::state_update::NUMBER_315700).unwrap();
+
+    c.bench_function("persist block", |b| {
+        b.iter_batched(
+            || {
+                (
+                    Storage::in_memory().unwrap(),
+                    block.clone(),
+                    state_update.clone(),
+                )
+            },
+            |(storage, block, state_update)| {
+                let mut connection = storage.connection().unwrap();
+                let tx = connection.transaction().unwrap();
+
+                let (storage_commitment, class_commitment) =
+                    pathfinder_lib::state::update_starknet_state(&tx, &state_update).unwrap();
+
+                let stored_block = StarknetBlock {
+                    number: block.block_number,
+                    hash: block.block_hash,
+                    root: block.state_commitment,
+                    timestamp: block.timestamp,
+                    gas_price: block.gas_price.unwrap_or(GasPrice::ZERO),
+                    sequencer_address: block
+                        .sequencer_address
+                        .unwrap_or(SequencerAddress(Felt::ZERO)),
+                    transaction_commitment: None,
+                    event_commitment: None,
+                };
+                StarknetBlocksTable::insert(
+                    &tx,
+                    &stored_block,
+                    block.starknet_version.as_deref(),
+                    storage_commitment,
+                    class_commitment,
+                )
+                .unwrap();
+                StarknetStateUpdatesTable::insert(&tx, block.block_hash, &state_update.into())
+                    .unwrap();
+                CanonicalBlocksTable::insert(&tx, block.block_number, block.block_hash).unwrap();
+
+                let transaction_data = block
+                    .transactions
+                    .into_iter()
+                    .zip(block.transaction_receipts.into_iter())
+                    .collect::<Vec<_>>();
+                StarknetTransactionsTable::upsert(
+                    &tx,
+                    block.block_hash,
+                    block.block_number,
+                    &transaction_data,
+                )
+                .unwrap();
+
+                tx.commit().unwrap();
+            },
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+/// Creates a database with [EVENT_BLOCKS] blocks of synthetic events.
+fn event_storage() -> Storage {
+    let storage = Storage::in_memory().unwrap();
+    let mut connection = storage.connection().unwrap();
+    let tx = connection.transaction().unwrap();
+
+    for number in 0..EVENT_BLOCKS {
+        let block = StarknetBlock {
+            number: StarknetBlockNumber::new_or_panic(number),
+            hash: StarknetBlockHash(Felt::from(number + 1)),
+            root: StateCommitment::ZERO,
+            timestamp: StarknetBlockTimestamp::new_or_panic(number),
+            gas_price: GasPrice::ZERO,
+            sequencer_address: SequencerAddress(Felt::ZERO),
+            transaction_commitment: None,
+            event_commitment: None,
+        };
+        StarknetBlocksTable::insert(
+            &tx,
+            &block,
+            None,
+            StorageCommitment::ZERO,
+            ClassCommitment::ZERO,
+        )
+        .unwrap();
+        CanonicalBlocksTable::insert(&tx, block.number, block.hash).unwrap();
+
+        let transaction_data = (0..TRANSACTIONS_PER_BLOCK)
+            .map(|i| {
+                let index = number * TRANSACTIONS_PER_BLOCK + i;
+                let transaction_hash = StarknetTransactionHash(Felt::from(index + 1));
+                let transaction = Transaction::Invoke(InvokeTransaction::V1(InvokeTransactionV1 {
+                    calldata: vec![],
+                    sender_address: ContractAddress::new_or_panic(Felt::from(index + 1)),
+                    max_fee: Fee::ZERO,
+                    signature: vec![],
+                    nonce: TransactionNonce::ZERO,
+                    transaction_hash,
+                }));
+                let events = (0..EVENTS_PER_TRANSACTION)
+                    .map(|e| {
+                        let variant = (index * EVENTS_PER_TRANSACTION + e) % EVENT_VARIANTS;
+                        Event {
+                            data: vec![EventData(Felt::from(e))],
+                            from_address: ContractAddress::new_or_panic(Felt::from(variant + 1)),
+                            keys: vec![EventKey(Felt::from(variant))],
+                        }
+                    })
+                    .collect();
+                let receipt = Receipt {
+                    actual_fee: None,
+                    events,
+                    execution_resources: None,
+                    l1_to_l2_consumed_message: None,
+                    l2_to_l1_messages: vec![],
+                    transaction_hash,
+                    transaction_index: StarknetTransactionIndex::new_or_panic(i),
+                    execution_status: Default::default(),
+                    revert_error: None,
+                };
+                (transaction, receipt)
+            })
+            .collect::<Vec<_>>();
+        StarknetTransactionsTable::upsert(&tx, block.hash, block.number, &transaction_data)
+            .unwrap();
+    }
+
+    tx.commit().unwrap();
+    storage
+}
+
+fn events(c: &mut Criterion) {
+    let storage = event_storage();
+    let mut connection = storage.connection().unwrap();
+    let tx = connection.transaction().unwrap();
+
+    let filter =
+        |contract_address: Option<ContractAddress>, keys: Vec<EventKey>| StarknetEventFilter {
+            from_block: None,
+            to_block: None,
+            contract_address,
+            keys: V02KeyFilter(keys),
+            page_size: StarknetEventsTable::PAGE_SIZE_LIMIT,
+            offset: 0,
+        };
+
+    let mut group = c.benchmark_group("events");
+
+    let unfiltered = filter(None, vec![]);
+    group.bench_function("unfiltered", |b| {
+        b.iter(|| StarknetEventsTable::get_events(&tx, black_box(&unfiltered)).unwrap())
+    });
+
+    let by_address = filter(
+        Some(ContractAddress::new_or_panic(Felt::from(1u64))),
+        vec![],
+    );
+    group.bench_function("by contract address", |b| {
+        b.iter(|| StarknetEventsTable::get_events(&tx, black_box(&by_address)).unwrap())
+    });
+
+    let by_key = filter(None, vec![EventKey(Felt::from(0u64))]);
+    group.bench_function("by key", |b| {
+        b.iter(|| StarknetEventsTable::get_events(&tx, black_box(&by_key)).unwrap())
+    });
+
+    // Such as the ERC-20 `Transfer` events of a token. Looked up by the index on the address and
+    // first key of events, while the same query with the key in any position takes the plan of
+    // the full-text index and the bloom filters.
+    let by_address_and_first_key = StarknetEventFilter {
+        from_block: None,
+        to_block: None,
+        contract_address: Some(ContractAddress::new_or_panic(Felt::from(1u64))),
+        keys: V03KeyFilter(vec![vec![EventKey(Felt::from(0u64))]]),
+        page_size: StarknetEventsTable::PAGE_SIZE_LIMIT,
+        offset: 0,
+    };
+    group.bench_function("by contract address and first key", |b| {
+        b.iter(|| {
+            StarknetEventsTable::get_events(&tx, black_box(&by_address_and_first_key)).unwrap()
+        })
+    });
+
+    let by_address_and_key = filter(
+        Some(ContractAddress::new_or_panic(Felt::from(1u64))),
+        vec![EventKey(Felt::from(0u64))],
+    );
+    group.bench_function("by contract address and key", |b| {
+        b.iter(|| StarknetEventsTable::get_events(&tx, black_box(&by_address_and_key)).unwrap())
+    });
+
+    // Pages deep into the results, which is what slow clients do.
+    let last_page = StarknetEventFilter {
+        offset: (EVENT_BLOCKS * TRANSACTIONS_PER_BLOCK * EVENTS_PER_TRANSACTION) as usize
+            - StarknetEventsTable::PAGE_SIZE_LIMIT,
+        ..filter(None, vec![])
+    };
+    group.bench_function("last page", |b| {
+        b.iter(|| StarknetEventsTable::get_events(&tx, black_box(&last_page)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, decode, persist, events);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! Downloads blocks, state updates and classes from the feeder gateway and stores them as test
+//! fixtures, for use in the `starknet-gateway-test-fixtures` crate.
+//!
+//! Blocks and state updates are stored as JSON with sorted keys and four space indentation, so that
+//! captures of the same data are identical. Classes are stored as zstd compressed JSON, as they
+//! are rather large.
+//!
+//! For example, to capture fixtures for a new StarkNet version:
+//! ```text
+//! cargo run --example capture_fixtures -- testnet crates/gateway-test-fixtures/fixtures/0.11.1 \
+//!     block:pending state-update:800000 class:0x1234
+//! ```
+//! which creates `block/pending.json`, `state-update/800000.json` and `class/0x1234.json.zst`.
+
+use anyhow::Context;
+use pathfinder_common::Chain;
+use std::path::{Path, PathBuf};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    tracing_subscriber::fmt::init();
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.len() < 4 {
+        println!(
+            "USAGE: {} <mainnet|testnet|testnet2|integration> <output_dir> <fixture>...
+
+Each fixture is one of:
+    block:<number|latest|pending>
+    state-update:<number|latest|pending>
+    class:<class hash>",
+            args.first()
+                .map(String::as_str)
+                .unwrap_or("capture_fixtures")
+        );
+        std::process::exit(1);
+    }
+
+    let chain = match args[1].as_str() {
+        "mainnet" => Chain::Mainnet,
+        "testnet" => Chain::Testnet,
+        "testnet2" => Chain::Testnet2,
+        "integration" => Chain::Integration,
+        other => anyhow::bail!("Unsupported network: {other}"),
+    };
+    let output_dir = PathBuf::from(&args[2]);
+    let fixtures = args[3..]
+        .iter()
+        .map(String::as_str)
+        .map(Fixture::parse)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let feeder_gateway = reqwest::Url::parse(match chain {
+        Chain::Mainnet => "https://alpha-mainnet.starknet.io/feeder_gateway/",
+        Chain::Testnet => "https://alpha4.starknet.io/feeder_gateway/",
+        Chain::Testnet2 => "https://alpha4-2.starknet.io/feeder_gateway/",
+        Chain::Integration => "https://external.integration.starknet.io/feeder_gateway/",
+        Chain::Custom => unreachable!("Custom networks are not supported"),
+    })?;
+    let client = reqwest::Client::new();
+
+    for fixture in fixtures {
+        let url = fixture.url(&feeder_gateway)?;
+        let bytes = client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Downloading {url}"))?
+            .bytes()
+            .await
+            .with_context(|| format!("Downloading {url}"))?;
+
+        let path = fixture.path(&output_dir);
+        let contents = fixture
+            .format(&bytes)
+            .with_context(|| format!("Formatting {url}"))?;
+
+        std::fs::create_dir_all(path.parent().expect("Fixture paths have a parent"))
+            .context("Creating fixture directory")?;
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Writing fixture to {}", path.display()))?;
+
+        tracing::info!(path=%path.display(), "Captured fixture");
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Fixture {
+    Block(String),
+    StateUpdate(String),
+    Class(String),
+}
+
+impl Fixture {
+    fn parse(fixture: &str) -> anyhow::Result<Self> {
+        let (kind, id) = fixture
+            .split_once(':')
+            .with_context(|| format!("Fixture {fixture} is not of the form <kind>:<id>"))?;
+
+        match kind {
+            "block" => Ok(Self::Block(id.to_owned())),
+            "state-update" => Ok(Self::StateUpdate(id.to_owned())),
+            "class" => Ok(Self::Class(id.to_owned())),
+            other => anyhow::bail!("Unknown fixture kind: {other}"),
+        }
+    }
+
+    fn url(&self, feeder_gateway: &reqwest::Url) -> anyhow::Result<reqwest::Url> {
+        let (method, param, id) = match self {
+            Fixture::Block(id) => ("get_block", "blockNumber", id),
+            Fixture::StateUpdate(id) => ("get_state_update", "blockNumber", id),
+            Fixture::Class(hash) => ("get_class_by_hash", "classHash", hash),
+        };
+
+        let mut url = feeder_gateway.join(method)?;
+        url.query_pairs_mut().append_pair(param, id);
+        Ok(url)
+    }
+
+    /// The location of the fixture, following the layout of the existing fixtures.
+    fn path(&self, output_dir: &Path) -> PathBuf {
+        let name = |id: &str| match id {
+            "0" => "genesis".to_owned(),
+            other => other.to_owned(),
+        };
+
+        match self {
+            Fixture::Block(id) => output_dir.join("block").join(format!("{}.json", name(id))),
+            Fixture::StateUpdate(id) => output_dir
+                .join("state-update")
+                .join(format!("{}.json", name(id))),
+            Fixture::Class(hash) => output_dir.join("class").join(format!("{hash}.json.zst")),
+        }
+    }
+
+    fn format(&self, bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+        use serde::Serialize;
+
+        let json: serde_json::Value = serde_json::from_slice(bytes).context("Parsing JSON")?;
+
+        let mut formatted = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut formatted, formatter);
+        json.serialize(&mut serializer)?;
+        formatted.push(b'\n');
+
+        match self {
+            Fixture::Block(_) | Fixture::StateUpdate(_) => Ok(formatted),
+            Fixture::Class(_) => zstd::encode_all(formatted.as_slice(), 10).context("Compressing"),
+        }
+    }
+}
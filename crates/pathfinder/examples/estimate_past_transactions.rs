@@ -355,14 +355,14 @@ struct SimpleDeployAccount {
     #[serde_as(as = "pathfinder_serde::TransactionVersionAsHexStr")]
     pub version: pathfinder_common::TransactionVersion,
     pub max_fee: pathfinder_common::Fee,
-    #[serde_as(as = "Vec<pathfinder_serde::TransactionSignatureElemAsDecimalStr>")]
+    #[serde_as(as = "Vec<pathfinder_serde::policy::Numeric<pathfinder_serde::policy::Gateway>>")]
     #[serde(default)]
     pub signature: Vec<pathfinder_common::TransactionSignatureElem>,
     #[serde(default = "default_transaction_nonce")]
     pub nonce: pathfinder_common::TransactionNonce,
 
     contract_address_salt: pathfinder_common::ContractAddressSalt,
-    #[serde_as(as = "Vec<pathfinder_serde::CallParamAsDecimalStr>")]
+    #[serde_as(as = "Vec<pathfinder_serde::policy::Numeric<pathfinder_serde::policy::Gateway>>")]
     pub constructor_calldata: Vec<pathfinder_common::CallParam>,
     pub class_hash: pathfinder_common::ClassHash,
 }
@@ -374,14 +374,14 @@ struct SimpleInvoke {
     #[serde_as(as = "Option<pathfinder_serde::TransactionVersionAsHexStr>")]
     pub version: Option<pathfinder_common::TransactionVersion>,
     pub max_fee: pathfinder_common::Fee,
-    #[serde_as(as = "Vec<pathfinder_serde::TransactionSignatureElemAsDecimalStr>")]
+    #[serde_as(as = "Vec<pathfinder_serde::policy::Numeric<pathfinder_serde::policy::Gateway>>")]
     #[serde(default)]
     pub signature: Vec<pathfinder_common::TransactionSignatureElem>,
     #[serde(default = "default_transaction_nonce")]
     pub nonce: pathfinder_common::TransactionNonce,
 
     contract_address: pathfinder_common::ContractAddress,
-    #[serde_as(as = "Vec<pathfinder_serde::CallParamAsDecimalStr>")]
+    #[serde_as(as = "Vec<pathfinder_serde::policy::Numeric<pathfinder_serde::policy::Gateway>>")]
     pub calldata: Vec<pathfinder_common::CallParam>,
     #[serde(default)]
     pub entry_point_selector: Option<pathfinder_common::EntryPoint>,
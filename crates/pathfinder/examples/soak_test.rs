@@ -0,0 +1,278 @@
+//! Soak test of sync's crash safety.
+//!
+//! Syncs against a mock feeder gateway whose chain keeps growing and reorging, and which fails
+//! random requests with rate limits, server errors, truncated bodies and responses slower than the
+//! client's timeout. The syncing process is killed at random points in time, and the database is
+//! checked for consistency after every kill. Finally the process is left to catch up with the
+//! gateway, after which the database must contain exactly the gateway's chain.
+//!
+//! ```text
+//! cargo run --release --example soak_test -- [duration in minutes] [seed]
+//! ```
+//!
+//! A failing run can be reproduced with the seed it logs, up to the timing of the kills.
+//!
+//! The syncing process is this binary, started with the `SOAK_TEST_GATEWAY` and
+//! `SOAK_TEST_DATABASE` environment variables.
+use anyhow::Context;
+use pathfinder_common::{Chain, StarknetBlockHash, StarknetBlockNumber, StateCommitment};
+use pathfinder_ethereum::provider::DisabledTransport;
+use pathfinder_lib::state;
+use pathfinder_storage::{
+    DatabaseLock, JournalMode, StarknetBlocksTable, StarknetStateUpdatesTable, Storage,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rusqlite::OptionalExtension;
+use starknet_gateway_client::test_utils::{InjectedError, MockGateway, ScriptedChain};
+use starknet_gateway_client::Client;
+use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::sync_state::SyncState;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const GATEWAY_VAR: &str = "SOAK_TEST_GATEWAY";
+const DATABASE_VAR: &str = "SOAK_TEST_DATABASE";
+
+/// How often the chain is changed and faults are injected.
+const TICK: Duration = Duration::from_millis(100);
+/// Longer than the timeout of the gateway client.
+const HANGING_RESPONSE: Duration = Duration::from_secs(150);
+const MAX_REORG_DEPTH: u64 = 5;
+/// How long the syncing process gets to catch up at the end of the test.
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    if std::env::var_os("RUST_LOG").is_none() {
+        std::env::set_var("RUST_LOG", "info");
+    }
+    tracing_subscriber::fmt::init();
+
+    if let Ok(gateway) = std::env::var(GATEWAY_VAR) {
+        let database = std::env::var_os(DATABASE_VAR).context("Database path is missing")?;
+        return sync(&gateway, database.into()).await;
+    }
+
+    let args = std::env::args().collect::<Vec<_>>();
+    let minutes = match args.get(1) {
+        Some(minutes) => minutes.parse::<u64>().context("Parsing duration")?,
+        None => 10,
+    };
+    let seed = match args.get(2) {
+        Some(seed) => seed.parse::<u64>().context("Parsing seed")?,
+        None => rand::random(),
+    };
+    tracing::info!(%minutes, %seed, "Starting soak test");
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let directory = tempfile::tempdir().context("Creating database directory")?;
+    let database = directory.path().join("soak_test.sqlite");
+
+    let mut chain = ScriptedChain::default();
+    chain.push_empty_blocks(10);
+    let gateway = MockGateway::spawn(chain);
+
+    let deadline = Instant::now() + Duration::from_secs(minutes * 60);
+    let mut kills = 0;
+    while Instant::now() < deadline {
+        let mut child = spawn_sync(&gateway, &database)?;
+
+        let lifetime = Duration::from_millis(rng.gen_range(500..5000));
+        let kill_at = Instant::now() + lifetime;
+        while Instant::now() < kill_at {
+            gateway.update(|chain| change_chain(chain, &mut rng));
+            tokio::time::sleep(TICK).await;
+        }
+
+        child.kill().await.context("Killing sync process")?;
+        kills += 1;
+
+        let blocks = check_database(&database)
+            .with_context(|| format!("Database is inconsistent after {kills} kills"))?;
+        tracing::info!(%kills, stored=%blocks.len(), "Database is consistent");
+    }
+
+    tracing::info!("Waiting for sync to catch up");
+    let expected = gateway.update(|chain| {
+        chain.clear_errors();
+        (0..)
+            .map_while(|number| chain.block(StarknetBlockNumber::new_or_panic(number)))
+            .map(|block| block.block_hash)
+            .collect::<Vec<_>>()
+    });
+    let mut child = spawn_sync(&gateway, &database)?;
+    let caught_up = async {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if latest_block_hash(&database)? == expected.last().copied() {
+                return anyhow::Ok(());
+            }
+        }
+    };
+    let caught_up = tokio::time::timeout(CATCH_UP_TIMEOUT, caught_up).await;
+    child.kill().await.context("Killing sync process")?;
+    caught_up.context("Sync did not catch up with the gateway")??;
+
+    let blocks = check_database(&database).context("Database is inconsistent")?;
+    anyhow::ensure!(
+        blocks == expected,
+        "Stored blocks do not match the gateway's chain"
+    );
+
+    tracing::info!(%kills, blocks=%blocks.len(), "Soak test passed");
+
+    Ok(())
+}
+
+/// Syncs the database from the gateway until killed, the way a node without Ethereum does.
+async fn sync(gateway: &str, database: PathBuf) -> anyhow::Result<()> {
+    let _lock = DatabaseLock::acquire(&database)?;
+    let storage = Storage::migrate(database, JournalMode::WAL)?;
+
+    let gateway = reqwest::Url::parse(gateway).context("Parsing gateway URL")?;
+    // Retrying would only slow down the test, as the process is killed regularly anyway.
+    let client = Client::with_base_url(gateway)?.disable_retry_for_tests();
+
+    let rate_limit = client.rate_limit().clone();
+    state::sync(
+        storage,
+        DisabledTransport,
+        Chain::Testnet,
+        pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+        client,
+        Arc::new(SyncState::default()),
+        state::l1::disabled,
+        state::l2::sync,
+        PendingData::default(),
+        Some(Duration::from_millis(500)),
+        // The blocks of the mock gateway have made up hashes.
+        state::l2::BlockValidationMode::AllowMismatch,
+        Vec::new(),
+        false,
+        Default::default(),
+        rate_limit,
+        // Download concurrently so that kills also interrupt prefetching.
+        NonZeroUsize::new(4).unwrap(),
+    )
+    .await
+}
+
+fn spawn_sync(gateway: &MockGateway, database: &Path) -> anyhow::Result<tokio::process::Child> {
+    let executable = std::env::current_exe().context("Locating executable")?;
+
+    tokio::process::Command::new(executable)
+        .env(GATEWAY_VAR, gateway.url().as_str())
+        .env(DATABASE_VAR, database)
+        .env("RUST_LOG", "warn")
+        .kill_on_drop(true)
+        .spawn()
+        .context("Spawning sync process")
+}
+
+/// Randomly extends or reorgs the chain, and injects faults.
+fn change_chain(chain: &mut ScriptedChain, rng: &mut StdRng) {
+    if rng.gen_bool(0.2) {
+        chain.push_empty_blocks(1);
+    }
+
+    let head = chain
+        .head()
+        .expect("Chain is never empty")
+        .block_number
+        .get();
+    if head > 0 && rng.gen_bool(0.005) {
+        let depth = rng.gen_range(1..=head.min(MAX_REORG_DEPTH));
+        chain.reorg(StarknetBlockNumber::new_or_panic(head - depth));
+    }
+
+    if rng.gen_bool(0.03) {
+        let method = match rng.gen_bool(0.5) {
+            true => "get_block",
+            false => "get_state_update",
+        };
+        let fault = match rng.gen_range(0..5) {
+            0 => InjectedError::Status(429),
+            1 => InjectedError::Status(503),
+            2 => InjectedError::Truncated,
+            3 => InjectedError::Delay(Duration::from_millis(rng.gen_range(0..3000))),
+            _ => InjectedError::Delay(HANGING_RESPONSE),
+        };
+        chain.inject_error(method, fault);
+    }
+}
+
+fn latest_block_hash(database: &Path) -> anyhow::Result<Option<StarknetBlockHash>> {
+    // Opened directly, as the syncing process holds the database lock.
+    let connection = rusqlite::Connection::open(database)?;
+    let latest = connection
+        .query_row(
+            "SELECT hash FROM starknet_blocks ORDER BY number DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(latest)
+}
+
+/// Checks that the database is consistent, and returns the hashes of the stored blocks.
+fn check_database(database: &Path) -> anyhow::Result<Vec<StarknetBlockHash>> {
+    // A killed process must not leave the database locked.
+    let _lock = DatabaseLock::acquire(database)?;
+    let storage = Storage::migrate(database.to_owned(), JournalMode::WAL)?;
+    let mut connection = storage.connection()?;
+    let tx = connection.transaction()?;
+
+    let integrity: String = tx.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    anyhow::ensure!(integrity == "ok", "Integrity check failed: {integrity}");
+
+    let count: i64 = tx.query_row("SELECT COUNT(*) FROM starknet_blocks", [], |row| row.get(0))?;
+    let canonical_count: i64 =
+        tx.query_row("SELECT COUNT(*) FROM canonical_blocks", [], |row| {
+            row.get(0)
+        })?;
+    anyhow::ensure!(
+        count == canonical_count,
+        "There are {count} blocks but {canonical_count} canonical blocks"
+    );
+
+    let mut hashes = Vec::new();
+    for number in 0..count as u64 {
+        let number = StarknetBlockNumber::new_or_panic(number);
+
+        // Blocks must be stored without gaps, as only the latest blocks are removed on reorgs.
+        let block = StarknetBlocksTable::get(&tx, number.into())?
+            .with_context(|| format!("Block {number} is missing"))?;
+
+        let canonical: Option<StarknetBlockHash> = tx
+            .query_row(
+                "SELECT hash FROM canonical_blocks WHERE number = ?",
+                [number],
+                |row| row.get(0),
+            )
+            .optional()?;
+        anyhow::ensure!(
+            canonical == Some(block.hash),
+            "Canonical hash of block {number} is {canonical:?} instead of {:?}",
+            block.hash
+        );
+
+        anyhow::ensure!(
+            StarknetStateUpdatesTable::get(&tx, block.hash)?.is_some(),
+            "State update of block {number} is missing"
+        );
+
+        let (storage_commitment, class_commitment) =
+            StarknetBlocksTable::get_state_commitment(&tx, number.into())?
+                .with_context(|| format!("State commitment of block {number} is missing"))?;
+        anyhow::ensure!(
+            StateCommitment::calculate(storage_commitment, class_commitment) == block.root,
+            "State commitment of block {number} does not match its storage and class commitments"
+        );
+
+        hashes.push(block.hash);
+    }
+
+    Ok(hashes)
+}
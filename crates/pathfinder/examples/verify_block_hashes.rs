@@ -1,6 +1,6 @@
 use anyhow::Context;
 use pathfinder_common::{Chain, StarknetBlockHash, StarknetBlockNumber};
-use pathfinder_lib::state::block_hash::{verify_block_hash, VerifyResult};
+use pathfinder_lib::state::block_hash::{verify_block_hash, IrregularBlocks, VerifyResult};
 use pathfinder_storage::{
     JournalMode, StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable, Storage,
 };
@@ -31,6 +31,7 @@ fn main() -> anyhow::Result<()> {
         .context("Opening database connection")?;
 
     let mut parent_block_hash = StarknetBlockHash(Felt::ZERO);
+    let irregular_blocks = IrregularBlocks::for_chain(chain);
 
     let latest_block_number = {
         let tx = db.transaction().unwrap();
@@ -65,7 +66,7 @@ fn main() -> anyhow::Result<()> {
         };
         parent_block_hash = block_hash;
 
-        let result = verify_block_hash(&block, chain, block_hash)?;
+        let result = verify_block_hash(&block, chain, block_hash, &irregular_blocks)?;
         match result {
             VerifyResult::Match(_) => {}
             VerifyResult::NotVerifiable => println!(
@@ -0,0 +1,74 @@
+//! The `audit` subcommand, which checks the stored chain against L1 instead of running the node.
+use std::path::PathBuf;
+
+use anyhow::Context;
+#[cfg(feature = "ethereum")]
+use pathfinder_ethereum::blob::BlobClient;
+use pathfinder_lib::state::audit::{self, Ethereum};
+use pathfinder_storage::{DatabaseLock, JournalMode, Storage};
+
+use crate::config::{Audit, GatewayTransport, NetworkConfig};
+use crate::{verify_networks, EthereumContext, PathfinderContext};
+
+pub async fn run(
+    config: Audit,
+    ethereum: Option<EthereumContext>,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let ethereum = match ethereum {
+        Some(EthereumContext {
+            transport,
+            chain: Some(chain),
+        }) => {
+            verify_networks(context.network, chain)?;
+            Some(Ethereum {
+                transport,
+                core_address: context.core_address().0,
+                #[cfg(feature = "ethereum")]
+                blobs: config.blob_url.map(BlobClient::new),
+                #[cfg(not(feature = "ethereum"))]
+                blobs: None,
+            })
+        }
+        Some(EthereumContext { chain: None, .. }) => {
+            anyhow::bail!("Ethereum endpoint is unreachable")
+        }
+        None => {
+            tracing::info!("Ethereum is disabled, auditing against the recorded L1 state only.");
+            None
+        }
+    };
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    tracing::info!(from=%config.from, to=%config.to, "Auditing blocks against L1.");
+    let report = audit::audit(storage, ethereum, config.from, config.to).await?;
+
+    for finding in &report.findings {
+        tracing::warn!("{finding}");
+    }
+    tracing::info!(
+        verified_state_commitments=%report.verified_state_commitments,
+        verified_state_diffs=%report.verified_state_diffs,
+        skipped_state_diffs=%report.skipped_state_diffs,
+        findings=%report.findings.len(),
+        "Audit complete."
+    );
+
+    anyhow::ensure!(
+        report.findings.is_empty(),
+        "{} blocks or ranges of blocks cannot be reproduced from L1",
+        report.findings.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! The `export-chain` and `import-chain` subcommands, which move blocks between the database
+//! and an archive file instead of running the node.
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pathfinder_lib::state::archive;
+use pathfinder_storage::{DatabaseLock, JournalMode, StarknetBlocksTable, Storage};
+
+use crate::config::{ExportChain, GatewayTransport, ImportChain, NetworkConfig};
+use crate::PathfinderContext;
+
+pub async fn export(
+    config: ExportChain,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    let file = std::fs::File::create(&config.output)
+        .with_context(|| format!("Creating {}", config.output.display()))?;
+    let chain_id = context.network_id;
+
+    let blocks = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        let to = match config.to {
+            Some(to) => to,
+            None => StarknetBlocksTable::get_latest_number(&tx)
+                .context("Reading latest block")?
+                .context("Database contains no blocks")?,
+        };
+        let blocks = config.from..=to;
+
+        archive::export(&tx, chain_id, blocks.clone(), std::io::BufWriter::new(file))?;
+
+        Ok(blocks)
+    })
+    .await
+    .context("Exporting chain panicked")??;
+
+    tracing::info!(
+        from=%blocks.start(),
+        to=%blocks.end(),
+        output=%config.output.display(),
+        "Chain exported."
+    );
+
+    Ok(())
+}
+
+pub async fn import(
+    config: ImportChain,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    let file = std::fs::File::open(&config.input)
+        .with_context(|| format!("Opening {}", config.input.display()))?;
+
+    tracing::info!(input=%config.input.display(), "Importing chain.");
+    let blocks = archive::import(
+        storage,
+        context.network,
+        context.network_id,
+        &Default::default(),
+        std::io::BufReader::new(file),
+    )
+    .await?;
+
+    tracing::info!(from=%blocks.start(), to=%blocks.end(), "Chain imported.");
+
+    Ok(())
+}
@@ -0,0 +1,371 @@
+//! The `compare-traces` subcommand, which traces a block locally and on another node and prints
+//! the differences between the traces instead of running the node.
+//!
+//! Both nodes simulate the block's transactions on top of its parent block with
+//! `starknet_simulateTransaction`, so that any node serving the v0.3 API can be compared against.
+//! The local node serves the simulation from a temporary RPC server, which makes both traces go
+//! through the same code path.
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
+use pathfinder_rpc::context::RpcContext;
+use pathfinder_rpc::{RpcServer, SyncState};
+use pathfinder_storage::{
+    DatabaseLock, JournalMode, StarknetBlocksTable, StarknetTransactionsTable, Storage,
+};
+use reqwest::Url;
+use serde_json::{json, Value};
+use starknet_gateway_types::reply::transaction::{InvokeTransaction, Transaction};
+
+use crate::config::{CompareTraces, GatewayTransport, NetworkConfig};
+use crate::PathfinderContext;
+
+pub async fn run(
+    config: CompareTraces,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database.clone(), journal_mode)?;
+
+    let block = config.block;
+    let read_storage = storage.clone();
+    let (parent_hash, transactions) = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut connection = read_storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        let parent = StarknetBlockNumber::new_or_panic(block.get() - 1);
+        let parent_hash = StarknetBlocksTable::get_hash(&tx, parent.into())
+            .context("Reading parent block")?
+            .with_context(|| format!("Parent block {parent} is missing"))?;
+        anyhow::ensure!(
+            StarknetBlocksTable::get_hash(&tx, block.into())
+                .context("Reading block")?
+                .is_some(),
+            "Block {block} is missing"
+        );
+        let transactions =
+            StarknetTransactionsTable::get_transaction_data_for_block(&tx, block.into())
+                .context("Reading transactions")?;
+
+        Ok((parent_hash, transactions))
+    })
+    .await
+    .context("Reading block panicked")??;
+
+    let (hashes, simulated): (Vec<_>, Vec<_>) = transactions
+        .iter()
+        .filter_map(|(transaction, _)| {
+            simulated_transaction(transaction).map(|simulated| (transaction.hash(), simulated))
+        })
+        .unzip();
+    anyhow::ensure!(
+        !simulated.is_empty(),
+        "Block {block} has no transactions which can be simulated"
+    );
+    if simulated.len() < transactions.len() {
+        tracing::warn!(
+            skipped=%(transactions.len() - simulated.len()),
+            "Declare, deploy and L1 handler transactions cannot be simulated, and are skipped"
+        );
+    }
+    let request = simulate_request(parent_hash, simulated);
+
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let (call_handle, cairo_handle) = pathfinder_rpc::cairo::ext_py::start(
+        context.database,
+        std::num::NonZeroUsize::new(1).unwrap(),
+        async move {
+            let _ = stop_rx.await;
+        },
+        context.network,
+    )
+    .await
+    .context("Creating python process for execution. Have you setup our Python dependencies?")?;
+
+    let rpc_context = RpcContext::new(
+        storage,
+        Arc::new(SyncState::default()),
+        context.network_id,
+        context.gateway,
+    )
+    .with_call_handling(call_handle);
+    let (rpc_handle, local_addr) = RpcServer::new(([127, 0, 0, 1], 0).into(), rpc_context)
+        .run()
+        .await
+        .context("Starting local RPC server")?;
+
+    let local_url = Url::parse(&format!("http://{local_addr}/")).expect("Valid URL");
+    let local = simulate(&local_url, &request)
+        .await
+        .context("Tracing block locally")?;
+    let other = simulate(&config.other, &request)
+        .await
+        .with_context(|| format!("Tracing block on {}", config.other))?;
+
+    let _ = rpc_handle.stop();
+    drop(stop_tx);
+    let _ = cairo_handle.await;
+
+    let mut differences = Vec::new();
+    for (i, hash) in hashes.iter().enumerate() {
+        diff(
+            &hash.0.to_hex_str(),
+            local.get(i),
+            other.get(i),
+            &mut differences,
+        );
+    }
+
+    for difference in &differences {
+        println!("{difference}");
+    }
+
+    anyhow::ensure!(
+        differences.is_empty(),
+        "Traces of block {block} differ in {} places",
+        differences.len()
+    );
+    tracing::info!(%block, transactions=%hashes.len(), "Traces match.");
+
+    Ok(())
+}
+
+/// Converts a transaction of a block into the broadcasted transaction which simulates it, or
+/// returns [None] if the transaction cannot be simulated.
+fn simulated_transaction(transaction: &Transaction) -> Option<Value> {
+    let simulated = match transaction {
+        Transaction::Invoke(InvokeTransaction::V0(tx)) => json!({
+            "type": "INVOKE",
+            "version": "0x0",
+            "max_fee": tx.max_fee,
+            "signature": tx.signature,
+            "contract_address": tx.sender_address,
+            "entry_point_selector": tx.entry_point_selector,
+            "calldata": tx.calldata,
+        }),
+        Transaction::Invoke(InvokeTransaction::V1(tx)) => json!({
+            "type": "INVOKE",
+            "version": "0x1",
+            "max_fee": tx.max_fee,
+            "signature": tx.signature,
+            "nonce": tx.nonce,
+            "sender_address": tx.sender_address,
+            "calldata": tx.calldata,
+        }),
+        Transaction::DeployAccount(tx) => json!({
+            "type": "DEPLOY_ACCOUNT",
+            "version": format!("{:#x}", tx.version.without_query_version()),
+            "max_fee": tx.max_fee,
+            "signature": tx.signature,
+            "nonce": tx.nonce,
+            "contract_address_salt": tx.contract_address_salt,
+            "constructor_calldata": tx.constructor_calldata,
+            "class_hash": tx.class_hash,
+        }),
+        // Declare transactions would need their class, and the others cannot be broadcasted.
+        Transaction::Declare(_) | Transaction::Deploy(_) | Transaction::L1Handler(_) => {
+            return None
+        }
+    };
+
+    Some(simulated)
+}
+
+fn simulate_request(parent_hash: StarknetBlockHash, transactions: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "starknet_simulateTransaction",
+        "params": {
+            "block_id": { "block_hash": parent_hash },
+            "transactions": transactions,
+            "simulation_flags": [],
+        },
+    })
+}
+
+/// Sends the simulation `request` to the v0.3 API of the node at `url`, returning the simulated
+/// transactions.
+async fn simulate(url: &Url, request: &Value) -> anyhow::Result<Vec<Value>> {
+    let url = url.join("rpc/v0.3").context("Creating RPC URL")?;
+    let mut response = reqwest::Client::new()
+        .post(url)
+        .json(request)
+        .send()
+        .await
+        .context("Sending request")?
+        .error_for_status()?
+        .json::<Value>()
+        .await
+        .context("Reading response")?;
+
+    if let Some(error) = response.get("error") {
+        anyhow::bail!("Simulation failed: {error}");
+    }
+
+    serde_json::from_value(response["result"].take()).context("Parsing simulated transactions")
+}
+
+/// A value which differs between the local trace and the other node's trace.
+#[derive(Debug, PartialEq)]
+struct Difference {
+    /// Path of the value, starting with the hash of its transaction.
+    path: String,
+    /// [None] if the value is missing from the local trace.
+    local: Option<Value>,
+    /// [None] if the value is missing from the other node's trace.
+    other: Option<Value>,
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "<missing>".to_owned(),
+        };
+
+        write!(
+            f,
+            "{}: local {}, other {}",
+            self.path,
+            value(&self.local),
+            value(&self.other)
+        )
+    }
+}
+
+/// Collects the differences between `local` and `other` into `differences`.
+///
+/// Objects and arrays are compared member by member, so that a difference deep within a trace is
+/// reported with its own path rather than for the whole trace.
+fn diff(
+    path: &str,
+    local: Option<&Value>,
+    other: Option<&Value>,
+    differences: &mut Vec<Difference>,
+) {
+    match (local, other) {
+        (Some(Value::Object(local)), Some(Value::Object(other))) => {
+            let mut keys = local.keys().chain(other.keys()).collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                diff(
+                    &format!("{path}.{key}"),
+                    local.get(key),
+                    other.get(key),
+                    differences,
+                );
+            }
+        }
+        (Some(Value::Array(local)), Some(Value::Array(other))) => {
+            for i in 0..local.len().max(other.len()) {
+                diff(
+                    &format!("{path}[{i}]"),
+                    local.get(i),
+                    other.get(i),
+                    differences,
+                );
+            }
+        }
+        (local, other) if local != other => differences.push(Difference {
+            path: path.to_owned(),
+            local: local.cloned(),
+            other: other.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn differences(local: Value, other: Value) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        diff("0x1", Some(&local), Some(&other), &mut differences);
+        differences
+    }
+
+    #[test]
+    fn equal() {
+        let trace = json!({
+            "fee_estimation": {"overall_fee": "0x10"},
+            "transaction_trace": {"function_invocation": {"calls": [], "result": ["0x1"]}},
+        });
+
+        assert!(differences(trace.clone(), trace).is_empty());
+    }
+
+    #[test]
+    fn nested() {
+        let local = json!({
+            "fee_estimation": {"overall_fee": "0x10"},
+            "function_invocation": {
+                "calls": [{"result": ["0x1", "0x2"]}],
+                "events": [],
+            },
+        });
+        let other = json!({
+            "fee_estimation": {"overall_fee": "0x11"},
+            "function_invocation": {
+                "calls": [{"result": ["0x1"]}, {"result": []}],
+                "messages": [],
+            },
+        });
+
+        let found = differences(local, other);
+        let paths = found
+            .iter()
+            .map(|difference| difference.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "0x1.fee_estimation.overall_fee",
+                "0x1.function_invocation.calls[0].result[1]",
+                "0x1.function_invocation.calls[1]",
+                "0x1.function_invocation.events",
+                "0x1.function_invocation.messages",
+            ]
+        );
+        assert_eq!(
+            found[1],
+            Difference {
+                path: "0x1.function_invocation.calls[0].result[1]".to_owned(),
+                local: Some(json!("0x2")),
+                other: None,
+            }
+        );
+    }
+
+    #[test]
+    fn display() {
+        let found = differences(json!({"result": null}), json!({"result": ["0x1"]}));
+        assert_eq!(
+            found[0].to_string(),
+            r#"0x1.result: local null, other ["0x1"]"#
+        );
+
+        let found = differences(json!([]), json!(["0x1"]));
+        assert_eq!(
+            found[0].to_string(),
+            r#"0x1[0]: local <missing>, other "0x1""#
+        );
+    }
+}
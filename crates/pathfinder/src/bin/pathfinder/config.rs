@@ -1,8 +1,23 @@
 use clap::{CommandFactory, Parser};
+use pathfinder_common::{ContractAddress, StarknetBlockNumber};
+use pathfinder_lib::retention::{RetentionConfig, RetentionPolicy};
+use pathfinder_lib::state::block_hash::{IrregularBlocks, IrregularRange};
+use pathfinder_lib::state::checkpoint::Checkpoint;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::ip_filter::{IpFilter, IpNet};
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::request_limits::RequestLimits;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::request_log::RequestLogConfig;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::tls::TlsConfig;
 use pathfinder_storage::JournalMode;
-use reqwest::Url;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{StatusCode, Url};
+use stark_hash::Felt;
+use starknet_gateway_client::RetryPolicy;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use pathfinder_common::consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT;
 
@@ -19,12 +34,13 @@ struct Cli {
         long,
         value_name = "DIR", 
         value_hint = clap::ValueHint::DirPath,
-        long_help = "Directory where the node should store its data",
+        long_help = "Directory where the node should store its data. A leading '~' is expanded to the user's home directory.",
         env = "PATHFINDER_DATA_DIRECTORY", 
         default_value_os_t = (&std::path::Component::CurDir).into()
     )]
     data_directory: PathBuf,
 
+    #[cfg(feature = "ethereum")]
     #[arg(
         long = "ethereum.password",
         long_help = "The optional password to use for the Ethereum API",
@@ -33,6 +49,7 @@ struct Cli {
     )]
     ethereum_password: Option<String>,
 
+    #[cfg(feature = "ethereum")]
     #[arg(
         long = "ethereum.url",
         long_help = r"This should point to the HTTP RPC endpoint of your Ethereum entry-point, typically a local Ethereum client or a hosted gateway service such as Infura or Cloudflare.
@@ -44,8 +61,29 @@ Examples:
         value_hint = clap::ValueHint::Url,
         env = "PATHFINDER_ETHEREUM_API_URL", 
     )]
-    ethereum_url: Url,
+    ethereum_url: Option<Url>,
 
+    #[cfg(feature = "ethereum")]
+    #[arg(
+        long = "ethereum.enable",
+        long_help = "Enable syncing L1 state from Ethereum. If disabled, '--network' must be specified and blocks are never marked as accepted on L1.",
+        action = clap::ArgAction::Set,
+        default_value = "true",
+        env = "PATHFINDER_ETHEREUM_ENABLE",
+    )]
+    ethereum_enable: bool,
+
+    #[cfg(feature = "ethereum")]
+    #[arg(
+        long = "ethereum.proxy-url",
+        long_help = "HTTP, HTTPS or SOCKS5 proxy through which Ethereum API requests are sent, such as socks5://127.0.0.1:1080. Defaults to the proxy of the HTTPS_PROXY, HTTP_PROXY or ALL_PROXY environment variables, if any.",
+        value_name = "URL",
+        value_hint = clap::ValueHint::Url,
+        env = "PATHFINDER_ETHEREUM_PROXY_URL"
+    )]
+    ethereum_proxy: Option<Url>,
+
+    #[cfg(feature = "rpc")]
     #[arg(
         long = "http-rpc",
         long_help = "HTTP-RPC listening address",
@@ -55,6 +93,174 @@ Examples:
     )]
     rpc_address: SocketAddr,
 
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.enable",
+        long_help = "Enable the HTTP-RPC server",
+        action = clap::ArgAction::Set,
+        default_value = "true",
+        env = "PATHFINDER_RPC_ENABLE",
+    )]
+    rpc_enable: bool,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.get-events-max-cost",
+        long_help = "Reject starknet_getEvents queries whose block range contains more than this many events. Such queries are answered with an error suggesting a narrower block range. Unlimited by default.",
+        value_name = "EVENTS",
+        env = "PATHFINDER_RPC_GET_EVENTS_MAX_COST"
+    )]
+    rpc_get_events_max_cost: Option<u64>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.prefetch-blocks",
+        long_help = "Once a client requests consecutive blocks which are not synced yet, such as an indexer walking the chain while the node catches up, serve them from the gateway and prefetch this many following blocks. A request waits at most 2 seconds for its block. Zero disables prefetching.",
+        value_name = "BLOCKS",
+        default_value = "0",
+        env = "PATHFINDER_RPC_PREFETCH_BLOCKS"
+    )]
+    rpc_prefetch_blocks: u64,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.api-keys",
+        long_help = r#"JSON file with the API keys which HTTP-RPC requests must carry in the 'x-api-key' header. Without it, API keys are not required.
+
+Each key has a name, by which its usage is reported in the rpc_api_key_calls_total metric, and optionally a rate limit and a set of allowed methods:
+    [{"name": "indexer", "key": "<secret>", "requests_per_second": 100, "methods": ["starknet_getEvents"]}]"#,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        env = "PATHFINDER_RPC_API_KEYS"
+    )]
+    rpc_api_keys: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.attestation-key",
+        long_help = r#"File with the hex encoded secp256k1 private key with which HTTP-RPC responses containing block hashes, state roots and proof roots are signed.
+
+The signature is added to the response as an 'attestation' member, so that downstream services can verify which operator served the data. The Ethereum address of the key is logged on startup."#,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        env = "PATHFINDER_RPC_ATTESTATION_KEY"
+    )]
+    rpc_attestation_key: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.allow-ips",
+        long_help = "Comma separated list of the IP addresses or CIDR ranges of the clients which may use the HTTP-RPC server. All clients are allowed if empty.",
+        value_name = "IP/CIDR",
+        value_delimiter = ',',
+        env = "PATHFINDER_RPC_ALLOW_IPS"
+    )]
+    rpc_allow_ips: Vec<IpNet>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.deny-ips",
+        long_help = "Comma separated list of the IP addresses or CIDR ranges of the clients which may not use the HTTP-RPC server, even if they are also allowed.",
+        value_name = "IP/CIDR",
+        value_delimiter = ',',
+        env = "PATHFINDER_RPC_DENY_IPS"
+    )]
+    rpc_deny_ips: Vec<IpNet>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.trusted-proxies",
+        long_help = r#"Comma separated list of the IP addresses or CIDR ranges of the proxies, such as load balancers, in front of the HTTP-RPC server.
+
+Once set, only these proxies and the loopback interface may connect, and the allowed and denied IPs apply to the client addresses which they forward in the 'Forwarded' or 'X-Forwarded-For' header."#,
+        value_name = "IP/CIDR",
+        value_delimiter = ',',
+        env = "PATHFINDER_RPC_TRUSTED_PROXIES"
+    )]
+    rpc_trusted_proxies: Vec<IpNet>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.tls-cert",
+        long_help = "PEM file with the TLS certificate chain of the HTTP-RPC server, which then serves HTTPS instead of HTTP.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "rpc_tls_key",
+        env = "PATHFINDER_RPC_TLS_CERT"
+    )]
+    rpc_tls_cert: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.tls-key",
+        long_help = "PEM file with the private key of the TLS certificate of the HTTP-RPC server.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "rpc_tls_cert",
+        env = "PATHFINDER_RPC_TLS_KEY"
+    )]
+    rpc_tls_key: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.tls-client-ca",
+        long_help = "PEM file with the certificate authorities of the client certificates which the HTTP-RPC server accepts. Once set, clients must present a certificate signed by one of them.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "rpc_tls_cert",
+        env = "PATHFINDER_RPC_TLS_CLIENT_CA"
+    )]
+    rpc_tls_client_ca: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.request-log-sample",
+        long_help = "Log one in every N HTTP-RPC requests, with its method, a hash of its parameters, its duration, the size of its response and its error code. Disabled by default.",
+        value_name = "N",
+        env = "PATHFINDER_RPC_REQUEST_LOG_SAMPLE"
+    )]
+    rpc_request_log_sample: Option<std::num::NonZeroU64>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.slow-request-threshold",
+        long_help = "HTTP-RPC requests which take longer than this many milliseconds are always logged, regardless of sampling.",
+        value_name = "MILLISECONDS",
+        default_value = "5000",
+        env = "PATHFINDER_RPC_SLOW_REQUEST_THRESHOLD"
+    )]
+    rpc_slow_request_threshold: u64,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.max-request-body-size",
+        long_help = "HTTP-RPC requests with a larger body than this many bytes are rejected.",
+        value_name = "BYTES",
+        default_value = "10485760",
+        env = "PATHFINDER_RPC_MAX_REQUEST_BODY_SIZE"
+    )]
+    rpc_max_request_body_size: u32,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.max-request-depth",
+        long_help = "HTTP-RPC requests whose arrays and objects are nested deeper than this are rejected before they are parsed, where the request itself is at depth 1.",
+        value_name = "DEPTH",
+        default_value = "32",
+        env = "PATHFINDER_RPC_MAX_REQUEST_DEPTH"
+    )]
+    rpc_max_request_depth: std::num::NonZeroUsize,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "rpc.max-request-array-length",
+        long_help = "HTTP-RPC requests with an array of more elements than this, including a batch of more requests, are rejected before they are parsed.",
+        value_name = "ELEMENTS",
+        default_value = "100000",
+        env = "PATHFINDER_RPC_MAX_REQUEST_ARRAY_LENGTH"
+    )]
+    rpc_max_request_array_length: std::num::NonZeroUsize,
+
     #[arg(
         long = "monitor-address",
         long_help = "The address at which pathfinder will serve monitoring related information",
@@ -63,9 +269,145 @@ Examples:
     )]
     monitor_address: Option<SocketAddr>,
 
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "monitor-tls-cert",
+        long_help = "PEM file with the TLS certificate chain of the monitoring server, which then serves HTTPS instead of HTTP.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "monitor_tls_key",
+        env = "PATHFINDER_MONITOR_TLS_CERT"
+    )]
+    monitor_tls_cert: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "monitor-tls-key",
+        long_help = "PEM file with the private key of the TLS certificate of the monitoring server.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "monitor_tls_cert",
+        env = "PATHFINDER_MONITOR_TLS_KEY"
+    )]
+    monitor_tls_key: Option<PathBuf>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "monitor-tls-client-ca",
+        long_help = "PEM file with the certificate authorities of the client certificates which the monitoring server accepts. Once set, clients must present a certificate signed by one of them.",
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        requires = "monitor_tls_cert",
+        env = "PATHFINDER_MONITOR_TLS_CLIENT_CA"
+    )]
+    monitor_tls_client_ca: Option<PathBuf>,
+
     #[clap(flatten)]
     network: NetworkCli,
 
+    #[arg(
+        long = "gateway.report-unknown-fields",
+        long_help = "Report fields in gateway responses which are unknown to pathfinder, instead of silently ignoring them. Unknown fields are counted by the gateway_unknown_fields_total metric and summarized in a periodic warning.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_GATEWAY_REPORT_UNKNOWN_FIELDS",
+    )]
+    gateway_report_unknown_fields: bool,
+
+    #[arg(
+        long = "feeder-gateway.fallback-urls",
+        long_help = "Comma separated list of feeder gateway URLs, such as mirrors or proxies of the network's feeder gateway, which requests fail over to in order while the feeder gateway has an outage, i.e. responds with a 5xx or 429 status or times out. Each URL must serve the same chain, which is checked at startup or, while the feeder gateway is unreachable, before sync starts, and is tracked separately by the gateway_endpoint_unavailable metric's endpoint label.",
+        value_name = "URL",
+        value_hint = clap::ValueHint::Url,
+        value_delimiter = ',',
+        env = "PATHFINDER_FEEDER_GATEWAY_FALLBACK_URLS"
+    )]
+    feeder_gateway_fallbacks: Vec<Url>,
+
+    #[arg(
+        long = "gateway.retry.max-attempts",
+        long_help = "Number of attempts, including the first, after which a failed feeder gateway request is given up. Zero retries forever. Requests which fail with maintenance responses count as attempts too.",
+        value_name = "COUNT",
+        default_value = "0",
+        env = "PATHFINDER_GATEWAY_RETRY_MAX_ATTEMPTS"
+    )]
+    gateway_retry_max_attempts: usize,
+
+    #[arg(
+        long = "gateway.retry.base-delay",
+        long_help = "Seconds to wait before retrying a failed feeder gateway request for the first time. The delay doubles with each further retry.",
+        value_name = "SECONDS",
+        default_value = "30",
+        env = "PATHFINDER_GATEWAY_RETRY_BASE_DELAY"
+    )]
+    gateway_retry_base_delay: u64,
+
+    #[arg(
+        long = "gateway.retry.max-delay",
+        long_help = "Maximum number of seconds to wait between retries of a failed feeder gateway request.",
+        value_name = "SECONDS",
+        default_value = "600",
+        env = "PATHFINDER_GATEWAY_RETRY_MAX_DELAY"
+    )]
+    gateway_retry_max_delay: u64,
+
+    #[arg(
+        long = "gateway.retry.jitter",
+        long_help = "Randomize each delay between retries to between half and all of it, so that many nodes sharing a gateway do not retry in lockstep.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_GATEWAY_RETRY_JITTER"
+    )]
+    gateway_retry_jitter: bool,
+
+    #[arg(
+        long = "gateway.retry.statuses",
+        long_help = "Comma separated list of HTTP statuses of failed feeder gateway responses which are retried. All statuses are retried by default. Failures without a response, such as timeouts, are always retried, and StarkNet errors never are.",
+        value_name = "STATUS",
+        value_delimiter = ',',
+        value_parser = parse_status,
+        env = "PATHFINDER_GATEWAY_RETRY_STATUSES"
+    )]
+    gateway_retry_statuses: Option<Vec<StatusCode>>,
+
+    #[arg(
+        long = "gateway.proxy-url",
+        long_help = "HTTP, HTTPS or SOCKS5 proxy through which gateway and feeder gateway requests are sent, such as socks5://127.0.0.1:1080. Defaults to the proxy of the HTTPS_PROXY, HTTP_PROXY or ALL_PROXY environment variables, if any.",
+        value_name = "URL",
+        value_hint = clap::ValueHint::Url,
+        env = "PATHFINDER_GATEWAY_PROXY_URL"
+    )]
+    gateway_proxy: Option<Url>,
+
+    #[arg(
+        long = "gateway.headers",
+        long_help = "Header which is attached to every request to the gateway and feeder gateway, such as the API key required by a hosted gateway. The header is given as 'name: value', and the option can be repeated to attach several headers. Headers are not sent to feeder gateway fallbacks. The values are redacted from logs.",
+        value_name = "NAME: VALUE",
+        value_parser = parse_header,
+        env = "PATHFINDER_GATEWAY_HEADERS"
+    )]
+    gateway_headers: Vec<(HeaderName, HeaderValue)>,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "gateway.response-cache",
+        long_help = "Persist the feeder gateway responses which RPC methods request and which can never change in the database, so that they are not downloaded again after a restart. These are blocks by hash and transactions once they are accepted on L1. Sync and class downloads are not cached.",
+        action = clap::ArgAction::Set,
+        default_value = "true",
+        env = "PATHFINDER_GATEWAY_RESPONSE_CACHE"
+    )]
+    gateway_response_cache: bool,
+
+    #[cfg(feature = "rpc")]
+    #[arg(
+        long = "gateway.response-cache-size",
+        long_help = "Size of the compressed gateway responses which are persisted by the response cache. The oldest responses are evicted beyond it.",
+        value_name = "MiB",
+        default_value = "512",
+        env = "PATHFINDER_GATEWAY_RESPONSE_CACHE_SIZE"
+    )]
+    gateway_response_cache_size: u64,
+
     #[arg(
         long = "poll-pending",
         long_help = "Enable polling pending block",
@@ -75,6 +417,17 @@ Examples:
     )]
     poll_pending: bool,
 
+    #[cfg(feature = "execution")]
+    #[arg(
+        long = "poll-pending.execute-locally",
+        long_help = "Execute the transactions of the pending block using the execution engine, and serve the resulting receipts and events instead of the ones from the gateway. Transactions are executed up to the first transaction other than an invoke or deploy account transaction, and the gateway's receipts are used for the rest. A pending block is only executed once it is first requested, and the gateway's receipts are served until the execution completes.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_POLL_PENDING_EXECUTE_LOCALLY",
+    )]
+    poll_pending_execute_locally: bool,
+
+    #[cfg(feature = "execution")]
     #[arg(
         long = "python-subprocesses",
         long_help = "Number of Python starknet VMs subprocesses to start",
@@ -83,6 +436,26 @@ Examples:
     )]
     python_subprocesses: std::num::NonZeroUsize,
 
+    #[cfg(feature = "execution")]
+    #[arg(
+        long = "execution.enable",
+        long_help = "Enable the Python execution engine. If disabled, RPC methods which execute transactions, such as starknet_call and starknet_estimateFee, are unavailable.",
+        action = clap::ArgAction::Set,
+        default_value = "true",
+        env = "PATHFINDER_EXECUTION_ENABLE",
+    )]
+    execution_enable: bool,
+
+    #[cfg(feature = "execution")]
+    #[arg(
+        long = "execution.memory-limit",
+        long_help = "Total memory in MiB which the transactions executing at once may take, as estimated from the transactions and the classes they declare. Requests which would exceed it wait for earlier ones to complete.",
+        value_name = "MiB",
+        default_value = "4096",
+        env = "PATHFINDER_EXECUTION_MEMORY_LIMIT"
+    )]
+    execution_memory_limit: std::num::NonZeroU32,
+
     #[arg(
         long = "sqlite-wal",
         long_help = "Enable SQLite write-ahead logging",
@@ -91,6 +464,344 @@ Examples:
         env = "PATHFINDER_SQLITE_WAL", 
     )]
     sqlite_wal: bool,
+
+    #[arg(
+        long = "yes-i-have-a-backup",
+        long_help = "Skip the automatic database backup which is otherwise made before applying destructive database migrations. The backup needs as much free disk space as the database itself.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_YES_I_HAVE_A_BACKUP",
+    )]
+    yes_i_have_a_backup: bool,
+
+    #[arg(
+        long = "storage.target-size",
+        long_help = "Free space in the database is returned to the filesystem while the database is larger than this size. Free space below it is kept for reuse by new data. Apart from pruned state, pathfinder does not delete data, so this limits only the free space. Zero disables returning free space. Only databases using incremental vacuum are supported, which are those created by this version of pathfinder or later, or rebuilt using --storage.rebuild-for-incremental-vacuum.",
+        value_name = "MiB",
+        default_value = "0",
+        env = "PATHFINDER_STORAGE_TARGET_SIZE"
+    )]
+    storage_target_size: u64,
+
+    #[arg(
+        long = "storage.rebuild-for-incremental-vacuum",
+        long_help = "Rebuild a database created without incremental vacuum once at startup, so that --storage.target-size applies to it. The rebuild blocks startup, takes hours for large databases and temporarily needs up to twice the database size in free disk space.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_STORAGE_REBUILD_FOR_INCREMENTAL_VACUUM"
+    )]
+    storage_rebuild_for_incremental_vacuum: bool,
+
+    #[arg(
+        long = "storage.state-retention",
+        long_help = "Number of blocks before the latest one whose historical state is kept. The state trie nodes of older blocks are pruned during sync, and RPC queries of their state fail with an error. Reorgs deeper than this cannot be followed. Only state synced while pruning is enabled is pruned. All historical state is kept by default.",
+        value_name = "BLOCKS",
+        env = "PATHFINDER_STORAGE_STATE_RETENTION"
+    )]
+    storage_state_retention: Option<u64>,
+
+    #[arg(
+        long = "storage.read-only",
+        long_help = "Serve RPC from a database which another pathfinder instance syncs, without writing to it. The database is opened without write locks, so that several read-only replicas can scale reads of one syncing node on the same host. Nothing is synced, the pending block is not served and methods which submit transactions fail. The database must already be migrated by a writer of the same version.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_STORAGE_READ_ONLY"
+    )]
+    storage_read_only: bool,
+
+    #[arg(
+        long = "storage.trie-node-cache-size",
+        long_help = "Number of state trie nodes which are cached in memory, shared by sync and the RPC API. The upper nodes of the tries are read for every state update and storage query, so caching them saves database reads. Each node takes about 150 bytes. Zero disables the cache.",
+        value_name = "NODES",
+        default_value = "100000",
+        env = "PATHFINDER_STORAGE_TRIE_NODE_CACHE_SIZE"
+    )]
+    storage_trie_node_cache_size: usize,
+
+    #[arg(
+        long = "retention.database-backups.max-count",
+        long_help = "Number of database backups, made before destructive migrations, which are kept next to the database. Older backups are removed at startup. Zero keeps all backups.",
+        value_name = "COUNT",
+        default_value = "2",
+        env = "PATHFINDER_RETENTION_DATABASE_BACKUPS_MAX_COUNT"
+    )]
+    retention_database_backups_max_count: usize,
+
+    #[arg(
+        long = "retention.database-backups.max-age",
+        long_help = "Database backups, made before destructive migrations, are removed at startup once they are older than this many days. Unlimited by default.",
+        value_name = "DAYS",
+        env = "PATHFINDER_RETENTION_DATABASE_BACKUPS_MAX_AGE"
+    )]
+    retention_database_backups_max_age: Option<u64>,
+
+    #[arg(
+        long = "sync.checkpoints",
+        long_help = "Comma separated list of trusted blocks, each given as '<block number>:<block hash>:<state commitment>', which the synced chain must pass through. Pathfinder stops syncing instead of following a gateway whose history conflicts with them, and refuses to start if the stored chain conflicts with them.",
+        value_name = "NUMBER:HASH:COMMITMENT",
+        value_delimiter = ',',
+        env = "PATHFINDER_SYNC_CHECKPOINTS"
+    )]
+    sync_checkpoints: Vec<Checkpoint>,
+
+    #[arg(
+        long = "sync.irregular-blocks",
+        long_help = "Comma separated list of block ranges, each given as '<first block number>-<last block number>' or as a single block number, whose hashes were computed in a non-standard way and are accepted without being verified. Replaces the built-in list of irregular blocks of the network.",
+        value_name = "FIRST-LAST",
+        value_delimiter = ',',
+        env = "PATHFINDER_SYNC_IRREGULAR_BLOCKS"
+    )]
+    sync_irregular_blocks: Option<Vec<IrregularRange>>,
+
+    #[arg(
+        long = "sync.lazy-class-download",
+        long_help = "Store only the hashes of new classes while syncing, and download their definitions in the background or when first needed by a request. This reduces the bandwidth of the initial sync. Requests using a class whose definition is not downloaded yet are slower.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_SYNC_LAZY_CLASS_DOWNLOAD",
+    )]
+    sync_lazy_class_download: bool,
+
+    #[arg(
+        long = "sync.behind-threshold",
+        long_help = "Number of blocks sync may trail the network by before `pathfinder_subscribeSyncMilestones` subscribers are notified that the node fell behind. They are notified again once it has caught up with the latest block.",
+        value_name = "BLOCKS",
+        default_value = "10",
+        env = "PATHFINDER_SYNC_BEHIND_THRESHOLD"
+    )]
+    sync_behind_threshold: u64,
+
+    #[arg(
+        long = "sync.download-workers",
+        long_help = "Number of blocks which are downloaded concurrently, together with their state updates and declared classes, while syncing blocks older than the latest one. Blocks are still applied one at a time and in order. Set to 1 to download one block at a time.",
+        default_value = "4",
+        env = "PATHFINDER_SYNC_DOWNLOAD_WORKERS"
+    )]
+    sync_download_workers: std::num::NonZeroUsize,
+
+    #[arg(
+        long = "bandwidth.monthly-quota",
+        long_help = "Monthly quota in MiB for the data received from the feeder gateway and Ethereum endpoint, counted since the start of the calendar month (UTC) and persisted across restarts. Once 90% of it is used, downloads of deferred classes and pending blocks pause until the next month. Once all of it is used, sync pauses until the next month, and requests forwarded to the gateway fail. Usage is always reported by the `bandwidth_received_bytes_*` metrics.",
+        value_name = "MiB",
+        env = "PATHFINDER_BANDWIDTH_MONTHLY_QUOTA"
+    )]
+    bandwidth_monthly_quota: Option<u64>,
+
+    #[cfg(feature = "execution")]
+    #[arg(
+        long = "fork.block",
+        long_help = "Instead of syncing, execute calls, fee estimates and simulations against the state of this block of the network. Each piece of state is fetched from the gateway the first time it is used and then cached in the database, so that no full sync is needed. Requires --execution.enable.",
+        value_name = "BLOCK NUMBER",
+        env = "PATHFINDER_FORK_BLOCK"
+    )]
+    fork_block: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Audit the stored chain against the state updates posted to L1, and exit.
+    ///
+    /// Reports every block whose contents cannot be reproduced from L1. Without Ethereum, only the
+    /// state updates recorded by L1 sync are used.
+    Audit(AuditCli),
+    /// Export a contract's class, nonce and storage at a block to a dump file, and exit.
+    ExportContract(ExportContractCli),
+    /// Import a dump written by `export-contract` into the state of the forked block, and exit.
+    ///
+    /// Calls, fee estimates and simulations in fork mode at `--fork.block` then see the contract
+    /// with the dumped class, nonce and storage, and zero for any other storage of it.
+    #[cfg(feature = "execution")]
+    ImportContract(ImportContractCli),
+    /// Export blocks of the stored chain to an archive file, and exit.
+    ///
+    /// The archive holds the headers, transactions, receipts, state updates and classes of the
+    /// blocks in a versioned format which is independent of pathfinder's database.
+    ExportChain(ExportChainCli),
+    /// Import an archive written by `export-chain` into the database, and exit.
+    ///
+    /// The archive must continue the stored chain. Every block is verified like sync verifies
+    /// downloaded blocks.
+    ImportChain(ImportChainCli),
+    /// Bootstrap a new database from a snapshot of another node's database, and exit.
+    ///
+    /// The snapshot must end at the last block of a state update on L1. Its state commitment is
+    /// verified against that state update on L1 before the snapshot is moved into place, after
+    /// which the node syncs from the next block on. Requires Ethereum.
+    ImportSnapshot(ImportSnapshotCli),
+    /// Trace a block locally and on another node, print the differences, and exit.
+    ///
+    /// Both nodes simulate the block's transactions on top of its parent block, so that a new
+    /// pathfinder version can be validated against the running one before rolling it out. Declare,
+    /// deploy and L1 handler transactions cannot be simulated and are skipped.
+    #[cfg(feature = "execution")]
+    CompareTraces(CompareTracesCli),
+    /// Check the RPC API against a specification version, print the differences, and exit.
+    ///
+    /// A built-in suite of requests is sent to a temporary RPC server over the database, and each
+    /// response is validated against a bundled JSON schema. At least the genesis block must be
+    /// synced.
+    #[cfg(feature = "rpc")]
+    Conformance(ConformanceCli),
+    /// Check connectivity, disk performance and database integrity, print a report, and exit.
+    ///
+    /// The database is only read, so that a running node can be checked as well.
+    Doctor,
+    /// Rebuild the event indexes of the database, and exit.
+    ///
+    /// The migrations build the indexes, so this is only needed if they are suspected to be
+    /// corrupt or out of date. The node must be stopped while reindexing.
+    Reindex,
+}
+
+#[derive(clap::Args)]
+struct AuditCli {
+    #[arg(long, value_name = "BLOCK", long_help = "First block to audit")]
+    from: u64,
+
+    #[arg(long, value_name = "BLOCK", long_help = "Last block to audit")]
+    to: u64,
+
+    #[cfg(feature = "ethereum")]
+    #[arg(
+        long = "blob-url",
+        long_help = "Beacon API of a consensus client, or a blob archive serving the same endpoint. If set, state diffs which were posted to L1 as blobs are audited as well. Requires Ethereum.",
+        value_name = "URL",
+        value_hint = clap::ValueHint::Url,
+        env = "PATHFINDER_AUDIT_BLOB_URL"
+    )]
+    blob_url: Option<Url>,
+}
+
+#[derive(clap::Args)]
+struct ExportContractCli {
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        long_help = "Address of the contract to export"
+    )]
+    contract: String,
+
+    #[arg(
+        long,
+        value_name = "BLOCK",
+        long_help = "Block at which to export the contract. Defaults to the latest block."
+    )]
+    block: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        long_help = "File to write the contract's state to"
+    )]
+    output: PathBuf,
+}
+
+#[cfg(feature = "execution")]
+#[derive(clap::Args)]
+struct ImportContractCli {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        long_help = "Dump to import"
+    )]
+    input: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct ExportChainCli {
+    #[arg(
+        long,
+        value_name = "BLOCK",
+        default_value = "0",
+        long_help = "First block to export"
+    )]
+    from: u64,
+
+    #[arg(
+        long,
+        value_name = "BLOCK",
+        long_help = "Last block to export. Defaults to the latest block."
+    )]
+    to: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        long_help = "File to write the archive to"
+    )]
+    output: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct ImportChainCli {
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        long_help = "Archive to import"
+    )]
+    input: PathBuf,
+}
+
+#[derive(clap::Args)]
+struct ImportSnapshotCli {
+    #[arg(
+        long,
+        value_name = "FILE or URL",
+        long_help = "Snapshot to import, as a file or an http(s) URL to download it from. A snapshot is a zstd-compressed copy of a pathfinder database, such as one written by `sqlite3 <database> \"VACUUM INTO '<copy>'\"` and then compressed with `zstd`."
+    )]
+    input: String,
+}
+
+#[cfg(feature = "execution")]
+#[derive(clap::Args)]
+struct CompareTracesCli {
+    #[arg(
+        long,
+        value_name = "URL",
+        value_hint = clap::ValueHint::Url,
+        long_help = "HTTP-RPC address of the other node, such as http://localhost:9545"
+    )]
+    other: Url,
+
+    #[arg(long, value_name = "BLOCK", long_help = "Block to trace")]
+    block: u64,
+}
+
+#[cfg(feature = "rpc")]
+#[derive(clap::Args)]
+struct ConformanceCli {
+    #[arg(
+        long,
+        value_enum,
+        value_name = "VERSION",
+        long_help = "RPC specification version to check against"
+    )]
+    spec: RpcSpec,
+
+    #[arg(
+        long = "spec-file",
+        value_name = "PATH",
+        required = true,
+        long_help = "OpenRPC document of the specification version, such as api/starknet_api_openrpc.json, api/starknet_write_api.json and api/starknet_trace_api_openrpc.json of the starknet-specs repository at the version's tag. Repeat for each document."
+    )]
+    spec_files: Vec<PathBuf>,
+}
+
+/// An RPC specification version served by pathfinder.
+#[cfg(feature = "rpc")]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcSpec {
+    #[value(name = "v0.2")]
+    V02,
+    #[value(name = "v0.3")]
+    V03,
 }
 
 #[derive(clap::Args)]
@@ -158,18 +869,182 @@ impl From<Network> for clap::builder::OsStr {
 
 pub struct Config {
     pub data_directory: PathBuf,
-    pub ethereum: Ethereum,
-    pub rpc_address: SocketAddr,
+    /// [None] if Ethereum is disabled.
+    #[cfg(feature = "ethereum")]
+    pub ethereum: Option<Ethereum>,
+    /// [None] if the RPC server is disabled.
+    #[cfg(feature = "rpc")]
+    pub rpc_address: Option<SocketAddr>,
+    #[cfg(feature = "rpc")]
+    pub rpc_get_events_max_cost: Option<u64>,
+    /// Number of blocks prefetched from the gateway for sequential requests, [None] if disabled.
+    #[cfg(feature = "rpc")]
+    pub rpc_prefetch_blocks: Option<std::num::NonZeroU64>,
+    #[cfg(feature = "rpc")]
+    pub rpc_api_keys: Option<PathBuf>,
+    /// Key file with which to sign the data of RPC responses, if any.
+    #[cfg(feature = "rpc")]
+    pub rpc_attestation_key: Option<PathBuf>,
+    /// [None] if all clients may connect directly.
+    #[cfg(feature = "rpc")]
+    pub rpc_ip_filter: Option<IpFilter>,
+    /// [None] if the RPC server serves plain HTTP.
+    #[cfg(feature = "rpc")]
+    pub rpc_tls: Option<TlsConfig>,
+    #[cfg(feature = "rpc")]
+    pub rpc_request_log: RequestLogConfig,
+    #[cfg(feature = "rpc")]
+    pub rpc_request_limits: RequestLimits,
     pub monitor_address: Option<SocketAddr>,
+    /// [None] if the monitoring server serves plain HTTP.
+    #[cfg(feature = "rpc")]
+    pub monitor_tls: Option<TlsConfig>,
     pub network: Option<NetworkConfig>,
+    pub gateway_report_unknown_fields: bool,
+    /// Feeder gateways which requests fail over to, in order.
+    pub feeder_gateway_fallbacks: Vec<Url>,
+    /// How failed feeder gateway requests are retried.
+    pub gateway_retry: RetryPolicy,
+    pub gateway_transport: GatewayTransport,
+    /// Size in bytes of the immutable gateway responses persisted in the database, [None] if
+    /// they are not persisted.
+    #[cfg(feature = "rpc")]
+    pub gateway_response_cache: Option<u64>,
     pub poll_pending: bool,
-    pub python_subprocesses: std::num::NonZeroUsize,
+    /// Whether the receipts of the pending block are computed by executing its transactions.
+    #[cfg(feature = "execution")]
+    pub execute_pending: bool,
+    /// [None] if the execution engine is disabled.
+    #[cfg(feature = "execution")]
+    pub python_subprocesses: Option<std::num::NonZeroUsize>,
+    /// Total memory in MiB which the commands executing at once may take.
+    #[cfg(feature = "execution")]
+    pub execution_memory_limit: std::num::NonZeroU32,
     pub sqlite_wal: JournalMode,
+    /// Whether the database is backed up before destructive migrations.
+    pub backup_before_migration: bool,
+    /// Size in bytes above which free database space is returned to the filesystem, [None] if
+    /// it is not returned.
+    pub storage_target_size: Option<u64>,
+    /// Whether a database without incremental vacuum is rebuilt to use it.
+    pub storage_rebuild_for_incremental_vacuum: bool,
+    /// Number of blocks before the latest one whose state is kept, [None] to keep all state.
+    pub storage_state_retention: Option<u64>,
+    /// Whether only RPC is served from a database which another instance syncs.
+    pub storage_read_only: bool,
+    /// Number of state trie nodes cached in memory, zero to disable the cache.
+    pub storage_trie_node_cache_size: usize,
+    /// How long the files written next to the database are kept.
+    pub retention: RetentionConfig,
+    /// Trusted blocks which the synced chain must pass through.
+    pub checkpoints: Vec<Checkpoint>,
+    /// Blocks whose hashes are not verified, [None] for the built-in ones of the network.
+    pub irregular_blocks: Option<IrregularBlocks>,
+    /// Whether sync defers downloading class definitions.
+    pub lazy_class_download: bool,
+    /// Number of blocks sync may trail the network by before it counts as behind.
+    pub sync_behind_threshold: u64,
+    /// Number of blocks sync downloads concurrently.
+    pub sync_download_workers: std::num::NonZeroUsize,
+    /// Monthly bandwidth quota in bytes, [None] if unlimited.
+    pub bandwidth_monthly_quota: Option<u64>,
+    /// The remote block to execute against instead of syncing, [None] unless in fork mode.
+    #[cfg(feature = "execution")]
+    pub fork_block: Option<StarknetBlockNumber>,
+    /// Run an [Audit] instead of the node.
+    pub audit: Option<Audit>,
+    /// Run an [ExportContract] instead of the node.
+    pub export_contract: Option<ExportContract>,
+    /// Run an [ImportContract] instead of the node.
+    #[cfg(feature = "execution")]
+    pub import_contract: Option<ImportContract>,
+    /// Run an [ExportChain] instead of the node.
+    pub export_chain: Option<ExportChain>,
+    /// Run an [ImportChain] instead of the node.
+    pub import_chain: Option<ImportChain>,
+    /// Run an [ImportSnapshot] instead of the node.
+    pub import_snapshot: Option<ImportSnapshot>,
+    /// Run [CompareTraces] instead of the node.
+    #[cfg(feature = "execution")]
+    pub compare_traces: Option<CompareTraces>,
+    /// Run a [Conformance] check instead of the node.
+    #[cfg(feature = "rpc")]
+    pub conformance: Option<Conformance>,
+    /// Run the health checks of the `doctor` subcommand instead of the node.
+    pub doctor: bool,
+    /// Rebuild the event indexes with the `reindex` subcommand instead of running the node.
+    pub reindex: bool,
+}
+
+pub struct Audit {
+    pub from: StarknetBlockNumber,
+    pub to: StarknetBlockNumber,
+    #[cfg(feature = "ethereum")]
+    pub blob_url: Option<Url>,
+}
+
+pub struct ExportContract {
+    pub contract: ContractAddress,
+    /// [None] for the latest block.
+    pub block: Option<StarknetBlockNumber>,
+    pub output: PathBuf,
+}
+
+#[cfg(feature = "execution")]
+pub struct ImportContract {
+    pub input: PathBuf,
+    /// The forked block whose state the contract is imported into.
+    pub fork_block: StarknetBlockNumber,
+}
+
+pub struct ExportChain {
+    pub from: StarknetBlockNumber,
+    /// [None] for the latest block.
+    pub to: Option<StarknetBlockNumber>,
+    pub output: PathBuf,
+}
+
+pub struct ImportChain {
+    pub input: PathBuf,
+}
+
+pub struct ImportSnapshot {
+    pub source: SnapshotSource,
+}
+
+pub enum SnapshotSource {
+    File(PathBuf),
+    Url(Url),
+}
+
+#[cfg(feature = "execution")]
+pub struct CompareTraces {
+    pub other: Url,
+    pub block: StarknetBlockNumber,
+}
+
+#[cfg(feature = "rpc")]
+pub struct Conformance {
+    pub spec: RpcSpec,
+    /// The OpenRPC documents of the specification version.
+    pub spec_files: Vec<PathBuf>,
 }
 
+/// How requests reach the gateway, which is needed by every command which talks to it.
+#[derive(Clone, Default)]
+pub struct GatewayTransport {
+    /// Proxy for gateway requests, instead of that of the environment.
+    pub proxy: Option<Url>,
+    /// Attached to every gateway request.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+#[cfg(feature = "ethereum")]
 pub struct Ethereum {
     pub url: Url,
     pub password: Option<String>,
+    /// Proxy for Ethereum API requests, instead of that of the environment.
+    pub proxy: Option<Url>,
 }
 
 pub enum NetworkConfig {
@@ -233,21 +1108,397 @@ impl Config {
 
         let network = NetworkConfig::from_components(cli.network);
 
-        Config {
-            data_directory: cli.data_directory,
-            ethereum: Ethereum {
+        #[cfg(feature = "ethereum")]
+        let ethereum = match (cli.ethereum_enable, cli.ethereum_url) {
+            (true, Some(url)) => Some(Ethereum {
                 password: cli.ethereum_password,
-                url: cli.ethereum_url,
+                url,
+                proxy: cli.ethereum_proxy,
+            }),
+            (true, None) => {
+                use clap::error::ErrorKind;
+
+                Cli::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        "--ethereum.url is required unless Ethereum is disabled using --ethereum.enable=false",
+                    )
+                    .exit()
+            }
+            (false, _) => None,
+        };
+
+        let block = |number| {
+            StarknetBlockNumber::new(number).unwrap_or_else(|| {
+                use clap::error::ErrorKind;
+
+                Cli::command()
+                    .error(ErrorKind::ValueValidation, "Block number is out of range")
+                    .exit()
+            })
+        };
+
+        let mut audit = None;
+        let mut export_contract = None;
+        #[cfg(feature = "execution")]
+        let mut import_contract = None;
+        let mut export_chain = None;
+        let mut import_chain = None;
+        let mut import_snapshot = None;
+        #[cfg(feature = "execution")]
+        let mut compare_traces = None;
+        #[cfg(feature = "rpc")]
+        let mut conformance = None;
+        let mut doctor = false;
+        let mut reindex = false;
+        match cli.command {
+            Some(Command::Audit(audit)) => {
+                #[cfg(feature = "ethereum")]
+                if audit.blob_url.is_some() && ethereum.is_none() {
+                    use clap::error::ErrorKind;
+
+                    Cli::command()
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            "--blob-url requires Ethereum to be enabled",
+                        )
+                        .exit()
+                }
+
+                audit = Some(Audit {
+                    from: block(audit.from),
+                    to: block(audit.to),
+                    #[cfg(feature = "ethereum")]
+                    blob_url: audit.blob_url,
+                });
+            }
+            Some(Command::ExportContract(export)) => {
+                use clap::error::ErrorKind;
+
+                let contract = Felt::from_hex_str(&export.contract)
+                    .ok()
+                    .and_then(ContractAddress::new)
+                    .unwrap_or_else(|| {
+                        Cli::command()
+                            .error(ErrorKind::ValueValidation, "Invalid contract address")
+                            .exit()
+                    });
+
+                export_contract = Some(ExportContract {
+                    contract,
+                    block: export.block.map(block),
+                    output: expand_home(export.output),
+                });
+            }
+            #[cfg(feature = "execution")]
+            Some(Command::ImportContract(import)) => {
+                use clap::error::ErrorKind;
+
+                let fork_block = cli.fork_block.map(block).unwrap_or_else(|| {
+                    Cli::command()
+                        .error(
+                            ErrorKind::MissingRequiredArgument,
+                            "import-contract requires --fork.block, whose state the contract is imported into",
+                        )
+                        .exit()
+                });
+
+                import_contract = Some(ImportContract {
+                    input: expand_home(import.input),
+                    fork_block,
+                });
+            }
+            Some(Command::ExportChain(export)) => {
+                use clap::error::ErrorKind;
+
+                if export.to.map_or(false, |to| to < export.from) {
+                    Cli::command()
+                        .error(ErrorKind::ValueValidation, "--to must not precede --from")
+                        .exit()
+                }
+
+                export_chain = Some(ExportChain {
+                    from: block(export.from),
+                    to: export.to.map(block),
+                    output: expand_home(export.output),
+                });
+            }
+            Some(Command::ImportChain(import)) => {
+                import_chain = Some(ImportChain {
+                    input: expand_home(import.input),
+                });
+            }
+            Some(Command::ImportSnapshot(import)) => {
+                let source = match Url::parse(&import.input) {
+                    Ok(url) if matches!(url.scheme(), "http" | "https") => SnapshotSource::Url(url),
+                    _ => SnapshotSource::File(expand_home(PathBuf::from(import.input))),
+                };
+                import_snapshot = Some(ImportSnapshot { source });
+            }
+            #[cfg(feature = "execution")]
+            Some(Command::CompareTraces(compare)) => {
+                use clap::error::ErrorKind;
+
+                if compare.block == 0 {
+                    Cli::command()
+                        .error(
+                            ErrorKind::ValueValidation,
+                            "--block must not be the genesis block, which has no parent to trace on",
+                        )
+                        .exit()
+                }
+
+                compare_traces = Some(CompareTraces {
+                    other: compare.other,
+                    block: block(compare.block),
+                });
+            }
+            #[cfg(feature = "rpc")]
+            Some(Command::Conformance(check)) => {
+                conformance = Some(Conformance {
+                    spec: check.spec,
+                    spec_files: check.spec_files,
+                });
+            }
+            Some(Command::Doctor) => doctor = true,
+            Some(Command::Reindex) => reindex = true,
+            None => {}
+        }
+
+        #[cfg(feature = "execution")]
+        if cli.poll_pending_execute_locally && !(cli.poll_pending && cli.execution_enable) {
+            use clap::error::ErrorKind;
+
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--poll-pending.execute-locally requires both --poll-pending and --execution.enable",
+                )
+                .exit()
+        }
+
+        #[cfg(feature = "execution")]
+        if cli.fork_block.is_some() && !cli.execution_enable && import_contract.is_none() {
+            use clap::error::ErrorKind;
+
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--fork.block requires --execution.enable",
+                )
+                .exit()
+        }
+
+        #[cfg(feature = "execution")]
+        if cli.storage_read_only && cli.fork_block.is_some() {
+            use clap::error::ErrorKind;
+
+            Cli::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "--storage.read-only cannot be used with --fork.block, which stores fetched state",
+                )
+                .exit()
+        }
+
+        #[cfg(feature = "rpc")]
+        let rpc_ip_filter = {
+            let ip_filter = IpFilter {
+                allow: cli.rpc_allow_ips,
+                deny: cli.rpc_deny_ips,
+                trusted_proxies: cli.rpc_trusted_proxies,
+            };
+            (ip_filter != IpFilter::default()).then_some(ip_filter)
+        };
+
+        Config {
+            data_directory: expand_home(cli.data_directory),
+            #[cfg(feature = "ethereum")]
+            ethereum,
+            #[cfg(feature = "rpc")]
+            rpc_address: cli.rpc_enable.then_some(cli.rpc_address),
+            #[cfg(feature = "rpc")]
+            rpc_get_events_max_cost: cli.rpc_get_events_max_cost,
+            #[cfg(feature = "rpc")]
+            rpc_prefetch_blocks: std::num::NonZeroU64::new(cli.rpc_prefetch_blocks),
+            #[cfg(feature = "rpc")]
+            rpc_api_keys: cli.rpc_api_keys.map(expand_home),
+            #[cfg(feature = "rpc")]
+            rpc_attestation_key: cli.rpc_attestation_key.map(expand_home),
+            #[cfg(feature = "rpc")]
+            rpc_ip_filter,
+            #[cfg(feature = "rpc")]
+            rpc_tls: tls_config(cli.rpc_tls_cert, cli.rpc_tls_key, cli.rpc_tls_client_ca),
+            #[cfg(feature = "rpc")]
+            rpc_request_log: RequestLogConfig {
+                sample_every: cli.rpc_request_log_sample,
+                slow_threshold: std::time::Duration::from_millis(cli.rpc_slow_request_threshold),
+            },
+            #[cfg(feature = "rpc")]
+            rpc_request_limits: RequestLimits {
+                max_body_size: cli.rpc_max_request_body_size,
+                max_depth: cli.rpc_max_request_depth.get(),
+                max_array_length: cli.rpc_max_request_array_length.get(),
             },
-            rpc_address: cli.rpc_address,
             monitor_address: cli.monitor_address,
+            #[cfg(feature = "rpc")]
+            monitor_tls: tls_config(
+                cli.monitor_tls_cert,
+                cli.monitor_tls_key,
+                cli.monitor_tls_client_ca,
+            ),
             network,
+            gateway_report_unknown_fields: cli.gateway_report_unknown_fields,
+            feeder_gateway_fallbacks: cli.feeder_gateway_fallbacks,
+            gateway_retry: RetryPolicy {
+                max_attempts: std::num::NonZeroUsize::new(cli.gateway_retry_max_attempts),
+                base_delay: std::time::Duration::from_secs(cli.gateway_retry_base_delay),
+                max_delay: std::time::Duration::from_secs(cli.gateway_retry_max_delay),
+                jitter: cli.gateway_retry_jitter,
+                retryable_statuses: cli.gateway_retry_statuses,
+            },
+            gateway_transport: GatewayTransport {
+                proxy: cli.gateway_proxy,
+                headers: cli.gateway_headers,
+            },
+            #[cfg(feature = "rpc")]
+            gateway_response_cache: cli
+                .gateway_response_cache
+                .then(|| cli.gateway_response_cache_size.saturating_mul(1024 * 1024)),
             poll_pending: cli.poll_pending,
-            python_subprocesses: cli.python_subprocesses,
+            #[cfg(feature = "execution")]
+            execute_pending: cli.poll_pending_execute_locally,
+            #[cfg(feature = "execution")]
+            python_subprocesses: cli.execution_enable.then_some(cli.python_subprocesses),
+            #[cfg(feature = "execution")]
+            execution_memory_limit: cli.execution_memory_limit,
             sqlite_wal: match cli.sqlite_wal {
                 true => JournalMode::WAL,
                 false => JournalMode::Rollback,
             },
+            backup_before_migration: !cli.yes_i_have_a_backup,
+            storage_target_size: (cli.storage_target_size > 0)
+                .then(|| cli.storage_target_size.saturating_mul(1024 * 1024)),
+            storage_rebuild_for_incremental_vacuum: cli.storage_rebuild_for_incremental_vacuum,
+            storage_state_retention: cli.storage_state_retention,
+            storage_read_only: cli.storage_read_only,
+            storage_trie_node_cache_size: cli.storage_trie_node_cache_size,
+            retention: RetentionConfig {
+                database_backups: RetentionPolicy {
+                    max_count: std::num::NonZeroUsize::new(
+                        cli.retention_database_backups_max_count,
+                    ),
+                    max_age: cli.retention_database_backups_max_age.map(|days| {
+                        std::time::Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+                    }),
+                },
+            },
+            checkpoints: cli.sync_checkpoints,
+            irregular_blocks: cli.sync_irregular_blocks.map(IrregularBlocks::new),
+            lazy_class_download: cli.sync_lazy_class_download,
+            sync_behind_threshold: cli.sync_behind_threshold,
+            sync_download_workers: cli.sync_download_workers,
+            bandwidth_monthly_quota: cli
+                .bandwidth_monthly_quota
+                .map(|quota| quota.saturating_mul(1024 * 1024)),
+            #[cfg(feature = "execution")]
+            fork_block: cli.fork_block.map(block),
+            audit,
+            export_contract,
+            #[cfg(feature = "execution")]
+            import_contract,
+            export_chain,
+            import_chain,
+            import_snapshot,
+            #[cfg(feature = "execution")]
+            compare_traces,
+            #[cfg(feature = "rpc")]
+            conformance,
+            doctor,
+            reindex,
+        }
+    }
+}
+
+fn parse_status(s: &str) -> Result<StatusCode, String> {
+    s.parse::<u16>()
+        .ok()
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .ok_or_else(|| format!("{s} is not an HTTP status"))
+}
+
+fn parse_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| "expected a header as 'name: value'".to_owned())?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("{} is not a valid header name", name.trim()))?;
+    // The value is not echoed, as it is usually a secret.
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("The value of header {name} is not valid"))?;
+    Ok((name, value))
+}
+
+/// The cert and key are either both set or both unset, as clap requires them together.
+#[cfg(feature = "rpc")]
+fn tls_config(
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    client_ca: Option<PathBuf>,
+) -> Option<TlsConfig> {
+    match (cert, key) {
+        (Some(cert), Some(key)) => Some(TlsConfig {
+            cert: expand_home(cert),
+            key: expand_home(key),
+            client_ca: client_ca.map(expand_home),
+        }),
+        _ => None,
+    }
+}
+
+/// Expands a leading `~` to the user's home directory.
+///
+/// Shells only do this for command line arguments, so paths set via environment variables,
+/// e.g. in docker or systemd configurations, would otherwise create a literal `~` directory.
+fn expand_home(path: PathBuf) -> PathBuf {
+    let home = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+
+    match std::env::var_os(home) {
+        Some(home) => expand_home_with(path, Path::new(&home)),
+        None => path,
+    }
+}
+
+fn expand_home_with(path: PathBuf, home: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) if rest.as_os_str().is_empty() => home.to_owned(),
+        Ok(rest) => home.join(rest),
+        Err(_) => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_home() {
+        let home = Path::new("/home/pathfinder");
+
+        let cases = [
+            ("~", "/home/pathfinder"),
+            ("~/data", "/home/pathfinder/data"),
+            ("/data/~", "/data/~"),
+            ("~other/data", "~other/data"),
+            ("data", "data"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(
+                expand_home_with(PathBuf::from(path), home),
+                PathBuf::from(expected),
+                "{path}"
+            );
         }
     }
 }
@@ -0,0 +1,660 @@
+//! The `conformance` subcommand, which checks the RPC API against a specification version instead
+//! of running the node.
+//!
+//! Requests are sent to a temporary RPC server over the node's database, and each response is
+//! validated against the OpenRPC documents of the specification, as published in the
+//! [starknet-specs](https://github.com/starkware-libs/starknet-specs) repository. A result must
+//! match the schema of its method's result, and an error must have the code of one of its
+//! method's errors.
+//!
+//! The requests are a built-in suite, followed by requests for the transactions, contracts and
+//! classes of the genesis and latest blocks, so at least the genesis block needs to be synced.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_common::StarknetBlockNumber;
+use pathfinder_rpc::context::RpcContext;
+use pathfinder_rpc::{RpcServer, SyncState};
+use pathfinder_storage::{DatabaseLock, JournalMode, StarknetBlocksTable, Storage};
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::config::{Conformance, GatewayTransport, NetworkConfig, RpcSpec};
+use crate::PathfinderContext;
+
+/// Number of transactions, contracts and storage entries of each block which requests are
+/// derived from.
+const MAX_DERIVED: usize = 10;
+
+/// A bundled suite of requests.
+#[derive(Deserialize)]
+struct Suite {
+    cases: Vec<Case>,
+}
+
+#[derive(Deserialize)]
+struct Case {
+    method: String,
+    params: Value,
+    /// Code of the expected error, or [None] if a result is expected.
+    #[serde(default)]
+    error: Option<i64>,
+}
+
+impl Case {
+    fn result(method: &str, params: Value) -> Self {
+        Self {
+            method: method.to_owned(),
+            params,
+            error: None,
+        }
+    }
+}
+
+/// The methods and schemas of a specification version, merged from its OpenRPC documents.
+#[derive(Default)]
+struct Spec {
+    methods: HashMap<String, Method>,
+    /// Schemas by name, which are referred to as `#/components/schemas/<name>`, possibly from
+    /// another document.
+    schemas: Map<String, Value>,
+}
+
+struct Method {
+    /// Schema of the result.
+    result: Value,
+    /// Codes of the errors which the method may return.
+    errors: Vec<i64>,
+}
+
+impl Spec {
+    /// Merges the OpenRPC `documents`. References are resolved by name, as the documents of a
+    /// specification version refer to each other's schemas and errors.
+    fn parse(documents: &[Value]) -> anyhow::Result<Self> {
+        let mut spec = Spec::default();
+        let mut errors = Map::new();
+        for components in documents.iter().map(|document| &document["components"]) {
+            if let Some(schemas) = components["schemas"].as_object() {
+                spec.schemas.extend(schemas.clone());
+            }
+            if let Some(components) = components["errors"].as_object() {
+                errors.extend(components.clone());
+            }
+        }
+
+        for method in documents
+            .iter()
+            .filter_map(|document| document["methods"].as_array())
+            .flatten()
+        {
+            let name = method["name"].as_str().context("Method without a name")?;
+            let codes = method["errors"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|error| {
+                    let error = match error.get("$ref").and_then(Value::as_str) {
+                        Some(reference) => errors
+                            .get(reference_name(reference))
+                            .with_context(|| format!("Unknown error {reference} of {name}"))?,
+                        None => error,
+                    };
+                    error["code"]
+                        .as_i64()
+                        .with_context(|| format!("Error of {name} without a code"))
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            spec.methods.insert(
+                name.to_owned(),
+                Method {
+                    result: method["result"]["schema"].clone(),
+                    errors: codes,
+                },
+            );
+        }
+
+        anyhow::ensure!(!spec.methods.is_empty(), "The documents define no methods");
+        Ok(spec)
+    }
+}
+
+/// The name which a `$ref` refers to, i.e. its last path segment.
+fn reference_name(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+impl RpcSpec {
+    fn suite(&self) -> &'static str {
+        match self {
+            RpcSpec::V02 => include_str!("../../../resources/conformance/v0.2.json"),
+            RpcSpec::V03 => include_str!("../../../resources/conformance/v0.3.json"),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            RpcSpec::V02 => "v0.2",
+            RpcSpec::V03 => "v0.3",
+        }
+    }
+}
+
+pub async fn run(
+    config: Conformance,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let suite: Suite =
+        serde_json::from_str(config.spec.suite()).context("Parsing bundled conformance suite")?;
+
+    let mut documents = Vec::new();
+    for path in &config.spec_files {
+        let document = std::fs::read(path)
+            .with_context(|| format!("Reading specification {}", path.display()))?;
+        let document: Value = serde_json::from_slice(&document)
+            .with_context(|| format!("Parsing specification {}", path.display()))?;
+
+        let version = document["info"]["version"].as_str().unwrap_or_default();
+        anyhow::ensure!(
+            version.starts_with(&config.spec.name()[1..]),
+            "{} is the specification of version {version}, not {}",
+            path.display(),
+            config.spec.name()
+        );
+        documents.push(document);
+    }
+    let spec = Spec::parse(&documents).context("Parsing specification")?;
+
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database.clone(), journal_mode)?;
+
+    let read_storage = storage.clone();
+    let has_genesis = tokio::task::spawn_blocking(move || {
+        read_storage.read(|tx| {
+            let genesis = StarknetBlockNumber::GENESIS.into();
+            let genesis = StarknetBlocksTable::get(tx, genesis).context("Reading genesis block")?;
+            Ok(genesis.is_some())
+        })
+    })
+    .await
+    .context("Reading database panicked")??;
+    anyhow::ensure!(
+        has_genesis,
+        "The database has no blocks to check against, sync at least the genesis block first"
+    );
+
+    let rpc_context = RpcContext::new(
+        storage,
+        Arc::new(SyncState::default()),
+        context.network_id,
+        context.gateway,
+    );
+    let (rpc_handle, local_addr) = RpcServer::new(([127, 0, 0, 1], 0).into(), rpc_context)
+        .run()
+        .await
+        .context("Starting local RPC server")?;
+    let url = Url::parse(&format!("http://{local_addr}/"))
+        .expect("Valid URL")
+        .join(&format!("rpc/{}", config.spec.name()))
+        .context("Creating RPC URL")?;
+
+    let client = reqwest::Client::new();
+    let mut cases = suite.cases;
+    cases.extend(
+        derived_cases(&client, &url)
+            .await
+            .context("Deriving requests from the stored blocks")?,
+    );
+
+    let mut failures = 0;
+    for (id, case) in cases.iter().enumerate() {
+        let problems = check(&client, &url, id, case, &spec)
+            .await
+            .unwrap_or_else(|e| vec![format!("{e:#}")]);
+
+        if !problems.is_empty() {
+            failures += 1;
+        }
+        for problem in problems {
+            println!("{} {}: {problem}", case.method, case.params);
+        }
+    }
+
+    let _ = rpc_handle.stop();
+
+    anyhow::ensure!(
+        failures == 0,
+        "{failures} of {} requests do not conform to {}",
+        cases.len(),
+        config.spec.name()
+    );
+    tracing::info!(requests=%cases.len(), spec=%config.spec.name(), "All responses conform.");
+
+    Ok(())
+}
+
+/// Requests for the transactions of the genesis and latest blocks, and for the contracts, classes
+/// and storage in their state updates.
+async fn derived_cases(client: &reqwest::Client, url: &Url) -> anyhow::Result<Vec<Case>> {
+    let mut cases = Vec::new();
+    for block_id in [json!({"block_number": 0}), json!("latest")] {
+        let params = json!({ "block_id": block_id });
+        let block = send(client, url, 0, "starknet_getBlockWithTxHashes", &params).await?;
+        let block = block.get("result").context("Fetching block")?;
+        for (index, hash) in block["transactions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .take(MAX_DERIVED)
+            .enumerate()
+        {
+            let params = json!({ "transaction_hash": hash });
+            cases.push(Case::result(
+                "starknet_getTransactionByHash",
+                params.clone(),
+            ));
+            cases.push(Case::result("starknet_getTransactionReceipt", params));
+            cases.push(Case::result(
+                "starknet_getTransactionByBlockIdAndIndex",
+                json!({ "block_id": block_id, "index": index }),
+            ));
+        }
+
+        let update = send(client, url, 0, "starknet_getStateUpdate", &params).await?;
+        let diff = &update.get("result").context("Fetching state update")?["state_diff"];
+        for contract in diff["deployed_contracts"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .take(MAX_DERIVED)
+        {
+            let params = json!({ "block_id": block_id, "contract_address": contract["address"] });
+            cases.push(Case::result("starknet_getClassHashAt", params.clone()));
+            cases.push(Case::result("starknet_getClassAt", params.clone()));
+            cases.push(Case::result("starknet_getNonce", params));
+            cases.push(Case::result(
+                "starknet_getClass",
+                json!({ "block_id": block_id, "class_hash": contract["class_hash"] }),
+            ));
+        }
+        for storage in diff["storage_diffs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .take(MAX_DERIVED)
+        {
+            if let Some(key) = storage["storage_entries"][0].get("key") {
+                let address = &storage["address"];
+                cases.push(Case::result(
+                    "starknet_getStorageAt",
+                    json!({ "contract_address": address, "key": key, "block_id": block_id }),
+                ));
+            }
+        }
+    }
+
+    Ok(cases)
+}
+
+/// Sends a JSON-RPC request and returns the response.
+async fn send(
+    client: &reqwest::Client,
+    url: &Url,
+    id: usize,
+    method: &str,
+    params: &Value,
+) -> anyhow::Result<Value> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": params,
+    });
+    client
+        .post(url.clone())
+        .json(&request)
+        .send()
+        .await
+        .context("Sending request")?
+        .error_for_status()?
+        .json::<Value>()
+        .await
+        .context("Reading response")
+}
+
+/// Sends the request of `case` and returns how its response does not conform.
+async fn check(
+    client: &reqwest::Client,
+    url: &Url,
+    id: usize,
+    case: &Case,
+    spec: &Spec,
+) -> anyhow::Result<Vec<String>> {
+    let method = match spec.methods.get(&case.method) {
+        Some(method) => method,
+        None => return Ok(vec!["not a method of the specification".to_owned()]),
+    };
+    let response = send(client, url, id, &case.method, &case.params).await?;
+
+    let mut problems = Vec::new();
+    match (case.error, response.get("result"), response.get("error")) {
+        (None, Some(result), None) => validate(
+            &method.result,
+            result,
+            &spec.schemas,
+            "result",
+            &mut problems,
+        ),
+        (Some(expected), None, Some(error)) => {
+            if error.get("code").and_then(Value::as_i64) != Some(expected) {
+                problems.push(format!("expected error {expected}, got {error}"));
+            } else if !method.errors.contains(&expected) {
+                problems.push(format!("error {expected} is not specified for the method"));
+            }
+        }
+        _ => problems.push(format!("unexpected response {response}")),
+    }
+
+    Ok(problems)
+}
+
+/// Collects the ways in which `value` does not match `schema` into `problems`.
+///
+/// Supports the keywords which the specification uses: `$ref` to the named `schemas`, `allOf`,
+/// `oneOf`, `anyOf`, `not`, `enum`, `const`, `type`, `pattern`, `minimum`, `maximum`,
+/// `properties`, `required`, `additionalProperties` and `items`. `oneOf` is checked like `anyOf`,
+/// as the alternatives of the specification overlap, e.g. every block also matches the schema of
+/// a pending block.
+fn validate(
+    schema: &Value,
+    value: &Value,
+    schemas: &Map<String, Value>,
+    path: &str,
+    problems: &mut Vec<String>,
+) {
+    if schema == &Value::Bool(false) {
+        problems.push(format!("{path}: {value} is not allowed"));
+        return;
+    }
+
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        match schemas.get(reference_name(reference)) {
+            Some(schema) => validate(schema, value, schemas, path, problems),
+            None => problems.push(format!("{path}: unknown schema {reference}")),
+        }
+        return;
+    }
+
+    let matches = |schema: &Value| {
+        let mut ignored = Vec::new();
+        validate(schema, value, schemas, path, &mut ignored);
+        ignored.is_empty()
+    };
+
+    for schema in schema
+        .get("allOf")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        validate(schema, value, schemas, path, problems);
+    }
+
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(alternatives) = schema.get(keyword).and_then(Value::as_array) {
+            if !alternatives.iter().any(matches) {
+                problems.push(format!(
+                    "{path}: {value} matches none of the allowed schemas"
+                ));
+            }
+        }
+    }
+
+    if let Some(forbidden) = schema.get("not") {
+        if matches(forbidden) {
+            problems.push(format!("{path}: {value} matches a forbidden schema"));
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            problems.push(format!("{path}: {value} is not one of {}", schema["enum"]));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            problems.push(format!("{path}: {value} is not {expected}"));
+        }
+    }
+
+    let types = match schema.get("type") {
+        Some(Value::String(expected)) => vec![expected.as_str()],
+        Some(Value::Array(expected)) => expected.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    let is_type = |expected: &&str| match *expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => false,
+    };
+    if !types.is_empty() && !types.iter().any(is_type) {
+        problems.push(format!("{path}: {value} is not of type {}", schema["type"]));
+        return;
+    }
+
+    if let (Some(pattern), Some(string)) = (
+        schema.get("pattern").and_then(Value::as_str),
+        value.as_str(),
+    ) {
+        match regex::Regex::new(pattern) {
+            Ok(regex) if regex.is_match(string) => {}
+            Ok(_) => problems.push(format!("{path}: {value} does not match {pattern}")),
+            Err(e) => problems.push(format!("{path}: invalid pattern {pattern}: {e}")),
+        }
+    }
+
+    if let (Some(minimum), Some(number)) = (
+        schema.get("minimum").and_then(Value::as_f64),
+        value.as_f64(),
+    ) {
+        if number < minimum {
+            problems.push(format!("{path}: {value} is less than {minimum}"));
+        }
+    }
+    if let (Some(maximum), Some(number)) = (
+        schema.get("maximum").and_then(Value::as_f64),
+        value.as_f64(),
+    ) {
+        if number > maximum {
+            problems.push(format!("{path}: {value} is more than {maximum}"));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for required in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(required) {
+                problems.push(format!("{path}: missing {required}"));
+            }
+        }
+
+        for (key, member) in object {
+            let path = format!("{path}.{key}");
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(schema) => validate(schema, member, schemas, &path, problems),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => problems.push(format!("{path}: unexpected")),
+                    Some(schema @ Value::Object(_)) => {
+                        validate(schema, member, schemas, &path, problems)
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            validate(items, item, schemas, &format!("{path}[{i}]"), problems);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FELT: &str = "#/components/schemas/FELT";
+
+    fn schemas() -> Map<String, Value> {
+        let schemas = json!({
+            "FELT": {"type": "string", "pattern": "^0x(0|[a-fA-F1-9]{1}[a-fA-F0-9]{0,62})$"},
+        });
+        schemas.as_object().unwrap().clone()
+    }
+
+    fn problems(schema: Value, value: Value) -> Vec<String> {
+        let mut problems = Vec::new();
+        validate(&schema, &value, &schemas(), "result", &mut problems);
+        // Object members are not necessarily visited in order.
+        problems.sort();
+        problems
+    }
+
+    #[test]
+    fn objects() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "hash": {"$ref": FELT},
+                "number": {"type": "integer", "minimum": 0},
+                "hashes": {"type": "array", "items": {"$ref": FELT}},
+            },
+            "required": ["hash", "number"],
+            "additionalProperties": false,
+        });
+
+        assert!(problems(schema.clone(), json!({"hash": "0x1", "number": 2})).is_empty());
+        assert_eq!(
+            problems(
+                schema,
+                json!({"hash": "1", "hashes": ["0x2", "0x01"], "extra": null})
+            ),
+            vec![
+                r#"result.extra: unexpected"#,
+                r#"result.hash: "1" does not match ^0x(0|[a-fA-F1-9]{1}[a-fA-F0-9]{0,62})$"#,
+                r#"result.hashes[1]: "0x01" does not match ^0x(0|[a-fA-F1-9]{1}[a-fA-F0-9]{0,62})$"#,
+                "result: missing number",
+            ]
+        );
+    }
+
+    #[test]
+    fn combinations() {
+        // A block also matches the schema of a pending block, which only lacks members.
+        let block =
+            json!({"properties": {"block_hash": {"$ref": FELT}}, "required": ["block_hash"]});
+        let pending = json!({"properties": {"parent_hash": {"$ref": FELT}}});
+        let schema = json!({"oneOf": [block, pending]});
+        assert!(problems(schema, json!({"block_hash": "0x1", "parent_hash": "0x2"})).is_empty());
+
+        let schema = json!({"allOf": [
+            {"type": "object", "required": ["type"]},
+            {"properties": {"type": {"const": "INVOKE"}}},
+        ]});
+        assert!(problems(schema.clone(), json!({"type": "INVOKE"})).is_empty());
+        assert_eq!(
+            problems(schema, json!({"type": "DECLARE"})),
+            vec![r#"result.type: "DECLARE" is not "INVOKE""#]
+        );
+
+        let schema = json!({"anyOf": [{"enum": [false]}, {"type": ["object", "null"]}]});
+        assert!(problems(schema.clone(), json!(null)).is_empty());
+        assert_eq!(
+            problems(schema, json!(true)),
+            vec!["result: true matches none of the allowed schemas"]
+        );
+
+        assert_eq!(
+            problems(json!({"$ref": "#/components/schemas/MISSING"}), json!(1)),
+            vec!["result: unknown schema #/components/schemas/MISSING"]
+        );
+    }
+
+    #[test]
+    fn spec() {
+        let api = json!({
+            "info": {"version": "0.3.0"},
+            "methods": [{
+                "name": "starknet_getBlockWithTxHashes",
+                "result": {"name": "result", "schema": {"$ref": "#/components/schemas/BLOCK"}},
+                "errors": [{"$ref": "#/components/errors/BLOCK_NOT_FOUND"}],
+            }],
+            "components": {
+                "schemas": {"BLOCK": {"type": "object"}},
+                "errors": {"BLOCK_NOT_FOUND": {"code": 24, "message": "Block not found"}},
+            },
+        });
+        // Documents refer to each other's components.
+        const API: &str = "./api/starknet_api_openrpc.json#/components";
+        let write = json!({
+            "methods": [{
+                "name": "starknet_addInvokeTransaction",
+                "result": {"name": "result", "schema": {"$ref": format!("{API}/schemas/BLOCK")}},
+                "errors": [{"$ref": format!("{API}/errors/BLOCK_NOT_FOUND")}],
+            }],
+        });
+
+        let spec = Spec::parse(&[api, write]).unwrap();
+        for method in [
+            "starknet_getBlockWithTxHashes",
+            "starknet_addInvokeTransaction",
+        ] {
+            let method = &spec.methods[method];
+            assert_eq!(method.errors, vec![24]);
+
+            let mut problems = Vec::new();
+            validate(
+                &method.result,
+                &json!([]),
+                &spec.schemas,
+                "result",
+                &mut problems,
+            );
+            assert_eq!(problems, vec![r#"result: [] is not of type "object""#]);
+        }
+    }
+
+    /// Every bundled suite parses.
+    #[test]
+    fn bundled_suites() {
+        for spec in [RpcSpec::V02, RpcSpec::V03] {
+            let suite: Suite = serde_json::from_str(spec.suite()).unwrap();
+            assert!(!suite.cases.is_empty());
+        }
+    }
+}
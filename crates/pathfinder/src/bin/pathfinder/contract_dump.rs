@@ -0,0 +1,127 @@
+//! The `export-contract` and `import-contract` subcommands, which move a contract's state between
+//! the database and a dump file instead of running the node.
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pathfinder_lib::state::dump;
+#[cfg(feature = "execution")]
+use pathfinder_lib::state::fork;
+use pathfinder_storage::{DatabaseLock, JournalMode, StarknetBlocksBlockId, Storage};
+
+#[cfg(feature = "execution")]
+use crate::config::ImportContract;
+use crate::config::{ExportContract, GatewayTransport, NetworkConfig};
+use crate::PathfinderContext;
+
+pub async fn export(
+    config: ExportContract,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    let block = match config.block {
+        Some(number) => number.into(),
+        None => StarknetBlocksBlockId::Latest,
+    };
+
+    let output = config.output.clone();
+    let exported = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        let file = std::fs::File::create(&output)
+            .with_context(|| format!("Creating {}", output.display()))?;
+        let exported = dump::export(&tx, config.contract, block, std::io::BufWriter::new(file))
+            .with_context(|| format!("Writing {}", output.display()))?;
+        if exported.is_none() {
+            // Nothing was written.
+            let _ = std::fs::remove_file(&output);
+        }
+        Ok(exported)
+    })
+    .await
+    .context("Exporting contract panicked")??;
+
+    let (dump, storage_entries) = exported.with_context(|| {
+        format!(
+            "Contract {} is not deployed at the requested block",
+            config.contract
+        )
+    })?;
+
+    tracing::info!(
+        contract=%config.contract,
+        block=%dump.block_number,
+        %storage_entries,
+        output=%config.output.display(),
+        "Contract exported."
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "execution")]
+pub async fn import(
+    config: ImportContract,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    // The contract's state is imported into that of the forked block, which must be stored first.
+    let fork_block = fork::init(&storage, &context.gateway, config.fork_block)
+        .await
+        .context("Initializing fork")?;
+
+    let input = config.input.clone();
+    let (dump, storage_entries) = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        let file =
+            std::fs::File::open(&input).with_context(|| format!("Opening {}", input.display()))?;
+        let imported = dump::import(&tx, fork_block.number, std::io::BufReader::new(file))
+            .with_context(|| format!("Importing {}", input.display()))?;
+        tx.commit().context("Committing database transaction")?;
+
+        Ok(imported)
+    })
+    .await
+    .context("Importing contract panicked")??;
+
+    tracing::info!(
+        contract=%dump.contract_address,
+        from_block=%dump.block_number,
+        fork_block=%config.fork_block,
+        %storage_entries,
+        input=%config.input.display(),
+        "Contract imported."
+    );
+
+    Ok(())
+}
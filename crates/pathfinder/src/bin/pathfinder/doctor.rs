@@ -0,0 +1,365 @@
+//! The `doctor` subcommand, which checks the node's environment and prints a report instead of
+//! running the node.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pathfinder_common::BlockId;
+use pathfinder_ethereum::provider::EthereumTransport;
+use pathfinder_storage::JournalMode;
+use starknet_gateway_client::ClientApi;
+
+use crate::config::{GatewayTransport, NetworkConfig};
+use crate::{verify_networks, EthereumContext, PathfinderContext};
+
+/// How long to wait for the gateway or Ethereum before failing the check.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(10);
+/// Network responses slower than this are reported as a warning.
+const SLOW_RESPONSE: Duration = Duration::from_secs(2);
+/// Number of synced writes used to measure the disk.
+const FSYNC_SAMPLES: usize = 50;
+/// Median synced write latencies above these are reported as a warning and a failure.
+const SLOW_FSYNC: Duration = Duration::from_millis(10);
+const VERY_SLOW_FSYNC: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        })
+    }
+}
+
+/// The outcome of a single check.
+#[derive(Debug, PartialEq, Eq)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// How to address a warning or failure.
+    suggestion: Option<&'static str>,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+            suggestion: None,
+        }
+    }
+
+    fn suggest(self, suggestion: &'static str) -> Self {
+        Self {
+            suggestion: (self.status != Status::Pass).then_some(suggestion),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, "\n       hint: {suggestion}")?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(
+    ethereum: Option<EthereumContext>,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context = PathfinderContext::configure_and_proxy_check(
+        network,
+        gateway_transport,
+        data_directory.clone(),
+    )
+    .await
+    .context("Configuring pathfinder")?;
+
+    let mut checks = vec![
+        gateway(&context.gateway).await,
+        ethereum_check(ethereum, &context).await,
+    ];
+    let database = context.database.clone();
+    let local = tokio::task::spawn_blocking(move || {
+        [
+            disk(&data_directory),
+            journal(journal_mode),
+            integrity(&database),
+        ]
+    })
+    .await
+    .context("Checking disk and database panicked")?;
+    checks.extend(local);
+
+    for check in &checks {
+        println!("{check}");
+    }
+
+    let worst = checks
+        .iter()
+        .map(|check| check.status)
+        .max()
+        .unwrap_or(Status::Pass);
+    match worst {
+        Status::Pass => tracing::info!("All checks passed."),
+        Status::Warn => tracing::warn!("Some checks raised warnings."),
+        Status::Fail => anyhow::bail!("Some checks failed"),
+    }
+
+    Ok(())
+}
+
+async fn gateway(gateway: &impl ClientApi) -> Check {
+    const NAME: &str = "gateway";
+    const SUGGESTION: &str =
+        "Check the network connection, and the gateway URLs if this is a custom network.";
+
+    let start = Instant::now();
+    let check = match tokio::time::timeout(NETWORK_TIMEOUT, gateway.block(BlockId::Latest)).await {
+        Ok(Ok(block)) => {
+            let elapsed = start.elapsed();
+            let detail = match block.as_block() {
+                Some(block) => format!("latest block {} in {elapsed:?}", block.block_number),
+                None => format!("responded in {elapsed:?}"),
+            };
+            Check::new(NAME, latency_status(elapsed), detail)
+        }
+        Ok(Err(e)) => Check::new(NAME, Status::Fail, format!("request failed: {e}")),
+        Err(_) => Check::new(
+            NAME,
+            Status::Fail,
+            format!("no response within {NETWORK_TIMEOUT:?}"),
+        ),
+    };
+
+    check.suggest(SUGGESTION)
+}
+
+async fn ethereum_check(ethereum: Option<EthereumContext>, context: &PathfinderContext) -> Check {
+    const NAME: &str = "ethereum";
+
+    let ethereum = match ethereum {
+        Some(EthereumContext {
+            transport,
+            chain: Some(chain),
+        }) => match verify_networks(context.network, chain) {
+            Ok(()) => transport,
+            Err(e) => {
+                return Check::new(NAME, Status::Fail, e.to_string()).suggest(
+                    "Point --ethereum.url at an endpoint of the matching Ethereum network.",
+                )
+            }
+        },
+        Some(EthereumContext { chain: None, .. }) => {
+            return Check::new(NAME, Status::Fail, "endpoint is unreachable")
+                .suggest("Check --ethereum.url and --ethereum.password.")
+        }
+        None => return Check::new(NAME, Status::Warn, "disabled").suggest(
+            "Without Ethereum, the synced state is not verified against L1. Set --ethereum.url.",
+        ),
+    };
+
+    let start = Instant::now();
+    let check = match tokio::time::timeout(NETWORK_TIMEOUT, ethereum.block_number()).await {
+        Ok(Ok(number)) => {
+            let elapsed = start.elapsed();
+            Check::new(
+                NAME,
+                latency_status(elapsed),
+                format!("latest block {number} in {elapsed:?}"),
+            )
+        }
+        Ok(Err(e)) => Check::new(NAME, Status::Fail, format!("request failed: {e}")),
+        Err(_) => Check::new(
+            NAME,
+            Status::Fail,
+            format!("no response within {NETWORK_TIMEOUT:?}"),
+        ),
+    };
+
+    check.suggest("A slow or rate limited Ethereum endpoint delays L1 verification.")
+}
+
+fn latency_status(elapsed: Duration) -> Status {
+    if elapsed > SLOW_RESPONSE {
+        Status::Warn
+    } else {
+        Status::Pass
+    }
+}
+
+/// Measures the latency of synced writes in `directory`, which bounds how fast blocks can be
+/// committed to the database.
+fn disk(directory: &Path) -> Check {
+    const NAME: &str = "disk";
+
+    let latencies = match fsync_latencies(directory, FSYNC_SAMPLES) {
+        Ok(latencies) => latencies,
+        Err(e) => return Check::new(NAME, Status::Fail, format!("{e:#}")),
+    };
+
+    disk_check(latencies).suggest(
+        "Use a local SSD for the data directory; network storage and hard drives slow down sync.",
+    )
+}
+
+fn disk_check(mut latencies: Vec<Duration>) -> Check {
+    latencies.sort();
+    let median = latencies[latencies.len() / 2];
+    let worst = latencies[latencies.len() - 1];
+    let per_second = 1.0 / median.as_secs_f64().max(f64::EPSILON);
+
+    let status = if median > VERY_SLOW_FSYNC {
+        Status::Fail
+    } else if median > SLOW_FSYNC {
+        Status::Warn
+    } else {
+        Status::Pass
+    };
+
+    Check::new(
+        "disk",
+        status,
+        format!(
+            "synced writes take {median:?} (worst {worst:?}), about {per_second:.0} per second"
+        ),
+    )
+}
+
+fn fsync_latencies(directory: &Path, samples: usize) -> anyhow::Result<Vec<Duration>> {
+    let mut file = tempfile::NamedTempFile::new_in(directory)
+        .with_context(|| format!("Creating a file in {}", directory.display()))?;
+    let page = [0u8; 4096];
+
+    (0..samples)
+        .map(|_| {
+            let start = Instant::now();
+            file.write_all(&page).context("Writing to file")?;
+            file.as_file().sync_data().context("Syncing file")?;
+            Ok(start.elapsed())
+        })
+        .collect()
+}
+
+fn journal(journal_mode: JournalMode) -> Check {
+    match journal_mode {
+        JournalMode::WAL => Check::new("journal", Status::Pass, "write-ahead logging"),
+        JournalMode::Rollback => Check::new("journal", Status::Warn, "rollback journal")
+            .suggest("Enable --sqlite-wal, which lets RPC reads proceed while sync writes."),
+    }
+}
+
+/// Runs SQLite's quick check on the database, without migrating or locking it, so that it can be
+/// checked while the node is running.
+fn integrity(database: &Path) -> Check {
+    const NAME: &str = "database";
+
+    if !database.exists() {
+        return Check::new(
+            NAME,
+            Status::Warn,
+            format!("{} does not exist yet", database.display()),
+        )
+        .suggest("The database is created when the node first starts.");
+    }
+
+    let result =
+        rusqlite::Connection::open_with_flags(database, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .and_then(|connection| {
+                connection.query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0))
+            });
+
+    let check = match result {
+        Ok(result) if result == "ok" => Check::new(
+            NAME,
+            Status::Pass,
+            format!("{} passed the quick check", database.display()),
+        ),
+        Ok(problem) => Check::new(NAME, Status::Fail, format!("corrupted: {problem}")),
+        Err(e) => Check::new(NAME, Status::Fail, format!("cannot be read: {e}")),
+    };
+
+    check.suggest("Restore the database from a backup or snapshot, or sync it again.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_thresholds() {
+        let millis = |values: &[u64]| {
+            values
+                .iter()
+                .copied()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        };
+
+        let check = disk_check(millis(&[2, 1, 40, 1, 2]));
+        assert_eq!(check.status, Status::Pass);
+        assert_eq!(
+            check.detail,
+            "synced writes take 2ms (worst 40ms), about 500 per second"
+        );
+
+        assert_eq!(disk_check(millis(&[20, 20, 1])).status, Status::Warn);
+        assert_eq!(disk_check(millis(&[100, 100, 1])).status, Status::Fail);
+    }
+
+    #[test]
+    fn fsync_latencies_are_measured() {
+        let directory = tempfile::tempdir().unwrap();
+        let latencies = fsync_latencies(directory.path(), 3).unwrap();
+        assert_eq!(latencies.len(), 3);
+    }
+
+    #[test]
+    fn integrity_of_database() {
+        let directory = tempfile::tempdir().unwrap();
+        let database = directory.path().join("test.sqlite");
+        assert_eq!(integrity(&database).status, Status::Warn);
+
+        let connection = rusqlite::Connection::open(&database).unwrap();
+        connection
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+        drop(connection);
+        assert_eq!(integrity(&database).status, Status::Pass);
+
+        std::fs::write(&database, b"not a database").unwrap();
+        assert_eq!(integrity(&database).status, Status::Fail);
+    }
+
+    #[test]
+    fn display() {
+        let check =
+            Check::new("journal", Status::Warn, "rollback journal").suggest("Enable --sqlite-wal.");
+        assert_eq!(
+            check.to_string(),
+            "[WARN] journal: rollback journal\n       hint: Enable --sqlite-wal."
+        );
+
+        let check = Check::new("journal", Status::Pass, "write-ahead logging")
+            .suggest("Enable --sqlite-wal.");
+        assert_eq!(check.to_string(), "[PASS] journal: write-ahead logging");
+    }
+}
@@ -1,28 +1,53 @@
 #![deny(rust_2018_idioms)]
 
 use anyhow::Context;
-use metrics_exporter_prometheus::PrometheusBuilder;
+use futures::FutureExt;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+use pathfinder_common::bandwidth::Bandwidth;
 use pathfinder_common::EthereumAddress;
 use pathfinder_common::{
-    consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT, Chain, ChainId, EthereumChain, StarknetBlockNumber,
+    consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT, Chain, ChainId, EthereumChain, StarknetBlockHash,
+    StarknetBlockNumber,
 };
-use pathfinder_ethereum::provider::{EthereumTransport, HttpProvider};
+use pathfinder_ethereum::provider::{DisabledTransport, EthereumTransport, HttpProvider};
 use pathfinder_lib::{
     monitoring::{self},
-    state,
+    persisted_metrics, state, systemd, vacuum,
 };
-use pathfinder_rpc::{cairo, metrics::logger::RpcMetricsLogger, SyncState};
-use pathfinder_storage::Storage;
+#[cfg(feature = "execution")]
+use pathfinder_rpc::cairo;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::context::NodeIdentity;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::prefetch::{BlockPrefetch, PrefetchConfig};
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::{metrics::logger::RpcMetricsLogger, tls::TlsConfig, LivenessProbe};
+#[cfg(feature = "rpc")]
+use pathfinder_storage::GatewayResponseStore;
+use pathfinder_storage::{DatabaseLock, Storage, TreePruningTable, TrieNodeCache};
 use starknet_gateway_client::ClientApi;
 use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::sync_state::SyncState;
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Duration;
 use tracing::info;
 
 use crate::config::NetworkConfig;
 
+mod audit;
+mod chain_archive;
+#[cfg(feature = "execution")]
+mod compare_traces;
 mod config;
+#[cfg(feature = "rpc")]
+mod conformance;
+mod contract_dump;
+mod doctor;
+mod preflight;
+mod reindex;
+mod snapshot;
 mod update;
 
 #[tokio::main]
@@ -45,107 +70,775 @@ async fn main() -> anyhow::Result<()> {
 
     // A readiness flag which is used to indicate that pathfinder is ready via monitoring.
     let readiness = Arc::new(AtomicBool::new(false));
+    // Filled in once the node is set up, which the status page reports until then.
+    let node_status = monitoring::NodeStatus::default();
+
+    // Spawn monitoring if configured, which is not needed by the subcommands.
+    let runs_node = config.audit.is_none()
+        && config.export_contract.is_none()
+        && config.export_chain.is_none()
+        && config.import_chain.is_none()
+        && config.import_snapshot.is_none()
+        && !config.doctor
+        && !config.reindex;
+    #[cfg(feature = "execution")]
+    let runs_node =
+        runs_node && config.import_contract.is_none() && config.compare_traces.is_none();
+    #[cfg(feature = "rpc")]
+    let runs_node = runs_node && config.conformance.is_none();
+    if let Some(address) = config.monitor_address.filter(|_| runs_node) {
+        #[cfg(feature = "rpc")]
+        let monitoring = spawn_monitoring(
+            address,
+            config.monitor_tls.clone(),
+            readiness.clone(),
+            node_status.clone(),
+        );
+        #[cfg(not(feature = "rpc"))]
+        let monitoring = spawn_monitoring(address, readiness.clone(), node_status.clone());
+        monitoring.await.context("Starting monitoring task")?;
+    }
 
-    // Spawn monitoring if configured.
-    if let Some(address) = config.monitor_address {
-        spawn_monitoring(address, readiness.clone())
-            .await
-            .context("Starting monitoring task")?;
+    #[cfg(feature = "ethereum")]
+    let ethereum = match config.ethereum {
+        Some(ethereum) => Some(
+            EthereumContext::setup(ethereum.url, ethereum.password, ethereum.proxy)
+                .await
+                .context("Creating Ethereum context")?,
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "ethereum"))]
+    let ethereum: Option<EthereumContext> = None;
+
+    let network = select_network(config.network, ethereum.as_ref())?;
+
+    if let Some(audit) = config.audit {
+        return audit::run(
+            audit,
+            ethereum,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
     }
 
-    let ethereum = EthereumContext::setup(config.ethereum.url, config.ethereum.password)
-        .await
-        .context("Creating Ethereum context")?;
+    if config.doctor {
+        return doctor::run(
+            ethereum,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
 
-    // Use the default starknet network if none was configured.
-    let network = match config.network {
-        Some(network) => network,
-        None => ethereum
-            .default_network()
-            .context("Using default Starknet network based on Ethereum configuration")?,
-    };
+    if config.reindex {
+        return reindex::run(
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
 
-    let pathfinder_context =
-        PathfinderContext::configure_and_proxy_check(network, config.data_directory)
-            .await
-            .context("Configuring pathfinder")?;
+    if let Some(export) = config.export_contract {
+        return contract_dump::export(
+            export,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
+
+    #[cfg(feature = "execution")]
+    if let Some(import) = config.import_contract {
+        return contract_dump::import(
+            import,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
+
+    if let Some(export) = config.export_chain {
+        return chain_archive::export(
+            export,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
+
+    if let Some(import) = config.import_chain {
+        return chain_archive::import(
+            import,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
+
+    if let Some(import) = config.import_snapshot {
+        return snapshot::import(
+            import,
+            ethereum,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+        )
+        .await;
+    }
+
+    #[cfg(feature = "execution")]
+    if let Some(compare) = config.compare_traces {
+        return compare_traces::run(
+            compare,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
 
-    verify_networks(pathfinder_context.network, ethereum.chain)?;
+    #[cfg(feature = "rpc")]
+    if let Some(conformance) = config.conformance {
+        return conformance::run(
+            conformance,
+            network,
+            config.gateway_transport.clone(),
+            config.data_directory,
+            config.sqlite_wal,
+        )
+        .await;
+    }
+
+    let mut pathfinder_context = PathfinderContext::configure(
+        network,
+        config.gateway_transport.clone(),
+        config.data_directory,
+    )
+    .context("Configuring pathfinder")?;
+    if config.gateway_report_unknown_fields {
+        pathfinder_context.gateway = pathfinder_context.gateway.with_unknown_field_reporting();
+    }
+    let verify_fallbacks = !config.feeder_gateway_fallbacks.is_empty();
+    if verify_fallbacks {
+        pathfinder_context.gateway = pathfinder_context
+            .gateway
+            .with_feeder_gateway_fallbacks(config.feeder_gateway_fallbacks);
+    }
+    pathfinder_context.gateway = pathfinder_context
+        .gateway
+        .with_retry_policy(config.gateway_retry);
+
+    let bandwidth = Bandwidth::new(config.bandwidth_monthly_quota);
+    pathfinder_context.gateway = pathfinder_context
+        .gateway
+        .with_bandwidth_accounting(bandwidth.clone());
+    let ethereum = ethereum.map(|ethereum| EthereumContext {
+        transport: ethereum
+            .transport
+            .with_bandwidth_accounting(bandwidth.clone()),
+        ..ethereum
+    });
+
+    // Held until shutdown to prevent another instance from using the same database. Read-only
+    // replicas share the database with the instance which holds the lock.
+    let _database_lock = match config.storage_read_only {
+        true => None,
+        false => {
+            let database_lock =
+                DatabaseLock::acquire(&pathfinder_context.database).context("Locking database")?;
+            tracing::debug!(path=%database_lock.path().display(), "Database locked.");
+            Some(database_lock)
+        }
+    };
 
     // Setup and verify database
-    let storage = Storage::migrate(pathfinder_context.database.clone(), config.sqlite_wal).unwrap();
-    info!(location=?pathfinder_context.database, "Database migrated.");
-    verify_database(
-        &storage,
+    let storage = match config.storage_read_only {
+        true => {
+            let storage = Storage::open_read_only(pathfinder_context.database.clone())
+                .context("Opening database")?;
+            info!(location=?pathfinder_context.database, "Database opened read-only.");
+            storage
+        }
+        false => {
+            let storage = Storage::migrate_with_backup(
+                pathfinder_context.database.clone(),
+                config.sqlite_wal,
+                config.backup_before_migration,
+            )
+            .unwrap();
+            info!(location=?pathfinder_context.database, "Database migrated.");
+            if config.storage_rebuild_for_incremental_vacuum {
+                let storage = storage.clone();
+                tokio::task::spawn_blocking(move || vacuum::enable_incremental(&storage))
+                    .await
+                    .context("Joining database rebuild task")?
+                    .context("Enabling incremental vacuum")?;
+            }
+            config
+                .retention
+                .apply(&pathfinder_context.database)
+                .context("Applying file retention")?;
+            storage
+        }
+    };
+    TrieNodeCache::configure(config.storage_trie_node_cache_size);
+    verify_database(&storage, pathfinder_context.network)
+        .await
+        .context("Verifying database")?;
+
+    // Without a reachable feeder gateway we start in degraded mode, and sync completes the
+    // checks which need it once it becomes available.
+    let mut gateway_checks = Box::pin(verify_gateway(
+        // Retried until the gateway responds, whatever the configured retry policy.
+        pathfinder_context
+            .gateway
+            .clone()
+            .with_retry_policy(Default::default()),
+        storage.clone(),
         pathfinder_context.network,
-        &pathfinder_context.gateway,
+        pathfinder_context.l1_core_address,
+        verify_fallbacks,
+    ));
+    let (gateway_degraded, gateway_checks) = match tokio::time::timeout(
+        GATEWAY_STARTUP_TIMEOUT,
+        &mut gateway_checks,
     )
     .await
-    .context("Verifying database")?;
+    {
+        Ok(checked) => {
+            let (network, l1_core_address) = checked?;
+            pathfinder_context.network = network;
+            pathfinder_context.l1_core_address = Some(l1_core_address);
+            (
+                false,
+                futures::future::ready(Ok((network, l1_core_address))).boxed(),
+            )
+        }
+        Err(_) => {
+            tracing::warn!(
+                "Feeder gateway is unreachable, starting in degraded mode. Only stored data will be served until it becomes available."
+            );
+            (true, gateway_checks.boxed())
+        }
+    };
+
+    // Without a reachable Ethereum endpoint we start in degraded mode: stored data is
+    // served over RPC while sync waits for the endpoint in the background.
+    let ethereum_degraded = match ethereum.as_ref().map(|ethereum| ethereum.chain) {
+        Some(Some(chain)) => {
+            verify_networks(pathfinder_context.network, chain)?;
+            false
+        }
+        Some(None) => {
+            tracing::warn!(
+                "Ethereum endpoint is unreachable, starting in degraded mode. Only stored data will be served until it becomes available."
+            );
+            true
+        }
+        None => {
+            info!("Ethereum is disabled, L1 state will not be synced.");
+            false
+        }
+    };
+
+    let degraded = ethereum_degraded || gateway_degraded;
+    persisted_metrics::restore(&storage, &bandwidth).context("Restoring persisted metrics")?;
+    if !config.storage_read_only {
+        storage
+            .write(|tx| TreePruningTable::set_retention(tx, config.storage_state_retention))
+            .context("Configuring state pruning")?;
+        if let Some(retained_blocks) = config.storage_state_retention {
+            info!(%retained_blocks, "Pruning historical state.");
+        }
+    }
+    // Only RPC requests are cached, as sync stores what it downloads in the database anyway.
+    #[cfg(feature = "rpc")]
+    let rpc_gateway = match config.gateway_response_cache {
+        Some(max_size) if !config.storage_read_only => pathfinder_context
+            .gateway
+            .clone()
+            .with_response_store(Arc::new(GatewayResponseStore::new(
+                storage.clone(),
+                max_size,
+            ))),
+        _ => pathfinder_context.gateway.clone(),
+    };
 
     let sync_state = Arc::new(SyncState::default());
+    sync_state
+        .degraded
+        .store(degraded, std::sync::atomic::Ordering::Relaxed);
+    sync_state
+        .milestones
+        .set_behind_threshold(config.sync_behind_threshold);
+    node_status.set(monitoring::StatusSource {
+        storage: storage.clone(),
+        sync_state: sync_state.clone(),
+        gateway_availability: pathfinder_context.gateway.availability().clone(),
+        ethereum_reachability: ethereum
+            .as_ref()
+            .map(|ethereum| ethereum.transport.reachability().clone()),
+        read_only: config.storage_read_only,
+    });
     let pending_state = PendingData::default();
-    let pending_interval = match config.poll_pending {
+    // Only sync polls the pending block.
+    let poll_pending = config.poll_pending && !config.storage_read_only;
+    let pending_interval = match poll_pending {
         true => Some(std::time::Duration::from_secs(5)),
         false => None,
     };
 
-    // TODO: the error could be recovered, but currently it's required for startup. There should
-    // not be other reason for the start to fail than python script not firing up.
-    let (call_handle, cairo_handle) = cairo::ext_py::start(
-        storage.path().into(),
-        config.python_subprocesses,
-        futures::future::pending(),
-        pathfinder_context.network,
-    )
-    .await
-    .context(
-        "Creating python process for call handling. Have you setup our Python dependencies?",
-    )?;
+    // Class definitions deferred by lazy sync are downloaded on first use. These can exist even if
+    // lazy download has since been disabled. Replicas cannot store them, so they wait for the
+    // writer to download them instead.
+    #[cfg(feature = "rpc")]
+    let deferred_class_download = match config.storage_read_only {
+        true => None,
+        false => {
+            let storage = storage.clone();
+            let sequencer = pathfinder_context.gateway.clone();
+            let download: pathfinder_rpc::context::DeferredClassDownload =
+                Arc::new(move |class_hash: pathfinder_common::ClassHash| {
+                    let storage = storage.clone();
+                    let sequencer = sequencer.clone();
+                    async move { state::deferred::download(&storage, &sequencer, class_hash).await }
+                        .boxed()
+                });
+            Some(download)
+        }
+    };
 
-    let sync_handle = tokio::spawn(state::sync(
-        storage.clone(),
-        ethereum.transport.clone(),
-        pathfinder_context.network,
-        pathfinder_context.l1_core_address.0,
-        pathfinder_context.gateway.clone(),
-        sync_state.clone(),
-        state::l1::sync,
-        state::l2::sync,
-        pending_state.clone(),
-        pending_interval,
-        state::l2::BlockValidationMode::Strict,
-    ));
+    // In fork mode nothing is synced, instead calls execute against the state of the forked block
+    // which is fetched from the gateway as it is used.
+    #[cfg(feature = "execution")]
+    let fork_state_fetch = match config.fork_block {
+        Some(number) => {
+            let fork = state::fork::init(&storage, &pathfinder_context.gateway, number)
+                .await
+                .context("Initializing fork")?;
+            info!(block=%fork.number, hash=%fork.hash, "Forking from block, sync is disabled.");
+
+            let storage = storage.clone();
+            let sequencer = pathfinder_context.gateway.clone();
+            let fork = Arc::new(fork);
+            let fetch: pathfinder_rpc::context::ForkStateFetch = Arc::new(move |missing| {
+                let storage = storage.clone();
+                let sequencer = sequencer.clone();
+                let fork = fork.clone();
+                async move { state::fork::fetch(&storage, &sequencer, &fork, missing).await }
+                    .boxed()
+            });
+
+            Some((number, fetch))
+        }
+        None => None,
+    };
 
-    let shared = pathfinder_rpc::gas_price::Cached::new(Arc::new(ethereum.transport));
+    #[cfg(feature = "execution")]
+    let (call_handle, cairo_handle): (Option<cairo::ext_py::Handle>, _) = match config
+        .python_subprocesses
+    {
+        Some(python_subprocesses) => {
+            if matches!(config.sqlite_wal, pathfinder_storage::JournalMode::Rollback) {
+                tracing::warn!(
+                    "Execution requests block sync from writing to the database while they run, \
+                    as write-ahead logging is disabled"
+                );
+            }
 
-    let context = pathfinder_rpc::context::RpcContext::new(
-        storage.clone(),
-        sync_state.clone(),
-        pathfinder_context.network_id,
-        pathfinder_context.gateway,
-    )
-    .with_call_handling(call_handle)
-    .with_eth_gas_price(shared);
-    let context = match config.poll_pending {
-        true => context.with_pending_data(pending_state),
-        false => context,
+            // TODO: the error could be recovered, but currently it's required for startup. There should
+            // not be other reason for the start to fail than python script not firing up.
+            let started = match &fork_state_fetch {
+                Some((fork_block, _)) => {
+                    cairo::ext_py::start_forked(
+                        storage.path().into(),
+                        *fork_block,
+                        python_subprocesses,
+                        futures::future::pending(),
+                        pathfinder_context.network,
+                    )
+                    .await
+                }
+                None => {
+                    cairo::ext_py::start(
+                        storage.path().into(),
+                        python_subprocesses,
+                        futures::future::pending(),
+                        pathfinder_context.network,
+                    )
+                    .await
+                }
+            };
+            let (call_handle, cairo_handle) = started.context(
+                "Creating python process for call handling. Have you setup our Python dependencies?",
+            )?;
+            if let Some(download) = &deferred_class_download {
+                call_handle.set_deferred_class_download(download.clone());
+            }
+            call_handle.set_memory_limit(cairo::ext_py::MemoryLimit::new(
+                config.execution_memory_limit,
+            ));
+            if let Some((_, fetch)) = &fork_state_fetch {
+                call_handle.set_fork_state_fetch(fetch.clone());
+            }
+
+            (Some(call_handle), cairo_handle)
+        }
+        None => {
+            info!("Execution engine is disabled.");
+            (None, tokio::spawn(futures::future::pending()))
+        }
     };
+    #[cfg(not(feature = "execution"))]
+    let cairo_handle = tokio::spawn(futures::future::pending::<()>());
+
+    let block_validation_mode = match &config.irregular_blocks {
+        Some(irregular_blocks) => state::l2::BlockValidationMode::StrictWithIrregularBlocks(
+            Arc::new(irregular_blocks.clone()),
+        ),
+        None => state::l2::BlockValidationMode::Strict,
+    };
+    // Sync starts once the startup checks which degraded mode deferred have completed.
+    let startup = {
+        let network = pathfinder_context.network;
+        let transport = ethereum
+            .as_ref()
+            .filter(|_| ethereum_degraded)
+            .map(|ethereum| ethereum.transport.clone());
+        let sync_state = sync_state.clone();
+        async move {
+            let (checked_network, l1_core_address) = gateway_checks.await?;
+            anyhow::ensure!(
+                checked_network == network,
+                "Proxy gateway for {checked_network} detected after startup, restart pathfinder to serve it"
+            );
+            if let Some(transport) = transport {
+                wait_for_ethereum(&transport, network).await?;
+            }
 
-    let (rpc_handle, local_addr) = pathfinder_rpc::RpcServer::new(config.rpc_address, context)
-        .with_logger(RpcMetricsLogger)
-        .run()
-        .await
-        .context("Starting the RPC server")?;
+            if degraded {
+                sync_state
+                    .degraded
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+                info!("Upstream endpoints are reachable, leaving degraded mode.");
+            }
 
-    info!("📡 HTTP-RPC server started on: {}", local_addr);
+            Ok(l1_core_address)
+        }
+    };
+    let sync_handle = match &ethereum {
+        // Replicas follow the chain synced by the writer instead.
+        _ if config.storage_read_only => tokio::spawn({
+            let follow = state::replica::follow(
+                storage.clone(),
+                sync_state.clone(),
+                state::replica::POLL_INTERVAL,
+            );
+            async move {
+                startup.await?;
+                follow.await
+            }
+        }),
+        #[cfg(feature = "execution")]
+        _ if fork_state_fetch.is_some() => tokio::spawn(async move {
+            startup.await?;
+            futures::future::pending::<anyhow::Result<()>>().await
+        }),
+        #[cfg(feature = "ethereum")]
+        Some(ethereum) => tokio::spawn({
+            let storage = storage.clone();
+            let transport = ethereum.transport.clone();
+            let network = pathfinder_context.network;
+            let gateway = pathfinder_context.gateway.clone();
+            let sync_state = sync_state.clone();
+            let pending_state = pending_state.clone();
+            let checkpoints = config.checkpoints.clone();
+            let lazy_class_download = config.lazy_class_download;
+            let download_workers = config.sync_download_workers;
+            async move {
+                let l1_core_address = startup.await?;
+                let rate_limit = gateway.rate_limit().clone();
+                state::sync(
+                    storage,
+                    transport,
+                    network,
+                    l1_core_address.0,
+                    gateway,
+                    sync_state,
+                    state::l1::sync,
+                    state::l2::sync,
+                    pending_state,
+                    pending_interval,
+                    block_validation_mode,
+                    checkpoints,
+                    lazy_class_download,
+                    Default::default(),
+                    rate_limit,
+                    download_workers,
+                )
+                .await
+            }
+        }),
+        #[cfg(not(feature = "ethereum"))]
+        Some(_) => unreachable!("Ethereum is only set up with the ethereum feature"),
+        None => tokio::spawn({
+            let storage = storage.clone();
+            let network = pathfinder_context.network;
+            let gateway = pathfinder_context.gateway.clone();
+            let sync_state = sync_state.clone();
+            let pending_state = pending_state.clone();
+            let checkpoints = config.checkpoints.clone();
+            let lazy_class_download = config.lazy_class_download;
+            let download_workers = config.sync_download_workers;
+            async move {
+                let l1_core_address = startup.await?;
+                let rate_limit = gateway.rate_limit().clone();
+                state::sync(
+                    storage,
+                    DisabledTransport,
+                    network,
+                    l1_core_address.0,
+                    gateway,
+                    sync_state,
+                    state::l1::disabled,
+                    state::l2::sync,
+                    pending_state,
+                    pending_interval,
+                    block_validation_mode,
+                    checkpoints,
+                    lazy_class_download,
+                    Default::default(),
+                    rate_limit,
+                    download_workers,
+                )
+                .await
+            }
+        }),
+    };
 
-    let p2p_handle = start_p2p(pathfinder_context.network_id, storage, sync_state).await?;
+    #[cfg(feature = "rpc")]
+    let rpc: Option<(pathfinder_rpc::ServerHandle, SocketAddr, LivenessProbe)> = match config
+        .rpc_address
+    {
+        Some(rpc_address) => {
+            // Replicas report the retention which the writer configured.
+            let state_retention = match config.storage_read_only {
+                true => storage
+                    .read(|tx| TreePruningTable::get_retention(tx))
+                    .context("Reading state retention")?,
+                false => config.storage_state_retention,
+            };
+            let context = pathfinder_rpc::context::RpcContext::new(
+                storage.clone(),
+                sync_state.clone(),
+                pathfinder_context.network_id,
+                rpc_gateway.clone(),
+            )
+            .with_node_identity(NodeIdentity {
+                network: Some(pathfinder_context.network),
+                p2p: cfg!(feature = "p2p"),
+                state_retention,
+            });
+            let context = match deferred_class_download {
+                Some(download) => context.with_deferred_class_download(download),
+                None => context,
+            };
+            let context = match config.storage_read_only {
+                true => context.with_read_only(),
+                false => context,
+            };
+            // Config guarantees that the execution engine is enabled for local pending execution.
+            #[cfg(feature = "execution")]
+            let context = match (poll_pending, config.execute_pending, &call_handle) {
+                (true, true, Some(call_handle)) => context.with_pending_data(
+                    cairo::ext_py::LocalPending::new(call_handle.clone(), pending_state),
+                ),
+                (true, _, _) => context.with_pending_data(pending_state),
+                (false, _, _) => context,
+            };
+            #[cfg(not(feature = "execution"))]
+            let context = match poll_pending {
+                true => context.with_pending_data(pending_state),
+                false => context,
+            };
+            #[cfg(feature = "execution")]
+            let context = match call_handle {
+                Some(call_handle) => context.with_call_handling(call_handle),
+                None => context,
+            };
+            let context = match &ethereum {
+                Some(ethereum) => context.with_eth_gas_price(
+                    pathfinder_rpc::gas_price::Cached::new(Arc::new(ethereum.transport.clone())),
+                ),
+                None => context,
+            };
+            let context = match config.rpc_get_events_max_cost {
+                Some(max_cost) => context.with_get_events_max_cost(max_cost),
+                None => context,
+            };
+            let context = match config.rpc_prefetch_blocks {
+                Some(window) => context.with_block_prefetch(BlockPrefetch::new(
+                    rpc_gateway,
+                    PrefetchConfig::new(window),
+                )),
+                None => context,
+            };
+
+            // Hourly, so that a summary covers clients which only call now and then.
+            tokio::spawn(pathfinder_rpc::deprecation::report(
+                context.deprecated_calls.clone(),
+                Duration::from_secs(60 * 60),
+            ));
+
+            let rpc_server = pathfinder_rpc::RpcServer::new(rpc_address, context)
+                .with_logger(RpcMetricsLogger)
+                .with_request_log(config.rpc_request_log)
+                .with_request_limits(config.rpc_request_limits);
+            let rpc_server = match config.rpc_tls.clone() {
+                Some(tls) => rpc_server.with_tls(tls),
+                None => rpc_server,
+            };
+            let rpc_server = match config.rpc_ip_filter {
+                Some(ip_filter) => rpc_server.with_ip_filter(ip_filter),
+                None => rpc_server,
+            };
+            let rpc_server = match &config.rpc_api_keys {
+                Some(path) => rpc_server.with_api_keys(
+                    pathfinder_rpc::api_keys::ApiKeys::from_file(path)
+                        .context("Loading API keys")?,
+                ),
+                None => rpc_server,
+            };
+            let rpc_server = match &config.rpc_attestation_key {
+                Some(path) => {
+                    let attestor = pathfinder_rpc::attestation::Attestor::from_file(
+                        path,
+                        pathfinder_context.network_id,
+                    )
+                    .context("Loading attestation key")?;
+                    info!(signer=?attestor.signer(), "Attesting RPC responses");
+                    rpc_server.with_attestor(attestor)
+                }
+                None => rpc_server,
+            };
+
+            let (rpc_handle, local_addr, probe) = rpc_server
+                .run_with_liveness_probe()
+                .await
+                .context("Starting the RPC server")?;
+
+            match config.rpc_tls {
+                Some(_) => info!("📡 HTTPS-RPC server started on: {}", local_addr),
+                None => info!("📡 HTTP-RPC server started on: {}", local_addr),
+            }
+
+            Some((rpc_handle, local_addr, probe))
+        }
+        None => {
+            info!("HTTP-RPC server is disabled.");
+            None
+        }
+    };
+    #[cfg(feature = "rpc")]
+    let local_addr = rpc.as_ref().map(|(_, local_addr, _)| *local_addr);
+    #[cfg(feature = "rpc")]
+    let probe = rpc.as_ref().map(|(_, _, probe)| probe.clone());
+    #[cfg(feature = "rpc")]
+    let rpc_stopped = async move {
+        match rpc {
+            Some((rpc_handle, _, _)) => rpc_handle.stopped().await,
+            None => futures::future::pending().await,
+        }
+    };
+    #[cfg(not(feature = "rpc"))]
+    let (local_addr, rpc_stopped) = {
+        info!("HTTP-RPC server is disabled.");
+        (None, futures::future::pending::<()>())
+    };
+
+    let p2p_handle = start_p2p(
+        pathfinder_context.network_id,
+        storage.clone(),
+        sync_state.clone(),
+    )
+    .await?;
+
+    #[cfg(feature = "execution")]
+    let execution = config.python_subprocesses.is_some();
+    #[cfg(not(feature = "execution"))]
+    let execution = false;
+    preflight::StartupSummary::collect(
+        &storage,
+        pathfinder_context.network,
+        pathfinder_context.network_id,
+        &pathfinder_context.gateway,
+        preflight::Settings {
+            rpc_address: local_addr,
+            monitor_address: config.monitor_address,
+            poll_pending,
+            execution,
+            ethereum: ethereum.is_some(),
+            degraded,
+        },
+    )
+    .await
+    .context("Collecting startup summary")?
+    .print()?;
 
     let update_handle = tokio::spawn(update::poll_github_for_releases());
+    // The instance which syncs the database maintains it.
+    if !config.storage_read_only {
+        if let Some(target_size) = config.storage_target_size {
+            tokio::spawn(vacuum::run(storage.clone(), target_size));
+        }
+        tokio::spawn(state::deferred::run(
+            storage.clone(),
+            pathfinder_context.gateway.clone(),
+            bandwidth.clone(),
+        ));
+        tokio::spawn(persisted_metrics::persist_bandwidth(
+            storage.clone(),
+            bandwidth,
+        ));
+    }
 
     // We are now ready.
     readiness.store(true, std::sync::atomic::Ordering::Relaxed);
+    systemd::notify_ready();
+
+    if let Some(interval) = systemd::watchdog_interval() {
+        info!(?interval, "Notifying systemd watchdog");
+        tokio::spawn(systemd::watchdog(interval, move || {
+            #[cfg(feature = "rpc")]
+            let probe = probe.clone();
+            let storage = storage.clone();
+            let sync_state = sync_state.clone();
+            async move {
+                #[cfg(feature = "rpc")]
+                if let Some(probe) = probe {
+                    probe.check().await?;
+                }
+                liveness_probe(storage, sync_state).await
+            }
+        }));
+    }
 
     // Monitor our spawned process tasks.
     tokio::select! {
@@ -161,7 +854,7 @@ async fn main() -> anyhow::Result<()> {
                 Err(err) => tracing::error!("Cairo process ended unexpected; failed to join task handle: {:?}", err),
             }
         }
-        _result = rpc_handle.stopped() => {
+        _result = rpc_stopped => {
             // This handle returns () so its not very useful.
             tracing::error!("RPC server process ended unexpected");
         }
@@ -179,6 +872,34 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    systemd::notify_stopping();
+
+    Ok(())
+}
+
+/// How long sync's main loop may go without a [heartbeat](SyncState::beat) before the liveness
+/// probe fails. Storing a block can keep the loop busy for a while, so this is generous.
+const SYNC_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Checks that the database is accessible and that sync's main loop is making progress, if it has
+/// started. Used to drive the systemd watchdog, together with the check of the RPC server.
+async fn liveness_probe(storage: Storage, sync_state: Arc<SyncState>) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let conn = storage.connection().context("Create database connection")?;
+        conn.query_row("SELECT 1", [], |_| Ok(()))
+            .context("Querying database")
+    })
+    .await
+    .context("Database probe panicked")??;
+
+    // Sync only starts beating once the upstream endpoints are reachable.
+    if let Some(since_heartbeat) = sync_state.since_heartbeat() {
+        anyhow::ensure!(
+            since_heartbeat < SYNC_HEARTBEAT_TIMEOUT,
+            "Sync has not made progress for {since_heartbeat:?}"
+        );
+    }
+
     Ok(())
 }
 
@@ -236,12 +957,32 @@ async fn start_p2p(
         .map(|a| a.parse::<p2p::libp2p::Multiaddr>())
         .collect::<Result<Vec<_>, _>>()?;
 
-    let (_p2p_peers, _p2p_client, p2p_handle) = pathfinder_lib::p2p_network::start(
+    let header_signers = std::env::var("PATHFINDER_P2P_HEADER_SIGNERS")
+        .unwrap_or_default()
+        .split_ascii_whitespace()
+        .map(|a| a.parse::<ethers::types::Address>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Parsing PATHFINDER_P2P_HEADER_SIGNERS")?;
+    let header_signature_threshold =
+        match std::env::var("PATHFINDER_P2P_HEADER_SIGNATURE_THRESHOLD") {
+            Ok(threshold) => threshold
+                .parse()
+                .context("Parsing PATHFINDER_P2P_HEADER_SIGNATURE_THRESHOLD")?,
+            Err(_) => header_signers.len(),
+        };
+    let header_verifier = pathfinder_lib::p2p_network::header_verification::for_network(
+        chain_id,
+        header_signers,
+        header_signature_threshold,
+    )?;
+
+    let (_p2p_peers, _p2p_client, _accepted_head, p2p_handle) = pathfinder_lib::p2p_network::start(
         chain_id,
         storage,
         sync_state,
         listen_on,
         &bootstrap_addresses,
+        header_verifier,
     )
     .await?;
 
@@ -262,32 +1003,81 @@ async fn start_p2p(
 /// Spawns the monitoring task at the given address.
 async fn spawn_monitoring(
     address: SocketAddr,
+    #[cfg(feature = "rpc")] tls: Option<TlsConfig>,
     readiness: Arc<AtomicBool>,
+    node_status: monitoring::NodeStatus,
 ) -> anyhow::Result<tokio::task::JoinHandle<()>> {
+    #[cfg(feature = "rpc")]
+    if let Some(tls) = &tls {
+        tls.server_config()
+            .context("Loading monitoring TLS certificates")?;
+    }
+
     let prometheus_handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(starknet_gateway_client::METRIC_REQUEST_DURATION.to_owned()),
+            starknet_gateway_client::REQUEST_DURATION_BUCKETS,
+        )
+        .context("Configuring request duration buckets")?
         .install_recorder()
         .context("Creating Prometheus recorder")?;
 
-    let handle = monitoring::spawn_server(address, readiness, prometheus_handle).await;
+    #[cfg(feature = "rpc")]
+    let handle =
+        monitoring::spawn_server(address, tls, readiness, prometheus_handle, node_status).await;
+    #[cfg(not(feature = "rpc"))]
+    let handle = monitoring::spawn_server(address, readiness, prometheus_handle, node_status).await;
     Ok(handle)
 }
 
+/// Uses the default Starknet network of the Ethereum chain if none was configured.
+fn select_network(
+    network: Option<NetworkConfig>,
+    ethereum: Option<&EthereumContext>,
+) -> anyhow::Result<NetworkConfig> {
+    match (network, ethereum) {
+        (Some(network), _) => Ok(network),
+        (None, Some(ethereum)) => ethereum
+            .default_network()
+            .context("Using default Starknet network based on Ethereum configuration"),
+        (None, None) => anyhow::bail!(
+            "The Starknet network must be specified using '--network' when Ethereum is disabled."
+        ),
+    }
+}
+
 /// Convenience bundle for an Ethereum transport and chain.
 struct EthereumContext {
     transport: HttpProvider,
-    chain: EthereumChain,
+    /// [None] if the Ethereum endpoint could not be reached during startup.
+    chain: Option<EthereumChain>,
 }
 
 impl EthereumContext {
-    /// Configure an [EthereumContext]'s transport and read the chain ID using it.
-    async fn setup(url: reqwest::Url, password: Option<String>) -> anyhow::Result<Self> {
-        let transport = HttpProvider::from_config(url, password).context("Creating transport")?;
+    /// How long to wait for the Ethereum endpoint at startup before continuing without it.
+    #[cfg(feature = "ethereum")]
+    const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
 
-        let chain = transport.chain().await.context(
-            r"Determining Ethereum chain.
+    /// Configure an [EthereumContext]'s transport and read the chain ID using it.
+    ///
+    /// The chain is left unknown if the endpoint does not respond within [Self::STARTUP_TIMEOUT].
+    #[cfg(feature = "ethereum")]
+    async fn setup(
+        url: reqwest::Url,
+        password: Option<String>,
+        proxy: Option<reqwest::Url>,
+    ) -> anyhow::Result<Self> {
+        let transport =
+            HttpProvider::from_config(url, password, proxy).context("Creating transport")?;
+
+        let chain = match tokio::time::timeout(Self::STARTUP_TIMEOUT, transport.chain()).await {
+            Ok(chain) => Some(chain.context(
+                r"Determining Ethereum chain.
                             
 Hint: Make sure the provided ethereum.url and ethereum.password are good.",
-        )?;
+            )?),
+            Err(_) => None,
+        };
 
         Ok(Self { transport, chain })
     }
@@ -297,9 +1087,14 @@ Hint: Make sure the provided ethereum.url and ethereum.password are good.",
     ///     Goerli  => Testnet
     fn default_network(&self) -> anyhow::Result<NetworkConfig> {
         match self.chain {
-            EthereumChain::Mainnet => Ok(NetworkConfig::Mainnet),
-            EthereumChain::Goerli => Ok(NetworkConfig::Testnet),
-            EthereumChain::Other(id) => {
+            None => anyhow::bail!(
+                r"Timed out determining Ethereum chain.
+
+Hint: Make sure the provided ethereum.url and ethereum.password are good. To start without a reachable Ethereum endpoint, please specify the Starknet network using '--network'."
+            ),
+            Some(EthereumChain::Mainnet) => Ok(NetworkConfig::Mainnet),
+            Some(EthereumChain::Goerli) => Ok(NetworkConfig::Testnet),
+            Some(EthereumChain::Other(id)) => {
                 anyhow::bail!(
                     r"Implicit Starknet networks are only available for Ethereum mainnet and Goerli, but the provided Ethereum network has chain ID = {id}.
 
@@ -315,13 +1110,15 @@ struct PathfinderContext {
     network_id: ChainId,
     gateway: starknet_gateway_client::Client,
     database: PathBuf,
-    l1_core_address: EthereumAddress,
+    /// [None] for custom networks, whose L1 core contract is only known once it has been
+    /// downloaded from the gateway.
+    l1_core_address: Option<EthereumAddress>,
 }
 
 /// Used to hide private fn's for [PathfinderContext].
 mod pathfinder_context {
     use super::PathfinderContext;
-    use crate::config::NetworkConfig;
+    use crate::config::{GatewayTransport, NetworkConfig};
 
     use std::path::PathBuf;
 
@@ -340,76 +1137,136 @@ mod pathfinder_context {
         const TESTNET2_CORE: EthereumAddress = EthereumAddress(TESTNET2_ADDRESSES.core);
         const INTEGRATION_CORE: EthereumAddress = EthereumAddress(INTEGRATION_ADDRESSES.core);
 
+        /// Configures the network like [configure](Self::configure), and then completes the
+        /// [proxy check](Self::proxy_check) of a custom network.
         pub async fn configure_and_proxy_check(
             cfg: NetworkConfig,
+            transport: GatewayTransport,
+            data_directory: PathBuf,
+        ) -> anyhow::Result<Self> {
+            let mut context = Self::configure(cfg, transport, data_directory)?;
+            if context.l1_core_address.is_none() {
+                let (network, l1_core_address) = Self::proxy_check(&context.gateway).await?;
+                context.network = network;
+                context.l1_core_address = Some(l1_core_address);
+            }
+
+            Ok(context)
+        }
+
+        /// Configures the network without contacting the gateway.
+        pub fn configure(
+            cfg: NetworkConfig,
+            transport: GatewayTransport,
             data_directory: PathBuf,
         ) -> anyhow::Result<Self> {
-            let context = match cfg {
+            let mut context = match cfg {
                 NetworkConfig::Mainnet => Self {
                     network: Chain::Mainnet,
                     network_id: ChainId::MAINNET,
                     gateway: GatewayClient::mainnet(),
                     database: data_directory.join("mainnet.sqlite"),
-                    l1_core_address: Self::MAINNET_CORE,
+                    l1_core_address: Some(Self::MAINNET_CORE),
                 },
                 NetworkConfig::Testnet => Self {
                     network: Chain::Testnet,
                     network_id: ChainId::TESTNET,
                     gateway: GatewayClient::testnet(),
                     database: data_directory.join("goerli.sqlite"),
-                    l1_core_address: Self::TESTNET_CORE,
+                    l1_core_address: Some(Self::TESTNET_CORE),
                 },
                 NetworkConfig::Testnet2 => Self {
                     network: Chain::Testnet2,
                     network_id: ChainId::TESTNET2,
                     gateway: GatewayClient::testnet2(),
                     database: data_directory.join("testnet2.sqlite"),
-                    l1_core_address: Self::TESTNET2_CORE,
+                    l1_core_address: Some(Self::TESTNET2_CORE),
                 },
                 NetworkConfig::Integration => Self {
                     network: Chain::Integration,
                     network_id: ChainId::INTEGRATION,
                     gateway: GatewayClient::integration(),
                     database: data_directory.join("integration.sqlite"),
-                    l1_core_address: Self::INTEGRATION_CORE,
+                    l1_core_address: Some(Self::INTEGRATION_CORE),
                 },
                 NetworkConfig::Custom {
                     gateway,
                     feeder_gateway,
                     chain_id,
-                } => Self::configure_custom(gateway, feeder_gateway, chain_id, data_directory)
-                    .await
-                    .context("Configuring custom network")?,
+                } => {
+                    return Self::configure_custom(
+                        gateway,
+                        feeder_gateway,
+                        chain_id,
+                        transport,
+                        data_directory,
+                    )
+                    .context("Configuring custom network")
+                }
             };
 
+            context.gateway = Self::apply_transport(context.gateway, transport)?;
+
             Ok(context)
         }
 
-        /// Creates a [PathfinderContext] for a custom network. Provides additional verification
-        /// by checking for a proxy gateway by comparing against L1 starknet address against of
-        /// the known networks.
-        async fn configure_custom(
+        fn apply_transport(
+            mut gateway: GatewayClient,
+            transport: GatewayTransport,
+        ) -> anyhow::Result<GatewayClient> {
+            if let Some(proxy) = transport.proxy {
+                gateway = gateway.with_proxy(proxy)?;
+            }
+            if !transport.headers.is_empty() {
+                gateway = gateway.with_headers(transport.headers);
+            }
+            Ok(gateway)
+        }
+
+        /// Creates a [PathfinderContext] for a custom network. Its L1 core contract address is
+        /// left unknown until the [proxy check](Self::proxy_check).
+        fn configure_custom(
             gateway: Url,
             feeder: Url,
             chain_id: String,
+            transport: GatewayTransport,
             data_directory: PathBuf,
         ) -> anyhow::Result<Self> {
             use stark_hash::Felt;
-            use starknet_gateway_client::ClientApi;
 
             let gateway =
                 GatewayClient::with_urls(gateway, feeder).context("Creating gateway client")?;
+            let gateway = Self::apply_transport(gateway, transport)?;
 
             let network_id =
                 ChainId(Felt::from_be_slice(chain_id.as_bytes()).context("Parsing chain ID")?);
 
+            let context = Self {
+                network: Chain::Custom,
+                network_id,
+                gateway,
+                database: data_directory.join("custom.sqlite"),
+                l1_core_address: None,
+            };
+
+            Ok(context)
+        }
+
+        /// Downloads the L1 core contract address of a custom network from the gateway, and checks
+        /// for a proxy gateway by comparing it against those of the known networks.
+        ///
+        /// Returns the network which the gateway serves along with its L1 core contract address.
+        pub async fn proxy_check(
+            gateway: &GatewayClient,
+        ) -> anyhow::Result<(Chain, EthereumAddress)> {
+            use starknet_gateway_client::ClientApi;
+
             let l1_core_address = gateway
                 .eth_contract_addresses()
                 .await
                 .context("Downloading starknet L1 address from gateway for proxy check")?
                 .starknet;
 
-            // Check for proxies by comparing the core address against those of the known networks.
             let network = match l1_core_address {
                 x if x == Self::MAINNET_CORE => Chain::Mainnet,
                 x if x == Self::TESTNET_CORE => Chain::Testnet,
@@ -422,19 +1279,31 @@ mod pathfinder_context {
                 tracing::info!(%network, "Proxy gateway detected");
             }
 
-            let context = Self {
-                network,
-                network_id,
-                gateway,
-                database: data_directory.join("custom.sqlite"),
-                l1_core_address,
-            };
+            Ok((network, l1_core_address))
+        }
 
-            Ok(context)
+        /// The L1 core contract address, which is known once the [proxy check](Self::proxy_check)
+        /// of a custom network has completed.
+        pub fn core_address(&self) -> EthereumAddress {
+            self.l1_core_address
+                .expect("L1 core address is known after the proxy check")
         }
     }
 }
 
+/// Waits for the Ethereum endpoint to become reachable, and then verifies it.
+async fn wait_for_ethereum(transport: &HttpProvider, network: Chain) -> anyhow::Result<()> {
+    // The transport retries internally until the endpoint responds.
+    let chain = transport
+        .chain()
+        .await
+        .context("Determining Ethereum chain")?;
+    verify_networks(network, chain)?;
+    info!("Ethereum endpoint is reachable.");
+
+    Ok(())
+}
+
 /// Errors if there is a mismatch between the starknet and ethereum networks.
 fn verify_networks(starknet: Chain, ethereum: EthereumChain) -> anyhow::Result<()> {
     if starknet != Chain::Custom {
@@ -452,15 +1321,69 @@ fn verify_networks(starknet: Chain, ethereum: EthereumChain) -> anyhow::Result<(
     Ok(())
 }
 
-async fn verify_database(
-    storage: &Storage,
+/// How long to wait for the feeder gateway at startup before continuing without it.
+const GATEWAY_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Completes the startup checks which need the feeder gateway: the
+/// [proxy check](PathfinderContext::proxy_check) of a custom network if its L1 core contract
+/// address is unknown, the verification of any feeder gateway fallbacks, and the verification of
+/// a custom network's database against the gateway.
+///
+/// Returns the network which the gateway serves along with its L1 core contract address.
+async fn verify_gateway(
+    gateway: starknet_gateway_client::Client,
+    storage: Storage,
     network: Chain,
-    gateway_client: &starknet_gateway_client::Client,
-) -> anyhow::Result<()> {
+    l1_core_address: Option<EthereumAddress>,
+    verify_fallbacks: bool,
+) -> anyhow::Result<(Chain, EthereumAddress)> {
+    let (network, l1_core_address) = match l1_core_address {
+        Some(l1_core_address) => (network, l1_core_address),
+        None => {
+            let (network, l1_core_address) = PathfinderContext::proxy_check(&gateway).await?;
+            // The database was verified as custom before a proxy gateway could be detected.
+            if network != Chain::Custom {
+                verify_database(&storage, network)
+                    .await
+                    .context("Verifying database")?;
+            }
+            (network, l1_core_address)
+        }
+    };
+
+    if verify_fallbacks {
+        gateway
+            .verify_feeder_gateway_fallbacks()
+            .await
+            .context("Verifying feeder gateway fallbacks")?;
+    }
+
+    if network == Chain::Custom {
+        if let Some(database_genesis) = database_genesis(&storage).await? {
+            let gateway_block = gateway
+                .block(StarknetBlockNumber::GENESIS.into())
+                .await
+                .context("Downloading genesis block from gateway for database verification")?
+                .as_block()
+                .context("Genesis block should not be pending")?;
+
+            anyhow::ensure!(
+                database_genesis == gateway_block.block_hash,
+                "Database genesis block does not match gateway. {} != {}",
+                database_genesis,
+                gateway_block.block_hash
+            );
+        }
+    }
+
+    Ok((network, l1_core_address))
+}
+
+async fn database_genesis(storage: &Storage) -> anyhow::Result<Option<StarknetBlockHash>> {
     use pathfinder_storage::StarknetBlocksTable;
 
     let storage = storage.clone();
-    let db_genesis = tokio::task::spawn_blocking(move || {
+    tokio::task::spawn_blocking(move || {
         let mut conn = storage.connection().context("Create database connection")?;
         let tx = conn.transaction().context("Create database transaction")?;
 
@@ -468,9 +1391,13 @@ async fn verify_database(
     })
     .await
     .context("Fetching genesis hash from database")?
-    .context("Waiting for genesis block to be fetched from database")?;
+    .context("Waiting for genesis block to be fetched from database")
+}
 
-    if let Some(database_genesis) = db_genesis {
+/// Verifies that the database belongs to the given network. Custom networks are verified against
+/// the gateway by [verify_gateway] instead.
+async fn verify_database(storage: &Storage, network: Chain) -> anyhow::Result<()> {
+    if let Some(database_genesis) = database_genesis(storage).await? {
         use pathfinder_common::consts::{
             INTEGRATION_GENESIS_HASH, MAINNET_GENESIS_HASH, TESTNET2_GENESIS_HASH,
             TESTNET_GENESIS_HASH,
@@ -485,22 +1412,7 @@ async fn verify_database(
         };
 
         match (network, db_network) {
-            (Chain::Custom, _) => {
-                // Verify against gateway.
-                let gateway_block = gateway_client
-                    .block(StarknetBlockNumber::GENESIS.into())
-                    .await
-                    .context("Downloading genesis block from gateway for database verification")?
-                    .as_block()
-                    .context("Genesis block should not be pending")?;
-
-                anyhow::ensure!(
-                    database_genesis == gateway_block.block_hash,
-                    "Database genesis block does not match gateway. {} != {}",
-                    database_genesis,
-                    gateway_block.block_hash
-                );
-            }
+            (Chain::Custom, _) => {}
             (network, db_network) => anyhow::ensure!(
                 network == db_network,
                 "Database ({}) does not match the expected network ({})",
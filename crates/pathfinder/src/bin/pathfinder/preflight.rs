@@ -0,0 +1,181 @@
+//! Startup summary of the node's identity and state.
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::{BlockId, Chain, ChainId};
+use pathfinder_storage::{StarknetBlocksTable, Storage};
+use serde::Serialize;
+use starknet_gateway_client::ClientApi;
+
+/// How long to wait for the gateway's latest block before omitting it from the summary.
+const GATEWAY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Summary of the node printed once at startup, both as human readable text and
+/// as a single JSON line for orchestration tooling.
+#[derive(Debug, Serialize)]
+pub struct StartupSummary {
+    version: &'static str,
+    network: String,
+    chain_id: String,
+    /// Latest block stored in the database.
+    head_block: Option<u64>,
+    /// Latest block according to the gateway, if it was reachable.
+    chain_head_block: Option<u64>,
+    /// Number of blocks the database is behind the gateway.
+    blocks_behind: Option<u64>,
+    database_path: PathBuf,
+    database_size_bytes: u64,
+    features: Vec<&'static str>,
+    /// [None] if the RPC server is disabled.
+    rpc_address: Option<SocketAddr>,
+    monitor_address: Option<SocketAddr>,
+    degraded: bool,
+}
+
+pub struct Settings {
+    pub rpc_address: Option<SocketAddr>,
+    pub monitor_address: Option<SocketAddr>,
+    pub poll_pending: bool,
+    pub execution: bool,
+    pub ethereum: bool,
+    pub degraded: bool,
+}
+
+impl StartupSummary {
+    pub async fn collect(
+        storage: &Storage,
+        network: Chain,
+        chain_id: ChainId,
+        gateway: &impl ClientApi,
+        settings: Settings,
+    ) -> anyhow::Result<Self> {
+        let head_block = {
+            let storage = storage.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut conn = storage.connection().context("Create database connection")?;
+                let tx = conn.transaction().context("Create database transaction")?;
+
+                StarknetBlocksTable::get_latest_number(&tx)
+            })
+            .await
+            .context("Fetching latest block number from database")??
+            .map(|number| number.get())
+        };
+
+        let chain_head_block = match tokio::time::timeout(
+            GATEWAY_TIMEOUT,
+            gateway.block(BlockId::Latest),
+        )
+        .await
+        {
+            Ok(Ok(block)) => block.as_block().map(|block| block.block_number.get()),
+            Ok(Err(e)) => {
+                tracing::debug!(error=%e, "Fetching latest block from gateway for startup summary");
+                None
+            }
+            Err(_) => None,
+        };
+
+        let blocks_behind = chain_head_block.map(|chain_head| {
+            let head = head_block.map(|head| head + 1).unwrap_or_default();
+            chain_head.saturating_add(1).saturating_sub(head)
+        });
+
+        let database_path = storage.path().to_owned();
+        let database_size_bytes = database_size(&database_path);
+
+        let mut features = Vec::new();
+        if cfg!(feature = "p2p") {
+            features.push("p2p");
+        }
+        if cfg!(feature = "tokio-console") {
+            features.push("tokio-console");
+        }
+        if settings.poll_pending {
+            features.push("poll-pending");
+        }
+        if settings.rpc_address.is_some() {
+            features.push("rpc");
+        }
+        if settings.execution {
+            features.push("execution");
+        }
+        if settings.ethereum {
+            features.push("ethereum");
+        }
+
+        Ok(Self {
+            version: pathfinder_common::consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT,
+            network: network.to_string(),
+            chain_id: chain_id.to_hex_str().into_owned(),
+            head_block,
+            chain_head_block,
+            blocks_behind,
+            database_path,
+            database_size_bytes,
+            features,
+            rpc_address: settings.rpc_address,
+            monitor_address: settings.monitor_address,
+            degraded: settings.degraded,
+        })
+    }
+
+    /// Logs the summary as human readable text, and prints it as a single JSON line to stdout.
+    pub fn print(&self) -> anyhow::Result<()> {
+        let or_unknown = |x: Option<u64>| {
+            x.map(|x| x.to_string())
+                .unwrap_or_else(|| "unknown".to_owned())
+        };
+
+        tracing::info!(
+            version = self.version,
+            network = %self.network,
+            chain_id = %self.chain_id,
+            head_block = %or_unknown(self.head_block),
+            chain_head_block = %or_unknown(self.chain_head_block),
+            blocks_behind = %or_unknown(self.blocks_behind),
+            database = %self.database_path.display(),
+            database_size_bytes = self.database_size_bytes,
+            features = ?self.features,
+            rpc_address = ?self.rpc_address,
+            monitor_address = ?self.monitor_address,
+            degraded = self.degraded,
+            "📋 Startup summary."
+        );
+
+        let json = serde_json::to_string(self).context("Serializing startup summary")?;
+        println!("{json}");
+
+        Ok(())
+    }
+}
+
+/// Size of the database including its WAL file, if any.
+fn database_size(path: &std::path::Path) -> u64 {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+
+    [path.as_os_str().to_owned(), wal]
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_size_includes_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.sqlite");
+        std::fs::write(&path, [0u8; 10]).unwrap();
+        assert_eq!(database_size(&path), 10);
+
+        std::fs::write(dir.path().join("test.sqlite-wal"), [0u8; 5]).unwrap();
+        assert_eq!(database_size(&path), 15);
+    }
+}
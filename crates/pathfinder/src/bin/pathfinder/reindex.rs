@@ -0,0 +1,45 @@
+//! The `reindex` subcommand, which rebuilds the event indexes of the database instead of running
+//! the node.
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::Context;
+use pathfinder_storage::{DatabaseLock, JournalMode, StarknetEventsTable, Storage};
+
+use crate::config::{GatewayTransport, NetworkConfig};
+use crate::PathfinderContext;
+
+pub async fn run(
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+    journal_mode: JournalMode,
+) -> anyhow::Result<()> {
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    let storage = Storage::migrate(context.database, journal_mode)?;
+
+    tracing::info!("Rebuilding event indexes, this might take a while");
+    let started = Instant::now();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        StarknetEventsTable::reindex(&tx)?;
+        tx.commit().context("Committing database transaction")
+    })
+    .await
+    .context("Reindexing panicked")??;
+
+    tracing::info!(elapsed=?started.elapsed(), "Event indexes rebuilt.");
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! The `import-snapshot` subcommand, which bootstraps the database from a snapshot instead of
+//! syncing from genesis.
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use pathfinder_lib::state::{self, audit};
+use pathfinder_storage::snapshot::{self, SnapshotHead};
+use pathfinder_storage::DatabaseLock;
+use reqwest::Url;
+
+use crate::config::{GatewayTransport, ImportSnapshot, NetworkConfig, SnapshotSource};
+use crate::{verify_database, verify_networks, EthereumContext, PathfinderContext};
+
+pub async fn import(
+    config: ImportSnapshot,
+    ethereum: Option<EthereumContext>,
+    network: NetworkConfig,
+    gateway_transport: GatewayTransport,
+    data_directory: PathBuf,
+) -> anyhow::Result<()> {
+    let proxy = gateway_transport.proxy.clone();
+    let context =
+        PathfinderContext::configure_and_proxy_check(network, gateway_transport, data_directory)
+            .await
+            .context("Configuring pathfinder")?;
+
+    let transport = match ethereum {
+        Some(EthereumContext {
+            transport,
+            chain: Some(chain),
+        }) => {
+            verify_networks(context.network, chain)?;
+            transport
+        }
+        Some(EthereumContext { chain: None, .. }) => {
+            anyhow::bail!("Ethereum endpoint is unreachable")
+        }
+        None => anyhow::bail!("Importing a snapshot requires Ethereum to verify it against"),
+    };
+
+    let _lock = DatabaseLock::acquire(&context.database).context("Locking database")?;
+    anyhow::ensure!(
+        !context.database.exists(),
+        "Database {} already exists, snapshots can only bootstrap a new database",
+        context.database.display()
+    );
+
+    let (file, downloaded) = match config.source {
+        SnapshotSource::File(path) => (path, None),
+        SnapshotSource::Url(url) => {
+            let path = download_path(&context.database);
+            download(url, proxy, &path).await?;
+            (path.clone(), Some(path))
+        }
+    };
+
+    tracing::info!(snapshot=%file.display(), "Staging snapshot.");
+    let database = context.database.clone();
+    let staged = tokio::task::spawn_blocking(move || {
+        let file =
+            std::fs::File::open(&file).with_context(|| format!("Opening {}", file.display()))?;
+        snapshot::stage(std::io::BufReader::new(file), &database)
+    })
+    .await
+    .context("Staging snapshot panicked")?;
+    if let Some(downloaded) = downloaded {
+        let _ = std::fs::remove_file(downloaded);
+    }
+    let staged = staged?;
+
+    let head = staged.head().clone();
+    tracing::info!(number=%head.number, hash=%head.hash, "Verifying snapshot.");
+
+    verify_database(staged.storage(), context.network, &context.gateway)
+        .await
+        .context("Verifying snapshot network")?;
+    verify_head(&head, &transport, context.core_address().0).await?;
+
+    tracing::info!("Verifying snapshot chain and state, this may take a while.");
+    let storage = staged.storage().clone();
+    let chain = context.network;
+    let verified_head = head.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut connection = storage.connection().context("Opening snapshot")?;
+        let tx = connection
+            .transaction()
+            .context("Creating database transaction")?;
+        state::snapshot::verify(&tx, chain, &verified_head, &Default::default())?;
+        tx.commit().context("Committing database transaction")
+    })
+    .await
+    .context("Verifying snapshot panicked")??;
+
+    staged.commit()?;
+
+    tracing::info!(
+        number=%head.number,
+        database=%context.database.display(),
+        "Snapshot imported, the node syncs from the block after it once started."
+    );
+
+    Ok(())
+}
+
+/// Checks that the snapshot's head is the block of a state update on L1, with the same state
+/// commitment, and that the state update is still part of the canonical Ethereum chain.
+async fn verify_head(
+    head: &SnapshotHead,
+    transport: &impl pathfinder_ethereum::provider::EthereumTransport,
+    core_address: ethers::types::H160,
+) -> anyhow::Result<()> {
+    let log = head.l1_state_update.as_ref().with_context(|| {
+        format!(
+            "Snapshot head {} has no recorded state update on L1, snapshots must end at the last \
+            block of a state update",
+            head.number
+        )
+    })?;
+
+    anyhow::ensure!(
+        log.block_number == head.number && log.global_root == head.state_commitment,
+        "Snapshot head {} has state commitment {} but its recorded state update on L1 is of \
+        block {} with {}",
+        head.number,
+        head.state_commitment,
+        log.block_number,
+        log.global_root
+    );
+
+    anyhow::ensure!(
+        audit::is_on_l1(transport, core_address, log).await?,
+        "State update of snapshot head {} is not in L1 transaction {:?}",
+        head.number,
+        log.origin.transaction.hash.0
+    );
+
+    Ok(())
+}
+
+/// Where a snapshot for `database` is downloaded to, next to where it is staged.
+fn download_path(database: &Path) -> PathBuf {
+    let mut path = snapshot::staging_path(database).into_os_string();
+    path.push(".download");
+    PathBuf::from(path)
+}
+
+/// Downloads the snapshot at `url` to `path`, through the gateway's proxy if one is configured.
+async fn download(url: Url, proxy: Option<Url>, path: &Path) -> anyhow::Result<()> {
+    tracing::info!(%url, "Downloading snapshot.");
+
+    let mut client = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        client = client.proxy(reqwest::Proxy::all(proxy).context("Invalid proxy URL")?);
+    }
+    let client = client.build().context("Creating HTTP client")?;
+
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .with_context(|| format!("Requesting snapshot from {url}"))?;
+    let mut file =
+        std::fs::File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+
+    let result = async {
+        let mut bytes = 0u64;
+        while let Some(chunk) = response.chunk().await.context("Downloading snapshot")? {
+            tokio::task::block_in_place(|| file.write_all(&chunk)).context("Writing snapshot")?;
+            bytes += chunk.len() as u64;
+        }
+        tokio::task::block_in_place(|| file.sync_all()).context("Writing snapshot")?;
+        anyhow::Ok(bytes)
+    }
+    .await;
+
+    match result {
+        Ok(bytes) => {
+            tracing::info!(%bytes, "Snapshot downloaded.");
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(path);
+            Err(e)
+        }
+    }
+}
@@ -1,8 +1,12 @@
 #![deny(rust_2018_idioms)]
 
 pub mod monitoring;
+pub mod persisted_metrics;
+pub mod retention;
 pub mod sierra;
 pub mod state;
+pub mod systemd;
+pub mod vacuum;
 
 #[cfg(feature = "p2p")]
 pub mod p2p_network;
@@ -1,15 +1,51 @@
+use std::fmt::Write;
 use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use anyhow::Context;
 use metrics_exporter_prometheus::PrometheusHandle;
+use pathfinder_common::consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT;
+use pathfinder_common::{ClassHash, StarknetBlockHash, StarknetBlockNumber};
+use pathfinder_ethereum::provider::Reachability;
+#[cfg(feature = "rpc")]
+use pathfinder_rpc::tls::TlsConfig;
+use pathfinder_storage::{
+    RefsTable, StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable, Storage,
+};
+use starknet_gateway_client::Availability;
+use starknet_gateway_types::sync_state::syncing::Syncing;
+use starknet_gateway_types::sync_state::SyncState;
 use warp::Filter;
 
+/// Number of the most recent blocks listed on the status page.
+const RECENT_BLOCKS: u64 = 10;
+
 /// Spawns a server which hosts a `/health` endpoint.
+///
+/// The `tls` configuration, which is only available with the `rpc` feature, must have been
+/// validated with `TlsConfig::server_config`, as warp panics if it is invalid.
 pub async fn spawn_server(
     addr: impl Into<std::net::SocketAddr> + 'static,
+    #[cfg(feature = "rpc")] tls: Option<TlsConfig>,
     readiness: std::sync::Arc<AtomicBool>,
     prometheus_handle: PrometheusHandle,
+    node_status: NodeStatus,
 ) -> tokio::task::JoinHandle<()> {
-    let server = warp::serve(routes(readiness, prometheus_handle));
+    let server = warp::serve(routes(readiness, prometheus_handle, node_status));
+
+    #[cfg(feature = "rpc")]
+    if let Some(tls) = tls {
+        let server = server.tls().cert_path(tls.cert).key_path(tls.key);
+        let server = match tls.client_ca {
+            Some(client_ca) => server.client_auth_required_path(client_ca),
+            None => server,
+        };
+        let server = server.bind(addr);
+
+        return tokio::spawn(async move { server.await });
+    }
+
     let server = server.bind(addr);
 
     tokio::spawn(async move { server.await })
@@ -18,10 +54,14 @@ pub async fn spawn_server(
 fn routes(
     readiness: std::sync::Arc<AtomicBool>,
     prometheus_handle: PrometheusHandle,
+    node_status: NodeStatus,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     health_route()
         .or(ready_route(readiness))
         .or(metrics_route(prometheus_handle))
+        .or(status_route(node_status.clone()))
+        .or(backup_route(node_status.clone()))
+        .or(casm_import_route(node_status))
 }
 
 /// Always returns `Ok(200)` at `/health`.
@@ -56,6 +96,433 @@ fn metrics_route(
         })
 }
 
+/// Returns a read-only HTML page with an overview of the node at `/status`.
+fn status_route(
+    node_status: NodeStatus,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("status"))
+        .map(move || -> NodeStatus { node_status.clone() })
+        .and_then(|node_status: NodeStatus| async move {
+            let (status, body) = match node_status.snapshot().await {
+                Ok(Some(snapshot)) => (warp::http::StatusCode::OK, render(&snapshot)),
+                Ok(None) => (
+                    warp::http::StatusCode::SERVICE_UNAVAILABLE,
+                    page("<p>The node is starting.</p>"),
+                ),
+                Err(error) => {
+                    tracing::warn!(error=%format!("{error:#}"), "Failed to read node status");
+                    (
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        page("<p>Failed to read the node status, see the logs.</p>"),
+                    )
+                }
+            };
+            Ok::<_, std::convert::Infallible>(warp::reply::with_status(
+                warp::reply::html(body),
+                status,
+            ))
+        })
+}
+
+/// Writes a consistent copy of the database next to it on `POST /backup`, while the node keeps
+/// syncing, and returns the path of the copy once it is written.
+///
+/// Only one backup is written at a time, further requests fail with `CONFLICT` meanwhile.
+fn backup_route(
+    node_status: NodeStatus,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    let in_progress = Arc::new(AtomicBool::new(false));
+
+    warp::post()
+        .and(warp::path!("backup"))
+        .map(move || (node_status.clone(), in_progress.clone()))
+        .and_then(|(node_status, in_progress)| handle_backup(node_status, in_progress))
+}
+
+async fn handle_backup(
+    node_status: NodeStatus,
+    in_progress: Arc<AtomicBool>,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, std::convert::Infallible> {
+    use std::sync::atomic::Ordering;
+    use warp::http::StatusCode;
+
+    let storage = match node_status.source() {
+        Some(source) => source.storage,
+        None => {
+            return Ok(json_reply(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "error",
+                "The node is starting",
+            ))
+        }
+    };
+    if in_progress
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Ok(json_reply(
+            StatusCode::CONFLICT,
+            "error",
+            "A backup is already in progress",
+        ));
+    }
+
+    // The backup is only done once the blocking task is, even if the request is dropped before.
+    let guard = BackupGuard(in_progress);
+    let result = tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        backup(&storage)
+    })
+    .await;
+
+    let reply = match result.context("Backup panicked").and_then(|result| result) {
+        Ok(path) => json_reply(StatusCode::OK, "path", &path.display().to_string()),
+        Err(error) => {
+            tracing::warn!(error=%format!("{error:#}"), "Failed to back up database");
+            json_reply(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "error",
+                "Failed to back up the database, see the logs",
+            )
+        }
+    };
+
+    Ok(reply)
+}
+
+/// Allows the next backup once dropped.
+struct BackupGuard(Arc<AtomicBool>);
+
+impl Drop for BackupGuard {
+    fn drop(&mut self) {
+        self.0.store(false, std::sync::atomic::Ordering::Release);
+    }
+}
+
+/// A JSON object with the single `field`.
+fn json_reply(
+    status: warp::http::StatusCode,
+    field: &str,
+    value: &str,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let body = serde_json::json!({ field: value });
+    warp::reply::with_status(warp::reply::json(&body), status)
+}
+
+/// Stores the CASM definition in the request body for the Sierra class on
+/// `POST /casm/<class hash>`, if it hashes to the compiled class hash declared for the class.
+///
+/// This lets operators supply definitions compiled elsewhere for classes the node could not
+/// compile itself, see [import_casm](crate::sierra::import_casm).
+fn casm_import_route(
+    node_status: NodeStatus,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    /// Bounds the memory used by a single request.
+    const MAX_CASM_DEFINITION_SIZE: u64 = 64 * 1024 * 1024;
+
+    warp::post()
+        .and(warp::path!("casm" / String))
+        .and(warp::body::content_length_limit(MAX_CASM_DEFINITION_SIZE))
+        .and(warp::body::bytes())
+        .map(move |class_hash, body| (node_status.clone(), class_hash, body))
+        .and_then(|(node_status, class_hash, body)| {
+            handle_casm_import(node_status, class_hash, body)
+        })
+}
+
+async fn handle_casm_import(
+    node_status: NodeStatus,
+    class_hash: String,
+    casm_definition: warp::hyper::body::Bytes,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, std::convert::Infallible> {
+    use crate::sierra::CasmImport;
+    use warp::http::StatusCode;
+
+    let class_hash = match stark_hash::Felt::from_hex_str(&class_hash) {
+        Ok(hash) => ClassHash(hash),
+        Err(_) => {
+            return Ok(json_reply(
+                StatusCode::BAD_REQUEST,
+                "error",
+                "Invalid class hash",
+            ))
+        }
+    };
+    let storage = match node_status.source() {
+        Some(source) if source.read_only => {
+            return Ok(json_reply(
+                StatusCode::FORBIDDEN,
+                "error",
+                "The database is read-only, import the CASM definition into the syncing node",
+            ))
+        }
+        Some(source) => source.storage,
+        None => {
+            return Ok(json_reply(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "error",
+                "The node is starting",
+            ))
+        }
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        storage.write(|tx| crate::sierra::import_casm(tx, class_hash, &casm_definition))
+    })
+    .await
+    .context("CASM import panicked")
+    .and_then(|result| result);
+
+    let reply = match result {
+        Ok(CasmImport::Imported(compiled_class_hash)) => {
+            tracing::info!(%class_hash, %compiled_class_hash, "Imported CASM definition");
+            json_reply(
+                StatusCode::OK,
+                "compiled_class_hash",
+                &compiled_class_hash.to_string(),
+            )
+        }
+        Ok(CasmImport::UnknownClass) => json_reply(
+            StatusCode::NOT_FOUND,
+            "error",
+            "The node has not stored a Sierra class with this hash",
+        ),
+        Ok(CasmImport::Invalid(error)) => json_reply(
+            StatusCode::BAD_REQUEST,
+            "error",
+            &format!("Invalid CASM definition: {error}"),
+        ),
+        Ok(CasmImport::HashMismatch { expected, actual }) => json_reply(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "error",
+            &format!("Compiled class hash {actual} does not match the declared {expected}"),
+        ),
+        Err(error) => {
+            tracing::warn!(%class_hash, error=%format!("{error:#}"), "Failed to import CASM definition");
+            json_reply(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "error",
+                "Failed to import the CASM definition, see the logs",
+            )
+        }
+    };
+
+    Ok(reply)
+}
+
+/// Backs up the database to `<database>.backup-<unix timestamp>`, and returns that path.
+fn backup(storage: &Storage) -> anyhow::Result<std::path::PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut path = storage.path().as_os_str().to_owned();
+    path.push(format!(".backup-{timestamp}"));
+    let path = std::path::PathBuf::from(path);
+
+    tracing::info!(path=%path.display(), "Backing up database");
+    let started = std::time::Instant::now();
+    storage.backup(&path)?;
+    tracing::info!(path=%path.display(), elapsed=?started.elapsed(), "Database backed up");
+
+    Ok(path)
+}
+
+/// The parts of the node which the `/status` page reports on.
+#[derive(Clone)]
+pub struct StatusSource {
+    pub storage: Storage,
+    pub sync_state: Arc<SyncState>,
+    pub gateway_availability: Availability,
+    /// [None] when running without Ethereum.
+    pub ethereum_reachability: Option<Reachability>,
+    /// Set if the database is opened with `--storage.read-only`, so nothing can be imported.
+    pub read_only: bool,
+}
+
+/// Shared with the monitoring server before the node is set up, which [NodeStatus::set] completes
+/// once it is. Until then the `/status` page reports that the node is starting.
+///
+/// Cheap to clone, with all clones sharing the same source.
+#[derive(Clone, Default)]
+pub struct NodeStatus(Arc<RwLock<Option<StatusSource>>>);
+
+impl NodeStatus {
+    pub fn set(&self, source: StatusSource) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(source);
+    }
+
+    /// [None] while the node is still starting.
+    fn source(&self) -> Option<StatusSource> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Reads the current status, or returns [None] if the node is still starting.
+    async fn snapshot(&self) -> anyhow::Result<Option<Snapshot>> {
+        let source = match self.source() {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+
+        let syncing = source.sync_state.status.read().await.clone();
+        let storage = source.storage.clone();
+        let (head, l1_l2_head, recent_blocks) = tokio::task::spawn_blocking(move || {
+            storage.read(|tx| {
+                let head = StarknetBlocksTable::get_latest_hash_and_number(tx)
+                    .context("Reading latest block")?;
+                let l1_l2_head = RefsTable::get_l1_l2_head(tx).context("Reading L1-L2 head")?;
+
+                let mut recent_blocks = Vec::new();
+                if let Some((_, latest)) = head {
+                    let oldest = latest.get().saturating_sub(RECENT_BLOCKS - 1);
+                    for number in (oldest..=latest.get()).rev() {
+                        let id = StarknetBlocksBlockId::Number(StarknetBlockNumber::new_or_panic(
+                            number,
+                        ));
+                        let block =
+                            match StarknetBlocksTable::get(tx, id).context("Reading block")? {
+                                Some(block) => block,
+                                None => break,
+                            };
+                        let transaction_count =
+                            StarknetTransactionsTable::get_transaction_count(tx, id)
+                                .context("Counting transactions")?;
+
+                        recent_blocks.push(RecentBlock {
+                            number: block.number,
+                            hash: block.hash,
+                            timestamp: block.timestamp.get(),
+                            transaction_count,
+                        });
+                    }
+                }
+
+                Ok((head, l1_l2_head, recent_blocks))
+            })
+        })
+        .await
+        .context("Database read panic or shutting down")??;
+
+        Ok(Some(Snapshot {
+            head,
+            l1_l2_head,
+            syncing,
+            degraded: source
+                .sync_state
+                .degraded
+                .load(std::sync::atomic::Ordering::Relaxed),
+            gateway_unavailable: source.gateway_availability.is_unavailable(),
+            ethereum_unreachable: source
+                .ethereum_reachability
+                .as_ref()
+                .map(Reachability::is_unreachable),
+            recent_blocks,
+            now: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }))
+    }
+}
+
+struct Snapshot {
+    head: Option<(StarknetBlockHash, StarknetBlockNumber)>,
+    l1_l2_head: Option<StarknetBlockNumber>,
+    syncing: Syncing,
+    degraded: bool,
+    gateway_unavailable: bool,
+    /// [None] when running without Ethereum.
+    ethereum_unreachable: Option<bool>,
+    /// Newest first.
+    recent_blocks: Vec<RecentBlock>,
+    /// Seconds since the Unix epoch, which block ages are relative to.
+    now: u64,
+}
+
+struct RecentBlock {
+    number: StarknetBlockNumber,
+    hash: StarknetBlockHash,
+    timestamp: u64,
+    transaction_count: usize,
+}
+
+/// Renders the status page. All values are numbers, hashes or fixed strings, so none of them need
+/// to be escaped.
+fn render(snapshot: &Snapshot) -> String {
+    let mut body = String::new();
+
+    let head = match snapshot.head {
+        Some((hash, number)) => format!("{number} <code>{}</code>", hash.0),
+        None => "none".to_owned(),
+    };
+    let l1_l2_head = match snapshot.l1_l2_head {
+        Some(number) => number.to_string(),
+        None => "none".to_owned(),
+    };
+    let syncing = match snapshot.syncing {
+        Syncing::False(_) => "not started".to_owned(),
+        Syncing::Status(status) if status.current.number >= status.highest.number => {
+            "up to date".to_owned()
+        }
+        Syncing::Status(status) => format!(
+            "at block {} of {}",
+            status.current.number, status.highest.number
+        ),
+    };
+    let mode = match snapshot.degraded {
+        true => "degraded, serving stored data only",
+        false => "ok",
+    };
+    let ethereum = match snapshot.ethereum_unreachable {
+        Some(true) => "unreachable, retrying",
+        Some(false) => "ok",
+        None => "disabled",
+    };
+    let gateway = match snapshot.gateway_unavailable {
+        true => "down for maintenance, sync is paused",
+        false => "ok",
+    };
+
+    let _ = write!(
+        body,
+        "<table>\
+        <tr><th>Version</th><td>{VERGEN_GIT_SEMVER_LIGHTWEIGHT}</td></tr>\
+        <tr><th>Latest block</th><td>{head}</td></tr>\
+        <tr><th>Accepted on L1 up to</th><td>{l1_l2_head}</td></tr>\
+        <tr><th>Sync</th><td>{syncing}</td></tr>\
+        <tr><th>Mode</th><td>{mode}</td></tr>\
+        <tr><th>Ethereum</th><td>{ethereum}</td></tr>\
+        <tr><th>Gateway</th><td>{gateway}</td></tr>\
+        </table>"
+    );
+
+    body.push_str(
+        "<h2>Recent blocks</h2><table>\
+        <tr><th>Number</th><th>Hash</th><th>Transactions</th><th>Age</th></tr>",
+    );
+    for block in &snapshot.recent_blocks {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}s</td></tr>",
+            block.number,
+            block.hash.0,
+            block.transaction_count,
+            snapshot.now.saturating_sub(block.timestamp),
+        );
+    }
+    body.push_str("</table>");
+
+    page(&body)
+}
+
+fn page(body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Pathfinder status</title>\
+        </head><body><h1>Pathfinder</h1>{body}</body></html>"
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use metrics_exporter_prometheus::PrometheusBuilder;
@@ -67,7 +534,7 @@ mod tests {
         let recorder = PrometheusBuilder::new().build_recorder();
         let handle = recorder.handle();
         let readiness = Arc::new(AtomicBool::new(false));
-        let filter = super::routes(readiness, handle);
+        let filter = super::routes(readiness, handle, super::NodeStatus::default());
         let response = warp::test::request().path("/health").reply(&filter).await;
 
         assert_eq!(response.status(), http::StatusCode::OK);
@@ -78,7 +545,7 @@ mod tests {
         let recorder = PrometheusBuilder::new().build_recorder();
         let handle = recorder.handle();
         let readiness = Arc::new(AtomicBool::new(false));
-        let filter = super::routes(readiness.clone(), handle);
+        let filter = super::routes(readiness.clone(), handle, super::NodeStatus::default());
         let response = warp::test::request().path("/ready").reply(&filter).await;
         assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
 
@@ -100,7 +567,7 @@ mod tests {
         counter.increment(123);
 
         let readiness = Arc::new(AtomicBool::new(false));
-        let filter = super::routes(readiness.clone(), handle);
+        let filter = super::routes(readiness.clone(), handle, super::NodeStatus::default());
         let response = warp::test::request().path("/metrics").reply(&filter).await;
 
         // Drop to avoid poisoning the internal lock if the following asserts fail
@@ -110,4 +577,195 @@ mod tests {
         assert_eq!(response.status(), http::StatusCode::OK);
         assert_eq!(response.body(), "# TYPE x counter\nx 123\n\n");
     }
+
+    #[tokio::test]
+    async fn status() {
+        use pathfinder_storage::test_utils;
+        use starknet_gateway_client::Availability;
+        use starknet_gateway_types::sync_state::SyncState;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let readiness = Arc::new(AtomicBool::new(false));
+        let node_status = super::NodeStatus::default();
+        let filter = super::routes(readiness, handle, node_status.clone());
+
+        let response = warp::test::request().path("/status").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        let (storage, data) = test_utils::setup_test_storage();
+        node_status.set(super::StatusSource {
+            storage,
+            sync_state: Arc::new(SyncState::default()),
+            gateway_availability: Availability::default(),
+            ethereum_reachability: None,
+            read_only: false,
+        });
+
+        let response = warp::test::request().path("/status").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        let latest = data.blocks.last().unwrap();
+        assert!(body.contains(&format!(
+            "{} <code>{}</code>",
+            latest.block.number, latest.block.hash.0
+        )));
+        // Every test block is recent, and listed with its transactions.
+        let rows = format!("<td>{}</td>", test_utils::TRANSACTIONS_PER_BLOCK);
+        assert_eq!(body.matches(&rows).count(), test_utils::NUM_BLOCKS);
+        assert!(body.contains("<tr><th>Ethereum</th><td>disabled</td></tr>"));
+    }
+
+    #[tokio::test]
+    async fn backup() {
+        use pathfinder_storage::{JournalMode, Storage};
+        use starknet_gateway_client::Availability;
+        use starknet_gateway_types::sync_state::SyncState;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let readiness = Arc::new(AtomicBool::new(false));
+        let node_status = super::NodeStatus::default();
+        let filter = super::routes(readiness, handle, node_status.clone());
+
+        let request = || warp::test::request().method("POST").path("/backup");
+        let response = request().reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL).unwrap();
+        node_status.set(super::StatusSource {
+            storage,
+            sync_state: Arc::new(SyncState::default()),
+            gateway_availability: Availability::default(),
+            ethereum_reachability: None,
+            read_only: false,
+        });
+
+        let response = request().reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let path = std::path::Path::new(body["path"].as_str().unwrap());
+        assert!(path.exists());
+        assert!(path.starts_with(dir.path()));
+    }
+
+    #[tokio::test]
+    async fn backup_dropped_request() {
+        use pathfinder_storage::{JournalMode, Storage};
+        use starknet_gateway_client::Availability;
+        use starknet_gateway_types::sync_state::SyncState;
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+        use warp::Reply;
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL).unwrap();
+        let node_status = super::NodeStatus::default();
+        node_status.set(super::StatusSource {
+            storage,
+            sync_state: Arc::new(SyncState::default()),
+            gateway_availability: Availability::default(),
+            ethereum_reachability: None,
+            read_only: false,
+        });
+        let in_progress = Arc::new(AtomicBool::new(false));
+
+        // The request is dropped while the backup is being written.
+        let request = super::handle_backup(node_status.clone(), in_progress.clone());
+        let _ = tokio::time::timeout(Duration::ZERO, request).await;
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while in_progress.load(Ordering::Acquire) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The backup should finish");
+
+        let reply = super::handle_backup(node_status, in_progress)
+            .await
+            .unwrap();
+        assert_eq!(reply.into_response().status(), http::StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn casm_import() {
+        use pathfinder_common::{felt, ClassHash};
+        use pathfinder_storage::types::CompressedCasmClass;
+        use pathfinder_storage::{CasmClassTable, ContractCodeTable, Storage};
+        use starknet_gateway_client::Availability;
+        use starknet_gateway_test_fixtures::zstd_compressed_contracts::{
+            CAIRO_1_0_0_ALPHA5_CASM, CAIRO_1_0_0_ALPHA5_SIERRA,
+        };
+        use starknet_gateway_types::sync_state::SyncState;
+
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        let readiness = Arc::new(AtomicBool::new(false));
+        let node_status = super::NodeStatus::default();
+        let filter = super::routes(readiness, handle, node_status.clone());
+
+        let casm_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_CASM).unwrap();
+        let compiled_class_hash =
+            starknet_gateway_types::class_hash::compute_casm_class_hash(&casm_definition).unwrap();
+        let request = |path: &str| {
+            warp::test::request()
+                .method("POST")
+                .path(path)
+                .body(casm_definition.clone())
+        };
+
+        let response = request("/casm/0x123").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let class_hash = ClassHash(felt!("0x123"));
+        let sierra_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_SIERRA).unwrap();
+        ContractCodeTable::insert(&tx, class_hash, &sierra_definition).unwrap();
+        let empty = CompressedCasmClass {
+            definition: zstd::bulk::compress(&[], 10).unwrap(),
+            hash: class_hash,
+        };
+        CasmClassTable::upsert_compressed(&tx, &empty, &compiled_class_hash, "v1").unwrap();
+        tx.commit().unwrap();
+
+        node_status.set(super::StatusSource {
+            storage: storage.clone(),
+            sync_state: Arc::new(SyncState::default()),
+            gateway_availability: Availability::default(),
+            ethereum_reachability: None,
+            read_only: true,
+        });
+
+        let response = request("/casm/0x123").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::FORBIDDEN);
+
+        node_status.set(super::StatusSource {
+            storage,
+            sync_state: Arc::new(SyncState::default()),
+            gateway_availability: Availability::default(),
+            ethereum_reachability: None,
+            read_only: false,
+        });
+
+        let response = request("/casm/not-a-hash").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::BAD_REQUEST);
+
+        let response = request("/casm/0x456").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+
+        let response = request("/casm/0x123").reply(&filter).await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(
+            body["compiled_class_hash"],
+            compiled_class_hash.to_string().as_str()
+        );
+    }
 }
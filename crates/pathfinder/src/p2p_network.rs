@@ -4,16 +4,27 @@ use anyhow::Context;
 use p2p::libp2p::{identity::Keypair, multiaddr::Multiaddr, PeerId};
 use p2p::Peers;
 use p2p_proto as proto;
-use pathfinder_common::ChainId;
-use pathfinder_rpc::SyncState;
+use pathfinder_common::{ChainId, StarknetBlockHash, StarknetBlockNumber};
 use pathfinder_storage::Storage;
 use proto::sync::StateDiffs;
 use stark_hash::Felt;
-use tokio::sync::RwLock;
+use starknet_gateway_types::sync_state::SyncState;
+use tokio::sync::{watch, RwLock};
 use tracing::Instrument;
 
+pub mod header_verification;
 mod sync_handlers;
 
+use header_verification::HeaderVerifier;
+
+/// Counts the block headers rejected by the network's [HeaderVerifier], or in sync responses.
+const REJECTED_HEADERS: &str = "p2p_block_headers_rejected_total";
+
+/// The highest block whose propagated header was accepted by the network's [HeaderVerifier].
+///
+/// Only headers which link back to it can be fetched with [fetch_block_headers].
+pub type AcceptedHead = watch::Receiver<Option<(StarknetBlockNumber, StarknetBlockHash)>>;
+
 #[tracing::instrument(name = "p2p", skip_all)]
 pub async fn start(
     chain_id: ChainId,
@@ -21,7 +32,13 @@ pub async fn start(
     sync_state: Arc<SyncState>,
     listen_on: Multiaddr,
     bootstrap_addresses: &[Multiaddr],
-) -> anyhow::Result<(Arc<RwLock<Peers>>, p2p::Client, tokio::task::JoinHandle<()>)> {
+    header_verifier: Box<dyn HeaderVerifier>,
+) -> anyhow::Result<(
+    Arc<RwLock<Peers>>,
+    p2p::Client,
+    AcceptedHead,
+    tokio::task::JoinHandle<()>,
+)> {
     let keypair = Keypair::Ed25519(p2p::libp2p::identity::ed25519::Keypair::generate());
 
     let peer_id = keypair.public().to_peer_id();
@@ -63,6 +80,8 @@ pub async fn start(
     let block_propagation_topic = format!("blocks/{}", chain_id.to_hex_str());
     p2p_client.subscribe_topic(&block_propagation_topic).await?;
 
+    let (accepted_head, accepted_head_rx) = watch::channel(None);
+
     let join_handle = {
         let mut p2p_client = p2p_client.clone();
         tokio::task::spawn(
@@ -74,7 +93,7 @@ pub async fn start(
                             break;
                         }
                         Some(event) = p2p_events.recv() => {
-                            match handle_p2p_event(event, chain_id, &mut storage, &sync_state, &mut p2p_client).await {
+                            match handle_p2p_event(event, chain_id, &mut storage, &sync_state, &mut p2p_client, header_verifier.as_ref(), &accepted_head).await {
                                 Ok(()) => {},
                                 Err(e) => { tracing::error!("Failed to handle P2P event: {}", e) },
                             }
@@ -86,7 +105,7 @@ pub async fn start(
         )
     };
 
-    Ok((peers, p2p_client, join_handle))
+    Ok((peers, p2p_client, accepted_head_rx, join_handle))
 }
 
 async fn handle_p2p_event(
@@ -95,6 +114,8 @@ async fn handle_p2p_event(
     storage: &mut Storage,
     sync_state: &SyncState,
     p2p_client: &mut p2p::Client,
+    header_verifier: &dyn HeaderVerifier,
+    accepted_head: &watch::Sender<Option<(StarknetBlockNumber, StarknetBlockHash)>>,
 ) -> anyhow::Result<()> {
     match event {
         p2p::Event::SyncPeerConnected { peer_id }
@@ -122,6 +143,29 @@ async fn handle_p2p_event(
             };
             p2p_client.send_sync_response(channel, response).await;
         }
+        p2p::Event::BlockPropagation(proto::propagation::Message::NewBlockHeader(header)) => {
+            let block_hash = header.block_hash;
+            let block_number = header.header.block_number;
+            match header_verifier.verify(&header) {
+                Ok(()) => {
+                    tracing::info!(%block_number, %block_hash, "Accepted block header");
+                    let number = StarknetBlockNumber::new(block_number)
+                        .context("Block number out of range")?;
+                    let newer = accepted_head
+                        .borrow()
+                        .map_or(true, |(accepted, _)| accepted < number);
+                    if newer {
+                        accepted_head.send_replace(Some((number, StarknetBlockHash(block_hash))));
+                    }
+                }
+                Err(error) => {
+                    metrics::increment_counter!(REJECTED_HEADERS);
+                    tracing::warn!(
+                        %block_number, %block_hash, reason=%error, "Rejected block header"
+                    );
+                }
+            }
+        }
         p2p::Event::BlockPropagation(block_propagation) => {
             tracing::info!(?block_propagation, "Block Propagation");
         }
@@ -131,9 +175,42 @@ async fn handle_p2p_event(
     Ok(())
 }
 
+/// Fetches up to `count` headers from `peer_id`, starting at the accepted block `start`.
+///
+/// The response is rejected as a whole unless it links back to `start`, see
+/// [header_verification::verify_chain]. Returns the headers with their block hashes.
+pub async fn fetch_block_headers(
+    p2p_client: &p2p::Client,
+    peer_id: PeerId,
+    start: StarknetBlockHash,
+    count: u64,
+) -> anyhow::Result<Vec<(StarknetBlockHash, proto::common::BlockHeader)>> {
+    use p2p_proto::sync::{Direction, GetBlockHeaders, Request, Response};
+
+    let request = Request::GetBlockHeaders(GetBlockHeaders {
+        start_block: start.0,
+        count,
+        size_limit: u64::MAX,
+        direction: Direction::Forward,
+    });
+    let headers = match p2p_client.send_sync_request(peer_id, request).await? {
+        Response::BlockHeaders(response) => response.headers,
+        other => anyhow::bail!("Unexpected response to block headers request: {other:?}"),
+    };
+
+    match header_verification::verify_chain(start, &headers) {
+        Ok(hashes) => Ok(hashes.into_iter().zip(headers).collect()),
+        Err(error) => {
+            metrics::increment_counter!(REJECTED_HEADERS);
+            tracing::warn!(%peer_id, reason=%error, "Rejected block headers");
+            Err(error.context("Verifying block headers"))
+        }
+    }
+}
+
 async fn current_status(chain_id: ChainId, sync_state: &SyncState) -> p2p_proto::sync::Status {
     use p2p_proto::sync::Status;
-    use pathfinder_rpc::v02::types::syncing::Syncing;
+    use starknet_gateway_types::sync_state::syncing::Syncing;
 
     let sync_status = { sync_state.status.read().await.clone() };
     match sync_status {
@@ -0,0 +1,290 @@
+//! Verification of propagated block headers before they are accepted.
+//!
+//! Which headers are acceptable depends on the consensus rules of the network, so the policy is
+//! chosen per network by [for_network]. StarkNet's networks are run by a single sequencer and
+//! carry no consensus signatures, so their headers only have to hash to the announced block hash.
+//! Networks with a set of signers additionally require signatures by enough of them.
+//!
+//! Headers received in sync responses carry neither block hashes nor signatures, so they are only
+//! accepted by [verify_chain] if they link back to a header which was already accepted.
+use std::collections::HashSet;
+
+use anyhow::Context;
+use ethers::types::{Address, Signature, H256};
+use p2p_proto::common::BlockHeader;
+use p2p_proto::propagation::NewBlockHeader;
+use pathfinder_common::{
+    ChainId, SequencerAddress, StarknetBlockHash, StarknetBlockNumber, StarknetBlockTimestamp,
+    StateCommitment,
+};
+
+use crate::state::block_hash::compute_final_hash;
+
+/// A network's rules for accepting a propagated block header.
+pub trait HeaderVerifier: Send + Sync {
+    /// Returns an error describing why `header` must be rejected.
+    fn verify(&self, header: &NewBlockHeader) -> anyhow::Result<()>;
+}
+
+/// Accepts headers which hash to their announced block hash.
+pub struct BlockHashVerifier;
+
+impl HeaderVerifier for BlockHashVerifier {
+    fn verify(&self, header: &NewBlockHeader) -> anyhow::Result<()> {
+        let computed = block_hash(&header.header)?;
+        anyhow::ensure!(
+            computed.0 == header.block_hash,
+            "Block hash mismatch: announced {}, computed {}",
+            header.block_hash,
+            computed.0
+        );
+        Ok(())
+    }
+}
+
+/// Accepts headers with a valid block hash, signed by at least `threshold` of the `signers`.
+///
+/// Signatures are 65 byte secp256k1 signatures over the block hash, from which the signer's
+/// Ethereum address is recovered. Signatures which cannot be recovered, or are by unknown signers,
+/// do not count towards the threshold.
+pub struct SignatureVerifier {
+    signers: HashSet<Address>,
+    threshold: usize,
+}
+
+impl SignatureVerifier {
+    pub fn new(signers: impl IntoIterator<Item = Address>, threshold: usize) -> Self {
+        Self {
+            signers: signers.into_iter().collect(),
+            threshold,
+        }
+    }
+}
+
+impl HeaderVerifier for SignatureVerifier {
+    fn verify(&self, header: &NewBlockHeader) -> anyhow::Result<()> {
+        BlockHashVerifier.verify(header)?;
+
+        let message = H256::from(header.block_hash.to_be_bytes());
+        let signed_by = header
+            .signatures
+            .iter()
+            .filter_map(|signature| Signature::try_from(signature.0.as_slice()).ok())
+            .filter_map(|signature| signature.recover(message).ok())
+            .filter(|signer| self.signers.contains(signer))
+            .collect::<HashSet<_>>();
+
+        anyhow::ensure!(
+            signed_by.len() >= self.threshold,
+            "Signed by {} of the required {} signers",
+            signed_by.len(),
+            self.threshold
+        );
+        Ok(())
+    }
+}
+
+/// The header verification policy of the network with `chain_id`.
+///
+/// StarkNet's networks have no consensus signatures, so `signers` may only be given for other
+/// networks, for which `threshold` of them must sign each header.
+pub fn for_network(
+    chain_id: ChainId,
+    signers: Vec<Address>,
+    threshold: usize,
+) -> anyhow::Result<Box<dyn HeaderVerifier>> {
+    let starknet = [
+        ChainId::MAINNET,
+        ChainId::TESTNET,
+        ChainId::TESTNET2,
+        ChainId::INTEGRATION,
+    ]
+    .contains(&chain_id);
+
+    if signers.is_empty() {
+        return Ok(Box::new(BlockHashVerifier));
+    }
+
+    anyhow::ensure!(
+        !starknet,
+        "Headers of {} are not signed by consensus signers",
+        chain_id.to_hex_str()
+    );
+    anyhow::ensure!(
+        (1..=signers.len()).contains(&threshold),
+        "Signature threshold must be between 1 and the number of signers ({})",
+        signers.len()
+    );
+
+    Ok(Box::new(SignatureVerifier::new(signers, threshold)))
+}
+
+/// Verifies the headers of a forward sync response starting at the accepted block `start`.
+///
+/// The first header must hash to `start`, and every following header must be the child of the one
+/// before it. Returns the block hashes of the headers, which may then be accepted as well.
+pub fn verify_chain(
+    start: StarknetBlockHash,
+    headers: &[BlockHeader],
+) -> anyhow::Result<Vec<StarknetBlockHash>> {
+    let mut hashes: Vec<StarknetBlockHash> = Vec::with_capacity(headers.len());
+
+    for header in headers {
+        let hash = block_hash(header)?;
+        match hashes.last() {
+            None => anyhow::ensure!(
+                hash == start,
+                "Block hash mismatch: requested {}, computed {}",
+                start.0,
+                hash.0
+            ),
+            Some(parent) => {
+                anyhow::ensure!(
+                    header.parent_block_hash == parent.0,
+                    "Block {} is not a child of {}",
+                    header.block_number,
+                    parent.0
+                );
+                anyhow::ensure!(
+                    headers[hashes.len() - 1].block_number + 1 == header.block_number,
+                    "Block {} does not follow block {}",
+                    header.block_number,
+                    headers[hashes.len() - 1].block_number
+                );
+            }
+        }
+        hashes.push(hash);
+    }
+
+    Ok(hashes)
+}
+
+/// Computes the block hash of `header`.
+fn block_hash(header: &BlockHeader) -> anyhow::Result<StarknetBlockHash> {
+    let block_number =
+        StarknetBlockNumber::new(header.block_number).context("Block number out of range")?;
+    let timestamp =
+        StarknetBlockTimestamp::new(header.block_timestamp).context("Timestamp out of range")?;
+
+    Ok(compute_final_hash(
+        block_number,
+        StateCommitment(header.global_state_root),
+        &SequencerAddress(header.sequencer_address),
+        timestamp,
+        header.transaction_count.into(),
+        header.transaction_commitment,
+        header.event_count.into(),
+        header.event_commitment,
+        StarknetBlockHash(header.parent_block_hash),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::{LocalWallet, Signer};
+    use p2p_proto::propagation::ConsensusSignature;
+    use pathfinder_common::felt;
+
+    fn header() -> NewBlockHeader {
+        let mut header = NewBlockHeader {
+            block_hash: felt!("0x0"),
+            header: BlockHeader {
+                parent_block_hash: felt!("0x1"),
+                block_number: 2,
+                global_state_root: felt!("0x3"),
+                sequencer_address: felt!("0x4"),
+                block_timestamp: 5,
+                transaction_count: 6,
+                transaction_commitment: felt!("0x7"),
+                event_count: 8,
+                event_commitment: felt!("0x9"),
+                protocol_version: 0,
+            },
+            signatures: vec![],
+        };
+        header.block_hash = block_hash(&header.header).unwrap().0;
+        header
+    }
+
+    fn wallet(key: &str) -> LocalWallet {
+        key.parse().unwrap()
+    }
+
+    fn sign(header: &mut NewBlockHeader, wallet: &LocalWallet) {
+        let signature = wallet.sign_hash(H256::from(header.block_hash.to_be_bytes()));
+        header
+            .signatures
+            .push(ConsensusSignature(signature.to_vec()));
+    }
+
+    #[test]
+    fn block_hash_mismatch() {
+        let mut header = header();
+        BlockHashVerifier.verify(&header).unwrap();
+
+        header.header.block_timestamp += 1;
+        BlockHashVerifier.verify(&header).unwrap_err();
+    }
+
+    #[test]
+    fn signature_threshold() {
+        let alice = wallet("4c0883a69102937d6231471b5decb0f4d9f6e4da5ac8b8ab8ab3bbff6c1dad4e");
+        let bob = wallet("0123456789012345678901234567890123456789012345678901234567890123");
+        let mallory = wallet("1111111111111111111111111111111111111111111111111111111111111111");
+        let verifier = SignatureVerifier::new([alice.address(), bob.address()], 2);
+
+        let mut header = header();
+        sign(&mut header, &alice);
+        sign(&mut header, &alice);
+        sign(&mut header, &mallory);
+        header.signatures.push(ConsensusSignature(vec![0; 3]));
+        verifier.verify(&header).unwrap_err();
+
+        sign(&mut header, &bob);
+        verifier.verify(&header).unwrap();
+
+        // Signatures don't vouch for a header which doesn't match its hash.
+        header.header.block_number += 1;
+        verifier.verify(&header).unwrap_err();
+    }
+
+    #[test]
+    fn sync_response_chain() {
+        let first = header();
+        let mut second = first.header.clone();
+        second.block_number += 1;
+        second.parent_block_hash = first.block_hash;
+        let start = StarknetBlockHash(first.block_hash);
+
+        let headers = vec![first.header.clone(), second.clone()];
+        let hashes = verify_chain(start, &headers).unwrap();
+        assert_eq!(hashes[0], start);
+        assert_eq!(hashes[1], block_hash(&second).unwrap());
+
+        // The response must start at the requested block.
+        verify_chain(StarknetBlockHash(felt!("0x1")), &headers).unwrap_err();
+
+        // Every header must link to the one before it.
+        let mut orphan = second.clone();
+        orphan.parent_block_hash = felt!("0x1");
+        verify_chain(start, &[first.header.clone(), orphan]).unwrap_err();
+
+        let mut skipped = second;
+        skipped.block_number += 1;
+        verify_chain(start, &[first.header, skipped]).unwrap_err();
+    }
+
+    #[test]
+    fn policy_per_network() {
+        let signer = Address::repeat_byte(1);
+
+        for_network(ChainId::MAINNET, vec![], 0).unwrap();
+        for_network(ChainId::MAINNET, vec![signer], 1).unwrap_err();
+
+        let appchain = ChainId(felt!("0x1234"));
+        for_network(appchain, vec![signer], 1).unwrap();
+        for_network(appchain, vec![signer], 0).unwrap_err();
+        for_network(appchain, vec![signer], 2).unwrap_err();
+    }
+}
@@ -0,0 +1,131 @@
+//! Cumulative counters which are persisted in the database, so that their metrics continue from
+//! their previous values after a restart instead of resetting dashboards to zero.
+//!
+//! Sync persists its counters in the same database transaction as the change they count. The
+//! bytes received from the gateway and the Ethereum endpoint are persisted periodically instead,
+//! so that up to one [FLUSH_INTERVAL] of them is lost when the node stops. Their monthly tallies
+//! are persisted as well, so that the monthly bandwidth quota is not reset by a restart.
+use std::time::Duration;
+
+use pathfinder_common::bandwidth::{self, Bandwidth, Month, MonthlyBytes, Source};
+use pathfinder_storage::{MetricCountersTable, Storage};
+
+/// Blocks stored by sync, including blocks which were later removed by a reorg.
+pub const BLOCKS: &str = "sync_blocks_total";
+/// L2 reorgs processed by sync.
+pub const REORGS: &str = "sync_reorgs_total";
+/// Bytes received from the gateway, which [Bandwidth] counts in the
+/// `bandwidth_received_bytes_total` metric with the `gateway` source label.
+const GATEWAY_BYTES: &str = "gateway_received_bytes_total";
+/// Prefix of the bytes received from a source during a month, such as
+/// `bandwidth_received_bytes_gateway_2023-04`.
+const MONTHLY_BYTES: &str = "bandwidth_received_bytes_";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sets the counter metrics to their persisted values, and restores the monthly tally of
+/// `bandwidth`. Must be called at startup, before any of the counters are incremented.
+pub fn restore(storage: &Storage, bandwidth: &Bandwidth) -> anyhow::Result<()> {
+    let counters = storage.read(|tx| MetricCountersTable::get_all(tx))?;
+
+    for (name, value) in counters {
+        match name.as_str() {
+            BLOCKS => metrics::absolute_counter!(BLOCKS, value),
+            REORGS => metrics::absolute_counter!(REORGS, value),
+            GATEWAY_BYTES => metrics::absolute_counter!(
+                bandwidth::METRIC_TOTAL,
+                value,
+                "source" => Source::Gateway.label()
+            ),
+            other => match parse_monthly_bytes(other) {
+                Some((source, month)) => bandwidth.restore(source, month, value),
+                None => tracing::debug!(counter=%other, "Ignoring unknown persisted counter"),
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Persists the bytes received from the gateway and the monthly tallies of `bandwidth` every
+/// [FLUSH_INTERVAL], until the task is aborted.
+pub async fn persist_bandwidth(storage: Storage, bandwidth: Bandwidth) {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    let mut persisted = 0;
+
+    loop {
+        interval.tick().await;
+
+        let received = bandwidth.received_since_startup(Source::Gateway);
+        let delta = received - persisted;
+        let monthly = bandwidth.take_unpersisted();
+        if delta == 0 && monthly.is_empty() {
+            continue;
+        }
+
+        let storage = storage.clone();
+        let result = tokio::task::spawn_blocking({
+            let monthly = monthly.clone();
+            move || {
+                storage.write(|tx| {
+                    MetricCountersTable::add(tx, GATEWAY_BYTES, delta)?;
+                    for bytes in &monthly {
+                        MetricCountersTable::add(tx, &monthly_bytes_name(bytes), bytes.bytes)?;
+                    }
+                    Ok(())
+                })
+            }
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => persisted = received,
+            Ok(Err(error)) => {
+                tracing::warn!(%error, "Failed to persist bandwidth counters");
+                bandwidth.return_unpersisted(monthly);
+            }
+            Err(error) => {
+                tracing::warn!(%error, "Persisting bandwidth counters panicked");
+                bandwidth.return_unpersisted(monthly);
+            }
+        }
+    }
+}
+
+fn monthly_bytes_name(bytes: &MonthlyBytes) -> String {
+    format!("{MONTHLY_BYTES}{}_{}", bytes.source.label(), bytes.month)
+}
+
+/// Parses the source and month of a counter named by [monthly_bytes_name].
+fn parse_monthly_bytes(name: &str) -> Option<(Source, Month)> {
+    let (source, month) = name.strip_prefix(MONTHLY_BYTES)?.split_once('_')?;
+    let source = match source {
+        "gateway" => Source::Gateway,
+        "ethereum" => Source::Ethereum,
+        _ => return None,
+    };
+
+    Some((source, month.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monthly_bytes_name() {
+        let bytes = MonthlyBytes {
+            source: Source::Ethereum,
+            month: "2023-04".parse().unwrap(),
+            bytes: 10,
+        };
+        let name = super::monthly_bytes_name(&bytes);
+
+        assert_eq!(name, "bandwidth_received_bytes_ethereum_2023-04");
+        assert_eq!(
+            parse_monthly_bytes(&name),
+            Some((bytes.source, bytes.month))
+        );
+        assert_eq!(parse_monthly_bytes(GATEWAY_BYTES), None);
+    }
+}
@@ -0,0 +1,171 @@
+//! Retention of the auxiliary files which pathfinder writes next to its database, such as the
+//! backups made before destructive migrations.
+//!
+//! Each kind of file has its own [RetentionPolicy], and all of them are configured together in a
+//! [RetentionConfig]. Files are only ever removed, never truncated, and the newest files are kept.
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+
+/// How many files of one kind are kept, and for how long.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Files beyond the newest `max_count` are removed, [None] if unlimited.
+    pub max_count: Option<NonZeroUsize>,
+    /// Files last modified longer ago than this are removed, [None] if unlimited.
+    pub max_age: Option<Duration>,
+}
+
+/// The [RetentionPolicy] of each kind of file written by pathfinder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionConfig {
+    /// Database backups made before destructive migrations.
+    pub database_backups: RetentionPolicy,
+}
+
+impl RetentionConfig {
+    /// Removes the files of the database at `database_path` which its policies no longer retain.
+    pub fn apply(&self, database_path: &Path) -> anyhow::Result<()> {
+        let directory = match database_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let file_name = database_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .context("Database file name is not valid UTF-8")?;
+
+        let removed = prune(
+            directory,
+            &format!("{file_name}.backup-v"),
+            &self.database_backups,
+        )
+        .context("Pruning database backups")?;
+        for path in removed {
+            tracing::info!(path=%path.display(), "Removed database backup past its retention");
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the files in `directory` whose names start with `prefix` and which `policy` does not
+/// retain, and returns their paths.
+///
+/// Files are ordered by their modification time, and by name if these are equal.
+pub fn prune(
+    directory: &Path,
+    prefix: &str,
+    policy: &RetentionPolicy,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory).context("Listing directory")? {
+        let entry = entry.context("Reading directory entry")?;
+        let matches = entry
+            .file_name()
+            .to_str()
+            .map(|name| name.starts_with(prefix))
+            .unwrap_or(false);
+        if !matches || !entry.file_type().context("Reading file type")?.is_file() {
+            continue;
+        }
+
+        let modified = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .context("Reading modification time")?;
+        files.push((modified, entry.path()));
+    }
+    // Newest first.
+    files.sort_unstable_by(|a, b| b.cmp(a));
+
+    let now = SystemTime::now();
+    let max_count = policy
+        .max_count
+        .map(NonZeroUsize::get)
+        .unwrap_or(usize::MAX);
+
+    let mut removed = Vec::new();
+    for (index, (modified, path)) in files.into_iter().enumerate() {
+        let expired = policy
+            .max_age
+            .map(|max_age| now.duration_since(modified).unwrap_or_default() > max_age)
+            .unwrap_or(false);
+        if index < max_count && !expired {
+            continue;
+        }
+
+        std::fs::remove_file(&path).with_context(|| format!("Removing {}", path.display()))?;
+        removed.push(path);
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_by_count() {
+        let dir = tempfile::tempdir().unwrap();
+        for version in 1..=4 {
+            std::fs::write(dir.path().join(format!("db.backup-v{version}")), []).unwrap();
+        }
+        std::fs::write(dir.path().join("db"), []).unwrap();
+
+        let policy = RetentionPolicy {
+            max_count: NonZeroUsize::new(2),
+            max_age: None,
+        };
+        let mut removed = prune(dir.path(), "db.backup-v", &policy).unwrap();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![
+                dir.path().join("db.backup-v1"),
+                dir.path().join("db.backup-v2")
+            ]
+        );
+
+        let mut left = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(left, vec!["db", "db.backup-v3", "db.backup-v4"]);
+    }
+
+    #[test]
+    fn prune_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("db.backup-v1"), []).unwrap();
+
+        let keep = RetentionPolicy {
+            max_count: None,
+            max_age: Some(Duration::from_secs(3600)),
+        };
+        assert!(prune(dir.path(), "db.backup-v", &keep).unwrap().is_empty());
+
+        std::thread::sleep(Duration::from_millis(10));
+        let expire = RetentionPolicy {
+            max_count: None,
+            max_age: Some(Duration::ZERO),
+        };
+        assert_eq!(
+            prune(dir.path(), "db.backup-v", &expire).unwrap(),
+            vec![dir.path().join("db.backup-v1")]
+        );
+    }
+
+    #[test]
+    fn unlimited_keeps_everything() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("db.backup-v1"), []).unwrap();
+
+        let removed = prune(dir.path(), "db.backup-v", &RetentionPolicy::default()).unwrap();
+        assert!(removed.is_empty());
+    }
+}
@@ -1,11 +1,135 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
 use anyhow::Context;
 use cairo_lang_starknet::allowed_libfuncs::{validate_compatible_sierra_version, ListSelector};
 use cairo_lang_starknet::{casm_contract_class::CasmContractClass, contract_class::ContractClass};
+use pathfinder_common::{CasmHash, ClassHash};
+use pathfinder_storage::types::CompressedCasmClass;
+use pathfinder_storage::{CasmClassTable, CasmCompilationFailuresTable};
+use rusqlite::Connection;
+use tokio::sync::Semaphore;
 
 pub const COMPILER_VERSION: &str = env!("SIERRA_CASM_COMPILER_VERSION");
 
+/// Recorded as the compiler version of CASM definitions which were imported with [import_casm]
+/// instead of being compiled by the node.
+pub const IMPORTED_COMPILER_VERSION: &str = "imported";
+
+/// Resource limits of [compile_to_casm_isolated].
+#[derive(Debug, Clone, Copy)]
+pub struct CompileLimits {
+    /// Larger Sierra class definitions are not compiled. The compiler's memory use grows with
+    /// the size of the program, so this bounds it.
+    pub max_definition_size: usize,
+    /// Compilation is abandoned if it takes longer than this.
+    pub timeout: std::time::Duration,
+    /// Stack size of the compiler thread. Overflowing the stack would abort the node, so this is
+    /// generous.
+    pub stack_size: usize,
+}
+
+/// How many compiler threads may run at once, including the threads of compilations which timed
+/// out but are still running.
+const MAX_COMPILER_THREADS: usize = 4;
+
+lazy_static::lazy_static!(
+    /// Held by each compiler thread until it exits, see [MAX_COMPILER_THREADS].
+    static ref COMPILER_THREADS: Arc<Semaphore> = Arc::new(Semaphore::new(MAX_COMPILER_THREADS));
+);
+
+/// Error of [compile_to_casm_isolated].
+#[derive(Debug)]
+pub enum CompileError {
+    /// The class cannot be compiled, and compiling it again would fail the same way.
+    Failed(anyhow::Error),
+    /// The compilation did not complete, but may complete on another attempt.
+    Interrupted(anyhow::Error),
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompileError::Failed(error) | CompileError::Interrupted(error) => {
+                write!(f, "{error:#}")
+            }
+        }
+    }
+}
+
+impl Default for CompileLimits {
+    fn default() -> Self {
+        Self {
+            max_definition_size: 16 * 1024 * 1024,
+            timeout: std::time::Duration::from_secs(120),
+            stack_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Compiles a Sierra class definition into CASM in a dedicated thread, within the given limits.
+///
+/// Panics of the compiler are returned as errors. A compilation which times out cannot be
+/// cancelled, so it runs to completion in its thread, but no longer blocks the caller. At most
+/// [MAX_COMPILER_THREADS] compiler threads run at once, so that such compilations cannot pile up.
+/// Waiting for a thread counts towards the timeout.
+pub async fn compile_to_casm_isolated(
+    sierra_definition: &[u8],
+    limits: CompileLimits,
+) -> Result<Vec<u8>, CompileError> {
+    if sierra_definition.len() > limits.max_definition_size {
+        return Err(CompileError::Failed(anyhow::anyhow!(
+            "Sierra class definition is {} bytes, exceeding the limit of {} bytes",
+            sierra_definition.len(),
+            limits.max_definition_size
+        )));
+    }
+
+    let sierra_definition = sierra_definition.to_vec();
+    let compile = async move {
+        let permit = COMPILER_THREADS
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore is never closed");
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        std::thread::Builder::new()
+            .name("sierra-compiler".to_owned())
+            .stack_size(limits.stack_size)
+            .spawn(move || {
+                let _permit = permit;
+                let result = std::panic::catch_unwind(|| compile_to_casm(&sierra_definition));
+                // The receiver is gone if the compilation timed out.
+                let _ = tx.send(result);
+            })
+            .context("Spawning compiler thread")
+            .map_err(CompileError::Interrupted)?;
+
+        rx.await
+            .map_err(|_| CompileError::Interrupted(anyhow::anyhow!("Compiler thread terminated")))
+    };
+
+    match tokio::time::timeout(limits.timeout, compile).await {
+        Ok(Ok(Ok(result))) => result.map_err(CompileError::Failed),
+        Ok(Ok(Err(panic))) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            Err(CompileError::Failed(anyhow::anyhow!(
+                "Compiler panicked: {message}"
+            )))
+        }
+        Ok(Err(error)) => Err(error),
+        Err(_) => Err(CompileError::Interrupted(anyhow::anyhow!(
+            "Compilation timed out after {:?}",
+            limits.timeout
+        ))),
+    }
+}
+
 /// Compile a Sierra class definition into CASM.
 ///
 /// The class representation expected by the compiler doesn't match the representation used
@@ -34,6 +158,82 @@ pub fn compile_to_casm(sierra_definition: &[u8]) -> anyhow::Result<Vec<u8>> {
     Ok(casm_definition)
 }
 
+/// Compiles a Sierra class definition into CASM within the default [CompileLimits].
+///
+/// If the class [cannot be compiled](CompileError::Failed) it is given an empty CASM definition,
+/// so that a single pathological class cannot stop sync. The error is returned alongside, to be
+/// recorded. [Interrupted](CompileError::Interrupted) compilations are returned as errors
+/// instead, so that the class is compiled again when sync retries it.
+pub async fn compile_to_casm_or_empty(
+    sierra_definition: &[u8],
+    class_hash: ClassHash,
+) -> anyhow::Result<(Vec<u8>, Option<String>)> {
+    match compile_to_casm_isolated(sierra_definition, CompileLimits::default()).await {
+        Ok(casm_definition) => Ok((casm_definition, None)),
+        Err(CompileError::Failed(error)) => {
+            tracing::warn!(%class_hash, error=%format!("{error:#}"), "Compiling Sierra class failed, storing it without CASM");
+            Ok((Vec::new(), Some(format!("{error:#}"))))
+        }
+        Err(CompileError::Interrupted(error)) => {
+            Err(error.context(format!("Compiling Sierra class {}", class_hash.0)))
+        }
+    }
+}
+
+/// Outcome of [import_casm].
+#[derive(Debug, PartialEq, Eq)]
+pub enum CasmImport {
+    /// The CASM definition was stored, replacing the one the node had.
+    Imported(CasmHash),
+    /// The node has not stored a Sierra class with this hash (yet).
+    UnknownClass,
+    /// The CASM definition could not be parsed.
+    Invalid(String),
+    /// The CASM definition does not hash to the compiled class hash declared for the class.
+    HashMismatch {
+        expected: CasmHash,
+        actual: CasmHash,
+    },
+}
+
+/// Stores a CASM definition of a Sierra class which was compiled elsewhere, for example on a
+/// machine which can compile classes the node could not.
+///
+/// The definition is only accepted if it hashes to the compiled class hash declared for the
+/// class, in which case any recorded compilation failure of the class is cleared.
+pub fn import_casm(
+    connection: &Connection,
+    class_hash: ClassHash,
+    casm_definition: &[u8],
+) -> anyhow::Result<CasmImport> {
+    let expected = match CasmClassTable::get_compiled_class_hash(connection, class_hash)? {
+        Some(expected) => expected,
+        None => return Ok(CasmImport::UnknownClass),
+    };
+
+    let actual = match starknet_gateway_types::class_hash::compute_casm_class_hash(casm_definition)
+    {
+        Ok(actual) => actual,
+        Err(error) => return Ok(CasmImport::Invalid(format!("{error:#}"))),
+    };
+    if actual != expected {
+        return Ok(CasmImport::HashMismatch { expected, actual });
+    }
+
+    let definition =
+        zstd::bulk::compress(casm_definition, 10).context("Compress CASM definition")?;
+    let class = CompressedCasmClass {
+        definition,
+        hash: class_hash,
+    };
+    CasmClassTable::upsert_compressed(connection, &class, &expected, IMPORTED_COMPILER_VERSION)
+        .context("Storing CASM definition")?;
+    CasmCompilationFailuresTable::remove(connection, class_hash)
+        .context("Clearing compilation failure")?;
+
+    Ok(CasmImport::Imported(expected))
+}
+
 impl<'a> TryFrom<FeederGatewayContractClass<'a>> for ContractClass {
     type Error = serde_json::Error;
 
@@ -66,9 +266,14 @@ struct FeederGatewayContractClass<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{compile_to_casm, FeederGatewayContractClass};
+    use super::{
+        compile_to_casm, compile_to_casm_isolated, import_casm, CasmImport, CompileError,
+        CompileLimits, FeederGatewayContractClass,
+    };
 
-    use starknet_gateway_test_fixtures::zstd_compressed_contracts::CAIRO_1_0_0_ALPHA5_SIERRA;
+    use starknet_gateway_test_fixtures::zstd_compressed_contracts::{
+        CAIRO_1_0_0_ALPHA5_CASM, CAIRO_1_0_0_ALPHA5_SIERRA,
+    };
 
     #[test]
     fn test_feeder_gateway_contract_conversion() {
@@ -85,4 +290,123 @@ mod tests {
         let contract_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_SIERRA).unwrap();
         compile_to_casm(&contract_definition).unwrap();
     }
+
+    #[tokio::test]
+    async fn test_compile_isolated() {
+        let contract_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_SIERRA).unwrap();
+        compile_to_casm_isolated(&contract_definition, CompileLimits::default())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compile_isolated_limits() {
+        let contract_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_SIERRA).unwrap();
+
+        let too_large = CompileLimits {
+            max_definition_size: contract_definition.len() - 1,
+            ..Default::default()
+        };
+        let error = compile_to_casm_isolated(&contract_definition, too_large)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompileError::Failed(_)));
+        assert!(error.to_string().contains("exceeding the limit"), "{error}");
+
+        let too_slow = CompileLimits {
+            timeout: std::time::Duration::ZERO,
+            ..Default::default()
+        };
+        let error = compile_to_casm_isolated(&contract_definition, too_slow)
+            .await
+            .unwrap_err();
+        // Timed out compilations are retried instead of being recorded as failures.
+        assert!(matches!(error, CompileError::Interrupted(_)));
+        assert!(error.to_string().contains("timed out"), "{error}");
+
+        let error = compile_to_casm_isolated(b"not json", CompileLimits::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, CompileError::Failed(_)));
+    }
+
+    #[test]
+    fn test_import_casm() {
+        use pathfinder_common::{felt, ClassHash};
+        use pathfinder_storage::types::CompressedCasmClass;
+        use pathfinder_storage::{
+            CasmClassTable, CasmCompilationFailuresTable, ContractCodeTable, Storage,
+        };
+        use starknet_gateway_types::class_hash::compute_casm_class_hash;
+
+        let sierra_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_SIERRA).unwrap();
+        let casm_definition = zstd::decode_all(CAIRO_1_0_0_ALPHA5_CASM).unwrap();
+        let compiled_class_hash = compute_casm_class_hash(&casm_definition).unwrap();
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        // A class which failed to compile locally.
+        let class_hash = ClassHash(felt!("0x123"));
+        ContractCodeTable::insert(&tx, class_hash, &sierra_definition).unwrap();
+        let empty = CompressedCasmClass {
+            definition: zstd::bulk::compress(&[], 10).unwrap(),
+            hash: class_hash,
+        };
+        CasmClassTable::upsert_compressed(&tx, &empty, &compiled_class_hash, "v1").unwrap();
+        CasmCompilationFailuresTable::upsert(&tx, class_hash, "v1", "timed out").unwrap();
+
+        assert_eq!(
+            import_casm(&tx, ClassHash(felt!("0x456")), &casm_definition).unwrap(),
+            CasmImport::UnknownClass
+        );
+        assert!(matches!(
+            import_casm(&tx, class_hash, b"not json").unwrap(),
+            CasmImport::Invalid(_)
+        ));
+
+        let mut tampered: serde_json::Value = serde_json::from_slice(&casm_definition).unwrap();
+        tampered["bytecode"][0] = serde_json::json!("0x1");
+        let tampered = serde_json::to_vec(&tampered).unwrap();
+        assert!(matches!(
+            import_casm(&tx, class_hash, &tampered).unwrap(),
+            CasmImport::HashMismatch { expected, .. } if expected == compiled_class_hash
+        ));
+
+        assert_eq!(
+            import_casm(&tx, class_hash, &casm_definition).unwrap(),
+            CasmImport::Imported(compiled_class_hash)
+        );
+        assert_eq!(
+            CasmCompilationFailuresTable::get(&tx, class_hash).unwrap(),
+            None
+        );
+        let stored: Vec<u8> = tx
+            .query_row(
+                "SELECT definition FROM casm_definitions WHERE hash = ?",
+                [class_hash],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(zstd::decode_all(&stored[..]).unwrap(), casm_definition);
+    }
+
+    #[test]
+    fn test_compiled_class_hash_matches_declaration() {
+        use pathfinder_common::{felt, CasmHash};
+        use starknet_gateway_test_fixtures::zstd_compressed_contracts::CAIRO_0_11_SIERRA;
+        use starknet_gateway_types::class_hash::compute_casm_class_hash;
+
+        // The compiled class hash declared for this class on integration, see
+        // https://external.integration.starknet.io/feeder_gateway/get_state_update?blockNumber=283364
+        let expected = CasmHash(felt!(
+            "0x711c0c3e56863e29d3158804aac47f424241eda64db33e2cc2999d60ee5105"
+        ));
+
+        let sierra_definition = zstd::decode_all(CAIRO_0_11_SIERRA).unwrap();
+        let casm_definition = compile_to_casm(&sierra_definition).unwrap();
+
+        assert_eq!(compute_casm_class_hash(&casm_definition).unwrap(), expected);
+    }
 }
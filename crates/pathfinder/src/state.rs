@@ -1,7 +1,14 @@
+pub mod archive;
+pub mod audit;
 pub mod block_hash;
+pub mod dump;
+pub mod replica;
+pub mod snapshot;
 mod sync;
 
-pub use sync::{l1, l2, sync};
+#[cfg(feature = "execution")]
+pub use sync::fork;
+pub use sync::{checkpoint, deferred, hooks, l1, l2, sync, update_starknet_state};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,968 @@
+//! Export and import of the canonical chain in an archive format which is independent of
+//! pathfinder's database, for long-term archiving and for exchanging chain data with other
+//! implementations.
+//!
+//! # Format
+//!
+//! An archive starts with the [MAGIC] bytes and the [VERSION] of the format as a big endian
+//! `u16`, followed by a stream of records. Each record consists of
+//!
+//! | size     | content                                                  |
+//! |----------|----------------------------------------------------------|
+//! | 1        | kind of the record                                       |
+//! | 4        | length of the payload, big endian                        |
+//! | length   | payload                                                  |
+//! | 32       | keccak256 checksum of the kind and payload               |
+//!
+//! The first record is a [Header] and the last an empty end record, so that a truncated archive
+//! is detected. In between, each block is written as the definitions of the classes it
+//! introduces, followed by the block and its state update. The payloads are
+//!
+//! - header: the [Header] as JSON,
+//! - class: the class definition as served by the feeder gateway,
+//! - block: the block, including its transactions and receipts, as served by the feeder gateway,
+//! - state update: the state update in the format of the JSON-RPC API v0.1.
+//!
+//! The feeder gateway formats are those which the StarkNet hashes are defined over, so that an
+//! importer can verify the block hash, class hashes and state commitment of every block.
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::ops::RangeInclusive;
+
+use anyhow::Context;
+use pathfinder_common::{
+    CasmHash, Chain, ChainId, ClassHash, EventCommitment, StarknetBlockHash, StarknetBlockNumber,
+    TransactionCommitment,
+};
+use pathfinder_storage::types::StateUpdate;
+use pathfinder_storage::{
+    CasmClassTable, CasmCompilationFailuresTable, ContractCodeTable, RefsTable,
+    StarknetBlocksTable, StarknetStateUpdatesTable, StarknetTransactionsTable, Storage,
+};
+use rusqlite::{Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::{self, Block, Status};
+
+use super::block_hash::{verify_block_hash, IrregularBlocks, VerifyResult};
+use super::hooks::ChainHooks;
+use super::sync::{compute_class_hash, insert_block, prepare_class, DownloadedClass};
+
+pub const MAGIC: [u8; 8] = *b"SNARCHIV";
+pub const VERSION: u16 = 1;
+
+/// Guards against allocating absurd amounts of memory for a corrupted record length.
+const MAX_PAYLOAD_LENGTH: u32 = 512 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Header {
+    pub chain_id: ChainId,
+    pub first_block: StarknetBlockNumber,
+    pub last_block: StarknetBlockNumber,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Record {
+    Header(Header),
+    /// A class definition as served by the feeder gateway.
+    Class(Vec<u8>),
+    Block(Box<Block>),
+    /// The state update of the preceding block.
+    StateUpdate(Box<StateUpdate>),
+    End,
+}
+
+impl Record {
+    const HEADER: u8 = 0;
+    const CLASS: u8 = 1;
+    const BLOCK: u8 = 2;
+    const STATE_UPDATE: u8 = 3;
+    const END: u8 = 0xff;
+
+    fn kind(&self) -> u8 {
+        match self {
+            Record::Header(_) => Self::HEADER,
+            Record::Class(_) => Self::CLASS,
+            Record::Block(_) => Self::BLOCK,
+            Record::StateUpdate(_) => Self::STATE_UPDATE,
+            Record::End => Self::END,
+        }
+    }
+}
+
+/// Writes the records of an archive.
+pub struct Writer<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(mut inner: W) -> anyhow::Result<Self> {
+        inner.write_all(&MAGIC)?;
+        inner.write_all(&VERSION.to_be_bytes())?;
+
+        Ok(Self { inner })
+    }
+
+    pub fn write(&mut self, record: &Record) -> anyhow::Result<()> {
+        // The checksum covers the kind as well as the payload.
+        let mut frame = vec![record.kind()];
+        match record {
+            Record::Header(header) => serde_json::to_writer(&mut frame, header)?,
+            Record::Class(definition) => frame.extend_from_slice(definition),
+            Record::Block(block) => serde_json::to_writer(&mut frame, block)?,
+            Record::StateUpdate(state_update) => serde_json::to_writer(&mut frame, state_update)?,
+            Record::End => {}
+        }
+
+        let length = u32::try_from(frame.len() - 1)
+            .ok()
+            .filter(|length| *length <= MAX_PAYLOAD_LENGTH)
+            .context("Record is too large")?;
+        let checksum = ethers::utils::keccak256(&frame);
+
+        self.inner.write_all(&frame[..1])?;
+        self.inner.write_all(&length.to_be_bytes())?;
+        self.inner.write_all(&frame[1..])?;
+        self.inner.write_all(&checksum)?;
+
+        Ok(())
+    }
+
+    /// Writes the end record and returns the underlying writer.
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.write(&Record::End)?;
+        self.inner.flush()?;
+
+        Ok(self.inner)
+    }
+}
+
+/// Reads the records of an archive, verifying their checksums.
+pub struct Reader<R: Read> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(mut inner: R) -> anyhow::Result<Self> {
+        let mut magic = [0u8; 8];
+        inner.read_exact(&mut magic).context("Reading magic")?;
+        anyhow::ensure!(magic == MAGIC, "Not a chain archive");
+
+        let mut version = [0u8; 2];
+        inner.read_exact(&mut version).context("Reading version")?;
+        let version = u16::from_be_bytes(version);
+        anyhow::ensure!(
+            version == VERSION,
+            "Unsupported archive version {version}, expected {VERSION}"
+        );
+
+        Ok(Self { inner })
+    }
+
+    pub fn read(&mut self) -> anyhow::Result<Record> {
+        let mut prefix = [0u8; 5];
+        self.inner
+            .read_exact(&mut prefix)
+            .context("Reading record, the archive may be truncated")?;
+        let length = u32::from_be_bytes(prefix[1..].try_into().expect("Prefix has 4 length bytes"));
+        anyhow::ensure!(
+            length <= MAX_PAYLOAD_LENGTH,
+            "Record length {length} exceeds the maximum of {MAX_PAYLOAD_LENGTH}"
+        );
+
+        let mut frame = vec![0u8; 1 + length as usize];
+        frame[0] = prefix[0];
+        self.inner
+            .read_exact(&mut frame[1..])
+            .context("Reading record payload, the archive may be truncated")?;
+
+        let mut checksum = [0u8; 32];
+        self.inner
+            .read_exact(&mut checksum)
+            .context("Reading record checksum, the archive may be truncated")?;
+        anyhow::ensure!(
+            ethers::utils::keccak256(&frame) == checksum,
+            "Record checksum mismatch, the archive is corrupted"
+        );
+
+        let payload = &frame[1..];
+        let record = match frame[0] {
+            Record::HEADER => {
+                Record::Header(serde_json::from_slice(payload).context("Parsing header")?)
+            }
+            Record::CLASS => Record::Class(payload.to_vec()),
+            Record::BLOCK => {
+                Record::Block(serde_json::from_slice(payload).context("Parsing block")?)
+            }
+            Record::STATE_UPDATE => Record::StateUpdate(
+                serde_json::from_slice(payload).context("Parsing state update")?,
+            ),
+            Record::END => {
+                anyhow::ensure!(payload.is_empty(), "End record has a payload");
+                Record::End
+            }
+            kind => anyhow::bail!("Unknown record kind {kind}"),
+        };
+
+        Ok(record)
+    }
+}
+
+/// Writes `blocks` of the chain stored in `tx` as an archive to `output`.
+///
+/// Fails if a block of the range is missing, or the definition of a class introduced by it has
+/// not been downloaded yet. Blocks are exported as read by [stored_block].
+pub fn export<W: Write>(
+    tx: &Transaction<'_>,
+    chain_id: ChainId,
+    blocks: RangeInclusive<StarknetBlockNumber>,
+    output: W,
+) -> anyhow::Result<W> {
+    let mut writer = Writer::new(output).context("Writing archive header")?;
+    writer.write(&Record::Header(Header {
+        chain_id,
+        first_block: *blocks.start(),
+        last_block: *blocks.end(),
+    }))?;
+
+    let l1_l2_head = RefsTable::get_l1_l2_head(tx).context("Reading L1-L2 head")?;
+    let mut parent_hash = match blocks.start().get().checked_sub(1) {
+        Some(parent) => {
+            StarknetBlocksTable::get_hash(tx, StarknetBlockNumber::new_or_panic(parent).into())
+                .context("Reading parent block hash")?
+                .context("Parent of the first block is missing")?
+        }
+        None => StarknetBlockHash(Felt::ZERO),
+    };
+    let mut exported_classes = HashSet::new();
+
+    for number in blocks.start().get()..=blocks.end().get() {
+        let number = StarknetBlockNumber::new_or_panic(number);
+
+        let (block, state_update) = stored_block(tx, number, parent_hash, l1_l2_head)?;
+
+        for class_hash in introduced_classes(&state_update) {
+            if !exported_classes.insert(class_hash) {
+                continue;
+            }
+
+            let definition = ContractCodeTable::get_definition(tx, class_hash)
+                .context("Reading class definition")?
+                .with_context(|| {
+                    format!(
+                        "Definition of class {} is missing, it may not have been downloaded yet",
+                        class_hash.0
+                    )
+                })?;
+            writer.write(&Record::Class(definition))?;
+        }
+
+        let hash = block.block_hash;
+        writer.write(&Record::Block(Box::new(block)))?;
+        writer.write(&Record::StateUpdate(Box::new(StateUpdate {
+            block_hash: Some(hash),
+            ..state_update
+        })))?;
+
+        parent_hash = hash;
+    }
+
+    writer.finish()
+}
+
+/// Reads block `number` of the chain stored in `tx` in the format of the gateway, along with its
+/// state update.
+///
+/// Pathfinder does not store the parent hashes and status of blocks, so that the block is given
+/// `parent_hash`, and is accepted on L1 up to the `l1_l2_head` and accepted on L2 after it. Gas
+/// prices and sequencer addresses which the gateway omitted for old blocks are read as zero.
+pub(super) fn stored_block(
+    tx: &Transaction<'_>,
+    number: StarknetBlockNumber,
+    parent_hash: StarknetBlockHash,
+    l1_l2_head: Option<StarknetBlockNumber>,
+) -> anyhow::Result<(Block, StateUpdate)> {
+    let header = StarknetBlocksTable::get(tx, number.into())
+        .context("Reading block")?
+        .with_context(|| format!("Block {number} is missing"))?;
+    let starknet_version = StarknetBlocksTable::get_starknet_version(tx, number)
+        .context("Reading StarkNet version")?;
+    let (transactions, transaction_receipts) =
+        StarknetTransactionsTable::get_transaction_data_for_block(tx, number.into())
+            .context("Reading transactions")?
+            .into_iter()
+            .unzip();
+    let state_update = StarknetStateUpdatesTable::get(tx, header.hash)
+        .context("Reading state update")?
+        .with_context(|| format!("State update of block {number} is missing"))?;
+
+    let status = match l1_l2_head {
+        Some(head) if head >= number => Status::AcceptedOnL1,
+        _ => Status::AcceptedOnL2,
+    };
+    let block = Block {
+        block_hash: header.hash,
+        block_number: number,
+        gas_price: Some(header.gas_price),
+        parent_block_hash: parent_hash,
+        sequencer_address: Some(header.sequencer_address),
+        state_commitment: header.root,
+        status,
+        timestamp: header.timestamp,
+        transaction_receipts,
+        transactions,
+        starknet_version,
+    };
+
+    Ok((block, state_update))
+}
+
+/// Imports the blocks of an archive read from `input`, which must continue the chain stored in
+/// `storage`.
+///
+/// Every block is verified like sync verifies downloaded blocks: its parent hash must match the
+/// current head, its block hash and the hashes of its classes are recomputed, and applying its
+/// state update must result in its state commitment. Blocks are committed one by one, so that a
+/// failed import keeps the blocks preceding the failure.
+///
+/// The `hooks` are called for every imported block, within the transaction which stores it, as
+/// they are by sync.
+///
+/// Returns the range of imported blocks.
+pub async fn import<R: Read>(
+    storage: Storage,
+    chain: Chain,
+    chain_id: ChainId,
+    hooks: &ChainHooks,
+    input: R,
+) -> anyhow::Result<RangeInclusive<StarknetBlockNumber>> {
+    let mut connection = storage
+        .connection()
+        .context("Creating database connection")?;
+    let mut reader = tokio::task::block_in_place(|| Reader::new(input))?;
+
+    let header = match tokio::task::block_in_place(|| reader.read())? {
+        Record::Header(header) => header,
+        _ => anyhow::bail!("Archive does not start with a header"),
+    };
+    anyhow::ensure!(
+        header.chain_id == chain_id,
+        "Archive is of chain {}, but the node is of chain {}",
+        header.chain_id.0,
+        chain_id.0
+    );
+
+    let head = tokio::task::block_in_place(|| {
+        let tx = connection.transaction()?;
+        StarknetBlocksTable::get_latest_hash_and_number(&tx)
+    })
+    .context("Reading latest block")?;
+    let (mut parent_hash, mut next) = match head {
+        Some((hash, number)) => (hash, number + 1),
+        None => (StarknetBlockHash(Felt::ZERO), StarknetBlockNumber::GENESIS),
+    };
+    anyhow::ensure!(
+        header.first_block == next,
+        "Archive starts at block {}, but the database continues at block {next}",
+        header.first_block
+    );
+
+    let mut classes = Vec::new();
+    let mut block = None;
+    loop {
+        match tokio::task::block_in_place(|| reader.read())? {
+            Record::Header(_) => anyhow::bail!("Archive contains a second header"),
+            Record::Class(definition) => {
+                anyhow::ensure!(block.is_none(), "Block {next} has no state update");
+                classes.push(definition);
+            }
+            Record::Block(new_block) => {
+                anyhow::ensure!(block.is_none(), "Block {next} has no state update");
+                anyhow::ensure!(
+                    new_block.block_number == next,
+                    "Expected block {next}, but got block {}",
+                    new_block.block_number
+                );
+                anyhow::ensure!(
+                    next <= header.last_block,
+                    "Archive continues after its last block {}",
+                    header.last_block
+                );
+                anyhow::ensure!(
+                    new_block.parent_block_hash == parent_hash,
+                    "Parent hash of block {next} does not match the hash of its parent"
+                );
+
+                block = Some(verify_block(*new_block, chain).await?);
+            }
+            Record::StateUpdate(state_update) => {
+                let (block, tx_commitment, ev_commitment) = block
+                    .take()
+                    .with_context(|| format!("State update without block {next}"))?;
+                anyhow::ensure!(
+                    state_update.block_hash == Some(block.block_hash),
+                    "State update does not belong to block {next}"
+                );
+
+                let classes = prepare_classes(std::mem::take(&mut classes), &state_update)
+                    .await
+                    .with_context(|| format!("Preparing classes of block {next}"))?;
+                let referenced_classes = introduced_classes(&state_update);
+                let state_update = gateway_state_update(block.block_hash, *state_update);
+                let hash = block.block_hash;
+
+                tokio::task::block_in_place(|| {
+                    let tx = connection
+                        .transaction_with_behavior(TransactionBehavior::Immediate)
+                        .context("Creating database transaction")?;
+
+                    for (class_hash, class, compiled_class_hash) in &classes {
+                        insert_class(&tx, *class_hash, class, *compiled_class_hash)
+                            .with_context(|| format!("Inserting class {}", class_hash.0))?;
+                    }
+
+                    let exists = ContractCodeTable::exists(&tx, &referenced_classes)
+                        .context("Querying class existence")?;
+                    for (class_hash, exists) in referenced_classes.iter().zip(exists) {
+                        anyhow::ensure!(
+                            exists,
+                            "Class {} is neither in the archive nor in the database",
+                            class_hash.0
+                        );
+                    }
+
+                    // Inserting consumes the block, so keep a copy for the hooks if there are any.
+                    let for_hooks =
+                        (!hooks.is_empty()).then(|| (block.clone(), state_update.clone()));
+                    insert_block(&tx, block, tx_commitment, ev_commitment, state_update)
+                        .with_context(|| format!("Inserting block {next}"))?;
+                    if let Some((block, state_update)) = for_hooks {
+                        hooks.block_applied(&tx, &block, &state_update)?;
+                    }
+
+                    tx.commit().context("Committing database transaction")
+                })?;
+
+                tracing::debug!(block=%next, "Block imported");
+                parent_hash = hash;
+                next += 1;
+            }
+            Record::End => {
+                anyhow::ensure!(
+                    block.is_none() && classes.is_empty(),
+                    "Archive ends within block {next}"
+                );
+                anyhow::ensure!(
+                    next == header.last_block + 1,
+                    "Archive ends before its last block {}",
+                    header.last_block
+                );
+                break;
+            }
+        }
+    }
+
+    Ok(header.first_block..=header.last_block)
+}
+
+/// Verifies the hash of a block, returning it along with its transaction and event commitments.
+async fn verify_block(
+    block: Block,
+    chain: Chain,
+) -> anyhow::Result<(Block, TransactionCommitment, EventCommitment)> {
+    let number = block.block_number;
+    anyhow::ensure!(
+        matches!(block.status, Status::AcceptedOnL1 | Status::AcceptedOnL2),
+        "Block {number} has status {}, but only accepted blocks can be imported",
+        block.status
+    );
+
+    let verify = tokio::task::spawn_blocking(move || {
+        let irregular_blocks = IrregularBlocks::for_chain(chain);
+        let result = verify_block_hash(&block, chain, block.block_hash, &irregular_blocks);
+        (block, result)
+    });
+    let (block, result) = verify.await.context("Verifying block hash")?;
+
+    match result.with_context(|| format!("Verifying hash of block {number}"))? {
+        VerifyResult::Match((tx_commitment, ev_commitment)) => {
+            Ok((block, tx_commitment, ev_commitment))
+        }
+        // Sync accepts these blocks as well, without commitments.
+        VerifyResult::NotVerifiable => Ok((block, Default::default(), Default::default())),
+        VerifyResult::Mismatch => anyhow::bail!("Block hash mismatch for block {number}"),
+    }
+}
+
+/// Computes the hashes of the class definitions of a block and prepares them for storage,
+/// along with the compiled class hashes of Sierra classes.
+///
+/// Fails for classes which are not introduced by the block's state update.
+async fn prepare_classes(
+    definitions: Vec<Vec<u8>>,
+    state_update: &StateUpdate,
+) -> anyhow::Result<Vec<(ClassHash, DownloadedClass, Option<CasmHash>)>> {
+    let introduced = introduced_classes(state_update);
+    let mut classes = Vec::with_capacity(definitions.len());
+
+    for definition in definitions {
+        let (definition, hash) = compute_class_hash(definition).await?;
+        let class_hash = hash.hash();
+        anyhow::ensure!(
+            introduced.contains(&class_hash),
+            "Class {} is not introduced by its block",
+            class_hash.0
+        );
+
+        let compiled_class_hash = state_update
+            .state_diff
+            .declared_sierra_classes
+            .iter()
+            .find(|class| class.class_hash.0 == class_hash.0)
+            .map(|class| class.compiled_class_hash);
+
+        let class = prepare_class(definition, hash).await?;
+        classes.push((class_hash, class, compiled_class_hash));
+    }
+
+    Ok(classes)
+}
+
+/// Stores a class unless it is stored already.
+fn insert_class(
+    tx: &Transaction<'_>,
+    class_hash: ClassHash,
+    class: &DownloadedClass,
+    compiled_class_hash: Option<CasmHash>,
+) -> anyhow::Result<()> {
+    if ContractCodeTable::exists(tx, &[class_hash])?[0] {
+        return Ok(());
+    }
+
+    match class {
+        DownloadedClass::Cairo(class) => ContractCodeTable::insert_compressed(tx, class),
+        DownloadedClass::Sierra(sierra, casm, compilation_error) => {
+            // The compiled class hash committed to is the one of the state update.
+            let compiled_class_hash =
+                compiled_class_hash.context("Sierra class is not declared by its block")?;
+
+            ContractCodeTable::insert_compressed(tx, sierra)?;
+            CasmClassTable::upsert_compressed(
+                tx,
+                casm,
+                &compiled_class_hash,
+                crate::sierra::COMPILER_VERSION,
+            )?;
+            if let Some(error) = compilation_error {
+                CasmCompilationFailuresTable::upsert(
+                    tx,
+                    class_hash,
+                    crate::sierra::COMPILER_VERSION,
+                    error,
+                )?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// The classes which are declared or deployed by a state update.
+pub(super) fn introduced_classes(state_update: &StateUpdate) -> Vec<ClassHash> {
+    let diff = &state_update.state_diff;
+    let classes = diff
+        .declared_contracts
+        .iter()
+        .map(|class| class.class_hash)
+        .chain(
+            diff.declared_sierra_classes
+                .iter()
+                .map(|class| ClassHash(class.class_hash.0)),
+        )
+        .chain(
+            diff.deployed_contracts
+                .iter()
+                .map(|contract| contract.class_hash),
+        );
+
+    let mut unique = HashSet::new();
+    classes.filter(|class| unique.insert(*class)).collect()
+}
+
+/// Converts a stored state update back into the format of the gateway, which sync applies.
+pub(super) fn gateway_state_update(
+    block_hash: StarknetBlockHash,
+    state_update: StateUpdate,
+) -> reply::StateUpdate {
+    use reply::state_update::{
+        DeclaredSierraClass, DeployedContract, ReplacedClass, StateDiff, StorageDiff,
+    };
+    use std::collections::HashMap;
+
+    let diff = state_update.state_diff;
+    let mut storage_diffs = HashMap::<_, Vec<_>>::new();
+    for diff in diff.storage_diffs {
+        storage_diffs
+            .entry(diff.address)
+            .or_default()
+            .push(StorageDiff {
+                key: diff.key,
+                value: diff.value,
+            });
+    }
+
+    reply::StateUpdate {
+        block_hash,
+        new_root: state_update.new_root,
+        old_root: state_update.old_root,
+        state_diff: StateDiff {
+            storage_diffs,
+            deployed_contracts: diff
+                .deployed_contracts
+                .into_iter()
+                .map(|contract| DeployedContract {
+                    address: contract.address,
+                    class_hash: contract.class_hash,
+                })
+                .collect(),
+            old_declared_contracts: diff
+                .declared_contracts
+                .into_iter()
+                .map(|class| class.class_hash)
+                .collect(),
+            declared_classes: diff
+                .declared_sierra_classes
+                .into_iter()
+                .map(|class| DeclaredSierraClass {
+                    class_hash: class.class_hash,
+                    compiled_class_hash: class.compiled_class_hash,
+                })
+                .collect(),
+            nonces: diff
+                .nonces
+                .into_iter()
+                .map(|nonce| (nonce.contract_address, nonce.nonce))
+                .collect(),
+            replaced_classes: diff
+                .replaced_classes
+                .into_iter()
+                .map(|class| ReplacedClass {
+                    address: class.address,
+                    class_hash: class.class_hash,
+                })
+                .collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::state::update_starknet_state;
+    use pathfinder_common::{
+        felt, ContractAddress, ContractNonce, GasPrice, SequencerAddress, StarknetBlockTimestamp,
+        StateCommitment, StorageAddress, StorageValue,
+    };
+    use pathfinder_storage::types::state_update::{
+        DeclaredCairoClass, DeployedContract, Nonce, StateDiff, StorageDiff,
+    };
+    use starknet_gateway_test_fixtures::zstd_compressed_contracts::{
+        DUMMY_ACCOUNT, DUMMY_ACCOUNT_CLASS_HASH,
+    };
+
+    /// The block hashes of the first integration blocks cannot be verified, which lets the tests
+    /// use made up hashes.
+    const CHAIN: Chain = Chain::Integration;
+    const CHAIN_ID: ChainId = ChainId::INTEGRATION;
+
+    fn number(number: u64) -> StarknetBlockNumber {
+        StarknetBlockNumber::new_or_panic(number)
+    }
+
+    /// Two blocks, of which the first declares a class and deploys a contract of it, and the
+    /// second updates the contract's storage and nonce.
+    pub(in crate::state) fn setup() -> Storage {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let contract = ContractAddress::new_or_panic(felt!("0x123"));
+        let storage_diff = |value| StorageDiff {
+            address: contract,
+            key: StorageAddress::new_or_panic(felt!("0x1")),
+            value: StorageValue(value),
+        };
+        let diffs = [
+            StateDiff {
+                storage_diffs: vec![storage_diff(felt!("0x10"))],
+                declared_contracts: vec![DeclaredCairoClass {
+                    class_hash: DUMMY_ACCOUNT_CLASS_HASH,
+                }],
+                deployed_contracts: vec![DeployedContract {
+                    address: contract,
+                    class_hash: DUMMY_ACCOUNT_CLASS_HASH,
+                }],
+                nonces: vec![],
+                declared_sierra_classes: vec![],
+                replaced_classes: vec![],
+            },
+            StateDiff {
+                storage_diffs: vec![storage_diff(felt!("0x20"))],
+                declared_contracts: vec![],
+                deployed_contracts: vec![],
+                nonces: vec![Nonce {
+                    contract_address: contract,
+                    nonce: ContractNonce(felt!("0x1")),
+                }],
+                declared_sierra_classes: vec![],
+                replaced_classes: vec![],
+            },
+        ];
+
+        let tx = connection.transaction().unwrap();
+        let definition = zstd::decode_all(DUMMY_ACCOUNT).unwrap();
+        ContractCodeTable::insert(&tx, DUMMY_ACCOUNT_CLASS_HASH, &definition).unwrap();
+        tx.commit().unwrap();
+
+        let mut parent_hash = StarknetBlockHash(Felt::ZERO);
+        for (i, state_diff) in diffs.into_iter().enumerate() {
+            let hash = StarknetBlockHash(Felt::from(i as u64 + 0xabc));
+            let state_update = gateway_state_update(
+                hash,
+                StateUpdate {
+                    block_hash: Some(hash),
+                    new_root: StateCommitment::ZERO,
+                    old_root: StateCommitment::ZERO,
+                    state_diff,
+                },
+            );
+
+            // Computed in a transaction which is rolled back, as the block must carry it.
+            let (storage_commitment, class_commitment) =
+                update_starknet_state(&connection.transaction().unwrap(), &state_update).unwrap();
+
+            let block = Block {
+                block_hash: hash,
+                block_number: number(i as u64),
+                gas_price: Some(GasPrice::ZERO),
+                parent_block_hash: parent_hash,
+                sequencer_address: Some(SequencerAddress(Felt::ZERO)),
+                state_commitment: StateCommitment::calculate(storage_commitment, class_commitment),
+                status: Status::AcceptedOnL2,
+                timestamp: StarknetBlockTimestamp::new_or_panic(i as u64),
+                transaction_receipts: vec![],
+                transactions: vec![],
+                starknet_version: Some("0.11.0".to_owned()),
+            };
+
+            let tx = connection.transaction().unwrap();
+            insert_block(
+                &tx,
+                block,
+                Default::default(),
+                Default::default(),
+                state_update,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+
+            parent_hash = hash;
+        }
+
+        storage
+    }
+
+    fn export(storage: &Storage, blocks: RangeInclusive<u64>) -> Vec<u8> {
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let blocks = number(*blocks.start())..=number(*blocks.end());
+
+        super::export(&tx, CHAIN_ID, blocks, Vec::new()).unwrap()
+    }
+
+    async fn import(storage: &Storage, archive: &[u8]) -> anyhow::Result<()> {
+        import_hooked(storage, archive, &ChainHooks::default()).await
+    }
+
+    async fn import_hooked(
+        storage: &Storage,
+        archive: &[u8],
+        hooks: &ChainHooks,
+    ) -> anyhow::Result<()> {
+        super::import(storage.clone(), CHAIN, CHAIN_ID, hooks, archive)
+            .await
+            .map(|_| ())
+    }
+
+    fn latest(storage: &Storage) -> Option<StarknetBlockNumber> {
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        StarknetBlocksTable::get_latest_number(&tx).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn round_trip() {
+        let source = setup();
+        let archive = export(&source, 0..=1);
+
+        let destination = Storage::in_memory().unwrap();
+        import(&destination, &archive).await.unwrap();
+
+        let mut source_connection = source.connection().unwrap();
+        let source_tx = source_connection.transaction().unwrap();
+        let mut destination_connection = destination.connection().unwrap();
+        let destination_tx = destination_connection.transaction().unwrap();
+        for block in [number(0), number(1)] {
+            assert_eq!(
+                StarknetBlocksTable::get(&destination_tx, block.into()).unwrap(),
+                StarknetBlocksTable::get(&source_tx, block.into()).unwrap()
+            );
+            assert_eq!(
+                StarknetBlocksTable::get_storage_commitment(&destination_tx, block.into()).unwrap(),
+                StarknetBlocksTable::get_storage_commitment(&source_tx, block.into()).unwrap()
+            );
+        }
+        assert_eq!(
+            ContractCodeTable::get_definition(&destination_tx, DUMMY_ACCOUNT_CLASS_HASH).unwrap(),
+            ContractCodeTable::get_definition(&source_tx, DUMMY_ACCOUNT_CLASS_HASH).unwrap()
+        );
+
+        // The format is canonical, so that the imported chain exports to the same archive.
+        assert_eq!(export(&destination, 0..=1), archive);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn continues_stored_chain() {
+        let source = setup();
+        let destination = Storage::in_memory().unwrap();
+
+        let error = import(&destination, &export(&source, 1..=1))
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("continues at block 0"));
+
+        import(&destination, &export(&source, 0..=0)).await.unwrap();
+        assert_eq!(latest(&destination), Some(number(0)));
+
+        // The class of block 0 is not exported again, as block 1 does not introduce it.
+        import(&destination, &export(&source, 1..=1)).await.unwrap();
+        assert_eq!(latest(&destination), Some(number(1)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn state_commitment_mismatch() {
+        let source = setup();
+        let archive = export(&source, 0..=1);
+
+        let mut reader = Reader::new(archive.as_slice()).unwrap();
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        loop {
+            let record = match reader.read().unwrap() {
+                Record::End => break,
+                Record::Block(mut block) if block.block_number == number(1) => {
+                    block.state_commitment = StateCommitment(felt!("0x1234"));
+                    Record::Block(block)
+                }
+                record => record,
+            };
+            writer.write(&record).unwrap();
+        }
+        let tampered = writer.finish().unwrap();
+
+        let destination = Storage::in_memory().unwrap();
+        let error = import(&destination, &tampered).await.unwrap_err();
+        assert!(format!("{error:#}").contains("State root mismatch"));
+        // Blocks preceding the failure are kept.
+        assert_eq!(latest(&destination), Some(number(0)));
+    }
+
+    /// Records the blocks it is called for, and fails for block `fail_at`.
+    struct RecordingHook {
+        blocks: std::sync::Mutex<Vec<StarknetBlockNumber>>,
+        fail_at: Option<StarknetBlockNumber>,
+    }
+
+    impl crate::state::hooks::ChainHook for RecordingHook {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn block_applied(
+            &self,
+            _: &Transaction<'_>,
+            block: &Block,
+            _: &reply::StateUpdate,
+        ) -> anyhow::Result<()> {
+            anyhow::ensure!(
+                Some(block.block_number) != self.fail_at,
+                "Failing on purpose"
+            );
+            self.blocks.lock().unwrap().push(block.block_number);
+            Ok(())
+        }
+
+        fn reorg(&self, _: &Transaction<'_>, _: StarknetBlockNumber) -> anyhow::Result<()> {
+            unreachable!("Imports are not reorged")
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn hooks() {
+        let source = setup();
+        let archive = export(&source, 0..=1);
+
+        let hook = std::sync::Arc::new(RecordingHook {
+            blocks: Default::default(),
+            fail_at: None,
+        });
+        let mut hooks = ChainHooks::default();
+        hooks.register(hook.clone());
+        let destination = Storage::in_memory().unwrap();
+        import_hooked(&destination, &archive, &hooks).await.unwrap();
+        assert_eq!(*hook.blocks.lock().unwrap(), vec![number(0), number(1)]);
+
+        // A failing hook aborts the import of the block, like a failure to store it.
+        let hook = std::sync::Arc::new(RecordingHook {
+            blocks: Default::default(),
+            fail_at: Some(number(1)),
+        });
+        let mut hooks = ChainHooks::default();
+        hooks.register(hook.clone());
+        let destination = Storage::in_memory().unwrap();
+        let error = import_hooked(&destination, &archive, &hooks)
+            .await
+            .unwrap_err();
+        assert!(format!("{error:#}").contains("Failing on purpose"));
+        assert_eq!(latest(&destination), Some(number(0)));
+    }
+
+    #[test]
+    fn corruption() {
+        let mut writer = Writer::new(Vec::new()).unwrap();
+        writer.write(&Record::Class(b"{}".to_vec())).unwrap();
+        let archive = writer.finish().unwrap();
+
+        let mut reader = Reader::new(archive.as_slice()).unwrap();
+        assert_eq!(reader.read().unwrap(), Record::Class(b"{}".to_vec()));
+        assert_eq!(reader.read().unwrap(), Record::End);
+
+        // Flips a bit of the class definition.
+        let mut corrupted = archive.clone();
+        corrupted[MAGIC.len() + 2 + 5] ^= 1;
+        let error = Reader::new(corrupted.as_slice())
+            .unwrap()
+            .read()
+            .unwrap_err();
+        assert!(error.to_string().contains("checksum mismatch"));
+
+        // Drops the end record.
+        let truncated = &archive[..archive.len() - 5 - 32];
+        let mut reader = Reader::new(truncated).unwrap();
+        reader.read().unwrap();
+        let error = reader.read().unwrap_err();
+        assert!(error.to_string().contains("truncated"));
+
+        let error = Reader::new(&b"SQLite format 3\0"[..]).err().unwrap();
+        assert!(error.to_string().contains("Not a chain archive"));
+    }
+}
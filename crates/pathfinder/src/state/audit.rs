@@ -0,0 +1,555 @@
+//! Audit of the stored chain against the data StarkNet posted to L1, independent of the gateway.
+//!
+//! Each state update on L1 covers the L2 blocks since the previous one, and commits to the state
+//! after the last of them. The stored state commitment of that block must match it. L1 does not
+//! commit to the state after the blocks before it, so those are only checked if the state update
+//! posted its state diff as blobs: the stored state diffs of the covered blocks must then add up
+//! to the posted one. Blocks which are not yet covered by a state update on L1 cannot be
+//! reproduced from L1.
+//!
+//! The state updates are the ones recorded by L1 sync. If Ethereum is available, each of them is
+//! also looked up on L1 again. The blocks covered by a state update are only known if L1 sync has
+//! also recorded the state update before it, so the state diff of the first state update of an
+//! audit is skipped if that one is missing.
+use std::ops::RangeInclusive;
+
+use anyhow::Context;
+use ethers::types::{Filter, H160};
+use pathfinder_common::{EthereumTransactionHash, StarknetBlockNumber, StateCommitment};
+use pathfinder_ethereum::blob::{self, BlobClient, BlobTransaction, StateDiffMismatch};
+use pathfinder_ethereum::log::StateUpdateLog;
+use pathfinder_ethereum::provider::EthereumTransport;
+use pathfinder_storage::{
+    L1StateTable, L1TableBlockId, StarknetBlocksBlockId, StarknetBlocksTable,
+    StarknetStateUpdatesTable, Storage,
+};
+use rusqlite::Transaction;
+
+/// L1 data used in addition to the recorded state updates.
+pub struct Ethereum<T> {
+    pub transport: T,
+    /// Address of the StarkNet core contract.
+    pub core_address: H160,
+    /// State diffs are only checked if blobs are available.
+    pub blobs: Option<BlobClient>,
+}
+
+/// A block, or range of blocks, whose contents cannot be reproduced from L1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// The blocks are not covered by a state update on L1 yet.
+    NotOnL1(RangeInclusive<StarknetBlockNumber>),
+    MissingBlock(StarknetBlockNumber),
+    StateCommitmentMismatch {
+        block: StarknetBlockNumber,
+        stored: StateCommitment,
+        l1: StateCommitment,
+    },
+    /// The recorded state update ending at the block is no longer on L1.
+    StateUpdateNotOnL1 {
+        block: StarknetBlockNumber,
+        transaction: EthereumTransactionHash,
+    },
+    StateDiffMismatch {
+        blocks: RangeInclusive<StarknetBlockNumber>,
+        mismatches: Vec<StateDiffMismatch>,
+    },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finding::NotOnL1(blocks) => write!(
+                f,
+                "Blocks {} to {} are not covered by a state update on L1",
+                blocks.start(),
+                blocks.end()
+            ),
+            Finding::MissingBlock(block) => write!(f, "Block {block} is missing"),
+            Finding::StateCommitmentMismatch { block, stored, l1 } => write!(
+                f,
+                "State commitment of block {block} is {stored} instead of {l1} on L1"
+            ),
+            Finding::StateUpdateNotOnL1 { block, transaction } => write!(
+                f,
+                "State update ending at block {block} is not in L1 transaction {:?}",
+                transaction.0
+            ),
+            Finding::StateDiffMismatch { blocks, mismatches } => {
+                write!(
+                    f,
+                    "State diff of blocks {} to {} differs from L1:",
+                    blocks.start(),
+                    blocks.end()
+                )?;
+                for mismatch in mismatches {
+                    write!(f, "\n  {mismatch}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+    /// Number of state updates on L1 whose state commitment matched that of the last block they
+    /// cover.
+    pub verified_state_commitments: u64,
+    /// Number of state updates on L1 whose state diff matched the stored state diffs.
+    pub verified_state_diffs: u64,
+    /// Number of state updates on L1 whose state diff was not checked, because the blocks they
+    /// cover are not known.
+    pub skipped_state_diffs: u64,
+}
+
+/// The blocks covered by one state update on L1, or by none yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Span {
+    blocks: RangeInclusive<StarknetBlockNumber>,
+    log: Option<StateUpdateLog>,
+    /// Whether `blocks` starts at the first block covered by the state update. Otherwise, the
+    /// state update before it is not recorded, and `blocks` starts at genesis instead.
+    complete: bool,
+}
+
+/// Audits blocks `from` to `to` of `storage` against L1.
+pub async fn audit<T: EthereumTransport>(
+    storage: Storage,
+    ethereum: Option<Ethereum<T>>,
+    from: StarknetBlockNumber,
+    to: StarknetBlockNumber,
+) -> anyhow::Result<Report> {
+    anyhow::ensure!(from <= to, "Block range is empty");
+
+    let (spans, mut report) = {
+        let storage = storage.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+            let mut connection = storage
+                .connection()
+                .context("Opening database connection")?;
+            let tx = connection
+                .transaction()
+                .context("Creating database transaction")?;
+
+            let spans = spans(&tx, from, to)?;
+            let report = check_state_commitments(&tx, &spans, from, to)?;
+            Ok((spans, report))
+        })
+        .await
+        .context("Joining blocking task")??
+    };
+
+    let ethereum = match ethereum {
+        Some(ethereum) => ethereum,
+        None => return Ok(report),
+    };
+
+    for span in spans {
+        let log = match &span.log {
+            Some(log) => log,
+            None => continue,
+        };
+
+        if !is_on_l1(&ethereum.transport, ethereum.core_address, log).await? {
+            report.findings.push(Finding::StateUpdateNotOnL1 {
+                block: log.block_number,
+                transaction: log.origin.transaction.hash,
+            });
+            continue;
+        }
+
+        let blob_client = match &ethereum.blobs {
+            Some(blob_client) => blob_client,
+            None => continue,
+        };
+        if !span.complete {
+            tracing::warn!(
+                block=%log.block_number,
+                "Skipping state diff of the state update on L1, as the state update before it is \
+                not recorded"
+            );
+            report.skipped_state_diffs += 1;
+            continue;
+        }
+        let transaction = match BlobTransaction::fetch(&ethereum.transport, log).await? {
+            Some(transaction) => transaction,
+            // Posted as calldata, which is not supported.
+            None => continue,
+        };
+        let blobs = blob_client
+            .blobs(&transaction)
+            .await
+            .with_context(|| format!("Fetching blobs of block {}", log.block_number))?;
+        let l1 = blob::StateDiff::from_blobs(&blobs)
+            .with_context(|| format!("Decoding blobs of block {}", log.block_number))?;
+
+        let expected = {
+            let storage = storage.clone();
+            let blocks = span.blocks.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut connection = storage
+                    .connection()
+                    .context("Opening database connection")?;
+                let tx = connection
+                    .transaction()
+                    .context("Creating database transaction")?;
+                stored_state_diff(&tx, blocks)
+            })
+            .await
+            .context("Joining blocking task")??
+        };
+
+        let mismatches = l1.verify(&expected);
+        if mismatches.is_empty() {
+            report.verified_state_diffs += 1;
+        } else {
+            report.findings.push(Finding::StateDiffMismatch {
+                blocks: span.blocks,
+                mismatches,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Groups the blocks from `from` to `to` by the state update on L1 covering them.
+///
+/// The first and last span extend beyond the range to the full set of blocks covered by their
+/// state update.
+fn spans(
+    tx: &Transaction<'_>,
+    from: StarknetBlockNumber,
+    to: StarknetBlockNumber,
+) -> anyhow::Result<Vec<Span>> {
+    let latest = StarknetBlocksTable::get_latest_number(tx)?.context("Database is empty")?;
+    anyhow::ensure!(to <= latest, "Latest stored block is {latest}");
+
+    let preceding = L1StateTable::get_preceding_number(tx, from)?;
+    let mut start = match preceding {
+        Some(preceding) => preceding + 1,
+        None => StarknetBlockNumber::GENESIS,
+    };
+    // Without the preceding state update, only a state update of genesis alone is known to
+    // start there.
+    let mut complete = preceding.is_some();
+
+    let mut spans = Vec::new();
+    let mut number = start;
+    loop {
+        let log = L1StateTable::get(tx, L1TableBlockId::Number(number))?;
+        if log.is_some() || number == latest {
+            spans.push(Span {
+                blocks: start..=number,
+                log,
+                complete: complete || number == StarknetBlockNumber::GENESIS,
+            });
+            if number >= to {
+                return Ok(spans);
+            }
+            start = number + 1;
+            complete = true;
+        }
+        number += 1;
+    }
+}
+
+fn check_state_commitments(
+    tx: &Transaction<'_>,
+    spans: &[Span],
+    from: StarknetBlockNumber,
+    to: StarknetBlockNumber,
+) -> anyhow::Result<Report> {
+    let mut report = Report::default();
+
+    for span in spans {
+        // Only blocks within the audited range are reported.
+        let first = if *span.blocks.start() < from {
+            from
+        } else {
+            *span.blocks.start()
+        };
+        let last = if *span.blocks.end() > to {
+            to
+        } else {
+            *span.blocks.end()
+        };
+
+        let log = match &span.log {
+            Some(log) => log,
+            None => {
+                report.findings.push(Finding::NotOnL1(first..=last));
+                continue;
+            }
+        };
+
+        let block =
+            match StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Number(log.block_number))? {
+                Some(block) => block,
+                None => {
+                    report
+                        .findings
+                        .push(Finding::MissingBlock(log.block_number));
+                    continue;
+                }
+            };
+
+        if block.root == log.global_root {
+            report.verified_state_commitments += 1;
+        } else {
+            report.findings.push(Finding::StateCommitmentMismatch {
+                block: log.block_number,
+                stored: block.root,
+                l1: log.global_root,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+/// Checks that `log` is still part of the canonical Ethereum chain.
+pub async fn is_on_l1(
+    transport: &impl EthereumTransport,
+    core_address: H160,
+    log: &StateUpdateLog,
+) -> anyhow::Result<bool> {
+    let filter = Filter::new()
+        .at_block_hash(log.origin.block.hash.0)
+        .address(core_address)
+        .topic0(StateUpdateLog::signature());
+
+    let logs = transport
+        .logs(filter)
+        .await
+        .with_context(|| format!("Fetching state update of block {}", log.block_number))?;
+
+    Ok(logs
+        .into_iter()
+        .filter_map(|l1_log| StateUpdateLog::try_from(l1_log).ok())
+        .any(|l1_log| &l1_log == log))
+}
+
+/// Combines the stored state diffs of `blocks` into the state diff which StarkNet posts to L1.
+pub fn stored_state_diff(
+    tx: &Transaction<'_>,
+    blocks: RangeInclusive<StarknetBlockNumber>,
+) -> anyhow::Result<blob::StateDiff> {
+    let mut combined = blob::StateDiff::default();
+
+    for number in blocks.start().get()..=blocks.end().get() {
+        let number = StarknetBlockNumber::new_or_panic(number);
+        let hash = StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Number(number))?
+            .with_context(|| format!("Block {number} is missing"))?
+            .hash;
+        let state_diff = StarknetStateUpdatesTable::get(tx, hash)?
+            .with_context(|| format!("State update of block {number} is missing"))?
+            .state_diff;
+
+        // Later blocks override the changes of earlier ones.
+        let mut contract = |address| combined.contracts.entry(address).or_default();
+        for diff in state_diff.storage_diffs {
+            contract(diff.address).storage.insert(diff.key, diff.value);
+        }
+        for nonce in state_diff.nonces {
+            contract(nonce.contract_address).nonce = Some(nonce.nonce);
+        }
+        for deployed in state_diff.deployed_contracts {
+            contract(deployed.address).class_hash = Some(deployed.class_hash);
+        }
+        for replaced in state_diff.replaced_classes {
+            contract(replaced.address).class_hash = Some(replaced.class_hash);
+        }
+        for declared in state_diff.declared_sierra_classes {
+            combined
+                .declared_classes
+                .insert(declared.class_hash, declared.compiled_class_hash);
+        }
+    }
+
+    Ok(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+    use pathfinder_common::{
+        felt, EthereumBlockHash, EthereumBlockNumber, EthereumLogIndex, EthereumTransactionIndex,
+    };
+    use pathfinder_ethereum::provider::DisabledTransport;
+    use pathfinder_ethereum::{BlockOrigin, EthOrigin, TransactionOrigin};
+    use pathfinder_storage::test_fixtures::init;
+    use pathfinder_storage::StarknetBlock;
+
+    fn log(block: u8, global_root: StateCommitment) -> StateUpdateLog {
+        StateUpdateLog {
+            origin: EthOrigin {
+                block: BlockOrigin {
+                    hash: EthereumBlockHash(H256::from_low_u64_be(block as u64)),
+                    number: EthereumBlockNumber(block as u64),
+                },
+                transaction: TransactionOrigin {
+                    hash: EthereumTransactionHash(H256::from_low_u64_be(block as u64)),
+                    index: EthereumTransactionIndex(0),
+                },
+                log_index: EthereumLogIndex(0),
+            },
+            global_root,
+            block_number: StarknetBlockNumber::new_or_panic(block as u64),
+        }
+    }
+
+    /// Blocks 0 to 5, of which 0 to 2 and 3 to 4 are covered by state updates on L1. The state
+    /// commitment on L1 of block 4 is wrong.
+    fn setup() -> Storage {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        init::with_n_state_updates(&tx, 6);
+        L1StateTable::upsert(&tx, &log(2, StarknetBlock::nth(2).root)).unwrap();
+        L1StateTable::upsert(&tx, &log(4, StateCommitment(felt!("0x1234")))).unwrap();
+
+        tx.commit().unwrap();
+        storage
+    }
+
+    fn block(number: u64) -> StarknetBlockNumber {
+        StarknetBlockNumber::new_or_panic(number)
+    }
+
+    #[tokio::test]
+    async fn recorded_state_updates() {
+        let report = audit::<DisabledTransport>(setup(), None, block(1), block(5))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            report,
+            Report {
+                findings: vec![
+                    Finding::StateCommitmentMismatch {
+                        block: block(4),
+                        stored: StarknetBlock::nth(4).root,
+                        l1: StateCommitment(felt!("0x1234")),
+                    },
+                    Finding::NotOnL1(block(5)..=block(5)),
+                ],
+                verified_state_commitments: 1,
+                verified_state_diffs: 0,
+                skipped_state_diffs: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn range_within_state_update() {
+        let report = audit::<DisabledTransport>(setup(), None, block(1), block(1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            report,
+            Report {
+                findings: vec![],
+                verified_state_commitments: 1,
+                verified_state_diffs: 0,
+                skipped_state_diffs: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn beyond_latest_block() {
+        audit::<DisabledTransport>(setup(), None, block(1), block(6))
+            .await
+            .unwrap_err();
+    }
+
+    #[test]
+    fn spans() {
+        let storage = setup();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let spans = super::spans(&tx, block(1), block(3)).unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                Span {
+                    blocks: block(0)..=block(2),
+                    log: Some(log(2, StarknetBlock::nth(2).root)),
+                    complete: false,
+                },
+                Span {
+                    blocks: block(3)..=block(4),
+                    log: Some(log(4, StateCommitment(felt!("0x1234")))),
+                    complete: true,
+                },
+            ]
+        );
+
+        // The recorded state update ending at block 2 bounds the span of block 3.
+        let spans = super::spans(&tx, block(4), block(5)).unwrap();
+        assert_eq!(
+            spans,
+            vec![
+                Span {
+                    blocks: block(3)..=block(4),
+                    log: Some(log(4, StateCommitment(felt!("0x1234")))),
+                    complete: true,
+                },
+                Span {
+                    blocks: block(5)..=block(5),
+                    log: None,
+                    complete: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stored_state_diff() {
+        use pathfinder_storage::types::StateUpdate;
+
+        let storage = setup();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let combined = super::stored_state_diff(&tx, block(1)..=block(2)).unwrap();
+
+        // Each fixture state update touches four distinct contracts and declares one Sierra class.
+        assert_eq!(combined.contracts.len(), 8);
+        assert_eq!(combined.declared_classes.len(), 2);
+
+        let diff = StateUpdate::with_block_hash(2).state_diff;
+        let storage = &diff.storage_diffs[0];
+        assert_eq!(
+            combined.contracts[&storage.address].storage[&storage.key],
+            storage.value
+        );
+        let nonce = &diff.nonces[0];
+        assert_eq!(
+            combined.contracts[&nonce.contract_address].nonce,
+            Some(nonce.nonce)
+        );
+        let deployed = &diff.deployed_contracts[0];
+        assert_eq!(
+            combined.contracts[&deployed.address].class_hash,
+            Some(deployed.class_hash)
+        );
+        let replaced = &diff.replaced_classes[0];
+        assert_eq!(
+            combined.contracts[&replaced.address].class_hash,
+            Some(replaced.class_hash)
+        );
+        let declared = &diff.declared_sierra_classes[0];
+        assert_eq!(
+            combined.declared_classes[&declared.class_hash],
+            declared.compiled_class_hash
+        );
+    }
+}
@@ -1,13 +1,16 @@
+use std::ops::RangeInclusive;
+
 use anyhow::{Context, Error, Result};
-use bitvec::prelude::BitView;
 use pathfinder_common::{
-    Chain, EventCommitment, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
-    StarknetBlockTimestamp, StateCommitment, TransactionCommitment,
+    Chain, ChainParameters, EventCommitment, SequencerAddress, StarknetBlockHash,
+    StarknetBlockNumber, StarknetBlockTimestamp, StateCommitment, TransactionCommitment,
+};
+use pathfinder_merkle_tree::commitment_tree::{
+    calculate_event_hash, calculate_transaction_hash_with_signature, CommitmentTree,
 };
-use pathfinder_merkle_tree::merkle_tree::MerkleTree;
-use stark_hash::{stark_hash, Felt, HashChain};
+use stark_hash::{Felt, HashChain};
 use starknet_gateway_types::reply::{
-    transaction::{Event, Receipt, Transaction},
+    transaction::{Receipt, Transaction},
     Block,
 };
 
@@ -18,6 +21,69 @@ pub enum VerifyResult {
     NotVerifiable,
 }
 
+/// A range of historical blocks whose hashes were computed in a way which cannot be reproduced
+/// from the blocks, such as with a sequencer address which the blocks do not include.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IrregularRange(pub RangeInclusive<StarknetBlockNumber>);
+
+impl std::str::FromStr for IrregularRange {
+    type Err = anyhow::Error;
+
+    /// Parses `<first block number>-<last block number>`, or a single block number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |number: &str| {
+            number
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .and_then(StarknetBlockNumber::new)
+                .with_context(|| format!("Invalid block number {number:?}"))
+        };
+
+        let (first, last) = match s.split_once('-') {
+            Some((first, last)) => (parse(first)?, parse(last)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        anyhow::ensure!(first <= last, "Range {s:?} ends before it starts");
+
+        Ok(Self(first..=last))
+    }
+}
+
+/// The known irregular blocks of a network, which [verify_block_hash] reports as
+/// [VerifyResult::NotVerifiable] instead of as a mismatch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IrregularBlocks(Vec<IrregularRange>);
+
+impl IrregularBlocks {
+    pub fn new(ranges: Vec<IrregularRange>) -> Self {
+        Self(ranges)
+    }
+
+    /// The built-in registry of irregular blocks of `chain`.
+    pub fn for_chain(chain: Chain) -> Self {
+        let range = |first, last| {
+            IrregularRange(
+                StarknetBlockNumber::new_or_panic(first)..=StarknetBlockNumber::new_or_panic(last),
+            )
+        };
+
+        let ranges = match chain {
+            // Starknet 0.8.0 to 0.8.2 hashed with sequencer addresses which are not recoverable.
+            Chain::Testnet => vec![range(119802, 148427)],
+            // Hashed before Starknet 0.7 with a chain id which is not recoverable.
+            Chain::Integration => vec![range(0, 110510)],
+            Chain::Mainnet | Chain::Testnet2 | Chain::Custom => Vec::new(),
+        };
+
+        Self(ranges)
+    }
+
+    pub fn contains(&self, block_number: StarknetBlockNumber) -> bool {
+        self.0.iter().any(|range| range.0.contains(&block_number))
+    }
+}
+
 /// Verify the block hash value.
 ///
 /// The method to compute the block hash is documented
@@ -30,16 +96,20 @@ pub enum VerifyResult {
 ///
 /// See the `compute_block_hash.py` helper script that uses the cairo-lang
 /// Python implementation to compute the block hash for details.
+///
+/// Blocks in `irregular_blocks` are not verified.
 pub fn verify_block_hash(
     block: &Block,
     chain: Chain,
     expected_block_hash: StarknetBlockHash,
+    irregular_blocks: &IrregularBlocks,
 ) -> Result<VerifyResult> {
-    let meta_info = meta::for_chain(chain);
-    if !meta_info.can_verify(block.block_number) {
+    if irregular_blocks.contains(block.block_number) {
         return Ok(VerifyResult::NotVerifiable);
     }
 
+    let meta_info = meta::for_chain(chain);
+
     let num_transactions: u64 = block
         .transactions
         .len()
@@ -49,15 +119,9 @@ pub fn verify_block_hash(
     let event_commitment = calculate_event_commitment(&block.transaction_receipts)?;
 
     let verified = if meta_info.uses_pre_0_7_hash_algorithm(block.block_number) {
-        use pathfinder_common::ChainId;
-        let chain_id = match chain {
-            Chain::Mainnet => ChainId::MAINNET,
-            Chain::Testnet => ChainId::TESTNET,
-            Chain::Integration => ChainId::INTEGRATION,
-            Chain::Testnet2 => ChainId::TESTNET2,
-            Chain::Custom => {
-                anyhow::bail!("Chain::Custom should not have any pre 0.7 block hashes")
-            }
+        let chain_id = match ChainParameters::for_chain(chain) {
+            Some(parameters) => parameters.chain_id,
+            None => anyhow::bail!("Chain::Custom should not have any pre 0.7 block hashes"),
         };
 
         let block_hash = compute_final_hash_pre_0_7(
@@ -103,7 +167,6 @@ pub fn verify_block_hash(
 
 mod meta {
     use pathfinder_common::{felt, Chain, SequencerAddress, StarknetBlockNumber};
-    use std::ops::Range;
 
     /// Metadata about Starknet chains we use for block hash calculation
     ///
@@ -125,24 +188,17 @@ mod meta {
     ///   value is irrecoverable.
     /// * After Starknet 0.8.2 all blocks include the correct sequencer address
     ///   value.
+    ///
+    /// Irrecoverable hashes are listed in [IrregularBlocks](super::IrregularBlocks).
     #[derive(Clone)]
     pub struct BlockHashMetaInfo {
         /// The number of the first block that was hashed with the Starknet 0.7 hash algorithm.
         pub first_0_7_block: StarknetBlockNumber,
-        /// The range of block numbers that can't be verified because of an unknown sequencer address.
-        pub not_verifiable_range: Option<Range<StarknetBlockNumber>>,
         /// Fallback sequencer address to use for blocks that don't include the address.
         pub fallback_sequencer_address: Option<SequencerAddress>,
     }
 
     impl BlockHashMetaInfo {
-        pub fn can_verify(&self, block_number: StarknetBlockNumber) -> bool {
-            match &self.not_verifiable_range {
-                Some(range) => !range.contains(&block_number),
-                None => true,
-            }
-        }
-
         pub fn uses_pre_0_7_hash_algorithm(&self, block_number: StarknetBlockNumber) -> bool {
             block_number < self.first_0_7_block
         }
@@ -150,9 +206,6 @@ mod meta {
 
     const TESTNET_METAINFO: BlockHashMetaInfo = BlockHashMetaInfo {
         first_0_7_block: StarknetBlockNumber::new_or_panic(47028),
-        not_verifiable_range: Some(
-            StarknetBlockNumber::new_or_panic(119802)..StarknetBlockNumber::new_or_panic(148428),
-        ),
         fallback_sequencer_address: Some(SequencerAddress(felt!(
             "046a89ae102987331d369645031b49c27738ed096f2789c24449966da4c6de6b"
         ))),
@@ -160,7 +213,6 @@ mod meta {
 
     const TESTNET2_METAINFO: BlockHashMetaInfo = BlockHashMetaInfo {
         first_0_7_block: StarknetBlockNumber::new_or_panic(0),
-        not_verifiable_range: None,
         fallback_sequencer_address: Some(SequencerAddress(felt!(
             "046a89ae102987331d369645031b49c27738ed096f2789c24449966da4c6de6b"
         ))),
@@ -168,7 +220,6 @@ mod meta {
 
     const MAINNET_METAINFO: BlockHashMetaInfo = BlockHashMetaInfo {
         first_0_7_block: StarknetBlockNumber::new_or_panic(833),
-        not_verifiable_range: None,
         fallback_sequencer_address: Some(SequencerAddress(felt!(
             "021f4b90b0377c82bf330b7b5295820769e72d79d8acd0effa0ebde6e9988bc5"
         ))),
@@ -176,9 +227,6 @@ mod meta {
 
     const INTEGRATION_METAINFO: BlockHashMetaInfo = BlockHashMetaInfo {
         first_0_7_block: StarknetBlockNumber::new_or_panic(110511),
-        not_verifiable_range: Some(
-            StarknetBlockNumber::new_or_panic(0)..StarknetBlockNumber::new_or_panic(110511),
-        ),
         fallback_sequencer_address: Some(SequencerAddress(felt!(
             "046a89ae102987331d369645031b49c27738ed096f2789c24449966da4c6de6b"
         ))),
@@ -186,7 +234,6 @@ mod meta {
 
     const CUSTOM_METAINFO: BlockHashMetaInfo = BlockHashMetaInfo {
         first_0_7_block: StarknetBlockNumber::new_or_panic(0),
-        not_verifiable_range: None,
         fallback_sequencer_address: None,
     };
 
@@ -250,7 +297,7 @@ fn compute_final_hash_pre_0_7(
 
 /// This implements the final hashing step for post-0.7 blocks.
 #[allow(clippy::too_many_arguments)]
-fn compute_final_hash(
+pub(crate) fn compute_final_hash(
     block_number: StarknetBlockNumber,
     state_root: StateCommitment,
     sequencer_address: &SequencerAddress,
@@ -289,36 +336,6 @@ fn compute_final_hash(
     StarknetBlockHash(chain.finalize())
 }
 
-/// A Patricia Merkle tree with height 64 used to compute transaction and event commitments.
-///
-/// According to the [documentation](https://docs.starknet.io/docs/Blocks/header/#block-header)
-/// the commitment trees are of height 64, because the key used is the 64 bit representation
-/// of the index of the transaction / event within the block.
-///
-/// The tree height is 64 in our case since our set operation takes u64 index values.
-struct CommitmentTree {
-    tree: MerkleTree<(), pathfinder_merkle_tree::PedersenHash>,
-}
-
-impl Default for CommitmentTree {
-    fn default() -> Self {
-        Self {
-            tree: MerkleTree::empty((), 64),
-        }
-    }
-}
-
-impl CommitmentTree {
-    pub fn set(&mut self, index: u64, value: Felt) -> Result<()> {
-        let key = index.to_be_bytes();
-        self.tree.set(key.view_bits(), value)
-    }
-
-    pub fn commit(self) -> Result<Felt> {
-        self.tree.commit()
-    }
-}
-
 /// Calculate transaction commitment hash value.
 ///
 /// The transaction commitment is the root of the Patricia Merkle tree with height 64
@@ -345,37 +362,6 @@ pub fn calculate_transaction_commitment(
     Ok(TransactionCommitment(tree.commit()?))
 }
 
-/// Compute the combined hash of the transaction hash and the signature.
-///
-/// Since the transaction hash doesn't take the signature values as its input
-/// computing the transaction commitent uses a hash value that combines
-/// the transaction hash with the array of signature values.
-///
-/// Note that for non-invoke transactions we don't actually have signatures. The
-/// cairo-lang uses an empty list (whose hash is not the ZERO value!) in that
-/// case.
-fn calculate_transaction_hash_with_signature(tx: &Transaction) -> Felt {
-    lazy_static::lazy_static!(
-        static ref HASH_OF_EMPTY_LIST: Felt = HashChain::default().finalize();
-    );
-
-    let signature_hash = match tx {
-        Transaction::Invoke(tx) => {
-            let mut hash = HashChain::default();
-            for signature in tx.signature() {
-                hash.update(signature.0);
-            }
-            hash.finalize()
-        }
-        Transaction::Declare(_)
-        | Transaction::Deploy(_)
-        | Transaction::DeployAccount(_)
-        | Transaction::L1Handler(_) => *HASH_OF_EMPTY_LIST,
-    };
-
-    stark_hash(tx.hash().0, signature_hash)
-}
-
 /// Calculate event commitment hash value.
 ///
 /// The event commitment is the root of the Patricia Merkle tree with height 64
@@ -401,31 +387,6 @@ pub fn calculate_event_commitment(transaction_receipts: &[Receipt]) -> Result<Ev
     Ok(EventCommitment(tree.commit()?))
 }
 
-/// Calculate the hash of an event.
-///
-/// See the [documentation](https://docs.starknet.io/docs/Events/starknet-events#event-hash)
-/// for details.
-fn calculate_event_hash(event: &Event) -> Felt {
-    let mut keys_hash = HashChain::default();
-    for key in event.keys.iter() {
-        keys_hash.update(key.0);
-    }
-    let keys_hash = keys_hash.finalize();
-
-    let mut data_hash = HashChain::default();
-    for data in event.data.iter() {
-        data_hash.update(data.0);
-    }
-    let data_hash = data_hash.finalize();
-
-    let mut event_hash = HashChain::default();
-    event_hash.update(*event.from_address.get());
-    event_hash.update(keys_hash);
-    event_hash.update(data_hash);
-
-    event_hash.finalize()
-}
-
 /// Return the number of events in the block.
 fn number_of_events_in_block(block: &Block) -> usize {
     block
@@ -439,87 +400,7 @@ fn number_of_events_in_block(block: &Block) -> usize {
 mod tests {
     use super::*;
     use assert_matches::assert_matches;
-    use pathfinder_common::{felt, EntryPoint, Fee};
-    use starknet_gateway_types::reply::{
-        transaction::{EntryPointType, InvokeTransaction, InvokeTransactionV0},
-        Block,
-    };
-
-    #[test]
-    fn test_event_hash() {
-        use pathfinder_common::{ContractAddress, EventData, EventKey};
-
-        let event = Event {
-            from_address: ContractAddress::new_or_panic(felt!("0xdeadbeef")),
-            data: vec![
-                EventData(felt!("0x5")),
-                EventData(felt!("0x6")),
-                EventData(felt!("0x7")),
-                EventData(felt!("0x8")),
-                EventData(felt!("0x9")),
-            ],
-            keys: vec![
-                EventKey(felt!("0x1")),
-                EventKey(felt!("0x2")),
-                EventKey(felt!("0x3")),
-                EventKey(felt!("0x4")),
-            ],
-        };
-
-        // produced by the cairo-lang Python implementation:
-        // `hex(calculate_event_hash(0xdeadbeef, [1, 2, 3, 4], [5, 6, 7, 8, 9]))`
-        let expected_event_hash =
-            felt!("0xdb96455b3a61f9139f7921667188d31d1e1d49fb60a1aa3dbf3756dbe3a9b4");
-        let calculated_event_hash = calculate_event_hash(&event);
-        assert_eq!(expected_event_hash, calculated_event_hash);
-    }
-
-    #[test]
-    fn test_final_transaction_hash() {
-        use pathfinder_common::{
-            ContractAddress, StarknetTransactionHash, TransactionSignatureElem,
-        };
-
-        let transaction = Transaction::Invoke(InvokeTransaction::V0(InvokeTransactionV0 {
-            calldata: vec![],
-            sender_address: ContractAddress::new_or_panic(felt!("0xdeadbeef")),
-            entry_point_type: Some(EntryPointType::External),
-            entry_point_selector: EntryPoint(felt!("0xe")),
-            max_fee: Fee::ZERO,
-            signature: vec![
-                TransactionSignatureElem(felt!("0x2")),
-                TransactionSignatureElem(felt!("0x3")),
-            ],
-            transaction_hash: StarknetTransactionHash(felt!("0x1")),
-        }));
-
-        // produced by the cairo-lang Python implementation:
-        // `hex(calculate_single_tx_hash_with_signature(1, [2, 3], hash_function=pedersen_hash))`
-        let expected_final_hash =
-            Felt::from_hex_str("0x259c3bd5a1951eafb2f41e0b783eab92cfe4e108b2b1f071e3736f06b909431")
-                .unwrap();
-        let calculated_final_hash = calculate_transaction_hash_with_signature(&transaction);
-        assert_eq!(expected_final_hash, calculated_final_hash);
-    }
-
-    #[test]
-    fn test_commitment_merkle_tree() {
-        let mut tree = CommitmentTree::default();
-
-        for (idx, hash) in [1u64, 2, 3, 4].into_iter().enumerate() {
-            let hash = Felt::from(hash);
-            let idx: u64 = idx.try_into().unwrap();
-            tree.set(idx, hash).unwrap();
-        }
-
-        // produced by the cairo-lang Python implementation:
-        // `hex(asyncio.run(calculate_patricia_root([1, 2, 3, 4], height=64, ffc=ffc))))`
-        let expected_root_hash =
-            felt!("0x1a0e579b6b444769e4626331230b5ae39bd880f47e703b73fa56bf77e52e461");
-        let computed_root_hash = tree.commit().unwrap();
-
-        assert_eq!(expected_root_hash, computed_root_hash);
-    }
+    use starknet_gateway_types::reply::Block;
 
     #[test]
     fn test_number_of_events_in_block() {
@@ -536,9 +417,10 @@ mod tests {
         // This tests with a post-0.7, pre-0.8.0 block where zero is used as the sequencer address.
         let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_90000;
         let block: Block = serde_json::from_str(json).unwrap();
+        let testnet = IrregularBlocks::for_chain(Chain::Testnet);
 
         assert_matches!(
-            verify_block_hash(&block, Chain::Testnet, block.block_hash).unwrap(),
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &testnet).unwrap(),
             VerifyResult::Match(_)
         );
     }
@@ -549,9 +431,10 @@ mod tests {
         // information in the block itself.
         let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_231579;
         let block: Block = serde_json::from_str(json).unwrap();
+        let testnet = IrregularBlocks::for_chain(Chain::Testnet);
 
         assert_matches!(
-            verify_block_hash(&block, Chain::Testnet, block.block_hash).unwrap(),
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &testnet).unwrap(),
             VerifyResult::Match(_)
         );
     }
@@ -563,9 +446,10 @@ mod tests {
         // instead of zero.
         let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_156000;
         let block: Block = serde_json::from_str(json).unwrap();
+        let testnet = IrregularBlocks::for_chain(Chain::Testnet);
 
         assert_matches!(
-            verify_block_hash(&block, Chain::Testnet, block.block_hash,).unwrap(),
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &testnet).unwrap(),
             VerifyResult::Match(_)
         );
     }
@@ -576,10 +460,45 @@ mod tests {
         // the block hash.
         let json = starknet_gateway_test_fixtures::v0_9_0::block::GENESIS;
         let block: Block = serde_json::from_str(json).unwrap();
+        let testnet = IrregularBlocks::for_chain(Chain::Testnet);
 
         assert_matches!(
-            verify_block_hash(&block, Chain::Testnet, block.block_hash).unwrap(),
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &testnet).unwrap(),
             VerifyResult::Match(_)
         );
     }
+
+    #[test]
+    fn irregular_blocks_are_not_verified() {
+        let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_90000;
+        let mut block: Block = serde_json::from_str(json).unwrap();
+        block.state_commitment = StateCommitment(Felt::ZERO);
+
+        let testnet = IrregularBlocks::for_chain(Chain::Testnet);
+        assert_matches!(
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &testnet).unwrap(),
+            VerifyResult::Mismatch
+        );
+
+        let irregular = IrregularBlocks::new(vec!["89999-90000".parse().unwrap()]);
+        assert_matches!(
+            verify_block_hash(&block, Chain::Testnet, block.block_hash, &irregular).unwrap(),
+            VerifyResult::NotVerifiable
+        );
+    }
+
+    #[test]
+    fn parse_irregular_range() {
+        let number = StarknetBlockNumber::new_or_panic;
+        assert_eq!(
+            "5-7".parse::<IrregularRange>().unwrap(),
+            IrregularRange(number(5)..=number(7))
+        );
+        assert_eq!(
+            "5".parse::<IrregularRange>().unwrap(),
+            IrregularRange(number(5)..=number(5))
+        );
+        "7-5".parse::<IrregularRange>().unwrap_err();
+        "x".parse::<IrregularRange>().unwrap_err();
+    }
 }
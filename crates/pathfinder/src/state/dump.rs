@@ -0,0 +1,318 @@
+//! Export of a single contract's complete state at a block, and its import into the state of
+//! the forked block of fork mode, e.g. for local testing against the state of a live contract.
+//!
+//! A dump is newline-delimited JSON: a [ContractDump] line, followed by a [StorageEntry] line for
+//! each of the contract's non-zero storage entries in ascending key order. Storage is read and
+//! written a page at a time, so that large contracts are never held in memory.
+use std::io::{BufRead, Write};
+
+use anyhow::Context;
+use pathfinder_common::{
+    ClassHash, ContractAddress, ContractNonce, StarknetBlockHash, StarknetBlockNumber,
+    StorageAddress, StorageValue,
+};
+use pathfinder_merkle_tree::state_tree::{ContractsStateTree, StorageCommitmentTree};
+use pathfinder_storage::{
+    CasmClassTable, ContractCodeTable, ContractsStateTable, ForkStateTable, StarknetBlocksBlockId,
+    StarknetBlocksTable,
+};
+use rusqlite::Transaction;
+use serde::{Deserialize, Serialize};
+use stark_hash::Felt;
+
+/// The number of storage entries read from the contract's storage tree at once.
+const PAGE_SIZE: usize = 1024;
+
+/// A contract's class and nonce at a block, which is followed by its storage in a dump.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContractDump {
+    pub contract_address: ContractAddress,
+    pub block_number: StarknetBlockNumber,
+    pub block_hash: StarknetBlockHash,
+    pub class_hash: ClassHash,
+    /// The class definition, as served by the gateway.
+    pub class_definition: serde_json::Value,
+    /// The compiled CASM definition of a Sierra class.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub casm_definition: Option<serde_json::Value>,
+    pub nonce: ContractNonce,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageEntry {
+    pub key: StorageAddress,
+    pub value: StorageValue,
+}
+
+/// Writes a dump of the state of the contract at the given block to `output`, and returns the
+/// dump's header and the number of storage entries written.
+///
+/// Returns [None], without writing anything, if the block does not exist or the contract is not
+/// deployed at it.
+pub fn export(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+    block: StarknetBlocksBlockId,
+    mut output: impl Write,
+) -> anyhow::Result<Option<(ContractDump, usize)>> {
+    let block = match StarknetBlocksTable::get(tx, block).context("Reading block")? {
+        Some(block) => block,
+        None => return Ok(None),
+    };
+
+    let storage_commitment = StarknetBlocksTable::get_storage_commitment(tx, block.number.into())
+        .context("Reading storage commitment")?
+        .context("Storage commitment missing")?;
+
+    let contract_state_hash = StorageCommitmentTree::load(tx, storage_commitment)
+        .context("Loading storage commitment tree")?
+        .get(contract_address)
+        .context("Reading contract state hash")?;
+    let contract_state_hash = match contract_state_hash {
+        Some(contract_state_hash) => contract_state_hash,
+        None => return Ok(None),
+    };
+
+    let (root, class_hash, nonce) =
+        ContractsStateTable::get_root_class_hash_and_nonce(tx, contract_state_hash)
+            .context("Reading contract state")?
+            .with_context(|| format!("Contract state {} missing", contract_state_hash.0))?;
+
+    let class_definition = ContractCodeTable::get_definition(tx, class_hash)
+        .context("Reading class definition")?
+        .with_context(|| format!("Class definition {} missing", class_hash.0))?;
+    let class_definition =
+        serde_json::from_slice(&class_definition).context("Parsing class definition")?;
+
+    let casm_definition = CasmClassTable::get_definition(tx, class_hash)
+        .context("Reading CASM definition")?
+        .map(|casm_definition| {
+            // Classes which failed to compile are stored with an empty definition.
+            anyhow::ensure!(
+                !casm_definition.is_empty(),
+                "Class {} failed to compile to CASM",
+                class_hash.0
+            );
+            serde_json::from_slice(&casm_definition).context("Parsing CASM definition")
+        })
+        .transpose()?;
+
+    let dump = ContractDump {
+        contract_address,
+        block_number: block.number,
+        block_hash: block.hash,
+        class_hash,
+        class_definition,
+        casm_definition,
+        nonce,
+    };
+    write_line(&mut output, &dump)?;
+
+    let contract_state_tree =
+        ContractsStateTree::load(tx, root).context("Loading contract state tree")?;
+    let mut start = Some(StorageAddress::new_or_panic(Felt::ZERO));
+    let mut written = 0;
+    while let Some(key) = start {
+        // Fetch one extra entry, whose key starts the next page.
+        let mut entries = contract_state_tree
+            .entries(key, PAGE_SIZE + 1)
+            .context("Reading contract storage")?;
+        start = match entries.len() > PAGE_SIZE {
+            true => entries.pop().map(|(key, _)| key),
+            false => None,
+        };
+
+        for (key, value) in entries {
+            write_line(&mut output, &StorageEntry { key, value })?;
+            written += 1;
+        }
+    }
+    output.flush().context("Flushing dump")?;
+
+    Ok(Some((dump, written)))
+}
+
+fn write_line(output: &mut impl Write, value: &impl Serialize) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *output, value).context("Writing dump")?;
+    output.write_all(b"\n").context("Writing dump")
+}
+
+/// Imports a dump read from `input` into the state of the forked `block`, and returns the dump's
+/// header and the number of storage entries imported.
+///
+/// The contract's class, nonce and storage replace any of its state fetched before, and its
+/// storage which is not part of the dump is zero.
+pub fn import(
+    tx: &Transaction<'_>,
+    block: StarknetBlockNumber,
+    input: impl BufRead,
+) -> anyhow::Result<(ContractDump, usize)> {
+    let mut lines = input.lines();
+    let dump = lines
+        .next()
+        .context("Dump is empty")?
+        .context("Reading dump")?;
+    let dump: ContractDump = serde_json::from_str(&dump).context("Parsing dump header")?;
+
+    let compress = |definition: &serde_json::Value| -> anyhow::Result<Vec<u8>> {
+        let definition = serde_json::to_vec(definition).context("Serializing definition")?;
+        zstd::bulk::compress(&definition, 10).context("Compressing definition")
+    };
+    let definition = compress(&dump.class_definition)?;
+    let casm_definition = dump.casm_definition.as_ref().map(compress).transpose()?;
+    ForkStateTable::insert_class_compressed(
+        tx,
+        dump.class_hash,
+        &definition,
+        casm_definition.as_deref(),
+    )?;
+    ForkStateTable::insert_imported_contract(
+        tx,
+        block,
+        dump.contract_address,
+        dump.class_hash,
+        dump.nonce,
+    )?;
+
+    let mut imported = 0;
+    for (number, line) in lines.enumerate() {
+        let line = line.context("Reading dump")?;
+        let entry: StorageEntry = serde_json::from_str(&line)
+            .with_context(|| format!("Parsing storage entry on line {}", number + 2))?;
+        ForkStateTable::insert_storage(tx, block, dump.contract_address, entry.key, entry.value)?;
+        imported += 1;
+    }
+
+    Ok((dump, imported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt, ClassCommitment, GasPrice, SequencerAddress, StarknetBlockTimestamp,
+        StorageCommitment,
+    };
+    use pathfinder_merkle_tree::contract_state::update_contract_state;
+    use pathfinder_storage::{ForkBlock, StarknetBlock, Storage};
+    use starknet_gateway_types::reply::state_update::StorageDiff;
+
+    #[test]
+    fn export_and_import() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let contract_address = ContractAddress::new_or_panic(felt!("0x123"));
+        let class_hash = ClassHash(felt!("0xabc"));
+        let nonce = ContractNonce(felt!("0x5"));
+        // More than a page, so that the storage is exported in several pages.
+        let storage_entries = (1..=PAGE_SIZE as u64 + 2)
+            .map(|i| StorageEntry {
+                key: StorageAddress::new_or_panic(Felt::from(i)),
+                value: StorageValue(Felt::from(i * 16)),
+            })
+            .collect::<Vec<_>>();
+
+        ContractCodeTable::insert(&tx, class_hash, br#"{"abi":[]}"#).unwrap();
+
+        let mut storage_commitment_tree =
+            StorageCommitmentTree::load(&tx, StorageCommitment(Felt::ZERO)).unwrap();
+        let diffs = storage_entries
+            .iter()
+            .map(|entry| StorageDiff {
+                key: entry.key,
+                value: entry.value,
+            })
+            .collect::<Vec<_>>();
+        let contract_state_hash = update_contract_state(
+            contract_address,
+            &diffs,
+            Some(nonce),
+            Some(class_hash),
+            &storage_commitment_tree,
+            &tx,
+        )
+        .unwrap();
+        storage_commitment_tree
+            .set(contract_address, contract_state_hash)
+            .unwrap();
+        let storage_commitment = storage_commitment_tree.apply().unwrap();
+
+        let block = StarknetBlock::nth(0);
+        StarknetBlocksTable::insert(&tx, &block, None, storage_commitment, ClassCommitment::ZERO)
+            .unwrap();
+
+        let mut file = Vec::new();
+        let (dump, written) = super::export(
+            &tx,
+            contract_address,
+            StarknetBlocksBlockId::Latest,
+            &mut file,
+        )
+        .unwrap()
+        .unwrap();
+        let expected = ContractDump {
+            contract_address,
+            block_number: block.number,
+            block_hash: block.hash,
+            class_hash,
+            class_definition: serde_json::json!({"abi": []}),
+            casm_definition: None,
+            nonce,
+        };
+        assert_eq!(dump, expected);
+        assert_eq!(written, storage_entries.len());
+
+        let mut lines = std::str::from_utf8(&file).unwrap().lines();
+        let header: ContractDump = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!(header, expected);
+        let entries = lines
+            .map(|line| serde_json::from_str::<StorageEntry>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries, storage_entries);
+
+        let other_contract = ContractAddress::new_or_panic(felt!("0x456"));
+        let mut output = Vec::new();
+        let missing = super::export(
+            &tx,
+            other_contract,
+            StarknetBlocksBlockId::Latest,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(missing, None);
+        assert!(output.is_empty());
+
+        let missing_block = StarknetBlockNumber::new_or_panic(1).into();
+        let missing = super::export(&tx, contract_address, missing_block, Vec::new()).unwrap();
+        assert_eq!(missing, None);
+
+        // Import into the state of a forked block.
+        let fork_block = ForkBlock {
+            number: StarknetBlockNumber::new_or_panic(100),
+            hash: StarknetBlockHash(felt!("0xf0")),
+            timestamp: StarknetBlockTimestamp::new_or_panic(1),
+            gas_price: GasPrice::ZERO,
+            sequencer_address: SequencerAddress(Felt::ZERO),
+            starknet_version: None,
+        };
+        ForkStateTable::insert_block(&tx, &fork_block).unwrap();
+
+        let (dump, imported) = super::import(&tx, fork_block.number, &file[..]).unwrap();
+        assert_eq!(dump, expected);
+        assert_eq!(imported, storage_entries.len());
+
+        let contract = ForkStateTable::get_contract(&tx, fork_block.number, contract_address);
+        assert_eq!(contract.unwrap(), Some((class_hash, nonce)));
+        assert!(ForkStateTable::class_exists(&tx, class_hash).unwrap());
+        let last = storage_entries.last().unwrap();
+        let value = ForkStateTable::get_storage(&tx, fork_block.number, contract_address, last.key);
+        assert_eq!(value.unwrap(), Some(last.value));
+        let unset = StorageAddress::new_or_panic(felt!("0xdead"));
+        let value = ForkStateTable::get_storage(&tx, fork_block.number, contract_address, unset);
+        assert_eq!(value.unwrap(), Some(StorageValue(Felt::ZERO)));
+    }
+}
@@ -0,0 +1,265 @@
+//! Follows the chain stored by another pathfinder instance, for read-only replicas which share its
+//! database but do not sync themselves.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
+use pathfinder_storage::{StarknetBlocksTable, Storage};
+use starknet_gateway_types::sync_state::{
+    syncing::{self, NumberedBlock, Syncing},
+    SyncState,
+};
+
+/// How often a replica checks for blocks stored or reorged by the writer.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The number of most recent blocks whose hashes are compared to detect reorgs.
+const RECENT_BLOCKS: u64 = 64;
+
+/// Polls the latest stored blocks, to report the writer's progress as the replica's sync status
+/// and to evict the cached responses of blocks the writer reorged away.
+///
+/// Cached responses of a reorged block may be served until the next poll notices the reorg. Only
+/// reorgs of the [RECENT_BLOCKS] before the latest block are noticed, so replicas should poll
+/// more often than the writer can store as many blocks.
+pub async fn follow(
+    storage: Storage,
+    state: Arc<SyncState>,
+    interval: Duration,
+) -> anyhow::Result<()> {
+    let mut recent = Vec::new();
+    let mut starting = None;
+
+    loop {
+        state.beat();
+
+        let (latest, new_headers) = tokio::task::spawn_blocking({
+            let storage = storage.clone();
+            let known = recent.clone();
+            move || {
+                storage.read(|tx| {
+                    let latest = recent_blocks(tx)?;
+                    // Same transaction, so the headers belong to the latest blocks.
+                    let new_headers = latest
+                        .iter()
+                        .filter(|block| !known.contains(block))
+                        .filter_map(|(number, _)| {
+                            StarknetBlocksTable::get(tx, (*number).into()).transpose()
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                    Ok((latest, new_headers))
+                })
+            }
+        })
+        .await
+        .context("Joining database task")?
+        .context("Reading recent blocks")?;
+
+        if let Some(reorg_tail) = reorg_tail(&recent, &latest) {
+            tracing::debug!(%reorg_tail, "Evicting cached responses of reorged blocks");
+            storage.header_cache().reorg(reorg_tail);
+            storage.response_cache().reorg(reorg_tail);
+        }
+        // Like sync, the replica caches the headers of the blocks which the writer stored.
+        for header in new_headers {
+            storage.header_cache().insert(header);
+        }
+
+        if let Some(&(number, hash)) = latest.last() {
+            let current = NumberedBlock::from((hash, number));
+            let starting = *starting.get_or_insert(current);
+            // Replicas do not query the gateway, so the writer's latest block is the highest known.
+            *state.status.write().await = Syncing::Status(syncing::Status {
+                starting,
+                current,
+                highest: current,
+            });
+            state.milestones.update(number, number);
+        }
+
+        recent = latest;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Returns the numbers and hashes of the [RECENT_BLOCKS] up to the latest one, in ascending order.
+fn recent_blocks(
+    tx: &impl pathfinder_storage::ReadAccess,
+) -> anyhow::Result<Vec<(StarknetBlockNumber, StarknetBlockHash)>> {
+    let latest = match StarknetBlocksTable::get_latest_number(tx)? {
+        Some(latest) => latest.get(),
+        None => return Ok(Vec::new()),
+    };
+
+    let first = latest.saturating_sub(RECENT_BLOCKS - 1);
+    (first..=latest)
+        .filter_map(|number| {
+            let number = StarknetBlockNumber::new_or_panic(number);
+            StarknetBlocksTable::get_hash(tx, number.into())
+                .map(|hash| hash.map(|hash| (number, hash)))
+                .transpose()
+        })
+        .collect()
+}
+
+/// Returns the first of the `previous` recent blocks which is no longer part of the `latest`
+/// ones, if any.
+///
+/// Blocks before the latest recent blocks cannot be compared, and are assumed to be unchanged.
+fn reorg_tail(
+    previous: &[(StarknetBlockNumber, StarknetBlockHash)],
+    latest: &[(StarknetBlockNumber, StarknetBlockHash)],
+) -> Option<StarknetBlockNumber> {
+    let (first, head) = match (latest.first(), latest.last()) {
+        (Some((first, _)), Some((head, _))) => (*first, *head),
+        // All blocks were removed.
+        _ if !previous.is_empty() => return Some(StarknetBlockNumber::GENESIS),
+        _ => return None,
+    };
+
+    previous
+        .iter()
+        .find(|(number, hash)| {
+            if *number < first {
+                false
+            } else if *number > head {
+                true
+            } else {
+                latest[(number.get() - first.get()) as usize].1 != *hash
+            }
+        })
+        .map(|(number, _)| *number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt_bytes, ClassCommitment, GasPrice, SequencerAddress, StarknetBlockTimestamp,
+        StateCommitment, StorageCommitment,
+    };
+    use pathfinder_storage::{StarknetBlock, StarknetBlocksBlockId};
+    use stark_hash::Felt;
+
+    fn block(number: u64, hash: &[u8]) -> (StarknetBlockNumber, StarknetBlockHash) {
+        (
+            StarknetBlockNumber::new_or_panic(number),
+            StarknetBlockHash(Felt::from_be_slice(hash).unwrap()),
+        )
+    }
+
+    #[test]
+    fn reorg_tail() {
+        let previous = [block(2, b"2"), block(3, b"3"), block(4, b"4")];
+
+        // New blocks.
+        let latest = [block(3, b"3"), block(4, b"4"), block(5, b"5")];
+        assert_eq!(super::reorg_tail(&previous, &latest), None);
+
+        // Block 4 was replaced.
+        let latest = [block(3, b"3"), block(4, b"4'"), block(5, b"5'")];
+        assert_eq!(
+            super::reorg_tail(&previous, &latest),
+            Some(StarknetBlockNumber::new_or_panic(4))
+        );
+
+        // Blocks 3 and 4 were removed.
+        let latest = [block(1, b"1"), block(2, b"2")];
+        assert_eq!(
+            super::reorg_tail(&previous, &latest),
+            Some(StarknetBlockNumber::new_or_panic(3))
+        );
+
+        assert_eq!(
+            super::reorg_tail(&previous, &[]),
+            Some(StarknetBlockNumber::GENESIS)
+        );
+        assert_eq!(super::reorg_tail(&[], &[]), None);
+    }
+
+    #[tokio::test]
+    async fn follow() {
+        let storage = Storage::in_memory().unwrap();
+        let state = Arc::new(SyncState::default());
+
+        let insert = |number: u64, hash: &[u8]| {
+            let (number, hash) = block(number, hash);
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+            if StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Number(number))
+                .unwrap()
+                .is_some()
+            {
+                StarknetBlocksTable::reorg(&tx, number).unwrap();
+            }
+            let block = StarknetBlock {
+                number,
+                hash,
+                root: StateCommitment(Felt::ZERO),
+                timestamp: StarknetBlockTimestamp::new_or_panic(number.get()),
+                gas_price: GasPrice::ZERO,
+                sequencer_address: SequencerAddress(Felt::ZERO),
+                transaction_commitment: None,
+                event_commitment: None,
+            };
+            StarknetBlocksTable::insert(
+                &tx,
+                &block,
+                None,
+                StorageCommitment(Felt::ZERO),
+                ClassCommitment(Felt::ZERO),
+            )
+            .unwrap();
+            tx.commit().unwrap();
+            hash
+        };
+        let synced = |hash: StarknetBlockHash| {
+            let state = state.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(5), async {
+                    loop {
+                        if let Syncing::Status(status) = &*state.status.read().await {
+                            if status.current.hash == hash {
+                                return;
+                            }
+                        }
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                    }
+                })
+                .await
+                .expect("The replica should follow the latest block")
+            }
+        };
+
+        insert(0, b"0");
+        insert(1, b"1");
+        let head = insert(2, b"2");
+
+        let _jh = tokio::spawn(super::follow(
+            storage.clone(),
+            state.clone(),
+            Duration::from_millis(5),
+        ));
+        synced(head).await;
+        let latest = storage.header_cache().get(StarknetBlocksBlockId::Latest);
+        assert_eq!(latest.map(|latest| latest.hash), Some(head));
+
+        let cache = storage.response_cache();
+        let generation = cache.generation();
+        let (number, _) = block(1, b"1");
+        cache.insert("method", Felt::ZERO, number, generation, 1u32);
+        let (number, _) = block(2, b"2");
+        cache.insert("method", felt_bytes!(b"2"), number, generation, 2u32);
+
+        // The writer replaces block 2.
+        let head = insert(2, b"2'");
+        synced(head).await;
+
+        assert_eq!(cache.get::<u32>("method", Felt::ZERO), Some(1));
+        assert_eq!(cache.get::<u32>("method", felt_bytes!(b"2")), None);
+        let latest = storage.header_cache().get(StarknetBlocksBlockId::Latest);
+        assert_eq!(latest.map(|latest| latest.hash), Some(head));
+    }
+}
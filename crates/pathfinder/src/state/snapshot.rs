@@ -0,0 +1,250 @@
+//! Verification of database snapshots, which bootstrap a node from the database of another node
+//! instead of syncing from genesis.
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Context;
+use pathfinder_common::{
+    calculate_class_commitment_leaf_hash, Chain, StarknetBlockHash, StarknetBlockNumber,
+    StateCommitment,
+};
+use pathfinder_merkle_tree::contract_state::calculate_contract_state_hash;
+use pathfinder_merkle_tree::state_tree::{
+    ClassCommitmentTree, ContractsStateTree, StorageCommitmentTree,
+};
+use pathfinder_storage::snapshot::SnapshotHead;
+use pathfinder_storage::{ContractCodeTable, ContractsStateTable, RefsTable, StarknetBlocksTable};
+use rusqlite::Transaction;
+use stark_hash::Felt;
+
+use super::archive::{gateway_state_update, introduced_classes, stored_block};
+use super::block_hash::{verify_block_hash, IrregularBlocks, VerifyResult};
+use super::hooks::ChainHooks;
+
+/// Verifies the chain of a staged snapshot up to its `head`, and lets the `hooks` derive their
+/// tables from each of its blocks within `tx`.
+///
+/// Every block hash is recomputed over the hash of its parent, so that the stored blocks form a
+/// chain up to `head`, and the stored definitions of the classes they introduce must match their
+/// class hashes. The state tries are then rehashed node by node from the storage and class
+/// commitments of `head`, so that the stored state is exactly the one `head` commits to.
+///
+/// Whether `head` itself is canonical is left to the caller to check against L1.
+pub fn verify(
+    tx: &Transaction<'_>,
+    chain: Chain,
+    head: &SnapshotHead,
+    hooks: &ChainHooks,
+) -> anyhow::Result<()> {
+    let l1_l2_head = RefsTable::get_l1_l2_head(tx).context("Reading L1-L2 head")?;
+    let irregular_blocks = IrregularBlocks::for_chain(chain);
+
+    let mut parent_hash = StarknetBlockHash(Felt::ZERO);
+    let mut compiled_class_hashes = HashMap::new();
+    for number in 0..=head.number.get() {
+        let number = StarknetBlockNumber::new_or_panic(number);
+
+        let (block, state_update) = stored_block(tx, number, parent_hash, l1_l2_head)?;
+        match verify_block_hash(&block, chain, block.block_hash, &irregular_blocks)
+            .with_context(|| format!("Verifying hash of block {number}"))?
+        {
+            // Sync accepts these blocks as well.
+            VerifyResult::Match(_) | VerifyResult::NotVerifiable => {}
+            VerifyResult::Mismatch => anyhow::bail!("Block hash mismatch for block {number}"),
+        }
+
+        // Definitions which were not downloaded yet are downloaded and verified by sync.
+        for class_hash in introduced_classes(&state_update) {
+            let definition = match ContractCodeTable::get_definition(tx, class_hash)
+                .context("Reading class definition")?
+            {
+                Some(definition) => definition,
+                None => continue,
+            };
+
+            let computed = starknet_gateway_types::class_hash::compute_class_hash(&definition)
+                .with_context(|| format!("Computing hash of class {}", class_hash.0))?;
+            anyhow::ensure!(
+                computed.hash() == class_hash,
+                "Definition of class {} has hash {}",
+                class_hash.0,
+                computed.hash().0
+            );
+        }
+
+        compiled_class_hashes.extend(
+            state_update
+                .state_diff
+                .declared_sierra_classes
+                .iter()
+                .map(|class| (class.class_hash, class.compiled_class_hash)),
+        );
+
+        if !hooks.is_empty() {
+            let state_update = gateway_state_update(block.block_hash, state_update);
+            hooks.block_applied(tx, &block, &state_update)?;
+        }
+
+        parent_hash = block.block_hash;
+    }
+    anyhow::ensure!(
+        parent_hash == head.hash,
+        "Snapshot head {} has hash {} but the stored chain ends in {}",
+        head.number,
+        head.hash,
+        parent_hash
+    );
+
+    let (storage_commitment, class_commitment) =
+        StarknetBlocksTable::get_state_commitment(tx, head.number.into())
+            .context("Reading state commitment")?
+            .context("State commitment of snapshot head is missing")?;
+    anyhow::ensure!(
+        StateCommitment::calculate(storage_commitment, class_commitment) == head.state_commitment,
+        "State commitment of snapshot head does not match its storage and class commitments"
+    );
+
+    let mut verified_roots = HashSet::new();
+    StorageCommitmentTree::load(tx, storage_commitment)
+        .context("Loading storage commitment tree")?
+        .verify(|address, state_hash| {
+            let (root, class_hash, nonce) =
+                ContractsStateTable::get_root_class_hash_and_nonce(tx, state_hash)
+                    .context("Reading contract state")?
+                    .with_context(|| format!("State of contract {} is missing", address.0))?;
+            anyhow::ensure!(
+                calculate_contract_state_hash(class_hash, root, nonce) == state_hash,
+                "State hash of contract {} does not match its state",
+                address.0
+            );
+
+            // Contracts with identical storage share their storage trie.
+            if verified_roots.insert(root.0) {
+                ContractsStateTree::load(tx, root)
+                    .and_then(|tree| tree.verify())
+                    .with_context(|| format!("Verifying storage of contract {}", address.0))?;
+            }
+
+            Ok(())
+        })
+        .context("Verifying storage commitment")?;
+
+    ClassCommitmentTree::load(tx, class_commitment)
+        .context("Loading class commitment tree")?
+        .verify(|class_hash, leaf| {
+            let compiled_class_hash =
+                compiled_class_hashes.remove(&class_hash).with_context(|| {
+                    format!(
+                        "Class {} is committed to but was never declared",
+                        class_hash.0
+                    )
+                })?;
+            anyhow::ensure!(
+                calculate_class_commitment_leaf_hash(compiled_class_hash) == leaf,
+                "Class commitment leaf of class {} does not match its compiled class hash",
+                class_hash.0
+            );
+            Ok(())
+        })
+        .context("Verifying class commitment")?;
+    anyhow::ensure!(
+        compiled_class_hashes.is_empty(),
+        "{} declared classes are missing from the class commitment",
+        compiled_class_hashes.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::archive::tests::setup;
+    use crate::state::hooks::ChainHook;
+    use pathfinder_storage::Storage;
+    use starknet_gateway_types::reply::{Block, StateUpdate};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    /// The block hashes of the blocks of [setup] cannot be verified on this chain.
+    const CHAIN: Chain = Chain::Integration;
+
+    fn head(storage: &Storage) -> SnapshotHead {
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let number = StarknetBlockNumber::new_or_panic(1);
+        let block = StarknetBlocksTable::get(&tx, number.into())
+            .unwrap()
+            .unwrap();
+
+        SnapshotHead {
+            number,
+            hash: block.hash,
+            state_commitment: block.root,
+            l1_state_update: None,
+        }
+    }
+
+    fn verify(storage: &Storage, head: &SnapshotHead, hooks: &ChainHooks) -> anyhow::Result<()> {
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        super::verify(&tx, CHAIN, head, hooks)
+    }
+
+    #[derive(Default)]
+    struct CountingHook(AtomicU64);
+
+    impl ChainHook for CountingHook {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn block_applied(
+            &self,
+            _: &Transaction<'_>,
+            _: &Block,
+            _: &StateUpdate,
+        ) -> anyhow::Result<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+
+        fn reorg(&self, _: &Transaction<'_>, _: StarknetBlockNumber) -> anyhow::Result<()> {
+            unreachable!("Snapshots are not reorged")
+        }
+    }
+
+    #[test]
+    fn stored_chain_is_verified_and_hooked() {
+        let storage = setup();
+        let hook = Arc::new(CountingHook::default());
+        let mut hooks = ChainHooks::default();
+        hooks.register(hook.clone());
+
+        verify(&storage, &head(&storage), &hooks).unwrap();
+
+        assert_eq!(hook.0.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn head_must_end_the_chain() {
+        let storage = setup();
+        let mut head = head(&storage);
+        head.hash = StarknetBlockHash(pathfinder_common::felt!("0x1234"));
+
+        let error = verify(&storage, &head, &ChainHooks::default()).unwrap_err();
+        assert!(format!("{error:#}").contains("the stored chain ends in"));
+    }
+
+    #[test]
+    fn tampered_contract_state() {
+        let storage = setup();
+        storage
+            .connection()
+            .unwrap()
+            .execute("UPDATE contract_states SET nonce = X'1234'", [])
+            .unwrap();
+
+        let error = verify(&storage, &head(&storage), &ChainHooks::default()).unwrap_err();
+        assert!(format!("{error:#}").contains("does not match its state"));
+    }
+}
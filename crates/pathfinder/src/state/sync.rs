@@ -1,42 +1,56 @@
+pub mod checkpoint;
+pub mod deferred;
+#[cfg(feature = "execution")]
+pub mod fork;
+pub mod hooks;
 pub mod l1;
 pub mod l2;
 mod pending;
+mod poll;
+mod stall;
 
+use crate::persisted_metrics;
 use anyhow::Context;
 use ethers::types::H160;
 use pathfinder_common::{
-    Chain, ClassCommitment, ClassHash, ContractNonce, ContractRoot, EventCommitment, GasPrice,
-    SequencerAddress, StarknetBlockHash, StarknetBlockNumber, StateCommitment, StorageCommitment,
-    TransactionCommitment,
+    Chain, ClassCommitment, ClassHash, ContractAddress, ContractNonce, ContractRoot,
+    EventCommitment, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
+    StateCommitment, StorageCommitment, TransactionCommitment,
 };
 use pathfinder_ethereum::{log::StateUpdateLog, provider::EthereumTransport};
 use pathfinder_merkle_tree::{
     contract_state::{calculate_contract_state_hash, update_contract_state},
     state_tree::{ClassCommitmentTree, StorageCommitmentTree},
 };
-use pathfinder_rpc::{
-    v02::types::syncing::{self, NumberedBlock, Syncing},
-    SyncState,
-};
 use pathfinder_storage::{
     types::{CompressedCasmClass, CompressedContract},
-    CasmClassTable, ClassCommitmentLeavesTable, ContractCodeTable, ContractsStateTable,
-    L1StateTable, L1TableBlockId, RefsTable, StarknetBlock, StarknetBlocksBlockId,
-    StarknetBlocksTable, StarknetStateUpdatesTable, StarknetTransactionsTable, Storage,
+    BlockHeaderCache, CasmClassTable, CasmCompilationFailuresTable, ClassCommitmentLeavesTable,
+    ContractCodeTable, ContractsStateTable, DeferredClassesTable, L1StateTable, L1TableBlockId,
+    MetricCountersTable, NonceHistoryTable, PrunedTree, RefsTable, Reorg, ReorgHistoryTable,
+    ResponseCache, StarknetBlock, StarknetBlocksBlockId, StarknetBlocksTable,
+    StarknetStateUpdatesTable, StarknetTransactionsTable, Storage, TreePruningTable,
 };
 use rusqlite::{Connection, Transaction, TransactionBehavior};
 use stark_hash::Felt;
-use starknet_gateway_client::ClientApi;
+use starknet_gateway_client::{ClientApi, RateLimit};
 use starknet_gateway_types::{
-    pending::PendingData,
+    pending::{PendingData, PendingStateVersion},
     reply::{
         state_update::DeployedContract, Block, MaybePendingBlock, PendingStateUpdate, StateUpdate,
     },
+    sync_state::{
+        syncing::{self, NumberedBlock, Syncing},
+        ChainUpdate, SyncState,
+    },
 };
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::{collections::HashMap, future::Future};
 use tokio::sync::mpsc;
 
+/// How often the main sync loop [beats](SyncState::beat) while it is responsive.
+pub const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
 /// Implements the main sync loop, where L1 and L2 sync results are combined.
 #[allow(clippy::too_many_arguments)]
 pub async fn sync<Transport, SequencerClient, F1, F2, L1Sync, L2Sync>(
@@ -51,6 +65,11 @@ pub async fn sync<Transport, SequencerClient, F1, F2, L1Sync, L2Sync>(
     pending_data: PendingData,
     pending_poll_interval: Option<std::time::Duration>,
     block_validation_mode: l2::BlockValidationMode,
+    checkpoints: Vec<checkpoint::Checkpoint>,
+    lazy_class_download: bool,
+    hooks: hooks::ChainHooks,
+    rate_limit: RateLimit,
+    download_workers: NonZeroUsize,
 ) -> anyhow::Result<()>
 where
     Transport: EthereumTransport + Clone,
@@ -65,6 +84,8 @@ where
             Chain,
             Option<std::time::Duration>,
             l2::BlockValidationMode,
+            RateLimit,
+            l2::Prefetch,
         ) -> F2
         + Copy,
 {
@@ -81,10 +102,26 @@ where
         let l1_head = L1StateTable::get(&tx, L1TableBlockId::Latest)
             .context("Query L1 head from database")?;
         let l2_head = StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest)
-            .context("Query L2 head from database")?
-            .map(|block| (block.number, block.hash, block.root));
+            .context("Query L2 head from database")?;
+
+        // The stored chain must already pass through the checkpoints below its head.
+        for checkpoint in &checkpoints {
+            if let Some(block) = StarknetBlocksTable::get(&tx, checkpoint.block_number.into())
+                .context("Query checkpointed block from database")?
+            {
+                checkpoint::verify_block(&checkpoints, block.number, block.hash, block.root)
+                    .context("Verify stored chain against checkpoints")?;
+            }
+        }
+
         Ok((l1_head, l2_head))
     })?;
+    // Sync is the only writer, so the stored head stays valid until sync replaces it. This way
+    // RPC queries of the latest block are served from the cache from the start.
+    if let Some(head) = &l2_head {
+        storage.header_cache().insert(head.clone());
+    }
+    let l2_head = l2_head.map(|block| (block.number, block.hash, block.root));
 
     // Start update sync-status process.
     let (starting_block_num, starting_block_hash, _) = l2_head.unwrap_or((
@@ -93,14 +130,24 @@ where
         StarknetBlockHash(Felt::ZERO),
         StateCommitment(Felt::ZERO),
     ));
+    let stalls = stall::StallDetector::new(l2_head.map(|(number, _, _)| number));
+    let _stall_publisher = tokio::spawn(stalls.clone().publish_periodically());
     let _status_sync = tokio::spawn(update_sync_status_latest(
         Arc::clone(&state),
         sequencer.clone(),
         starting_block_hash,
         starting_block_num,
         chain,
+        rate_limit.clone(),
+        stalls.clone(),
     ));
 
+    // Classes are not prefetched if they are only downloaded once needed.
+    let prefetch = l2::Prefetch {
+        workers: download_workers,
+        classes: !lazy_class_download,
+    };
+
     // Start L1 and L2 sync processes.
     let mut l1_handle = tokio::spawn(l1_sync(
         tx_l1,
@@ -115,7 +162,9 @@ where
         l2_head,
         chain,
         pending_poll_interval,
-        block_validation_mode,
+        block_validation_mode.clone(),
+        rate_limit.clone(),
+        prefetch,
     ));
 
     let mut existed = (0, 0);
@@ -128,16 +177,24 @@ where
     #[cfg(not(test))]
     const RESET_DELAY_ON_FAILURE: std::time::Duration = std::time::Duration::from_secs(60);
 
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
     loop {
         tokio::select! {
+            _ = heartbeat.tick() => state.beat(),
             l1_event = rx_l1.recv() => match l1_event {
                 Some(l1::Event::Update(updates)) => {
                     let first = updates.first().map(|u| u.block_number.get());
                     let last = updates.last().map(|u| u.block_number.get());
 
-                    l1_update(&mut db_conn, &updates).await.with_context(|| {
+                    let l1_l2_head = l1_update(&mut db_conn, &updates).await.with_context(|| {
                         format!("Update L1 state with blocks {first:?}-{last:?}")
                     })?;
+                    if let Some(l1_l2_head) = l1_l2_head {
+                        // An error only means that nobody is subscribed.
+                        let _ = state.chain_updates.send(ChainUpdate::AcceptedOnL1(l1_l2_head));
+                    }
 
                     match updates.as_slice() {
                         [single] => {
@@ -154,7 +211,7 @@ where
                     }
                 }
                 Some(l1::Event::Reorg(reorg_tail)) => {
-                    l1_reorg(&mut db_conn, reorg_tail)
+                    l1_reorg(&mut db_conn, storage.response_cache(), reorg_tail)
                         .await
                         .with_context(|| format!("Reorg L1 state to block {reorg_tail}"))?;
 
@@ -218,10 +275,25 @@ where
                     let block_number = block.block_number;
                     let block_hash = block.block_hash;
                     let storage_updates: usize = state_update.state_diff.storage_diffs.values().map(|storage_diffs| storage_diffs.len()).sum();
+                    // Only clone the block if anyone is subscribed to it.
+                    let applied_block = (state.chain_updates.receiver_count() > 0).then(|| Arc::new(block.as_ref().clone()));
+                    checkpoint::verify_block(&checkpoints, block_number, block_hash, state_update.new_root)?;
                     let update_t = std::time::Instant::now();
-                    l2_update(&mut db_conn, *block, tx_comm, ev_comm, *state_update)
+                    stalls.applying();
+                    let accepted_on_l1 = l2_update(&mut db_conn, storage.header_cache(), &hooks, *block, tx_comm, ev_comm, *state_update)
                         .await
                         .with_context(|| format!("Update L2 state to {block_number}"))?;
+                    // Pending data prepared on top of the previous head, or read by the RPC API
+                    // before the new head was written, is stale from now on.
+                    pending_data.advance_head().await;
+                    stalls.applied(block_number);
+                    if let Some(applied_block) = applied_block {
+                        // An error only means that all subscribers have since gone away.
+                        let _ = state.chain_updates.send(ChainUpdate::Block(applied_block));
+                        if accepted_on_l1 {
+                            let _ = state.chain_updates.send(ChainUpdate::AcceptedOnL1(block_number));
+                        }
+                    }
                     let block_time = last_block_start.elapsed();
                     let update_t = update_t.elapsed();
                     last_block_start = std::time::Instant::now();
@@ -238,6 +310,8 @@ where
                             if status.highest.number <= block_number {
                                 status.highest = status.current;
                             }
+
+                            state.milestones.update(status.current.number, status.highest.number);
                         }
                     }
 
@@ -268,16 +342,26 @@ where
                     }
                 }
                 Some(l2::Event::Reorg(reorg_tail)) => {
+                    let head = tokio::task::block_in_place(|| {
+                        let tx = db_conn.transaction()?;
+                        StarknetBlocksTable::get_latest_number(&tx)
+                    })
+                    .context("Query L2 head")?;
+                    checkpoint::verify_reorg(&checkpoints, reorg_tail, head)?;
                     pending_data.clear().await;
 
-                    l2_reorg(&mut db_conn, reorg_tail)
+                    l2_reorg(&mut db_conn, storage.header_cache(), storage.response_cache(), &hooks, reorg_tail)
                         .await
                         .with_context(|| format!("Reorg L2 state to {reorg_tail:?}"))?;
+                    pending_data.advance_head().await;
+                    // An error only means that there are no subscribers.
+                    let _ = state.chain_updates.send(ChainUpdate::Reorg(reorg_tail));
 
                     let new_head = match reorg_tail {
                         StarknetBlockNumber::GENESIS => None,
                         other => Some(other - 1),
                     };
+                    stalls.reorged(new_head);
                     match new_head {
                         Some(head) => {
                             tracing::info!("L2 reorg occurred, new L2 head is block {}", head)
@@ -306,6 +390,12 @@ where
 
                     tracing::trace!("Inserted new Sierra contract {}", sierra_class.hash.0.to_hex_str());
                 }
+                Some(l2::Event::CasmCompilationFailed(class_hash, error)) => {
+                    tokio::task::block_in_place(|| {
+                        CasmCompilationFailuresTable::upsert(&db_conn, class_hash, crate::sierra::COMPILER_VERSION, &error)
+                    })
+                    .with_context(|| format!("Record CASM compilation failure of class {class_hash}"))?;
+                }
                 Some(l2::Event::QueryBlock(number, tx)) => {
                     let block = tokio::task::block_in_place(|| {
                         let tx = db_conn.transaction()?;
@@ -332,17 +422,48 @@ where
                     //        Overall, quite nasty as is, so should get a proper refactor instead.
                     existed = (contracts.len(), count);
 
+                    // Store placeholders for the missing classes, whose definitions are downloaded later.
+                    let exists = if lazy_class_download && count < contracts.len() {
+                        let missing = contracts
+                            .iter()
+                            .zip(&exists)
+                            .filter_map(|(class, exists)| (!exists).then_some(*class))
+                            .collect::<Vec<_>>();
+
+                        tokio::task::block_in_place(|| {
+                            let tx = db_conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                            DeferredClassesTable::insert(&tx, &missing)?;
+                            tx.commit()?;
+                            anyhow::Result::<()>::Ok(())
+                        })
+                        .with_context(|| format!("Defer download of classes {missing:?}"))?;
+
+                        vec![true; contracts.len()]
+                    } else {
+                        exists
+                    };
+
                     let _ = tx.send(exists);
 
                     tracing::trace!("Query for existence of contracts: {:?}", contracts);
                 }
                 Some(l2::Event::Pending(block, state_update)) => {
-                    download_verify_and_insert_missing_classes(sequencer.clone(), &mut db_conn, &state_update, chain)
+                    let generation = pending_data.generation();
+                    download_verify_and_insert_missing_classes(sequencer.clone(), &mut db_conn, &state_update)
                         .await
                         .context("Downloading missing classes for pending block")?;
 
-                    pending_data.set(block, state_update).await;
-                    tracing::debug!("Updated pending data");
+                    let changed = pending_data.version().await != Some(PendingStateVersion::of(&block));
+                    let pending_block = (changed && state.chain_updates.receiver_count() > 0).then(|| block.clone());
+                    if pending_data.set_if_current(generation, block, state_update).await {
+                        if let Some(pending_block) = pending_block {
+                            // An error only means that all subscribers have since gone away.
+                            let _ = state.chain_updates.send(ChainUpdate::Pending(pending_block));
+                        }
+                        tracing::debug!("Updated pending data");
+                    } else {
+                        tracing::debug!("Discarded pending data of a previous head");
+                    }
                 }
                 None => {
                     pending_data.clear().await;
@@ -366,7 +487,7 @@ where
                     let (new_tx, new_rx) = mpsc::channel(1);
                     rx_l2 = new_rx;
 
-                    let fut = l2_sync(new_tx, sequencer.clone(), l2_head, chain, pending_poll_interval, block_validation_mode);
+                    let fut = l2_sync(new_tx, sequencer.clone(), l2_head, chain, pending_poll_interval, block_validation_mode.clone(), rate_limit.clone(), prefetch);
 
                     l2_handle = tokio::spawn(async move {
                         #[cfg(not(test))]
@@ -387,16 +508,28 @@ async fn update_sync_status_latest(
     starting_block_hash: StarknetBlockHash,
     starting_block_num: StarknetBlockNumber,
     chain: Chain,
+    rate_limit: RateLimit,
+    stalls: stall::StallDetector,
 ) -> anyhow::Result<()> {
     use pathfinder_common::BlockId;
 
-    let poll_interval = head_poll_interval(chain);
+    let mut poll = poll::AdaptivePoll::new(head_poll_interval(chain), rate_limit);
 
     let starting = NumberedBlock::from((starting_block_hash, starting_block_num));
+    let mut last_skew = TimestampSkew::Ok;
 
     loop {
         match sequencer.block(BlockId::Latest).await {
             Ok(MaybePendingBlock::Block(block)) => {
+                stalls.gateway_head(block.block_number);
+                last_skew = check_timestamp_skew(
+                    chain,
+                    block.block_number,
+                    block.timestamp,
+                    std::time::SystemTime::now(),
+                    last_skew,
+                );
+
                 let latest = {
                     let latest_hash = block.block_hash;
                     let latest_num = block.block_number;
@@ -410,6 +543,7 @@ async fn update_sync_status_latest(
                             current: starting,
                             highest: latest,
                         });
+                        state.milestones.update(starting.number, latest.number);
 
                         tracing::debug!(
                             status=%sync_status,
@@ -419,6 +553,9 @@ async fn update_sync_status_latest(
                     Syncing::Status(status) => {
                         if status.highest.hash != latest.hash {
                             status.highest = latest;
+                            state
+                                .milestones
+                                .update(status.current.number, latest.number);
 
                             tracing::debug!(
                                 %status,
@@ -436,11 +573,86 @@ async fn update_sync_status_latest(
             }
         }
 
-        tokio::time::sleep(poll_interval).await;
+        poll.sleep().await;
+    }
+}
+
+/// How far the latest block's timestamp may be ahead of the local clock before the local clock is
+/// considered wrong.
+const MAX_TIMESTAMP_AHEAD: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How old the latest block may get before the gateway is considered stalled. This is well above
+/// the expected block time, see [head_poll_interval].
+fn max_head_age(chain: Chain) -> std::time::Duration {
+    use pathfinder_common::Chain::*;
+    use std::time::Duration;
+
+    match chain {
+        Mainnet => Duration::from_secs(60 * 60 * 2),
+        Testnet | Testnet2 | Integration | Custom => Duration::from_secs(60 * 30),
     }
 }
 
-async fn l1_update(connection: &mut Connection, updates: &[StateUpdateLog]) -> anyhow::Result<()> {
+/// The result of comparing the latest block's timestamp with the local clock.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum TimestampSkew {
+    Ok,
+    /// The latest block is older than [max_head_age].
+    Stalled,
+    /// The latest block's timestamp lies more than [MAX_TIMESTAMP_AHEAD] in the future.
+    ClockBehind,
+}
+
+/// Publishes the difference between the local clock and the latest block's timestamp as the
+/// `chain_head_timestamp_skew_seconds` gauge, and warns once whenever the skew becomes suspicious.
+///
+/// A positive skew means the latest block is in the past. Consumers relying on block timestamps,
+/// for example for expiry logic, are affected by both a stalled gateway and a wrong local clock.
+fn check_timestamp_skew(
+    chain: Chain,
+    head: StarknetBlockNumber,
+    head_timestamp: pathfinder_common::StarknetBlockTimestamp,
+    now: std::time::SystemTime,
+    previous: TimestampSkew,
+) -> TimestampSkew {
+    let now = now
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let skew = now as i64 - head_timestamp.get() as i64;
+
+    metrics::gauge!("chain_head_timestamp_skew_seconds", skew as f64);
+
+    let current = if skew > max_head_age(chain).as_secs() as i64 {
+        TimestampSkew::Stalled
+    } else if -skew > MAX_TIMESTAMP_AHEAD.as_secs() as i64 {
+        TimestampSkew::ClockBehind
+    } else {
+        TimestampSkew::Ok
+    };
+
+    if current != previous {
+        match current {
+            TimestampSkew::Ok => {
+                tracing::info!(%head, skew_seconds=%skew, "Latest block timestamp is in line with the local clock again")
+            }
+            TimestampSkew::Stalled => {
+                tracing::warn!(%head, skew_seconds=%skew, "Latest block is unexpectedly old, the gateway appears to be stalled")
+            }
+            TimestampSkew::ClockBehind => {
+                tracing::warn!(%head, skew_seconds=%skew, "Latest block timestamp is in the future, the local clock is likely wrong")
+            }
+        }
+    }
+
+    current
+}
+
+/// Stores the L1 state updates, and returns the new L1-L2 head if it advanced.
+async fn l1_update(
+    connection: &mut Connection,
+    updates: &[StateUpdateLog],
+) -> anyhow::Result<Option<StarknetBlockNumber>> {
     tokio::task::block_in_place(move || {
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
@@ -456,9 +668,9 @@ async fn l1_update(connection: &mut Connection, updates: &[StateUpdateLog]) -> a
             .map(|head| head + 1)
             .unwrap_or(StarknetBlockNumber::GENESIS);
 
+        let mut next_head = None;
         match updates.first() {
             Some(update) if update.block_number == expected_next => {
-                let mut next_head = None;
                 for update in updates {
                     let l2_root = StarknetBlocksTable::get_state_commitment(
                         &transaction,
@@ -483,12 +695,17 @@ async fn l1_update(connection: &mut Connection, updates: &[StateUpdateLog]) -> a
             _ => {}
         }
 
-        transaction.commit().context("Commit database transaction")
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
+
+        Ok(next_head)
     })
 }
 
 async fn l1_reorg(
     connection: &mut Connection,
+    response_cache: &ResponseCache,
     reorg_tail: StarknetBlockNumber,
 ) -> anyhow::Result<()> {
     tokio::task::block_in_place(move || {
@@ -511,144 +728,243 @@ async fn l1_reorg(
             _ => {}
         }
 
-        transaction.commit().context("Commit database transaction")
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
+
+        // Cached responses of the reorged blocks claim that they are accepted on L1.
+        response_cache.reorg(reorg_tail);
+
+        Ok(())
     })
 }
 
-/// Returns the new [StateCommitment] after the update.
+/// Stores the block, and returns whether L1 had already accepted it.
 async fn l2_update(
     connection: &mut Connection,
+    header_cache: &BlockHeaderCache,
+    hooks: &hooks::ChainHooks,
     block: Block,
     tx_commitment: TransactionCommitment,
     ev_commitment: EventCommitment,
     state_update: StateUpdate,
-) -> anyhow::Result<()> {
-    use pathfinder_storage::CanonicalBlocksTable;
-
+) -> anyhow::Result<bool> {
     tokio::task::block_in_place(move || {
+        let started = std::time::Instant::now();
+        let transaction_count = block.transactions.len();
+        let event_count: usize = block
+            .transaction_receipts
+            .iter()
+            .map(|receipt| receipt.events.len())
+            .sum();
+
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
             .context("Create database transaction")?;
 
-        let (new_storage_commitment, new_class_commitment) =
-            update_starknet_state(&transaction, &state_update)
-                .context("Updating Starknet state")?;
-        let new_root = StateCommitment::calculate(new_storage_commitment, new_class_commitment);
-
-        // Ensure that roots match.. what should we do if it doesn't? For now the whole sync process ends..
-        anyhow::ensure!(new_root == block.state_commitment, "State root mismatch");
-
-        // Update L2 database. These types shouldn't be options at this level,
-        // but for now the unwraps are "safe" in that these should only ever be
-        // None for pending queries to the sequencer, but we aren't using those here.
-        let starknet_block = StarknetBlock {
-            number: block.block_number,
-            hash: block.block_hash,
-            root: block.state_commitment,
-            timestamp: block.timestamp,
-            // Default value for cairo <0.8.2 is 0
-            gas_price: block.gas_price.unwrap_or(GasPrice::ZERO),
-            sequencer_address: block
-                .sequencer_address
-                .unwrap_or(SequencerAddress(Felt::ZERO)),
-            transaction_commitment: Some(tx_commitment),
-            event_commitment: Some(ev_commitment),
-        };
-        StarknetBlocksTable::insert(
+        // Inserting consumes the block, so keep a copy for the hooks if there are any.
+        let for_hooks = (!hooks.is_empty()).then(|| (block.clone(), state_update.clone()));
+        let starknet_block = insert_block(
             &transaction,
-            &starknet_block,
-            block.starknet_version.as_deref(),
-            new_storage_commitment,
-            new_class_commitment,
-        )
-        .context("Insert block into database")?;
+            block,
+            tx_commitment,
+            ev_commitment,
+            state_update,
+        )?;
+        if let Some((block, state_update)) = for_hooks {
+            hooks.block_applied(&transaction, &block, &state_update)?;
+        }
+        MetricCountersTable::add(&transaction, persisted_metrics::BLOCKS, 1)
+            .context("Counting block")?;
+        // Set by inserting the block, if L1 had already accepted it.
+        let accepted_on_l1 = RefsTable::get_l1_l2_head(&transaction).context("Query L1-L2 head")?
+            == Some(starknet_block.number);
 
-        let rpc_state_update = state_update.into();
-        StarknetStateUpdatesTable::insert(&transaction, block.block_hash, &rpc_state_update)
-            .context("Insert state update into database")?;
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
 
-        CanonicalBlocksTable::insert(&transaction, block.block_number, block.block_hash)
-            .context("Inserting canonical block into database")?;
+        header_cache.insert(starknet_block);
+        metrics::increment_counter!(persisted_metrics::BLOCKS);
+        record_ingest(transaction_count, event_count, started.elapsed());
 
-        let declared_sierra_class_hashes = rpc_state_update
-            .state_diff
-            .declared_sierra_classes
-            .iter()
-            .map(|c| ClassHash(c.class_hash.0));
-        let declared_cairo_class_hashes = rpc_state_update
-            .state_diff
-            .declared_contracts
-            .iter()
-            .map(|c| c.class_hash);
-        let deployed_class_hashes = rpc_state_update
-            .state_diff
-            .deployed_contracts
-            .iter()
-            .map(|d| d.class_hash);
-        let declared_class_hashes = declared_sierra_class_hashes
-            .chain(declared_cairo_class_hashes)
-            .chain(deployed_class_hashes);
-        for class_hash in declared_class_hashes {
-            ContractCodeTable::update_declared_on_if_null(
-                &transaction,
-                class_hash,
-                block.block_hash,
-            )
-            .with_context(|| format!("Setting declared_on for class={:?}", class_hash))?;
-        }
+        Ok(accepted_on_l1)
+    })
+}
 
-        // Insert the transactions.
-        anyhow::ensure!(
-            block.transactions.len() == block.transaction_receipts.len(),
-            "Transactions and receipts mismatch. There were {} transactions and {} receipts.",
-            block.transactions.len(),
-            block.transaction_receipts.len()
+/// Reports how fast a block with `transaction_count` transactions and `event_count` events was
+/// written to the database, which took `elapsed` including applying its state update.
+fn record_ingest(transaction_count: usize, event_count: usize, elapsed: std::time::Duration) {
+    metrics::counter!("sync_ingested_transactions_total", transaction_count as u64);
+    metrics::counter!("sync_ingested_events_total", event_count as u64);
+
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        metrics::gauge!(
+            "sync_ingest_transactions_per_second",
+            transaction_count as f64 / seconds
         );
-        let transaction_data = block
-            .transactions
-            .into_iter()
-            .zip(block.transaction_receipts.into_iter())
-            .collect::<Vec<_>>();
-        StarknetTransactionsTable::upsert(
-            &transaction,
-            starknet_block.hash,
-            starknet_block.number,
-            &transaction_data,
-        )
-        .context("Insert transaction data into database")?;
+    }
+}
 
-        // Track combined L1 and L2 state.
-        let l1_l2_head = RefsTable::get_l1_l2_head(&transaction).context("Query L1-L2 head")?;
-        let expected_next = l1_l2_head
-            .map(|head| head + 1)
-            .unwrap_or(StarknetBlockNumber::GENESIS);
+/// Applies the state update of `block` and stores the block, its transactions and state update
+/// as the new head of the chain.
+///
+/// Fails if the resulting state commitment does not match the block's.
+pub(crate) fn insert_block(
+    transaction: &Transaction<'_>,
+    block: Block,
+    tx_commitment: TransactionCommitment,
+    ev_commitment: EventCommitment,
+    state_update: StateUpdate,
+) -> anyhow::Result<StarknetBlock> {
+    use pathfinder_storage::CanonicalBlocksTable;
 
-        if expected_next == starknet_block.number {
-            let l1_root =
-                L1StateTable::get_state_commitment(&transaction, starknet_block.number.into())
-                    .context("Query L1 root")?;
-            if l1_root == Some(starknet_block.root) {
-                RefsTable::set_l1_l2_head(&transaction, Some(starknet_block.number))
-                    .context("Update L1-L2 head")?;
-            }
+    let (new_storage_commitment, new_class_commitment) =
+        update_starknet_state(transaction, &state_update).context("Updating Starknet state")?;
+    let new_root = StateCommitment::calculate(new_storage_commitment, new_class_commitment);
+
+    // Ensure that roots match.. what should we do if it doesn't? For now the whole sync process ends..
+    anyhow::ensure!(new_root == block.state_commitment, "State root mismatch");
+
+    // Update L2 database. These types shouldn't be options at this level,
+    // but for now the unwraps are "safe" in that these should only ever be
+    // None for pending queries to the sequencer, but we aren't using those here.
+    let starknet_block = StarknetBlock {
+        number: block.block_number,
+        hash: block.block_hash,
+        root: block.state_commitment,
+        timestamp: block.timestamp,
+        // Default value for cairo <0.8.2 is 0
+        gas_price: block.gas_price.unwrap_or(GasPrice::ZERO),
+        sequencer_address: block
+            .sequencer_address
+            .unwrap_or(SequencerAddress(Felt::ZERO)),
+        transaction_commitment: Some(tx_commitment),
+        event_commitment: Some(ev_commitment),
+    };
+    StarknetBlocksTable::insert(
+        transaction,
+        &starknet_block,
+        block.starknet_version.as_deref(),
+        new_storage_commitment,
+        new_class_commitment,
+    )
+    .context("Insert block into database")?;
+
+    TreePruningTable::prune(transaction, starknet_block.number)
+        .context("Pruning historical state")?;
+
+    let rpc_state_update = state_update.into();
+    StarknetStateUpdatesTable::insert(transaction, block.block_hash, &rpc_state_update)
+        .context("Insert state update into database")?;
+
+    CanonicalBlocksTable::insert(transaction, block.block_number, block.block_hash)
+        .context("Inserting canonical block into database")?;
+
+    NonceHistoryTable::insert(
+        transaction,
+        block.block_number,
+        &rpc_state_update.state_diff.nonces,
+    )
+    .context("Inserting nonce updates into database")?;
+
+    let declared_sierra_class_hashes = rpc_state_update
+        .state_diff
+        .declared_sierra_classes
+        .iter()
+        .map(|c| ClassHash(c.class_hash.0));
+    let declared_cairo_class_hashes = rpc_state_update
+        .state_diff
+        .declared_contracts
+        .iter()
+        .map(|c| c.class_hash);
+    let deployed_class_hashes = rpc_state_update
+        .state_diff
+        .deployed_contracts
+        .iter()
+        .map(|d| d.class_hash);
+    let declared_class_hashes = declared_sierra_class_hashes
+        .chain(declared_cairo_class_hashes)
+        .chain(deployed_class_hashes);
+    for class_hash in declared_class_hashes {
+        ContractCodeTable::update_declared_on_if_null(transaction, class_hash, block.block_hash)
+            .with_context(|| format!("Setting declared_on for class={:?}", class_hash))?;
+    }
+
+    // The compiled class hash is required to store the CASM of deferred Sierra classes.
+    for class in &rpc_state_update.state_diff.declared_sierra_classes {
+        let class_hash = ClassHash(class.class_hash.0);
+        DeferredClassesTable::set_compiled_class_hash(
+            transaction,
+            class_hash,
+            &class.compiled_class_hash,
+        )
+        .with_context(|| format!("Setting compiled class hash for class={:?}", class_hash))?;
+    }
+
+    // Insert the transactions.
+    anyhow::ensure!(
+        block.transactions.len() == block.transaction_receipts.len(),
+        "Transactions and receipts mismatch. There were {} transactions and {} receipts.",
+        block.transactions.len(),
+        block.transaction_receipts.len()
+    );
+    let transaction_data = block
+        .transactions
+        .into_iter()
+        .zip(block.transaction_receipts.into_iter())
+        .collect::<Vec<_>>();
+    StarknetTransactionsTable::upsert(
+        transaction,
+        starknet_block.hash,
+        starknet_block.number,
+        &transaction_data,
+    )
+    .context("Insert transaction data into database")?;
+
+    // Track combined L1 and L2 state.
+    let l1_l2_head = RefsTable::get_l1_l2_head(transaction).context("Query L1-L2 head")?;
+    let expected_next = l1_l2_head
+        .map(|head| head + 1)
+        .unwrap_or(StarknetBlockNumber::GENESIS);
+
+    if expected_next == starknet_block.number {
+        let l1_root = L1StateTable::get_state_commitment(transaction, starknet_block.number.into())
+            .context("Query L1 root")?;
+        if l1_root == Some(starknet_block.root) {
+            RefsTable::set_l1_l2_head(transaction, Some(starknet_block.number))
+                .context("Update L1-L2 head")?;
         }
+    }
 
-        transaction.commit().context("Commit database transaction")
-    })
+    Ok(starknet_block)
 }
 
 async fn l2_reorg(
     connection: &mut Connection,
+    header_cache: &BlockHeaderCache,
+    response_cache: &ResponseCache,
+    hooks: &hooks::ChainHooks,
     reorg_tail: StarknetBlockNumber,
 ) -> anyhow::Result<()> {
     use pathfinder_storage::CanonicalBlocksTable;
 
+    header_cache.reorg(reorg_tail);
+    // Stop serving the reorged responses while the reorg is written.
+    response_cache.reorg(reorg_tail);
+
     tokio::task::block_in_place(move || {
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
             .context("Create database transaction")?;
 
-        // TODO: clean up state tree's as well...
+        // The state of the new head is required to continue syncing from it, so this fails if it
+        // was pruned.
+        TreePruningTable::reorg(&transaction, reorg_tail)
+            .context("Release the state of reorged blocks")?;
+        record_reorg(&transaction, reorg_tail).context("Recording reorg")?;
+        hooks.reorg(&transaction, reorg_tail)?;
 
         CanonicalBlocksTable::reorg(&transaction, reorg_tail)
             .context("Delete canonical blocks from database")?;
@@ -669,11 +985,65 @@ async fn l2_reorg(
             _ => {}
         }
 
-        transaction.commit().context("Commit database transaction")
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
+
+        // Discard responses which were read from the database before the reorg was committed,
+        // but are only cached now.
+        response_cache.reorg(reorg_tail);
+
+        Ok(())
     })
 }
 
-fn update_starknet_state(
+/// Adds the reorg to the [ReorgHistoryTable] and counts it. Must be called before the reorged
+/// blocks are removed.
+fn record_reorg(
+    transaction: &Transaction<'_>,
+    reorg_tail: StarknetBlockNumber,
+) -> anyhow::Result<()> {
+    let old_head = StarknetBlocksTable::get(transaction, StarknetBlocksBlockId::Latest)
+        .context("Query L2 head")?;
+    let old_head = match old_head {
+        Some(head) if head.number >= reorg_tail => head,
+        // Nothing is being removed.
+        _ => return Ok(()),
+    };
+
+    let new_head = match reorg_tail {
+        StarknetBlockNumber::GENESIS => None,
+        other => StarknetBlocksTable::get(transaction, StarknetBlocksBlockId::Number(other - 1))
+            .context("Query new L2 head")?
+            .map(|block| (block.number, block.hash)),
+    };
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    let reorg = Reorg {
+        timestamp,
+        depth: old_head.number.get() - reorg_tail.get() + 1,
+        old_head_number: old_head.number,
+        old_head_hash: old_head.hash,
+        new_head,
+    };
+
+    ReorgHistoryTable::insert(transaction, &reorg)?;
+    MetricCountersTable::add(transaction, persisted_metrics::REORGS, 1)
+        .context("Counting reorg")?;
+    metrics::increment_counter!(persisted_metrics::REORGS);
+
+    Ok(())
+}
+
+/// Applies the state diff to the storage and class commitment trees of the latest block, and
+/// returns the new commitments.
+///
+/// If state is pruned, the trie roots which the new state supersedes are queued for pruning.
+pub fn update_starknet_state(
     transaction: &Transaction<'_>,
     state_update: &StateUpdate,
 ) -> anyhow::Result<(StorageCommitment, ClassCommitment)> {
@@ -682,6 +1052,15 @@ fn update_starknet_state(
             .context("Query latest state commitment")?
             .unwrap_or((StorageCommitment::ZERO, ClassCommitment::ZERO));
 
+    let pruning = TreePruningTable::get_retention(transaction)
+        .context("Query state retention")?
+        .is_some();
+    let mut superseded = vec![
+        (PrunedTree::StorageCommitment, storage_commitment.0),
+        (PrunedTree::ClassCommitment, class_commitment.0),
+    ];
+    let mut committed = Vec::new();
+
     let mut storage_commitment_tree = StorageCommitmentTree::load(transaction, storage_commitment)
         .context("Loading storage commitment tree")?;
 
@@ -707,6 +1086,12 @@ fn update_starknet_state(
         // Remove from replaced classes so we don't update it again in the next stage.
         let replaced_class_hash = replaced_classes.remove(contract_address);
 
+        if pruning && !updates.is_empty() {
+            let root = contract_root(transaction, &storage_commitment_tree, *contract_address)
+                .context("Query contract root")?;
+            superseded.push((PrunedTree::ContractStorage, root.0));
+        }
+
         let contract_state_hash = update_contract_state(
             *contract_address,
             updates,
@@ -721,6 +1106,16 @@ fn update_starknet_state(
         storage_commitment_tree
             .set(*contract_address, contract_state_hash)
             .context("Updating storage commitment tree")?;
+
+        if pruning && !updates.is_empty() {
+            let (root, _, _) = ContractsStateTable::get_root_class_hash_and_nonce(
+                transaction,
+                contract_state_hash,
+            )
+            .context("Query contract root")?
+            .context("Contract state is missing")?;
+            committed.push((PrunedTree::ContractStorage, root.0));
+        }
     }
 
     // Apply all remaining nonces (without storage updates).
@@ -793,9 +1188,45 @@ fn update_starknet_state(
         .apply()
         .context("Apply class commitment tree updates")?;
 
+    if pruning {
+        let block = StarknetBlocksTable::get_latest_number(transaction)
+            .context("Query latest block number")?
+            .map(|latest| latest + 1)
+            .unwrap_or(StarknetBlockNumber::GENESIS);
+        for (tree, root) in superseded {
+            TreePruningTable::supersede(transaction, block, tree, root)
+                .context("Queueing superseded root for pruning")?;
+        }
+
+        committed.push((PrunedTree::StorageCommitment, new_storage_commitment.0));
+        committed.push((PrunedTree::ClassCommitment, new_class_commitment.0));
+        for (tree, root) in committed {
+            TreePruningTable::commit(transaction, block, tree, root)
+                .context("Recording committed root")?;
+        }
+    }
+
     Ok((new_storage_commitment, new_class_commitment))
 }
 
+/// Returns the storage root of the contract in the state of the `storage_commitment_tree`.
+fn contract_root(
+    transaction: &Transaction<'_>,
+    storage_commitment_tree: &StorageCommitmentTree<'_, '_>,
+    contract_address: ContractAddress,
+) -> anyhow::Result<ContractRoot> {
+    let state_hash = match storage_commitment_tree.get(contract_address)? {
+        Some(state_hash) => state_hash,
+        None => return Ok(ContractRoot::ZERO),
+    };
+
+    let root = ContractsStateTable::get_root_class_hash_and_nonce(transaction, state_hash)?
+        .map(|(root, _, _)| root)
+        .unwrap_or(ContractRoot::ZERO);
+
+    Ok(root)
+}
+
 fn deploy_contract(
     transaction: &Transaction<'_>,
     storage_commitment_tree: &mut StorageCommitmentTree<'_, '_>,
@@ -828,7 +1259,6 @@ async fn download_verify_and_insert_missing_classes<SequencerClient: ClientApi>(
     sequencer: SequencerClient,
     connection: &mut Connection,
     state_update: &PendingStateUpdate,
-    chain: Chain,
 ) -> anyhow::Result<()> {
     let deployed_classes = state_update
         .state_diff
@@ -881,7 +1311,7 @@ async fn download_verify_and_insert_missing_classes<SequencerClient: ClientApi>(
 
     // For each missing, download, verify and insert definition.
     for class_hash in missing {
-        let class = download_class(&sequencer, class_hash, chain).await?;
+        let class = download_class(&sequencer, class_hash).await?;
 
         match class {
             DownloadedClass::Cairo(class) => {
@@ -894,7 +1324,7 @@ async fn download_verify_and_insert_missing_classes<SequencerClient: ClientApi>(
                 })
                 .with_context(|| format!("Insert class definition with hash: {:?}", class.hash))?;
             }
-            DownloadedClass::Sierra(sierra, casm) => {
+            DownloadedClass::Sierra(sierra, casm, compilation_error) => {
                 // NOTE: we _have_ to use the same compiled_class_class hash as returned by the feeder gateway,
                 // since that's what has been added to the class commitment tree.
                 let compiled_class_hash = state_update
@@ -919,6 +1349,14 @@ async fn download_verify_and_insert_missing_classes<SequencerClient: ClientApi>(
                         &compiled_class_hash,
                         crate::sierra::COMPILER_VERSION,
                     )?;
+                    if let Some(error) = &compilation_error {
+                        CasmCompilationFailuresTable::upsert(
+                            &transaction,
+                            class_hash,
+                            crate::sierra::COMPILER_VERSION,
+                            error,
+                        )?;
+                    }
                     transaction.commit()?;
                     anyhow::Result::<()>::Ok(())
                 })
@@ -932,30 +1370,21 @@ async fn download_verify_and_insert_missing_classes<SequencerClient: ClientApi>(
     Ok(())
 }
 
-enum DownloadedClass {
+pub(crate) enum DownloadedClass {
     Cairo(CompressedContract),
-    Sierra(CompressedContract, CompressedCasmClass),
+    Sierra(CompressedContract, CompressedCasmClass, Option<String>),
 }
 
 async fn download_class<SequencerClient: ClientApi>(
     sequencer: &SequencerClient,
     class_hash: ClassHash,
-    chain: Chain,
 ) -> Result<DownloadedClass, anyhow::Error> {
-    use starknet_gateway_types::class_hash::compute_class_hash;
-
     let definition = sequencer
         .pending_class_by_hash(class_hash)
         .await
         .with_context(|| format!("Downloading class {}", class_hash.0))?;
 
-    let extract = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
-        let hash = compute_class_hash(&definition)?;
-        Ok((definition, hash))
-    });
-    let (definition, hash) = extract
-        .await
-        .context("Parse class definition and compute hash")??;
+    let (definition, hash) = compute_class_hash(definition).await?;
 
     anyhow::ensure!(
         class_hash == hash.hash(),
@@ -964,6 +1393,28 @@ async fn download_class<SequencerClient: ClientApi>(
         class_hash.0
     );
 
+    prepare_class(definition, hash).await
+}
+
+/// Computes the hash of a class definition as served by the gateway.
+pub(crate) async fn compute_class_hash<D: AsRef<[u8]> + Send + 'static>(
+    definition: D,
+) -> anyhow::Result<(D, starknet_gateway_types::class_hash::ComputedClassHash)> {
+    let extract = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let hash = starknet_gateway_types::class_hash::compute_class_hash(definition.as_ref())?;
+        Ok((definition, hash))
+    });
+    extract
+        .await
+        .context("Parse class definition and compute hash")?
+}
+
+/// Compresses a class definition whose `hash` has been verified, compiling Sierra classes to
+/// CASM, so that it is ready to be stored.
+pub(crate) async fn prepare_class<D: AsRef<[u8]> + Send + 'static>(
+    definition: D,
+    hash: starknet_gateway_types::class_hash::ComputedClassHash,
+) -> anyhow::Result<DownloadedClass> {
     match hash {
         starknet_gateway_types::class_hash::ComputedClassHash::Cairo(hash) => {
             let compress = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
@@ -971,7 +1422,7 @@ async fn download_class<SequencerClient: ClientApi>(
                     zstd::bulk::Compressor::new(10).context("Create zstd compressor")?;
 
                 let definition = compressor
-                    .compress(&definition)
+                    .compress(definition.as_ref())
                     .context("Compress definition")?;
 
                 Ok(definition)
@@ -986,29 +1437,15 @@ async fn download_class<SequencerClient: ClientApi>(
             ))
         }
         starknet_gateway_types::class_hash::ComputedClassHash::Sierra(hash) => {
-            // FIXME(integration reset): work-around for integration containing Sierra classes
-            // that are incompatible with production compiler. This will get "fixed" in the future
-            // by resetting integration to remove these classes at which point we can revert this.
-            //
-            // The work-around ignores compilation errors on integration, and instead replaces the
-            // casm definition with empty bytes.
-            let casm_definition =
-                crate::sierra::compile_to_casm(&definition).context("Compiling Sierra class");
-            let casm_definition = match (casm_definition, chain) {
-                (Ok(casm_definition), _) => casm_definition,
-                (Err(_), Chain::Integration) => {
-                    tracing::info!(class_hash=%hash, "Ignored CASM compilation failure integration network");
-                    Vec::new()
-                }
-                (Err(e), _) => return Err(e),
-            };
+            let (casm_definition, compilation_error) =
+                crate::sierra::compile_to_casm_or_empty(definition.as_ref(), hash).await?;
 
             let compress = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
                 let mut compressor =
                     zstd::bulk::Compressor::new(10).context("Create zstd compressor")?;
 
                 let definition = compressor
-                    .compress(&definition)
+                    .compress(definition.as_ref())
                     .context("Compress definition")?;
 
                 let casm_definition = compressor
@@ -1029,6 +1466,7 @@ async fn download_class<SequencerClient: ClientApi>(
                     definition: compressed_casm_definition,
                     hash,
                 },
+                compilation_error,
             ))
         }
     }
@@ -1054,34 +1492,35 @@ pub fn head_poll_interval(chain: Chain) -> std::time::Duration {
 
 #[cfg(test)]
 mod tests {
-    use super::{l1, l2};
+    use super::{checkpoint, l1, l2};
     use crate::state;
     use ethers::types::H256;
     use futures::stream::{StreamExt, TryStreamExt};
     use pathfinder_common::{
         BlockId, CallParam, CasmHash, Chain, ClassCommitment, ClassHash, ContractAddress,
-        ContractAddressSalt, EntryPoint, EthereumBlockHash, EthereumBlockNumber, EthereumChain,
-        EthereumLogIndex, EthereumTransactionHash, EthereumTransactionIndex, Fee, GasPrice,
-        SequencerAddress, SierraHash, StarknetBlockHash, StarknetBlockNumber,
+        ContractAddressSalt, ContractNonce, EntryPoint, EthereumBlockHash, EthereumBlockNumber,
+        EthereumChain, EthereumLogIndex, EthereumTransactionHash, EthereumTransactionIndex, Fee,
+        GasPrice, SequencerAddress, SierraHash, StarknetBlockHash, StarknetBlockNumber,
         StarknetBlockTimestamp, StarknetTransactionHash, StateCommitment, StorageAddress,
         StorageCommitment, StorageValue, TransactionNonce, TransactionSignatureElem,
         TransactionVersion,
     };
-    use pathfinder_rpc::SyncState;
     use pathfinder_storage::{
         types::{CompressedCasmClass, CompressedContract},
-        CasmClassTable, ContractCodeTable, L1StateTable, L1TableBlockId, RefsTable, StarknetBlock,
-        StarknetBlocksBlockId, StarknetBlocksTable, Storage,
+        CasmClassTable, ContractCodeTable, DeferredClassesTable, L1StateTable, L1TableBlockId,
+        RefsTable, ReorgHistoryTable, StarknetBlock, StarknetBlocksBlockId, StarknetBlocksTable,
+        Storage,
     };
     use stark_hash::Felt;
-    use starknet_gateway_client::ClientApi;
+    use starknet_gateway_client::{ClientApi, RateLimit};
+    use starknet_gateway_types::sync_state::SyncState;
     use starknet_gateway_types::{
         error::SequencerError,
         pending::PendingData,
         reply,
         request::{add_transaction::ContractDefinition, BlockHashOrTag},
     };
-    use std::{sync::Arc, time::Duration};
+    use std::{num::NonZeroUsize, sync::Arc, time::Duration};
     use tokio::sync::mpsc;
 
     #[derive(Debug, Clone)]
@@ -1163,6 +1602,22 @@ mod tests {
             unimplemented!()
         }
 
+        async fn nonce(
+            &self,
+            _: ContractAddress,
+            _: BlockHashOrTag,
+        ) -> Result<ContractNonce, SequencerError> {
+            unimplemented!()
+        }
+
+        async fn class_hash_at(
+            &self,
+            _: ContractAddress,
+            _: BlockHashOrTag,
+        ) -> Result<ClassHash, SequencerError> {
+            unimplemented!()
+        }
+
         async fn transaction(
             &self,
             _: StarknetTransactionHash,
@@ -1183,6 +1638,10 @@ mod tests {
             unimplemented!()
         }
 
+        async fn block_traces(&self, _: BlockId) -> Result<bytes::Bytes, SequencerError> {
+            unimplemented!()
+        }
+
         async fn add_invoke_transaction(
             &self,
             _: TransactionVersion,
@@ -1243,6 +1702,8 @@ mod tests {
         _: Chain,
         _: Option<std::time::Duration>,
         _: l2::BlockValidationMode,
+        _: RateLimit,
+        _: l2::Prefetch,
     ) -> anyhow::Result<()> {
         // Avoid being restarted all the time by the outer sync() loop
         std::future::pending::<()>().await;
@@ -1394,6 +1855,11 @@ mod tests {
                 PendingData::default(),
                 None,
                 l2::BlockValidationMode::Strict,
+                Vec::new(),
+                false,
+                Default::default(),
+                Default::default(),
+                NonZeroUsize::new(1).unwrap(),
             ));
 
             // TODO Find a better way to figure out that the DB update has already been performed
@@ -1500,6 +1966,11 @@ mod tests {
                 PendingData::default(),
                 None,
                 l2::BlockValidationMode::Strict,
+                Vec::new(),
+                false,
+                Default::default(),
+                Default::default(),
+                NonZeroUsize::new(1).unwrap(),
             ));
 
             // TODO Find a better way to figure out that the DB update has already been performed
@@ -1511,7 +1982,12 @@ mod tests {
                 .unwrap()
                 .map(|s| s.block_number);
             let head = RefsTable::get_l1_l2_head(&tx).unwrap();
-            (head, latest_block_number)
+            let reorg_depths = ReorgHistoryTable::get_latest(&tx, 10)
+                .unwrap()
+                .into_iter()
+                .map(|reorg| reorg.depth)
+                .collect::<Vec<_>>();
+            (head, latest_block_number, reorg_depths)
         })
         .collect::<futures::stream::FuturesOrdered<_>>()
         .collect::<Vec<_>>()
@@ -1521,11 +1997,12 @@ mod tests {
             results,
             vec![
                 // Case 0: no L1-L2 head expected, as we start from genesis
-                (None, None),
+                (None, None, vec![1]),
                 // Case 1: some L1-L2 head expected, block #1 removed
                 (
                     Some(StarknetBlockNumber::GENESIS),
-                    Some(StarknetBlockNumber::GENESIS)
+                    Some(StarknetBlockNumber::GENESIS),
+                    vec![1]
                 ),
             ]
         );
@@ -1571,11 +2048,44 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
 
         tokio::time::sleep(Duration::from_millis(10)).await;
     }
 
+    #[tokio::test]
+    async fn heartbeat() {
+        let state = Arc::new(SyncState::default());
+        assert_eq!(state.since_heartbeat(), None);
+
+        let _jh = tokio::spawn(state::sync(
+            Storage::in_memory().unwrap(),
+            FakeTransport,
+            Chain::Testnet,
+            pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+            FakeSequencer,
+            state.clone(),
+            l1_noop,
+            l2_noop,
+            PendingData::default(),
+            None,
+            l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(state.since_heartbeat().unwrap() < super::HEARTBEAT_INTERVAL);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn l1_restart() -> Result<(), anyhow::Error> {
         use anyhow::Context;
@@ -1608,6 +2118,11 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
 
         let timeout = std::time::Duration::from_secs(1);
@@ -1640,7 +2155,7 @@ mod tests {
         };
 
         // A simple L2 sync task
-        let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+        let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
             tx.send(l2::Event::Update(
                 (Box::new(block()), Default::default()),
                 Box::new(state_update()),
@@ -1683,6 +2198,11 @@ mod tests {
                 PendingData::default(),
                 None,
                 l2::BlockValidationMode::Strict,
+                Vec::new(),
+                false,
+                Default::default(),
+                Default::default(),
+                NonZeroUsize::new(1).unwrap(),
             ));
 
             // TODO Find a better way to figure out that the DB update has already been performed
@@ -1707,6 +2227,61 @@ mod tests {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn l2_update_conflicting_with_checkpoint() {
+        let storage = Storage::in_memory().unwrap();
+        let timings = l2::Timings {
+            block_download: Duration::default(),
+            state_diff_download: Duration::default(),
+            class_declaration: Duration::default(),
+        };
+
+        let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
+            tx.send(l2::Event::Update(
+                (Box::new(BLOCK0.clone()), Default::default()),
+                Box::new(STATE_UPDATE0.clone()),
+                timings,
+            ))
+            .await
+            .unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        };
+
+        let checkpoint = checkpoint::Checkpoint {
+            block_number: StarknetBlockNumber::GENESIS,
+            block_hash: StarknetBlockHash(*B),
+            state_commitment: STATE_UPDATE0.new_root,
+        };
+
+        // UUT
+        state::sync(
+            storage.clone(),
+            FakeTransport,
+            Chain::Testnet,
+            pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+            FakeSequencer,
+            Arc::new(SyncState::default()),
+            l1_noop,
+            l2,
+            PendingData::default(),
+            None,
+            l2::BlockValidationMode::Strict,
+            vec![checkpoint],
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
+        )
+        .await
+        .unwrap_err();
+
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        let latest = StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest).unwrap();
+        assert_eq!(latest, None);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn l2_reorg() {
         let results = [
@@ -1743,7 +2318,7 @@ mod tests {
             let tx = connection.transaction().unwrap();
 
             // A simple L2 sync task
-            let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+            let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
                 tx.send(l2::Event::Reorg(StarknetBlockNumber::new_or_panic(
                     reorg_on_block,
                 )))
@@ -1783,6 +2358,11 @@ mod tests {
                 PendingData::default(),
                 None,
                 l2::BlockValidationMode::Strict,
+                Vec::new(),
+                false,
+                Default::default(),
+                Default::default(),
+                NonZeroUsize::new(1).unwrap(),
             ));
 
             // TODO Find a better way to figure out that the DB update has already been performed
@@ -1813,13 +2393,111 @@ mod tests {
         );
     }
 
+    /// Maintains a log of the changes to the chain in its own table.
+    struct LogHook;
+
+    impl super::hooks::ChainHook for LogHook {
+        fn name(&self) -> &str {
+            "log"
+        }
+
+        fn block_applied(
+            &self,
+            transaction: &rusqlite::Transaction<'_>,
+            block: &reply::Block,
+            _: &reply::StateUpdate,
+        ) -> anyhow::Result<()> {
+            let event = format!("applied {}", block.block_number);
+            transaction.execute("INSERT INTO hook_log (event) VALUES (?)", [event])?;
+            Ok(())
+        }
+
+        fn reorg(
+            &self,
+            transaction: &rusqlite::Transaction<'_>,
+            reorg_tail: StarknetBlockNumber,
+        ) -> anyhow::Result<()> {
+            // The reorged blocks must still be stored.
+            let stored = StarknetBlocksTable::get(transaction, reorg_tail.into())?.is_some();
+            let event = format!("reorg {reorg_tail} stored={stored}");
+            transaction.execute("INSERT INTO hook_log (event) VALUES (?)", [event])?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn chain_hooks() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        connection
+            .execute("CREATE TABLE hook_log (event TEXT NOT NULL)", [])
+            .unwrap();
+
+        let timings = l2::Timings {
+            block_download: Duration::default(),
+            state_diff_download: Duration::default(),
+            class_declaration: Duration::default(),
+        };
+        let l2 = move |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
+            tx.send(l2::Event::Update(
+                (Box::new(BLOCK0.clone()), Default::default()),
+                Box::new(STATE_UPDATE0.clone()),
+                timings,
+            ))
+            .await
+            .unwrap();
+            tx.send(l2::Event::Reorg(StarknetBlockNumber::GENESIS))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok(())
+        };
+
+        let mut hooks = super::hooks::ChainHooks::default();
+        hooks.register(Arc::new(LogHook));
+
+        // UUT
+        let _jh = tokio::spawn(state::sync(
+            storage.clone(),
+            FakeTransport,
+            Chain::Testnet,
+            pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+            FakeSequencer,
+            Arc::new(SyncState::default()),
+            l1_noop,
+            l2,
+            PendingData::default(),
+            None,
+            l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            hooks,
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        // TODO Find a better way to figure out that the DB update has already been performed
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let tx = connection.transaction().unwrap();
+        let mut statement = tx
+            .prepare("SELECT event FROM hook_log ORDER BY rowid")
+            .unwrap();
+        let log = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(log, vec!["applied 0", "reorg 0 stored=true"]);
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn l2_new_cairo_contract() {
         let storage = Storage::in_memory().unwrap();
         let connection = storage.connection().unwrap();
 
         // A simple L2 sync task
-        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
             let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
             tx.send(l2::Event::NewCairoContract(CompressedContract {
                 definition: zstd_magic,
@@ -1845,6 +2523,11 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
 
         // TODO Find a better way to figure out that the DB update has already been performed
@@ -1862,7 +2545,7 @@ mod tests {
         let connection = storage.connection().unwrap();
 
         // A simple L2 sync task
-        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
             let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
             tx.send(l2::Event::NewSierraContract(
                 CompressedContract {
@@ -1895,6 +2578,11 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
 
         // TODO Find a better way to figure out that the DB update has already been performed
@@ -1927,7 +2615,7 @@ mod tests {
         .unwrap();
 
         // A simple L2 sync task which does the request and checks he result
-        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
             let (tx1, rx1) = tokio::sync::oneshot::channel();
 
             tx.send(l2::Event::QueryBlock(StarknetBlockNumber::GENESIS, tx1))
@@ -1955,6 +2643,11 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
     }
 
@@ -1975,7 +2668,7 @@ mod tests {
         .unwrap();
 
         // A simple L2 sync task which does the request and checks he result
-        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _| async move {
+        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
             let (tx1, rx1) = tokio::sync::oneshot::channel::<Vec<bool>>();
 
             tx.send(l2::Event::QueryContractExistance(vec![ClassHash(*A)], tx1))
@@ -2002,9 +2695,86 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn l2_query_contract_existance_lazy() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+        let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
+
+        ContractCodeTable::insert_compressed(
+            &connection,
+            &CompressedContract {
+                definition: zstd_magic,
+                hash: ClassHash(*A),
+            },
+        )
+        .unwrap();
+
+        // Missing classes are reported as existing, as their download is deferred
+        let l2 = |tx: mpsc::Sender<l2::Event>, _, _, _, _, _, _, _| async move {
+            let (tx1, rx1) = tokio::sync::oneshot::channel::<Vec<bool>>();
+
+            tx.send(l2::Event::QueryContractExistance(
+                vec![ClassHash(*A), ClassHash(*B)],
+                tx1,
+            ))
+            .await
+            .unwrap();
+
+            assert_eq!(rx1.await.unwrap(), vec![true, true]);
+
+            futures::future::pending::<()>().await;
+            Ok(())
+        };
+
+        // UUT
+        let _jh = tokio::spawn(state::sync(
+            storage,
+            FakeTransport,
+            Chain::Testnet,
+            pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+            FakeSequencer,
+            Arc::new(SyncState::default()),
+            l1_noop,
+            l2,
+            PendingData::default(),
+            None,
+            l2::BlockValidationMode::Strict,
+            Vec::new(),
+            true,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        let compiled_class_hash = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(Some(compiled_class_hash)) =
+                    DeferredClassesTable::get(&connection, ClassHash(*B))
+                {
+                    break compiled_class_hash;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(compiled_class_hash, None);
+        assert_eq!(
+            DeferredClassesTable::get(&connection, ClassHash(*A)).unwrap(),
+            None
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn l2_restart() {
         use std::sync::atomic::{AtomicUsize, Ordering};
@@ -2014,7 +2784,7 @@ mod tests {
         static CNT: AtomicUsize = AtomicUsize::new(0);
 
         // A simple L2 sync task
-        let l2 = move |_, _, _, _, _, _| async move {
+        let l2 = move |_, _, _, _, _, _, _, _| async move {
             CNT.fetch_add(1, Ordering::Relaxed);
             Ok(())
         };
@@ -2032,10 +2802,363 @@ mod tests {
             PendingData::default(),
             None,
             l2::BlockValidationMode::Strict,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
         ));
 
         tokio::time::sleep(Duration::from_millis(5)).await;
 
         assert!(CNT.load(Ordering::Relaxed) > 1);
     }
+
+    #[test]
+    fn timestamp_skew() {
+        use super::{check_timestamp_skew, TimestampSkew};
+
+        let head = StarknetBlockNumber::new_or_panic(10);
+        let timestamp = StarknetBlockTimestamp::new_or_panic(1_000_000);
+        let at = |secs: u64| std::time::UNIX_EPOCH + Duration::from_secs(secs);
+
+        let check = |now, previous| {
+            check_timestamp_skew(Chain::Testnet, head, timestamp, at(now), previous)
+        };
+
+        assert_eq!(check(1_000_120, TimestampSkew::Ok), TimestampSkew::Ok);
+        assert_eq!(
+            check(1_000_000 + 60 * 31, TimestampSkew::Ok),
+            TimestampSkew::Stalled
+        );
+        assert_eq!(
+            check(999_000, TimestampSkew::Ok),
+            TimestampSkew::ClockBehind
+        );
+        // Small clock differences are tolerated.
+        assert_eq!(
+            check(999_950, TimestampSkew::ClockBehind),
+            TimestampSkew::Ok
+        );
+
+        // Mainnet blocks are further apart.
+        assert_eq!(
+            check_timestamp_skew(
+                Chain::Mainnet,
+                head,
+                timestamp,
+                at(1_000_000 + 60 * 31),
+                TimestampSkew::Ok
+            ),
+            TimestampSkew::Ok
+        );
+    }
+
+    /// Syncs against a [MockGateway](starknet_gateway_client::test_utils::MockGateway), through
+    /// a gateway error and a reorg.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn scripted_chain() {
+        use starknet_gateway_client::test_utils::{InjectedError, MockGateway, ScriptedChain};
+
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(3);
+        // A failed request must not stop sync.
+        chain.inject_error("get_block", InjectedError::Status(503));
+        let gateway = MockGateway::spawn(chain);
+
+        let storage = Storage::in_memory().unwrap();
+
+        // UUT
+        let _jh = tokio::spawn(state::sync(
+            storage.clone(),
+            FakeTransport,
+            Chain::Testnet,
+            pathfinder_ethereum::contract::TESTNET_ADDRESSES.core,
+            gateway.client(),
+            Arc::new(SyncState::default()),
+            l1_noop,
+            l2::sync,
+            PendingData::default(),
+            Some(Duration::from_millis(10)),
+            l2::BlockValidationMode::AllowMismatch,
+            Vec::new(),
+            false,
+            Default::default(),
+            Default::default(),
+            NonZeroUsize::new(1).unwrap(),
+        ));
+
+        /// Waits until the latest block in storage is the head of the gateway's chain, and
+        /// returns the stored block hashes.
+        async fn synced(storage: &Storage, gateway: &MockGateway) -> Vec<StarknetBlockHash> {
+            let head = gateway.update(|chain| chain.head().unwrap().block_hash);
+            let mut connection = storage.connection().unwrap();
+
+            tokio::time::timeout(Duration::from_secs(10), async {
+                loop {
+                    let tx = connection.transaction().unwrap();
+                    let latest = StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest)
+                        .unwrap()
+                        .map(|block| block.hash);
+                    if latest == Some(head) {
+                        return (0..)
+                            .map_while(|number| {
+                                StarknetBlocksTable::get(
+                                    &tx,
+                                    StarknetBlockNumber::new_or_panic(number).into(),
+                                )
+                                .unwrap()
+                            })
+                            .map(|block| block.hash)
+                            .collect();
+                    }
+                    drop(tx);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("Sync should reach the head of the chain")
+        }
+
+        let expected = |gateway: &MockGateway| {
+            gateway.update(|chain| {
+                (0..)
+                    .map_while(|number| chain.block(StarknetBlockNumber::new_or_panic(number)))
+                    .map(|block| block.block_hash)
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(synced(&storage, &gateway).await, expected(&gateway));
+
+        gateway.update(|chain| {
+            chain.reorg(StarknetBlockNumber::new_or_panic(1));
+            chain.push_empty_blocks(3);
+        });
+
+        assert_eq!(synced(&storage, &gateway).await, expected(&gateway));
+    }
+
+    /// Syncs storage updates to a contract with pruning enabled, and checks which states are
+    /// retained, pruned and released by a reorg.
+    #[test]
+    fn state_pruning() {
+        use pathfinder_common::felt;
+        use pathfinder_merkle_tree::state_tree::{ContractsStateTree, StorageCommitmentTree};
+        use pathfinder_storage::{ContractsStateTable, TreePruningTable};
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        TreePruningTable::set_retention(&tx, Some(1)).unwrap();
+
+        let contract = ContractAddress::new_or_panic(felt!("0x123"));
+        let block = StarknetBlockNumber::new_or_panic;
+
+        // Each block updates the same storage slot, committing new roots of both the storage
+        // commitment and the contract's storage.
+        let mut roots = Vec::new();
+        for number in 0..5 {
+            let mut storage_diffs = std::collections::HashMap::new();
+            storage_diffs.insert(
+                contract,
+                vec![reply::state_update::StorageDiff {
+                    key: StorageAddress::new_or_panic(felt!("0x1")),
+                    value: StorageValue(Felt::from(number + 1)),
+                }],
+            );
+            let deployed_contracts = match number {
+                0 => vec![reply::state_update::DeployedContract {
+                    address: contract,
+                    class_hash: ClassHash(felt!("0xc1a55")),
+                }],
+                _ => vec![],
+            };
+            let state_update = reply::StateUpdate {
+                block_hash: StarknetBlockHash(Felt::from(number + 1)),
+                new_root: StateCommitment(Felt::ZERO),
+                old_root: StateCommitment(Felt::ZERO),
+                state_diff: reply::state_update::StateDiff {
+                    storage_diffs,
+                    deployed_contracts,
+                    old_declared_contracts: vec![],
+                    nonces: std::collections::HashMap::new(),
+                    declared_classes: vec![],
+                    replaced_classes: vec![],
+                },
+            };
+
+            let (storage_commitment, class_commitment) =
+                super::update_starknet_state(&tx, &state_update).unwrap();
+            let starknet_block = StarknetBlock {
+                number: block(number),
+                hash: state_update.block_hash,
+                root: StateCommitment::calculate(storage_commitment, class_commitment),
+                timestamp: StarknetBlockTimestamp::new_or_panic(number),
+                gas_price: GasPrice::ZERO,
+                sequencer_address: SequencerAddress(Felt::ZERO),
+                transaction_commitment: None,
+                event_commitment: None,
+            };
+            StarknetBlocksTable::insert(
+                &tx,
+                &starknet_block,
+                None,
+                storage_commitment,
+                class_commitment,
+            )
+            .unwrap();
+            TreePruningTable::prune(&tx, block(number)).unwrap();
+
+            let state_hash = StorageCommitmentTree::load(&tx, storage_commitment)
+                .unwrap()
+                .get(contract)
+                .unwrap()
+                .unwrap();
+            let (contract_root, _, _) =
+                ContractsStateTable::get_root_class_hash_and_nonce(&tx, state_hash)
+                    .unwrap()
+                    .unwrap();
+            roots.push((storage_commitment, contract_root));
+        }
+
+        let loads = |number: usize| {
+            let (storage_commitment, contract_root) = roots[number];
+            let storage_commitment = StorageCommitmentTree::load(&tx, storage_commitment).is_ok();
+            let contract_root = ContractsStateTree::load(&tx, contract_root).is_ok();
+            assert_eq!(storage_commitment, contract_root, "block {number}");
+            storage_commitment
+        };
+        let is_pruned = |number| TreePruningTable::is_pruned(&tx, block(number).into()).unwrap();
+
+        // Block 4 is the latest, so that the state of blocks 3 and 4 is retained.
+        for number in 0..3 {
+            assert!(!loads(number), "block {number}");
+            assert!(is_pruned(number as u64), "block {number}");
+        }
+        for number in 3..5 {
+            assert!(loads(number), "block {number}");
+            assert!(!is_pruned(number as u64), "block {number}");
+        }
+        let storage_value = ContractsStateTree::load(&tx, roots[3].1)
+            .unwrap()
+            .get(StorageAddress::new_or_panic(felt!("0x1")))
+            .unwrap();
+        assert_eq!(storage_value, Some(StorageValue(Felt::from(4u64))));
+
+        // The state of block 2 is required to reorg to it, but was pruned.
+        TreePruningTable::reorg(&tx, block(3)).unwrap_err();
+
+        // Reorging block 4 away releases its state, and keeps the state of the new head.
+        TreePruningTable::reorg(&tx, block(4)).unwrap();
+        StarknetBlocksTable::reorg(&tx, block(4)).unwrap();
+        assert!(loads(3));
+        assert!(!loads(4));
+    }
+
+    /// Syncs storage updates with pruning enabled, and checks that the trie nodes are served from
+    /// the process-wide cache, and only evicted from it once their deletion is committed.
+    #[test]
+    fn state_update_trie_node_cache() {
+        use pathfinder_common::felt;
+        use pathfinder_merkle_tree::state_tree::StorageCommitmentTree;
+        use pathfinder_storage::{TreePruningTable, TrieNodeCache};
+
+        TrieNodeCache::configure(TrieNodeCache::DEFAULT_CAPACITY);
+
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        TreePruningTable::set_retention(&tx, Some(1)).unwrap();
+        tx.commit().unwrap();
+
+        // Not shared with the other tests, whose tries would otherwise be served from the cache.
+        let contract = ContractAddress::new_or_panic(felt!("0x7c4c4e"));
+        let block = StarknetBlockNumber::new_or_panic;
+
+        let apply = |tx: &rusqlite::Transaction<'_>, number: u64| {
+            let state_update = reply::StateUpdate {
+                block_hash: StarknetBlockHash(Felt::from(number + 1)),
+                new_root: StateCommitment(Felt::ZERO),
+                old_root: StateCommitment(Felt::ZERO),
+                state_diff: reply::state_update::StateDiff {
+                    storage_diffs: [(
+                        contract,
+                        vec![reply::state_update::StorageDiff {
+                            key: StorageAddress::new_or_panic(felt!("0x1")),
+                            value: StorageValue(Felt::from(number + 0x7c4c4e)),
+                        }],
+                    )]
+                    .into(),
+                    deployed_contracts: match number {
+                        0 => vec![reply::state_update::DeployedContract {
+                            address: contract,
+                            class_hash: ClassHash(felt!("0xc1a55")),
+                        }],
+                        _ => vec![],
+                    },
+                    old_declared_contracts: vec![],
+                    nonces: std::collections::HashMap::new(),
+                    declared_classes: vec![],
+                    replaced_classes: vec![],
+                },
+            };
+
+            let (storage_commitment, class_commitment) =
+                super::update_starknet_state(tx, &state_update).unwrap();
+            let starknet_block = StarknetBlock {
+                number: block(number),
+                hash: state_update.block_hash,
+                root: StateCommitment::calculate(storage_commitment, class_commitment),
+                timestamp: StarknetBlockTimestamp::new_or_panic(number),
+                gas_price: GasPrice::ZERO,
+                sequencer_address: SequencerAddress(Felt::ZERO),
+                transaction_commitment: None,
+                event_commitment: None,
+            };
+            StarknetBlocksTable::insert(
+                tx,
+                &starknet_block,
+                None,
+                storage_commitment,
+                class_commitment,
+            )
+            .unwrap();
+            TreePruningTable::prune(tx, block(number)).unwrap();
+            storage_commitment
+        };
+
+        let tx = connection.transaction().unwrap();
+        let first_root = apply(&tx, 0);
+        tx.commit().unwrap();
+        assert!(!TrieNodeCache::contains("tree_global", first_root.0));
+
+        // Updating the state reads the root of the previous block, which is then cached.
+        let tx = connection.transaction().unwrap();
+        apply(&tx, 1);
+        tx.commit().unwrap();
+        assert!(TrieNodeCache::contains("tree_global", first_root.0));
+
+        // And served from the cache, even if the database no longer has it.
+        let tx = connection.transaction().unwrap();
+        tx.execute(
+            "DELETE FROM tree_global WHERE hash = ?",
+            [&first_root.0.as_be_bytes()[..]],
+        )
+        .unwrap();
+        let state_hash = StorageCommitmentTree::load(&tx, first_root)
+            .unwrap()
+            .get(contract)
+            .unwrap();
+        assert!(state_hash.is_some());
+        tx.rollback().unwrap();
+
+        // Pruning the first block deletes its root, which stays cached until that is committed.
+        let tx = connection.transaction().unwrap();
+        apply(&tx, 2);
+        assert!(TreePruningTable::is_pruned(&tx, block(0).into()).unwrap());
+        assert!(TrieNodeCache::contains("tree_global", first_root.0));
+        tx.commit().unwrap();
+        assert!(!TrieNodeCache::contains("tree_global", first_root.0));
+    }
 }
@@ -0,0 +1,169 @@
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber, StateCommitment};
+use stark_hash::Felt;
+
+/// A trusted block which the synced chain must pass through.
+///
+/// Sync refuses to store a block which conflicts with a checkpoint, and to
+/// reorg away a checkpointed block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub block_number: StarknetBlockNumber,
+    pub block_hash: StarknetBlockHash,
+    pub state_commitment: StateCommitment,
+}
+
+impl std::str::FromStr for Checkpoint {
+    type Err = anyhow::Error;
+
+    /// Parses `<block number>:<block hash>:<state commitment>`, with both hashes in hex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let (number, hash, commitment) =
+            match (parts.next(), parts.next(), parts.next(), parts.next()) {
+                (Some(number), Some(hash), Some(commitment), None) => (number, hash, commitment),
+                _ => anyhow::bail!(
+                    "Expected <block number>:<block hash>:<state commitment>, got {s:?}"
+                ),
+            };
+
+        let block_number = number
+            .parse::<u64>()
+            .ok()
+            .and_then(StarknetBlockNumber::new)
+            .with_context(|| format!("Invalid block number {number:?}"))?;
+        let block_hash =
+            Felt::from_hex_str(hash).with_context(|| format!("Invalid block hash {hash:?}"))?;
+        let state_commitment = Felt::from_hex_str(commitment)
+            .with_context(|| format!("Invalid state commitment {commitment:?}"))?;
+
+        Ok(Self {
+            block_number,
+            block_hash: StarknetBlockHash(block_hash),
+            state_commitment: StateCommitment(state_commitment),
+        })
+    }
+}
+
+/// Fails if the block conflicts with the checkpoint at its height.
+pub fn verify_block(
+    checkpoints: &[Checkpoint],
+    block_number: StarknetBlockNumber,
+    block_hash: StarknetBlockHash,
+    state_commitment: StateCommitment,
+) -> anyhow::Result<()> {
+    let checkpoint = match checkpoints.iter().find(|c| c.block_number == block_number) {
+        Some(checkpoint) => checkpoint,
+        None => return Ok(()),
+    };
+
+    anyhow::ensure!(
+        checkpoint.block_hash == block_hash && checkpoint.state_commitment == state_commitment,
+        "Block {block_number} conflicts with its checkpoint: hash {} and state commitment {} \
+        instead of {} and {}. Refusing to follow a gateway serving a conflicting history.",
+        block_hash.0,
+        state_commitment.0,
+        checkpoint.block_hash.0,
+        checkpoint.state_commitment.0,
+    );
+
+    Ok(())
+}
+
+/// Fails if a reorg starting at `reorg_tail` would remove a checkpointed block, i.e. one between
+/// the reorg tail and the stored `head`. Checkpoints above the head have not been reached yet.
+pub fn verify_reorg(
+    checkpoints: &[Checkpoint],
+    reorg_tail: StarknetBlockNumber,
+    head: Option<StarknetBlockNumber>,
+) -> anyhow::Result<()> {
+    let head = match head {
+        Some(head) => head,
+        None => return Ok(()),
+    };
+
+    match checkpoints
+        .iter()
+        .find(|c| (reorg_tail..=head).contains(&c.block_number))
+    {
+        Some(checkpoint) => anyhow::bail!(
+            "Reorg to block {reorg_tail} would remove checkpointed block {}. Refusing to follow \
+            a gateway serving a conflicting history.",
+            checkpoint.block_number
+        ),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    const CHECKPOINT: Checkpoint = Checkpoint {
+        block_number: StarknetBlockNumber::new_or_panic(10),
+        block_hash: StarknetBlockHash(felt!("0xabc")),
+        state_commitment: StateCommitment(felt!("0xdef")),
+    };
+
+    #[test]
+    fn parse() {
+        assert_eq!("10:0xabc:0xdef".parse::<Checkpoint>().unwrap(), CHECKPOINT);
+
+        for invalid in [
+            "10:0xabc",
+            "10:0xabc:0xdef:0x1",
+            "-1:0xabc:0xdef",
+            "10:xyz:0xdef",
+        ] {
+            assert!(invalid.parse::<Checkpoint>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn block() {
+        let other = StarknetBlockHash(felt!("0x123"));
+
+        verify_block(
+            &[CHECKPOINT],
+            CHECKPOINT.block_number,
+            CHECKPOINT.block_hash,
+            CHECKPOINT.state_commitment,
+        )
+        .unwrap();
+        verify_block(
+            &[CHECKPOINT],
+            CHECKPOINT.block_number + 1,
+            other,
+            CHECKPOINT.state_commitment,
+        )
+        .unwrap();
+        verify_block(
+            &[CHECKPOINT],
+            CHECKPOINT.block_number,
+            other,
+            CHECKPOINT.state_commitment,
+        )
+        .unwrap_err();
+        verify_block(
+            &[CHECKPOINT],
+            CHECKPOINT.block_number,
+            CHECKPOINT.block_hash,
+            StateCommitment(other.0),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn reorg() {
+        let above = Some(CHECKPOINT.block_number + 5);
+        verify_reorg(&[CHECKPOINT], CHECKPOINT.block_number + 1, above).unwrap();
+        verify_reorg(&[CHECKPOINT], CHECKPOINT.block_number, above).unwrap_err();
+        verify_reorg(&[CHECKPOINT], StarknetBlockNumber::GENESIS, above).unwrap_err();
+
+        // The checkpoint has not been reached yet, so the reorg cannot remove it.
+        let below = Some(CHECKPOINT.block_number - 1);
+        verify_reorg(&[CHECKPOINT], StarknetBlockNumber::GENESIS, below).unwrap();
+        verify_reorg(&[CHECKPOINT], StarknetBlockNumber::GENESIS, None).unwrap();
+    }
+}
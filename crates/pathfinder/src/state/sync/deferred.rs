@@ -0,0 +1,179 @@
+//! Downloads the class definitions which sync has deferred.
+//!
+//! With lazy class download, sync stores only the hashes of new classes. Their definitions are
+//! downloaded here, either in the background or on demand when a request needs one.
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::bandwidth::Bandwidth;
+use pathfinder_common::{CasmHash, ClassHash};
+use pathfinder_storage::{
+    CasmClassTable, CasmCompilationFailuresTable, DeferredClassesTable, Storage,
+};
+use rusqlite::TransactionBehavior;
+use starknet_gateway_client::ClientApi;
+
+use super::{download_class, DownloadedClass};
+
+/// Classes queried from the database at once.
+const BATCH_SIZE: usize = 100;
+/// Pause between downloads, which leaves the bandwidth and the write lock to sync.
+const THROTTLE: Duration = Duration::from_millis(200);
+/// Interval between checks while there is nothing to download.
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Downloads the definitions of all deferred classes which have been declared.
+///
+/// Classes which fail to download are retried only after a restart, so that they cannot stall
+/// the others. Downloads pause while the monthly [Bandwidth] quota is near, leaving the rest of
+/// it to sync and to classes needed by requests. The gateway client holds back the pending block
+/// as well, and everything else once the quota is used up.
+pub async fn run<SequencerClient: ClientApi>(
+    storage: Storage,
+    sequencer: SequencerClient,
+    bandwidth: Bandwidth,
+) {
+    let mut failed = HashSet::new();
+    let mut paused = false;
+
+    loop {
+        if bandwidth.quota_near() {
+            if !paused {
+                tracing::warn!(
+                    received=%bandwidth.received_this_month(),
+                    "Monthly bandwidth quota is nearly used, pausing deferred class downloads"
+                );
+                paused = true;
+            }
+            tokio::time::sleep(IDLE_INTERVAL).await;
+            continue;
+        }
+        if paused {
+            tracing::info!("Resuming deferred class downloads");
+            paused = false;
+        }
+
+        let batch = {
+            let storage = storage.clone();
+            let limit = BATCH_SIZE + failed.len();
+            tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+                let conn = storage
+                    .connection()
+                    .context("Creating database connection")?;
+                DeferredClassesTable::next_declared(&conn, limit)
+            })
+            .await
+            .context("Deferred classes query panicked")
+            .and_then(|result| result)
+        };
+
+        let batch = match batch {
+            Ok(batch) => batch
+                .into_iter()
+                .filter(|(class_hash, _)| !failed.contains(class_hash))
+                .collect::<Vec<_>>(),
+            Err(e) => {
+                tracing::warn!(error=%e, "Failed to query deferred classes");
+                tokio::time::sleep(IDLE_INTERVAL).await;
+                continue;
+            }
+        };
+
+        if batch.is_empty() {
+            tokio::time::sleep(IDLE_INTERVAL).await;
+            continue;
+        }
+
+        for (class_hash, compiled_class_hash) in batch {
+            if bandwidth.quota_near() {
+                break;
+            }
+
+            match download_deferred(&storage, &sequencer, class_hash, compiled_class_hash).await {
+                Ok(()) => tracing::trace!(%class_hash, "Downloaded deferred class"),
+                Err(e) => {
+                    tracing::warn!(%class_hash, error=%e, "Failed to download deferred class");
+                    failed.insert(class_hash);
+                }
+            }
+
+            tokio::time::sleep(THROTTLE).await;
+        }
+    }
+}
+
+/// Downloads and stores the definition of the class if it has been deferred.
+pub async fn download<SequencerClient: ClientApi>(
+    storage: &Storage,
+    sequencer: &SequencerClient,
+    class_hash: ClassHash,
+) -> anyhow::Result<()> {
+    let deferred = {
+        let storage = storage.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+            let conn = storage
+                .connection()
+                .context("Creating database connection")?;
+            DeferredClassesTable::get(&conn, class_hash)
+        })
+        .await
+        .context("Deferred class query panicked")??
+    };
+
+    match deferred {
+        Some(compiled_class_hash) => {
+            download_deferred(storage, sequencer, class_hash, compiled_class_hash).await
+        }
+        None => Ok(()),
+    }
+}
+
+async fn download_deferred<SequencerClient: ClientApi>(
+    storage: &Storage,
+    sequencer: &SequencerClient,
+    class_hash: ClassHash,
+    compiled_class_hash: Option<CasmHash>,
+) -> anyhow::Result<()> {
+    let class = download_class(sequencer, class_hash).await?;
+
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        let mut conn = storage
+            .connection()
+            .context("Creating database connection")?;
+        let transaction = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        match class {
+            DownloadedClass::Cairo(class) => DeferredClassesTable::resolve(&transaction, &class)?,
+            DownloadedClass::Sierra(sierra, casm, compilation_error) => {
+                // NOTE: we _have_ to use the compiled class hash of the declaration, since that's
+                // what has been added to the class commitment tree.
+                let compiled_class_hash = compiled_class_hash
+                    .context("Compiled class hash is unknown until the class is declared")?;
+
+                DeferredClassesTable::resolve(&transaction, &sierra)?;
+                CasmClassTable::upsert_compressed(
+                    &transaction,
+                    &casm,
+                    &compiled_class_hash,
+                    crate::sierra::COMPILER_VERSION,
+                )?;
+                if let Some(error) = &compilation_error {
+                    CasmCompilationFailuresTable::upsert(
+                        &transaction,
+                        class_hash,
+                        crate::sierra::COMPILER_VERSION,
+                        error,
+                    )?;
+                }
+            }
+        }
+
+        transaction.commit()?;
+        Ok(())
+    })
+    .await
+    .context("Storing deferred class panicked")?
+    .with_context(|| format!("Storing deferred class {class_hash}"))
+}
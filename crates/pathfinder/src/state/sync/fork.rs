@@ -0,0 +1,268 @@
+//! Fetches the state of a remote block for fork mode.
+//!
+//! In fork mode nothing is synced. Calls are instead executed against a pinned block of the remote
+//! network, and each piece of its state is fetched from the gateway the first time an execution
+//! reads it. The fetched state is cached in the database, so later executions find it locally.
+use anyhow::Context;
+use pathfinder_common::{
+    BlockId, ClassHash, ContractNonce, GasPrice, SequencerAddress, StarknetBlockNumber,
+};
+use pathfinder_rpc::cairo::ext_py::ForkState;
+use pathfinder_storage::{ForkBlock, ForkStateTable, Storage};
+use stark_hash::Felt;
+use starknet_gateway_client::ClientApi;
+use starknet_gateway_types::error::{SequencerError, StarknetErrorCode};
+use starknet_gateway_types::reply::MaybePendingBlock;
+use starknet_gateway_types::request::BlockHashOrTag;
+
+use super::{download_class, DownloadedClass};
+
+/// Fetches the header of the block to fork from and stores it.
+pub async fn init<SequencerClient: ClientApi>(
+    storage: &Storage,
+    sequencer: &SequencerClient,
+    number: StarknetBlockNumber,
+) -> anyhow::Result<ForkBlock> {
+    let block = match sequencer
+        .block(BlockId::Number(number))
+        .await
+        .with_context(|| format!("Fetching block {number} to fork from"))?
+    {
+        MaybePendingBlock::Block(block) => block,
+        MaybePendingBlock::Pending(_) => anyhow::bail!("Sequencer returned `pending` block"),
+    };
+
+    let block = ForkBlock {
+        number: block.block_number,
+        hash: block.block_hash,
+        timestamp: block.timestamp,
+        gas_price: block.gas_price.unwrap_or(GasPrice::ZERO),
+        sequencer_address: block
+            .sequencer_address
+            .unwrap_or(SequencerAddress(Felt::ZERO)),
+        starknet_version: block.starknet_version,
+    };
+
+    let storage = storage.clone();
+    let stored = block.clone();
+    tokio::task::spawn_blocking(move || {
+        let connection = storage
+            .connection()
+            .context("Creating database connection")?;
+        ForkStateTable::insert_block(&connection, &stored)
+    })
+    .await
+    .context("Storing fork block panicked")??;
+
+    Ok(block)
+}
+
+/// Fetches the missing `state` of the forked block and stores it.
+pub async fn fetch<SequencerClient: ClientApi>(
+    storage: &Storage,
+    sequencer: &SequencerClient,
+    block: &ForkBlock,
+    state: ForkState,
+) -> anyhow::Result<()> {
+    let at = BlockHashOrTag::Hash(block.hash);
+    let number = block.number;
+    let storage = storage.clone();
+
+    let stored = match state {
+        ForkState::Storage {
+            contract_address,
+            key,
+        } => {
+            let value = sequencer
+                .storage(contract_address, key, at)
+                .await
+                .context("Fetching storage value")?;
+
+            tokio::task::spawn_blocking(move || {
+                let connection = storage
+                    .connection()
+                    .context("Creating database connection")?;
+                ForkStateTable::insert_storage(&connection, number, contract_address, key, value)
+            })
+            .await
+            .context("Storing fork storage panicked")?
+        }
+        ForkState::Contract { contract_address } => {
+            let class_hash = match sequencer.class_hash_at(contract_address, at).await {
+                Ok(class_hash) => class_hash,
+                // Execution has to be able to tell that the contract does not exist.
+                Err(SequencerError::StarknetError(e))
+                    if e.code == StarknetErrorCode::UninitializedContract =>
+                {
+                    ClassHash(Felt::ZERO)
+                }
+                Err(e) => return Err(e).context("Fetching class hash"),
+            };
+
+            let nonce = if class_hash.0 == Felt::ZERO {
+                ContractNonce(Felt::ZERO)
+            } else {
+                sequencer
+                    .nonce(contract_address, at)
+                    .await
+                    .context("Fetching nonce")?
+            };
+
+            tokio::task::spawn_blocking(move || {
+                let connection = storage
+                    .connection()
+                    .context("Creating database connection")?;
+                ForkStateTable::insert_contract(
+                    &connection,
+                    number,
+                    contract_address,
+                    class_hash,
+                    nonce,
+                )
+            })
+            .await
+            .context("Storing fork contract panicked")?
+        }
+        ForkState::Class { class_hash } => {
+            let (definition, casm_definition) = match download_class(sequencer, class_hash).await? {
+                DownloadedClass::Cairo(class) => (class.definition, None),
+                DownloadedClass::Sierra(sierra, casm, compilation_error) => {
+                    if let Some(error) = compilation_error {
+                        anyhow::bail!("Compiling Sierra class {class_hash} failed: {error}");
+                    }
+                    (sierra.definition, Some(casm.definition))
+                }
+            };
+
+            tokio::task::spawn_blocking(move || {
+                let connection = storage
+                    .connection()
+                    .context("Creating database connection")?;
+                ForkStateTable::insert_class_compressed(
+                    &connection,
+                    class_hash,
+                    &definition,
+                    casm_definition.as_deref(),
+                )
+            })
+            .await
+            .context("Storing fork class panicked")?
+        }
+    };
+
+    stored.with_context(|| format!("Storing fork state of block {number}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt, ContractAddress, StarknetBlockHash, StarknetBlockTimestamp, StateCommitment,
+        StorageAddress, StorageValue,
+    };
+    use starknet_gateway_client::MockClientApi;
+    use starknet_gateway_types::error::StarknetError;
+    use starknet_gateway_types::reply::{Block, Status};
+
+    fn fork_block() -> ForkBlock {
+        ForkBlock {
+            number: StarknetBlockNumber::new_or_panic(5),
+            hash: StarknetBlockHash(felt!("0xabcd")),
+            timestamp: StarknetBlockTimestamp::new_or_panic(10),
+            gas_price: GasPrice(1),
+            sequencer_address: SequencerAddress(felt!("0x1234")),
+            starknet_version: Some("0.11.0".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn init() {
+        let storage = Storage::in_memory().unwrap();
+        let expected = fork_block();
+        let number = expected.number;
+
+        let mut sequencer = MockClientApi::new();
+        let block = Block {
+            block_hash: expected.hash,
+            block_number: expected.number,
+            gas_price: Some(expected.gas_price),
+            parent_block_hash: StarknetBlockHash(felt!("0x1")),
+            sequencer_address: Some(expected.sequencer_address),
+            state_commitment: StateCommitment(felt!("0x2")),
+            status: Status::AcceptedOnL2,
+            timestamp: expected.timestamp,
+            transaction_receipts: Vec::new(),
+            transactions: Vec::new(),
+            starknet_version: expected.starknet_version.clone(),
+        };
+        sequencer
+            .expect_block()
+            .withf(move |id| id == &BlockId::Number(number))
+            .returning(move |_| Ok(MaybePendingBlock::Block(block.clone())));
+
+        let result = super::init(&storage, &sequencer, number).await.unwrap();
+        assert_eq!(result, expected);
+
+        let connection = storage.connection().unwrap();
+        let stored = ForkStateTable::get_block(&connection, number).unwrap();
+        assert_eq!(stored, Some(expected));
+    }
+
+    #[tokio::test]
+    async fn storage() {
+        let storage = Storage::in_memory().unwrap();
+        let block = fork_block();
+        ForkStateTable::insert_block(&storage.connection().unwrap(), &block).unwrap();
+
+        let contract_address = ContractAddress::new_or_panic(felt!("0x1"));
+        let key = StorageAddress::new_or_panic(felt!("0x2"));
+        let value = StorageValue(felt!("0x3"));
+
+        let at_block = BlockHashOrTag::Hash(block.hash);
+
+        let mut sequencer = MockClientApi::new();
+        sequencer
+            .expect_storage()
+            .withf(move |a, k, at| a == &contract_address && k == &key && at == &at_block)
+            .returning(move |_, _, _| Ok(value));
+
+        let state = ForkState::Storage {
+            contract_address,
+            key,
+        };
+        fetch(&storage, &sequencer, &block, state).await.unwrap();
+
+        let connection = storage.connection().unwrap();
+        let stored =
+            ForkStateTable::get_storage(&connection, block.number, contract_address, key).unwrap();
+        assert_eq!(stored, Some(value));
+    }
+
+    #[tokio::test]
+    async fn uninitialized_contract() {
+        let storage = Storage::in_memory().unwrap();
+        let block = fork_block();
+        ForkStateTable::insert_block(&storage.connection().unwrap(), &block).unwrap();
+
+        let contract_address = ContractAddress::new_or_panic(felt!("0x1"));
+
+        let mut sequencer = MockClientApi::new();
+        sequencer.expect_class_hash_at().returning(|_, _| {
+            Err(SequencerError::StarknetError(StarknetError {
+                code: StarknetErrorCode::UninitializedContract,
+                message: String::new(),
+            }))
+        });
+
+        let state = ForkState::Contract { contract_address };
+        fetch(&storage, &sequencer, &block, state).await.unwrap();
+
+        let connection = storage.connection().unwrap();
+        let stored =
+            ForkStateTable::get_contract(&connection, block.number, contract_address).unwrap();
+        assert_eq!(
+            stored,
+            Some((ClassHash(Felt::ZERO), ContractNonce(Felt::ZERO)))
+        );
+    }
+}
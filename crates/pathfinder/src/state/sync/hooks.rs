@@ -0,0 +1,84 @@
+//! Lets embedders of pathfinder maintain their own tables derived from the synced chain.
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_common::StarknetBlockNumber;
+use rusqlite::Transaction;
+use starknet_gateway_types::reply::{Block, StateUpdate};
+
+/// Receives each change which sync makes to the stored L2 chain, within the database transaction
+/// which makes the change.
+///
+/// Whatever a hook writes using the transaction is committed together with pathfinder's own
+/// changes, so that derived tables never diverge from the chain. An error aborts the transaction
+/// and stops sync, in the same way as a failure to store the block itself.
+///
+/// Hooks run on the sync task and hold the database write lock, so they should be quick.
+pub trait ChainHook: Send + Sync {
+    /// Identifies the hook in errors.
+    fn name(&self) -> &str;
+
+    /// Called once `block` and its `state_update` have been stored as the new head of the chain.
+    fn block_applied(
+        &self,
+        transaction: &Transaction<'_>,
+        block: &Block,
+        state_update: &StateUpdate,
+    ) -> anyhow::Result<()>;
+
+    /// Called before the blocks from `reorg_tail` onwards are removed, so that they can still be
+    /// queried.
+    fn reorg(
+        &self,
+        transaction: &Transaction<'_>,
+        reorg_tail: StarknetBlockNumber,
+    ) -> anyhow::Result<()>;
+}
+
+/// The [ChainHook]s registered with sync, which are called in the order in which they were
+/// registered.
+#[derive(Clone, Default)]
+pub struct ChainHooks(Vec<Arc<dyn ChainHook>>);
+
+impl ChainHooks {
+    pub fn register(&mut self, hook: Arc<dyn ChainHook>) {
+        self.0.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn block_applied(
+        &self,
+        transaction: &Transaction<'_>,
+        block: &Block,
+        state_update: &StateUpdate,
+    ) -> anyhow::Result<()> {
+        for hook in &self.0 {
+            hook.block_applied(transaction, block, state_update)
+                .with_context(|| format!("Applying block in {} hook", hook.name()))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn reorg(
+        &self,
+        transaction: &Transaction<'_>,
+        reorg_tail: StarknetBlockNumber,
+    ) -> anyhow::Result<()> {
+        for hook in &self.0 {
+            hook.reorg(transaction, reorg_tail)
+                .with_context(|| format!("Reorging in {} hook", hook.name()))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ChainHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|hook| hook.name()))
+            .finish()
+    }
+}
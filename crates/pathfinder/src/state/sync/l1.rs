@@ -52,6 +52,21 @@ where
     sync_impl(eth_api, tx_event, chain).await
 }
 
+/// Stand-in for [sync] when running without Ethereum. Emits no events, so the L1 state
+/// is never updated.
+///
+/// Completes once the receiver of `tx_event` is dropped.
+pub async fn disabled<T>(
+    tx_event: mpsc::Sender<Event>,
+    _transport: T,
+    _chain: Chain,
+    _core_address: H160,
+    _head: Option<StateUpdateLog>,
+) -> anyhow::Result<()> {
+    tx_event.closed().await;
+    Ok(())
+}
+
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
 trait EthereumApi {
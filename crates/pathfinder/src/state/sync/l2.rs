@@ -1,11 +1,12 @@
-use crate::state::block_hash::{verify_block_hash, VerifyResult};
+use crate::state::block_hash::{verify_block_hash, IrregularBlocks, VerifyResult};
+use crate::state::sync::poll::AdaptivePoll;
 use anyhow::{anyhow, Context};
 use pathfinder_common::{
-    CasmHash, Chain, ClassHash, EventCommitment, StarknetBlockHash, StarknetBlockNumber,
-    StateCommitment, TransactionCommitment,
+    CasmHash, Chain, ClassHash, ContractAddress, EventCommitment, StarknetBlockHash,
+    StarknetBlockNumber, StarknetTransactionHash, StateCommitment, TransactionCommitment,
 };
 use pathfinder_storage::types::{CompressedCasmClass, CompressedContract};
-use starknet_gateway_client::ClientApi;
+use starknet_gateway_client::{ClientApi, RateLimit};
 use starknet_gateway_types::{
     class_hash::compute_class_hash,
     error::SequencerError,
@@ -14,9 +15,12 @@ use starknet_gateway_types::{
         StateUpdate, Status,
     },
 };
-use std::time::Duration;
-use std::{collections::HashSet, sync::Arc};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Timings {
@@ -42,6 +46,9 @@ pub enum Event {
     NewCairoContract(CompressedContract),
     /// A new unique L2 Cairo 1.x [contract](CompressedContract) was found.
     NewSierraContract(CompressedContract, CompressedCasmClass, CasmHash),
+    /// The Cairo 1.x class could not be compiled to CASM, and was sent with an empty CASM
+    /// definition. Contains the compilation error.
+    CasmCompilationFailed(ClassHash, String),
     /// Query for the [block hash](StarknetBlockHash) and [root](StateCommitment) of the given block.
     ///
     /// The receiver should return the data using the [oneshot::channel].
@@ -58,53 +65,94 @@ pub enum Event {
     Pending(Arc<PendingBlock>, Arc<PendingStateUpdate>),
 }
 
+/// How [sync] downloads the blocks after the one it is at.
+#[derive(Clone, Copy, Debug)]
+pub struct Prefetch {
+    /// The number of blocks which are downloaded concurrently. Only one block at a time is
+    /// downloaded if this is 1.
+    pub workers: NonZeroUsize,
+    /// Whether the classes declared by the blocks are downloaded ahead as well, which is wasted
+    /// if the classes are downloaded lazily.
+    pub classes: bool,
+}
+
+impl Default for Prefetch {
+    /// Downloads one block at a time.
+    fn default() -> Self {
+        Self {
+            workers: NonZeroUsize::new(1).unwrap(),
+            classes: true,
+        }
+    }
+}
+
 pub async fn sync(
     tx_event: mpsc::Sender<Event>,
-    sequencer: impl ClientApi,
+    sequencer: impl ClientApi + Send + Sync + 'static,
     mut head: Option<(StarknetBlockNumber, StarknetBlockHash, StateCommitment)>,
     chain: Chain,
     pending_poll_interval: Option<Duration>,
     block_validation_mode: BlockValidationMode,
+    rate_limit: RateLimit,
+    prefetch: Prefetch,
 ) -> anyhow::Result<()> {
     use crate::state::sync::head_poll_interval;
 
+    let mut head_poll = AdaptivePoll::new(head_poll_interval(chain), rate_limit.clone());
+    let mut pending_poll =
+        pending_poll_interval.map(|interval| AdaptivePoll::new(interval, rate_limit));
+
+    let sequencer = Arc::new(sequencer);
+    let mut prefetcher = Prefetcher::new(
+        sequencer.clone(),
+        chain,
+        block_validation_mode.clone(),
+        prefetch,
+    );
+
     'outer: loop {
         // Get the next block from L2.
         let (next, head_meta) = match head {
             Some(head) => (head.0 + 1, Some(head)),
             None => (StarknetBlockNumber::GENESIS, None),
         };
-        let t_block = std::time::Instant::now();
+        let t_block = Instant::now();
 
+        let mut prefetched = None;
         let (block, commitments) = loop {
+            if let Some(download) = prefetcher.take(next).await {
+                prefetched = Some((download.state_update, download.declared_classes));
+                break (download.block, download.commitments);
+            }
+
             match download_block(
                 next,
                 chain,
                 head_meta.map(|h| h.1),
-                &sequencer,
-                block_validation_mode,
+                sequencer.as_ref(),
+                &block_validation_mode,
             )
             .await?
             {
                 DownloadBlock::Block(block, commitments) => break (block, commitments),
                 DownloadBlock::AtHead => {
                     // Poll pending if it is enabled, otherwise just wait to poll head again.
-                    match pending_poll_interval {
-                        Some(interval) => {
+                    match pending_poll.as_mut() {
+                        Some(poll) => {
                             tracing::trace!("Entering pending mode");
                             let head = head_meta
                                 .expect("Head hash should exist when entering pending mode");
                             crate::state::sync::pending::poll_pending(
                                 tx_event.clone(),
-                                &sequencer,
+                                sequencer.as_ref(),
                                 (head.1, head.2),
-                                interval,
+                                poll,
                             )
                             .await
                             .context("Polling pending block")?;
                         }
                         None => {
-                            let poll_interval = head_poll_interval(chain);
+                            let poll_interval = head_poll.next_interval();
                             tracing::info!(poll_interval=?poll_interval, "At head of chain");
                             tokio::time::sleep(poll_interval).await;
                         }
@@ -116,8 +164,8 @@ pub async fn sync(
                         some_head,
                         chain,
                         &tx_event,
-                        &sequencer,
-                        block_validation_mode,
+                        sequencer.as_ref(),
+                        &block_validation_mode,
                     )
                     .await
                     .context("L2 reorg")?;
@@ -134,8 +182,8 @@ pub async fn sync(
                     some_head,
                     chain,
                     &tx_event,
-                    &sequencer,
-                    block_validation_mode,
+                    sequencer.as_ref(),
+                    &block_validation_mode,
                 )
                 .await
                 .context("L2 reorg")?;
@@ -146,16 +194,23 @@ pub async fn sync(
 
         // Unwrap in both block and state update is safe as the block hash always exists (unless we query for pending).
         let block_hash = block.block_hash;
-        let t_update = std::time::Instant::now();
-        let state_update = sequencer
-            .state_update(block_hash.into())
-            .await
-            .with_context(|| format!("Fetch state diff for block {next:?} from sequencer"))?;
+        let t_update = Instant::now();
+        let (state_update, declared_classes) = match prefetched {
+            Some(prefetched) => prefetched,
+            None => {
+                let state_update = sequencer
+                    .state_update(block_hash.into())
+                    .await
+                    .with_context(|| {
+                        format!("Fetch state diff for block {next:?} from sequencer")
+                    })?;
 
-        let state_update = match state_update {
-            MaybePendingStateUpdate::StateUpdate(su) => su,
-            MaybePendingStateUpdate::Pending(_) => {
-                anyhow::bail!("Sequencer returned `pending` state update")
+                match state_update {
+                    MaybePendingStateUpdate::StateUpdate(su) => (Box::new(su), HashMap::new()),
+                    MaybePendingStateUpdate::Pending(_) => {
+                        anyhow::bail!("Sequencer returned `pending` state update")
+                    }
+                }
             }
         };
 
@@ -169,10 +224,15 @@ pub async fn sync(
         let t_update = t_update.elapsed();
 
         // Download and emit newly declared classes.
-        let t_declare = std::time::Instant::now();
-        download_new_classes(&state_update.state_diff, &sequencer, &tx_event, chain)
-            .await
-            .with_context(|| format!("Handling newly declared classes for block {next:?}"))?;
+        let t_declare = Instant::now();
+        download_new_classes(
+            &state_update.state_diff,
+            sequencer.as_ref(),
+            &tx_event,
+            declared_classes,
+        )
+        .await
+        .with_context(|| format!("Handling newly declared classes for block {next:?}"))?;
         let t_declare = t_declare.elapsed();
 
         head = Some((next, block_hash, state_update.new_root));
@@ -184,17 +244,14 @@ pub async fn sync(
         };
 
         tx_event
-            .send(Event::Update(
-                (block, commitments),
-                Box::new(state_update),
-                timings,
-            ))
+            .send(Event::Update((block, commitments), state_update, timings))
             .await
             .context("Event channel closed")?;
     }
 }
 
-/// Download and emit new contract classes.
+/// Download and emit new contract classes, taking those which were already downloaded from
+/// `prefetched`.
 ///
 /// New classes can come from:
 /// - DECLARE transactions
@@ -211,7 +268,7 @@ async fn download_new_classes(
     state_diff: &StateDiff,
     sequencer: &impl ClientApi,
     tx_event: &mpsc::Sender<Event>,
-    chain: Chain,
+    mut prefetched: HashMap<ClassHash, DownloadedClass>,
 ) -> Result<(), anyhow::Error> {
     let deployed_classes = state_diff.deployed_contracts.iter().map(|x| x.class_hash);
     let declared_cairo_classes = state_diff.old_declared_contracts.iter().cloned();
@@ -259,9 +316,12 @@ async fn download_new_classes(
         .collect::<Vec<_>>();
 
     for class_hash in require_downloading {
-        let class = download_and_compress_class(class_hash, sequencer, chain)
-            .await
-            .with_context(|| format!("Downloading class {}", class_hash.0))?;
+        let class = match prefetched.remove(&class_hash) {
+            Some(class) => class,
+            None => download_and_compress_class(class_hash, sequencer)
+                .await
+                .with_context(|| format!("Downloading class {}", class_hash.0))?,
+        };
 
         match class {
             DownloadedClass::Cairo(class) => tx_event
@@ -273,7 +333,7 @@ async fn download_new_classes(
                         class_hash.0
                     )
                 })?,
-            DownloadedClass::Sierra(sierra_class, casm_class) => {
+            DownloadedClass::Sierra(sierra_class, casm_class, compilation_error) => {
                 // NOTE: we _have_ to use the same compiled_class_class hash as returned by the feeder gateway,
                 // since that's what has been added to the class commitment tree.
                 let compiled_class_hash = state_diff
@@ -299,7 +359,19 @@ async fn download_new_classes(
                             "Sending Event::NewSierraContract for declared class {}",
                             class_hash.0
                         )
-                    })?
+                    })?;
+
+                if let Some(error) = compilation_error {
+                    tx_event
+                        .send(Event::CasmCompilationFailed(class_hash, error))
+                        .await
+                        .with_context(|| {
+                            format!(
+                                "Sending Event::CasmCompilationFailed for declared class {}",
+                                class_hash.0
+                            )
+                        })?;
+                }
             }
         }
     }
@@ -313,21 +385,215 @@ enum DownloadBlock {
     Reorg,
 }
 
-#[derive(Copy, Clone, Default)]
+/// How often the latest block is queried to bound prefetching, once [sync] has caught up with the
+/// previously queried one.
+const LATEST_BLOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A block downloaded ahead of time by the [Prefetcher].
+struct Prefetched {
+    block: Box<Block>,
+    commitments: (TransactionCommitment, EventCommitment),
+    state_update: Box<StateUpdate>,
+    /// The classes declared by the block. Empty if [Prefetch::classes] is disabled.
+    declared_classes: HashMap<ClassHash, DownloadedClass>,
+}
+
+/// Downloads the blocks after the one [sync] is at concurrently, each in its own task together
+/// with its state update and declared classes, while [sync] applies them one by one in order.
+///
+/// Prefetching is only an optimisation: a download which fails, finds no block or is not taken
+/// next is discarded together with all the downloads after it. [sync] then downloads that block
+/// itself, which handles errors and reorgs as usual, after which prefetching resumes. Blocks after
+/// the latest one are not prefetched, so that sync at the head of the chain is unaffected.
+struct Prefetcher<S> {
+    sequencer: Arc<S>,
+    chain: Chain,
+    mode: BlockValidationMode,
+    prefetch: Prefetch,
+    /// The downloads of consecutive blocks in progress, in order.
+    downloads: VecDeque<(
+        StarknetBlockNumber,
+        JoinHandle<anyhow::Result<Option<Prefetched>>>,
+    )>,
+    latest: Option<StarknetBlockNumber>,
+    latest_refreshed_at: Option<Instant>,
+}
+
+impl<S: ClientApi + Send + Sync + 'static> Prefetcher<S> {
+    fn new(sequencer: Arc<S>, chain: Chain, mode: BlockValidationMode, prefetch: Prefetch) -> Self {
+        Self {
+            sequencer,
+            chain,
+            mode,
+            prefetch,
+            downloads: VecDeque::new(),
+            latest: None,
+            latest_refreshed_at: None,
+        }
+    }
+
+    /// Takes block `next` if it was prefetched, and starts downloading the blocks after it.
+    async fn take(&mut self, next: StarknetBlockNumber) -> Option<Prefetched> {
+        if self.prefetch.workers.get() < 2 {
+            return None;
+        }
+
+        if matches!(self.downloads.front(), Some((number, _)) if *number != next) {
+            self.clear();
+        }
+
+        let latest = self.latest(next).await?;
+        let mut number = match self.downloads.back() {
+            Some((number, _)) => *number + 1,
+            None => next,
+        };
+        while self.downloads.len() < self.prefetch.workers.get() && number <= latest {
+            let download = tokio::spawn(prefetch(
+                number,
+                self.chain,
+                self.sequencer.clone(),
+                self.mode.clone(),
+                self.prefetch.classes,
+            ));
+            self.downloads.push_back((number, download));
+            number += 1;
+        }
+
+        let (_, download) = self.downloads.pop_front()?;
+        let error = match download.await {
+            Ok(Ok(Some(prefetched))) => return Some(prefetched),
+            Ok(Ok(None)) => None,
+            Ok(Err(error)) => Some(error),
+            Err(error) => Some(anyhow::Error::new(error)),
+        };
+
+        if let Some(error) = error {
+            tracing::debug!(block=%next, error=%format!("{error:#}"), "Prefetching block failed");
+        }
+        self.clear();
+        None
+    }
+
+    /// The latest block, which is queried again once `next` is past it, but at most once per
+    /// [LATEST_BLOCK_REFRESH_INTERVAL].
+    async fn latest(&mut self, next: StarknetBlockNumber) -> Option<StarknetBlockNumber> {
+        use pathfinder_common::BlockId;
+        use starknet_gateway_types::reply::MaybePendingBlock;
+
+        let stale = self.latest.map(|latest| latest < next).unwrap_or(true);
+        let refreshed_recently = self
+            .latest_refreshed_at
+            .map(|at| at.elapsed() < LATEST_BLOCK_REFRESH_INTERVAL)
+            .unwrap_or_default();
+
+        if stale && !refreshed_recently {
+            self.latest_refreshed_at = Some(Instant::now());
+            if let Ok(MaybePendingBlock::Block(latest)) =
+                self.sequencer.block(BlockId::Latest).await
+            {
+                self.latest = Some(latest.block_number);
+            }
+        }
+
+        self.latest
+    }
+
+    fn clear(&mut self) {
+        for (_, download) in self.downloads.drain(..) {
+            download.abort();
+        }
+    }
+}
+
+impl<S> Drop for Prefetcher<S> {
+    fn drop(&mut self) {
+        for (_, download) in &self.downloads {
+            download.abort();
+        }
+    }
+}
+
+/// Downloads block `number`, its state update and, if `classes` is set, the classes it declares.
+///
+/// Returns [None] if the block does not exist (anymore).
+async fn prefetch<S: ClientApi>(
+    number: StarknetBlockNumber,
+    chain: Chain,
+    sequencer: Arc<S>,
+    mode: BlockValidationMode,
+    classes: bool,
+) -> anyhow::Result<Option<Prefetched>> {
+    let (block, commitments) =
+        match download_block(number, chain, None, sequencer.as_ref(), &mode).await? {
+            DownloadBlock::Block(block, commitments) => (block, commitments),
+            DownloadBlock::AtHead | DownloadBlock::Reorg => return Ok(None),
+        };
+
+    let state_update = match sequencer
+        .state_update(block.block_hash.into())
+        .await
+        .context("Fetch state diff from sequencer")?
+    {
+        MaybePendingStateUpdate::StateUpdate(state_update) => Box::new(state_update),
+        MaybePendingStateUpdate::Pending(_) => return Ok(None),
+    };
+
+    let mut declared_classes = HashMap::new();
+    if classes {
+        let state_diff = &state_update.state_diff;
+        let declared = state_diff.old_declared_contracts.iter().copied().chain(
+            state_diff
+                .declared_classes
+                .iter()
+                .map(|x| ClassHash(x.class_hash.0)),
+        );
+        for class_hash in declared {
+            let class = download_and_compress_class(class_hash, sequencer.as_ref())
+                .await
+                .with_context(|| format!("Downloading class {}", class_hash.0))?;
+            declared_classes.insert(class_hash, class);
+        }
+    }
+
+    Ok(Some(Prefetched {
+        block,
+        commitments,
+        state_update,
+        declared_classes,
+    }))
+}
+
+#[derive(Clone, Default)]
 pub enum BlockValidationMode {
+    /// Rejects blocks whose hash does not match, except for the built-in irregular blocks of the
+    /// chain, see [IrregularBlocks::for_chain].
     #[default]
     Strict,
 
+    /// Like [Strict](Self::Strict), with the given irregular blocks instead of the built-in ones.
+    StrictWithIrregularBlocks(Arc<IrregularBlocks>),
+
     // For testing only (test block hashes won't match)
     AllowMismatch,
 }
 
+impl BlockValidationMode {
+    fn irregular_blocks(&self, chain: Chain) -> Arc<IrregularBlocks> {
+        match self {
+            BlockValidationMode::StrictWithIrregularBlocks(irregular_blocks) => {
+                irregular_blocks.clone()
+            }
+            _ => Arc::new(IrregularBlocks::for_chain(chain)),
+        }
+    }
+}
+
 async fn download_block(
     block_number: StarknetBlockNumber,
     chain: Chain,
     prev_block_hash: Option<StarknetBlockHash>,
     sequencer: &impl ClientApi,
-    mode: BlockValidationMode,
+    mode: &BlockValidationMode,
 ) -> anyhow::Result<DownloadBlock> {
     use pathfinder_common::BlockId;
     use starknet_gateway_types::{
@@ -341,10 +607,18 @@ async fn download_block(
             let block = Box::new(block);
             // Check if block hash is correct.
             let expected_block_hash = block.block_hash;
+            let irregular_blocks = mode.irregular_blocks(chain);
             let verify_hash = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
                 let block_number = block.block_number;
-                let verify_result = verify_block_hash(&block, chain, expected_block_hash)
-                    .with_context(move || format!("Verify block {block_number}"))?;
+                let verify_result =
+                    verify_block_hash(&block, chain, expected_block_hash, &irregular_blocks)
+                        .with_context(move || format!("Verify block {block_number}"))?;
+                for transaction_hash in mismatching_deployed_addresses(&block) {
+                    tracing::warn!(
+                        %block_number, ?transaction_hash,
+                        "Deployed contract address does not match its deployment parameters"
+                    );
+                }
                 Ok((block, verify_result))
             });
             let (block, verify_result) = verify_hash.await.context("Verify block hash")??;
@@ -355,6 +629,10 @@ async fn download_block(
                     _,
                 ) => Ok(DownloadBlock::Block(block, commitments)),
                 (Status::AcceptedOnL1 | Status::AcceptedOnL2, VerifyResult::NotVerifiable, _) => {
+                    tracing::debug!(
+                        block_number=%block.block_number,
+                        "Accepting known irregular block without verifying its hash"
+                    );
                     Ok(DownloadBlock::Block(block, Default::default()))
                 }
                 (
@@ -362,9 +640,11 @@ async fn download_block(
                     VerifyResult::Mismatch,
                     BlockValidationMode::AllowMismatch,
                 ) => Ok(DownloadBlock::Block(block, Default::default())),
-                (_, VerifyResult::Mismatch, BlockValidationMode::Strict) => {
-                    Err(anyhow!("Block hash mismatch"))
-                }
+                (
+                    _,
+                    VerifyResult::Mismatch,
+                    BlockValidationMode::Strict | BlockValidationMode::StrictWithIrregularBlocks(_),
+                ) => Err(anyhow!("Block hash mismatch")),
                 _ => Err(anyhow!(
                     "Rejecting block as its status is {}, and only accepted blocks are allowed",
                     block.status
@@ -405,12 +685,52 @@ async fn download_block(
     }
 }
 
+/// Returns the hashes of the `DEPLOY` and `DEPLOY_ACCOUNT` transactions whose contract address
+/// does not match the one computed from their deployment parameters.
+///
+/// The addresses are already covered by the block hash, so mismatches point at a bug in our
+/// address derivation rather than at the gateway, and are therefore only logged.
+fn mismatching_deployed_addresses(block: &Block) -> Vec<StarknetTransactionHash> {
+    use pathfinder_common::calculate_contract_address;
+    use starknet_gateway_types::reply::transaction::Transaction;
+
+    block
+        .transactions
+        .iter()
+        .filter_map(|transaction| {
+            let (expected, computed) = match transaction {
+                Transaction::Deploy(tx) => (
+                    tx.contract_address,
+                    calculate_contract_address(
+                        tx.class_hash,
+                        tx.contract_address_salt,
+                        tx.constructor_calldata.iter().map(|param| param.0),
+                        ContractAddress::ZERO,
+                    ),
+                ),
+                Transaction::DeployAccount(tx) => (
+                    tx.contract_address,
+                    calculate_contract_address(
+                        tx.class_hash,
+                        tx.contract_address_salt,
+                        tx.constructor_calldata.iter().map(|param| param.0),
+                        ContractAddress::ZERO,
+                    ),
+                ),
+                _ => return None,
+            };
+
+            (expected != computed).then(|| transaction.hash())
+        })
+        .collect()
+}
+
 async fn reorg(
     head: (StarknetBlockNumber, StarknetBlockHash, StateCommitment),
     chain: Chain,
     tx_event: &mpsc::Sender<Event>,
     sequencer: &impl ClientApi,
-    mode: BlockValidationMode,
+    mode: &BlockValidationMode,
 ) -> anyhow::Result<Option<(StarknetBlockNumber, StarknetBlockHash, StateCommitment)>> {
     // Go back in history until we find an L2 block that does still exist.
     // We already know the current head is invalid.
@@ -467,13 +787,12 @@ async fn reorg(
 
 enum DownloadedClass {
     Cairo(CompressedContract),
-    Sierra(CompressedContract, CompressedCasmClass),
+    Sierra(CompressedContract, CompressedCasmClass, Option<String>),
 }
 
 async fn download_and_compress_class(
     class_hash: ClassHash,
     sequencer: &impl ClientApi,
-    chain: Chain,
 ) -> anyhow::Result<DownloadedClass> {
     let definition = sequencer
         .class_by_hash(class_hash)
@@ -518,16 +837,8 @@ async fn download_and_compress_class(
             }))
         }
         starknet_gateway_types::class_hash::ComputedClassHash::Sierra(hash) => {
-            let casm_definition =
-                crate::sierra::compile_to_casm(&definition).context("Compiling Sierra class");
-            let casm_definition = match (casm_definition, chain) {
-                (Ok(casm_definition), _) => casm_definition,
-                (Err(_), Chain::Integration) => {
-                    tracing::info!(class_hash=%hash, "Ignored CASM compilation failure integration network");
-                    Vec::new()
-                }
-                (Err(e), _) => return Err(e),
-            };
+            let (casm_definition, compilation_error) =
+                crate::sierra::compile_to_casm_or_empty(&definition, hash).await?;
 
             let compress = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
                 let mut compressor =
@@ -555,6 +866,7 @@ async fn download_and_compress_class(
                     definition: compressed_casm_definition,
                     hash,
                 },
+                compilation_error,
             ))
         }
     }
@@ -563,7 +875,7 @@ async fn download_and_compress_class(
 #[cfg(test)]
 mod tests {
     mod sync {
-        use super::super::{sync, BlockValidationMode, Event};
+        use super::super::{sync, BlockValidationMode, Event, Prefetch};
         use assert_matches::assert_matches;
         use pathfinder_common::{
             BlockId, ClassHash, ContractAddress, GasPrice, SequencerAddress, StarknetBlockHash,
@@ -571,7 +883,7 @@ mod tests {
             StorageValue,
         };
         use stark_hash::Felt;
-        use starknet_gateway_client::MockClientApi;
+        use starknet_gateway_client::{MockClientApi, RateLimit};
         use starknet_gateway_types::{
             error::{SequencerError, StarknetError, StarknetErrorCode},
             reply,
@@ -947,7 +1259,16 @@ mod tests {
                 );
 
                 // Let's run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -1029,6 +1350,8 @@ mod tests {
                     Chain::Testnet,
                     None,
                     MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
                 ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
@@ -1048,6 +1371,102 @@ mod tests {
                     assert_eq!(*state_update, *STATE_UPDATE1);
                 });
             }
+
+            #[tokio::test]
+            async fn prefetched() {
+                use mockall::predicate::eq;
+                use std::num::NonZeroUsize;
+
+                let (tx_event, mut rx_event) = tokio::sync::mpsc::channel(1);
+                let mut mock = MockClientApi::new();
+
+                // Block #1 declares the class it deploys, so that it is prefetched as well.
+                let state_update1 = reply::StateUpdate {
+                    state_diff: reply::state_update::StateDiff {
+                        old_declared_contracts: vec![*CONTRACT1_HASH],
+                        ..STATE_UPDATE1.state_diff.clone()
+                    },
+                    ..STATE_UPDATE1.clone()
+                };
+
+                // Blocks are downloaded concurrently, so only the number of requests is fixed.
+                mock.expect_block()
+                    .with(eq(BlockId::Latest))
+                    .returning(|_| Ok(BLOCK1.clone().into()));
+                mock.expect_block()
+                    .with(eq(BlockId::from(BLOCK0_NUMBER)))
+                    .times(1)
+                    .returning(|_| Ok(BLOCK0.clone().into()));
+                mock.expect_block()
+                    .with(eq(BlockId::from(BLOCK1_NUMBER)))
+                    .times(1)
+                    .returning(|_| Ok(BLOCK1.clone().into()));
+                mock.expect_block()
+                    .with(eq(BlockId::from(BLOCK2_NUMBER)))
+                    .returning(|_| Err(block_not_found()));
+                mock.expect_state_update()
+                    .with(eq(BlockId::from(*BLOCK0_HASH)))
+                    .times(1)
+                    .returning(|_| {
+                        Ok(reply::MaybePendingStateUpdate::StateUpdate(
+                            STATE_UPDATE0.clone(),
+                        ))
+                    });
+                let returned = state_update1.clone();
+                mock.expect_state_update()
+                    .with(eq(BlockId::from(*BLOCK1_HASH)))
+                    .times(1)
+                    .return_once(|_| Ok(reply::MaybePendingStateUpdate::StateUpdate(returned)));
+                mock.expect_class_by_hash()
+                    .with(eq(*CONTRACT0_HASH))
+                    .times(1)
+                    .returning(|_| Ok(CONTRACT0_DEF.clone()));
+                mock.expect_class_by_hash()
+                    .with(eq(*CONTRACT1_HASH))
+                    .times(1)
+                    .returning(|_| Ok(CONTRACT1_DEF.clone()));
+
+                // Let's run the UUT
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch {
+                        workers: NonZeroUsize::new(4).unwrap(),
+                        classes: true,
+                    },
+                ));
+
+                // Blocks are still emitted in order.
+                assert_matches!(rx_event.recv().await.unwrap(), Event::QueryContractExistance(contract_hashes, sender) => {
+                    assert_eq!(contract_hashes, vec![*CONTRACT0_HASH]);
+                    sender.send(vec![false]).unwrap();
+                });
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    Event::NewCairoContract(compressed_contract) => {
+                        assert_eq!(compressed_contract.hash, *CONTRACT0_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), Event::Update((block, _), state_update, _) => {
+                    assert_eq!(*block, *BLOCK0);
+                    assert_eq!(*state_update, *STATE_UPDATE0);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), Event::QueryContractExistance(contract_hashes, sender) => {
+                    assert_eq!(contract_hashes, vec![*CONTRACT1_HASH]);
+                    sender.send(vec![false]).unwrap();
+                });
+                assert_matches!(rx_event.recv().await.unwrap(),
+                    Event::NewCairoContract(compressed_contract) => {
+                        assert_eq!(compressed_contract.hash, *CONTRACT1_HASH);
+                });
+                assert_matches!(rx_event.recv().await.unwrap(), Event::Update((block, _), state_update, _) => {
+                    assert_eq!(*block, *BLOCK1);
+                    assert_eq!(*state_update, state_update1);
+                });
+            }
         }
 
         mod errors {
@@ -1066,7 +1485,16 @@ mod tests {
                 block.status = Status::Reverted;
                 expect_block(&mut mock, &mut seq, BLOCK0_NUMBER.into(), Ok(block.into()));
 
-                let jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
                 let error = jh.await.unwrap().unwrap_err();
                 assert_eq!(
                     &error.to_string(),
@@ -1169,7 +1597,16 @@ mod tests {
                 );
 
                 // Let's run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -1370,7 +1807,16 @@ mod tests {
                 );
 
                 // Run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -1644,7 +2090,16 @@ mod tests {
                 );
 
                 // Run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -1847,7 +2302,16 @@ mod tests {
                 );
 
                 // Run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -2039,7 +2503,16 @@ mod tests {
                 );
 
                 // Run the UUT
-                let _jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let _jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 let zstd_magic = vec![0x28, 0xb5, 0x2f, 0xfd];
 
@@ -2112,7 +2585,16 @@ mod tests {
                 );
 
                 // Run the UUT
-                let jh = tokio::spawn(sync(tx_event, mock, None, Chain::Testnet, None, MODE));
+                let jh = tokio::spawn(sync(
+                    tx_event,
+                    mock,
+                    None,
+                    Chain::Testnet,
+                    None,
+                    MODE,
+                    RateLimit::default(),
+                    Prefetch::default(),
+                ));
 
                 // Wrap this in a timeout so we don't wait forever in case of test failure.
                 // Right now closing the channel causes an error.
@@ -2124,4 +2606,44 @@ mod tests {
             }
         }
     }
+    mod deployed_addresses {
+        use super::super::mismatching_deployed_addresses;
+        use pathfinder_common::ContractAddress;
+        use stark_hash::Felt;
+        use starknet_gateway_types::reply::{transaction::Transaction, Block};
+
+        #[test]
+        fn match_deployment_parameters() {
+            for block in [
+                starknet_gateway_test_fixtures::v0_9_0::block::GENESIS,
+                starknet_gateway_test_fixtures::integration::block::NUMBER_228457,
+            ] {
+                let block = serde_json::from_str::<Block>(block).unwrap();
+                assert_eq!(mismatching_deployed_addresses(&block), vec![]);
+            }
+        }
+
+        #[test]
+        fn mismatch() {
+            let mut block = serde_json::from_str::<Block>(
+                starknet_gateway_test_fixtures::v0_9_0::block::GENESIS,
+            )
+            .unwrap();
+            let transaction = block
+                .transactions
+                .iter_mut()
+                .find_map(|transaction| match transaction {
+                    Transaction::Deploy(tx) => Some(tx),
+                    _ => None,
+                })
+                .unwrap();
+            transaction.contract_address = ContractAddress::new_or_panic(Felt::from(1u64));
+            let transaction_hash = transaction.transaction_hash;
+
+            assert_eq!(
+                mismatching_deployed_addresses(&block),
+                vec![transaction_hash]
+            );
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 /// Poll's the Sequencer's pending block and emits [Event::Pending](super::l2::Event::Pending)
 /// until the pending block is no longer connected to our current head.
 ///
@@ -5,6 +7,9 @@
 /// - `pending.parent_hash != head`, or
 /// - `pending` is a fully formed block and not [PendingBlock](starknet_gateway_types::reply::MaybePendingBlock::Pending), or
 /// - the state update parent root does not match head.
+///
+/// Pending mode is also exited after one poll interval while the gateway client holds back
+/// pending requests because the monthly bandwidth quota is near.
 pub async fn poll_pending(
     tx_event: tokio::sync::mpsc::Sender<super::l2::Event>,
     sequencer: &impl starknet_gateway_client::ClientApi,
@@ -12,24 +17,28 @@ pub async fn poll_pending(
         pathfinder_common::StarknetBlockHash,
         pathfinder_common::StateCommitment,
     ),
-    poll_interval: std::time::Duration,
+    poll: &mut super::poll::AdaptivePoll,
 ) -> anyhow::Result<()> {
     use anyhow::Context;
     use pathfinder_common::BlockId;
-    use std::sync::Arc;
+    use starknet_gateway_types::error::SequencerError;
 
     loop {
         use starknet_gateway_types::reply::{MaybePendingBlock, MaybePendingStateUpdate};
 
-        let pending_block = match sequencer
-            .block(BlockId::Pending)
-            .await
-            .context("Download pending block")?
-        {
+        let pending_block = match sequencer.block(BlockId::Pending).await {
+            Err(SequencerError::QuotaExceeded) => {
+                tracing::trace!("Bandwidth quota is near, exiting pending mode");
+                poll.sleep().await;
+                return Ok(());
+            }
+            result => result.context("Download pending block")?,
+        };
+        let pending_block = match pending_block {
             MaybePendingBlock::Block(block) if block.block_hash == head.0 => {
                 // Sequencer `pending` may return the latest full block for quite some time, so ignore it.
                 tracing::trace!(hash=%block.block_hash, "Found current head from pending mode");
-                tokio::time::sleep(poll_interval).await;
+                poll.sleep().await;
                 continue;
             }
             MaybePendingBlock::Block(block) => {
@@ -58,6 +67,10 @@ pub async fn poll_pending(
         )
         .await
         {
+            Ok(Err(SequencerError::QuotaExceeded)) => {
+                tracing::trace!("Bandwidth quota is near, exiting pending mode");
+                return Ok(());
+            }
             Ok(gateway_result) => gateway_result,
             Err(_timeout) => {
                 tracing::debug!("Pending state update query timed out, exiting pending mode.");
@@ -87,7 +100,7 @@ pub async fn poll_pending(
                     .await
                     .context("Event channel closed")?;
 
-                tokio::time::sleep(poll_interval).await;
+                poll.sleep().await;
             }
         }
     }
@@ -96,12 +109,13 @@ pub async fn poll_pending(
 #[cfg(test)]
 mod tests {
     use super::poll_pending;
+    use crate::state::sync::poll::AdaptivePoll;
     use assert_matches::assert_matches;
     use pathfinder_common::{
         felt, felt_bytes, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
         StarknetBlockTimestamp, StateCommitment,
     };
-    use starknet_gateway_client::MockClientApi;
+    use starknet_gateway_client::{MockClientApi, RateLimit};
     use starknet_gateway_types::reply::{
         state_update::StateDiff, Block, MaybePendingBlock, MaybePendingStateUpdate, PendingBlock,
         PendingStateUpdate, StateUpdate, Status,
@@ -171,7 +185,35 @@ mod tests {
                 tx,
                 &sequencer,
                 (*PARENT_HASH, *PARENT_ROOT),
-                std::time::Duration::ZERO,
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
+            )
+            .await
+        });
+
+        let result = tokio::time::timeout(TEST_TIMEOUT, rx.recv())
+            .await
+            .expect("Channel should be dropped");
+        assert_matches!(result, None);
+        jh.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn exits_on_quota_exceeded() {
+        use starknet_gateway_types::error::SequencerError;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let mut sequencer = MockClientApi::new();
+
+        sequencer
+            .expect_block()
+            .returning(move |_| Err(SequencerError::QuotaExceeded));
+
+        let jh = tokio::spawn(async move {
+            poll_pending(
+                tx,
+                &sequencer,
+                (*PARENT_HASH, *PARENT_ROOT),
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
             )
             .await
         });
@@ -209,7 +251,7 @@ mod tests {
                 tx,
                 &sequencer,
                 (*PARENT_HASH, *PARENT_ROOT),
-                std::time::Duration::ZERO,
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
             )
             .await
         });
@@ -240,7 +282,7 @@ mod tests {
                 tx,
                 &sequencer,
                 (*PARENT_HASH, *PARENT_ROOT),
-                std::time::Duration::ZERO,
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
             )
             .await
         });
@@ -272,7 +314,7 @@ mod tests {
                 tx,
                 &sequencer,
                 (*PARENT_HASH, *PARENT_ROOT),
-                std::time::Duration::ZERO,
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
             )
             .await
         });
@@ -301,7 +343,7 @@ mod tests {
                 tx,
                 &sequencer,
                 (*PARENT_HASH, *PARENT_ROOT),
-                std::time::Duration::ZERO,
+                &mut AdaptivePoll::new(std::time::Duration::ZERO, RateLimit::default()),
             )
             .await
         });
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use starknet_gateway_client::RateLimit;
+
+/// The longest interval to which polling backs off while the gateway is rate limiting.
+const MAX_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Schedules the polling of the gateway's `latest` or `pending` block.
+///
+/// Polling backs off while the gateway is rate limiting requests: the interval doubles after each
+/// poll during which any request was [rate limited](RateLimit), up to [MAX_INTERVAL]. The normal
+/// cadence resumes as soon as a poll passes without being rate limited.
+pub struct AdaptivePoll {
+    base: Duration,
+    current: Duration,
+    rate_limit: RateLimit,
+    /// The [RateLimit::count] at the previous poll.
+    rate_limited: u64,
+}
+
+impl AdaptivePoll {
+    pub fn new(base: Duration, rate_limit: RateLimit) -> Self {
+        let rate_limited = rate_limit.count();
+        Self {
+            base,
+            current: base,
+            rate_limit,
+            rate_limited,
+        }
+    }
+
+    /// The interval until the next poll, given whether requests were rate limited since the
+    /// previous one.
+    pub fn next_interval(&mut self) -> Duration {
+        let rate_limited = self.rate_limit.count();
+        let backoff = rate_limited != self.rate_limited;
+        self.rate_limited = rate_limited;
+
+        let next = if backoff {
+            (self.current * 2).min(MAX_INTERVAL.max(self.base))
+        } else {
+            self.base
+        };
+
+        if backoff && next != self.current {
+            tracing::info!(interval=?next, "Gateway is rate limiting, backing off polling");
+        } else if !backoff && self.current != self.base {
+            tracing::info!(interval=?next, "Gateway stopped rate limiting, resuming polling");
+        }
+
+        self.current = next;
+        next
+    }
+
+    pub async fn sleep(&mut self) {
+        tokio::time::sleep(self.next_interval()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_while_rate_limited() {
+        let base = Duration::from_secs(30);
+        let mut poll = AdaptivePoll::new(base, RateLimit::default());
+        assert_eq!(poll.next_interval(), base);
+
+        // Only the gateway client records rate limited requests, so fake a change of the count.
+        let rate_limited = |poll: &mut AdaptivePoll| poll.rate_limited = u64::MAX;
+
+        rate_limited(&mut poll);
+        assert_eq!(poll.next_interval(), base * 2);
+        rate_limited(&mut poll);
+        assert_eq!(poll.next_interval(), base * 4);
+        for _ in 0..10 {
+            rate_limited(&mut poll);
+            poll.next_interval();
+        }
+        assert_eq!(poll.current, MAX_INTERVAL);
+
+        assert_eq!(poll.next_interval(), base);
+    }
+}
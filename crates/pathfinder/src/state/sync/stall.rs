@@ -0,0 +1,196 @@
+//! Detects stalled sync, telling a gateway which has no new blocks apart from pathfinder being
+//! stuck on the blocks it has.
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use pathfinder_common::StarknetBlockNumber;
+
+/// How often the stall gauges are published.
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tracks when each stage of sync last made progress, and publishes how long each stage has been
+/// stalled as gauges:
+///
+/// - `sync_gateway_head_age_seconds`, the time since the gateway's latest block last changed,
+///   which grows while the gateway has no new blocks.
+/// - `sync_apply_stall_seconds`, the time since pathfinder last applied a block while it is behind
+///   the gateway's latest block, which grows while pathfinder is stuck even though there are
+///   blocks to apply. Zero while pathfinder is caught up.
+/// - `sync_block_apply_seconds`, how long applying the current block has taken so far. Zero
+///   between blocks.
+///
+/// Cheap to clone, with all clones sharing the same progress.
+#[derive(Clone)]
+pub struct StallDetector(Arc<Mutex<Progress>>);
+
+impl StallDetector {
+    /// `head` is the latest block in the database.
+    pub fn new(head: Option<StarknetBlockNumber>) -> Self {
+        Self(Arc::new(Mutex::new(Progress::new(head, Instant::now()))))
+    }
+
+    /// Records the gateway's latest block.
+    pub fn gateway_head(&self, number: StarknetBlockNumber) {
+        self.progress().gateway_head(number, Instant::now());
+    }
+
+    /// Records that applying a block has started.
+    pub fn applying(&self) {
+        self.progress().applying = Some(Instant::now());
+    }
+
+    /// Records that the block `number` has been applied.
+    pub fn applied(&self, number: StarknetBlockNumber) {
+        self.progress().head(Some(number), Instant::now());
+    }
+
+    /// Records that a reorg left `head` as the latest block.
+    pub fn reorged(&self, head: Option<StarknetBlockNumber>) {
+        self.progress().head(head, Instant::now());
+    }
+
+    fn publish(&self) {
+        let stalls = self.progress().stalls(Instant::now());
+
+        metrics::gauge!(
+            "sync_gateway_head_age_seconds",
+            stalls.gateway_head_age.as_secs_f64()
+        );
+        metrics::gauge!("sync_apply_stall_seconds", stalls.apply.as_secs_f64());
+        metrics::gauge!("sync_block_apply_seconds", stalls.block_apply.as_secs_f64());
+    }
+
+    /// Publishes the gauges every [PUBLISH_INTERVAL], so that they keep growing while nothing
+    /// happens.
+    pub async fn publish_periodically(self) {
+        let mut interval = tokio::time::interval(PUBLISH_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.publish();
+        }
+    }
+
+    fn progress(&self) -> std::sync::MutexGuard<'_, Progress> {
+        // Progress is always left consistent, so a panic elsewhere does not invalidate it.
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+struct Progress {
+    /// The gateway's latest block, and since when it has been the latest one.
+    gateway_head: (Option<StarknetBlockNumber>, Instant),
+    /// The latest block in the database.
+    head: Option<StarknetBlockNumber>,
+    /// Since when pathfinder has been behind the gateway without applying a block.
+    behind_since: Option<Instant>,
+    /// When applying the current block started.
+    applying: Option<Instant>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Stalls {
+    gateway_head_age: Duration,
+    apply: Duration,
+    block_apply: Duration,
+}
+
+impl Progress {
+    fn new(head: Option<StarknetBlockNumber>, now: Instant) -> Self {
+        Self {
+            // Until the gateway responds, its head is as old as the process.
+            gateway_head: (None, now),
+            head,
+            behind_since: None,
+            applying: None,
+        }
+    }
+
+    fn gateway_head(&mut self, number: StarknetBlockNumber, now: Instant) {
+        if self.gateway_head.0 != Some(number) {
+            self.gateway_head = (Some(number), now);
+        }
+        if self.is_behind() {
+            self.behind_since.get_or_insert(now);
+        }
+    }
+
+    fn head(&mut self, head: Option<StarknetBlockNumber>, now: Instant) {
+        self.head = head;
+        self.applying = None;
+        // Applying a block is progress, so that the stall restarts if still behind.
+        self.behind_since = self.is_behind().then_some(now);
+    }
+
+    fn is_behind(&self) -> bool {
+        match (self.gateway_head.0, self.head) {
+            (Some(gateway), Some(head)) => gateway > head,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    fn stalls(&self, now: Instant) -> Stalls {
+        let since = |instant: Instant| now.saturating_duration_since(instant);
+
+        Stalls {
+            gateway_head_age: since(self.gateway_head.1),
+            apply: self.behind_since.map(since).unwrap_or_default(),
+            block_apply: self.applying.map(since).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn block(number: u64) -> StarknetBlockNumber {
+        StarknetBlockNumber::new_or_panic(number)
+    }
+
+    #[test]
+    fn gateway_without_new_blocks() {
+        let start = Instant::now();
+        let mut progress = Progress::new(Some(block(10)), start);
+        progress.gateway_head(block(10), start);
+
+        // The same head again is no progress of the gateway.
+        progress.gateway_head(block(10), start + MINUTE);
+
+        assert_eq!(
+            progress.stalls(start + 2 * MINUTE),
+            Stalls {
+                gateway_head_age: 2 * MINUTE,
+                apply: Duration::ZERO,
+                block_apply: Duration::ZERO,
+            }
+        );
+    }
+
+    #[test]
+    fn stuck_applying() {
+        let start = Instant::now();
+        let mut progress = Progress::new(Some(block(10)), start);
+        progress.gateway_head(block(12), start);
+        progress.head(Some(block(11)), start + MINUTE);
+        progress.applying = Some(start + MINUTE);
+        progress.gateway_head(block(13), start + 2 * MINUTE);
+
+        assert_eq!(
+            progress.stalls(start + 3 * MINUTE),
+            Stalls {
+                gateway_head_age: MINUTE,
+                apply: 2 * MINUTE,
+                block_apply: 2 * MINUTE,
+            }
+        );
+
+        // Catching up ends the stall.
+        progress.head(Some(block(13)), start + 3 * MINUTE);
+        assert_eq!(progress.stalls(start + 4 * MINUTE).apply, Duration::ZERO);
+    }
+}
@@ -0,0 +1,137 @@
+//! Support for the systemd [service notification](https://www.freedesktop.org/software/systemd/man/sd_notify.html)
+//! protocol.
+//!
+//! This lets systemd know when pathfinder is actually serving (`Type=notify`), and lets it restart
+//! pathfinder if it stops making progress (`WatchdogSec=`). Both are no-ops if pathfinder is not
+//! running under systemd.
+use std::future::Future;
+use std::time::Duration;
+
+/// Notifies systemd that startup is complete.
+pub fn notify_ready() {
+    notify("READY=1");
+}
+
+/// Notifies systemd that pathfinder is shutting down.
+pub fn notify_stopping() {
+    notify("STOPPING=1");
+}
+
+/// Sends `state` to the systemd notification socket, if one is configured.
+fn notify(state: &str) {
+    let socket = match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => socket,
+        None => return,
+    };
+
+    if let Err(e) = notify_socket(std::path::Path::new(&socket), state) {
+        tracing::warn!(error=%e, %state, "Failed to notify systemd");
+    }
+}
+
+#[cfg(unix)]
+fn notify_socket(socket: &std::path::Path, state: &str) -> std::io::Result<()> {
+    use std::os::unix::net::UnixDatagram;
+
+    let datagram = UnixDatagram::unbound()?;
+    datagram.send_to(state.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn notify_socket(_socket: &std::path::Path, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Returns the interval at which the watchdog should be notified, if systemd has enabled it
+/// for this process.
+///
+/// This is half of the configured watchdog timeout, as recommended by systemd.
+pub fn watchdog_interval() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok();
+    let pid = std::env::var("WATCHDOG_PID").ok();
+
+    parse_watchdog_interval(usec.as_deref(), pid.as_deref(), std::process::id())
+}
+
+fn parse_watchdog_interval(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    // The watchdog is meant for a different process.
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+
+    let usec = usec?.parse::<u64>().ok().filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec) / 2)
+}
+
+/// Notifies the systemd watchdog every `interval`, but only if `probe` succeeds.
+///
+/// `probe` should check that pathfinder is still making progress, so that systemd restarts
+/// it if, for example, the main loop deadlocks. A probe which fails or does not complete
+/// within `interval` skips that heartbeat.
+pub async fn watchdog<F, Fut>(interval: Duration, mut probe: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        match tokio::time::timeout(interval, probe()).await {
+            Ok(Ok(())) => notify("WATCHDOG=1"),
+            Ok(Err(e)) => {
+                tracing::warn!(error=%e, "Liveness probe failed, skipping watchdog heartbeat")
+            }
+            Err(_) => tracing::warn!("Liveness probe timed out, skipping watchdog heartbeat"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchdog_interval() {
+        assert_eq!(
+            parse_watchdog_interval(Some("10000000"), None, 1),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("10000000"), Some("1"), 1),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            parse_watchdog_interval(Some("10000000"), Some("2"), 1),
+            None
+        );
+        assert_eq!(parse_watchdog_interval(Some("0"), None, 1), None);
+        assert_eq!(parse_watchdog_interval(Some("invalid"), None, 1), None);
+        assert_eq!(parse_watchdog_interval(None, None, 1), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn notify_socket() {
+        use std::os::unix::net::UnixDatagram;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        super::notify_socket(&path, "READY=1").unwrap();
+
+        let mut buf = [0u8; 16];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+}
@@ -0,0 +1,151 @@
+//! Continuously returns the free space of the database to the filesystem.
+//!
+//! Space is released in small steps with pauses in between, since every step holds the write lock
+//! of the database and would otherwise stall sync.
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_storage::vacuum::{self, DatabaseSize};
+use pathfinder_storage::Storage;
+
+/// Pages released per step, which is 4 MiB with the default page size.
+const STEP_PAGES: u64 = 1024;
+/// Pause between steps, which leaves the write lock to other writers.
+const THROTTLE: Duration = Duration::from_millis(500);
+/// Interval between checks while there is nothing to release.
+const IDLE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Switches a database created by an older version of pathfinder to incremental vacuum, which
+/// [run] requires. This rebuilds the database once, and has to be done before syncing since it
+/// blocks all writers until done.
+///
+/// Blocks for the whole rebuild, which takes a while for large databases.
+pub fn enable_incremental(storage: &Storage) -> anyhow::Result<()> {
+    let conn = storage
+        .connection()
+        .context("Creating database connection")?;
+    if vacuum::incremental_vacuum_enabled(&conn)? {
+        return Ok(());
+    }
+
+    let size = vacuum::database_size(&conn)?;
+    tracing::info!(
+        size=%size.bytes(),
+        "Rebuilding database once to return free space to the filesystem continuously, this may take a while"
+    );
+    let started = std::time::Instant::now();
+    vacuum::enable_incremental_vacuum(&conn)?;
+    tracing::info!(
+        size=%vacuum::database_size(&conn)?.bytes(),
+        elapsed=?started.elapsed(),
+        "Database rebuilt."
+    );
+
+    Ok(())
+}
+
+/// Releases free pages while the database is larger than `target_size` bytes.
+///
+/// Free pages below the target size are kept, since Sqlite reuses them for new data anyway.
+/// Returns immediately if the database does not support incremental vacuum, see
+/// [enable_incremental].
+pub async fn run(storage: Storage, target_size: u64) {
+    let enabled = storage
+        .connection()
+        .context("Creating database connection")
+        .and_then(|conn| vacuum::incremental_vacuum_enabled(&conn));
+    match enabled {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!(
+                "Free database space is not returned to the filesystem continuously, as the database does not use incremental vacuum, see --storage.rebuild-for-incremental-vacuum"
+            );
+            return;
+        }
+        Err(e) => {
+            tracing::warn!(error=%e, "Failed to check database vacuum mode");
+            return;
+        }
+    }
+
+    let mut warned = false;
+    loop {
+        let storage = storage.clone();
+        let result = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+            let conn = storage
+                .connection()
+                .context("Creating database connection")?;
+            let size = vacuum::database_size(&conn)?;
+
+            let pages = pages_to_release(size, target_size);
+            if pages > 0 {
+                vacuum::incremental_vacuum(&conn, pages)?;
+            }
+
+            Ok((size, pages))
+        })
+        .await
+        .context("Vacuum task panicked")
+        .and_then(|result| result);
+
+        match result {
+            Ok((_, pages)) if pages > 0 => {
+                tracing::trace!(%pages, "Released free database pages");
+                tokio::time::sleep(THROTTLE).await;
+            }
+            Ok((size, _)) => {
+                if size.bytes() > target_size && !warned {
+                    tracing::warn!(
+                        size=%size.bytes(),
+                        target=%target_size,
+                        "Database is larger than the target size but has no free space left to release"
+                    );
+                    warned = true;
+                }
+                tokio::time::sleep(IDLE_INTERVAL).await;
+            }
+            Err(e) => {
+                tracing::warn!(error=%e, "Failed to release free database pages");
+                tokio::time::sleep(IDLE_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// The number of free pages to release in the next step to approach `target_size`.
+fn pages_to_release(size: DatabaseSize, target_size: u64) -> u64 {
+    let excess = size.bytes().saturating_sub(target_size);
+    let excess_pages = (excess + size.page_size - 1) / size.page_size;
+
+    excess_pages.min(size.free_pages).min(STEP_PAGES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(pages: u64, free_pages: u64) -> DatabaseSize {
+        DatabaseSize {
+            page_size: 4096,
+            pages,
+            free_pages,
+        }
+    }
+
+    #[test]
+    fn pages_to_release() {
+        // Below the target size.
+        assert_eq!(super::pages_to_release(size(100, 50), 4096 * 100), 0);
+        // Releases only the excess.
+        assert_eq!(super::pages_to_release(size(100, 50), 4096 * 90), 10);
+        // Rounds up partial pages.
+        assert_eq!(super::pages_to_release(size(100, 50), 4096 * 90 - 1), 11);
+        // Limited by the free pages.
+        assert_eq!(super::pages_to_release(size(100, 5), 0), 5);
+        // Limited by the step size.
+        assert_eq!(
+            super::pages_to_release(size(10 * STEP_PAGES, 5 * STEP_PAGES), 0),
+            STEP_PAGES
+        );
+    }
+}
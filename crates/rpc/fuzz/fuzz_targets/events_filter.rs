@@ -0,0 +1,8 @@
+//! Parses arbitrary `starknet_getEvents` filters.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    pathfinder_rpc::fuzzing::parse_events_filter(data);
+});
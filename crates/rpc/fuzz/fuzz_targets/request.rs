@@ -0,0 +1,19 @@
+//! Parses arbitrary JSON-RPC requests, including the params of the requested method.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use once_cell::sync::Lazy;
+use pathfinder_rpc::fuzzing::RequestParser;
+
+static PARSER: Lazy<RequestParser> =
+    Lazy::new(|| RequestParser::new().expect("Registering the RPC methods"));
+
+const PATHS: [&str; 3] = ["/rpc/v0.2", "/rpc/v0.3", "/rpc/pathfinder/v0.1"];
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte selects the endpoint.
+    if let Some((endpoint, body)) = data.split_first() {
+        let path = PATHS[*endpoint as usize % PATHS.len()];
+        PARSER.parse(path, body);
+    }
+});
@@ -0,0 +1,504 @@
+//! Optional API keys, which let one node serve several clients with their own rate limits and
+//! sets of allowed methods.
+//!
+//! Once keys are configured, every request must carry one in the [HEADER] header. Usage is
+//! counted per key and method by the `rpc_api_key_calls_total` metric, and rejected requests by
+//! the `rpc_api_key_rejections_total` metric. Methods which are not registered are counted as
+//! `unknown`, so that clients cannot add labels to the metric.
+//!
+//! Websocket messages bypass the middleware, so their calls cannot be checked against the
+//! allowed methods and rate limit of a key. Websocket connections are therefore only accepted for
+//! keys without either.
+use std::collections::HashSet;
+use std::num::NonZeroU32;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use http::StatusCode;
+use hyper::{Body, Request, Response};
+use serde::Deserialize;
+use tower::BoxError;
+
+use crate::versioning::{read_request_body, response};
+
+/// The header carrying the API key.
+pub const HEADER: &str = "x-api-key";
+
+/// An API key, as configured in the API keys file.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Identifies the key in metrics and logs, as the key itself is secret.
+    pub name: String,
+    pub key: String,
+    /// Unlimited if not set. Each request of a batch counts separately.
+    pub requests_per_second: Option<NonZeroU32>,
+    /// Allowed methods, such as `starknet_getEvents`. All methods are allowed if not set.
+    pub methods: Option<Vec<String>>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ApiKeyError {
+    #[error("Missing or unknown API key")]
+    Unauthorized,
+    #[error("Method {0} is not allowed for this API key")]
+    MethodNotAllowed(String),
+    #[error("Rate limit of API key exceeded")]
+    RateLimited,
+    #[error("Websocket connections are not allowed for API keys with limits")]
+    WebsocketNotAllowed,
+}
+
+impl ApiKeyError {
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        match self {
+            ApiKeyError::Unauthorized => response::with_canonical_reason(StatusCode::UNAUTHORIZED),
+            ApiKeyError::MethodNotAllowed(_) | ApiKeyError::WebsocketNotAllowed => {
+                response::with_canonical_reason(StatusCode::FORBIDDEN)
+            }
+            ApiKeyError::RateLimited => {
+                response::with_canonical_reason(StatusCode::TOO_MANY_REQUESTS)
+            }
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            ApiKeyError::Unauthorized => "unauthorized",
+            ApiKeyError::MethodNotAllowed(_) => "method_not_allowed",
+            ApiKeyError::RateLimited => "rate_limited",
+            ApiKeyError::WebsocketNotAllowed => "websocket_not_allowed",
+        }
+    }
+}
+
+/// The configured API keys.
+#[derive(Debug)]
+pub struct ApiKeys {
+    tenants: Vec<Tenant>,
+    /// The methods served, which are the only method names used as metric labels.
+    registered_methods: HashSet<String>,
+}
+
+#[derive(Debug)]
+struct Tenant {
+    key: String,
+    name: String,
+    methods: Option<HashSet<String>>,
+    rate_limiter: Option<Mutex<RateLimiter>>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKeyConfig>) -> anyhow::Result<Self> {
+        let mut names = HashSet::new();
+        let mut tenants: Vec<Tenant> = Vec::new();
+
+        for config in keys {
+            anyhow::ensure!(!config.key.is_empty(), "API key {} is empty", config.name);
+            anyhow::ensure!(
+                names.insert(config.name.clone()),
+                "API key name {} is not unique",
+                config.name
+            );
+            if let Some(other) = tenants.iter().find(|tenant| tenant.key == config.key) {
+                anyhow::bail!("API key {} is also used by another key", other.name);
+            }
+
+            tenants.push(Tenant {
+                key: config.key,
+                methods: config.methods.map(|methods| methods.into_iter().collect()),
+                rate_limiter: config
+                    .requests_per_second
+                    .map(|rate| Mutex::new(RateLimiter::new(rate, Instant::now()))),
+                name: config.name,
+            });
+        }
+
+        Ok(Self {
+            tenants,
+            registered_methods: HashSet::new(),
+        })
+    }
+
+    /// Sets the methods served, without their version prefix.
+    pub(crate) fn with_registered_methods(self, methods: HashSet<String>) -> Self {
+        Self {
+            registered_methods: methods,
+            ..self
+        }
+    }
+
+    /// Reads the keys from a JSON file containing an array of [ApiKeyConfig]s.
+    pub fn from_file(path: &std::path::Path) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let file = std::fs::read(path)
+            .with_context(|| format!("Reading API keys from {}", path.display()))?;
+        let keys = serde_json::from_slice(&file).context("Parsing API keys")?;
+
+        Self::new(keys)
+    }
+
+    /// The tenant of `key`.
+    ///
+    /// Every key is compared in constant time, so that the response time does not reveal how
+    /// much of a key was guessed correctly.
+    fn tenant(&self, key: Option<&str>) -> Option<&Tenant> {
+        let key = key?;
+        let mut found = None;
+        for tenant in &self.tenants {
+            if constant_time_eq(key.as_bytes(), tenant.key.as_bytes()) {
+                found = Some(tenant);
+            }
+        }
+        found
+    }
+
+    /// Checks that websocket connections are allowed for `key`.
+    fn authorize_websocket(&self, key: Option<&str>) -> Result<(), ApiKeyError> {
+        let result = match self.tenant(key) {
+            None => Err(ApiKeyError::Unauthorized),
+            Some(tenant) if tenant.methods.is_some() || tenant.rate_limiter.is_some() => {
+                Err(ApiKeyError::WebsocketNotAllowed)
+            }
+            Some(_) => Ok(()),
+        };
+
+        if let Err(error) = &result {
+            let name = self
+                .tenant(key)
+                .map_or_else(|| "unknown".to_owned(), |tenant| tenant.name.clone());
+            metrics::increment_counter!(
+                "rpc_api_key_rejections_total",
+                "key" => name,
+                "reason" => error.reason()
+            );
+        }
+        result
+    }
+
+    /// Checks that the calls to `methods` are allowed for `key`, and counts them.
+    fn authorize(&self, key: Option<&str>, methods: &[String]) -> Result<(), ApiKeyError> {
+        let tenant = match self.tenant(key) {
+            Some(tenant) => tenant,
+            None => {
+                metrics::increment_counter!(
+                    "rpc_api_key_rejections_total",
+                    "key" => "unknown",
+                    "reason" => ApiKeyError::Unauthorized.reason()
+                );
+                return Err(ApiKeyError::Unauthorized);
+            }
+        };
+
+        let result = tenant.authorize(methods);
+        match &result {
+            Ok(()) => {
+                for method in methods {
+                    let method = if self.registered_methods.contains(method) {
+                        method.clone()
+                    } else {
+                        "unknown".to_owned()
+                    };
+                    metrics::increment_counter!(
+                        "rpc_api_key_calls_total",
+                        "key" => tenant.name.clone(),
+                        "method" => method
+                    );
+                }
+            }
+            Err(error) => metrics::increment_counter!(
+                "rpc_api_key_rejections_total",
+                "key" => tenant.name.clone(),
+                "reason" => error.reason()
+            ),
+        }
+        result
+    }
+}
+
+impl Tenant {
+    fn authorize(&self, methods: &[String]) -> Result<(), ApiKeyError> {
+        if let Some(allowed) = &self.methods {
+            if let Some(method) = methods.iter().find(|method| !allowed.contains(*method)) {
+                return Err(ApiKeyError::MethodNotAllowed(method.clone()));
+            }
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            // A request which could not be parsed still counts once.
+            let calls = methods.len().max(1) as u32;
+            let mut rate_limiter = rate_limiter.lock().unwrap();
+            if !rate_limiter.try_acquire(calls, Instant::now()) {
+                return Err(ApiKeyError::RateLimited);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `a` equals `b`, in a time which only depends on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut difference = a.len() ^ b.len();
+    for (i, x) in a.iter().enumerate() {
+        difference |= usize::from(x ^ b.get(i).copied().unwrap_or_default());
+    }
+    difference == 0
+}
+
+/// A token bucket, which allows bursts of up to one second worth of requests.
+#[derive(Debug)]
+struct RateLimiter {
+    requests_per_second: f64,
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: NonZeroU32, now: Instant) -> Self {
+        let requests_per_second = requests_per_second.get() as f64;
+        Self {
+            requests_per_second,
+            tokens: requests_per_second,
+            updated_at: now,
+        }
+    }
+
+    fn try_acquire(&mut self, calls: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.requests_per_second).min(self.requests_per_second);
+        self.updated_at = now;
+
+        if self.tokens >= calls as f64 {
+            self.tokens -= calls as f64;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Middleware which rejects requests whose API key is missing, unknown, not allowed to call the
+/// requested methods or over its rate limit. Does nothing if no keys are configured.
+pub(crate) async fn authorize(
+    request: Request<Body>,
+    api_keys: Option<&ApiKeys>,
+    max_request_body_size: u32,
+) -> Result<Request<Body>, BoxError> {
+    let api_keys = match api_keys {
+        Some(api_keys) => api_keys,
+        None => return Ok(request),
+    };
+
+    let key = request
+        .headers()
+        .get(HEADER)
+        .and_then(|key| key.to_str().ok())
+        .map(ToOwned::to_owned);
+
+    let is_websocket = request
+        .headers()
+        .get(hyper::header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        .unwrap_or_default();
+    if is_websocket {
+        api_keys.authorize_websocket(key.as_deref())?;
+        return Ok(request);
+    }
+
+    let (parts, body) = request.into_parts();
+    let (body, is_single) = read_request_body(&parts.headers, body, max_request_body_size).await?;
+
+    api_keys.authorize(key.as_deref(), &method_names(&body, is_single))?;
+
+    Ok(Request::from_parts(parts, body.into()))
+}
+
+/// The method names of a single request or a batch of requests, which are empty if the body is
+/// not a valid request.
+fn method_names(body: &[u8], is_single: bool) -> Vec<String> {
+    if is_single {
+        serde_json::from_slice::<jsonrpsee::types::Request<'_>>(body)
+            .map(|request| vec![request.method.into_owned()])
+            .unwrap_or_default()
+    } else {
+        serde_json::from_slice::<Vec<jsonrpsee::types::Request<'_>>>(body)
+            .map(|batch| {
+                batch
+                    .into_iter()
+                    .map(|request| request.method.into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn keys() -> ApiKeys {
+        let config = serde_json::from_value(serde_json::json!([
+            {
+                "name": "indexer",
+                "key": "indexer-key",
+                "requests_per_second": 2,
+                "methods": ["starknet_getEvents", "starknet_blockNumber"]
+            },
+            {
+                "name": "wallet",
+                "key": "wallet-key"
+            }
+        ]))
+        .unwrap();
+
+        ApiKeys::new(config).unwrap()
+    }
+
+    fn methods(methods: &[&str]) -> Vec<String> {
+        methods.iter().map(|method| method.to_string()).collect()
+    }
+
+    #[test]
+    fn unknown_key() {
+        let keys = keys();
+
+        assert_eq!(
+            keys.authorize(None, &methods(&["starknet_chainId"])),
+            Err(ApiKeyError::Unauthorized)
+        );
+        assert_eq!(
+            keys.authorize(Some("other-key"), &methods(&["starknet_chainId"])),
+            Err(ApiKeyError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn method_allowance() {
+        let keys = keys();
+
+        keys.authorize(Some("indexer-key"), &methods(&["starknet_getEvents"]))
+            .unwrap();
+        assert_eq!(
+            keys.authorize(
+                Some("indexer-key"),
+                &methods(&["starknet_blockNumber", "starknet_call"])
+            ),
+            Err(ApiKeyError::MethodNotAllowed("starknet_call".to_owned()))
+        );
+        keys.authorize(Some("wallet-key"), &methods(&["starknet_call"]))
+            .unwrap();
+    }
+
+    #[test]
+    fn rate_limit() {
+        let keys = keys();
+        let batch = methods(&["starknet_blockNumber", "starknet_blockNumber"]);
+
+        keys.authorize(Some("indexer-key"), &batch).unwrap();
+        assert_eq!(
+            keys.authorize(Some("indexer-key"), &methods(&["starknet_blockNumber"])),
+            Err(ApiKeyError::RateLimited)
+        );
+        // Keys are limited independently.
+        keys.authorize(Some("wallet-key"), &batch).unwrap();
+    }
+
+    #[test]
+    fn websocket() {
+        let keys = keys();
+
+        assert_eq!(
+            keys.authorize_websocket(None),
+            Err(ApiKeyError::Unauthorized)
+        );
+        // Calls over websocket could not be checked against the limits of the key.
+        assert_eq!(
+            keys.authorize_websocket(Some("indexer-key")),
+            Err(ApiKeyError::WebsocketNotAllowed)
+        );
+        keys.authorize_websocket(Some("wallet-key")).unwrap();
+    }
+
+    #[test]
+    fn key_comparison() {
+        assert!(constant_time_eq(b"key", b"key"));
+        assert!(!constant_time_eq(b"key", b"kez"));
+        assert!(!constant_time_eq(b"key", b"key-suffix"));
+        assert!(!constant_time_eq(b"key-suffix", b"key"));
+        assert!(!constant_time_eq(b"", b"key"));
+    }
+
+    #[test]
+    fn rate_limiter_refills() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(NonZeroU32::new(10).unwrap(), start);
+
+        assert!(limiter.try_acquire(10, start));
+        assert!(!limiter.try_acquire(1, start));
+        assert!(limiter.try_acquire(1, start + Duration::from_millis(100)));
+        assert!(!limiter.try_acquire(1, start + Duration::from_millis(100)));
+        // Bursts are capped at one second worth of requests.
+        assert!(!limiter.try_acquire(11, start + Duration::from_secs(60)));
+        assert!(limiter.try_acquire(10, start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let config = |name: &str, key: &str| ApiKeyConfig {
+            name: name.to_owned(),
+            key: key.to_owned(),
+            requests_per_second: None,
+            methods: None,
+        };
+
+        ApiKeys::new(vec![config("a", "key"), config("b", "key")]).unwrap_err();
+        ApiKeys::new(vec![config("a", "key"), config("a", "other-key")]).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        use crate::context::RpcContext;
+        use crate::RpcServer;
+
+        let context = RpcContext::for_tests();
+        let (_server_handle, address) = RpcServer::new("127.0.0.1:0".parse().unwrap(), context)
+            .with_api_keys(keys())
+            .run()
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let call = |key: Option<&'static str>, method: &'static str| {
+            let mut request =
+                client
+                    .post(format!("http://{address}/rpc/v0.3"))
+                    .json(&serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": 0,
+                        "method": method,
+                    }));
+            if let Some(key) = key {
+                request = request.header(HEADER, key);
+            }
+            async move { request.send().await.unwrap().status() }
+        };
+
+        assert_eq!(
+            call(None, "starknet_chainId").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call(Some("indexer-key"), "starknet_chainId").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call(Some("wallet-key"), "starknet_chainId").await,
+            StatusCode::OK
+        );
+    }
+}
@@ -0,0 +1,463 @@
+//! Optional signatures over the data in RPC responses, which let downstream services attribute
+//! the data they received to the operator of a particular node and verify it.
+//!
+//! Once an [Attestor] is configured, successful responses of the [ATTESTED_METHODS] carry an
+//! `attestation` member next to their `result`:
+//!
+//! ```json
+//! {"signer": "0x<address>", "message": "<message>", "signature": "0x<r, s and v>"}
+//! ```
+//!
+//! The message lists the node's chain, the method and the attested fields of the result, with
+//! their values as they appear in the result:
+//!
+//! ```text
+//! pathfinder attestation
+//! chain_id: 0x534e5f474f45524c49
+//! method: starknet_getStateUpdate
+//! block_hash: 0x...
+//! new_root: 0x...
+//! old_root: 0x...
+//! ```
+//!
+//! It is signed as an [EIP-191](https://eips.ethereum.org/EIPS/eip-191) personal message with
+//! the operator's secp256k1 key, so that the signer's Ethereum address can be recovered from the
+//! signature by standard Ethereum tooling. Results which lack any of the attested fields, such as
+//! pending blocks, are not attested.
+//!
+//! Websocket messages bypass the middleware, so subscriptions are not attested.
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Bytes};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hyper::{Body, Request, Response};
+use pathfinder_common::ChainId;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use tower::{BoxError, Layer, Service};
+
+use crate::versioning::read_request_body;
+
+/// The attested methods, with the fields of their results which are attested.
+pub const ATTESTED_METHODS: &[(&str, &[&str])] = &[
+    (
+        "starknet_blockHashAndNumber",
+        &["block_hash", "block_number"],
+    ),
+    (
+        "starknet_getBlockWithTxHashes",
+        &["block_hash", "block_number", "new_root"],
+    ),
+    (
+        "starknet_getBlockWithTxs",
+        &["block_hash", "block_number", "new_root"],
+    ),
+    (
+        "starknet_getStateUpdate",
+        &["block_hash", "new_root", "old_root"],
+    ),
+    (
+        "pathfinder_getProof",
+        &["state_commitment", "class_commitment"],
+    ),
+    (
+        "pathfinder_getClassProof",
+        &["state_commitment", "storage_commitment", "class_commitment"],
+    ),
+    (
+        "pathfinder_getTransactionInclusionProof",
+        &[
+            "block_hash",
+            "block_number",
+            "transaction_commitment",
+            "event_commitment",
+        ],
+    ),
+];
+
+/// Signs the attested fields of RPC responses with the operator's key.
+#[derive(Debug)]
+pub struct Attestor {
+    wallet: LocalWallet,
+    chain_id: ChainId,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Attestation {
+    signer: Address,
+    message: String,
+    signature: Bytes,
+}
+
+impl Attestor {
+    pub fn new(wallet: LocalWallet, chain_id: ChainId) -> Self {
+        Self { wallet, chain_id }
+    }
+
+    /// Reads the hex encoded private key of the operator from the file at `path`.
+    pub fn from_file(path: &std::path::Path, chain_id: ChainId) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let key = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading attestation key from {}", path.display()))?;
+        let wallet = key
+            .trim()
+            .parse::<LocalWallet>()
+            .context("Parsing attestation key")?;
+
+        Ok(Self::new(wallet, chain_id))
+    }
+
+    /// The Ethereum address of the operator's key.
+    pub fn signer(&self) -> Address {
+        self.wallet.address()
+    }
+
+    /// Attests the `result` of a call to `method`, or returns [None] if the method is not attested
+    /// or the result lacks an attested field.
+    fn attest(&self, method: &str, result: &RawValue) -> Option<Attestation> {
+        let (_, fields) = ATTESTED_METHODS.iter().find(|(name, _)| *name == method)?;
+        let result =
+            serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(result.get())
+                .ok()?;
+
+        let mut message = format!(
+            "pathfinder attestation\nchain_id: {}\nmethod: {method}",
+            self.chain_id.0.to_hex_str()
+        );
+        for field in fields.iter() {
+            let value = match result.get(*field)? {
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Number(value) => value.to_string(),
+                _ => return None,
+            };
+            message.push_str(&format!("\n{field}: {value}"));
+        }
+
+        let signature = self.wallet.sign_hash(ethers::utils::hash_message(&message));
+
+        Some(Attestation {
+            signer: self.signer(),
+            message,
+            signature: signature.to_vec().into(),
+        })
+    }
+
+    /// Adds attestations to the successful calls of a `response` to `request`, or returns [None]
+    /// if none of them are attested.
+    fn attest_response(&self, request: &[u8], is_single: bool, response: &[u8]) -> Option<Vec<u8>> {
+        if is_single {
+            let call = serde_json::from_slice::<Call<'_>>(request).ok()?;
+            let response = serde_json::from_slice::<CallResponse<'_>>(response).ok()?;
+
+            return self.attest_call(&call, response);
+        }
+
+        let calls = serde_json::from_slice::<Vec<Call<'_>>>(request).ok()?;
+        let responses = serde_json::from_slice::<Vec<&RawValue>>(response).ok()?;
+
+        let mut attested = false;
+        let responses = responses
+            .into_iter()
+            .map(|raw| {
+                let attested_response = serde_json::from_str::<CallResponse<'_>>(raw.get())
+                    .ok()
+                    .and_then(|response| {
+                        let id =
+                            serde_json::from_str::<serde_json::Value>(response.id.get()).ok()?;
+                        let call = calls.iter().find(|call| call.id == id)?;
+                        self.attest_call(call, response)
+                    });
+
+                match attested_response {
+                    Some(response) => {
+                        attested = true;
+                        Cow::Owned(response)
+                    }
+                    None => Cow::Borrowed(raw.get().as_bytes()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if !attested {
+            return None;
+        }
+
+        let mut batch = b"[".to_vec();
+        for (i, response) in responses.iter().enumerate() {
+            if i > 0 {
+                batch.push(b',');
+            }
+            batch.extend_from_slice(response);
+        }
+        batch.push(b']');
+
+        Some(batch)
+    }
+
+    fn attest_call(&self, call: &Call<'_>, response: CallResponse<'_>) -> Option<Vec<u8>> {
+        let attestation = self.attest(&call.method, response.result)?;
+
+        serde_json::to_vec(&CallResponse {
+            attestation: Some(attestation),
+            ..response
+        })
+        .ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct Call<'a> {
+    #[serde(borrow)]
+    method: Cow<'a, str>,
+    #[serde(default)]
+    id: serde_json::Value,
+}
+
+/// A successful response, which keeps its members as they were.
+#[derive(Deserialize, Serialize)]
+struct CallResponse<'a> {
+    #[serde(borrow)]
+    jsonrpc: &'a RawValue,
+    #[serde(borrow)]
+    result: &'a RawValue,
+    #[serde(borrow)]
+    id: &'a RawValue,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    attestation: Option<Attestation>,
+}
+
+#[derive(Clone)]
+pub(crate) struct AttestationLayer {
+    attestor: Option<Arc<Attestor>>,
+    max_request_body_size: u32,
+}
+
+impl AttestationLayer {
+    pub(crate) fn new(attestor: Option<Arc<Attestor>>, max_request_body_size: u32) -> Self {
+        Self {
+            attestor,
+            max_request_body_size,
+        }
+    }
+}
+
+impl<S> Layer<S> for AttestationLayer {
+    type Service = AttestationService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AttestationService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AttestationService<S> {
+    inner: S,
+    layer: AttestationLayer,
+}
+
+impl<S> Service<Request<Body>> for AttestationService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = BoxError> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // The clone is not ready, so swap it with the service which was polled ready.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let max_request_body_size = self.layer.max_request_body_size;
+
+        let is_websocket = request
+            .headers()
+            .get(hyper::header::UPGRADE)
+            .and_then(|upgrade| upgrade.to_str().ok())
+            .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+            .unwrap_or_default();
+        let attestor = match &self.layer.attestor {
+            Some(attestor) if !is_websocket => attestor.clone(),
+            _ => return inner.call(request).boxed(),
+        };
+
+        async move {
+            let (parts, body) = request.into_parts();
+            let (body, is_single) =
+                read_request_body(&parts.headers, body, max_request_body_size).await?;
+
+            let response = inner
+                .call(Request::from_parts(parts, body.clone().into()))
+                .await?;
+
+            let (mut parts, response_body) = response.into_parts();
+            let response_body = hyper::body::to_bytes(response_body).await?;
+
+            match attestor.attest_response(&body, is_single, &response_body) {
+                Some(attested) => {
+                    // Set anew from the attested body.
+                    parts.headers.remove(hyper::header::CONTENT_LENGTH);
+                    Ok(Response::from_parts(parts, attested.into()))
+                }
+                None => Ok(Response::from_parts(parts, response_body.into())),
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Signature;
+
+    fn attestor() -> Attestor {
+        let wallet = "4c0883a69102937d6231471b5decb0f4d9f6e4da5ac8b8ab8ab3bbff6c1dad4e"
+            .parse::<LocalWallet>()
+            .unwrap();
+        Attestor::new(wallet, ChainId::TESTNET)
+    }
+
+    /// Verifies the signature of an attestation and returns its message.
+    fn verify(attestation: &serde_json::Value) -> String {
+        let message = attestation["message"].as_str().unwrap().to_owned();
+        let signature = attestation["signature"].as_str().unwrap();
+        let signature = signature.parse::<Signature>().unwrap();
+        let signer = signature.recover(message.as_str()).unwrap();
+
+        assert_eq!(signer, attestor().signer());
+        assert_eq!(
+            attestation["signer"],
+            serde_json::to_value(attestor().signer()).unwrap()
+        );
+        message
+    }
+
+    #[test]
+    fn single() {
+        let request = br#"{"jsonrpc":"2.0","id":0,"method":"starknet_getStateUpdate","params":[]}"#;
+        let response = br#"{
+            "jsonrpc":"2.0",
+            "result":{"block_hash":"0x1","new_root":"0x2","old_root":"0x3","state_diff":{}},
+            "id":0
+        }"#;
+
+        let attested = attestor().attest_response(request, true, response).unwrap();
+        let attested = serde_json::from_slice::<serde_json::Value>(&attested).unwrap();
+        let original = serde_json::from_slice::<serde_json::Value>(response).unwrap();
+
+        assert_eq!(attested["result"], original["result"]);
+        assert_eq!(attested["id"], 0);
+        assert_eq!(
+            verify(&attested["attestation"]),
+            [
+                "pathfinder attestation",
+                "chain_id: 0x534e5f474f45524c49",
+                "method: starknet_getStateUpdate",
+                "block_hash: 0x1",
+                "new_root: 0x2",
+                "old_root: 0x3",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn not_attested() {
+        let attestor = attestor();
+
+        // The method is not attested.
+        let request = br#"{"jsonrpc":"2.0","id":0,"method":"starknet_chainId"}"#;
+        let response = br#"{"jsonrpc":"2.0","result":"0x534e5f474f45524c49","id":0}"#;
+        assert_eq!(attestor.attest_response(request, true, response), None);
+
+        // Pending blocks have no hash.
+        let request = br#"{"jsonrpc":"2.0","id":0,"method":"starknet_getBlockWithTxHashes"}"#;
+        let response =
+            br#"{"jsonrpc":"2.0","result":{"parent_hash":"0x1","transactions":[]},"id":0}"#;
+        assert_eq!(attestor.attest_response(request, true, response), None);
+
+        let response =
+            br#"{"jsonrpc":"2.0","error":{"code":24,"message":"Block not found"},"id":0}"#;
+        assert_eq!(attestor.attest_response(request, true, response), None);
+    }
+
+    #[test]
+    fn batch() {
+        let request = br#"[
+            {"jsonrpc":"2.0","id":"a","method":"starknet_chainId"},
+            {"jsonrpc":"2.0","id":"b","method":"starknet_blockHashAndNumber"},
+            {"jsonrpc":"2.0","id":"c","method":"starknet_blockHashAndNumber"}
+        ]"#;
+        // Responses may come in any order.
+        let response = br#"[
+            {"jsonrpc":"2.0","result":{"block_hash":"0x5","block_number":5},"id":"b"},
+            {"jsonrpc":"2.0","error":{"code":32,"message":"There are no blocks"},"id":"c"},
+            {"jsonrpc":"2.0","result":"0x534e5f474f45524c49","id":"a"}
+        ]"#;
+
+        let attested = attestor()
+            .attest_response(request, false, response)
+            .unwrap();
+        let attested = serde_json::from_slice::<serde_json::Value>(&attested).unwrap();
+        let original = serde_json::from_slice::<serde_json::Value>(response).unwrap();
+
+        assert_eq!(
+            verify(&attested[0]["attestation"]),
+            [
+                "pathfinder attestation",
+                "chain_id: 0x534e5f474f45524c49",
+                "method: starknet_blockHashAndNumber",
+                "block_hash: 0x5",
+                "block_number: 5",
+            ]
+            .join("\n")
+        );
+        assert_eq!(attested[0]["result"], original[0]["result"]);
+        assert_eq!(attested[1], original[1]);
+        assert_eq!(attested[2], original[2]);
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        use crate::context::RpcContext;
+        use crate::RpcServer;
+
+        let context = RpcContext::for_tests();
+        let (_server_handle, address) = RpcServer::new("127.0.0.1:0".parse().unwrap(), context)
+            .with_attestor(attestor())
+            .run()
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{address}/rpc/v0.3"))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "starknet_blockHashAndNumber",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+
+        let message = verify(&response["attestation"]);
+        let block_hash = response["result"]["block_hash"].as_str().unwrap();
+        assert!(message.contains(&format!("block_hash: {block_hash}")));
+    }
+}
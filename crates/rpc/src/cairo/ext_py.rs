@@ -2,8 +2,10 @@
 //!
 //! The python processes are executing `$REPO_ROOT/py/src/call.py` and communicate over by sending
 //! and receiving json + `'\n'`. Main entry point is the [`service::start`] which manages running
-//! given number of N processes. The python script uses sqlite to read pathfinder's database, which
-//! should not cause issues in WAL mode.
+//! given number of N processes. The python script uses sqlite to read pathfinder's database. Each
+//! command is executed on a snapshot of the database taken when the command starts, so long running
+//! commands see a consistent state while sync keeps writing. This requires WAL mode, as otherwise
+//! the snapshot blocks sync from writing until the command completes.
 //!
 //! Use of the call functionality happens through [`Handle::call`], which hands out futures in
 //! exchange for [`Call`] and "when" in chain, former selects the contract and method to call,
@@ -15,17 +17,30 @@
 //! global_state, and after that, calls can be made to it's `block_hash` for which we probably need
 //! to add an alternative way to use a hash directly rather as a root than assume it's a block hash.
 
+use crate::context::{DeferredClassDownload, ForkStateFetch};
 use crate::v02::types::reply::FeeEstimate;
 use crate::v02::types::request::{
     BroadcastedDeclareTransaction, BroadcastedInvokeTransaction, BroadcastedTransaction, Call,
 };
-use pathfinder_common::{CallResultValue, ClassHash, StarknetBlockTimestamp};
-use starknet_gateway_types::{reply::PendingStateUpdate, request::add_transaction};
+use pathfinder_common::{
+    CallResultValue, ClassHash, ContractAddress, EthereumAddress, EventData, EventKey, Fee,
+    GasPrice, L2ToL1MessagePayloadElem, StarknetBlockHash, StarknetBlockTimestamp,
+    StarknetTransactionIndex, StorageAddress, TransactionVersion,
+};
+use starknet_gateway_types::reply::transaction::{
+    self as gateway, Event, InvokeTransaction, L2ToL1Message, Receipt,
+};
+use starknet_gateway_types::reply::{PendingBlock, PendingStateUpdate};
+use starknet_gateway_types::request::add_transaction;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot, Mutex};
 
 mod de;
 
+/// The version of cairo-lang which the Python processes require, see `EXPECTED_CAIRO_VERSION` in
+/// `call.py`.
+pub const CAIRO_LANG_VERSION: &str = "0.11.0";
+
 use de::ErrorKind;
 
 mod ser;
@@ -33,13 +48,21 @@ mod ser;
 use ser::UsedChain;
 pub use ser::{BlockHashNumberOrLatest, Pending};
 
+mod memory;
+
+pub use memory::MemoryLimit;
+
+mod pending;
+
+pub use pending::LocalPending;
+
 mod sub_process;
 
 mod service;
 
-pub use service::start;
+pub use service::{start, start_forked};
 
-use self::types::TransactionSimulation;
+use self::types::{TransactionExecution, TransactionSimulation, TransactionTrace};
 
 pub mod types;
 
@@ -48,9 +71,62 @@ pub mod types;
 pub struct Handle {
     command_tx: mpsc::Sender<(Command, tracing::Span)>,
     chain: UsedChain,
+    deferred_class_download: SharedDeferredClassDownload,
+    fork_state_fetch: SharedForkStateFetch,
+    memory_limit: SharedMemoryLimit,
 }
 
 impl Handle {
+    /// Downloads the deferred class definitions the commands need instead of failing them.
+    ///
+    /// Applies to all clones of this handle.
+    pub fn set_deferred_class_download(&self, download: DeferredClassDownload) {
+        let mut g = self
+            .deferred_class_download
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *g = Some(download);
+    }
+
+    /// Fetches the state of the forked block the commands need instead of failing them.
+    ///
+    /// Applies to all clones of this handle, and only has an effect if the executors were
+    /// started with [`start_forked`].
+    pub fn set_fork_state_fetch(&self, fetch: ForkStateFetch) {
+        let mut g = self
+            .fork_state_fetch
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *g = Some(fetch);
+    }
+
+    /// Caps the memory which the commands executing at once take in total, queuing commands until
+    /// earlier ones complete instead of exceeding it.
+    ///
+    /// Applies to all clones of this handle.
+    pub fn set_memory_limit(&self, limit: MemoryLimit) {
+        let mut g = self.memory_limit.lock().unwrap_or_else(|e| e.into_inner());
+        *g = Some(limit);
+    }
+
+    /// Reserves the memory a command takes, as [estimated](MemoryLimit::estimate) from its
+    /// `transactions`, until the returned permit is dropped.
+    async fn reserve_memory(
+        &self,
+        transactions: &[TransactionAndClassHashHint],
+    ) -> Result<Option<tokio::sync::OwnedSemaphorePermit>, CallFailure> {
+        let limit = self
+            .memory_limit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        match limit {
+            Some(limit) => limit.reserve(limit.estimate(transactions)).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
     /// Execute the given call on the python cairo-lang executors.
     pub async fn call(
         &self,
@@ -64,6 +140,8 @@ impl Handle {
 
         let continued_span = tracing::info_span!("ext_py_call", pid = Empty);
 
+        let _memory = self.reserve_memory(&[]).await?;
+
         self.command_tx
             .send((
                 Command::Call {
@@ -106,6 +184,8 @@ impl Handle {
             .map(map_tx)
             .collect::<Result<Vec<_>, _>>()?;
 
+        let _memory = self.reserve_memory(&transactions).await?;
+
         self.command_tx
             .send((
                 Command::EstimateFee {
@@ -146,6 +226,8 @@ impl Handle {
             transactions.into_iter().map(map_tx).collect();
         let transactions = transactions?;
 
+        let _memory = self.reserve_memory(&transactions).await?;
+
         self.command_tx
             .send((
                 Command::SimulateTransaction {
@@ -168,6 +250,241 @@ impl Handle {
             Err(_closed) => Err(CallFailure::Shutdown),
         }
     }
+
+    /// Executes the transactions of the pending block on top of its parent block, returning their
+    /// receipts.
+    ///
+    /// Only invoke and deploy account transactions can be executed, as the other transactions
+    /// cannot be sent to the python executors. Execution therefore stops at the first other
+    /// transaction, as the transactions after it may depend on its effects, and only the receipts
+    /// of the transactions before it are returned.
+    pub async fn execute_pending_block(
+        &self,
+        block: &PendingBlock,
+    ) -> Result<Vec<Receipt>, CallFailure> {
+        use tracing::field::Empty;
+
+        let transactions = block
+            .transactions
+            .iter()
+            .map_while(|tx| map_gateway_tx(tx).ok())
+            .collect::<Vec<_>>();
+
+        // An empty output could not be told apart from the output of a call.
+        if transactions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut gas_price = ethers::types::H256::zero();
+        gas_price.0[16..].copy_from_slice(&block.gas_price.0.to_be_bytes());
+
+        let (response, rx) = oneshot::channel();
+
+        let continued_span = tracing::info_span!("ext_py_exec_pending", pid = Empty);
+
+        let _memory = self.reserve_memory(&transactions).await?;
+        let transactions_len = transactions.len();
+
+        self.command_tx
+            .send((
+                Command::ExecuteTransactions {
+                    transactions,
+                    at_block: BlockHashNumberOrLatest::Hash(block.parent_hash),
+                    gas_price: GasPriceSource::Current(gas_price),
+                    chain: self.chain,
+                    block_timestamp: block.timestamp,
+                    response,
+                },
+                continued_span,
+            ))
+            .await
+            .map_err(|_| CallFailure::Shutdown)?;
+
+        let executions = match rx.await {
+            Ok(x) => x?,
+            Err(_closed) => return Err(CallFailure::Shutdown),
+        };
+
+        if executions.len() != transactions_len {
+            return Err(CallFailure::Internal("Missing transaction executions"));
+        }
+
+        let receipts = executions
+            .into_iter()
+            .zip(&block.transactions)
+            .enumerate()
+            .map(|(index, (execution, transaction))| {
+                receipt(execution, transaction.hash(), index as u64)
+            })
+            .collect();
+
+        Ok(receipts)
+    }
+
+    /// Replays the transactions of a block on top of its parent block, returning their traces.
+    ///
+    /// Only invoke and deploy account transactions can be replayed, as the other transactions
+    /// cannot be sent to the python executors.
+    pub async fn trace_block(
+        &self,
+        parent_hash: StarknetBlockHash,
+        gas_price: GasPrice,
+        timestamp: StarknetBlockTimestamp,
+        transactions: &[gateway::Transaction],
+    ) -> Result<Vec<TransactionTrace>, CallFailure> {
+        use tracing::field::Empty;
+
+        let transactions = transactions
+            .iter()
+            .map(map_gateway_tx)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // An empty output could not be told apart from the output of a call.
+        if transactions.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut price = ethers::types::H256::zero();
+        price.0[16..].copy_from_slice(&gas_price.0.to_be_bytes());
+
+        let (response, rx) = oneshot::channel();
+
+        let continued_span = tracing::info_span!("ext_py_trace_block", pid = Empty);
+
+        let _memory = self.reserve_memory(&transactions).await?;
+
+        self.command_tx
+            .send((
+                Command::SimulateTransaction {
+                    transactions,
+                    at_block: BlockHashNumberOrLatest::Hash(parent_hash),
+                    gas_price: GasPriceSource::Current(price),
+                    chain: self.chain,
+                    diffs: None,
+                    block_timestamp: Some(timestamp),
+                    response,
+                    skip_validate: false,
+                },
+                continued_span,
+            ))
+            .await
+            .map_err(|_| CallFailure::Shutdown)?;
+
+        let simulations = match rx.await {
+            Ok(x) => x?,
+            Err(_closed) => return Err(CallFailure::Shutdown),
+        };
+
+        Ok(simulations
+            .into_iter()
+            .map(|simulation| simulation.trace)
+            .collect())
+    }
+}
+
+fn receipt(
+    execution: TransactionExecution,
+    transaction_hash: pathfinder_common::StarknetTransactionHash,
+    transaction_index: u64,
+) -> Receipt {
+    let events = execution
+        .events
+        .into_iter()
+        .map(|event| Event {
+            data: event.data.into_iter().map(EventData).collect(),
+            from_address: event.from_address,
+            keys: event.keys.into_iter().map(EventKey).collect(),
+        })
+        .collect();
+
+    let l2_to_l1_messages = execution
+        .l2_to_l1_messages
+        .into_iter()
+        .map(|message| L2ToL1Message {
+            from_address: message.from_address,
+            payload: message
+                .payload
+                .into_iter()
+                .map(L2ToL1MessagePayloadElem)
+                .collect(),
+            to_address: EthereumAddress(ethers::types::H160::from_slice(
+                &message.to_address.as_be_bytes()[12..],
+            )),
+        })
+        .collect();
+
+    Receipt {
+        actual_fee: Some(Fee(execution.actual_fee)),
+        events,
+        // Not returned by the python executors, and not exposed by the RPC API either.
+        execution_resources: None,
+        l1_to_l2_consumed_message: None,
+        l2_to_l1_messages,
+        transaction_hash,
+        transaction_index: StarknetTransactionIndex::new_or_panic(transaction_index),
+        execution_status: Default::default(),
+        revert_error: None,
+    }
+}
+
+/// Maps a transaction of a block to what the python executors accept, failing for transactions
+/// which cannot be executed.
+fn map_gateway_tx(tx: &gateway::Transaction) -> Result<TransactionAndClassHashHint, CallFailure> {
+    let transaction = match tx {
+        gateway::Transaction::Invoke(InvokeTransaction::V0(tx)) => {
+            add_transaction::AddTransaction::Invoke(add_transaction::InvokeFunction {
+                version: TransactionVersion::ZERO,
+                max_fee: tx.max_fee,
+                signature: tx.signature.clone(),
+                nonce: None,
+                sender_address: tx.sender_address,
+                entry_point_selector: Some(tx.entry_point_selector),
+                calldata: tx.calldata.clone(),
+            })
+        }
+        gateway::Transaction::Invoke(InvokeTransaction::V1(tx)) => {
+            add_transaction::AddTransaction::Invoke(add_transaction::InvokeFunction {
+                version: TransactionVersion::ONE,
+                max_fee: tx.max_fee,
+                signature: tx.signature.clone(),
+                nonce: Some(tx.nonce),
+                sender_address: tx.sender_address,
+                entry_point_selector: None,
+                calldata: tx.calldata.clone(),
+            })
+        }
+        gateway::Transaction::DeployAccount(tx) => {
+            add_transaction::AddTransaction::DeployAccount(add_transaction::DeployAccount {
+                version: tx.version,
+                max_fee: tx.max_fee,
+                signature: tx.signature.clone(),
+                nonce: tx.nonce,
+                class_hash: tx.class_hash,
+                contract_address_salt: tx.contract_address_salt,
+                constructor_calldata: tx.constructor_calldata.clone(),
+            })
+        }
+        gateway::Transaction::Declare(_) => {
+            return Err(CallFailure::Internal(
+                "Declare transactions cannot be executed",
+            ))
+        }
+        gateway::Transaction::Deploy(_) => {
+            return Err(CallFailure::Internal(
+                "Deploy transactions cannot be executed",
+            ))
+        }
+        gateway::Transaction::L1Handler(_) => {
+            return Err(CallFailure::Internal(
+                "L1 handler transactions cannot be executed",
+            ))
+        }
+    };
+
+    Ok(TransactionAndClassHashHint {
+        transaction,
+        class_hash_hint: None,
+    })
 }
 
 fn map_tx(tx: BroadcastedTransaction) -> Result<TransactionAndClassHashHint, CallFailure> {
@@ -270,6 +587,8 @@ fn map_tx(tx: BroadcastedTransaction) -> Result<TransactionAndClassHashHint, Cal
 pub enum CallFailure {
     /// The requested block could not be found.
     NoSuchBlock,
+    /// The state of the requested block has been pruned.
+    StatePruned,
     /// The called top-level contract could not be found.
     NoSuchContract,
     /// The called top-level entry point could not be found.
@@ -313,10 +632,13 @@ impl From<ErrorKind> for CallFailure {
         use ErrorKind::*;
         match e {
             NoSuchBlock => CallFailure::NoSuchBlock,
+            StatePruned => CallFailure::StatePruned,
             NoSuchContract => CallFailure::NoSuchContract,
             InvalidEntryPoint => CallFailure::InvalidEntryPoint,
             InvalidSchemaVersion => CallFailure::Internal("Wrong database version"),
             InvalidCommand => CallFailure::Internal("Invalid json sent"),
+            DeferredClass => CallFailure::Internal("Class definition has not been downloaded"),
+            ForkStateMissing => CallFailure::Internal("Fork state has not been fetched"),
         }
     }
 }
@@ -325,6 +647,36 @@ impl From<ErrorKind> for CallFailure {
 /// to be.
 type SharedReceiver<T> = Arc<Mutex<mpsc::Receiver<T>>>;
 
+/// The [DeferredClassDownload] shared by all the python processes, which can be set after they
+/// have been started.
+type SharedDeferredClassDownload = Arc<std::sync::Mutex<Option<DeferredClassDownload>>>;
+
+/// The [ForkStateFetch] shared by all the python processes, which can be set after they have been
+/// started.
+type SharedForkStateFetch = Arc<std::sync::Mutex<Option<ForkStateFetch>>>;
+
+/// The [MemoryLimit] shared by all handles, which can be set after the python processes have been
+/// started.
+type SharedMemoryLimit = Arc<std::sync::Mutex<Option<MemoryLimit>>>;
+
+/// State of the forked block which the python process has not found in the database, and which
+/// has to be fetched from the remote network.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ForkState {
+    /// The class hash and nonce of a contract.
+    Contract {
+        contract_address: ContractAddress,
+    },
+    Storage {
+        contract_address: ContractAddress,
+        key: StorageAddress,
+    },
+    Class {
+        class_hash: ClassHash,
+    },
+}
+
 /// Command from outside of the module wrapped by [`Handle`] to be sent for execution in python.
 ///
 /// The used chain is tagged along not to require knowledge of it at the callers of [`Handle`] but to
@@ -360,6 +712,15 @@ enum Command {
         block_timestamp: Option<StarknetBlockTimestamp>,
         response: oneshot::Sender<Result<Vec<TransactionSimulation>, CallFailure>>,
     },
+    /// Executes the transactions in order on top of `at_block`, as if they were in the next block.
+    ExecuteTransactions {
+        transactions: Vec<TransactionAndClassHashHint>,
+        at_block: BlockHashNumberOrLatest,
+        gas_price: GasPriceSource,
+        chain: UsedChain,
+        block_timestamp: StarknetBlockTimestamp,
+        response: oneshot::Sender<Result<Vec<TransactionExecution>, CallFailure>>,
+    },
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -375,6 +736,7 @@ impl Command {
             Call { response, .. } => response.is_closed(),
             EstimateFee { response, .. } => response.is_closed(),
             SimulateTransaction { response, .. } => response.is_closed(),
+            ExecuteTransactions { response, .. } => response.is_closed(),
         }
     }
 
@@ -386,6 +748,20 @@ impl Command {
             SimulateTransaction { response, .. } => {
                 response.send(Err(err)).map_err(|e| e.unwrap_err())
             }
+            ExecuteTransactions { response, .. } => {
+                response.send(Err(err)).map_err(|e| e.unwrap_err())
+            }
+        }
+    }
+
+    /// The transactions executed by the command, none for [Command::Call].
+    fn transactions(&self) -> &[TransactionAndClassHashHint] {
+        use Command::*;
+        match self {
+            Call { .. } => &[],
+            EstimateFee { transactions, .. }
+            | SimulateTransaction { transactions, .. }
+            | ExecuteTransactions { transactions, .. } => transactions,
         }
     }
 
@@ -395,6 +771,7 @@ impl Command {
             Call { response, .. } => response.closed().await,
             EstimateFee { response, .. } => response.closed().await,
             SimulateTransaction { response, .. } => response.closed().await,
+            ExecuteTransactions { response, .. } => response.closed().await,
         }
     }
 }
@@ -464,9 +841,9 @@ mod tests {
     use pathfinder_common::{
         felt, felt_bytes, CallParam, CallResultValue, Chain, ClassCommitment, ClassHash,
         ContractAddress, ContractAddressSalt, ContractNonce, ContractRoot, ContractStateHash,
-        EntryPoint, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
-        StarknetBlockTimestamp, StateCommitment, StorageAddress, StorageCommitment, StorageValue,
-        TransactionVersion,
+        EntryPoint, Fee, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
+        StarknetBlockTimestamp, StarknetTransactionHash, StateCommitment, StorageAddress,
+        StorageCommitment, StorageValue, TransactionVersion,
     };
     use pathfinder_storage::{
         ContractCodeTable, ContractsStateTable, JournalMode, StarknetBlock, StarknetBlocksTable,
@@ -494,7 +871,11 @@ mod tests {
 
         let err = launch_python(
             db_file.path().into(),
+            None,
             work_rx.into(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             status_tx,
             shutdown_rx,
         )
@@ -737,6 +1118,134 @@ mod tests {
         jh.await.unwrap();
     }
 
+    #[test_log::test(tokio::test)]
+    async fn execute_pending_block() {
+        use starknet_gateway_types::reply::transaction::{DeployAccountTransaction, Transaction};
+        use starknet_gateway_types::reply::{PendingBlock, Status};
+
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+
+        let s = Storage::migrate(PathBuf::from(db_file.path()), JournalMode::WAL).unwrap();
+
+        let mut conn = s.connection().unwrap();
+        conn.execute("PRAGMA foreign_keys = off", []).unwrap();
+
+        let tx = conn.transaction().unwrap();
+
+        let account_contract_class_hash = deploy_account_contract_in_block_one(&tx);
+
+        tx.commit().unwrap();
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let (handle, jh) = super::start(
+            PathBuf::from(db_file.path()),
+            std::num::NonZeroUsize::new(1).unwrap(),
+            async move {
+                let _ = shutdown_rx.await;
+            },
+            Chain::Testnet,
+        )
+        .await
+        .unwrap();
+
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"deploy account"));
+        let block = PendingBlock {
+            gas_price: GasPrice(1),
+            parent_hash: StarknetBlockHash(felt_bytes!(b"some blockhash somewhere")),
+            sequencer_address: SequencerAddress(Felt::ZERO),
+            status: Status::Pending,
+            timestamp: StarknetBlockTimestamp::new_or_panic(2),
+            transaction_receipts: Vec::new(),
+            transactions: vec![Transaction::DeployAccount(DeployAccountTransaction {
+                contract_address: ContractAddress::new_or_panic(felt!("0x1")),
+                transaction_hash,
+                // No fee is charged, as there is no fee token contract.
+                max_fee: Fee(Felt::ZERO),
+                version: TransactionVersion::ONE,
+                signature: Default::default(),
+                nonce: super::Call::DEFAULT_NONCE,
+                contract_address_salt: ContractAddressSalt(Felt::ZERO),
+                constructor_calldata: vec![],
+                class_hash: account_contract_class_hash,
+            })],
+            starknet_version: None,
+        };
+
+        let receipts = handle.execute_pending_block(&block).await.unwrap();
+
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].transaction_hash, transaction_hash);
+        assert_eq!(receipts[0].actual_fee, Some(Fee(felt!("0xc18"))));
+        assert_eq!(receipts[0].events, vec![]);
+
+        shutdown_tx.send(()).unwrap();
+
+        jh.await.unwrap();
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn trace_block() {
+        use starknet_gateway_types::reply::transaction::{DeployAccountTransaction, Transaction};
+
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+
+        let s = Storage::migrate(PathBuf::from(db_file.path()), JournalMode::WAL).unwrap();
+
+        let mut conn = s.connection().unwrap();
+        conn.execute("PRAGMA foreign_keys = off", []).unwrap();
+
+        let tx = conn.transaction().unwrap();
+
+        let account_contract_class_hash = deploy_account_contract_in_block_one(&tx);
+
+        tx.commit().unwrap();
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let (handle, jh) = super::start(
+            PathBuf::from(db_file.path()),
+            std::num::NonZeroUsize::new(1).unwrap(),
+            async move {
+                let _ = shutdown_rx.await;
+            },
+            Chain::Testnet,
+        )
+        .await
+        .unwrap();
+
+        let transactions = vec![Transaction::DeployAccount(DeployAccountTransaction {
+            contract_address: ContractAddress::new_or_panic(felt!("0x1")),
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"deploy account")),
+            // No fee is charged, as there is no fee token contract.
+            max_fee: Fee(Felt::ZERO),
+            version: TransactionVersion::ONE,
+            signature: Default::default(),
+            nonce: super::Call::DEFAULT_NONCE,
+            contract_address_salt: ContractAddressSalt(Felt::ZERO),
+            constructor_calldata: vec![],
+            class_hash: account_contract_class_hash,
+        })];
+
+        let traces = handle
+            .trace_block(
+                StarknetBlockHash(felt_bytes!(b"some blockhash somewhere")),
+                GasPrice(1),
+                StarknetBlockTimestamp::new_or_panic(2),
+                &transactions,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(traces.len(), 1);
+        assert!(traces[0].validate_invocation.is_some());
+        assert!(traces[0].function_invocation.is_some());
+
+        shutdown_tx.send(()).unwrap();
+
+        jh.await.unwrap();
+    }
+
     #[test_log::test(tokio::test)]
     async fn call_with_unknown_contract() {
         let db_file = tempfile::NamedTempFile::new().unwrap();
@@ -952,7 +1461,9 @@ mod tests {
         test_contract_class_hash
     }
 
-    fn deploy_account_contract_in_block_one(tx: &rusqlite::Transaction<'_>) -> ClassHash {
+    pub(super) fn deploy_account_contract_in_block_one(
+        tx: &rusqlite::Transaction<'_>,
+    ) -> ClassHash {
         let account_contract_definition = zstd::decode_all(
             starknet_gateway_test_fixtures::zstd_compressed_contracts::DUMMY_ACCOUNT,
         )
@@ -1,8 +1,11 @@
 //! The json deserializable types
 
-use super::{types::TransactionSimulation, CallFailure, SubprocessError};
+use super::{
+    types::{TransactionExecution, TransactionSimulation},
+    CallFailure, ForkState, SubprocessError,
+};
 use crate::v02::types::reply::FeeEstimate;
-use pathfinder_common::CallResultValue;
+use pathfinder_common::{CallResultValue, ClassHash};
 
 /// The python loop currently responds with these four possibilities. An enum would be more
 /// appropriate.
@@ -18,16 +21,29 @@ pub(crate) struct ChildResponse<'a> {
     exception: Option<std::borrow::Cow<'a, str>>,
     /// Enumeration of "known errors", present when `status` is [`Status::Error`].
     kind: Option<ErrorKind>,
+    /// The class whose definition is missing, present when `kind` is [`ErrorKind::DeferredClass`].
+    #[serde(default)]
+    class_hash: Option<ClassHash>,
+    /// The fork state which is missing, present when `kind` is [`ErrorKind::ForkStateMissing`].
+    #[serde(default)]
+    missing: Option<ForkState>,
     /// The real output from the contract when `status` is [`Status::Ok`].
     #[serde(default)]
     output: Option<OutputValue>,
+    /// The peak memory allocated by the python process while executing the command, in bytes.
+    #[serde(default)]
+    memory: Option<usize>,
 }
 
-/// Deserializes either the call output value or the fee estimate.
+/// Deserializes either the call output value, the transaction executions, the fee estimate or the
+/// traces.
+///
+/// The variants are tried in order, and an empty list is always parsed as [OutputValue::Call].
 #[derive(serde::Deserialize, Debug)]
 #[serde(untagged)]
 pub(crate) enum OutputValue {
     Call(Vec<CallResultValue>),
+    Executions(Vec<TransactionExecution>),
     Fee(Vec<FeeEstimate>),
     Traces(Vec<TransactionSimulation>),
 }
@@ -36,12 +52,27 @@ impl<'a> ChildResponse<'a> {
     pub(super) fn refine(mut self) -> Result<RefinedChildResponse<'a>, SubprocessError> {
         match (&self.status, &mut self.kind, &mut self.exception) {
             (Status::Ok, None, None) => Ok(RefinedChildResponse {
+                memory: self.memory,
                 status: RefinedStatus::Ok(self.output.ok_or(SubprocessError::InvalidResponse)?),
             }),
+            (Status::Error, Some(ErrorKind::DeferredClass), None) => Ok(RefinedChildResponse {
+                memory: self.memory,
+                status: RefinedStatus::DeferredClass(
+                    self.class_hash.ok_or(SubprocessError::InvalidResponse)?,
+                ),
+            }),
+            (Status::Error, Some(ErrorKind::ForkStateMissing), None) => Ok(RefinedChildResponse {
+                memory: self.memory,
+                status: RefinedStatus::ForkStateMissing(
+                    self.missing.ok_or(SubprocessError::InvalidResponse)?,
+                ),
+            }),
             (Status::Error, x @ Some(_), None) => Ok(RefinedChildResponse {
+                memory: self.memory,
                 status: RefinedStatus::Error(x.take().unwrap()),
             }),
             (Status::Failed, None, s @ &mut Some(_)) => Ok(RefinedChildResponse {
+                memory: self.memory,
                 status: RefinedStatus::Failed(s.take().unwrap()),
             }),
             // these should not happen, so turn them into similar as serde_json errors
@@ -51,16 +82,49 @@ impl<'a> ChildResponse<'a> {
 }
 
 impl RefinedChildResponse<'_> {
+    /// Returns the state which has to be fetched before the command can be executed.
+    pub(super) fn missing_state(&self) -> Option<MissingState> {
+        match &self.status {
+            RefinedStatus::DeferredClass(class_hash) => {
+                Some(MissingState::DeferredClass(*class_hash))
+            }
+            RefinedStatus::ForkStateMissing(state) => Some(MissingState::Fork(state.clone())),
+            _ => None,
+        }
+    }
+
+    /// The peak memory allocated by the python process while executing the command, in bytes.
+    pub(super) fn memory(&self) -> Option<usize> {
+        self.memory
+    }
+
     pub(super) fn into_messages(self) -> (Status, Result<OutputValue, CallFailure>) {
         match self {
             RefinedChildResponse {
                 status: RefinedStatus::Ok(x),
+                ..
             } => (Status::Ok, Ok(x)),
             RefinedChildResponse {
                 status: RefinedStatus::Error(e),
+                ..
             } => (Status::Error, Err(CallFailure::from(e))),
+            RefinedChildResponse {
+                status: RefinedStatus::DeferredClass(_),
+                ..
+            } => (
+                Status::Error,
+                Err(CallFailure::from(ErrorKind::DeferredClass)),
+            ),
+            RefinedChildResponse {
+                status: RefinedStatus::ForkStateMissing(_),
+                ..
+            } => (
+                Status::Error,
+                Err(CallFailure::from(ErrorKind::ForkStateMissing)),
+            ),
             RefinedChildResponse {
                 status: RefinedStatus::Failed(s),
+                ..
             } => (
                 Status::Failed,
                 Err(CallFailure::ExecutionFailed(s.to_string())),
@@ -82,6 +146,12 @@ pub enum ErrorKind {
     InvalidCommand,
     #[serde(rename = "INVALID_ENTRY_POINT")]
     InvalidEntryPoint,
+    #[serde(rename = "DEFERRED_CLASS")]
+    DeferredClass,
+    #[serde(rename = "FORK_STATE_MISSING")]
+    ForkStateMissing,
+    #[serde(rename = "STATE_PRUNED")]
+    StatePruned,
 }
 
 #[derive(serde::Deserialize, PartialEq, Eq, Debug)]
@@ -100,11 +170,23 @@ pub enum Status {
 /// The format we'd prefer to process instead of [`ChildResponse`].
 pub(super) struct RefinedChildResponse<'a> {
     status: RefinedStatus<'a>,
+    memory: Option<usize>,
 }
 
 /// More sensible alternative to [`Status`].
 pub(super) enum RefinedStatus<'a> {
     Ok(OutputValue),
     Error(ErrorKind),
+    /// The definition of the class has not been downloaded yet.
+    DeferredClass(ClassHash),
+    /// The state of the forked block has not been fetched yet.
+    ForkStateMissing(ForkState),
     Failed(std::borrow::Cow<'a, str>),
 }
+
+/// State which is fetched on demand, after which the command is executed again.
+#[derive(Debug)]
+pub(super) enum MissingState {
+    DeferredClass(ClassHash),
+    Fork(ForkState),
+}
@@ -0,0 +1,203 @@
+//! Accounting of the memory which executing commands takes, so that the commands executing at
+//! once can be capped in the memory they take in total.
+//!
+//! The memory of a command is dominated by the python executor loading the classes involved, and
+//! keeping the trace, the hint processor's allocations and the returned builtin segments of each
+//! executed transaction until it has rendered the output. The executors measure the peak of these
+//! allocations for every command, and collect the garbage the command left behind before they
+//! respond, but that is only known once the command is done. So the memory of a command is
+//! [estimated](MemoryLimit::estimate) up front from what earlier commands were
+//! [measured](MemoryLimit::record) to take. Commands which would exceed the [MemoryLimit] wait
+//! for earlier commands to complete, so that a burst of simulations queues instead of running the
+//! node out of memory.
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use starknet_gateway_types::request::add_transaction::{AddTransaction, ContractDefinition};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{CallFailure, TransactionAndClassHashHint};
+
+const MIB: usize = 1024 * 1024;
+
+/// Assumed to be taken by any command, for the execution state and the database snapshot it
+/// executes on, until a call has been measured.
+const PER_COMMAND: usize = 16 * MIB;
+
+/// Assumed to be taken by each executed transaction, for its trace and the memory segments of its
+/// execution, until a transaction has been measured.
+const PER_TRANSACTION: usize = 8 * MIB;
+
+/// The smallest estimate of either, so that a run of tiny commands can't estimate them as free.
+const MIN_ESTIMATE: usize = MIB;
+
+/// How many measurements a larger estimate takes to halve the distance to smaller measurements.
+///
+/// Larger measurements are taken over at once, so that the estimates err on the side of queuing.
+const DECAY: usize = 8;
+
+/// How much larger a declared class is once decompressed and loaded than its compressed program.
+const CLASS_EXPANSION: usize = 32;
+
+/// Caps the memory taken by the commands executing at once.
+///
+/// Cheap to clone, with all clones sharing the same cap.
+#[derive(Clone, Debug)]
+pub struct MemoryLimit {
+    /// One permit per MiB.
+    permits: Arc<Semaphore>,
+    mib: u32,
+    /// The estimated memory taken by any command, in bytes.
+    per_command: Arc<AtomicUsize>,
+    /// The estimated memory taken by each executed transaction, in bytes.
+    per_transaction: Arc<AtomicUsize>,
+}
+
+impl MemoryLimit {
+    pub fn new(mib: NonZeroU32) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(mib.get() as usize)),
+            mib: mib.get(),
+            per_command: Arc::new(AtomicUsize::new(PER_COMMAND)),
+            per_transaction: Arc::new(AtomicUsize::new(PER_TRANSACTION)),
+        }
+    }
+
+    /// Waits until `bytes` of memory are available, and reserves them until the returned permit
+    /// is dropped.
+    ///
+    /// Reservations larger than the limit take all of it, so that they execute on their own
+    /// instead of never.
+    pub(super) async fn reserve(&self, bytes: usize) -> Result<OwnedSemaphorePermit, CallFailure> {
+        let mib = ((bytes + MIB - 1) / MIB).clamp(1, self.mib as usize) as u32;
+
+        if self.permits.available_permits() < mib as usize {
+            tracing::debug!(%mib, "Queuing command until enough memory is available");
+        }
+
+        self.permits
+            .clone()
+            .acquire_many_owned(mib)
+            .await
+            .map_err(|_| CallFailure::Shutdown)
+    }
+
+    /// Estimates the memory which executing the `transactions` takes, in bytes.
+    pub(super) fn estimate(&self, transactions: &[TransactionAndClassHashHint]) -> usize {
+        self.per_command.load(Ordering::Relaxed)
+            + transactions.len() * self.per_transaction.load(Ordering::Relaxed)
+            + declared_classes(transactions)
+    }
+
+    /// Improves the estimates with the `bytes` which executing the `transactions` was measured to
+    /// take.
+    ///
+    /// Calls are measured for the memory of any command, and the other commands for the memory of
+    /// each of their transactions.
+    pub(super) fn record(&self, transactions: &[TransactionAndClassHashHint], bytes: usize) {
+        if transactions.is_empty() {
+            update(&self.per_command, bytes);
+        } else {
+            let bytes = bytes
+                .saturating_sub(self.per_command.load(Ordering::Relaxed))
+                .saturating_sub(declared_classes(transactions));
+            update(&self.per_transaction, bytes / transactions.len());
+        }
+    }
+}
+
+/// Moves the `estimate` towards the `measured` bytes, see [DECAY].
+fn update(estimate: &AtomicUsize, measured: usize) {
+    let _ = estimate.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        let next = if measured >= current {
+            measured
+        } else {
+            current - (current - measured) / DECAY
+        };
+        Some(next.max(MIN_ESTIMATE))
+    });
+}
+
+/// The memory taken by loading the classes declared by the `transactions`, in bytes.
+fn declared_classes(transactions: &[TransactionAndClassHashHint]) -> usize {
+    transactions
+        .iter()
+        .map(|tx| match &tx.transaction {
+            AddTransaction::Declare(declare) => match &declare.contract_class {
+                ContractDefinition::Cairo(class) => class.program.len(),
+                ContractDefinition::Sierra(class) => class.sierra_program.len(),
+            },
+            _ => 0,
+        })
+        .sum::<usize>()
+        * CLASS_EXPANSION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn queues_until_memory_is_available() {
+        let limit = MemoryLimit::new(NonZeroU32::new(64).unwrap());
+
+        let first = limit.reserve(48 * MIB).await.unwrap();
+        let second = limit.reserve(16 * MIB).await.unwrap();
+
+        let queued = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.reserve(32 * MIB).await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!queued.is_finished());
+
+        // Only enough for it once both are released.
+        drop(second);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!queued.is_finished());
+
+        drop(first);
+        let _third = tokio::time::timeout(Duration::from_secs(5), queued)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(limit.permits.available_permits(), 32);
+    }
+
+    #[test]
+    fn estimates_follow_measurements() {
+        let limit = MemoryLimit::new(NonZeroU32::new(64).unwrap());
+        assert_eq!(limit.estimate(&[]), PER_COMMAND);
+
+        // Larger measurements are taken over at once.
+        limit.record(&[], 40 * MIB);
+        assert_eq!(limit.estimate(&[]), 40 * MIB);
+
+        // Smaller ones only gradually.
+        limit.record(&[], 0);
+        assert_eq!(limit.estimate(&[]), 35 * MIB);
+
+        for _ in 0..100 {
+            limit.record(&[], 0);
+        }
+        assert_eq!(limit.estimate(&[]), MIN_ESTIMATE);
+
+        // The estimates are shared by all clones.
+        limit.clone().record(&[], 2 * MIB);
+        assert_eq!(limit.estimate(&[]), 2 * MIB);
+    }
+
+    #[tokio::test]
+    async fn reservations_are_clamped_to_the_limit() {
+        let limit = MemoryLimit::new(NonZeroU32::new(64).unwrap());
+
+        let permit = limit.reserve(1024 * MIB).await.unwrap();
+        assert_eq!(limit.permits.available_permits(), 0);
+        drop(permit);
+
+        let _permit = limit.reserve(0).await.unwrap();
+        assert_eq!(limit.permits.available_permits(), 63);
+    }
+}
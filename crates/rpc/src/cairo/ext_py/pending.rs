@@ -0,0 +1,285 @@
+//! Serves pending blocks with the receipts of executing their transactions locally.
+use std::sync::{Arc, Mutex};
+
+use pathfinder_common::{StarknetBlockHash, StarknetBlockTimestamp};
+use starknet_gateway_types::pending::{PendingData, PendingSource, PendingStateVersion};
+use starknet_gateway_types::reply::{PendingBlock, PendingStateUpdate};
+use tokio::sync::watch;
+
+use super::{CallFailure, Handle};
+
+/// Serves the [PendingData] polled by sync, with the receipts of the pending block replaced by
+/// the receipts of executing its transactions locally, on top of the pending block's parent.
+///
+/// A pending block is executed by a background task once it is first requested, so that requests
+/// are not held up by the execution. Until then it is served with the gateway's receipts. Only the
+/// latest requested pending block is executed, so that the task never falls behind.
+///
+/// The pending block only ever grows, but is executed from scratch whenever it changes. The state
+/// update is served as polled.
+pub struct LocalPending {
+    data: PendingData,
+    blocks: watch::Sender<Option<Arc<PendingBlock>>>,
+    /// The last executed block, which is served for as long as the pending block is unchanged.
+    executed: Arc<Mutex<Option<(PendingStateVersion, Arc<PendingBlock>)>>>,
+}
+
+impl LocalPending {
+    /// Spawns the task which executes the pending blocks of `data`.
+    pub fn new(handle: Handle, data: PendingData) -> Self {
+        let (blocks, mut rx) = watch::channel(None::<Arc<PendingBlock>>);
+        let executed = Arc::new(Mutex::new(None));
+
+        tokio::spawn({
+            let executed = executed.clone();
+            async move {
+                // Ends once the source is dropped.
+                while rx.changed().await.is_ok() {
+                    let block = match rx.borrow_and_update().clone() {
+                        Some(block) => block,
+                        None => continue,
+                    };
+
+                    let version = PendingStateVersion::of(&block);
+                    let block = execute(&handle, block).await;
+                    *executed.lock().unwrap() = Some((version, block));
+                }
+            }
+        });
+
+        Self {
+            data,
+            blocks,
+            executed,
+        }
+    }
+
+    /// Returns the executed `block` if it has been executed, and otherwise schedules its
+    /// execution and returns it as is.
+    fn executed(
+        &self,
+        block: Arc<PendingBlock>,
+        version: PendingStateVersion,
+    ) -> Arc<PendingBlock> {
+        if let Some((executed_version, executed)) = self.executed.lock().unwrap().as_ref() {
+            if *executed_version == version {
+                return executed.clone();
+            }
+        }
+
+        // The receiver only goes away with the runtime.
+        self.blocks.send_if_modified(|pending| {
+            let changed = pending
+                .as_ref()
+                .map_or(true, |pending| PendingStateVersion::of(pending) != version);
+            if changed {
+                *pending = Some(block.clone());
+            }
+            changed
+        });
+
+        block
+    }
+}
+
+/// Returns the block with locally computed receipts, or the block as is if its transactions
+/// could not be executed.
+///
+/// Only the receipts of the transactions which could be executed are replaced, the receipts of
+/// the remaining transactions are the gateway's.
+async fn execute(handle: &Handle, block: Arc<PendingBlock>) -> Arc<PendingBlock> {
+    match handle.execute_pending_block(&block).await {
+        Ok(receipts) => {
+            tracing::trace!(
+                transactions=%block.transactions.len(),
+                executed=%receipts.len(),
+                "Executed pending block"
+            );
+            let mut block = block.as_ref().clone();
+            let executed = receipts.len().min(block.transaction_receipts.len());
+            block.transaction_receipts.splice(..executed, receipts);
+            Arc::new(block)
+        }
+        Err(CallFailure::Internal(reason)) => {
+            tracing::debug!(%reason, "Using the gateway's receipts for the pending block");
+            block
+        }
+        Err(e) => {
+            tracing::warn!(reason=?e, "Failed to execute pending block, using gateway receipts");
+            block
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PendingSource for LocalPending {
+    fn generation(&self) -> u64 {
+        self.data.generation()
+    }
+
+    async fn block_at(&self, generation: u64) -> Option<Arc<PendingBlock>> {
+        let block = self.data.block_at(generation).await?;
+        let version = PendingStateVersion::of(&block);
+        Some(self.executed(block, version))
+    }
+
+    async fn block(&self) -> Option<Arc<PendingBlock>> {
+        let (block, version) = self.data.block_with_version().await?;
+        Some(self.executed(block, version))
+    }
+
+    async fn block_with_version(&self) -> Option<(Arc<PendingBlock>, PendingStateVersion)> {
+        let (block, version) = self.data.block_with_version().await?;
+        Some((self.executed(block, version), version))
+    }
+
+    async fn version(&self) -> Option<PendingStateVersion> {
+        self.data.version().await
+    }
+
+    async fn state_update(&self) -> Option<Arc<PendingStateUpdate>> {
+        self.data.state_update().await
+    }
+
+    async fn state_update_on_parent_block(
+        &self,
+    ) -> Option<(
+        StarknetBlockHash,
+        StarknetBlockTimestamp,
+        Arc<PendingStateUpdate>,
+    )> {
+        self.data.state_update_on_parent_block().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt, felt_bytes, Chain, ContractAddress, ContractAddressSalt, Fee, GasPrice,
+        SequencerAddress, StarknetTransactionHash, StarknetTransactionIndex, StateCommitment,
+        TransactionVersion,
+    };
+    use pathfinder_storage::{JournalMode, Storage};
+    use stark_hash::Felt;
+    use starknet_gateway_types::reply::state_update::StateDiff;
+    use starknet_gateway_types::reply::transaction::{
+        DeployAccountTransaction, Receipt, Transaction,
+    };
+    use starknet_gateway_types::reply::Status;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    #[test_log::test(tokio::test)]
+    async fn serves_executed_receipts() {
+        let db_file = tempfile::NamedTempFile::new().unwrap();
+        let storage = Storage::migrate(PathBuf::from(db_file.path()), JournalMode::WAL).unwrap();
+
+        let mut conn = storage.connection().unwrap();
+        conn.execute("PRAGMA foreign_keys = off", []).unwrap();
+        let tx = conn.transaction().unwrap();
+        let account_contract_class_hash =
+            super::super::tests::deploy_account_contract_in_block_one(&tx);
+        tx.commit().unwrap();
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let (handle, jh) = super::super::start(
+            PathBuf::from(db_file.path()),
+            std::num::NonZeroUsize::new(1).unwrap(),
+            async move {
+                let _ = shutdown_rx.await;
+            },
+            Chain::Testnet,
+        )
+        .await
+        .unwrap();
+
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"deploy account"));
+        let block = PendingBlock {
+            gas_price: GasPrice(1),
+            parent_hash: StarknetBlockHash(felt_bytes!(b"some blockhash somewhere")),
+            sequencer_address: SequencerAddress(Felt::ZERO),
+            status: Status::Pending,
+            timestamp: StarknetBlockTimestamp::new_or_panic(2),
+            transaction_receipts: vec![Receipt {
+                actual_fee: None,
+                events: vec![],
+                execution_resources: None,
+                l1_to_l2_consumed_message: None,
+                l2_to_l1_messages: vec![],
+                transaction_hash,
+                transaction_index: StarknetTransactionIndex::new_or_panic(0),
+                execution_status: Default::default(),
+                revert_error: None,
+            }],
+            transactions: vec![Transaction::DeployAccount(DeployAccountTransaction {
+                contract_address: ContractAddress::new_or_panic(felt!("0x1")),
+                transaction_hash,
+                // No fee is charged, as there is no fee token contract.
+                max_fee: Fee(Felt::ZERO),
+                version: TransactionVersion::ONE,
+                signature: Default::default(),
+                nonce: super::super::Call::DEFAULT_NONCE,
+                contract_address_salt: ContractAddressSalt(Felt::ZERO),
+                constructor_calldata: vec![],
+                class_hash: account_contract_class_hash,
+            })],
+            starknet_version: None,
+        };
+        let state_update = PendingStateUpdate {
+            old_root: StateCommitment(Felt::ZERO),
+            state_diff: StateDiff {
+                storage_diffs: Default::default(),
+                deployed_contracts: vec![],
+                old_declared_contracts: vec![],
+                declared_classes: vec![],
+                nonces: Default::default(),
+                replaced_classes: vec![],
+            },
+        };
+
+        let state_update = Arc::new(state_update);
+        let data = PendingData::default();
+        data.set(Arc::new(block), state_update.clone()).await;
+        let source = LocalPending::new(handle, data.clone());
+
+        // The first request schedules the execution, and is served the gateway's receipts.
+        let (block, version) = source.block_with_version().await.unwrap();
+        assert_eq!(block.transaction_receipts[0].actual_fee, None);
+        assert_eq!(Some(version), data.version().await);
+
+        let executed = tokio::time::timeout(Duration::from_secs(60), async {
+            loop {
+                let block = source.block().await.unwrap();
+                if block.transaction_receipts[0].actual_fee.is_some() {
+                    break block;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("The pending block should be executed");
+        assert_eq!(
+            executed.transaction_receipts[0].actual_fee,
+            Some(Fee(felt!("0xc18")))
+        );
+        assert_eq!(executed.transactions, block.transactions);
+        assert_eq!(
+            source.block_at(data.generation()).await,
+            Some(executed.clone())
+        );
+
+        // Pending data of a new head is served as polled until it is executed as well.
+        data.advance_head().await;
+        assert_eq!(source.block().await, None);
+        let mut next = executed.as_ref().clone();
+        next.parent_hash = StarknetBlockHash(felt_bytes!(b"next"));
+        next.transaction_receipts[0].actual_fee = None;
+        data.set(Arc::new(next.clone()), state_update).await;
+        assert_eq!(source.block().await, Some(Arc::new(next)));
+
+        shutdown_tx.send(()).unwrap();
+        jh.await.unwrap();
+    }
+}
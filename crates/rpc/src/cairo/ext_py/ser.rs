@@ -47,6 +47,15 @@ pub(crate) enum ChildCommand<'a> {
         transactions: &'a [TransactionAndClassHashHint],
         skip_validate: &'a bool,
     },
+    ExecuteTx {
+        #[serde(flatten)]
+        common: CommonProperties<'a>,
+
+        // zero means use the gas price from the block.
+        #[serde_as(as = "&pathfinder_serde::H256AsHexStr")]
+        gas_price: &'a ethers::types::H256,
+        transactions: &'a [TransactionAndClassHashHint],
+    },
 }
 
 #[serde_with::serde_as]
@@ -1,8 +1,11 @@
 //! Starting and maintaining processes, and the main entry point
 
-use super::{sub_process::launch_python, Command, Handle, SharedReceiver, SubProcessEvent};
+use super::{
+    sub_process::launch_python, Command, Handle, SharedDeferredClassDownload, SharedForkStateFetch,
+    SharedMemoryLimit, SharedReceiver, SubProcessEvent,
+};
 use anyhow::Context;
-use pathfinder_common::Chain;
+use pathfinder_common::{Chain, StarknetBlockNumber};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::{broadcast, mpsc, Mutex};
@@ -27,6 +30,32 @@ pub async fn start(
     count: std::num::NonZeroUsize,
     stop_flag: impl std::future::Future<Output = ()> + Send + 'static,
     chain: Chain,
+) -> anyhow::Result<(Handle, tokio::task::JoinHandle<()>)> {
+    start_inner(database_path, None, count, stop_flag, chain).await
+}
+
+/// Like [`start`], but the sub-processes execute the calls against the state of `fork_block`,
+/// which is read from the fork tables instead of the synced state.
+///
+/// State missing from the fork tables is fetched with the callback set by
+/// [`Handle::set_fork_state_fetch`].
+#[tracing::instrument(name = "ext_py", skip_all, fields(%count, %fork_block))]
+pub async fn start_forked(
+    database_path: PathBuf,
+    fork_block: StarknetBlockNumber,
+    count: std::num::NonZeroUsize,
+    stop_flag: impl std::future::Future<Output = ()> + Send + 'static,
+    chain: Chain,
+) -> anyhow::Result<(Handle, tokio::task::JoinHandle<()>)> {
+    start_inner(database_path, Some(fork_block), count, stop_flag, chain).await
+}
+
+async fn start_inner(
+    database_path: PathBuf,
+    fork_block: Option<StarknetBlockNumber>,
+    count: std::num::NonZeroUsize,
+    stop_flag: impl std::future::Future<Output = ()> + Send + 'static,
+    chain: Chain,
 ) -> anyhow::Result<(Handle, tokio::task::JoinHandle<()>)> {
     use futures::stream::StreamExt;
 
@@ -37,6 +66,9 @@ pub async fn start(
     // this will never need to become deeper
     let (child_shutdown_tx, _) = broadcast::channel(1);
     let command_rx: SharedReceiver<(Command, tracing::Span)> = Arc::new(Mutex::new(command_rx));
+    let deferred_class_download = SharedDeferredClassDownload::default();
+    let fork_state_fetch = SharedForkStateFetch::default();
+    let memory_limit = SharedMemoryLimit::default();
 
     let metrics = Metrics::register();
 
@@ -46,7 +78,11 @@ pub async fn start(
     let jh = tokio::task::spawn(
         launch_python(
             database_path.clone(),
+            fork_block,
             Arc::clone(&command_rx),
+            Arc::clone(&deferred_class_download),
+            Arc::clone(&fork_state_fetch),
+            Arc::clone(&memory_limit),
             status_tx.clone(),
             child_shutdown_tx.subscribe(),
         )
@@ -79,6 +115,9 @@ pub async fn start(
     let handle = Handle {
         command_tx: command_tx.clone(),
         chain: chain.into(),
+        deferred_class_download: Arc::clone(&deferred_class_download),
+        fork_state_fetch: Arc::clone(&fork_state_fetch),
+        memory_limit: Arc::clone(&memory_limit),
     };
 
     let jh = tokio::task::spawn(
@@ -136,7 +175,11 @@ pub async fn start(
                     let jh = tokio::task::spawn(
                         launch_python(
                             database_path.clone(),
+                            fork_block,
                             Arc::clone(&command_rx),
+                            Arc::clone(&deferred_class_download),
+                            Arc::clone(&fork_state_fetch),
+                            Arc::clone(&memory_limit),
                             status_tx.clone(),
                             child_shutdown_tx.subscribe(),
                         )
@@ -1,11 +1,15 @@
 //! Launching and communication with the subprocess
 
 use super::{
-    de::{ChildResponse, OutputValue, RefinedChildResponse, Status},
+    de::{ChildResponse, MissingState, OutputValue, RefinedChildResponse, Status},
     ser::{ChildCommand, CommonProperties},
-    CallFailure, Command, SharedReceiver, SubProcessEvent, SubprocessError, SubprocessExitReason,
+    CallFailure, Command, MemoryLimit, SharedDeferredClassDownload, SharedForkStateFetch,
+    SharedMemoryLimit, SharedReceiver, SubProcessEvent, SubprocessError, SubprocessExitReason,
 };
+use crate::context::{DeferredClassDownload, ForkStateFetch};
 use anyhow::Context;
+use pathfinder_common::StarknetBlockNumber;
+use starknet_gateway_types::reply::PendingStateUpdate;
 use std::path::PathBuf;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, ChildStdin, ChildStdout};
@@ -20,22 +24,31 @@ use tracing::{debug, error, info, trace, warn, Instrument};
 /// Launching happens in two stages, similar to the python process. Initially we only launch, then
 /// read `"ready\n"` from the subprocess and after that enter the loop where we contend for the
 /// commands.
+///
+/// With `fork_block`, the subprocess executes the commands against the state of that block in the
+/// fork tables.
 #[tracing::instrument(name = "subproc", skip_all, fields(pid))]
 pub(super) async fn launch_python(
     database_path: PathBuf,
+    fork_block: Option<StarknetBlockNumber>,
     commands: SharedReceiver<(Command, tracing::Span)>,
+    deferred_class_download: SharedDeferredClassDownload,
+    fork_state_fetch: SharedForkStateFetch,
+    memory_limit: SharedMemoryLimit,
     status_updates: mpsc::Sender<SubProcessEvent>,
     mut shutdown_rx: broadcast::Receiver<()>,
 ) -> anyhow::Result<(u32, Option<std::process::ExitStatus>, SubprocessExitReason)> {
     let current_span = std::sync::Arc::new(std::sync::Mutex::new(tracing::Span::none()));
 
-    let (mut child, pid, mut stdin, mut stdout, mut buffer) =
-        match spawn(database_path, std::sync::Arc::clone(&current_span)).await {
-            Ok(tuple) => tuple,
-            Err(e) => {
-                return Err(e.context("Failed to start python subprocess"));
-            }
-        };
+    let span = std::sync::Arc::clone(&current_span);
+    let spawned = spawn(database_path, fork_block, span).await;
+
+    let (mut child, pid, mut stdin, mut stdout, mut buffer) = match spawned {
+        Ok(tuple) => tuple,
+        Err(e) => {
+            return Err(e.context("Failed to start python subprocess"));
+        }
+    };
 
     if status_updates
         .send(SubProcessEvent::ProcessLaunched(pid))
@@ -85,10 +98,26 @@ pub(super) async fn launch_python(
 
         span.record("pid", pid);
 
+        let download = deferred_class_download
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let fetch = fork_state_fetch
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let limit = memory_limit
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
         {
             let op = process(
                 &current_span,
                 command,
+                download,
+                fetch,
+                limit,
                 &mut command_buffer,
                 &mut stdin,
                 &mut stdout,
@@ -168,12 +197,14 @@ pub(super) async fn launch_python(
 
 async fn spawn(
     database_path: PathBuf,
+    fork_block: Option<StarknetBlockNumber>,
     current_span: std::sync::Arc<std::sync::Mutex<tracing::Span>>,
 ) -> anyhow::Result<(Child, u32, ChildStdin, BufReader<ChildStdout>, String)> {
     // FIXME: use choom, add something over /proc/self/oom_score_adj ?
     let mut command = tokio::process::Command::new("pathfinder_python_worker");
     command
         .arg(database_path)
+        .args(fork_block.map(|block| block.get().to_string()))
         .stdin(std::process::Stdio::piped())
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
@@ -305,8 +336,19 @@ async fn spawn(
     Ok((child, pid, stdin, stdout, buffer))
 }
 
+/// How many deferred class definitions are downloaded for a single command before giving up.
+const MAX_DEFERRED_CLASS_DOWNLOADS: usize = 16;
+/// How many pieces of fork state are fetched for a single command before giving up.
+///
+/// Each storage slot a command reads is fetched separately, so this is much higher than
+/// [`MAX_DEFERRED_CLASS_DOWNLOADS`].
+const MAX_FORK_STATE_FETCHES: usize = 1000;
+
 /// Process a single command with the external process.
 ///
+/// The command is retried after downloading any deferred class definition or fetching any fork
+/// state it needs.
+///
 /// Returns:
 /// - Ok(_) on succesful completion
 /// - Err(None) if nothing was done
@@ -314,6 +356,9 @@ async fn spawn(
 async fn process(
     current_span: &std::sync::Mutex<tracing::Span>,
     mut command: Command,
+    deferred_class_download: Option<DeferredClassDownload>,
+    fork_state_fetch: Option<ForkStateFetch>,
+    memory_limit: Option<MemoryLimit>,
     command_buffer: &mut Vec<u8>,
     stdin: &mut ChildStdin,
     stdout: &mut BufReader<ChildStdout>,
@@ -392,6 +437,29 @@ async fn process(
             transactions,
             skip_validate,
         },
+        Command::ExecuteTransactions {
+            transactions,
+            at_block,
+            gas_price,
+            chain,
+            block_timestamp,
+            ..
+        } => {
+            // The transactions are executed on top of the block itself, without pending state.
+            let no_diffs: Option<&PendingStateUpdate> = None;
+            ChildCommand::ExecuteTx {
+                common: CommonProperties {
+                    at_block,
+                    chain: *chain,
+                    pending_updates: no_diffs.into(),
+                    pending_deployed: no_diffs.into(),
+                    pending_nonces: no_diffs.into(),
+                    pending_timestamp: block_timestamp.get(),
+                },
+                gas_price: gas_price.as_price(),
+                transactions,
+            }
+        }
     };
 
     let mut cursor = std::io::Cursor::new(command_buffer);
@@ -404,59 +472,102 @@ async fn process(
 
     let command_buffer = cursor.into_inner();
 
-    // using tokio::select to race against the shutdown_rx requires additional block to release
-    // the &mut borrow on buffer to have it printed/logged
-    let res = {
-        // AsyncWriteExt::write_all used in the rpc_round is not cancellation safe, but
-        // similar to above, if we lose the race, will kill the subprocess and get out.
-        let rpc_op = rpc_round(command_buffer, stdin, stdout, buffer);
-        tokio::pin!(rpc_op);
-
-        tokio::select! {
-            res = &mut rpc_op => res,
-            // no need to await for child dying here, because the event would close the childs
-            // stdout and thus break our read_line and thus return a SubprocessError::IO and
-            // we'd break out.
-            _ = command.closed() => {
-                // attempt to guard against a call that essentially freezes up the python for
-                // how many minutes. by keeping our eye on this, we'll give the caller a
-                // chance to set timeouts, which will drop the futures.
-                //
-                // breaking out here will end up killing the python. it's probably the safest
-                // way to not cancel processing, because you can can't rely on SIGINT not being
-                // handled in a `expect Exception:` branch.
-                return Err(Some(SubprocessExitReason::Cancellation));
+    let mut downloads = 0;
+    let mut fetches = 0;
+
+    let (status, output, memory) = loop {
+        // using tokio::select to race against the shutdown_rx requires additional block to release
+        // the &mut borrow on buffer to have it printed/logged
+        let res = {
+            // AsyncWriteExt::write_all used in the rpc_round is not cancellation safe, but
+            // similar to above, if we lose the race, will kill the subprocess and get out.
+            let rpc_op = rpc_round(command_buffer, stdin, stdout, buffer);
+            tokio::pin!(rpc_op);
+
+            tokio::select! {
+                res = &mut rpc_op => res,
+                // no need to await for child dying here, because the event would close the childs
+                // stdout and thus break our read_line and thus return a SubprocessError::IO and
+                // we'd break out.
+                _ = command.closed() => {
+                    // attempt to guard against a call that essentially freezes up the python for
+                    // how many minutes. by keeping our eye on this, we'll give the caller a
+                    // chance to set timeouts, which will drop the futures.
+                    //
+                    // breaking out here will end up killing the python. it's probably the safest
+                    // way to not cancel processing, because you can can't rely on SIGINT not being
+                    // handled in a `expect Exception:` branch.
+                    return Err(Some(SubprocessExitReason::Cancellation));
+                }
             }
-        }
-    };
+        };
 
-    let (status, output) = match res {
-        Ok(resp) => resp.into_messages(),
-        Err(SubprocessError::InvalidJson(error)) => {
-            // buffer still holds the response... might be good for debugging
-            // this doesn't however mess up our line at once, so no worries.
-            error!(%error, ?buffer, "Failed to parse json from subprocess");
-            (
-                Status::Failed,
-                Err(CallFailure::Internal("Invalid json received")),
-            )
-        }
-        Err(SubprocessError::InvalidResponse) => {
-            error!(?buffer, "Failed to understand parsed json from subprocess");
-            (
-                Status::Failed,
-                Err(CallFailure::Internal("Invalid json received")),
-            )
-        }
-        Err(SubprocessError::IO) => {
-            let error = CallFailure::Internal("Input/output");
-            let _ = command.fail(error);
+        let missing = res.as_ref().ok().and_then(|resp| resp.missing_state());
+        let memory = res.as_ref().ok().and_then(|resp| resp.memory());
+
+        let (status, output) = match res {
+            Ok(resp) => resp.into_messages(),
+            Err(SubprocessError::InvalidJson(error)) => {
+                // buffer still holds the response... might be good for debugging
+                // this doesn't however mess up our line at once, so no worries.
+                error!(%error, ?buffer, "Failed to parse json from subprocess");
+                (
+                    Status::Failed,
+                    Err(CallFailure::Internal("Invalid json received")),
+                )
+            }
+            Err(SubprocessError::InvalidResponse) => {
+                error!(?buffer, "Failed to understand parsed json from subprocess");
+                (
+                    Status::Failed,
+                    Err(CallFailure::Internal("Invalid json received")),
+                )
+            }
+            Err(SubprocessError::IO) => {
+                let error = CallFailure::Internal("Input/output");
+                let _ = command.fail(error);
+
+                // TODO: consider if we'd just retry; put this back into the queue?
+                return Err(Some(SubprocessExitReason::UnrecoverableIO));
+            }
+        };
+
+        let fetch = match (missing, &deferred_class_download, &fork_state_fetch) {
+            (Some(MissingState::DeferredClass(class_hash)), Some(download), _)
+                if downloads < MAX_DEFERRED_CLASS_DOWNLOADS =>
+            {
+                downloads += 1;
+                debug!(%class_hash, "Downloading deferred class definition");
+                download(class_hash)
+            }
+            (Some(MissingState::Fork(state)), _, Some(fetch))
+                if fetches < MAX_FORK_STATE_FETCHES =>
+            {
+                fetches += 1;
+                debug!(?state, "Fetching fork state");
+                fetch(state)
+            }
+            _ => break (status, output, memory),
+        };
 
-            // TODO: consider if we'd just retry; put this back into the queue?
-            return Err(Some(SubprocessExitReason::UnrecoverableIO));
+        // the python process is idle while fetching, so it can be reused if the caller leaves
+        let fetched = tokio::select! {
+            res = fetch => res,
+            _ = command.closed() => return Err(None),
+        };
+
+        if let Err(error) = fetched {
+            warn!(%error, "Failed to fetch the state missing for the command");
+            break (status, output, memory);
         }
     };
 
+    // only completed commands measure all the memory which executing them takes
+    if let (Some(limit), Some(bytes), Ok(_)) = (&memory_limit, memory, &output) {
+        // the rendered output is held once more on this side while it is parsed
+        limit.record(command.transactions(), bytes + buffer.len());
+    }
+
     // TODO: this could be pushed to Command but ...
     match (command, output) {
         (Command::Call { response, .. }, Ok(OutputValue::Call(x))) => {
@@ -468,6 +579,9 @@ async fn process(
         (Command::SimulateTransaction { response, .. }, Ok(OutputValue::Traces(x))) => {
             let _ = response.send(Ok(x));
         }
+        (Command::ExecuteTransactions { response, .. }, Ok(OutputValue::Executions(x))) => {
+            let _ = response.send(Ok(x));
+        }
         (command, Err(fail)) => {
             let _ = command.fail(fail);
         }
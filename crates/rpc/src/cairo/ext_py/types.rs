@@ -55,3 +55,29 @@ pub struct FeeEstimate {
     #[serde_as(as = "pathfinder_serde::H256AsHexStr")]
     pub overall_fee: ethers::types::H256,
 }
+
+/// The parts of a transaction receipt which executing the transaction produces.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TransactionExecution {
+    pub actual_fee: Felt,
+    pub events: Vec<ExecutedEvent>,
+    pub l2_to_l1_messages: Vec<ExecutedMessage>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutedEvent {
+    pub from_address: ContractAddress,
+    pub keys: Vec<Felt>,
+    pub data: Vec<Felt>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ExecutedMessage {
+    pub from_address: ContractAddress,
+    /// The Ethereum address of the recipient.
+    pub to_address: Felt,
+    pub payload: Vec<Felt>,
+}
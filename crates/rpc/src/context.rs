@@ -1,22 +1,58 @@
 use crate::cairo::ext_py;
+use crate::deprecation::DeprecatedCalls;
 use crate::gas_price;
+use crate::prefetch::BlockPrefetch;
 use crate::SyncState;
-use pathfinder_common::ChainId;
+use futures::future::BoxFuture;
+use pathfinder_common::{Chain, ChainId, ChainParameters, ClassHash};
 use pathfinder_storage::Storage;
-use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::pending::PendingSource;
 use std::sync::Arc;
 
 type SequencerClient = starknet_gateway_client::Client;
 
+/// Downloads and stores the definition of a class whose download was deferred by sync.
+pub type DeferredClassDownload =
+    Arc<dyn Fn(ClassHash) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// Fetches and stores state of the forked block which is missing from the fork tables.
+pub type ForkStateFetch =
+    Arc<dyn Fn(ext_py::ForkState) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+/// What `pathfinder_getNodeInfo` reports about the node which the rest of the context does not
+/// tell.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NodeIdentity {
+    /// [None] if the network is not known.
+    pub network: Option<Chain>,
+    /// Whether the node exchanges blocks with peers.
+    pub p2p: bool,
+    /// Number of blocks before the latest one whose state is kept, [None] if all state is kept.
+    pub state_retention: Option<u64>,
+}
+
 #[derive(Clone)]
 pub struct RpcContext {
     pub storage: Storage,
-    pub pending_data: Option<PendingData>,
+    pub pending_data: Option<Arc<dyn PendingSource>>,
     pub sync_status: Arc<SyncState>,
     pub chain_id: ChainId,
+    /// The constants of the chain, which are StarkNet's for [chain_id](Self::chain_id).
+    pub chain_parameters: ChainParameters,
     pub call_handle: Option<ext_py::Handle>,
     pub eth_gas_price: Option<gas_price::Cached>,
     pub sequencer: SequencerClient,
+    /// Events queries with a higher [estimated cost](pathfinder_storage::EventQueryCost) are rejected.
+    pub get_events_max_cost: Option<u64>,
+    /// Used to download deferred class definitions on first use.
+    pub deferred_class_download: Option<DeferredClassDownload>,
+    /// Serves blocks which are not stored yet to clients which request blocks in sequence.
+    pub block_prefetch: Option<BlockPrefetch>,
+    pub node_identity: NodeIdentity,
+    /// Set on read-only replicas, which reject methods that submit transactions.
+    pub read_only: bool,
+    /// Counts the calls to deprecated versions and methods for [crate::deprecation::report].
+    pub deprecated_calls: DeprecatedCalls,
 }
 
 impl RpcContext {
@@ -30,10 +66,17 @@ impl RpcContext {
             storage,
             sync_status,
             chain_id,
+            chain_parameters: ChainParameters::starknet(chain_id),
             pending_data: None,
             call_handle: None,
             eth_gas_price: None,
             sequencer,
+            get_events_max_cost: None,
+            deferred_class_download: None,
+            block_prefetch: None,
+            node_identity: NodeIdentity::default(),
+            read_only: false,
+            deprecated_calls: DeprecatedCalls::default(),
         }
     }
 
@@ -46,7 +89,6 @@ impl RpcContext {
     pub fn for_tests_on(chain: pathfinder_common::Chain) -> Self {
         assert_ne!(chain, Chain::Mainnet, "Testing on MainNet?");
 
-        use pathfinder_common::Chain;
         let chain_id = match chain {
             Chain::Mainnet => ChainId::MAINNET,
             Chain::Testnet => ChainId::TESTNET,
@@ -66,9 +108,14 @@ impl RpcContext {
         Self { storage, ..self }
     }
 
-    pub fn with_pending_data(self, pending_data: PendingData) -> Self {
+    #[cfg(test)]
+    pub fn with_sequencer(self, sequencer: SequencerClient) -> Self {
+        Self { sequencer, ..self }
+    }
+
+    pub fn with_pending_data(self, pending_data: impl PendingSource + 'static) -> Self {
         Self {
-            pending_data: Some(pending_data),
+            pending_data: Some(Arc::new(pending_data)),
             ..self
         }
     }
@@ -95,4 +142,47 @@ impl RpcContext {
             ..self
         }
     }
+
+    pub fn with_get_events_max_cost(self, max_cost: u64) -> Self {
+        Self {
+            get_events_max_cost: Some(max_cost),
+            ..self
+        }
+    }
+
+    pub fn with_deferred_class_download(self, download: DeferredClassDownload) -> Self {
+        Self {
+            deferred_class_download: Some(download),
+            ..self
+        }
+    }
+
+    pub fn with_block_prefetch(self, block_prefetch: BlockPrefetch) -> Self {
+        Self {
+            block_prefetch: Some(block_prefetch),
+            ..self
+        }
+    }
+
+    pub fn with_node_identity(self, node_identity: NodeIdentity) -> Self {
+        Self {
+            node_identity,
+            ..self
+        }
+    }
+
+    pub fn with_read_only(self) -> Self {
+        Self {
+            read_only: true,
+            ..self
+        }
+    }
+
+    /// Downloads the definition of a class whose download was deferred by sync.
+    pub async fn download_deferred_class(&self, class_hash: ClassHash) -> anyhow::Result<()> {
+        match &self.deferred_class_download {
+            Some(download) => download(class_hash).await,
+            None => anyhow::bail!("Class definition {class_hash} has not been downloaded"),
+        }
+    }
 }
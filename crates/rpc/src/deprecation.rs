@@ -0,0 +1,107 @@
+//! Tracks the calls to deprecated API versions and methods, so that operators can tell when
+//! their clients have stopped using them and the old endpoints can be disabled.
+//!
+//! Each call is counted by the `rpc_deprecated_method_calls_total` metric, labelled with the
+//! method and the served version, and [report] periodically logs a summary of the calls.
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// API versions which are deprecated in full.
+const DEPRECATED_VERSIONS: &[&str] = &["v0.2"];
+
+/// Methods which are deprecated in versions that are otherwise still supported.
+const DEPRECATED_METHODS: &[(&str, &str)] = &[("v0.3", "starknet_pendingTransactions")];
+
+/// Returns true if `method` is deprecated in the served `version`.
+pub(crate) fn is_deprecated(version: &str, method: &str) -> bool {
+    DEPRECATED_VERSIONS.contains(&version) || DEPRECATED_METHODS.contains(&(version, method))
+}
+
+/// The number of calls to each deprecated method since the last [report], by version and method.
+#[derive(Clone, Debug, Default)]
+pub struct DeprecatedCalls(Arc<Mutex<BTreeMap<(String, String), u64>>>);
+
+impl DeprecatedCalls {
+    pub(crate) fn record(&self, version: &str, method: &str) {
+        metrics::increment_counter!(
+            "rpc_deprecated_method_calls_total",
+            "method" => method.to_owned(),
+            "version" => version.to_owned()
+        );
+
+        let mut calls = self.0.lock().unwrap();
+        *calls
+            .entry((version.to_owned(), method.to_owned()))
+            .or_default() += 1;
+    }
+
+    fn take(&self) -> BTreeMap<(String, String), u64> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+/// Logs a summary of the `calls` made during each `period`.
+pub async fn report(calls: DeprecatedCalls, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    // The first tick completes immediately.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let calls = calls.take();
+        if calls.is_empty() {
+            tracing::info!(
+                ?period,
+                "No calls to deprecated RPC versions or methods during the last period"
+            );
+            continue;
+        }
+
+        let total: u64 = calls.values().sum();
+        let summary = calls
+            .iter()
+            .map(|((version, method), count)| format!("{version} {method}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        tracing::warn!(
+            ?period,
+            %total,
+            %summary,
+            "Deprecated RPC versions or methods were called during the last period"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deprecated() {
+        assert!(is_deprecated("v0.2", "starknet_chainId"));
+        assert!(is_deprecated("v0.3", "starknet_pendingTransactions"));
+        assert!(!is_deprecated("v0.3", "starknet_chainId"));
+        assert!(!is_deprecated("v0.1", "pathfinder_version"));
+    }
+
+    #[test]
+    fn calls_are_taken_per_period() {
+        let calls = DeprecatedCalls::default();
+        calls.record("v0.2", "starknet_chainId");
+        calls.record("v0.2", "starknet_chainId");
+        calls.record("v0.3", "starknet_pendingTransactions");
+
+        let expected = BTreeMap::from([
+            (("v0.2".to_owned(), "starknet_chainId".to_owned()), 2),
+            (
+                ("v0.3".to_owned(), "starknet_pendingTransactions".to_owned()),
+                1,
+            ),
+        ]);
+        assert_eq!(calls.take(), expected);
+        assert!(calls.take().is_empty());
+    }
+}
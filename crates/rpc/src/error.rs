@@ -33,10 +33,53 @@ pub enum RpcError {
     ContractError,
     #[error("Invalid contract class")]
     InvalidContractClass,
+    #[error("Class already declared")]
+    ClassAlreadyDeclared,
+    #[error("Invalid transaction nonce")]
+    InvalidTransactionNonce,
+    #[error("Max fee is smaller than the minimal transaction cost (validation plus fee transfer)")]
+    InsufficientMaxFee,
+    #[error("Account balance is smaller than the transaction's max_fee")]
+    InsufficientAccountBalance,
+    #[error("Account validation failed")]
+    ValidationFailure,
+    #[error("Compilation failed")]
+    CompilationFailed,
+    #[error("A transaction with the same hash already exists")]
+    DuplicateTransaction,
+    #[error("The compiled class hash did not match the one supplied in the transaction")]
+    CompiledClassHashMismatch,
+    #[error("The transaction version is not supported")]
+    UnsupportedTxVersion,
+    #[error("No trace available for transaction")]
+    NoTraceAvailable,
     #[error("Too many storage keys requested")]
     ProofLimitExceeded { limit: u32, requested: u32 },
     #[error("Too many keys provided in a filter")]
     TooManyKeysInFilter { limit: usize, requested: usize },
+    #[error("Events query is too expensive, use a narrower block range")]
+    EventsQueryTooExpensive {
+        blocks: u64,
+        events: u64,
+        limit: u64,
+    },
+    #[error("Invalid typed data")]
+    InvalidTypedData { reason: String },
+    #[error("Typed data is for a different chain")]
+    TypedDataChainIdMismatch {
+        expected: pathfinder_common::ChainId,
+        domain: pathfinder_common::ChainId,
+    },
+    #[error("Pending state has changed")]
+    PendingStateChanged,
+    #[error("Block range is too large")]
+    BlockRangeTooLarge { limit: u64, requested: u64 },
+    #[error("Historical state of the requested block has been pruned")]
+    StatePruned,
+    #[error("Not available on a read-only replica")]
+    ReadOnly,
+    #[error("Block does not commit to its transactions and events")]
+    NoCommitments,
     #[error(transparent)]
     Internal(anyhow::Error),
 }
@@ -45,6 +88,7 @@ impl RpcError {
     pub fn code(&self) -> i32 {
         match self {
             RpcError::FailedToReceiveTxn => 1,
+            RpcError::NoTraceAvailable => 10,
             RpcError::ContractNotFound => 20,
             RpcError::InvalidMessageSelector => 21,
             RpcError::InvalidCallData => 22,
@@ -58,10 +102,45 @@ impl RpcError {
             RpcError::TooManyKeysInFilter { .. } => 34,
             RpcError::ContractError => 40,
             RpcError::InvalidContractClass => 50,
+            RpcError::ClassAlreadyDeclared => 51,
+            RpcError::InvalidTransactionNonce => 52,
+            RpcError::InsufficientMaxFee => 53,
+            RpcError::InsufficientAccountBalance => 54,
+            RpcError::ValidationFailure => 55,
+            RpcError::CompilationFailed => 56,
+            RpcError::DuplicateTransaction => 59,
+            RpcError::CompiledClassHashMismatch => 60,
+            RpcError::UnsupportedTxVersion => 61,
             RpcError::ProofLimitExceeded { .. } => 10000,
+            RpcError::EventsQueryTooExpensive { .. } => 10001,
+            RpcError::InvalidTypedData { .. } => 10002,
+            RpcError::TypedDataChainIdMismatch { .. } => 10003,
+            RpcError::PendingStateChanged => 10004,
+            RpcError::BlockRangeTooLarge { .. } => 10005,
+            RpcError::StatePruned => 10006,
+            RpcError::ReadOnly => 10007,
+            RpcError::NoCommitments => 10008,
             RpcError::Internal(_) => jsonrpsee::types::error::ErrorCode::InternalError.code(),
         }
     }
+
+    /// Replaces the transaction rejections which the specification only defines from v0.4 on,
+    /// i.e. codes `51` to `61`, by an [internal](RpcError::Internal) error with the same message.
+    /// Used for the v0.2 and v0.3 APIs, whose clients do not expect these codes.
+    pub fn before_v04(self) -> Self {
+        match self {
+            RpcError::ClassAlreadyDeclared
+            | RpcError::InvalidTransactionNonce
+            | RpcError::InsufficientMaxFee
+            | RpcError::InsufficientAccountBalance
+            | RpcError::ValidationFailure
+            | RpcError::CompilationFailed
+            | RpcError::DuplicateTransaction
+            | RpcError::CompiledClassHashMismatch
+            | RpcError::UnsupportedTxVersion => RpcError::Internal(anyhow::anyhow!("{self}")),
+            other => other,
+        }
+    }
 }
 
 impl From<RpcError> for jsonrpsee::core::error::Error {
@@ -93,6 +172,62 @@ impl From<RpcError> for jsonrpsee::core::error::Error {
                 CallError::Custom(ErrorObject::owned(err.code(), err.to_string(), Some(data)))
                     .into()
             }
+            RpcError::EventsQueryTooExpensive {
+                blocks,
+                events,
+                limit,
+            } => {
+                #[derive(serde::Serialize)]
+                struct Data {
+                    blocks: u64,
+                    events: u64,
+                    limit: u64,
+                }
+
+                let data = Data {
+                    blocks,
+                    events,
+                    limit,
+                };
+
+                CallError::Custom(ErrorObject::owned(err.code(), err.to_string(), Some(data)))
+                    .into()
+            }
+            RpcError::InvalidTypedData { ref reason } => {
+                #[derive(serde::Serialize)]
+                struct Data<'a> {
+                    reason: &'a str,
+                }
+
+                let data = Data { reason };
+
+                CallError::Custom(ErrorObject::owned(err.code(), err.to_string(), Some(data)))
+                    .into()
+            }
+            RpcError::TypedDataChainIdMismatch { expected, domain } => {
+                #[derive(serde::Serialize)]
+                struct Data {
+                    expected: pathfinder_common::ChainId,
+                    domain: pathfinder_common::ChainId,
+                }
+
+                let data = Data { expected, domain };
+
+                CallError::Custom(ErrorObject::owned(err.code(), err.to_string(), Some(data)))
+                    .into()
+            }
+            RpcError::BlockRangeTooLarge { limit, requested } => {
+                #[derive(serde::Serialize)]
+                struct Data {
+                    limit: u64,
+                    requested: u64,
+                }
+
+                let data = Data { limit, requested };
+
+                CallError::Custom(ErrorObject::owned(err.code(), err.to_string(), Some(data)))
+                    .into()
+            }
             other => CallError::Custom(ErrorObject::owned(
                 other.code(),
                 other.to_string(),
@@ -118,6 +253,10 @@ impl From<RpcError> for jsonrpsee::core::error::Error {
 ///
 /// An `Internal` only variant can be generated using `generate_rpc_error_subset!(<enum_name>)`.
 ///
+/// Variants with data are given with their fields, which must match those of the [RpcError]
+/// variant, e.g. `generate_rpc_error_subset!(<enum_name>: TooManyKeysInFilter { limit: usize,
+/// requested: usize })`.
+///
 /// ## Specifics
 /// This macro generates the following:
 ///
@@ -179,17 +318,20 @@ macro_rules! generate_rpc_error_subset {
         generate_rpc_error_subset!(@from_def, $enum_name,);
     };
     // Main entry-point for the macro
-    ($enum_name:ident: $($subset:tt),+) => {
-        generate_rpc_error_subset!(@enum_def, $enum_name, $($subset),+);
+    //
+    // The field types of struct variants are only needed by the enum definition, so the match
+    // arms are generated from the field names alone.
+    ($enum_name:ident: $($subset:ident $({ $($field:ident: $ty:ty),* })?),+) => {
+        generate_rpc_error_subset!(@enum_def, $enum_name, $($subset $({ $($field: $ty),* })?),+);
         generate_rpc_error_subset!(@from_anyhow, $enum_name);
-        generate_rpc_error_subset!(@from_def, $enum_name, $($subset),+);
+        generate_rpc_error_subset!(@from_def, $enum_name, $($subset $({ $($field),* })?),+);
     };
     // Generates the enum definition, nothing tricky here.
-    (@enum_def, $enum_name:ident, $($subset:tt),*) => {
+    (@enum_def, $enum_name:ident, $($subset:ident $({ $($field:ident: $ty:ty),* })?),*) => {
         #[derive(Debug)]
         pub enum $enum_name {
             Internal(anyhow::Error),
-            $($subset),*
+            $($subset $({ $($field: $ty),* })?),*
         }
     };
     // Generates From<anyhow::Error>, nothing tricky here.
@@ -214,10 +356,12 @@ macro_rules! generate_rpc_error_subset {
     //
     // By pushing the arms from this level downwards, and creating the match statement at the lowest
     // level, we guarantee that only valid valid Rust will bubble back up.
-    (@from_def, $enum_name:ident, $($variants:ident),*) => {
+    //
+    // The variants are passed on as plain tokens, as struct variants are more than a single `tt`.
+    (@from_def, $enum_name:ident, $($variants:tt)*) => {
         impl From<$enum_name> for crate::error::RpcError {
             fn from(x: $enum_name) -> Self {
-                generate_rpc_error_subset!(@parse, x, $enum_name, {}, $($variants),*)
+                generate_rpc_error_subset!(@parse, x, $enum_name, {}, $($variants)*)
             }
         }
     };
@@ -228,26 +372,30 @@ macro_rules! generate_rpc_error_subset {
             $enum_name::Internal(internal) => Self::Internal(internal),
         }
     };
-    // Special case for single variant. This could probably be folded into one of the other
-    // cases but I struggled to do so correctly.
-    (@parse, $var:ident, $enum_name:ident, {$($arms:tt)*}, $variant:ident) => {
+    // Append this unit variant to arms. Continue parsing the remaining variants.
+    (@parse, $var:ident, $enum_name:ident, {$($arms:tt)*}, $variant:ident $(, $($tail:tt)*)?) => {
         generate_rpc_error_subset!(
             @parse, $var, $enum_name,
             {
                 $($arms)*
                 $enum_name::$variant => Self::$variant,
             },
+            $($($tail)*)?
         )
     };
-    // Append this variant to arms. Continue parsing the remaining variants.
-    (@parse, $var:ident, $enum_name:ident, {$($arms:tt)*}, $variant:ident, $($tail:ident),*) => {
+    // Append this struct variant to arms, moving its fields across. Continue parsing the
+    // remaining variants.
+    (
+        @parse, $var:ident, $enum_name:ident, {$($arms:tt)*},
+        $variant:ident { $($field:ident),* } $(, $($tail:tt)*)?
+    ) => {
         generate_rpc_error_subset!(
             @parse, $var, $enum_name,
             {
                 $($arms)*
-                $enum_name::$variant => Self::$variant,
+                $enum_name::$variant { $($field),* } => Self::$variant { $($field),* },
             },
-            $($tail),*
+            $($($tail)*)?
         )
     };
 }
@@ -257,6 +405,17 @@ pub(super) use generate_rpc_error_subset;
 
 #[cfg(test)]
 mod tests {
+    use super::RpcError;
+
+    #[test]
+    fn before_v04() {
+        let error = RpcError::InsufficientMaxFee.before_v04();
+        assert_eq!(error.code(), -32603);
+        assert_eq!(error.to_string(), RpcError::InsufficientMaxFee.to_string());
+
+        assert_eq!(RpcError::InvalidContractClass.before_v04().code(), 50);
+    }
+
     mod rpc_error_subset {
         use super::super::{generate_rpc_error_subset, RpcError};
         use assert_matches::assert_matches;
@@ -288,5 +447,29 @@ mod tests {
             assert_matches!(no_blocks, RpcError::NoBlocks);
             assert_matches!(contract_error, RpcError::ContractError);
         }
+
+        #[test]
+        fn struct_variant() {
+            generate_rpc_error_subset!(
+                Struct: NoBlocks,
+                TooManyKeysInFilter { limit: usize, requested: usize },
+                ContractError
+            );
+
+            let too_many_keys = RpcError::from(Struct::TooManyKeysInFilter {
+                limit: 1,
+                requested: 2,
+            });
+            let contract_error = RpcError::from(Struct::ContractError);
+
+            assert_matches!(
+                too_many_keys,
+                RpcError::TooManyKeysInFilter {
+                    limit: 1,
+                    requested: 2
+                }
+            );
+            assert_matches!(contract_error, RpcError::ContractError);
+        }
     }
 }
@@ -278,3 +278,60 @@ mod deserialization {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Generates a property test for each type, that its RPC shape is a hex string without leading
+    /// zeros which decodes to the same value.
+    macro_rules! round_trip {
+        ($as:ty => $($name:ident: $target:ty),+ $(,)?) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(value in any::<$target>()) {
+                        let json =
+                            serde_with::As::<$as>::serialize(&value, serde_json::value::Serializer)
+                                .unwrap();
+                        let hex = json.as_str().unwrap();
+                        prop_assert!(hex == "0x0" || !hex.starts_with("0x0"), "{}", hex);
+
+                        let decoded: $target = serde_with::As::<$as>::deserialize(json).unwrap();
+                        prop_assert_eq!(decoded, value);
+                    }
+                )+
+            }
+        };
+    }
+
+    round_trip!(RpcFelt =>
+        call_param: CallParam,
+        call_result_value: CallResultValue,
+        casm_hash: CasmHash,
+        chain_id: ChainId,
+        class_hash: ClassHash,
+        constructor_param: ConstructorParam,
+        contract_address_salt: ContractAddressSalt,
+        contract_nonce: ContractNonce,
+        entry_point: EntryPoint,
+        event_key: EventKey,
+        event_data: EventData,
+        l1_to_l2_message_payload_elem: L1ToL2MessagePayloadElem,
+        l2_to_l1_message_payload_elem: L2ToL1MessagePayloadElem,
+        sequencer_address: SequencerAddress,
+        sierra_hash: SierraHash,
+        block_hash: StarknetBlockHash,
+        transaction_hash: StarknetTransactionHash,
+        state_commitment: StateCommitment,
+        storage_value: StorageValue,
+        transaction_nonce: TransactionNonce,
+        transaction_signature_elem: TransactionSignatureElem,
+    );
+
+    round_trip!(RpcFelt251 =>
+        contract_address: ContractAddress,
+        storage_address: StorageAddress,
+    );
+}
@@ -0,0 +1,75 @@
+//! Entry points for the fuzz targets in `crates/rpc/fuzz`.
+//!
+//! Each entry point runs its input through the same parsing as the server, without executing
+//! any methods. Parse errors are expected and ignored, the fuzzer only looks for panics and
+//! excessive memory use.
+use std::collections::HashMap;
+
+use jsonrpsee::types::{Params, Request};
+use pathfinder_common::{Chain, ChainId};
+
+use crate::context::RpcContext;
+use crate::module::{InputParser, Module};
+
+/// Parses JSON-RPC requests into the inputs of the requested methods.
+pub struct RequestParser(HashMap<&'static str, InputParser>);
+
+impl RequestParser {
+    pub fn new() -> anyhow::Result<Self> {
+        // The context is required to register the methods, but is never used.
+        let context = RpcContext::new(
+            pathfinder_storage::Storage::in_memory()?,
+            Default::default(),
+            ChainId::TESTNET,
+            starknet_gateway_client::Client::new(Chain::Testnet)?,
+        );
+
+        let module = Module::new(context);
+        let module = crate::v02::register_methods(module)?;
+        let module = crate::v03::register_methods(module)?;
+        let module = crate::pathfinder::register_methods(module)?;
+
+        Ok(Self(module.into_input_parsers()))
+    }
+
+    /// Parses `body` as a single request or a batch of requests sent to the endpoint at `path`.
+    pub fn parse(&self, path: &str, body: &[u8]) {
+        let prefixes = match crate::versioning::method_name_prefixes(path) {
+            Some(prefixes) => prefixes,
+            None => return,
+        };
+
+        // The same check as used by jsonrpsee when reading the body.
+        let is_single = body.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'[');
+
+        let body = match crate::versioning::prefix_method_names(body, is_single, prefixes) {
+            Ok(Some(body)) => body,
+            Ok(None) | Err(_) => return,
+        };
+
+        if is_single {
+            if let Ok(request) = serde_json::from_slice::<Request<'_>>(&body) {
+                self.parse_input(&request);
+            }
+        } else if let Ok(batch) = serde_json::from_slice::<Vec<Request<'_>>>(&body) {
+            batch.iter().for_each(|request| self.parse_input(request));
+        }
+    }
+
+    fn parse_input(&self, request: &Request<'_>) {
+        if let Some(parser) = self.0.get(request.method.as_ref()) {
+            parser(Params::new(request.params.map(|params| params.get())));
+        }
+    }
+}
+
+/// Parses `data` as the input of both versions of `starknet_getEvents`, including the checks
+/// of the filter which are done before querying any events.
+pub fn parse_events_filter(data: &[u8]) {
+    if let Ok(input) = serde_json::from_slice::<crate::v02::method::GetEventsInput>(data) {
+        let _ = input.validate();
+    }
+    if let Ok(input) = serde_json::from_slice::<crate::v03::method::GetEventsInput>(data) {
+        let _ = input.validate();
+    }
+}
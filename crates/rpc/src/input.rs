@@ -0,0 +1,213 @@
+//! Builders for the inputs of RPC methods, for users of pathfinder as a library.
+//!
+//! Inputs are checked by the same validation which the server applies to deserialized requests,
+//! so that an input which the server would reject is caught when it is built rather than once it
+//! reaches the query layer. [GetEventsInput] serializes to the named parameters of
+//! `starknet_getEvents`, for clients which send it to a node, and with the `rpc-full-serde`
+//! feature [SimulateTrasactionInput] serializes to those of `starknet_simulateTransactions`.
+//!
+//! Blocks are identified by anything which converts into a [BlockId], such as a
+//! [StarknetBlockNumber](pathfinder_common::StarknetBlockNumber) or a
+//! [StarknetBlockHash](pathfinder_common::StarknetBlockHash).
+use pathfinder_common::{BlockId, ContractAddress, EventKey};
+
+pub use crate::v03::method::get_events::{EventFilter, GetEventsInput, InvalidEventFilter};
+pub use crate::v03::method::simulate_transaction::SimulateTrasactionInput;
+
+use crate::v02::types::request::BroadcastedTransaction;
+use crate::v03::method::simulate_transaction::dto::{SimulationFlag, SimulationFlags};
+
+/// Builds the [EventFilter] of `starknet_getEvents`, which matches all events until narrowed.
+#[derive(Clone, Debug)]
+pub struct EventFilterBuilder(EventFilter);
+
+impl EventFilterBuilder {
+    /// A filter which returns pages of up to `chunk_size` events.
+    pub fn new(chunk_size: usize) -> Self {
+        Self(EventFilter {
+            from_block: None,
+            to_block: None,
+            address: None,
+            keys: Vec::new(),
+            chunk_size,
+            continuation_token: None,
+        })
+    }
+
+    pub fn from_block(mut self, block: impl Into<BlockId>) -> Self {
+        self.0.from_block = Some(block.into());
+        self
+    }
+
+    pub fn to_block(mut self, block: impl Into<BlockId>) -> Self {
+        self.0.to_block = Some(block.into());
+        self
+    }
+
+    pub fn address(mut self, address: ContractAddress) -> Self {
+        self.0.address = Some(address);
+        self
+    }
+
+    /// Matches events whose next key is any of `keys`, where no keys match any key.
+    ///
+    /// The first call filters the first key of events, the second call the second key and so on.
+    pub fn keys(mut self, keys: impl IntoIterator<Item = EventKey>) -> Self {
+        self.0.keys.push(keys.into_iter().collect());
+        self
+    }
+
+    /// Continues from the page at which a previous request with the same filter stopped.
+    pub fn continuation_token(mut self, token: impl Into<String>) -> Self {
+        self.0.continuation_token = Some(token.into());
+        self
+    }
+
+    pub fn build(self) -> Result<EventFilter, InvalidEventFilter> {
+        self.0.validate()?;
+        Ok(self.0)
+    }
+}
+
+/// Builds the [SimulateTrasactionInput] of `starknet_simulateTransactions`.
+#[derive(Clone, Debug)]
+pub struct SimulationBuilder {
+    block_id: BlockId,
+    transactions: Vec<BroadcastedTransaction>,
+    skip_execute: bool,
+    skip_validate: bool,
+}
+
+impl SimulationBuilder {
+    /// Simulates on top of the state of `block`.
+    pub fn new(block: impl Into<BlockId>) -> Self {
+        Self {
+            block_id: block.into(),
+            transactions: Vec::new(),
+            skip_execute: false,
+            skip_validate: false,
+        }
+    }
+
+    /// Appends a transaction, which sees the state changes of the transactions before it.
+    pub fn transaction(mut self, transaction: BroadcastedTransaction) -> Self {
+        self.transactions.push(transaction);
+        self
+    }
+
+    pub fn skip_execute(mut self) -> Self {
+        self.skip_execute = true;
+        self
+    }
+
+    /// Does not call the account's `__validate__` entry point, so that unsigned transactions can
+    /// be simulated.
+    pub fn skip_validate(mut self) -> Self {
+        self.skip_validate = true;
+        self
+    }
+
+    pub fn build(self) -> SimulateTrasactionInput {
+        let flags = [
+            (self.skip_execute, SimulationFlag::SkipExecute),
+            (self.skip_validate, SimulationFlag::SkipValidate),
+        ];
+        let flags = flags
+            .into_iter()
+            .filter_map(|(set, flag)| set.then_some(flag))
+            .collect();
+
+        SimulateTrasactionInput {
+            block_id: self.block_id,
+            transactions: self.transactions,
+            simulation_flags: SimulationFlags(flags),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{felt, StarknetBlockNumber};
+    use serde_json::json;
+
+    #[test]
+    fn event_filter_matches_server_deserialization() {
+        let filter = EventFilterBuilder::new(10)
+            .from_block(StarknetBlockNumber::new_or_panic(1))
+            .to_block(BlockId::Latest)
+            .address(ContractAddress::new_or_panic(felt!("0x1")))
+            .keys([EventKey(felt!("0x2")), EventKey(felt!("0x3"))])
+            .keys([])
+            .continuation_token("20")
+            .build()
+            .unwrap();
+        let input = GetEventsInput::new(filter);
+
+        let params = serde_json::to_value(&input).unwrap();
+        assert_eq!(
+            params,
+            json!({"filter": {
+                "from_block": {"block_number": 1},
+                "to_block": "latest",
+                "address": "0x1",
+                "keys": [["0x2", "0x3"], []],
+                "chunk_size": 10,
+                "continuation_token": "20",
+            }})
+        );
+        assert_eq!(
+            serde_json::from_value::<GetEventsInput>(params).unwrap(),
+            input
+        );
+    }
+
+    #[test]
+    fn invalid_event_filters_are_rejected() {
+        assert_eq!(
+            EventFilterBuilder::new(usize::MAX).build().unwrap_err(),
+            InvalidEventFilter::PageSizeTooBig {
+                limit: 1024,
+                requested: usize::MAX
+            }
+        );
+
+        let mut too_many_keys = EventFilterBuilder::new(10);
+        for _ in 0..257 {
+            too_many_keys = too_many_keys.keys([]);
+        }
+        assert_eq!(
+            too_many_keys.build().unwrap_err(),
+            InvalidEventFilter::TooManyKeys {
+                limit: 256,
+                requested: 257
+            }
+        );
+
+        assert_eq!(
+            EventFilterBuilder::new(10)
+                .continuation_token("next")
+                .build()
+                .unwrap_err(),
+            InvalidEventFilter::InvalidContinuationToken
+        );
+    }
+
+    #[test]
+    fn simulation() {
+        let input = SimulationBuilder::new(BlockId::Pending)
+            .skip_validate()
+            .build();
+
+        let expected = json!({
+            "block_id": "pending",
+            "transactions": [],
+            "simulation_flags": ["SKIP_VALIDATE"],
+        });
+        assert_eq!(serde_json::to_value(&input).unwrap(), expected);
+        assert_eq!(
+            serde_json::from_value::<SimulateTrasactionInput>(expected).unwrap(),
+            input
+        );
+    }
+}
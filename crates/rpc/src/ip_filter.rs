@@ -0,0 +1,428 @@
+//! IP based access control for the HTTP-RPC server, which is applied at its listener.
+//!
+//! Without trusted proxies, connections are accepted or rejected based on their peer address.
+//! Connections from the loopback interface are always accepted, so that local tools can still
+//! reach the node.
+//!
+//! Behind a load balancer the peer address is that of the load balancer, so once trusted proxies
+//! are configured only they may connect. Each request is then accepted or rejected based on the
+//! client address which the proxies forwarded in the `Forwarded` or `X-Forwarded-For` header,
+//! skipping any addresses added by trusted proxies along the way. A rejected request gets a
+//! `403 Forbidden` response.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use http::StatusCode;
+use hyper::{Body, Request, Response};
+use tower::BoxError;
+
+use crate::versioning::response;
+
+/// An IP address range in CIDR notation, such as `10.0.0.0/8`, or a single IP address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid IP address or CIDR range: {0}")]
+pub struct InvalidIpNet(String);
+
+impl FromStr for IpNet {
+    type Err = InvalidIpNet;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidIpNet(s.to_owned());
+
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = canonical(addr.trim().parse().map_err(|_| invalid())?);
+        let max_prefix_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.trim().parse().map_err(|_| invalid())?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or_default();
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or_default();
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Treats IPv4-mapped IPv6 addresses, which dual stack listeners report for IPv4 peers, as the
+/// IPv4 addresses which they are.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+            }
+            _ => ip,
+        },
+        IpAddr::V4(_) => ip,
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpFilter {
+    /// Only these clients are allowed, or all clients if empty.
+    pub allow: Vec<IpNet>,
+    /// These clients are denied, even if they are also allowed.
+    pub deny: Vec<IpNet>,
+    /// Proxies whose forwarded client addresses are trusted.
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Client IP address is not allowed")]
+pub struct ForbiddenIp;
+
+impl ForbiddenIp {
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        response::with_canonical_reason(StatusCode::FORBIDDEN)
+    }
+}
+
+impl IpFilter {
+    /// Whether the client at `ip` is allowed. A client without a known address matches neither
+    /// list, so it is only denied if there is an allow list.
+    fn allows(&self, ip: Option<IpAddr>) -> bool {
+        let matches = |nets: &[IpNet]| ip.map_or(false, |ip| nets.iter().any(|n| n.contains(ip)));
+
+        !matches(self.deny.as_slice()) && (self.allow.is_empty() || matches(self.allow.as_slice()))
+    }
+
+    fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(ip))
+    }
+
+    pub(crate) fn accepts_connection(&self, peer: IpAddr) -> bool {
+        if canonical(peer).is_loopback() {
+            true
+        } else if self.trusted_proxies.is_empty() {
+            self.allows(Some(peer))
+        } else {
+            self.is_trusted_proxy(peer)
+        }
+    }
+
+    /// The address of the client which sent the request through the trusted proxies.
+    ///
+    /// The proxies append the address of the peer they received the request from, so the client
+    /// is the last address which was not added by a trusted proxy. [None] if the client address
+    /// is missing or obfuscated.
+    fn client_ip(&self, headers: &http::HeaderMap) -> Option<IpAddr> {
+        let forwarded = forwarded_for(headers);
+
+        let mut client = None;
+        for node in forwarded.iter().rev() {
+            client = *node;
+            match node {
+                Some(ip) if self.is_trusted_proxy(*ip) => continue,
+                _ => break,
+            }
+        }
+        client
+    }
+}
+
+/// The forwarded addresses in the order in which they were added by proxies, preferring the
+/// standard `Forwarded` header over `X-Forwarded-For`. Nodes which are not IP addresses, such as
+/// `unknown` or obfuscated identifiers, are [None].
+fn forwarded_for(headers: &http::HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .collect::<Vec<_>>()
+    };
+
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .filter_map(|element| {
+                element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    key.trim().eq_ignore_ascii_case("for").then_some(value)
+                })
+            })
+            .map(parse_node)
+            .collect();
+    }
+
+    values("x-forwarded-for")
+        .into_iter()
+        .map(parse_node)
+        .collect()
+}
+
+/// Parses a forwarded node, which is an IP address with an optional port. IPv6 addresses with a
+/// port are enclosed in brackets, and the `Forwarded` header quotes them.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return ip.parse::<Ipv6Addr>().ok().map(IpAddr::V6);
+    }
+
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Rejects requests from clients which are not allowed, as identified by the trusted proxies.
+///
+/// Without trusted proxies, clients are filtered by the [listener](crate::listener) as they
+/// connect instead.
+pub(crate) async fn authorize(
+    request: Request<Body>,
+    ip_filter: Option<&IpFilter>,
+) -> Result<Request<Body>, BoxError> {
+    match ip_filter {
+        Some(ip_filter) if !ip_filter.trusted_proxies.is_empty() => {
+            let client = ip_filter.client_ip(request.headers());
+            if !ip_filter.allows(client) {
+                tracing::debug!(?client, "Rejected RPC request from IP address");
+                return Err(ForbiddenIp.into());
+            }
+            Ok(request)
+        }
+        _ => Ok(request),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn nets(nets: &[&str]) -> Vec<IpNet> {
+        nets.iter().map(|net| net.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn ip_net() {
+        let net = "10.1.0.0/16".parse::<IpNet>().unwrap();
+        assert!(net.contains(ip("10.1.2.3")));
+        assert!(net.contains(ip("::ffff:10.1.2.3")));
+        assert!(!net.contains(ip("10.2.0.1")));
+
+        let net = "2001:db8::/32".parse::<IpNet>().unwrap();
+        assert!(net.contains(ip("2001:db8:cafe::17")));
+        assert!(!net.contains(ip("2001:db9::1")));
+        assert!(!net.contains(ip("10.1.2.3")));
+
+        let single = "192.0.2.1".parse::<IpNet>().unwrap();
+        assert!(single.contains(ip("192.0.2.1")));
+        assert!(!single.contains(ip("192.0.2.2")));
+
+        let all = "0.0.0.0/0".parse::<IpNet>().unwrap();
+        assert!(all.contains(ip("203.0.113.7")));
+
+        "10.0.0.0/33".parse::<IpNet>().unwrap_err();
+        "10.0.0/8".parse::<IpNet>().unwrap_err();
+        "localhost".parse::<IpNet>().unwrap_err();
+    }
+
+    #[test]
+    fn allow_and_deny() {
+        let filter = IpFilter {
+            allow: nets(&["10.0.0.0/8"]),
+            deny: nets(&["10.0.0.1"]),
+            ..Default::default()
+        };
+        assert!(filter.allows(Some(ip("10.0.0.2"))));
+        assert!(!filter.allows(Some(ip("10.0.0.1"))));
+        assert!(!filter.allows(Some(ip("192.0.2.1"))));
+        assert!(!filter.allows(None));
+
+        let filter = IpFilter {
+            deny: nets(&["10.0.0.1"]),
+            ..Default::default()
+        };
+        assert!(filter.allows(Some(ip("192.0.2.1"))));
+        assert!(filter.allows(None));
+    }
+
+    #[test]
+    fn only_trusted_proxies_may_connect() {
+        let filter = IpFilter {
+            allow: nets(&["192.0.2.1"]),
+            trusted_proxies: nets(&["10.0.0.0/8"]),
+            ..Default::default()
+        };
+        assert!(filter.accepts_connection(ip("10.0.0.1")));
+        assert!(!filter.accepts_connection(ip("192.0.2.1")));
+    }
+
+    #[test]
+    fn loopback_may_always_connect() {
+        let filter = IpFilter {
+            deny: nets(&["0.0.0.0/0", "::/0"]),
+            ..Default::default()
+        };
+        assert!(filter.accepts_connection(ip("127.0.0.1")));
+        assert!(filter.accepts_connection(ip("::1")));
+        assert!(filter.accepts_connection(ip("::ffff:127.0.0.1")));
+        assert!(!filter.accepts_connection(ip("192.0.2.1")));
+    }
+
+    mod client_ip {
+        use super::*;
+
+        fn client_ip(headers: &[(&'static str, &'static str)]) -> Option<IpAddr> {
+            let filter = IpFilter {
+                trusted_proxies: nets(&["10.0.0.0/8"]),
+                ..Default::default()
+            };
+            let mut map = http::HeaderMap::new();
+            for (name, value) in headers {
+                map.append(*name, value.parse().unwrap());
+            }
+            filter.client_ip(&map)
+        }
+
+        #[test]
+        fn x_forwarded_for() {
+            let client = client_ip(&[("x-forwarded-for", "192.0.2.1, 10.0.0.2")]);
+            assert_eq!(client, Some(ip("192.0.2.1")));
+        }
+
+        #[test]
+        fn spoofed_addresses_are_ignored() {
+            // The client sent the first address itself, and is identified by the last proxy.
+            let client = client_ip(&[
+                ("x-forwarded-for", "10.0.0.3"),
+                ("x-forwarded-for", "198.51.100.1, 192.0.2.1"),
+            ]);
+            assert_eq!(client, Some(ip("192.0.2.1")));
+        }
+
+        #[test]
+        fn forwarded() {
+            let client = client_ip(&[
+                ("x-forwarded-for", "198.51.100.1"),
+                (
+                    "forwarded",
+                    r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.0.0.2;by=10.0.0.1"#,
+                ),
+            ]);
+            assert_eq!(client, Some(ip("2001:db8:cafe::17")));
+
+            let client = client_ip(&[("forwarded", r#"For="192.0.2.43:47011""#)]);
+            assert_eq!(client, Some(ip("192.0.2.43")));
+        }
+
+        #[test]
+        fn obfuscated() {
+            assert_eq!(
+                client_ip(&[("forwarded", "for=_hidden, for=10.0.0.2")]),
+                None
+            );
+            assert_eq!(client_ip(&[("x-forwarded-for", "unknown")]), None);
+        }
+
+        #[test]
+        fn only_trusted_proxies() {
+            let client = client_ip(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+            assert_eq!(client, Some(ip("10.0.0.3")));
+
+            assert_eq!(client_ip(&[]), None);
+        }
+    }
+
+    mod server {
+        use super::*;
+        use crate::context::RpcContext;
+        use crate::RpcServer;
+        use jsonrpsee::server::ServerHandle;
+
+        async fn chain_id(
+            address: SocketAddr,
+            forwarded_for: Option<&str>,
+        ) -> reqwest::Result<StatusCode> {
+            let mut request = reqwest::Client::new()
+                .post(format!("http://{address}/rpc/v0.3"))
+                .json(&serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": "starknet_chainId",
+                }));
+            if let Some(forwarded_for) = forwarded_for {
+                request = request.header("x-forwarded-for", forwarded_for);
+            }
+            request.send().await.map(|response| response.status())
+        }
+
+        async fn server(ip_filter: IpFilter) -> (ServerHandle, SocketAddr) {
+            RpcServer::new("127.0.0.1:0".parse().unwrap(), RpcContext::for_tests())
+                .with_ip_filter(ip_filter)
+                .run()
+                .await
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn loopback_peer() {
+            let (_handle, address) = server(IpFilter {
+                allow: nets(&["192.0.2.0/24"]),
+                ..Default::default()
+            })
+            .await;
+            assert_eq!(chain_id(address, None).await.unwrap(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn forwarded_address() {
+            let (_handle, address) = server(IpFilter {
+                deny: nets(&["192.0.2.1"]),
+                trusted_proxies: nets(&["127.0.0.1"]),
+                ..Default::default()
+            })
+            .await;
+
+            assert_eq!(
+                chain_id(address, Some("192.0.2.1")).await.unwrap(),
+                StatusCode::FORBIDDEN
+            );
+            assert_eq!(
+                chain_id(address, Some("192.0.2.2")).await.unwrap(),
+                StatusCode::OK
+            );
+        }
+    }
+}
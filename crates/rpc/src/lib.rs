@@ -1,29 +1,56 @@
 //! StarkNet node JSON-RPC related modules.
+pub mod api_keys;
+pub mod attestation;
 pub mod cairo;
 pub mod context;
+pub mod deprecation;
 mod error;
 mod felt;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod gas_price;
+pub mod input;
+pub mod ip_filter;
+mod listener;
 pub mod metrics;
 mod module;
 mod pathfinder;
+pub mod prefetch;
+pub mod request_limits;
+pub mod request_log;
 #[cfg(test)]
 pub mod test_client;
+pub mod tls;
 pub mod v02;
 pub mod v03;
 mod versioning;
 
+use crate::api_keys::ApiKeys;
+use crate::attestation::{AttestationLayer, Attestor};
+use crate::ip_filter::IpFilter;
 use crate::metrics::logger::{MaybeRpcMetricsLogger, RpcMetricsLogger};
-use crate::v02::types::syncing::Syncing;
+use crate::request_limits::RequestLimits;
+use crate::request_log::{RequestLogConfig, RequestLogLayer};
+use crate::tls::TlsConfig;
+use anyhow::Context;
 use context::RpcContext;
-use jsonrpsee::server::{ServerBuilder, ServerHandle};
+use jsonrpsee::server::ServerBuilder;
+pub use jsonrpsee::server::ServerHandle;
+pub use listener::LivenessProbe;
+pub use starknet_gateway_types::sync_state::{milestones, ChainUpdate, SyncState};
+use std::sync::Arc;
 use std::{net::SocketAddr, result::Result};
-use tokio::sync::RwLock;
 
 pub struct RpcServer {
     addr: SocketAddr,
     context: RpcContext,
     logger: MaybeRpcMetricsLogger,
+    api_keys: Option<ApiKeys>,
+    ip_filter: Option<Arc<IpFilter>>,
+    tls: Option<TlsConfig>,
+    request_log: RequestLogConfig,
+    request_limits: RequestLimits,
+    attestor: Option<Arc<Attestor>>,
 }
 
 impl RpcServer {
@@ -32,6 +59,60 @@ impl RpcServer {
             addr,
             context,
             logger: MaybeRpcMetricsLogger::NoOp,
+            api_keys: None,
+            ip_filter: None,
+            tls: None,
+            request_log: Default::default(),
+            request_limits: Default::default(),
+            attestor: None,
+        }
+    }
+
+    /// Requires requests to carry one of the `api_keys`, and applies its limits.
+    pub fn with_api_keys(self, api_keys: ApiKeys) -> Self {
+        Self {
+            api_keys: Some(api_keys),
+            ..self
+        }
+    }
+
+    /// Accepts only the clients allowed by the `ip_filter`.
+    pub fn with_ip_filter(self, ip_filter: IpFilter) -> Self {
+        Self {
+            ip_filter: Some(Arc::new(ip_filter)),
+            ..self
+        }
+    }
+
+    /// Serves HTTPS instead of HTTP.
+    pub fn with_tls(self, tls: TlsConfig) -> Self {
+        Self {
+            tls: Some(tls),
+            ..self
+        }
+    }
+
+    /// Replaces the default [RequestLogConfig], which only logs slow requests.
+    pub fn with_request_log(self, request_log: RequestLogConfig) -> Self {
+        Self {
+            request_log,
+            ..self
+        }
+    }
+
+    /// Replaces the default [RequestLimits] on the size and shape of request bodies.
+    pub fn with_request_limits(self, request_limits: RequestLimits) -> Self {
+        Self {
+            request_limits,
+            ..self
+        }
+    }
+
+    /// Signs the data of selected responses with the `attestor`'s key.
+    pub fn with_attestor(self, attestor: Attestor) -> Self {
+        Self {
+            attestor: Some(Arc::new(attestor)),
+            ..self
         }
     }
 
@@ -44,30 +125,105 @@ impl RpcServer {
 
     /// Starts the HTTP-RPC server.
     pub async fn run(self) -> Result<(ServerHandle, SocketAddr), anyhow::Error> {
-        const TEN_MB: u32 = 10 * 1024 * 1024;
+        let (handle, addr, _probe) = self.run_with_liveness_probe().await?;
+        Ok((handle, addr))
+    }
 
+    /// Starts the HTTP-RPC server like [run](Self::run), and also returns a [LivenessProbe] for it.
+    pub async fn run_with_liveness_probe(
+        self,
+    ) -> Result<(ServerHandle, SocketAddr, LivenessProbe), anyhow::Error> {
+        let tls = self
+            .tls
+            .as_ref()
+            .map(TlsConfig::acceptor)
+            .transpose()
+            .context("Loading TLS certificates")?;
+
+        // The jsonrpsee server can neither filter IP addresses nor terminate TLS, so then it only
+        // listens on the loopback interface, and the guard listens on the RPC address instead.
+        let guard = match (&self.ip_filter, tls) {
+            (None, None) => None,
+            (ip_filter, tls) => match tokio::net::TcpListener::bind(self.addr).await {
+                Ok(listener) => {
+                    let guard = listener::Guard {
+                        ip_filter: ip_filter.clone(),
+                        tls,
+                        token: listener::Token::generate(),
+                    };
+                    Some((listener, guard))
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                    return Err(anyhow::Error::new(e).context(address_in_use(self.addr)));
+                }
+                Err(e) => return Err(e.into()),
+            },
+        };
+        let server_addr = match guard {
+            Some(_) => SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)),
+            None => self.addr,
+        };
+
+        let module = crate::module::Module::new(self.context);
+        let module = v02::register_methods(module)?;
+        let module = v03::register_methods(module)?;
+        let module = pathfinder::register_methods(module)?;
+        let methods = module.build();
+
+        let api_keys = self.api_keys.map(|api_keys| {
+            let registered_methods = methods
+                .method_names()
+                .map(|name| crate::module::split_version_prefix(name).1)
+                .collect();
+            Arc::new(api_keys.with_registered_methods(registered_methods))
+        });
+        let token = guard.as_ref().map(|(_, guard)| guard.token.clone());
+        let ip_filter = self.ip_filter.clone();
+        let limits = self.request_limits;
+        let max_body_size = limits.max_body_size;
         let server = ServerBuilder::default()
-            .max_request_body_size(TEN_MB)
+            .max_request_body_size(max_body_size)
             .set_logger(self.logger)
-            .set_middleware(tower::ServiceBuilder::new()
-                .map_result(versioning::try_map_errors_to_responses)
-                .filter_async(|result| async move {
-                    versioning::prefix_rpc_method_names_with_version(result, TEN_MB).await
-                }))
-            .build(self.addr)
+            .set_middleware(
+                tower::ServiceBuilder::new()
+                    .map_result(versioning::try_map_errors_to_responses)
+                    .filter_async({
+                        let token = token.clone();
+                        move |request| {
+                            let token = token.clone();
+                            async move { listener::authorize(request, token.as_ref()).await }
+                        }
+                    })
+                    .filter_async(move |request| {
+                        let ip_filter = ip_filter.clone();
+                        async move { ip_filter::authorize(request, ip_filter.as_deref()).await }
+                    })
+                    .filter_async(move |request| request_limits::check(request, limits))
+                    .filter_async(move |request| {
+                        let api_keys = api_keys.clone();
+                        async move {
+                            api_keys::authorize(request, api_keys.as_deref(), max_body_size).await
+                        }
+                    })
+                    .layer(AttestationLayer::new(self.attestor.clone(), max_body_size))
+                    .filter_async(|result| async move {
+                        versioning::prefix_rpc_method_names_with_version(result, max_body_size)
+                            .await
+                    })
+                    .layer(RequestLogLayer::new(self.request_log, max_body_size)),
+            )
+            .build(server_addr)
             .await
             .map_err(|e| match e {
                 jsonrpsee::core::Error::Transport(_) => {
                     use std::error::Error;
 
-                    if let Some(inner) = e.source().and_then(|inner| inner.downcast_ref::<std::io::Error>()) {
+                    if let Some(inner) = e
+                        .source()
+                        .and_then(|inner| inner.downcast_ref::<std::io::Error>())
+                    {
                         if let std::io::ErrorKind::AddrInUse = inner.kind() {
-                            return anyhow::Error::new(e)
-                                .context(format!("RPC address is already in use: {}.
-
-Hint: This usually means you are already running another instance of pathfinder.
-Hint: If this happens when upgrading, make sure to shut down the first one first.
-Hint: If you are looking to run two instances of pathfinder, you must configure them with different http rpc addresses.", self.addr));
+                            return anyhow::Error::new(e).context(address_in_use(self.addr));
                         }
                     }
 
@@ -77,26 +233,28 @@ Hint: If you are looking to run two instances of pathfinder, you must configure
             })?;
         let local_addr = server.local_addr()?;
 
-        let module = crate::module::Module::new(self.context);
-        let module = v02::register_methods(module)?;
-        let module = v03::register_methods(module)?;
-        let module = pathfinder::register_methods(module)?;
-        let methods = module.build();
+        let handle = server.start(methods)?;
+        let probe = LivenessProbe::new(local_addr, token).context("Creating liveness probe")?;
 
-        Ok(server.start(methods).map(|handle| (handle, local_addr))?)
+        match guard {
+            Some((listener, guard)) => {
+                let guard_addr = listener.local_addr()?;
+                tokio::spawn(listener::serve(listener, local_addr, guard, handle.clone()));
+                Ok((handle, guard_addr, probe))
+            }
+            None => Ok((handle, local_addr, probe)),
+        }
     }
 }
 
-pub struct SyncState {
-    pub status: RwLock<Syncing>,
-}
+fn address_in_use(addr: SocketAddr) -> String {
+    format!(
+        "RPC address is already in use: {addr}.
 
-impl Default for SyncState {
-    fn default() -> Self {
-        Self {
-            status: RwLock::new(Syncing::False(false)),
-        }
-    }
+Hint: This usually means you are already running another instance of pathfinder.
+Hint: If this happens when upgrading, make sure to shut down the first one first.
+Hint: If you are looking to run two instances of pathfinder, you must configure them with different http rpc addresses."
+    )
 }
 
 #[cfg(test)]
@@ -353,6 +511,8 @@ mod tests {
             l2_to_l1_messages: vec![],
             transaction_hash: txn0_hash,
             transaction_index: StarknetTransactionIndex::new_or_panic(0),
+            execution_status: Default::default(),
+            revert_error: None,
         };
         let txn1_hash = StarknetTransactionHash(felt_bytes!(b"txn 1"));
         let txn2_hash = StarknetTransactionHash(felt_bytes!(b"txn 2"));
@@ -483,6 +643,8 @@ mod tests {
                 l2_to_l1_messages: vec![],
                 transaction_hash: transactions[0].hash(),
                 transaction_index: StarknetTransactionIndex::new_or_panic(0),
+                execution_status: Default::default(),
+                revert_error: None,
             },
             Receipt {
                 actual_fee: None,
@@ -498,6 +660,8 @@ mod tests {
                 l2_to_l1_messages: vec![],
                 transaction_hash: transactions[1].hash(),
                 transaction_index: StarknetTransactionIndex::new_or_panic(1),
+                execution_status: Default::default(),
+                revert_error: None,
             },
         ];
 
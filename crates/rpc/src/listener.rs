@@ -0,0 +1,377 @@
+//! The listener of the HTTP-RPC server when it filters IP addresses or terminates TLS, neither of
+//! which the jsonrpsee server supports.
+//!
+//! It accepts connections on the configured address and proxies the requests of the ones which it
+//! accepts, decrypted, to the jsonrpsee server on the loopback interface. The jsonrpsee server
+//! rejects requests without the listener's secret [Token], so that local processes cannot bypass
+//! the listener by connecting to it directly.
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Context;
+use http::header::HeaderValue;
+use http::StatusCode;
+use hyper::client::HttpConnector;
+use hyper::{Body, Request, Response, Uri};
+use jsonrpsee::server::ServerHandle;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+use tower::BoxError;
+
+use crate::ip_filter::IpFilter;
+use crate::versioning::response;
+
+/// How long a client may take to complete the TLS handshake.
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The header in which the listener passes its [Token] to the jsonrpsee server.
+const TOKEN_HEADER: &str = "x-pathfinder-listener-token";
+
+/// A random secret, which the listener adds to each request that it proxies.
+#[derive(Clone)]
+pub(crate) struct Token(HeaderValue);
+
+impl Token {
+    pub fn generate() -> Self {
+        let bytes: [u8; 32] = rand::random();
+        let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        Self(HeaderValue::from_str(&hex).expect("Hex digits are a valid header value"))
+    }
+
+    /// Compares in constant time, so that the token cannot be guessed byte by byte.
+    fn matches(&self, value: Option<&HeaderValue>) -> bool {
+        let (expected, actual) = match value {
+            Some(value) => (self.0.as_bytes(), value.as_bytes()),
+            None => return false,
+        };
+
+        expected.len() == actual.len()
+            && expected
+                .iter()
+                .zip(actual)
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Request did not come through the RPC listener")]
+pub struct Unproxied;
+
+impl Unproxied {
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        response::with_canonical_reason(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Rejects requests without the listener's `token`, if the server is behind a listener.
+pub(crate) async fn authorize(
+    request: Request<Body>,
+    token: Option<&Token>,
+) -> Result<Request<Body>, BoxError> {
+    match token {
+        Some(token) if !token.matches(request.headers().get(TOKEN_HEADER)) => {
+            tracing::debug!("Rejected RPC request which did not come through the listener");
+            Err(Unproxied.into())
+        }
+        _ => Ok(request),
+    }
+}
+
+pub(crate) struct Guard {
+    pub ip_filter: Option<std::sync::Arc<IpFilter>>,
+    pub tls: Option<TlsAcceptor>,
+    pub token: Token,
+}
+
+/// Accepts connections on `listener` and proxies their requests to the server at `upstream`,
+/// until the server is stopped.
+pub(crate) async fn serve(
+    listener: TcpListener,
+    upstream: SocketAddr,
+    guard: Guard,
+    server: ServerHandle,
+) {
+    let proxy = Proxy {
+        client: hyper::Client::builder().build_http(),
+        upstream,
+        token: guard.token,
+    };
+
+    let accept = async move {
+        loop {
+            let (socket, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::debug!(reason=%e, "Failed to accept RPC connection");
+                    continue;
+                }
+            };
+
+            if let Some(ip_filter) = &guard.ip_filter {
+                if !ip_filter.accepts_connection(peer.ip()) {
+                    tracing::debug!(%peer, "Rejected RPC connection from IP address");
+                    continue;
+                }
+            }
+
+            let tls = guard.tls.clone();
+            let proxy = proxy.clone();
+            tokio::spawn(async move {
+                if let Err(e) = proxy.serve_connection(socket, tls).await {
+                    tracing::trace!(%peer, reason=%e, "RPC connection closed");
+                }
+            });
+        }
+    };
+
+    tokio::select! {
+        _ = accept => {},
+        _ = server.stopped() => {},
+    }
+}
+
+#[derive(Clone)]
+struct Proxy {
+    client: hyper::Client<HttpConnector>,
+    upstream: SocketAddr,
+    token: Token,
+}
+
+impl Proxy {
+    async fn serve_connection(
+        self,
+        socket: TcpStream,
+        tls: Option<TlsAcceptor>,
+    ) -> anyhow::Result<()> {
+        socket.set_nodelay(true)?;
+
+        match tls {
+            Some(tls) => {
+                let socket = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, tls.accept(socket))
+                    .await
+                    .context("TLS handshake timed out")??;
+                self.serve(socket).await?;
+            }
+            None => self.serve(socket).await?,
+        }
+
+        Ok(())
+    }
+
+    async fn serve<S>(self, stream: S) -> hyper::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let service = hyper::service::service_fn(move |request| self.clone().forward(request));
+        hyper::server::conn::Http::new()
+            .http1_only(true)
+            .serve_connection(stream, service)
+            .with_upgrades()
+            .await
+    }
+
+    async fn forward(self, mut request: Request<Body>) -> Result<Response<Body>, Infallible> {
+        let path = request
+            .uri()
+            .path_and_query()
+            .map_or("/", |path| path.as_str());
+        let uri = format!("http://{}{path}", self.upstream).parse::<Uri>();
+        *request.uri_mut() = match uri {
+            Ok(uri) => uri,
+            Err(_) => return Ok(response::with_canonical_reason(StatusCode::BAD_REQUEST)),
+        };
+        // Replaces any token which the client sent itself.
+        request
+            .headers_mut()
+            .insert(TOKEN_HEADER, self.token.0.clone());
+
+        // The client does not carry over upgrades, such as to websockets, so the upgraded
+        // connections are joined here instead.
+        let downstream = hyper::upgrade::on(&mut request);
+        let mut response = match self.client.request(request).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::debug!(reason=%e, "Failed to proxy RPC request");
+                return Ok(response::with_canonical_reason(StatusCode::BAD_GATEWAY));
+            }
+        };
+        if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let upstream = hyper::upgrade::on(&mut response);
+            tokio::spawn(async move {
+                match tokio::try_join!(downstream, upstream) {
+                    Ok((mut downstream, mut upstream)) => {
+                        let _ = tokio::io::copy_bidirectional(&mut downstream, &mut upstream).await;
+                    }
+                    Err(e) => tracing::trace!(reason=%e, "Failed to upgrade RPC connection"),
+                }
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+/// Checks from within the node that the HTTP-RPC server responds. It queries the jsonrpsee server
+/// directly, so that it needs neither a client certificate nor an allowed IP address.
+#[derive(Clone)]
+pub struct LivenessProbe {
+    client: hyper::Client<HttpConnector>,
+    uri: Uri,
+    token: Option<Token>,
+}
+
+impl LivenessProbe {
+    /// Probes the jsonrpsee server at `address`, with the `token` of its listener if it has one.
+    pub(crate) fn new(mut address: SocketAddr, token: Option<Token>) -> anyhow::Result<Self> {
+        if address.ip().is_unspecified() {
+            let loopback: std::net::IpAddr = match address {
+                SocketAddr::V4(_) => std::net::Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => std::net::Ipv6Addr::LOCALHOST.into(),
+            };
+            address.set_ip(loopback);
+        }
+
+        Ok(Self {
+            client: hyper::Client::builder().build_http(),
+            uri: format!("http://{address}/rpc/pathfinder/v0.1").parse()?,
+            token,
+        })
+    }
+
+    pub async fn check(&self) -> anyhow::Result<()> {
+        let mut request = Request::post(self.uri.clone())
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"jsonrpc":"2.0","id":0,"method":"pathfinder_version"}"#,
+            ))?;
+        if let Some(token) = &self.token {
+            request.headers_mut().insert(TOKEN_HEADER, token.0.clone());
+        }
+
+        let response = self
+            .client
+            .request(request)
+            .await
+            .context("Querying RPC server")?;
+        // The probe carries no API key or forwarded client address, so it is rejected if keys or
+        // trusted proxies are configured. That still shows that the server responds.
+        match response.status() {
+            status
+                if status.is_success()
+                    || status == StatusCode::UNAUTHORIZED
+                    || status == StatusCode::FORBIDDEN =>
+            {
+                Ok(())
+            }
+            status => anyhow::bail!("RPC server responded with {status}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::RpcContext;
+    use crate::tls::test_utils::Certificates;
+    use crate::RpcServer;
+
+    async fn chain_id(client: reqwest::Client, url: String) -> reqwest::Result<serde_json::Value> {
+        client
+            .post(url)
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "starknet_chainId",
+            }))
+            .send()
+            .await?
+            .json()
+            .await
+    }
+
+    /// The server certificate is self-signed, so it is not verified.
+    fn client() -> reqwest::ClientBuilder {
+        reqwest::Client::builder().danger_accept_invalid_certs(true)
+    }
+
+    #[tokio::test]
+    async fn tls() {
+        let certificates = Certificates::generate();
+        let (_handle, address) =
+            RpcServer::new("127.0.0.1:0".parse().unwrap(), RpcContext::for_tests())
+                .with_tls(certificates.config(false))
+                .run()
+                .await
+                .unwrap();
+
+        let client = client().build().unwrap();
+        let response = chain_id(client.clone(), format!("https://{address}/rpc/v0.3"))
+            .await
+            .unwrap();
+        assert!(response.get("result").is_some());
+
+        chain_id(client, format!("http://{address}/rpc/v0.3"))
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn client_certificates() {
+        let certificates = Certificates::generate();
+        let (_handle, address) =
+            RpcServer::new("127.0.0.1:0".parse().unwrap(), RpcContext::for_tests())
+                .with_tls(certificates.config(true))
+                .run()
+                .await
+                .unwrap();
+        let url = format!("https://{address}/rpc/v0.3");
+
+        chain_id(client().build().unwrap(), url.clone())
+            .await
+            .unwrap_err();
+
+        let identity = reqwest::Identity::from_pkcs8_pem(
+            certificates.client_cert.as_bytes(),
+            certificates.client_key.as_bytes(),
+        )
+        .unwrap();
+        let client = client().identity(identity).build().unwrap();
+        let response = chain_id(client, url).await.unwrap();
+        assert!(response.get("result").is_some());
+    }
+
+    #[tokio::test]
+    async fn jsonrpsee_server_requires_the_token() {
+        let certificates = Certificates::generate();
+        let (_handle, _address, probe) =
+            RpcServer::new("127.0.0.1:0".parse().unwrap(), RpcContext::for_tests())
+                .with_tls(certificates.config(true))
+                .run_with_liveness_probe()
+                .await
+                .unwrap();
+
+        // The probe needs no client certificate, as it bypasses the listener.
+        probe.check().await.unwrap();
+
+        let request = |token: &str| {
+            reqwest::Client::new()
+                .post(probe.uri.to_string())
+                .header(super::TOKEN_HEADER, token)
+                .json(&serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": 0,
+                    "method": "pathfinder_version",
+                }))
+                .send()
+        };
+
+        let token = probe.token.as_ref().unwrap().0.to_str().unwrap();
+        let response = request(token).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = request("guessed").await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    }
+}
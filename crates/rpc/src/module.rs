@@ -1,12 +1,23 @@
 use std::sync::Arc;
 
 use jsonrpsee::core::server::rpc_module::Methods;
+use starknet_gateway_types::pending::PendingStateVersion;
 
 use crate::context::RpcContext;
+use crate::deprecation::is_deprecated;
 use crate::error::RpcError;
 
 /// A builder for registering a set of JSON-RPC methods.
-pub struct Module(jsonrpsee::RpcModule<RpcContext>);
+pub struct Module {
+    module: jsonrpsee::RpcModule<RpcContext>,
+    /// Parses the params of each method with input, without calling the method.
+    #[cfg(feature = "fuzzing")]
+    input_parsers: std::collections::HashMap<&'static str, InputParser>,
+}
+
+/// Parses method params into the method's input, returning whether the params are valid.
+#[cfg(feature = "fuzzing")]
+pub(crate) type InputParser = fn(jsonrpsee::types::Params<'_>) -> bool;
 
 /// Splits the internal RPC method name, which is in the form of
 /// `apiVersion_proper_methodName` into two separate strings:
@@ -21,13 +32,28 @@ pub(crate) fn split_version_prefix(method: &str) -> (String, String) {
     (version.to_owned(), method.to_owned())
 }
 
+/// Counts a call to a deprecated method, which is rare enough to split its name again.
+fn record_deprecated_call(context: &RpcContext, method_name: &str) {
+    let (version, method) = split_version_prefix(method_name);
+    context.deprecated_calls.record(&version, &method);
+}
+
 impl Module {
     pub fn new(context: RpcContext) -> Self {
-        Self(jsonrpsee::RpcModule::new(context))
+        Self {
+            module: jsonrpsee::RpcModule::new(context),
+            #[cfg(feature = "fuzzing")]
+            input_parsers: Default::default(),
+        }
     }
 
     pub fn build(self) -> Methods {
-        self.0.into()
+        self.module.into()
+    }
+
+    #[cfg(feature = "fuzzing")]
+    pub(crate) fn into_input_parsers(self) -> std::collections::HashMap<&'static str, InputParser> {
+        self.input_parsers
     }
 
     /// Registers a JSON-RPC method with input parameters.
@@ -53,6 +79,8 @@ impl Module {
         use tracing::Instrument;
 
         let (version, metric_method_name) = split_version_prefix(method_name);
+        let deprecated = is_deprecated(&version, &metric_method_name);
+        let before_v04 = matches!(version.as_str(), "v0.2" | "v0.3");
         metrics::register_counter!("rpc_method_calls_total", "method" => metric_method_name.clone(), "version" => version.clone());
         metrics::register_counter!("rpc_method_calls_failed_total", "method" => metric_method_name, "version" => version);
 
@@ -60,19 +88,32 @@ impl Module {
             // why info here? it's the same used in warp tracing filter for example.
             let span = tracing::info_span!("rpc_method", name = method_name);
             async move {
-                let input = params.parse::<Input>()?;
-                method((*context).clone(), input).await.map_err(|err| {
-                    let rpc_err: RpcError = err.into();
+                if deprecated {
+                    record_deprecated_call(&context, method_name);
+                }
+                let (input, pending_state_version) = parse_input::<Input>(params)?;
+                check_pending_state_version(&context, pending_state_version).await?;
+                let output = method((*context).clone(), input).await.map_err(|err| {
+                    let mut rpc_err: RpcError = err.into();
+                    if before_v04 {
+                        rpc_err = rpc_err.before_v04();
+                    }
                     jsonrpsee::core::Error::from(rpc_err)
-                })
+                })?;
+                check_pending_state_version(&context, pending_state_version).await?;
+                Ok(output)
             }
             .instrument(span)
         };
 
-        self.0
+        self.module
             .register_async_method(method_name, method_callback)
             .with_context(|| format!("Registering {method_name}"))?;
 
+        #[cfg(feature = "fuzzing")]
+        self.input_parsers
+            .insert(method_name, |params| params.parse::<Input>().is_ok());
+
         Ok(self)
     }
 
@@ -94,30 +135,187 @@ impl Module {
         Method: (Fn(RpcContext) -> MethodFuture) + Copy + Send + Sync + 'static,
     {
         use anyhow::Context;
+        use jsonrpsee::types::Params;
         use tracing::Instrument;
 
         let (version, metric_method_name) = split_version_prefix(method_name);
+        let deprecated = is_deprecated(&version, &metric_method_name);
+        let before_v04 = matches!(version.as_str(), "v0.2" | "v0.3");
         metrics::register_counter!("rpc_method_calls_total", "method" => metric_method_name.clone(), "version" => version.clone());
         metrics::register_counter!("rpc_method_calls_failed_total", "method" => metric_method_name, "version" => version);
 
-        let method_callback = move |_params, context: Arc<RpcContext>| {
+        let method_callback = move |params: Params<'static>, context: Arc<RpcContext>| {
             // why info here? it's the same used in warp tracing filter for example.
             let span = tracing::info_span!("rpc_method", name = method_name);
             async move {
-                method((*context).clone()).await.map_err(|err| {
-                    let rpc_err: RpcError = err.into();
+                if deprecated {
+                    record_deprecated_call(&context, method_name);
+                }
+                // The params are otherwise ignored.
+                let (_, pending_state_version) = parse_input::<::serde::de::IgnoredAny>(params)?;
+                check_pending_state_version(&context, pending_state_version).await?;
+                let output = method((*context).clone()).await.map_err(|err| {
+                    let mut rpc_err: RpcError = err.into();
+                    if before_v04 {
+                        rpc_err = rpc_err.before_v04();
+                    }
                     jsonrpsee::core::Error::from(rpc_err)
-                })
+                })?;
+                check_pending_state_version(&context, pending_state_version).await?;
+                Ok(output)
             }
             .instrument(span)
         };
 
-        self.0
+        self.module
             .register_async_method(method_name, method_callback)
             .with_context(|| format!("Registering {method_name}"))?;
 
         Ok(self)
     }
+
+    /// Registers a JSON-RPC subscription, which is only available over websockets.
+    ///
+    /// Websocket messages bypass the versioning middleware, so unlike methods the names
+    /// are registered without a version prefix.
+    ///
+    /// An example signature for `subscribe` is:
+    /// ```ignore
+    /// fn subscribe(context: RpcContext, input: Input) -> Result<impl Stream<Item = Notification>, Error>
+    /// ```
+    /// The subscription ends once the stream does.
+    pub fn register_subscription<Input, Notification, Error, NotificationStream, Subscribe>(
+        mut self,
+        subscribe_method_name: &'static str,
+        notification_method_name: &'static str,
+        unsubscribe_method_name: &'static str,
+        subscribe: Subscribe,
+    ) -> anyhow::Result<Self>
+    where
+        Input: ::serde::de::DeserializeOwned + Send + Sync,
+        Notification: ::serde::Serialize,
+        Error: Into<RpcError>,
+        NotificationStream: futures::Stream<Item = Notification> + Unpin + Send + 'static,
+        Subscribe:
+            (Fn(RpcContext, Input) -> Result<NotificationStream, Error>) + Send + Sync + 'static,
+    {
+        use anyhow::Context;
+        use jsonrpsee::core::server::rpc_module::SubscriptionSink;
+        use jsonrpsee::types::Params;
+
+        let callback = move |params: Params<'_>,
+                             mut sink: SubscriptionSink,
+                             context: Arc<RpcContext>| {
+            let _span =
+                tracing::info_span!("rpc_subscription", name = subscribe_method_name).entered();
+
+            let input = match params.parse::<Input>() {
+                Ok(input) => input,
+                Err(e) => {
+                    let _ = sink.reject(e);
+                    return Ok(());
+                }
+            };
+
+            match subscribe((*context).clone(), input) {
+                Ok(stream) => {
+                    tokio::spawn(async move {
+                        let closed = sink.pipe_from_stream(stream).await;
+                        sink.close(closed);
+                    });
+                }
+                Err(e) => {
+                    let rpc_err: RpcError = e.into();
+                    match jsonrpsee::core::Error::from(rpc_err) {
+                        jsonrpsee::core::Error::Call(e) => {
+                            let _ = sink.reject(e);
+                        }
+                        other => {
+                            let _ = sink
+                                .reject(jsonrpsee::types::error::CallError::Failed(other.into()));
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        };
+
+        self.module
+            .register_subscription(
+                subscribe_method_name,
+                notification_method_name,
+                unsubscribe_method_name,
+                callback,
+            )
+            .with_context(|| format!("Registering {subscribe_method_name}"))?;
+
+        #[cfg(feature = "fuzzing")]
+        self.input_parsers.insert(subscribe_method_name, |params| {
+            params.parse::<Input>().is_ok()
+        });
+
+        Ok(self)
+    }
+}
+
+/// The name of the optional param with which clients pass back the [PendingStateVersion] of an
+/// earlier response.
+const PENDING_STATE_VERSION: &str = "pending_state_version";
+
+/// Parses method params into the method's input, along with the [PendingStateVersion] the client
+/// expects, if any.
+///
+/// The expected version can only be passed in named params, and is removed from them before
+/// parsing the input.
+fn parse_input<Input: ::serde::de::DeserializeOwned>(
+    params: jsonrpsee::types::Params<'_>,
+) -> Result<(Input, Option<PendingStateVersion>), jsonrpsee::core::Error> {
+    use jsonrpsee::types::error::CallError;
+    use jsonrpsee::types::Params;
+
+    let named = params
+        .as_str()
+        .filter(|json| json.contains(PENDING_STATE_VERSION))
+        .and_then(|json| serde_json::from_str::<serde_json::Map<_, _>>(json).ok());
+
+    match named {
+        Some(mut named) if named.contains_key(PENDING_STATE_VERSION) => {
+            let version = named.remove(PENDING_STATE_VERSION).expect("Key is present");
+            let version = serde_json::from_value(version).map_err(|e| {
+                CallError::InvalidParams(anyhow::anyhow!("Invalid {PENDING_STATE_VERSION}: {e}"))
+            })?;
+
+            let json = serde_json::Value::Object(named).to_string();
+            let input = Params::new(Some(&json)).parse()?;
+
+            Ok((input, Some(version)))
+        }
+        _ => Ok((params.parse()?, None)),
+    }
+}
+
+/// Fails with [RpcError::PendingStateChanged] if the pending block no longer has the version the
+/// client expects.
+async fn check_pending_state_version(
+    context: &RpcContext,
+    expected: Option<PendingStateVersion>,
+) -> Result<(), jsonrpsee::core::Error> {
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Ok(()),
+    };
+
+    let current = match &context.pending_data {
+        Some(pending_data) => pending_data.version().await,
+        None => None,
+    };
+
+    if current == Some(expected) {
+        Ok(())
+    } else {
+        Err(RpcError::PendingStateChanged.into())
+    }
 }
 
 #[cfg(test)]
@@ -208,4 +406,64 @@ mod tests {
             .unwrap();
         assert_eq!(message, input);
     }
+
+    #[tokio::test]
+    async fn pending_state_version() {
+        use starknet_gateway_types::pending::PendingStateVersion;
+
+        let ctx = RpcContext::for_tests_with_pending().await;
+        let version = ctx.pending_data.as_ref().unwrap().version().await.unwrap();
+
+        #[derive(serde::Deserialize)]
+        struct EchoInput {
+            inner: String,
+        }
+
+        async fn echo(_: RpcContext, input: EchoInput) -> Result<String, RpcError> {
+            Ok(input.inner)
+        }
+
+        let methods = super::Module::new(ctx)
+            .register_method("an_echo", echo)
+            .unwrap()
+            .build();
+
+        let server = ServerBuilder::default()
+            .build(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let _jh = server.start(methods).unwrap();
+
+        let client = TestClientBuilder::default()
+            .request_timeout(std::time::Duration::from_secs(2))
+            .address(addr)
+            .build()
+            .unwrap();
+
+        let message = client
+            .request::<String>(
+                "an_echo",
+                json!({"inner": "unchanged", "pending_state_version": version}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(message, "unchanged");
+
+        let stale = PendingStateVersion(version.0.wrapping_add(1));
+        let error = client
+            .request::<String>(
+                "an_echo",
+                json!({"inner": "changed", "pending_state_version": stale}),
+            )
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(
+            error,
+            jsonrpsee::core::Error::Call(jsonrpsee::types::error::CallError::Custom(error)) => {
+                assert_eq!(error.code(), RpcError::PendingStateChanged.code());
+            }
+        );
+    }
 }
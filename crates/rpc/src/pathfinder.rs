@@ -10,9 +10,80 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             Result::<_, RpcError>::Ok(pathfinder_common::consts::VERGEN_GIT_SEMVER_LIGHTWEIGHT)
         })?
         .register_method("v0.1_pathfinder_getProof", methods::get_proof)?
+        .register_method("v0.1_pathfinder_getClassProof", methods::get_class_proof)?
+        .register_method("v0.1_pathfinder_verifyProof", methods::verify_proof)?
         .register_method(
             "v0.1_pathfinder_getTransactionStatus",
             methods::get_transaction_status,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getTransactionByL1MessageHash",
+            methods::get_transaction_by_l1_message_hash,
+        )?
+        .register_method_with_no_input("v0.1_pathfinder_syncStatus", methods::sync_status)?
+        .register_method(
+            "v0.1_pathfinder_getReorgHistory",
+            methods::get_reorg_history,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getRevertedTransactions",
+            methods::get_reverted_transactions,
+        )?
+        .register_method_with_no_input(
+            "v0.1_pathfinder_getDatabaseStats",
+            methods::get_database_stats,
+        )?
+        .register_method_with_no_input("v0.1_pathfinder_getNodeInfo", methods::get_node_info)?
+        .register_method("v0.1_pathfinder_hashTypedData", methods::hash_typed_data)?
+        .register_method(
+            "v0.1_pathfinder_computeContractAddress",
+            methods::compute_contract_address,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getContractStorageEntries",
+            methods::get_contract_storage_entries,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getTransactionInclusionProof",
+            methods::get_transaction_inclusion_proof,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getReceiptsRange",
+            methods::get_receipts_range,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getNonceHistory",
+            methods::get_nonce_history,
+        )?
+        .register_subscription(
+            "pathfinder_subscribeTransactionReceipts",
+            "pathfinder_transactionReceipt",
+            "pathfinder_unsubscribeTransactionReceipts",
+            methods::subscribe_transaction_receipts,
+        )?
+        .register_subscription(
+            "pathfinder_subscribeTransactionStatus",
+            "pathfinder_transactionStatus",
+            "pathfinder_unsubscribeTransactionStatus",
+            methods::subscribe_transaction_status,
+        )?
+        .register_subscription(
+            "pathfinder_subscribeSyncMilestones",
+            "pathfinder_syncMilestone",
+            "pathfinder_unsubscribeSyncMilestones",
+            methods::subscribe_sync_milestones,
+        )?
+        .register_subscription(
+            "starknet_subscribeEvents",
+            "starknet_subscriptionEvents",
+            "starknet_unsubscribeEvents",
+            methods::subscribe_events,
+        )?
+        .register_subscription(
+            "pathfinder_subscribe",
+            "pathfinder_subscription",
+            "pathfinder_unsubscribe",
+            methods::subscribe,
         )?;
 
     Ok(module)
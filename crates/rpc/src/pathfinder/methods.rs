@@ -1,5 +1,43 @@
+mod compute_contract_address;
+mod get_class_proof;
+mod get_contract_storage_entries;
+mod get_database_stats;
+mod get_node_info;
+mod get_nonce_history;
 mod get_proof;
+mod get_receipts_range;
+mod get_reorg_history;
+mod get_reverted_transactions;
+mod get_transaction_by_l1_message_hash;
+mod get_transaction_inclusion_proof;
 mod get_transaction_status;
+mod hash_typed_data;
+mod subscribe;
+mod subscribe_events;
+mod subscribe_sync_milestones;
+mod subscribe_transaction_receipts;
+mod subscribe_transaction_status;
+mod sync_status;
+mod verify_proof;
 
+pub(crate) use compute_contract_address::compute_contract_address;
+pub(crate) use get_class_proof::get_class_proof;
+pub(crate) use get_contract_storage_entries::get_contract_storage_entries;
+pub(crate) use get_database_stats::get_database_stats;
+pub(crate) use get_node_info::get_node_info;
+pub(crate) use get_nonce_history::get_nonce_history;
 pub(crate) use get_proof::get_proof;
+pub(crate) use get_receipts_range::get_receipts_range;
+pub(crate) use get_reorg_history::get_reorg_history;
+pub(crate) use get_reverted_transactions::get_reverted_transactions;
+pub(crate) use get_transaction_by_l1_message_hash::get_transaction_by_l1_message_hash;
+pub(crate) use get_transaction_inclusion_proof::get_transaction_inclusion_proof;
 pub(crate) use get_transaction_status::get_transaction_status;
+pub(crate) use hash_typed_data::hash_typed_data;
+pub(crate) use subscribe::subscribe;
+pub(crate) use subscribe_events::subscribe_events;
+pub(crate) use subscribe_sync_milestones::subscribe_sync_milestones;
+pub(crate) use subscribe_transaction_receipts::subscribe_transaction_receipts;
+pub(crate) use subscribe_transaction_status::subscribe_transaction_status;
+pub(crate) use sync_status::sync_status;
+pub(crate) use verify_proof::verify_proof;
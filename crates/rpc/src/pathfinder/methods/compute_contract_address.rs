@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use crate::context::RpcContext;
+use pathfinder_common::{CallParam, ClassHash, ContractAddress, ContractAddressSalt};
+
+#[derive(Deserialize, Debug)]
+pub struct ComputeContractAddressInput {
+    pub class_hash: ClassHash,
+    pub contract_address_salt: ContractAddressSalt,
+    pub constructor_calldata: Vec<CallParam>,
+    /// The contract deploying the new contract. Defaults to zero, as for `DEPLOY_ACCOUNT`
+    /// transactions.
+    #[serde(default)]
+    pub deployer_address: Option<ContractAddress>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct ComputeContractAddressOutput {
+    contract_address: ContractAddress,
+}
+
+crate::error::generate_rpc_error_subset!(ComputeContractAddressError);
+
+/// Computes the address a contract will be deployed at, without deploying it.
+pub async fn compute_contract_address(
+    context: RpcContext,
+    input: ComputeContractAddressInput,
+) -> Result<ComputeContractAddressOutput, ComputeContractAddressError> {
+    let contract_address = context.chain_parameters.contract_address(
+        input.class_hash,
+        input.contract_address_salt,
+        input.constructor_calldata.into_iter().map(|param| param.0),
+        input.deployer_address.unwrap_or(ContractAddress::ZERO),
+    );
+
+    Ok(ComputeContractAddressOutput { contract_address })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    #[tokio::test]
+    async fn deploy_account() {
+        let input = serde_json::from_value(serde_json::json!({
+            "class_hash": "0x1fac3074c9d5282f0acc5c69a4781a1c711efea5e73c550c5d9fb253cf7fd3d",
+            "contract_address_salt": "0x6ccdf35bc37d9c5030215e2fb39b97de8f755c8ed43dd52a4c30e5ab96e61b",
+            "constructor_calldata": [
+                "0x539f848975104309396ee8ebaee0a45acb0238e0b13da9df0f855c082a3b7f3"
+            ]
+        }))
+        .unwrap();
+
+        let output = compute_contract_address(RpcContext::for_tests(), input)
+            .await
+            .unwrap();
+        assert_eq!(
+            output,
+            ComputeContractAddressOutput {
+                contract_address: ContractAddress::new_or_panic(felt!(
+                    "0x39696a0665e4c9806a709c7872030c63ca82b551925b2f517ed1985066217b8"
+                ))
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn deployer_address() {
+        let input = |deployer_address| ComputeContractAddressInput {
+            class_hash: ClassHash(felt!("0x1234")),
+            contract_address_salt: ContractAddressSalt(felt!("0x5678")),
+            constructor_calldata: vec![CallParam(felt!("0x1"))],
+            deployer_address,
+        };
+
+        let deployed_by_zero = compute_contract_address(RpcContext::for_tests(), input(None))
+            .await
+            .unwrap();
+        let deployed_by_contract = compute_contract_address(
+            RpcContext::for_tests(),
+            input(Some(ContractAddress::new_or_panic(felt!("0xabc")))),
+        )
+        .await
+        .unwrap();
+
+        assert_ne!(deployed_by_zero, deployed_by_contract);
+    }
+}
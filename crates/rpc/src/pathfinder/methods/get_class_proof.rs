@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use super::get_proof::{GetProofError, Proof};
+use crate::context::RpcContext;
+use pathfinder_common::{
+    BlockId, CasmHash, ClassCommitment, ClassCommitmentLeafHash, ClassHash, SierraHash,
+    StateCommitment, StorageCommitment,
+};
+use pathfinder_merkle_tree::state_tree::ClassCommitmentTree;
+use pathfinder_storage::{
+    ClassCommitmentLeavesTable, StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable,
+};
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct GetClassProofInput {
+    pub block_id: BlockId,
+    pub class_hash: ClassHash,
+}
+
+/// Holds the data required to verify the leaf of a class in the class commitment tree.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClassData {
+    /// The value of the class commitment tree leaf.
+    pub(super) leaf_hash: ClassCommitmentLeafHash,
+    /// Required to verify the compiled class hash to leaf hash calculation.
+    pub(super) compiled_class_hash: CasmHash,
+}
+
+/// Holds the membership/non-membership proof of a class in the class commitment tree.
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+pub struct GetClassProofOutput {
+    /// Required to verify that the hash of the storage commitment and the
+    /// [class_commitment](Self#class_commitment) matches the [state_commitment](Self#state_commitment).
+    storage_commitment: StorageCommitment,
+    /// The global state commitment. Absent for blocks prior to StarkNet 0.11.0,
+    /// which have no class commitment tree.
+    state_commitment: Option<StateCommitment>,
+    /// Root of the class commitment tree. Absent for blocks prior to StarkNet 0.11.0.
+    class_commitment: Option<ClassCommitment>,
+
+    /// Membership / Non-membership proof for the queried class
+    class_proof: Proof,
+
+    /// Additional class data if the class is a member of the tree.
+    class_data: Option<ClassData>,
+}
+
+/// Returns all the necessary data to trustlessly verify that a class is part of the
+/// class commitment tree of a particular block.
+pub async fn get_class_proof(
+    context: RpcContext,
+    input: GetClassProofInput,
+) -> Result<GetClassProofOutput, GetProofError> {
+    let block_id = match input.block_id {
+        BlockId::Hash(hash) => hash.into(),
+        BlockId::Number(number) => number.into(),
+        BlockId::Latest => StarknetBlocksBlockId::Latest,
+        BlockId::Pending => {
+            return Err(GetProofError::Internal(anyhow!(
+                "'pending' is not currently supported by this method!"
+            )))
+        }
+    };
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (storage_commitment, class_commitment) =
+            StarknetBlocksTable::get_state_commitment(&tx, block_id)
+                .context("Get state commitment for block")?
+                .ok_or(GetProofError::BlockNotFound)?;
+
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetProofError::StatePruned);
+        }
+
+        if class_commitment == ClassCommitment::ZERO {
+            // The class commitment tree is empty, so any class is trivially not a member.
+            return Ok(GetClassProofOutput {
+                storage_commitment,
+                state_commitment: None,
+                class_commitment: None,
+                class_proof: Proof(Vec::new()),
+                class_data: None,
+            });
+        }
+
+        let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
+
+        let class_commitment_tree =
+            ClassCommitmentTree::load(&tx, class_commitment).context("Class commitment tree")?;
+
+        let class = SierraHash(input.class_hash.0);
+
+        // Generate a proof for this class. If the class does not exist, this will
+        // be a "non membership" proof.
+        let class_proof = class_commitment_tree.get_proof(&class)?;
+        let class_proof = Proof(class_proof);
+
+        let class_data = match class_commitment_tree.get(class)? {
+            Some(leaf_hash) => {
+                let compiled_class_hash =
+                    ClassCommitmentLeavesTable::get_compiled_class_hash(&tx, &leaf_hash)
+                        .context("Get compiled class hash")?
+                        .ok_or_else(|| -> GetProofError {
+                            anyhow::anyhow!(
+                                "Compiled class hash missing for leaf_hash={}",
+                                leaf_hash
+                            )
+                            .into()
+                        })?;
+
+                Some(ClassData {
+                    leaf_hash,
+                    compiled_class_hash,
+                })
+            }
+            None => None,
+        };
+
+        Ok(GetClassProofOutput {
+            storage_commitment,
+            state_commitment: Some(state_commitment),
+            class_commitment: Some(class_commitment),
+            class_proof,
+            class_data,
+        })
+    });
+
+    jh.await.context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::{
+        felt, GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
+        StarknetBlockTimestamp,
+    };
+    use pathfinder_merkle_tree::merkle_tree::Membership;
+    use pathfinder_storage::{StarknetBlock, Storage};
+    use stark_hash::Felt;
+
+    use super::*;
+
+    const CLASS: ClassHash = ClassHash(felt!("0x123"));
+    const COMPILED_CLASS_HASH: CasmHash = CasmHash(felt!("0xabc"));
+    const STORAGE_COMMITMENT: StorageCommitment = StorageCommitment(felt!("0xdef"));
+
+    /// Block 0 predates the class commitment tree, while block 1 has [CLASS] declared in it.
+    fn setup() -> (RpcContext, ClassCommitment) {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let leaf_hash =
+            pathfinder_common::calculate_class_commitment_leaf_hash(COMPILED_CLASS_HASH);
+        ClassCommitmentLeavesTable::upsert(&tx, &leaf_hash, &COMPILED_CLASS_HASH).unwrap();
+        let mut tree = ClassCommitmentTree::load(&tx, ClassCommitment::ZERO).unwrap();
+        tree.set(SierraHash(CLASS.0), leaf_hash).unwrap();
+        let class_commitment = tree.apply().unwrap();
+
+        for (number, class_commitment) in [(0, ClassCommitment::ZERO), (1, class_commitment)] {
+            let block = StarknetBlock {
+                number: StarknetBlockNumber::new_or_panic(number),
+                hash: StarknetBlockHash(Felt::from_u64(number)),
+                root: StateCommitment::calculate(STORAGE_COMMITMENT, class_commitment),
+                timestamp: StarknetBlockTimestamp::new_or_panic(number),
+                gas_price: GasPrice::ZERO,
+                sequencer_address: SequencerAddress(Felt::ZERO),
+                transaction_commitment: None,
+                event_commitment: None,
+            };
+            StarknetBlocksTable::insert(&tx, &block, None, STORAGE_COMMITMENT, class_commitment)
+                .unwrap();
+        }
+        tx.commit().unwrap();
+
+        let context = RpcContext::for_tests().with_storage(storage);
+        (context, class_commitment)
+    }
+
+    fn input(block: u64, class_hash: ClassHash) -> GetClassProofInput {
+        GetClassProofInput {
+            block_id: BlockId::Number(StarknetBlockNumber::new_or_panic(block)),
+            class_hash,
+        }
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetClassProofInput {
+            block_id: BlockId::Hash(StarknetBlockHash(felt!("0xdeadbeef"))),
+            class_hash: ClassHash(felt!("0x1")),
+        };
+
+        let err = get_class_proof(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(err, GetProofError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn member() {
+        let (context, class_commitment) = setup();
+
+        let output = get_class_proof(context, input(1, CLASS)).await.unwrap();
+
+        assert_eq!(output.class_commitment, Some(class_commitment));
+        assert_eq!(
+            output.state_commitment,
+            Some(StateCommitment::calculate(
+                STORAGE_COMMITMENT,
+                class_commitment
+            ))
+        );
+        let class_data = output.class_data.unwrap();
+        assert_eq!(class_data.compiled_class_hash, COMPILED_CLASS_HASH);
+        assert_eq!(
+            class_data.leaf_hash,
+            pathfinder_common::calculate_class_commitment_leaf_hash(COMPILED_CLASS_HASH)
+        );
+        assert_eq!(
+            ClassCommitmentTree::verify_proof(
+                class_commitment,
+                SierraHash(CLASS.0),
+                class_data.leaf_hash,
+                &output.class_proof.0
+            ),
+            Some(Membership::Member)
+        );
+    }
+
+    #[tokio::test]
+    async fn non_member() {
+        let (context, class_commitment) = setup();
+        let undeclared = ClassHash(felt!("0x456"));
+
+        let output = get_class_proof(context, input(1, undeclared))
+            .await
+            .unwrap();
+
+        assert!(output.class_data.is_none());
+        assert_eq!(
+            ClassCommitmentTree::verify_proof(
+                class_commitment,
+                SierraHash(undeclared.0),
+                ClassCommitmentLeafHash(Felt::ZERO),
+                &output.class_proof.0
+            ),
+            Some(Membership::NonMember)
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_class_commitment() {
+        let (context, _) = setup();
+
+        let output = get_class_proof(context, input(0, CLASS)).await.unwrap();
+
+        assert!(output.class_proof.0.is_empty());
+        assert!(output.class_data.is_none());
+        assert_eq!(output.class_commitment, None);
+        assert_eq!(output.state_commitment, None);
+        assert_eq!(output.storage_commitment, STORAGE_COMMITMENT);
+    }
+}
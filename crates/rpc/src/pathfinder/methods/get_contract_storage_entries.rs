@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Context};
+use pathfinder_common::{BlockId, ContractAddress, StorageAddress, StorageValue};
+use pathfinder_merkle_tree::state_tree::{ContractsStateTree, StorageCommitmentTree};
+use pathfinder_storage::{
+    ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use stark_hash::Felt;
+
+use crate::context::RpcContext;
+
+/// Maximum number of storage entries returned by a single request.
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetContractStorageEntriesInput {
+    contract_address: ContractAddress,
+    block_id: BlockId,
+    /// The key to continue from, as returned by the previous request.
+    #[serde(default)]
+    continuation_token: Option<StorageAddress>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct StorageEntry {
+    key: StorageAddress,
+    value: StorageValue,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct GetContractStorageEntriesOutput {
+    /// Storage entries in ascending key order.
+    entries: Vec<StorageEntry>,
+    /// Present if there are more entries to fetch.
+    continuation_token: Option<StorageAddress>,
+}
+
+crate::error::generate_rpc_error_subset!(
+    GetContractStorageEntriesError: BlockNotFound,
+    ContractNotFound,
+    StatePruned
+);
+
+/// Returns a page of the key/value pairs in a contract's storage at the given block.
+pub async fn get_contract_storage_entries(
+    context: RpcContext,
+    input: GetContractStorageEntriesInput,
+) -> Result<GetContractStorageEntriesOutput, GetContractStorageEntriesError> {
+    let block_id = match input.block_id {
+        BlockId::Hash(hash) => hash.into(),
+        BlockId::Number(number) => number.into(),
+        BlockId::Latest => StarknetBlocksBlockId::Latest,
+        BlockId::Pending => {
+            return Err(GetContractStorageEntriesError::Internal(anyhow!(
+                "'pending' is not currently supported by this method!"
+            )))
+        }
+    };
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let storage_commitment = StarknetBlocksTable::get_storage_commitment(&tx, block_id)
+            .context("Get storage commitment for block")?
+            .ok_or(GetContractStorageEntriesError::BlockNotFound)?;
+
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetContractStorageEntriesError::StatePruned);
+        }
+
+        let storage_commitment_tree =
+            StorageCommitmentTree::load(&tx, storage_commitment).context("Global state tree")?;
+
+        let contract_state_hash = storage_commitment_tree
+            .get(input.contract_address)
+            .context("Get contract state hash from global state tree")?
+            .ok_or(GetContractStorageEntriesError::ContractNotFound)?;
+
+        let contract_state_root = ContractsStateTable::get_root(&tx, contract_state_hash)
+            .context("Get contract state root")?
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Contract state root not found for contract state hash {}",
+                    contract_state_hash.0
+                )
+            })?;
+
+        let contract_state_tree = ContractsStateTree::load(&tx, contract_state_root)
+            .context("Load contract state tree")?;
+
+        let start = input
+            .continuation_token
+            .unwrap_or(StorageAddress::new_or_panic(Felt::ZERO));
+
+        // Fetch one extra entry, whose key becomes the continuation token.
+        let mut entries = contract_state_tree
+            .entries(start, PAGE_SIZE + 1)
+            .context("Iterate contract state tree")?;
+
+        let continuation_token = if entries.len() > PAGE_SIZE {
+            entries.pop().map(|(key, _)| key)
+        } else {
+            None
+        };
+
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| StorageEntry { key, value })
+            .collect();
+
+        Ok(GetContractStorageEntriesOutput {
+            entries,
+            continuation_token,
+        })
+    });
+
+    jh.await.context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pathfinder_common::{felt_bytes, StarknetBlockNumber};
+
+    #[tokio::test]
+    async fn entries() {
+        let context = RpcContext::for_tests();
+        let input = GetContractStorageEntriesInput {
+            contract_address: ContractAddress::new_or_panic(felt_bytes!(b"contract 1")),
+            block_id: BlockId::Number(StarknetBlockNumber::new_or_panic(1)),
+            continuation_token: None,
+        };
+
+        let output = get_contract_storage_entries(context, input).await.unwrap();
+        assert_eq!(
+            output,
+            GetContractStorageEntriesOutput {
+                entries: vec![StorageEntry {
+                    key: StorageAddress::new_or_panic(felt_bytes!(b"storage addr 0")),
+                    value: StorageValue(felt_bytes!(b"storage value 1")),
+                }],
+                continuation_token: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn contract_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetContractStorageEntriesInput {
+            contract_address: ContractAddress::new_or_panic(felt_bytes!(b"invalid")),
+            block_id: BlockId::Latest,
+            continuation_token: None,
+        };
+
+        let error = get_contract_storage_entries(context, input)
+            .await
+            .unwrap_err();
+        assert_matches!(error, GetContractStorageEntriesError::ContractNotFound);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetContractStorageEntriesInput {
+            contract_address: ContractAddress::new_or_panic(felt_bytes!(b"contract 1")),
+            block_id: BlockId::Number(StarknetBlockNumber::new_or_panic(9999)),
+            continuation_token: None,
+        };
+
+        let error = get_contract_storage_entries(context, input)
+            .await
+            .unwrap_err();
+        assert_matches!(error, GetContractStorageEntriesError::BlockNotFound);
+    }
+}
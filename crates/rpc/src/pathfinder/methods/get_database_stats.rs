@@ -0,0 +1,81 @@
+use anyhow::Context;
+use pathfinder_storage::MigrationHistoryTable;
+use serde::Serialize;
+
+use crate::context::RpcContext;
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct DatabaseStats {
+    schema_version: usize,
+    /// Oldest first. Only migrations applied by a version of pathfinder which records them are
+    /// included.
+    migrations: Vec<Migration>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Migration {
+    /// The schema version after the migration.
+    revision: usize,
+    /// Unix timestamp in seconds of when the migration was started.
+    applied_at: u64,
+    duration_ms: u64,
+}
+
+impl From<pathfinder_storage::MigrationRecord> for Migration {
+    fn from(record: pathfinder_storage::MigrationRecord) -> Self {
+        Self {
+            revision: record.revision,
+            applied_at: record.applied_at,
+            duration_ms: record.duration_ms,
+        }
+    }
+}
+
+crate::error::generate_rpc_error_subset!(GetDatabaseStatsError);
+
+/// Returns the schema version of the database and the history of its migrations.
+pub async fn get_database_stats(
+    context: RpcContext,
+) -> Result<DatabaseStats, GetDatabaseStatsError> {
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let schema_version =
+                pathfinder_storage::schema_version(tx).context("Reading schema version")?;
+            let migrations = MigrationHistoryTable::get_all(tx)
+                .context("Reading migration history from database")?
+                .into_iter()
+                .map(Migration::from)
+                .collect();
+
+            Ok(DatabaseStats {
+                schema_version,
+                migrations,
+            })
+        })
+    });
+
+    let stats = jh.await.context("Database read panic or shutting down")??;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn new_database() {
+        let context = RpcContext::for_tests();
+
+        let stats = get_database_stats(context).await.unwrap();
+
+        let revisions = stats
+            .migrations
+            .iter()
+            .map(|migration| migration.revision)
+            .collect::<Vec<_>>();
+        let expected = (1..=stats.schema_version).collect::<Vec<_>>();
+        assert_eq!(revisions, expected);
+    }
+}
@@ -0,0 +1,125 @@
+use pathfinder_common::consts::{GIT_COMMIT, VERGEN_GIT_SEMVER_LIGHTWEIGHT};
+use pathfinder_common::{Chain, ChainId};
+use serde::Serialize;
+
+use crate::cairo::ext_py::CAIRO_LANG_VERSION;
+use crate::context::RpcContext;
+
+/// The RPC specification versions which are served, each at `/rpc/<version>`.
+const RPC_VERSIONS: [&str; 3] = ["v0.2", "v0.3", "pathfinder/v0.1"];
+
+crate::error::generate_rpc_error_subset!(GetNodeInfoError);
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct NodeInfo {
+    version: &'static str,
+    /// [None] if the build does not know its commit.
+    git_commit: Option<&'static str>,
+    /// [None] if the network is not known.
+    network: Option<&'static str>,
+    chain_id: ChainId,
+    rpc_versions: [&'static str; 3],
+    features: Features,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Features {
+    /// Whether the state of every historical block is kept.
+    archive: bool,
+    /// The pruning of historical state, [None] if no state is pruned.
+    pruning: Option<Pruning>,
+    p2p: bool,
+    /// [None] if calls, fee estimation and simulation are disabled.
+    execution_engine: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Pruning {
+    /// The number of blocks before the latest one whose state is kept.
+    retained_blocks: u64,
+}
+
+/// Returns the version and the capabilities of the node, so that clients can adapt to them.
+pub async fn get_node_info(context: RpcContext) -> Result<NodeInfo, GetNodeInfoError> {
+    let identity = context.node_identity;
+    let network = identity.network.map(|chain| match chain {
+        Chain::Mainnet => "mainnet",
+        Chain::Testnet => "testnet",
+        Chain::Testnet2 => "testnet2",
+        Chain::Integration => "integration",
+        Chain::Custom => "custom",
+    });
+
+    Ok(NodeInfo {
+        version: VERGEN_GIT_SEMVER_LIGHTWEIGHT,
+        git_commit: GIT_COMMIT,
+        network,
+        chain_id: context.chain_id,
+        rpc_versions: RPC_VERSIONS,
+        features: Features {
+            archive: identity.state_retention.is_none(),
+            pruning: identity
+                .state_retention
+                .map(|retained_blocks| Pruning { retained_blocks }),
+            p2p: identity.p2p,
+            execution_engine: context
+                .call_handle
+                .as_ref()
+                .map(|_| format!("cairo-lang {CAIRO_LANG_VERSION}")),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::NodeIdentity;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn node_info() {
+        let context = RpcContext::for_tests().with_node_identity(NodeIdentity {
+            network: Some(Chain::Testnet),
+            p2p: false,
+            state_retention: None,
+        });
+
+        let info = serde_json::to_value(get_node_info(context).await.unwrap()).unwrap();
+        assert_eq!(info["version"], VERGEN_GIT_SEMVER_LIGHTWEIGHT);
+        assert_eq!(info["network"], "testnet");
+        assert_eq!(info["chain_id"], json!(ChainId::TESTNET));
+        assert_eq!(
+            info["features"],
+            json!({
+                "archive": true,
+                "pruning": null,
+                "p2p": false,
+                "execution_engine": null,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn pruning() {
+        let context = RpcContext::for_tests().with_node_identity(NodeIdentity {
+            network: Some(Chain::Testnet),
+            p2p: false,
+            state_retention: Some(128),
+        });
+
+        let info = serde_json::to_value(get_node_info(context).await.unwrap()).unwrap();
+        assert_eq!(info["features"]["archive"], false);
+        assert_eq!(info["features"]["pruning"], json!({"retained_blocks": 128}));
+    }
+
+    #[test]
+    fn rpc_versions_are_served() {
+        for version in RPC_VERSIONS {
+            let path = format!("/rpc/{version}");
+            assert!(
+                crate::versioning::method_name_prefixes(&path).is_some(),
+                "{path}"
+            );
+        }
+    }
+}
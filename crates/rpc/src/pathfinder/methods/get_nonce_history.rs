@@ -0,0 +1,172 @@
+use anyhow::Context;
+use pathfinder_common::{ContractAddress, ContractNonce, StarknetBlockNumber};
+use pathfinder_storage::NonceHistoryTable;
+use serde::{Deserialize, Serialize};
+
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+/// Maximum number of blocks a single request may span.
+const MAX_BLOCK_RANGE: u64 = 10_000;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetNonceHistoryInput {
+    contract_address: ContractAddress,
+    from_block: StarknetBlockNumber,
+    /// Inclusive.
+    to_block: StarknetBlockNumber,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct NonceUpdate {
+    block_number: StarknetBlockNumber,
+    #[serde_as(as = "RpcFelt")]
+    nonce: ContractNonce,
+}
+
+impl From<pathfinder_storage::NonceUpdate> for NonceUpdate {
+    fn from(update: pathfinder_storage::NonceUpdate) -> Self {
+        Self {
+            block_number: update.block_number,
+            nonce: update.nonce,
+        }
+    }
+}
+
+// Written out, as `generate_rpc_error_subset!` does not support struct variants.
+#[derive(Debug)]
+pub enum GetNonceHistoryError {
+    Internal(anyhow::Error),
+    BlockRangeTooLarge { limit: u64, requested: u64 },
+}
+impl From<anyhow::Error> for GetNonceHistoryError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+impl From<GetNonceHistoryError> for crate::error::RpcError {
+    fn from(x: GetNonceHistoryError) -> Self {
+        match x {
+            GetNonceHistoryError::BlockRangeTooLarge { limit, requested } => {
+                Self::BlockRangeTooLarge { limit, requested }
+            }
+            GetNonceHistoryError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+/// Returns the changes of a contract's nonce in the given range of blocks, oldest first.
+///
+/// Consecutive nonces which differ by more than the number of transactions sent in between point
+/// at dropped or replayed transactions.
+pub async fn get_nonce_history(
+    context: RpcContext,
+    input: GetNonceHistoryInput,
+) -> Result<Vec<NonceUpdate>, GetNonceHistoryError> {
+    let requested = (input.to_block.get() + 1).saturating_sub(input.from_block.get());
+    if requested > MAX_BLOCK_RANGE {
+        return Err(GetNonceHistoryError::BlockRangeTooLarge {
+            limit: MAX_BLOCK_RANGE,
+            requested,
+        });
+    }
+
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let updates = NonceHistoryTable::get(
+                tx,
+                input.contract_address,
+                input.from_block,
+                input.to_block,
+            )
+            .context("Reading nonce history from database")?
+            .into_iter()
+            .map(NonceUpdate::from)
+            .collect();
+
+            Ok(updates)
+        })
+    });
+
+    let output = jh.await.context("Database read panic or shutting down")??;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{felt, felt_bytes};
+    use pathfinder_storage::types::state_update::Nonce;
+
+    #[tokio::test]
+    async fn history() {
+        let context = RpcContext::for_tests();
+        let contract_address = ContractAddress::new_or_panic(felt_bytes!(b"contract 0"));
+
+        let mut db = context.storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        for (block, nonce) in [(1, felt!("0x1")), (2, felt!("0x5"))] {
+            let nonces = [Nonce {
+                contract_address,
+                nonce: ContractNonce(nonce),
+            }];
+            NonceHistoryTable::insert(&tx, StarknetBlockNumber::new_or_panic(block), &nonces)
+                .unwrap();
+        }
+        tx.commit().unwrap();
+
+        let input = GetNonceHistoryInput {
+            contract_address,
+            from_block: StarknetBlockNumber::GENESIS,
+            to_block: StarknetBlockNumber::new_or_panic(2),
+        };
+        let result = get_nonce_history(context, input).await.unwrap();
+        assert_eq!(
+            result,
+            vec![
+                NonceUpdate {
+                    block_number: StarknetBlockNumber::new_or_panic(1),
+                    nonce: ContractNonce(felt!("0x1")),
+                },
+                NonceUpdate {
+                    block_number: StarknetBlockNumber::new_or_panic(2),
+                    nonce: ContractNonce(felt!("0x5")),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn range_too_large() {
+        let context = RpcContext::for_tests();
+        let input = GetNonceHistoryInput {
+            contract_address: ContractAddress::new_or_panic(felt!("0x1")),
+            from_block: StarknetBlockNumber::new_or_panic(1),
+            to_block: StarknetBlockNumber::new_or_panic(MAX_BLOCK_RANGE + 1),
+        };
+
+        let error = get_nonce_history(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(
+            error,
+            GetNonceHistoryError::BlockRangeTooLarge {
+                limit: MAX_BLOCK_RANGE,
+                requested
+            } if requested == MAX_BLOCK_RANGE + 1
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let update = NonceUpdate {
+            block_number: StarknetBlockNumber::new_or_panic(2),
+            nonce: ContractNonce(felt!("0x5")),
+        };
+
+        let json = serde_json::to_value(update).unwrap();
+        assert_eq!(json, serde_json::json!({"block_number": 2, "nonce": "0x5"}));
+    }
+}
@@ -9,7 +9,9 @@ use pathfinder_common::{
 };
 use pathfinder_merkle_tree::merkle_tree::ProofNode;
 use pathfinder_merkle_tree::state_tree::{ContractsStateTree, StorageCommitmentTree};
-use pathfinder_storage::{ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable};
+use pathfinder_storage::{
+    ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable,
+};
 use stark_hash::Felt;
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -25,6 +27,7 @@ pub enum GetProofError {
     Internal(anyhow::Error),
     BlockNotFound,
     ProofLimitExceeded { limit: u32, requested: u32 },
+    StatePruned,
 }
 impl From<anyhow::Error> for GetProofError {
     fn from(e: anyhow::Error) -> Self {
@@ -38,13 +41,15 @@ impl From<GetProofError> for crate::error::RpcError {
                 Self::ProofLimitExceeded { limit, requested }
             }
             GetProofError::BlockNotFound => Self::BlockNotFound,
+            GetProofError::StatePruned => Self::StatePruned,
             GetProofError::Internal(internal) => Self::Internal(internal),
         }
     }
 }
 
-/// Utility struct used for serializing.
-#[derive(Debug, Serialize)]
+/// Utility struct used for (de)serializing.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 struct PathWrapper {
     value: Felt,
     len: usize,
@@ -52,7 +57,7 @@ struct PathWrapper {
 
 /// Wrapper around [`Vec<ProofNode>`] as we don't control [ProofNode] in this crate.
 #[derive(Debug)]
-pub struct Proof(Vec<ProofNode>);
+pub struct Proof(pub(super) Vec<ProofNode>);
 
 impl Serialize for Proof {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -106,22 +111,73 @@ impl Serialize for Proof {
     }
 }
 
+impl<'de> Deserialize<'de> for Proof {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use pathfinder_merkle_tree::merkle_tree::{BinaryProofNode, EdgeProofNode};
+        use serde::de::Error;
+
+        /// Mirrors the serialization format of [Proof].
+        #[derive(Deserialize)]
+        #[serde(rename_all = "snake_case", deny_unknown_fields)]
+        enum DeProofNode {
+            Binary { left: Felt, right: Felt },
+            Edge { path: PathWrapper, child: Felt },
+        }
+
+        let nodes = Vec::<DeProofNode>::deserialize(deserializer)?;
+        let nodes = nodes
+            .into_iter()
+            .map(|node| match node {
+                DeProofNode::Binary { left, right } => Ok(ProofNode::Binary(BinaryProofNode {
+                    left_hash: left,
+                    right_hash: right,
+                })),
+                DeProofNode::Edge { path, child } => {
+                    if path.len > 251 {
+                        return Err(D::Error::custom(format!(
+                            "edge path length {} exceeds 251",
+                            path.len
+                        )));
+                    }
+                    let bits = path.value.view_bits();
+                    let (leading, path_bits) = bits.split_at(bits.len() - path.len);
+                    if leading.any() || path.value.has_more_than_251_bits() {
+                        return Err(D::Error::custom(format!(
+                            "edge path value does not fit in {} bits",
+                            path.len
+                        )));
+                    }
+                    Ok(ProofNode::Edge(EdgeProofNode {
+                        path: path_bits.to_bitvec(),
+                        child_hash: child,
+                    }))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Proof(nodes))
+    }
+}
+
 /// Holds the data and proofs for a specific contract.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ContractData {
     /// Required to verify the contract state hash to contract root calculation.
-    class_hash: ClassHash,
+    pub(super) class_hash: ClassHash,
     /// Required to verify the contract state hash to contract root calculation.
-    nonce: ContractNonce,
+    pub(super) nonce: ContractNonce,
 
     /// Root of the Contract state tree
-    root: ContractRoot,
+    pub(super) root: ContractRoot,
 
     /// This is currently just a constant = 0, however it might change in the future.
-    contract_state_hash_version: Felt,
+    pub(super) contract_state_hash_version: Felt,
 
     /// The proofs associated with the queried storage values
-    storage_proofs: Vec<Proof>,
+    pub(super) storage_proofs: Vec<Proof>,
 }
 
 /// Holds the membership/non-membership of a contract and its associated contract contract if the contract exists.
@@ -186,6 +242,10 @@ pub async fn get_proof(
                 // by using a dedicated error code from the RPC API spec
                 .ok_or(GetProofError::BlockNotFound)?;
 
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetProofError::StatePruned);
+        }
+
         let (state_commitment, class_commitment) = if class_commitment == ClassCommitment::ZERO {
             (None, None)
         } else {
@@ -280,4 +340,33 @@ mod tests {
         let err = get_proof(context, input).await.unwrap_err();
         assert_matches::assert_matches!(err, GetProofError::ProofLimitExceeded { .. });
     }
+
+    #[test]
+    fn proof_serde_round_trip() {
+        use pathfinder_merkle_tree::merkle_tree::{BinaryProofNode, EdgeProofNode};
+
+        let proof = Proof(vec![
+            ProofNode::Binary(BinaryProofNode {
+                left_hash: felt!("0x1"),
+                right_hash: felt!("0x2"),
+            }),
+            ProofNode::Edge(EdgeProofNode {
+                // 0b011
+                path: felt!("0x3").view_bits()[248..].to_bitvec(),
+                child_hash: felt!("0x3"),
+            }),
+        ]);
+
+        let json = serde_json::to_value(&proof).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"binary": {"left": "0x1", "right": "0x2"}},
+                {"edge": {"path": {"value": "0x3", "len": 3}, "child": "0x3"}},
+            ])
+        );
+
+        let deserialized = serde_json::from_value::<Proof>(json).unwrap();
+        assert_eq!(deserialized.0, proof.0);
+    }
 }
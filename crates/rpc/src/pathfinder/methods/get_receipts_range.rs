@@ -0,0 +1,217 @@
+use anyhow::Context;
+use pathfinder_common::StarknetBlockNumber;
+use pathfinder_storage::{RefsTable, StarknetTransactionsTable};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::context::RpcContext;
+use crate::v02::method::get_transaction_receipt::types::TransactionReceipt;
+use crate::v02::types::reply::BlockStatus;
+
+/// Maximum number of receipts returned by a single request.
+const PAGE_SIZE: usize = 1000;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetReceiptsRangeInput {
+    from_block: StarknetBlockNumber,
+    /// Inclusive.
+    to_block: StarknetBlockNumber,
+    /// The receipt to continue from, as returned by the previous request.
+    #[serde(default)]
+    cursor: Option<Cursor>,
+}
+
+/// Position of a receipt in the chain.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Cursor {
+    block_number: StarknetBlockNumber,
+    transaction_index: u64,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct GetReceiptsRangeOutput {
+    /// Receipts in execution order.
+    receipts: Vec<TransactionReceipt>,
+    /// Present if there are more receipts to fetch.
+    cursor: Option<Cursor>,
+}
+
+crate::error::generate_rpc_error_subset!(GetReceiptsRangeError);
+
+/// Returns a page of the receipts of the given range of blocks, in execution order.
+///
+/// Indexers can backfill a range of blocks by following the cursor, rather than requesting the
+/// receipt of each transaction separately.
+pub async fn get_receipts_range(
+    context: RpcContext,
+    input: GetReceiptsRangeInput,
+) -> Result<GetReceiptsRangeOutput, GetReceiptsRangeError> {
+    receipts_page(context, input, PAGE_SIZE).await
+}
+
+async fn receipts_page(
+    context: RpcContext,
+    input: GetReceiptsRangeInput,
+    page_size: usize,
+) -> Result<GetReceiptsRangeOutput, GetReceiptsRangeError> {
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let start = match input.cursor {
+                Some(cursor) if cursor.block_number >= input.from_block => cursor,
+                _ => Cursor {
+                    block_number: input.from_block,
+                    transaction_index: 0,
+                },
+            };
+
+            // Fetch one extra receipt, whose position becomes the cursor.
+            let mut transactions = StarknetTransactionsTable::get_receipts_range(
+                tx,
+                start.block_number,
+                start.transaction_index,
+                input.to_block,
+                page_size + 1,
+            )
+            .context("Reading receipts from database")?;
+
+            let cursor = if transactions.len() > page_size {
+                transactions.pop().map(|transaction| Cursor {
+                    block_number: transaction.block_number,
+                    transaction_index: transaction.index,
+                })
+            } else {
+                None
+            };
+
+            let l1_l2_head =
+                RefsTable::get_l1_l2_head(tx).context("Read latest L1 head from database")?;
+            let receipts = transactions
+                .into_iter()
+                .map(|transaction| {
+                    let status = match l1_l2_head {
+                        Some(number) if number >= transaction.block_number => {
+                            BlockStatus::AcceptedOnL1
+                        }
+                        _ => BlockStatus::AcceptedOnL2,
+                    };
+
+                    TransactionReceipt::with_block_data(
+                        transaction.receipt,
+                        status,
+                        transaction.block_hash,
+                        transaction.block_number,
+                        transaction.transaction,
+                    )
+                })
+                .collect();
+
+            Ok(GetReceiptsRangeOutput { receipts, cursor })
+        })
+    });
+
+    let output = jh.await.context("Database read panic or shutting down")??;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{felt_bytes, StarknetTransactionHash};
+
+    fn hashes(output: &GetReceiptsRangeOutput) -> Vec<StarknetTransactionHash> {
+        output
+            .receipts
+            .iter()
+            .map(|receipt| match receipt {
+                TransactionReceipt::Invoke(receipt) => receipt.common.transaction_hash,
+                _ => unreachable!("Test transactions are invokes"),
+            })
+            .collect()
+    }
+
+    fn input(from: u64, to: u64, cursor: Option<Cursor>) -> GetReceiptsRangeInput {
+        GetReceiptsRangeInput {
+            from_block: StarknetBlockNumber::new_or_panic(from),
+            to_block: StarknetBlockNumber::new_or_panic(to),
+            cursor,
+        }
+    }
+
+    #[tokio::test]
+    async fn range() {
+        let context = RpcContext::for_tests();
+        let output = get_receipts_range(context, input(1, 2, None))
+            .await
+            .unwrap();
+
+        let expected = [
+            felt_bytes!(b"txn 1"),
+            felt_bytes!(b"txn 2"),
+            felt_bytes!(b"txn 3"),
+            felt_bytes!(b"txn 4 "),
+            felt_bytes!(b"txn 5"),
+        ]
+        .map(StarknetTransactionHash);
+        assert_eq!(hashes(&output), expected);
+        assert_eq!(output.cursor, None);
+    }
+
+    #[tokio::test]
+    async fn pages() {
+        let context = RpcContext::for_tests();
+
+        let first = receipts_page(context.clone(), input(0, 2, None), 2)
+            .await
+            .unwrap();
+        let cursor = Cursor {
+            block_number: StarknetBlockNumber::new_or_panic(1),
+            transaction_index: 1,
+        };
+        assert_eq!(first.cursor, Some(cursor));
+
+        let second = receipts_page(context, input(0, 2, Some(cursor)), 2)
+            .await
+            .unwrap();
+        let expected = [felt_bytes!(b"txn 2"), felt_bytes!(b"txn 3")].map(StarknetTransactionHash);
+        assert_eq!(hashes(&second), expected);
+        assert_eq!(
+            second.cursor,
+            Some(Cursor {
+                block_number: StarknetBlockNumber::new_or_panic(2),
+                transaction_index: 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_range() {
+        let context = RpcContext::for_tests();
+        let output = get_receipts_range(context, input(2, 1, None))
+            .await
+            .unwrap();
+
+        assert_eq!(output.receipts, vec![]);
+        assert_eq!(output.cursor, None);
+    }
+
+    #[test]
+    fn cursor_serialization() {
+        let cursor = Cursor {
+            block_number: StarknetBlockNumber::new_or_panic(2),
+            transaction_index: 1,
+        };
+
+        let json = serde_json::to_value(cursor).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"block_number": 2, "transaction_index": 1})
+        );
+        assert_eq!(serde_json::from_value::<Cursor>(json).unwrap(), cursor);
+    }
+}
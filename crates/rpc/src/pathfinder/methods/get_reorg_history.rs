@@ -0,0 +1,156 @@
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
+use pathfinder_storage::ReorgHistoryTable;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+/// Number of reorgs returned if no limit is given.
+const DEFAULT_LIMIT: u64 = 100;
+/// Maximum number of reorgs returned by a single request.
+const MAX_LIMIT: u64 = 1000;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetReorgHistoryInput {
+    /// Maximum number of reorgs to return.
+    #[serde(default)]
+    limit: Option<u64>,
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Reorg {
+    /// Unix timestamp in seconds of when the reorg was processed.
+    timestamp: u64,
+    /// Number of blocks which were removed.
+    depth: u64,
+    old_head: BlockHashAndNumber,
+    /// The block the chain was rewound to. Absent if the genesis block was removed.
+    new_head: Option<BlockHashAndNumber>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct BlockHashAndNumber {
+    #[serde_as(as = "RpcFelt")]
+    block_hash: StarknetBlockHash,
+    block_number: StarknetBlockNumber,
+}
+
+impl From<pathfinder_storage::Reorg> for Reorg {
+    fn from(reorg: pathfinder_storage::Reorg) -> Self {
+        Self {
+            timestamp: reorg.timestamp,
+            depth: reorg.depth,
+            old_head: BlockHashAndNumber {
+                block_hash: reorg.old_head_hash,
+                block_number: reorg.old_head_number,
+            },
+            new_head: reorg
+                .new_head
+                .map(|(block_number, block_hash)| BlockHashAndNumber {
+                    block_hash,
+                    block_number,
+                }),
+        }
+    }
+}
+
+crate::error::generate_rpc_error_subset!(GetReorgHistoryError);
+
+/// Returns the most recent L2 reorgs processed by this node, newest first.
+pub async fn get_reorg_history(
+    context: RpcContext,
+    input: GetReorgHistoryInput,
+) -> Result<Vec<Reorg>, GetReorgHistoryError> {
+    let limit = input.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let reorgs = ReorgHistoryTable::get_latest(tx, limit)
+                .context("Reading reorg history from database")?
+                .into_iter()
+                .map(Reorg::from)
+                .collect();
+
+            Ok(reorgs)
+        })
+    });
+
+    let output = jh.await.context("Database read panic or shutting down")??;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    #[tokio::test]
+    async fn newest_first() {
+        let context = RpcContext::for_tests();
+
+        let old = pathfinder_storage::Reorg {
+            timestamp: 1000,
+            depth: 1,
+            old_head_number: StarknetBlockNumber::GENESIS,
+            old_head_hash: StarknetBlockHash(felt!("0x1")),
+            new_head: None,
+        };
+        let new = pathfinder_storage::Reorg {
+            timestamp: 2000,
+            depth: 2,
+            old_head_number: StarknetBlockNumber::new_or_panic(3),
+            old_head_hash: StarknetBlockHash(felt!("0x3")),
+            new_head: Some((
+                StarknetBlockNumber::new_or_panic(1),
+                StarknetBlockHash(felt!("0x11")),
+            )),
+        };
+
+        {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            ReorgHistoryTable::insert(&tx, &old).unwrap();
+            ReorgHistoryTable::insert(&tx, &new).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let input = GetReorgHistoryInput { limit: None };
+        let result = get_reorg_history(context.clone(), input).await.unwrap();
+        assert_eq!(result, vec![Reorg::from(new), Reorg::from(old)]);
+
+        let input = GetReorgHistoryInput { limit: Some(1) };
+        let result = get_reorg_history(context, input).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].depth, 2);
+    }
+
+    #[test]
+    fn serialization() {
+        let reorg = Reorg {
+            timestamp: 1000,
+            depth: 1,
+            old_head: BlockHashAndNumber {
+                block_hash: StarknetBlockHash(felt!("0x1")),
+                block_number: StarknetBlockNumber::GENESIS,
+            },
+            new_head: None,
+        };
+
+        let json = serde_json::to_value(reorg).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "timestamp": 1000,
+                "depth": 1,
+                "old_head": {"block_hash": "0x1", "block_number": 0},
+            })
+        );
+    }
+}
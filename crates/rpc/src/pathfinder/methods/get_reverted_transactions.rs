@@ -0,0 +1,148 @@
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockNumber, StarknetTransactionHash};
+use pathfinder_storage::StarknetTransactionsTable;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+/// Maximum number of blocks a single request may span.
+const MAX_BLOCK_RANGE: u64 = 10_000;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetRevertedTransactionsInput {
+    from_block: StarknetBlockNumber,
+    /// Inclusive.
+    to_block: StarknetBlockNumber,
+}
+
+#[serde_with::serde_as]
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct RevertedTransaction {
+    #[serde_as(as = "RpcFelt")]
+    transaction_hash: StarknetTransactionHash,
+    block_number: StarknetBlockNumber,
+    revert_reason: Option<String>,
+}
+
+impl From<pathfinder_storage::RevertedTransaction> for RevertedTransaction {
+    fn from(reverted: pathfinder_storage::RevertedTransaction) -> Self {
+        Self {
+            transaction_hash: reverted.transaction_hash,
+            block_number: reverted.block_number,
+            revert_reason: reverted.revert_error,
+        }
+    }
+}
+
+// Written out, as `generate_rpc_error_subset!` does not support struct variants.
+#[derive(Debug)]
+pub enum GetRevertedTransactionsError {
+    Internal(anyhow::Error),
+    BlockRangeTooLarge { limit: u64, requested: u64 },
+}
+impl From<anyhow::Error> for GetRevertedTransactionsError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+impl From<GetRevertedTransactionsError> for crate::error::RpcError {
+    fn from(x: GetRevertedTransactionsError) -> Self {
+        match x {
+            GetRevertedTransactionsError::BlockRangeTooLarge { limit, requested } => {
+                Self::BlockRangeTooLarge { limit, requested }
+            }
+            GetRevertedTransactionsError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+/// Returns the reverted transactions of the given range of blocks, in execution order.
+pub async fn get_reverted_transactions(
+    context: RpcContext,
+    input: GetRevertedTransactionsInput,
+) -> Result<Vec<RevertedTransaction>, GetRevertedTransactionsError> {
+    let requested = (input.to_block.get() + 1).saturating_sub(input.from_block.get());
+    if requested > MAX_BLOCK_RANGE {
+        return Err(GetRevertedTransactionsError::BlockRangeTooLarge {
+            limit: MAX_BLOCK_RANGE,
+            requested,
+        });
+    }
+
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let reverted =
+                StarknetTransactionsTable::get_reverted(tx, input.from_block, input.to_block)
+                    .context("Reading reverted transactions from database")?
+                    .into_iter()
+                    .map(RevertedTransaction::from)
+                    .collect();
+
+            Ok(reverted)
+        })
+    });
+
+    let output = jh.await.context("Database read panic or shutting down")??;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    #[tokio::test]
+    async fn range_too_large() {
+        let context = RpcContext::for_tests();
+        let input = GetRevertedTransactionsInput {
+            from_block: StarknetBlockNumber::new_or_panic(1),
+            to_block: StarknetBlockNumber::new_or_panic(MAX_BLOCK_RANGE + 1),
+        };
+
+        let error = get_reverted_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(
+            error,
+            GetRevertedTransactionsError::BlockRangeTooLarge {
+                limit: MAX_BLOCK_RANGE,
+                requested
+            } if requested == MAX_BLOCK_RANGE + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_range() {
+        let context = RpcContext::for_tests();
+        let input = GetRevertedTransactionsInput {
+            from_block: StarknetBlockNumber::new_or_panic(2),
+            to_block: StarknetBlockNumber::new_or_panic(1),
+        };
+
+        let result = get_reverted_transactions(context, input).await.unwrap();
+        assert_eq!(result, vec![]);
+    }
+
+    #[test]
+    fn serialization() {
+        let reverted = RevertedTransaction {
+            transaction_hash: StarknetTransactionHash(felt!("0x1")),
+            block_number: StarknetBlockNumber::new_or_panic(2),
+            revert_reason: Some("Out of gas".to_owned()),
+        };
+
+        let json = serde_json::to_value(reverted).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "transaction_hash": "0x1",
+                "block_number": 2,
+                "revert_reason": "Out of gas",
+            })
+        );
+    }
+}
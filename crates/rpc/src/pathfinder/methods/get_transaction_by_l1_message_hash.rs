@@ -0,0 +1,157 @@
+use anyhow::Context;
+use pathfinder_common::{
+    L1ToL2MessageHash, StarknetBlockHash, StarknetBlockNumber, StarknetTransactionHash,
+};
+use pathfinder_storage::StarknetTransactionsTable;
+use serde::{Deserialize, Serialize};
+
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetTransactionByL1MessageHashInput {
+    /// The hash of the L1 to L2 message, as computed by the StarkNet core contract.
+    message_hash: L1ToL2MessageHash,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct GetTransactionByL1MessageHashOutput {
+    #[serde_as(as = "RpcFelt")]
+    transaction_hash: StarknetTransactionHash,
+    #[serde_as(as = "RpcFelt")]
+    block_hash: StarknetBlockHash,
+    block_number: StarknetBlockNumber,
+}
+
+crate::error::generate_rpc_error_subset!(GetTransactionByL1MessageHashError);
+
+/// Returns the L1 handler transaction which consumed the given L1 to L2 message, or `null` if no
+/// synced block contains such a transaction yet.
+pub async fn get_transaction_by_l1_message_hash(
+    context: RpcContext,
+    input: GetTransactionByL1MessageHashInput,
+) -> Result<Option<GetTransactionByL1MessageHashOutput>, GetTransactionByL1MessageHashError> {
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        context.storage.read(|tx| {
+            let output = StarknetTransactionsTable::get_by_l1_to_l2_message(tx, input.message_hash)
+                .context("Reading L1 handler transaction from database")?
+                .map(|(transaction_hash, block_number, block_hash)| {
+                    GetTransactionByL1MessageHashOutput {
+                        transaction_hash,
+                        block_hash,
+                        block_number,
+                    }
+                });
+
+            Ok(output)
+        })
+    });
+
+    let output = jh.await.context("Database read panic or shutting down")??;
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt, CallParam, ContractAddress, EntryPoint, EthereumAddress, L1ToL2MessageNonce,
+        StarknetTransactionIndex, TransactionNonce, TransactionVersion,
+    };
+    use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable};
+    use starknet_gateway_types::reply::transaction::{
+        L1HandlerTransaction, L1ToL2Message, Receipt, Transaction,
+    };
+
+    #[tokio::test]
+    async fn consumed_message() {
+        let context = RpcContext::for_tests();
+
+        let message = L1ToL2Message {
+            from_address: EthereumAddress(ethers::types::H160::from_low_u64_be(0xabc)),
+            payload: vec![],
+            selector: EntryPoint(felt!("0x1")),
+            to_address: ContractAddress::new_or_panic(felt!("0x2")),
+            nonce: Some(L1ToL2MessageNonce(felt!("0x3"))),
+        };
+        let transaction_hash = StarknetTransactionHash(felt!("0x1234"));
+
+        let block = {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            let block = StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest)
+                .unwrap()
+                .unwrap();
+
+            let transaction = Transaction::L1Handler(L1HandlerTransaction {
+                contract_address: message.to_address,
+                entry_point_selector: message.selector,
+                nonce: TransactionNonce(felt!("0x3")),
+                calldata: vec![CallParam(felt!("0xabc"))],
+                transaction_hash,
+                version: TransactionVersion::ZERO,
+            });
+            let receipt = Receipt {
+                actual_fee: None,
+                events: vec![],
+                execution_resources: None,
+                l1_to_l2_consumed_message: Some(message.clone()),
+                l2_to_l1_messages: vec![],
+                transaction_hash,
+                transaction_index: StarknetTransactionIndex::new_or_panic(100),
+                execution_status: Default::default(),
+                revert_error: None,
+            };
+            StarknetTransactionsTable::upsert(
+                &tx,
+                block.hash,
+                block.number,
+                &[(transaction, receipt)],
+            )
+            .unwrap();
+            tx.commit().unwrap();
+            block
+        };
+
+        let input = GetTransactionByL1MessageHashInput {
+            message_hash: message.hash(),
+        };
+        let result = get_transaction_by_l1_message_hash(context.clone(), input)
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Some(GetTransactionByL1MessageHashOutput {
+                transaction_hash,
+                block_hash: block.hash,
+                block_number: block.number,
+            })
+        );
+
+        let input = GetTransactionByL1MessageHashInput {
+            message_hash: L1ToL2MessageHash(ethers::types::H256::from_low_u64_be(1)),
+        };
+        let result = get_transaction_by_l1_message_hash(context, input)
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn input() {
+        let input =
+            serde_json::from_value::<GetTransactionByL1MessageHashInput>(serde_json::json!({
+                "message_hash": "0x983362b305278e5d9be23bfae7108c6523131877c628adba0f2c32be5a9676ab"
+            }))
+            .unwrap();
+
+        let expected = "0x983362b305278e5d9be23bfae7108c6523131877c628adba0f2c32be5a9676ab"
+            .parse()
+            .unwrap();
+        assert_eq!(input.message_hash, L1ToL2MessageHash(expected));
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::Context;
+use pathfinder_common::{
+    EventCommitment, StarknetBlockHash, StarknetBlockNumber, StarknetTransactionHash,
+    TransactionCommitment,
+};
+use pathfinder_merkle_tree::commitment_tree::{
+    calculate_event_hash, calculate_transaction_hash_with_signature, CommitmentTree,
+};
+use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable};
+use serde::{Deserialize, Serialize};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::transaction::{Receipt, Transaction};
+
+use super::get_proof::Proof;
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct GetTransactionInclusionProofInput {
+    transaction_hash: StarknetTransactionHash,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug)]
+pub struct GetTransactionInclusionProofOutput {
+    block_hash: StarknetBlockHash,
+    block_number: StarknetBlockNumber,
+    transaction_index: u64,
+    transaction_commitment: TransactionCommitment,
+    /// The leaf of the transaction in the transaction commitment tree, which is the hash of the
+    /// transaction hash and its signature.
+    #[serde_as(as = "RpcFelt")]
+    transaction_leaf: Felt,
+    transaction_proof: Proof,
+    event_commitment: EventCommitment,
+    /// Proofs for each event emitted by the transaction.
+    events: Vec<EventProof>,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug)]
+pub struct EventProof {
+    /// Index of the event among all events of the block.
+    index: u64,
+    /// The leaf of the event in the event commitment tree, which is the hash of the event.
+    #[serde_as(as = "RpcFelt")]
+    leaf: Felt,
+    proof: Proof,
+}
+
+crate::error::generate_rpc_error_subset!(
+    GetTransactionInclusionProofError: TxnHashNotFound,
+    NoCommitments
+);
+
+/// Proves that a transaction and the events of its receipt are part of the transaction and event
+/// commitments of their block, so that inclusion can be verified against a trusted block header
+/// without downloading the whole block.
+///
+/// Blocks before StarkNet 0.7 have no commitments, so their transactions cannot be proven.
+pub async fn get_transaction_inclusion_proof(
+    context: RpcContext,
+    input: GetTransactionInclusionProofInput,
+) -> Result<GetTransactionInclusionProofOutput, GetTransactionInclusionProofError> {
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let stored = context.storage.read(|tx| {
+            let block_hash = match StarknetTransactionsTable::get_transaction_with_receipt(
+                tx,
+                input.transaction_hash,
+            )
+            .context("Reading transaction from database")?
+            {
+                Some((_, _, block_hash)) => block_hash,
+                None => return Ok(None),
+            };
+
+            let block = StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Hash(block_hash))
+                .context("Reading block from database")?
+                .context("Block of transaction is missing from database")?;
+            let transactions = StarknetTransactionsTable::get_transaction_data_for_block(
+                tx,
+                StarknetBlocksBlockId::Hash(block_hash),
+            )
+            .context("Reading block transactions from database")?;
+
+            Ok(Some((block_hash, block, transactions)))
+        })?;
+        let (block_hash, block, transactions) = match stored {
+            Some(stored) => stored,
+            None => return Err(GetTransactionInclusionProofError::TxnHashNotFound),
+        };
+        // The commitments are only stored for blocks whose hash commits to them.
+        let (stored_transaction_commitment, stored_event_commitment) =
+            match (block.transaction_commitment, block.event_commitment) {
+                (Some(transactions), Some(events)) => (transactions, events),
+                _ => return Err(GetTransactionInclusionProofError::NoCommitments),
+            };
+
+        let transaction_index = transactions
+            .iter()
+            .position(|(transaction, _)| transaction.hash() == input.transaction_hash)
+            .context("Transaction is missing from its block")?;
+
+        let (mut transaction_tree, mut event_tree) = commitment_trees(&transactions)?;
+        let transaction_commitment = TransactionCommitment(transaction_tree.commit_mut()?);
+        let event_commitment = EventCommitment(event_tree.commit_mut()?);
+        if transaction_commitment != stored_transaction_commitment
+            || event_commitment != stored_event_commitment
+        {
+            return Err(anyhow::anyhow!("Commitments do not match the stored block").into());
+        }
+
+        // The events of the block are numbered across all of its transactions.
+        let first_event = transactions[..transaction_index]
+            .iter()
+            .map(|(_, receipt)| receipt.events.len() as u64)
+            .sum::<u64>();
+        let (transaction, receipt) = &transactions[transaction_index];
+        let transaction_index = transaction_index as u64;
+        let events = receipt
+            .events
+            .iter()
+            .zip(first_event..)
+            .map(|(event, index)| {
+                Ok(EventProof {
+                    index,
+                    leaf: calculate_event_hash(event),
+                    proof: Proof(event_tree.get_proof(index)?),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(GetTransactionInclusionProofOutput {
+            block_hash,
+            block_number: block.number,
+            transaction_index,
+            transaction_commitment,
+            transaction_leaf: calculate_transaction_hash_with_signature(transaction),
+            transaction_proof: Proof(transaction_tree.get_proof(transaction_index)?),
+            event_commitment,
+            events,
+        })
+    });
+
+    jh.await.context("Database read panic or shutting down")?
+}
+
+/// Builds the transaction and event commitment trees of a block from its transactions.
+fn commitment_trees(
+    transactions: &[(Transaction, Receipt)],
+) -> anyhow::Result<(CommitmentTree, CommitmentTree)> {
+    let mut transaction_tree = CommitmentTree::default();
+    let mut event_tree = CommitmentTree::default();
+    let mut event_index = 0;
+    for (index, (transaction, receipt)) in transactions.iter().enumerate() {
+        transaction_tree.set(
+            index as u64,
+            calculate_transaction_hash_with_signature(transaction),
+        )?;
+
+        for event in &receipt.events {
+            event_tree.set(event_index, calculate_event_hash(event))?;
+            event_index += 1;
+        }
+    }
+
+    Ok((transaction_tree, event_tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt_bytes;
+    use pathfinder_merkle_tree::merkle_tree::Membership;
+
+    /// The test blocks have no commitments, so they are added as for blocks since StarkNet 0.7.
+    fn with_commitments(context: RpcContext) -> RpcContext {
+        let mut connection = context.storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        for number in 0..=2 {
+            let transactions = StarknetTransactionsTable::get_transaction_data_for_block(
+                &tx,
+                StarknetBlockNumber::new_or_panic(number).into(),
+            )
+            .unwrap();
+            let (mut transaction_tree, mut event_tree) = commitment_trees(&transactions).unwrap();
+            tx.execute(
+                "UPDATE starknet_blocks SET transaction_commitment = ?, event_commitment = ? \
+                 WHERE number = ?",
+                rusqlite::params![
+                    TransactionCommitment(transaction_tree.commit_mut().unwrap()),
+                    EventCommitment(event_tree.commit_mut().unwrap()),
+                    number
+                ],
+            )
+            .unwrap();
+        }
+        tx.commit().unwrap();
+        context
+    }
+
+    async fn proof(context: RpcContext, hash: &[u8]) -> GetTransactionInclusionProofOutput {
+        let input = GetTransactionInclusionProofInput {
+            transaction_hash: StarknetTransactionHash(Felt::from_be_slice(hash).unwrap()),
+        };
+        get_transaction_inclusion_proof(context, input)
+            .await
+            .unwrap()
+    }
+
+    fn verify(output: &GetTransactionInclusionProofOutput) {
+        assert_eq!(
+            CommitmentTree::verify_proof(
+                output.transaction_commitment.0,
+                output.transaction_index,
+                output.transaction_leaf,
+                &output.transaction_proof.0,
+            ),
+            Some(Membership::Member)
+        );
+
+        for event in &output.events {
+            assert_eq!(
+                CommitmentTree::verify_proof(
+                    output.event_commitment.0,
+                    event.index,
+                    event.leaf,
+                    &event.proof.0,
+                ),
+                Some(Membership::Member)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn with_events() {
+        let context = with_commitments(RpcContext::for_tests());
+        let output = proof(context, b"txn 0").await;
+
+        assert_eq!(
+            output.block_hash,
+            StarknetBlockHash(felt_bytes!(b"genesis"))
+        );
+        assert_eq!(output.transaction_index, 0);
+        assert_eq!(output.events.len(), 1);
+        verify(&output);
+    }
+
+    #[tokio::test]
+    async fn within_block() {
+        let context = with_commitments(RpcContext::for_tests());
+        let output = proof(context, b"txn 4 ").await;
+
+        assert_eq!(output.block_number, StarknetBlockNumber::new_or_panic(2));
+        assert_eq!(output.transaction_index, 1);
+        assert!(output.events.is_empty());
+        verify(&output);
+    }
+
+    #[tokio::test]
+    async fn hash_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetTransactionInclusionProofInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"non_existent")),
+        };
+
+        let error = get_transaction_inclusion_proof(context, input)
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(error, GetTransactionInclusionProofError::TxnHashNotFound);
+    }
+
+    #[tokio::test]
+    async fn without_commitments() {
+        let context = RpcContext::for_tests();
+        let input = GetTransactionInclusionProofInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"txn 0")),
+        };
+
+        let error = get_transaction_inclusion_proof(context, input)
+            .await
+            .unwrap_err();
+        assert_matches::assert_matches!(error, GetTransactionInclusionProofError::NoCommitments);
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::Context;
 use pathfinder_common::{StarknetBlockNumber, StarknetTransactionHash};
 use pathfinder_storage::Storage;
 use rusqlite::OptionalExtension;
-use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::pending::PendingSource;
 
 use crate::context::RpcContext;
 use crate::v02::common::get_block_status;
@@ -35,7 +35,7 @@ pub async fn get_transaction_status(
     .await
     .context("Database read panic or shutting down")?
     .context("Checking database for transaction")?;
-    if let Some(status) = db_status {
+    if let Some((_, status)) = db_status {
         return Ok(status);
     }
 
@@ -50,7 +50,10 @@ pub async fn get_transaction_status(
         .map_err(GetGatewayTransactionError::Internal)
 }
 
-async fn is_pending_tx(pending: &PendingData, tx_hash: &StarknetTransactionHash) -> bool {
+pub(super) async fn is_pending_tx(
+    pending: &dyn PendingSource,
+    tx_hash: &StarknetTransactionHash,
+) -> bool {
     pending
         .block()
         .await
@@ -58,47 +61,44 @@ async fn is_pending_tx(pending: &PendingData, tx_hash: &StarknetTransactionHash)
         .unwrap_or_default()
 }
 
-fn check_database(
+/// Returns the number of the stored block containing the transaction, along with its status.
+pub(super) fn check_database(
     storage: &Storage,
     transaction_hash: &StarknetTransactionHash,
-) -> anyhow::Result<Option<GatewayStatus>> {
-    let mut db = storage
-        .connection()
-        .context("Opening database connection")?;
-
-    let db_tx = db.transaction().context("Creating database transaction")?;
-
-    // Get the transaction from storage.
-    let block_number = db_tx
-        .query_row(
-            r"SELECT starknet_blocks.number FROM starknet_transactions 
+) -> anyhow::Result<Option<(StarknetBlockNumber, GatewayStatus)>> {
+    storage.read(|tx| {
+        // Get the transaction from storage.
+        let block_number = tx
+            .query_row(
+                r"SELECT starknet_blocks.number FROM starknet_transactions 
     JOIN starknet_blocks ON starknet_transactions.block_hash = starknet_blocks.hash
     WHERE starknet_transactions.hash = ?",
-            [transaction_hash],
-            |row| {
-                let number = row.get_ref_unwrap(0).as_i64()?;
-                Ok(StarknetBlockNumber::new_or_panic(number as u64))
-            },
-        )
-        .optional()
-        .context("Fetching transaction's block number from database")?;
-
-    let block_number = match block_number {
-        Some(block_number) => block_number,
-        None => return anyhow::Ok(None),
-    };
-
-    let status =
-        get_block_status(&db_tx, block_number).context("Fetching block status from database")?;
-    use crate::v02::types::reply::BlockStatus;
-    let status = match status {
-        BlockStatus::Pending => GatewayStatus::Pending,
-        BlockStatus::AcceptedOnL2 => GatewayStatus::AcceptedOnL2,
-        BlockStatus::AcceptedOnL1 => GatewayStatus::AcceptedOnL1,
-        BlockStatus::Rejected => GatewayStatus::Rejected,
-    };
-
-    Ok(Some(status))
+                [transaction_hash],
+                |row| {
+                    let number = row.get_ref_unwrap(0).as_i64()?;
+                    Ok(StarknetBlockNumber::new_or_panic(number as u64))
+                },
+            )
+            .optional()
+            .context("Fetching transaction's block number from database")?;
+
+        let block_number = match block_number {
+            Some(block_number) => block_number,
+            None => return anyhow::Ok(None),
+        };
+
+        let status =
+            get_block_status(tx, block_number).context("Fetching block status from database")?;
+        use crate::v02::types::reply::BlockStatus;
+        let status = match status {
+            BlockStatus::Pending => GatewayStatus::Pending,
+            BlockStatus::AcceptedOnL2 => GatewayStatus::AcceptedOnL2,
+            BlockStatus::AcceptedOnL1 => GatewayStatus::AcceptedOnL1,
+            BlockStatus::Rejected => GatewayStatus::Rejected,
+        };
+
+        Ok(Some((block_number, status)))
+    })
 }
 
 /// A local definition of the [gateway's status type](starknet_gateway_types::reply::Status) to decouple this from the official gateway types.
@@ -155,7 +155,10 @@ mod tests {
         .unwrap()
         .unwrap();
 
-        assert_eq!(status, GatewayStatus::AcceptedOnL2);
+        assert_eq!(
+            status,
+            (StarknetBlockNumber::GENESIS, GatewayStatus::AcceptedOnL2)
+        );
     }
 
     #[tokio::test]
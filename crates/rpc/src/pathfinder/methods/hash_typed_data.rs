@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use stark_hash::Felt;
+
+use crate::context::RpcContext;
+use pathfinder_common::typed_data::{TypedData, TypedDataError};
+use pathfinder_common::{ChainId, ContractAddress};
+
+#[derive(Deserialize, Debug)]
+pub struct HashTypedDataInput {
+    pub typed_data: TypedData,
+    /// The account which signs the message.
+    pub account_address: ContractAddress,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct HashTypedDataOutput {
+    message_hash: Felt,
+}
+
+#[derive(Debug)]
+pub enum HashTypedDataError {
+    Internal(anyhow::Error),
+    InvalidTypedData { reason: String },
+    ChainIdMismatch { expected: ChainId, domain: ChainId },
+}
+impl From<anyhow::Error> for HashTypedDataError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+impl From<TypedDataError> for HashTypedDataError {
+    fn from(e: TypedDataError) -> Self {
+        match e {
+            TypedDataError::ChainIdMismatch { expected, domain } => {
+                Self::ChainIdMismatch { expected, domain }
+            }
+            other => Self::InvalidTypedData {
+                reason: other.to_string(),
+            },
+        }
+    }
+}
+impl From<HashTypedDataError> for crate::error::RpcError {
+    fn from(x: HashTypedDataError) -> Self {
+        match x {
+            HashTypedDataError::InvalidTypedData { reason } => Self::InvalidTypedData { reason },
+            HashTypedDataError::ChainIdMismatch { expected, domain } => {
+                Self::TypedDataChainIdMismatch { expected, domain }
+            }
+            HashTypedDataError::Internal(internal) => Self::Internal(internal),
+        }
+    }
+}
+
+/// Calculates the SNIP-12 hash of a typed data message, which the account signs off-chain.
+///
+/// The message's domain must be for the chain of this node.
+pub async fn hash_typed_data(
+    context: RpcContext,
+    input: HashTypedDataInput,
+) -> Result<HashTypedDataOutput, HashTypedDataError> {
+    let message_hash = input
+        .typed_data
+        .message_hash_on(input.account_address, &context.chain_parameters)?;
+
+    Ok(HashTypedDataOutput { message_hash })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn input(chain_id: &str) -> HashTypedDataInput {
+        serde_json::from_value(serde_json::json!({
+            "typed_data": {
+                "types": {
+                    "StarkNetDomain": [
+                        { "name": "name", "type": "felt" },
+                        { "name": "version", "type": "felt" },
+                        { "name": "chainId", "type": "felt" }
+                    ],
+                    "Message": [
+                        { "name": "contents", "type": "felt" }
+                    ]
+                },
+                "primaryType": "Message",
+                "domain": {
+                    "name": "Test",
+                    "version": "1",
+                    "chainId": chain_id
+                },
+                "message": {
+                    "contents": "Hello"
+                }
+            },
+            "account_address": "0x1234"
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn hash() {
+        let context = RpcContext::for_tests();
+        let input = input("SN_GOERLI");
+        let expected = input
+            .typed_data
+            .message_hash(input.account_address, ChainId::TESTNET)
+            .unwrap();
+
+        let output = hash_typed_data(context, input).await.unwrap();
+        assert_eq!(
+            output,
+            HashTypedDataOutput {
+                message_hash: expected
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn other_chain() {
+        let context = RpcContext::for_tests();
+
+        let error = hash_typed_data(context, input("SN_MAIN"))
+            .await
+            .unwrap_err();
+        assert_matches!(
+            error,
+            HashTypedDataError::ChainIdMismatch { expected, domain }
+                if expected == ChainId::TESTNET && domain == ChainId::MAINNET
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        let context = RpcContext::for_tests();
+        let mut input = input("SN_GOERLI");
+        input.typed_data.message.clear();
+
+        let error = hash_typed_data(context, input).await.unwrap_err();
+        assert_matches!(
+            error,
+            HashTypedDataError::InvalidTypedData { reason } if reason == "Value of contents is missing"
+        );
+    }
+}
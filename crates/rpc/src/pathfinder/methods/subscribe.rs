@@ -0,0 +1,201 @@
+use futures::Stream;
+use pathfinder_common::{
+    SequencerAddress, StarknetBlockHash, StarknetBlockNumber, StarknetBlockTimestamp,
+    StateCommitment,
+};
+use serde::{Deserialize, Serialize};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::Block;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+use crate::ChainUpdate;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeInput {
+    kind: SubscriptionKind,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub enum SubscriptionKind {
+    /// The header of each block committed by sync.
+    #[serde(rename = "newHeads")]
+    NewHeads,
+}
+
+/// The header of a block committed by sync.
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct NewHead {
+    #[serde_as(as = "RpcFelt")]
+    block_hash: StarknetBlockHash,
+    #[serde_as(as = "RpcFelt")]
+    parent_hash: StarknetBlockHash,
+    block_number: StarknetBlockNumber,
+    #[serde_as(as = "RpcFelt")]
+    new_root: StateCommitment,
+    timestamp: StarknetBlockTimestamp,
+    #[serde_as(as = "RpcFelt")]
+    sequencer_address: SequencerAddress,
+}
+
+impl From<&Block> for NewHead {
+    fn from(block: &Block) -> Self {
+        Self {
+            block_hash: block.block_hash,
+            parent_hash: block.parent_block_hash,
+            block_number: block.block_number,
+            new_root: block.state_commitment,
+            timestamp: block.timestamp,
+            // Blocks prior to StarkNet 0.8 have no sequencer address.
+            sequencer_address: block
+                .sequencer_address
+                .unwrap_or(SequencerAddress(Felt::ZERO)),
+        }
+    }
+}
+
+crate::error::generate_rpc_error_subset!(SubscribeError: ReadOnly);
+
+/// Streams a notification of the given kind each time sync commits a new block, so that clients
+/// need not poll for new blocks.
+///
+/// The subscription is closed if the subscriber falls too far behind, rather than silently
+/// skipping blocks. Read-only replicas do not sync, and reject the subscription.
+pub fn subscribe(
+    context: RpcContext,
+    input: SubscribeInput,
+) -> Result<impl Stream<Item = NewHead> + Unpin + Send + 'static, SubscribeError> {
+    let SubscriptionKind::NewHeads = input.kind;
+    if context.read_only {
+        return Err(SubscribeError::ReadOnly);
+    }
+    let updates = context.sync_status.chain_updates.subscribe();
+
+    let stream = futures::stream::unfold(updates, |mut updates| async move {
+        loop {
+            match updates.recv().await {
+                Ok(ChainUpdate::Block(block)) => {
+                    return Some((NewHead::from(block.as_ref()), updates))
+                }
+                // After a reorg, the new chain's blocks are announced as they are stored.
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(%skipped, "Closing lagging new heads subscription");
+                    return None;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Box::pin(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use pathfinder_common::{felt, felt_bytes};
+    use std::sync::Arc;
+
+    fn block() -> Block {
+        Block {
+            block_hash: StarknetBlockHash(felt_bytes!(b"block 3")),
+            block_number: StarknetBlockNumber::new_or_panic(3),
+            gas_price: None,
+            parent_block_hash: StarknetBlockHash(felt_bytes!(b"latest")),
+            sequencer_address: Some(SequencerAddress(felt!("0x1"))),
+            state_commitment: StateCommitment(felt!("0x2")),
+            status: starknet_gateway_types::reply::Status::AcceptedOnL2,
+            timestamp: StarknetBlockTimestamp::new_or_panic(10),
+            transaction_receipts: vec![],
+            transactions: vec![],
+            starknet_version: None,
+        }
+    }
+
+    #[test]
+    fn input() {
+        let input = serde_json::from_value::<SubscribeInput>(serde_json::json!(["newHeads"]));
+        assert_eq!(
+            input.unwrap(),
+            SubscribeInput {
+                kind: SubscriptionKind::NewHeads
+            }
+        );
+
+        let input = serde_json::from_value::<SubscribeInput>(serde_json::json!(["newBlocks"]));
+        assert!(input.is_err());
+    }
+
+    #[tokio::test]
+    async fn streams_new_heads() {
+        let context = RpcContext::for_tests();
+        let input = SubscribeInput {
+            kind: SubscriptionKind::NewHeads,
+        };
+        let mut stream = subscribe(context.clone(), input).unwrap();
+
+        let block = block();
+        context
+            .sync_status
+            .chain_updates
+            .send(ChainUpdate::Reorg(StarknetBlockNumber::new_or_panic(3)))
+            .unwrap();
+        context
+            .sync_status
+            .chain_updates
+            .send(ChainUpdate::Block(Arc::new(block.clone())))
+            .unwrap();
+
+        let head = stream.next().await.unwrap();
+        assert_eq!(head, NewHead::from(&block));
+        assert_eq!(
+            serde_json::to_value(head).unwrap(),
+            serde_json::json!({
+                "block_hash": "0x626c6f636b2033",
+                "parent_hash": "0x6c6174657374",
+                "block_number": 3,
+                "new_root": "0x2",
+                "timestamp": 10,
+                "sequencer_address": "0x1",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn closes_when_lagging() {
+        let context = RpcContext::for_tests();
+        let input = SubscribeInput {
+            kind: SubscriptionKind::NewHeads,
+        };
+        let block = ChainUpdate::Block(Arc::new(block()));
+
+        let mut stream = subscribe(context.clone(), input).unwrap();
+        for _ in 0..=crate::SyncState::CHAIN_UPDATES_CAPACITY {
+            context
+                .sync_status
+                .chain_updates
+                .send(block.clone())
+                .unwrap();
+        }
+
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn read_only() {
+        let context = RpcContext::for_tests().with_read_only();
+        let input = SubscribeInput {
+            kind: SubscriptionKind::NewHeads,
+        };
+
+        assert!(matches!(
+            subscribe(context, input),
+            Err(SubscribeError::ReadOnly)
+        ));
+    }
+}
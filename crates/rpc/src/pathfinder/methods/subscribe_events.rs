@@ -0,0 +1,319 @@
+use futures::{Stream, StreamExt};
+use pathfinder_common::{
+    ContractAddress, EventData, EventKey, StarknetBlockHash, StarknetBlockNumber,
+    StarknetTransactionHash,
+};
+use pathfinder_storage::StarknetEventsTable;
+use serde::{Deserialize, Serialize};
+use starknet_gateway_types::reply::Block;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::RpcContext;
+use crate::felt::{RpcFelt, RpcFelt251};
+use crate::ChainUpdate;
+
+/// Filters events the same way as `starknet_getEvents`.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeEventsInput {
+    /// Only include events emitted by this contract.
+    #[serde(default)]
+    address: Option<ContractAddress>,
+    /// Only include events whose keys match these, position by position. Each position lists the
+    /// keys it accepts, where an empty list accepts any key.
+    #[serde(default)]
+    keys: Vec<Vec<EventKey>>,
+}
+
+impl SubscribeEventsInput {
+    fn validate(&self) -> Result<(), SubscribeEventsError> {
+        let limit = StarknetEventsTable::KEY_FILTER_LIMIT;
+        if self.keys.len() > limit {
+            return Err(SubscribeEventsError::TooManyKeysInFilter {
+                limit,
+                requested: self.keys.len(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Events without a key at a position which the filter restricts do not match.
+    fn matches(&self, from_address: ContractAddress, keys: &[EventKey]) -> bool {
+        let address_matches = self
+            .address
+            .map(|address| from_address == address)
+            .unwrap_or(true);
+        let keys_match = self.keys.iter().enumerate().all(|(i, filter)| {
+            filter.is_empty() || keys.get(i).map_or(false, |key| filter.contains(key))
+        });
+
+        address_matches && keys_match
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventNotification {
+    /// An event emitted in a block stored by sync.
+    Event(EmittedEvent),
+    /// A reorg removed the blocks from `first_block_number` onwards, so the events already sent
+    /// for these blocks are no longer part of the chain.
+    Reorg {
+        first_block_number: StarknetBlockNumber,
+    },
+}
+
+/// An event, in the same format as returned by `starknet_getEvents`.
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct EmittedEvent {
+    #[serde_as(as = "Vec<RpcFelt>")]
+    data: Vec<EventData>,
+    #[serde_as(as = "Vec<RpcFelt>")]
+    keys: Vec<EventKey>,
+    #[serde_as(as = "RpcFelt251")]
+    from_address: ContractAddress,
+    #[serde_as(as = "RpcFelt")]
+    block_hash: StarknetBlockHash,
+    block_number: StarknetBlockNumber,
+    #[serde_as(as = "RpcFelt")]
+    transaction_hash: StarknetTransactionHash,
+}
+
+#[derive(Debug)]
+pub enum SubscribeEventsError {
+    Internal(anyhow::Error),
+    TooManyKeysInFilter { limit: usize, requested: usize },
+    ReadOnly,
+}
+
+impl From<anyhow::Error> for SubscribeEventsError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Internal(e)
+    }
+}
+
+impl From<SubscribeEventsError> for crate::error::RpcError {
+    fn from(e: SubscribeEventsError) -> Self {
+        match e {
+            SubscribeEventsError::Internal(internal) => Self::Internal(internal),
+            SubscribeEventsError::TooManyKeysInFilter { limit, requested } => {
+                Self::TooManyKeysInFilter { limit, requested }
+            }
+            SubscribeEventsError::ReadOnly => Self::ReadOnly,
+        }
+    }
+}
+
+/// Streams the events matching the filter as each block is stored by sync, and notifies of reorgs
+/// so that subscribers can discard the events of the removed blocks.
+///
+/// The subscription is closed if the subscriber falls too far behind, rather than silently
+/// skipping events. Read-only replicas do not sync, and reject the subscription.
+pub fn subscribe_events(
+    context: RpcContext,
+    input: Option<SubscribeEventsInput>,
+) -> Result<impl Stream<Item = EventNotification> + Unpin + Send + 'static, SubscribeEventsError> {
+    if context.read_only {
+        return Err(SubscribeEventsError::ReadOnly);
+    }
+    let filter = input.unwrap_or_default();
+    filter.validate()?;
+    let updates = context.sync_status.chain_updates.subscribe();
+
+    let stream = futures::stream::unfold(updates, |mut updates| async move {
+        match updates.recv().await {
+            Ok(update) => Some((update, updates)),
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::debug!(%skipped, "Closing lagging event subscription");
+                None
+            }
+            Err(RecvError::Closed) => None,
+        }
+    })
+    .flat_map(move |update| futures::stream::iter(notifications(&update, &filter)));
+
+    Ok(Box::pin(stream))
+}
+
+fn notifications(update: &ChainUpdate, filter: &SubscribeEventsInput) -> Vec<EventNotification> {
+    match update {
+        ChainUpdate::Block(block) => events(block, filter),
+        ChainUpdate::Reorg(first_block_number) => vec![EventNotification::Reorg {
+            first_block_number: *first_block_number,
+        }],
+        ChainUpdate::AcceptedOnL1(_) | ChainUpdate::Pending(_) => vec![],
+    }
+}
+
+fn events(block: &Block, filter: &SubscribeEventsInput) -> Vec<EventNotification> {
+    block
+        .transaction_receipts
+        .iter()
+        .flat_map(|receipt| {
+            receipt
+                .events
+                .iter()
+                .map(move |event| (receipt.transaction_hash, event))
+        })
+        .filter(|(_, event)| filter.matches(event.from_address, &event.keys))
+        .map(|(transaction_hash, event)| {
+            EventNotification::Event(EmittedEvent {
+                data: event.data.clone(),
+                keys: event.keys.clone(),
+                from_address: event.from_address,
+                block_hash: block.block_hash,
+                block_number: block.block_number,
+                transaction_hash,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use pathfinder_common::{felt, felt_bytes, StateCommitment};
+    use stark_hash::Felt;
+    use std::sync::Arc;
+
+    /// Turns the pending test block into a block stored by sync.
+    pub(in crate::pathfinder::methods) async fn stored_block(context: &RpcContext) -> Block {
+        let pending = context
+            .pending_data
+            .as_ref()
+            .unwrap()
+            .block()
+            .await
+            .unwrap();
+
+        Block {
+            block_hash: StarknetBlockHash(felt_bytes!(b"stored block")),
+            block_number: StarknetBlockNumber::new_or_panic(3),
+            gas_price: Some(pending.gas_price),
+            parent_block_hash: pending.parent_hash,
+            sequencer_address: Some(pending.sequencer_address),
+            state_commitment: StateCommitment(Felt::ZERO),
+            status: starknet_gateway_types::reply::Status::AcceptedOnL2,
+            timestamp: pending.timestamp,
+            transaction_receipts: pending.transaction_receipts.clone(),
+            transactions: pending.transactions.clone(),
+            starknet_version: None,
+        }
+    }
+
+    #[test]
+    fn filter() {
+        let address = ContractAddress::new_or_panic(felt!("0x1"));
+        let keys = [EventKey(felt!("0x2")), EventKey(felt!("0x3"))];
+
+        let all = SubscribeEventsInput::default();
+        assert!(all.matches(address, &keys));
+
+        let by_address = SubscribeEventsInput {
+            address: Some(ContractAddress::new_or_panic(felt!("0x4"))),
+            keys: vec![],
+        };
+        assert!(!by_address.matches(address, &keys));
+
+        let by_second_key = SubscribeEventsInput {
+            address: Some(address),
+            keys: vec![vec![], vec![EventKey(felt!("0x5")), EventKey(felt!("0x3"))]],
+        };
+        assert!(by_second_key.matches(address, &keys));
+
+        let by_first_key = SubscribeEventsInput {
+            address: None,
+            keys: vec![vec![EventKey(felt!("0x3"))]],
+        };
+        assert!(!by_first_key.matches(address, &keys));
+
+        // The event has no third key to match.
+        let by_third_key = SubscribeEventsInput {
+            address: None,
+            keys: vec![vec![], vec![], vec![EventKey(felt!("0x3"))]],
+        };
+        assert!(!by_third_key.matches(address, &keys));
+        let any_third_key = SubscribeEventsInput {
+            address: None,
+            keys: vec![vec![], vec![], vec![]],
+        };
+        assert!(any_third_key.matches(address, &keys));
+    }
+
+    #[tokio::test]
+    async fn too_many_keys() {
+        let context = RpcContext::for_tests();
+        let limit = StarknetEventsTable::KEY_FILTER_LIMIT;
+        let input = SubscribeEventsInput {
+            address: None,
+            keys: vec![vec![]; limit + 1],
+        };
+
+        let error = match subscribe_events(context, Some(input)) {
+            Ok(_) => panic!("Filter with too many keys was accepted"),
+            Err(error) => error,
+        };
+        assert!(matches!(
+            error,
+            SubscribeEventsError::TooManyKeysInFilter { requested, .. } if requested == limit + 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn streams_events_and_reorgs() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = stored_block(&context).await;
+        let expected = events(&block, &Default::default());
+        assert!(!expected.is_empty());
+
+        let mut stream = subscribe_events(context.clone(), None).unwrap();
+        let updates = &context.sync_status.chain_updates;
+        updates.send(ChainUpdate::Block(Arc::new(block))).unwrap();
+        updates
+            .send(ChainUpdate::Reorg(StarknetBlockNumber::new_or_panic(3)))
+            .unwrap();
+
+        for event in expected {
+            assert_eq!(stream.next().await.unwrap(), event);
+        }
+        assert_eq!(
+            stream.next().await.unwrap(),
+            EventNotification::Reorg {
+                first_block_number: StarknetBlockNumber::new_or_panic(3)
+            }
+        );
+    }
+
+    #[test]
+    fn notification_format() {
+        let notification = EventNotification::Event(EmittedEvent {
+            data: vec![EventData(felt!("0x1"))],
+            keys: vec![EventKey(felt!("0x2"))],
+            from_address: ContractAddress::new_or_panic(felt!("0x3")),
+            block_hash: StarknetBlockHash(felt!("0x4")),
+            block_number: StarknetBlockNumber::new_or_panic(5),
+            transaction_hash: StarknetTransactionHash(felt!("0x6")),
+        });
+        assert_eq!(
+            serde_json::to_value(notification).unwrap(),
+            serde_json::json!({
+                "type": "event",
+                "data": ["0x1"],
+                "keys": ["0x2"],
+                "from_address": "0x3",
+                "block_hash": "0x4",
+                "block_number": 5,
+                "transaction_hash": "0x6",
+            })
+        );
+
+        let notification = EventNotification::Reorg {
+            first_block_number: StarknetBlockNumber::new_or_panic(7),
+        };
+        assert_eq!(
+            serde_json::to_value(notification).unwrap(),
+            serde_json::json!({"type": "reorg", "first_block_number": 7})
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use futures::Stream;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::RpcContext;
+use crate::milestones::SyncMilestone;
+
+/// Takes no parameters, but accepts empty ones.
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeSyncMilestonesInput {}
+
+crate::error::generate_rpc_error_subset!(SubscribeSyncMilestonesError);
+
+/// Streams each milestone sync crosses, such as having caught up with the network or having
+/// fallen behind it.
+///
+/// The subscription is closed if the subscriber falls too far behind, rather than silently
+/// skipping milestones.
+pub fn subscribe_sync_milestones(
+    context: RpcContext,
+    _input: Option<SubscribeSyncMilestonesInput>,
+) -> Result<impl Stream<Item = SyncMilestone> + Unpin + Send, SubscribeSyncMilestonesError> {
+    let milestones = context.sync_status.milestones.subscribe();
+
+    let stream = futures::stream::unfold(milestones, |mut milestones| async move {
+        match milestones.recv().await {
+            Ok(milestone) => Some((milestone, milestones)),
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::debug!(%skipped, "Closing lagging sync milestone subscription");
+                None
+            }
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    Ok(Box::pin(stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::milestones::SyncMilestones;
+    use futures::StreamExt;
+    use pathfinder_common::StarknetBlockNumber;
+
+    #[tokio::test]
+    async fn streams_milestones() {
+        let context = RpcContext::for_tests();
+        let mut stream = subscribe_sync_milestones(context.clone(), None).unwrap();
+
+        let milestones = &context.sync_status.milestones;
+        milestones.update(
+            StarknetBlockNumber::new_or_panic(0),
+            StarknetBlockNumber::new_or_panic(100),
+        );
+        milestones.update(
+            StarknetBlockNumber::new_or_panic(100),
+            StarknetBlockNumber::new_or_panic(100),
+        );
+
+        assert_eq!(
+            stream.next().await.unwrap(),
+            SyncMilestone::FellBehind {
+                current_block_number: StarknetBlockNumber::new_or_panic(0),
+                highest_block_number: StarknetBlockNumber::new_or_panic(100),
+            }
+        );
+        assert_eq!(
+            stream.next().await.unwrap(),
+            SyncMilestone::CaughtUp {
+                block_number: StarknetBlockNumber::new_or_panic(100),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn closes_when_lagging() {
+        let context = RpcContext::for_tests();
+        let milestone = SyncMilestone::CaughtUp {
+            block_number: StarknetBlockNumber::GENESIS,
+        };
+
+        let mut stream = subscribe_sync_milestones(context.clone(), None).unwrap();
+        for _ in 0..=SyncMilestones::CAPACITY {
+            context.sync_status.milestones.send(milestone.clone());
+        }
+
+        assert!(stream.next().await.is_none());
+    }
+}
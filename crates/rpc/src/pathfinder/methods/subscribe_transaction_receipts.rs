@@ -0,0 +1,202 @@
+use futures::{Stream, StreamExt};
+use pathfinder_common::ContractAddress;
+use serde::Deserialize;
+use starknet_gateway_types::reply::transaction::{Receipt, Transaction};
+use starknet_gateway_types::reply::Block;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::context::RpcContext;
+use crate::v02::method::get_transaction_receipt::types::TransactionReceipt;
+use crate::ChainUpdate;
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeTransactionReceiptsInput {
+    /// Only include transactions sent by this account or, for deploy and L1 handler
+    /// transactions, targeting this contract.
+    #[serde(default)]
+    sender_address: Option<ContractAddress>,
+    /// Only include receipts with an event emitted by this contract.
+    #[serde(default)]
+    contract_address: Option<ContractAddress>,
+}
+
+impl SubscribeTransactionReceiptsInput {
+    fn matches(&self, transaction: &Transaction, receipt: &Receipt) -> bool {
+        let sender_matches = self
+            .sender_address
+            .map(|sender| transaction.contract_address() == sender)
+            .unwrap_or(true);
+        let contract_matches = self
+            .contract_address
+            .map(|contract| {
+                receipt
+                    .events
+                    .iter()
+                    .any(|event| event.from_address == contract)
+            })
+            .unwrap_or(true);
+
+        sender_matches && contract_matches
+    }
+}
+
+crate::error::generate_rpc_error_subset!(SubscribeTransactionReceiptsError: ReadOnly);
+
+/// Streams the receipts of new transactions matching the filter, as each block is stored by sync.
+///
+/// The subscription is closed if the subscriber falls too far behind, rather than silently
+/// skipping receipts. Read-only replicas do not sync, and reject the subscription.
+pub fn subscribe_transaction_receipts(
+    context: RpcContext,
+    input: Option<SubscribeTransactionReceiptsInput>,
+) -> Result<
+    impl Stream<Item = TransactionReceipt> + Unpin + Send + 'static,
+    SubscribeTransactionReceiptsError,
+> {
+    if context.read_only {
+        return Err(SubscribeTransactionReceiptsError::ReadOnly);
+    }
+    let filter = input.unwrap_or_default();
+    let updates = context.sync_status.chain_updates.subscribe();
+
+    let stream = futures::stream::unfold(updates, |mut updates| async move {
+        loop {
+            match updates.recv().await {
+                Ok(ChainUpdate::Block(block)) => return Some((block, updates)),
+                // After a reorg, the new chain's receipts are sent as its blocks are stored.
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(%skipped, "Closing lagging transaction receipt subscription");
+                    return None;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .flat_map(move |block| futures::stream::iter(receipts(&block, &filter)));
+
+    Ok(Box::pin(stream))
+}
+
+fn receipts(block: &Block, filter: &SubscribeTransactionReceiptsInput) -> Vec<TransactionReceipt> {
+    block
+        .transactions
+        .iter()
+        .zip(block.transaction_receipts.iter())
+        .filter(|(transaction, receipt)| filter.matches(transaction, receipt))
+        .map(|(transaction, receipt)| {
+            TransactionReceipt::with_block_data(
+                receipt.clone(),
+                block.status.into(),
+                block.block_hash,
+                block.block_number,
+                transaction.clone(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{
+        felt, felt_bytes, StarknetBlockHash, StarknetBlockNumber, StateCommitment,
+    };
+    use stark_hash::Felt;
+    use std::sync::Arc;
+
+    /// Turns the pending test block into a block applied by sync.
+    async fn applied_block(context: &RpcContext) -> Block {
+        let pending = context
+            .pending_data
+            .as_ref()
+            .unwrap()
+            .block()
+            .await
+            .unwrap();
+
+        Block {
+            block_hash: StarknetBlockHash(felt_bytes!(b"applied block")),
+            block_number: StarknetBlockNumber::new_or_panic(3),
+            gas_price: Some(pending.gas_price),
+            parent_block_hash: pending.parent_hash,
+            sequencer_address: Some(pending.sequencer_address),
+            state_commitment: StateCommitment(Felt::ZERO),
+            status: starknet_gateway_types::reply::Status::AcceptedOnL2,
+            timestamp: pending.timestamp,
+            transaction_receipts: pending.transaction_receipts.clone(),
+            transactions: pending.transactions.clone(),
+            starknet_version: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn filters() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = applied_block(&context).await;
+        let all = receipts(&block, &Default::default());
+        assert_eq!(all.len(), 2);
+
+        let by_sender = SubscribeTransactionReceiptsInput {
+            sender_address: Some(ContractAddress::new_or_panic(felt_bytes!(
+                b"pending contract addr 0"
+            ))),
+            contract_address: None,
+        };
+        assert_eq!(receipts(&block, &by_sender), vec![all[0].clone()]);
+
+        // The deploy transaction targets the deployed contract.
+        let by_deployed = SubscribeTransactionReceiptsInput {
+            sender_address: Some(ContractAddress::new_or_panic(felt!("0x1122355"))),
+            contract_address: None,
+        };
+        assert_eq!(receipts(&block, &by_deployed), vec![all[1].clone()]);
+
+        let by_event = SubscribeTransactionReceiptsInput {
+            sender_address: None,
+            contract_address: Some(ContractAddress::new_or_panic(felt!("0xabcaaaaaaa"))),
+        };
+        assert_eq!(receipts(&block, &by_event), vec![all[0].clone()]);
+
+        let both = SubscribeTransactionReceiptsInput {
+            sender_address: by_deployed.sender_address,
+            contract_address: by_event.contract_address,
+        };
+        assert!(receipts(&block, &both).is_empty());
+    }
+
+    #[tokio::test]
+    async fn streams_applied_blocks() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = applied_block(&context).await;
+        let expected = receipts(&block, &Default::default());
+
+        let mut stream = subscribe_transaction_receipts(context.clone(), None).unwrap();
+        context
+            .sync_status
+            .chain_updates
+            .send(ChainUpdate::Block(Arc::new(block)))
+            .unwrap();
+
+        assert_eq!(stream.next().await.unwrap(), expected[0]);
+        assert_eq!(stream.next().await.unwrap(), expected[1]);
+    }
+
+    #[tokio::test]
+    async fn closes_when_lagging() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = ChainUpdate::Block(Arc::new(applied_block(&context).await));
+
+        let mut stream = subscribe_transaction_receipts(context.clone(), None).unwrap();
+        for _ in 0..=crate::SyncState::CHAIN_UPDATES_CAPACITY {
+            context
+                .sync_status
+                .chain_updates
+                .send(block.clone())
+                .unwrap();
+        }
+
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,401 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::Stream;
+use pathfinder_common::{StarknetBlockNumber, StarknetTransactionHash};
+use serde::{Deserialize, Serialize};
+use starknet_gateway_types::reply::transaction::Transaction;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::Instant;
+
+use super::get_transaction_status::{check_database, is_pending_tx, GatewayStatus};
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+use crate::ChainUpdate;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeTransactionStatusInput {
+    transaction_hash: StarknetTransactionHash,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TransactionStatusNotification {
+    #[serde_as(as = "RpcFelt")]
+    transaction_hash: StarknetTransactionHash,
+    status: GatewayStatus,
+}
+
+crate::error::generate_rpc_error_subset!(SubscribeTransactionStatusError: ReadOnly);
+
+/// How often the gateway is asked for the status of a transaction which is neither pending nor
+/// stored, as only the gateway knows whether it was received or rejected.
+const GATEWAY_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long after subscribing the gateway may report a transaction as not received before the
+/// subscription is closed.
+const NOT_RECEIVED_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Notifies of the transaction's current status, and then of each change to it: when the
+/// transaction appears in the pending block, when its block is stored by sync, and when that
+/// block is accepted on L1. The subscription ends with a final status, which is accepted on L1,
+/// rejected or aborted.
+///
+/// While the transaction is neither pending nor stored, its status is polled from the gateway,
+/// which notifies whether it was received or rejected. The subscription is closed if the gateway
+/// has still not received the transaction [NOT_RECEIVED_TIMEOUT] after subscribing.
+///
+/// If a reorg removes the transaction's block, its status is notified again as the transaction
+/// reappears. The subscription is closed if the subscriber falls too far behind, rather than
+/// silently skipping a status. Read-only replicas do not sync, and reject the subscription.
+pub fn subscribe_transaction_status(
+    context: RpcContext,
+    input: SubscribeTransactionStatusInput,
+) -> Result<
+    impl Stream<Item = TransactionStatusNotification> + Unpin + Send + 'static,
+    SubscribeTransactionStatusError,
+> {
+    if context.read_only {
+        return Err(SubscribeTransactionStatusError::ReadOnly);
+    }
+    // Subscribe before looking up the current status, so that no change is missed in between.
+    let updates = context.sync_status.chain_updates.subscribe();
+    let subscription = Subscription {
+        context,
+        looked_up: false,
+        not_received_deadline: Instant::now() + NOT_RECEIVED_TIMEOUT,
+        updates,
+        tracker: Tracker::new(input.transaction_hash),
+    };
+
+    let stream = futures::stream::unfold(Some(subscription), |subscription| async move {
+        let mut subscription = subscription?;
+        let status = subscription.next().await?;
+
+        let notification = TransactionStatusNotification {
+            transaction_hash: subscription.tracker.transaction_hash,
+            status,
+        };
+        let subscription = (!is_final(status)).then_some(subscription);
+
+        Some((notification, subscription))
+    });
+
+    Ok(Box::pin(stream))
+}
+
+/// Whether no status follows `status`.
+fn is_final(status: GatewayStatus) -> bool {
+    matches!(
+        status,
+        GatewayStatus::AcceptedOnL1 | GatewayStatus::Rejected | GatewayStatus::Aborted
+    )
+}
+
+struct Subscription {
+    context: RpcContext,
+    /// Whether the current status has been looked up.
+    looked_up: bool,
+    not_received_deadline: Instant,
+    updates: Receiver<ChainUpdate>,
+    tracker: Tracker,
+}
+
+impl Subscription {
+    /// Waits for the next change of status, or returns [None] if the subscription must close.
+    async fn next(&mut self) -> Option<GatewayStatus> {
+        if !self.looked_up {
+            self.looked_up = true;
+            let transaction_hash = self.tracker.transaction_hash;
+            match current_status(self.context.clone(), transaction_hash).await {
+                Ok(Some((block_number, status))) => {
+                    self.tracker.block_number = block_number;
+                    if let Some(status) = self.tracker.set(status) {
+                        return Some(status);
+                    }
+                }
+                Ok(None) => match self.poll_gateway().await {
+                    Ok(Some(status)) => return Some(status),
+                    Ok(None) => {}
+                    Err(()) => return None,
+                },
+                Err(error) => {
+                    tracing::warn!(%error, "Closing transaction status subscription");
+                    return None;
+                }
+            }
+        }
+
+        loop {
+            let update = if self.tracker.is_local() {
+                self.updates.recv().await
+            } else {
+                match tokio::time::timeout(GATEWAY_POLL_INTERVAL, self.updates.recv()).await {
+                    Ok(update) => update,
+                    Err(_) => match self.poll_gateway().await {
+                        Ok(Some(status)) => return Some(status),
+                        Ok(None) => continue,
+                        Err(()) => return None,
+                    },
+                }
+            };
+
+            match update {
+                Ok(update) => {
+                    if let Some(status) = self.tracker.update(&update) {
+                        return Some(status);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::debug!(%skipped, "Closing lagging transaction status subscription");
+                    return None;
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Asks the gateway for the status of a transaction which is neither pending nor stored.
+    ///
+    /// Returns the status if it changed, or `Err` if the subscription must close because the
+    /// transaction has not been received in time.
+    async fn poll_gateway(&mut self) -> Result<Option<GatewayStatus>, ()> {
+        use starknet_gateway_client::ClientApi;
+
+        let transaction_hash = self.tracker.transaction_hash;
+        let status = match self.context.sequencer.transaction(transaction_hash).await {
+            Ok(transaction) => GatewayStatus::from(transaction.status),
+            Err(error) => {
+                tracing::debug!(%error, "Polling transaction status from gateway failed");
+                return Ok(None);
+            }
+        };
+
+        match status {
+            GatewayStatus::NotReceived if Instant::now() >= self.not_received_deadline => {
+                tracing::debug!(
+                    %transaction_hash,
+                    "Closing transaction status subscription of unknown transaction"
+                );
+                Err(())
+            }
+            GatewayStatus::NotReceived => Ok(None),
+            // The other statuses are notified once sync sees the transaction.
+            GatewayStatus::Received | GatewayStatus::Rejected | GatewayStatus::Aborted => {
+                Ok(self.tracker.set(status))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The status of a transaction which is stored or pending, along with the number of its block if
+/// it is stored.
+async fn current_status(
+    context: RpcContext,
+    transaction_hash: StarknetTransactionHash,
+) -> anyhow::Result<Option<(Option<StarknetBlockNumber>, GatewayStatus)>> {
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+    let stored = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        check_database(&storage, &transaction_hash)
+    })
+    .await
+    .context("Database read panic or shutting down")?
+    .context("Checking database for transaction")?;
+    if let Some((block_number, status)) = stored {
+        return Ok(Some((Some(block_number), status)));
+    }
+
+    if let Some(pending) = &context.pending_data {
+        if is_pending_tx(pending, &transaction_hash).await {
+            return Ok(Some((None, GatewayStatus::Pending)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Follows the status of a transaction through the [ChainUpdate]s.
+struct Tracker {
+    transaction_hash: StarknetTransactionHash,
+    /// The stored block containing the transaction, if any.
+    block_number: Option<StarknetBlockNumber>,
+    /// The last status notified.
+    status: Option<GatewayStatus>,
+}
+
+impl Tracker {
+    fn new(transaction_hash: StarknetTransactionHash) -> Self {
+        Self {
+            transaction_hash,
+            block_number: None,
+            status: None,
+        }
+    }
+
+    /// Returns the new status, if the update changes it.
+    fn update(&mut self, update: &ChainUpdate) -> Option<GatewayStatus> {
+        match update {
+            ChainUpdate::Pending(block)
+                if self.block_number.is_none() && self.contained_in(&block.transactions) =>
+            {
+                self.set(GatewayStatus::Pending)
+            }
+            ChainUpdate::Block(block) if self.contained_in(&block.transactions) => {
+                self.block_number = Some(block.block_number);
+                self.set(GatewayStatus::AcceptedOnL2)
+            }
+            ChainUpdate::AcceptedOnL1(head) if self.block_matches(|number| number <= *head) => {
+                self.set(GatewayStatus::AcceptedOnL1)
+            }
+            ChainUpdate::Reorg(first_removed)
+                if self.block_matches(|number| number >= *first_removed) =>
+            {
+                self.block_number = None;
+                self.status = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the transaction is pending or stored, so that its status follows from the
+    /// [ChainUpdate]s alone.
+    fn is_local(&self) -> bool {
+        self.block_number.is_some() || self.status == Some(GatewayStatus::Pending)
+    }
+
+    fn set(&mut self, status: GatewayStatus) -> Option<GatewayStatus> {
+        if self.status == Some(status) {
+            return None;
+        }
+        self.status = Some(status);
+        Some(status)
+    }
+
+    /// Whether the transaction's block is stored and satisfies `f`.
+    fn block_matches(&self, f: impl FnOnce(StarknetBlockNumber) -> bool) -> bool {
+        self.block_number.map(f).unwrap_or(false)
+    }
+
+    fn contained_in(&self, transactions: &[Transaction]) -> bool {
+        transactions
+            .iter()
+            .any(|transaction| transaction.hash() == self.transaction_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::subscribe_events::tests::stored_block;
+    use super::*;
+    use futures::StreamExt;
+    use pathfinder_common::{felt, felt_bytes};
+    use std::sync::Arc;
+
+    fn input(transaction_hash: StarknetTransactionHash) -> SubscribeTransactionStatusInput {
+        SubscribeTransactionStatusInput { transaction_hash }
+    }
+
+    #[tokio::test]
+    async fn follows_pending_transaction() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = stored_block(&context).await;
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"pending tx hash 0"));
+
+        let mut stream =
+            subscribe_transaction_status(context.clone(), input(transaction_hash)).unwrap();
+        assert_eq!(stream.next().await.unwrap().status, GatewayStatus::Pending);
+
+        let updates = &context.sync_status.chain_updates;
+        updates.send(ChainUpdate::Block(Arc::new(block))).unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().status,
+            GatewayStatus::AcceptedOnL2
+        );
+
+        // Only blocks up to 2 are accepted on L1, which excludes the transaction's block.
+        updates
+            .send(ChainUpdate::AcceptedOnL1(
+                StarknetBlockNumber::new_or_panic(2),
+            ))
+            .unwrap();
+        updates
+            .send(ChainUpdate::AcceptedOnL1(
+                StarknetBlockNumber::new_or_panic(3),
+            ))
+            .unwrap();
+        assert_eq!(
+            stream.next().await.unwrap(),
+            TransactionStatusNotification {
+                transaction_hash,
+                status: GatewayStatus::AcceptedOnL1,
+            }
+        );
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn starts_from_stored_status() {
+        let context = RpcContext::for_tests();
+
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"txn 0"));
+
+        let mut stream =
+            subscribe_transaction_status(context.clone(), input(transaction_hash)).unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().status,
+            GatewayStatus::AcceptedOnL2
+        );
+
+        context
+            .sync_status
+            .chain_updates
+            .send(ChainUpdate::AcceptedOnL1(StarknetBlockNumber::GENESIS))
+            .unwrap();
+        assert_eq!(
+            stream.next().await.unwrap().status,
+            GatewayStatus::AcceptedOnL1
+        );
+    }
+
+    #[tokio::test]
+    async fn reorg_resets_status() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let block = Arc::new(stored_block(&context).await);
+        let mut tracker = Tracker::new(StarknetTransactionHash(felt_bytes!(b"pending tx hash 0")));
+
+        assert_eq!(
+            tracker.update(&ChainUpdate::Block(block.clone())),
+            Some(GatewayStatus::AcceptedOnL2)
+        );
+        // A reorg of later blocks does not affect the transaction.
+        let later = ChainUpdate::Reorg(StarknetBlockNumber::new_or_panic(4));
+        assert_eq!(tracker.update(&later), None);
+        assert_eq!(tracker.update(&ChainUpdate::Block(block.clone())), None);
+
+        let removed = ChainUpdate::Reorg(StarknetBlockNumber::new_or_panic(3));
+        assert_eq!(tracker.update(&removed), None);
+        assert_eq!(
+            tracker.update(&ChainUpdate::Block(block)),
+            Some(GatewayStatus::AcceptedOnL2)
+        );
+    }
+
+    #[tokio::test]
+    async fn rejected_transaction_ends_subscription() {
+        let context = RpcContext::for_tests();
+        // Transaction hash known to be rejected by the testnet gateway.
+        let transaction_hash = StarknetTransactionHash(felt!(
+            "0x07c64b747bdb0831e7045925625bfa6309c422fded9527bacca91199a1c8d212"
+        ));
+
+        let mut stream = subscribe_transaction_status(context, input(transaction_hash)).unwrap();
+        assert_eq!(stream.next().await.unwrap().status, GatewayStatus::Rejected);
+        assert!(stream.next().await.is_none());
+    }
+}
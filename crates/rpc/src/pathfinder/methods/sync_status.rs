@@ -0,0 +1,56 @@
+use std::sync::atomic::Ordering;
+
+use serde::Serialize;
+
+use crate::context::RpcContext;
+
+crate::error::generate_rpc_error_subset!(SyncStatusError);
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct SyncStatusOutput {
+    /// True if pathfinder is only serving stored data because its upstream
+    /// endpoints were unreachable at startup.
+    degraded: bool,
+    /// True while the feeder gateway is down for maintenance, during which sync is paused.
+    gateway_unavailable: bool,
+}
+
+/// Returns pathfinder specific sync flags which are not covered by `starknet_syncing`.
+pub async fn sync_status(context: RpcContext) -> Result<SyncStatusOutput, SyncStatusError> {
+    let degraded = context.sync_status.degraded.load(Ordering::Relaxed);
+    let gateway_unavailable = context.sequencer.availability().is_unavailable();
+
+    Ok(SyncStatusOutput {
+        degraded,
+        gateway_unavailable,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn degraded() {
+        let context = RpcContext::for_tests();
+
+        let output = sync_status(context.clone()).await.unwrap();
+        assert_eq!(
+            output,
+            SyncStatusOutput {
+                degraded: false,
+                gateway_unavailable: false,
+            }
+        );
+
+        context.sync_status.degraded.store(true, Ordering::Relaxed);
+        let output = sync_status(context).await.unwrap();
+        assert_eq!(
+            output,
+            SyncStatusOutput {
+                degraded: true,
+                gateway_unavailable: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use super::get_class_proof::ClassData;
+use super::get_proof::{ContractData, Proof};
+use crate::context::RpcContext;
+use pathfinder_common::{
+    ClassCommitment, ClassHash, ContractAddress, SierraHash, StateCommitment, StorageAddress,
+    StorageCommitment, StorageValue,
+};
+use pathfinder_merkle_tree::contract_state::calculate_contract_state_hash;
+use pathfinder_merkle_tree::merkle_tree::Membership;
+use pathfinder_merkle_tree::proof::{verify_class_proof, verify_contract_proof};
+use pathfinder_merkle_tree::state_tree::ContractsStateTree;
+use stark_hash::Felt;
+
+#[derive(Deserialize, Debug)]
+pub struct VerifyProofInput {
+    /// The trusted state commitment to verify the proof against.
+    pub state_commitment: StateCommitment,
+    pub proof: ProofToVerify,
+}
+
+/// A proof as returned by `pathfinder_getProof` or `pathfinder_getClassProof`, along
+/// with the data that was queried.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProofToVerify {
+    Storage {
+        contract_address: ContractAddress,
+        /// The expected storage values, in the same order as the
+        /// [storage proofs](ContractData::storage_proofs).
+        #[serde(default)]
+        storage_entries: Vec<StorageEntry>,
+        class_commitment: Option<ClassCommitment>,
+        contract_proof: Proof,
+        contract_data: Option<ContractData>,
+    },
+    Class {
+        class_hash: ClassHash,
+        storage_commitment: StorageCommitment,
+        class_proof: Proof,
+        class_data: Option<ClassData>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StorageEntry {
+    pub key: StorageAddress,
+    pub value: StorageValue,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Member,
+    NonMember,
+    Invalid,
+}
+
+impl From<Option<Membership>> for Verdict {
+    fn from(membership: Option<Membership>) -> Self {
+        match membership {
+            Some(Membership::Member) => Self::Member,
+            Some(Membership::NonMember) => Self::NonMember,
+            None => Self::Invalid,
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct VerifyProofOutput {
+    /// Verdict for the queried contract or class.
+    verdict: Verdict,
+    /// Verdicts for each of the queried storage entries. Only present for
+    /// storage proofs of contracts which are members of the state.
+    storage_verdicts: Option<Vec<Verdict>>,
+}
+
+crate::error::generate_rpc_error_subset!(VerifyProofError);
+
+/// Verifies a proof returned by `pathfinder_getProof` or `pathfinder_getClassProof` against
+/// a trusted state commitment.
+pub async fn verify_proof(
+    _context: RpcContext,
+    input: VerifyProofInput,
+) -> Result<VerifyProofOutput, VerifyProofError> {
+    let output = match input.proof {
+        ProofToVerify::Storage {
+            contract_address,
+            storage_entries,
+            class_commitment,
+            contract_proof,
+            contract_data,
+        } => verify_storage(
+            input.state_commitment,
+            contract_address,
+            storage_entries,
+            class_commitment.unwrap_or(ClassCommitment::ZERO),
+            contract_proof,
+            contract_data,
+        ),
+        ProofToVerify::Class {
+            class_hash,
+            storage_commitment,
+            class_proof,
+            class_data,
+        } => {
+            let verdict = verify_class_proof(
+                input.state_commitment,
+                storage_commitment,
+                SierraHash(class_hash.0),
+                class_data.map(|data| data.compiled_class_hash),
+                &class_proof.0,
+            );
+
+            VerifyProofOutput {
+                verdict: verdict.into(),
+                storage_verdicts: None,
+            }
+        }
+    };
+
+    Ok(output)
+}
+
+fn verify_storage(
+    state_commitment: StateCommitment,
+    contract_address: ContractAddress,
+    storage_entries: Vec<StorageEntry>,
+    class_commitment: ClassCommitment,
+    contract_proof: Proof,
+    contract_data: Option<ContractData>,
+) -> VerifyProofOutput {
+    const INVALID: VerifyProofOutput = VerifyProofOutput {
+        verdict: Verdict::Invalid,
+        storage_verdicts: None,
+    };
+
+    let contract_state_hash = match &contract_data {
+        // Only version 0 of the contract state hash exists.
+        Some(data) if data.contract_state_hash_version != Felt::ZERO => return INVALID,
+        Some(data) => Some(calculate_contract_state_hash(
+            data.class_hash,
+            data.root,
+            data.nonce,
+        )),
+        None => None,
+    };
+
+    let verdict: Verdict = verify_contract_proof(
+        state_commitment,
+        class_commitment,
+        contract_address,
+        contract_state_hash,
+        &contract_proof.0,
+    )
+    .into();
+
+    let contract_data = match (verdict, contract_data) {
+        (Verdict::Member, Some(data)) => data,
+        (verdict, _) => {
+            return VerifyProofOutput {
+                verdict,
+                storage_verdicts: None,
+            }
+        }
+    };
+
+    if contract_data.storage_proofs.len() != storage_entries.len() {
+        return INVALID;
+    }
+
+    let storage_verdicts = storage_entries
+        .iter()
+        .zip(contract_data.storage_proofs.iter())
+        .map(|(entry, proof)| {
+            match ContractsStateTree::verify_proof(
+                contract_data.root,
+                entry.key,
+                entry.value,
+                &proof.0,
+            ) {
+                // Storage slots which are not part of the tree have a value of zero.
+                Some(Membership::NonMember) if entry.value != StorageValue(Felt::ZERO) => {
+                    Verdict::Invalid
+                }
+                membership => membership.into(),
+            }
+        })
+        .collect();
+
+    VerifyProofOutput {
+        verdict: Verdict::Member,
+        storage_verdicts: Some(storage_verdicts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::felt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn invalid_proof() {
+        let context = RpcContext::for_tests();
+        let input = serde_json::from_value::<VerifyProofInput>(serde_json::json!({
+            "state_commitment": "0x1234",
+            "proof": {
+                "type": "storage",
+                "contract_address": "0xdeadbeef",
+                "contract_proof": [
+                    {"binary": {"left": "0x1", "right": "0x2"}},
+                ],
+            }
+        }))
+        .unwrap();
+
+        let output = verify_proof(context, input).await.unwrap();
+        assert_eq!(
+            output,
+            VerifyProofOutput {
+                verdict: Verdict::Invalid,
+                storage_verdicts: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_class_tree() {
+        let context = RpcContext::for_tests();
+        let input = VerifyProofInput {
+            state_commitment: StateCommitment(felt!("0x1234")),
+            proof: ProofToVerify::Class {
+                class_hash: ClassHash(felt!("0x1")),
+                storage_commitment: StorageCommitment(felt!("0x1234")),
+                class_proof: serde_json::from_value(serde_json::json!([])).unwrap(),
+                class_data: None,
+            },
+        };
+
+        let output = verify_proof(context, input).await.unwrap();
+        assert_eq!(output.verdict, Verdict::NonMember);
+    }
+}
@@ -0,0 +1,318 @@
+//! Serves blocks which sync has not stored yet from the gateway, to clients which request blocks
+//! in sequence ahead of sync, such as an indexer walking the chain while the node catches up.
+//!
+//! Once a client has requested a few consecutive block numbers, a request for a block which is
+//! missing from storage is answered from the gateway, and the following blocks are prefetched
+//! into a short-lived cache. A request waits for its block only until a deadline, after which it
+//! fails as if there were no prefetching, while the download continues for later requests.
+//!
+//! Requests carry no client identity, so each run of consecutive block numbers is tracked on its
+//! own instead. Clients walking different ranges of the chain are therefore recognized
+//! separately, and do not interrupt each other's runs.
+//!
+//! Blocks are served to `getBlockWithTxHashes`, `getBlockWithTxs`, `getBlockTransactionCount`
+//! and `getTransactionByBlockIdAndIndex`, and state updates to `getStateUpdate`. Blocks and state
+//! updates are prefetched separately, once they are requested.
+//!
+//! Prefetched blocks are not validated, and are only served until they expire, so that a reorg
+//! of the gateway is reflected soon.
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use pathfinder_common::{BlockId, StarknetBlockNumber};
+use starknet_gateway_client::{Client, ClientApi};
+use starknet_gateway_types::error::SequencerError;
+use starknet_gateway_types::reply::{
+    Block, MaybePendingBlock, MaybePendingStateUpdate, StateUpdate,
+};
+
+/// Number of consecutive block numbers which must have been requested before missing blocks are
+/// fetched from the gateway.
+const SEQUENTIAL_REQUESTS: u64 = 3;
+
+/// Number of runs of consecutive block numbers which are tracked at once. The least recently
+/// extended run is forgotten to make room for a new one.
+const MAX_RUNS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrefetchConfig {
+    /// Number of blocks fetched ahead of the requested block.
+    pub window: NonZeroU64,
+    /// How long a request waits for its block to arrive from the gateway.
+    pub deadline: Duration,
+    /// How long a fetched block is served for.
+    pub expiry: Duration,
+}
+
+impl PrefetchConfig {
+    pub fn new(window: NonZeroU64) -> Self {
+        Self {
+            window,
+            deadline: Duration::from_secs(2),
+            expiry: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Recognizes sequential block requests and prefetches the blocks which follow from the gateway.
+///
+/// Cheap to clone, with all clones sharing the same cache.
+#[derive(Clone)]
+pub struct BlockPrefetch(Arc<Inner>);
+
+struct Inner {
+    sequencer: Client,
+    config: PrefetchConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    runs: Vec<Run>,
+    /// Keyed by block number.
+    blocks: HashMap<u64, Entry<Block>>,
+    /// Keyed by block number.
+    state_updates: HashMap<u64, Entry<StateUpdate>>,
+}
+
+/// A run of consecutive requested block numbers.
+struct Run {
+    last: StarknetBlockNumber,
+    length: u64,
+    extended_at: Instant,
+}
+
+struct Entry<T> {
+    item: Shared<BoxFuture<'static, Option<Arc<T>>>>,
+    started_at: Instant,
+}
+
+impl BlockPrefetch {
+    pub fn new(sequencer: Client, config: PrefetchConfig) -> Self {
+        Self(Arc::new(Inner {
+            sequencer,
+            config,
+            state: Default::default(),
+        }))
+    }
+
+    /// Records a request for `block_id`, whether or not it is stored. Returns the block number
+    /// to [get](Self::block) from the prefetch if it turns out to be missing from storage.
+    pub fn observe(&self, block_id: BlockId) -> Option<StarknetBlockNumber> {
+        let number = match block_id {
+            BlockId::Number(number) => number,
+            _ => return None,
+        };
+
+        let mut state = self.state();
+        let now = Instant::now();
+        let run = state
+            .runs
+            .iter_mut()
+            .find(|run| number == run.last || number.get() == run.last.get() + 1);
+        match run {
+            Some(run) => {
+                if number != run.last {
+                    run.length += 1;
+                    run.last = number;
+                }
+                run.extended_at = now;
+            }
+            None => {
+                if state.runs.len() >= MAX_RUNS {
+                    let oldest = state
+                        .runs
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, run)| run.extended_at)
+                        .map(|(index, _)| index)
+                        .expect("Runs are not empty");
+                    state.runs.swap_remove(oldest);
+                }
+                state.runs.push(Run {
+                    last: number,
+                    length: 1,
+                    extended_at: now,
+                });
+            }
+        }
+
+        Some(number)
+    }
+
+    /// The block `number` from the gateway, if the requests have been sequential and the block
+    /// arrives before the deadline. Also prefetches the blocks which follow it.
+    pub async fn block(&self, number: StarknetBlockNumber) -> Option<Arc<Block>> {
+        self.get(
+            number,
+            |state| &mut state.blocks,
+            |sequencer, number| {
+                async move {
+                    match sequencer.block(number.into()).await? {
+                        MaybePendingBlock::Block(block) => Ok(Some(block)),
+                        MaybePendingBlock::Pending(_) => Ok(None),
+                    }
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    /// The state update of the block `number` from the gateway, like [block](Self::block).
+    pub async fn state_update(&self, number: StarknetBlockNumber) -> Option<Arc<StateUpdate>> {
+        self.get(
+            number,
+            |state| &mut state.state_updates,
+            |sequencer, number| {
+                async move {
+                    match sequencer.state_update(number.into()).await? {
+                        MaybePendingStateUpdate::StateUpdate(update) => Ok(Some(update)),
+                        MaybePendingStateUpdate::Pending(_) => Ok(None),
+                    }
+                }
+                .boxed()
+            },
+        )
+        .await
+    }
+
+    async fn get<T: Send + Sync + 'static>(
+        &self,
+        number: StarknetBlockNumber,
+        entries: fn(&mut State) -> &mut HashMap<u64, Entry<T>>,
+        download: fn(Client, StarknetBlockNumber) -> Download<T>,
+    ) -> Option<Arc<T>> {
+        let item = {
+            let mut state = self.state();
+            let sequential = state
+                .runs
+                .iter()
+                .any(|run| run.last == number && run.length >= SEQUENTIAL_REQUESTS);
+            if !sequential {
+                return None;
+            }
+
+            // Drop expired items, and failed downloads so that they are retried.
+            let expiry = self.0.config.expiry;
+            let entries = entries(&mut state);
+            entries.retain(|_, entry| {
+                entry.started_at.elapsed() < expiry && !matches!(entry.item.peek(), Some(None))
+            });
+
+            let last = number.get().saturating_add(self.0.config.window.get());
+            for next in (number.get()..=last).filter_map(StarknetBlockNumber::new) {
+                entries
+                    .entry(next.get())
+                    .or_insert_with(|| self.fetch(next, download));
+            }
+
+            entries[&number.get()].item.clone()
+        };
+
+        tokio::time::timeout(self.0.config.deadline, item)
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Starts downloading the item of block `number`.
+    fn fetch<T: Send + Sync + 'static>(
+        &self,
+        number: StarknetBlockNumber,
+        download: fn(Client, StarknetBlockNumber) -> Download<T>,
+    ) -> Entry<T> {
+        let download = download(self.0.sequencer.clone(), number);
+        let expiry = self.0.config.expiry;
+
+        let item = async move {
+            match tokio::time::timeout(expiry, download).await {
+                Ok(Ok(item)) => item.map(Arc::new),
+                Ok(Err(error)) => {
+                    tracing::debug!(%number, %error, "Prefetching failed");
+                    None
+                }
+                Err(_) => None,
+            }
+        }
+        .boxed()
+        .shared();
+        // Download in the background rather than once a request waits for the item.
+        tokio::spawn(item.clone());
+
+        Entry {
+            item,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Downloads an item from the gateway, which is [None] if the block is still pending.
+type Download<T> = BoxFuture<'static, Result<Option<T>, SequencerError>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use starknet_gateway_client::test_utils::{MockGateway, ScriptedChain};
+
+    #[tokio::test]
+    async fn prefetches_sequential_requests() {
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(10);
+        let gateway = MockGateway::spawn(chain);
+        let prefetch = BlockPrefetch::new(
+            gateway.client(),
+            PrefetchConfig::new(NonZeroU64::new(2).unwrap()),
+        );
+        let number = StarknetBlockNumber::new_or_panic;
+
+        // Not yet sequential.
+        prefetch.observe(BlockId::Number(number(0)));
+        prefetch.observe(BlockId::Number(number(1)));
+        assert!(prefetch.block(number(1)).await.is_none());
+        assert!(prefetch.state().blocks.is_empty());
+
+        prefetch.observe(BlockId::Number(number(2)));
+        let block = prefetch.block(number(2)).await.unwrap();
+        assert_eq!(block.block_number, number(2));
+        let mut prefetched = prefetch.state().blocks.keys().copied().collect::<Vec<_>>();
+        prefetched.sort();
+        assert_eq!(prefetched, vec![2, 3, 4]);
+        // State updates are only prefetched once they are requested.
+        assert!(prefetch.state().state_updates.is_empty());
+        let update = prefetch.state_update(number(2)).await.unwrap();
+        assert_eq!(update.block_hash, block.block_hash);
+
+        // Jumping elsewhere starts a new run.
+        prefetch.observe(BlockId::Number(number(7)));
+        assert!(prefetch.block(number(7)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn runs_are_tracked_separately() {
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(10);
+        let gateway = MockGateway::spawn(chain);
+        let prefetch = BlockPrefetch::new(
+            gateway.client(),
+            PrefetchConfig::new(NonZeroU64::new(1).unwrap()),
+        );
+        let number = StarknetBlockNumber::new_or_panic;
+
+        // Two clients walking different ranges, with interleaved requests.
+        for (a, b) in [(0, 5), (1, 6), (2, 7)] {
+            prefetch.observe(BlockId::Number(number(a)));
+            prefetch.observe(BlockId::Number(number(b)));
+        }
+
+        assert!(prefetch.block(number(2)).await.is_some());
+        assert!(prefetch.block(number(7)).await.is_some());
+    }
+}
@@ -0,0 +1,228 @@
+//! Limits on the size and shape of HTTP-RPC request bodies.
+//!
+//! Deserializing a request allocates in proportion to its nesting and the lengths of its arrays,
+//! so that even a body within the size limit could make the server allocate far more memory while
+//! it is parsed. Each body is therefore scanned first, without deserializing it, and rejected with
+//! an error naming the exceeded limit and where in the body it was exceeded.
+//!
+//! Websocket messages bypass the middleware, so they are only limited in size.
+use http::StatusCode;
+use hyper::{Body, Request, Response};
+use jsonrpsee::types::error::ErrorCode;
+use jsonrpsee::types::ErrorObject;
+use tower::BoxError;
+
+use crate::versioning::{read_request_body, response};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// The largest request body, in bytes.
+    pub max_body_size: u32,
+    /// The deepest nesting of arrays and objects, where the request itself is at depth 1.
+    pub max_depth: usize,
+    /// The most elements of any one array, including a batch of requests.
+    pub max_array_length: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_size: 10 * 1024 * 1024,
+            max_depth: 32,
+            max_array_length: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum LimitExceeded {
+    #[error("Request is nested deeper than the limit of {limit} at byte {offset}")]
+    Depth { limit: usize, offset: usize },
+    #[error("Request has an array longer than the limit of {limit} elements at byte {offset}")]
+    ArrayLength { limit: usize, offset: usize },
+}
+
+impl LimitExceeded {
+    pub(crate) fn to_response(&self) -> Response<Body> {
+        let error = ErrorObject::owned(
+            ErrorCode::InvalidRequest.code(),
+            self.to_string(),
+            None::<()>,
+        );
+        response::with_error(StatusCode::BAD_REQUEST, error)
+    }
+}
+
+impl RequestLimits {
+    /// Checks the nesting and the array lengths of the JSON `body`.
+    ///
+    /// Only as much state as the nesting is kept, so that the check itself cannot be made to
+    /// allocate. Malformed JSON passes, for deserialization to reject with the proper error.
+    fn check(&self, body: &[u8]) -> Result<(), LimitExceeded> {
+        // The number of commas of each enclosing array, or [None] for objects.
+        let mut enclosing: Vec<Option<usize>> = Vec::new();
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for (offset, byte) in body.iter().enumerate() {
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'[' | b'{' => {
+                    if enclosing.len() >= self.max_depth {
+                        return Err(LimitExceeded::Depth {
+                            limit: self.max_depth,
+                            offset,
+                        });
+                    }
+                    enclosing.push((*byte == b'[').then_some(0));
+                }
+                b']' | b'}' => {
+                    enclosing.pop();
+                }
+                b',' => {
+                    if let Some(Some(commas)) = enclosing.last_mut() {
+                        *commas += 1;
+                        if *commas >= self.max_array_length {
+                            return Err(LimitExceeded::ArrayLength {
+                                limit: self.max_array_length,
+                                offset,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Middleware which rejects requests exceeding the `limits`. It must precede the middleware which
+/// deserializes requests.
+pub(crate) async fn check(
+    request: Request<Body>,
+    limits: RequestLimits,
+) -> Result<Request<Body>, BoxError> {
+    let is_websocket = request
+        .headers()
+        .get(hyper::header::UPGRADE)
+        .and_then(|upgrade| upgrade.to_str().ok())
+        .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+        .unwrap_or_default();
+    if is_websocket {
+        return Ok(request);
+    }
+
+    let (parts, body) = request.into_parts();
+    let (body, _) = read_request_body(&parts.headers, body, limits.max_body_size).await?;
+
+    if let Err(e) = limits.check(&body) {
+        tracing::debug!(reason=%e, "Rejected RPC request exceeding limits");
+        return Err(e.into());
+    }
+
+    Ok(Request::from_parts(parts, body.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: RequestLimits = RequestLimits {
+        max_body_size: 1024,
+        max_depth: 3,
+        max_array_length: 3,
+    };
+
+    #[test]
+    fn depth() {
+        LIMITS.check(br#"{"params": [[1]]}"#).unwrap();
+        assert_eq!(
+            LIMITS.check(br#"{"params": [[{}]]}"#).unwrap_err(),
+            LimitExceeded::Depth {
+                limit: 3,
+                offset: 13
+            }
+        );
+    }
+
+    #[test]
+    fn array_length() {
+        LIMITS
+            .check(br#"[{"a": 1, "b": 2, "c": 3, "d": 4}, [], []]"#)
+            .unwrap();
+        assert_eq!(
+            LIMITS.check(br#"{"params": [1, 2, 3, 4]}"#).unwrap_err(),
+            LimitExceeded::ArrayLength {
+                limit: 3,
+                offset: 19
+            }
+        );
+    }
+
+    #[test]
+    fn strings_are_skipped() {
+        LIMITS
+            .check(br#"["[[[[,,,,", "\"[[[[,,,,", "{{{{"]"#)
+            .unwrap();
+        // The escaped backslash does not escape the closing quote.
+        assert_eq!(
+            LIMITS.check(br#"["\\", [[[]]]]"#).unwrap_err(),
+            LimitExceeded::Depth {
+                limit: 3,
+                offset: 9
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        use crate::context::RpcContext;
+        use crate::RpcServer;
+
+        let context = RpcContext::for_tests();
+        let (_server_handle, address) = RpcServer::new("127.0.0.1:0".parse().unwrap(), context)
+            .with_request_limits(LIMITS)
+            .run()
+            .await
+            .unwrap();
+
+        let client = reqwest::Client::new();
+        let call = |params: &str| {
+            let request = client
+                .post(format!("http://{address}/rpc/v0.3"))
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(format!(
+                    r#"{{"jsonrpc": "2.0", "id": 0, "method": "starknet_chainId", "params": {params}}}"#
+                ));
+            async move {
+                let response = request.send().await.unwrap();
+                let status = response.status();
+                let body = response.json::<serde_json::Value>().await.unwrap();
+                (status, body)
+            }
+        };
+
+        let (status, _) = call("[]").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, body) = call("[[[]]]").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["code"], ErrorCode::InvalidRequest.code());
+        assert_eq!(
+            body["error"]["message"],
+            "Request is nested deeper than the limit of 3 at byte 70"
+        );
+    }
+}
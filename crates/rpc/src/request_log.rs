@@ -0,0 +1,350 @@
+//! Per-request logs of the HTTP-RPC server, for finding the requests which are expensive to serve.
+//!
+//! Each logged request records its method names, a hash of its parameters, its duration, the size
+//! of its response and the error codes in its response. Requests which take longer than
+//! [RequestLogConfig::slow_threshold] are always logged as a warning, and otherwise one in every
+//! [RequestLogConfig::sample_every] requests is logged at info level.
+//!
+//! The logs use the `pathfinder_rpc::request_log` target, so they can be filtered separately.
+//!
+//! Websocket messages bypass the middleware, so subscriptions are not logged.
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroU64;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hyper::{Body, Request, Response};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use tower::{BoxError, Layer, Service};
+
+use crate::versioning::read_request_body;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestLogConfig {
+    /// Log one in every `sample_every` requests, or none if [None].
+    pub sample_every: Option<NonZeroU64>,
+    /// Requests which take longer than this are always logged.
+    pub slow_threshold: Duration,
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        Self {
+            sample_every: None,
+            slow_threshold: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RequestLogLayer {
+    config: RequestLogConfig,
+    max_request_body_size: u32,
+    requests: Arc<AtomicU64>,
+}
+
+impl RequestLogLayer {
+    pub(crate) fn new(config: RequestLogConfig, max_request_body_size: u32) -> Self {
+        Self {
+            config,
+            max_request_body_size,
+            requests: Default::default(),
+        }
+    }
+
+    /// Whether the next request is part of the sample.
+    fn sample(&self) -> bool {
+        match self.config.sample_every {
+            Some(every) => self.requests.fetch_add(1, Ordering::Relaxed) % every.get() == 0,
+            None => false,
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestLogLayer {
+    type Service = RequestLog<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLog {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RequestLog<S> {
+    inner: S,
+    layer: RequestLogLayer,
+}
+
+impl<S> Service<Request<Body>> for RequestLog<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = BoxError> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // The clone is not ready, so swap it with the service which was polled ready.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        let is_websocket = request
+            .headers()
+            .get(hyper::header::UPGRADE)
+            .and_then(|upgrade| upgrade.to_str().ok())
+            .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+            .unwrap_or_default();
+        if is_websocket {
+            return inner.call(request).boxed();
+        }
+
+        async move {
+            let (parts, body) = request.into_parts();
+            let (body, is_single) =
+                read_request_body(&parts.headers, body, layer.max_request_body_size).await?;
+            let sampled = layer.sample();
+
+            let started_at = Instant::now();
+            let response = inner
+                .call(Request::from_parts(parts, body.clone().into()))
+                .await?;
+            let duration = started_at.elapsed();
+
+            let slow = duration > layer.config.slow_threshold;
+            if !slow && !sampled {
+                return Ok(response);
+            }
+
+            let (parts, response_body) = response.into_parts();
+            let response_body = hyper::body::to_bytes(response_body).await?;
+
+            let record = Record::new(&body, is_single, &response_body, duration);
+            if slow {
+                tracing::warn!(
+                    method=%record.methods,
+                    params_hash=%record.params_hash,
+                    duration_ms=%record.duration.as_millis(),
+                    response_bytes=%record.response_bytes,
+                    error_code=%record.error_codes,
+                    "Slow RPC request"
+                );
+            } else {
+                tracing::info!(
+                    method=%record.methods,
+                    params_hash=%record.params_hash,
+                    duration_ms=%record.duration.as_millis(),
+                    response_bytes=%record.response_bytes,
+                    error_code=%record.error_codes,
+                    "RPC request"
+                );
+            }
+
+            Ok(Response::from_parts(parts, response_body.into()))
+        }
+        .boxed()
+    }
+}
+
+/// The logged summary of a single request or a batch of requests.
+///
+/// The fields of a batch list the method names and error codes of all its calls, separated by
+/// commas, and hash the parameters of all its calls together.
+#[derive(Debug, PartialEq)]
+struct Record {
+    methods: String,
+    params_hash: String,
+    duration: Duration,
+    response_bytes: usize,
+    error_codes: String,
+}
+
+impl Record {
+    fn new(request: &[u8], is_single: bool, response: &[u8], duration: Duration) -> Self {
+        let calls = if is_single {
+            serde_json::from_slice::<Call<'_>>(request).map(|call| vec![call])
+        } else {
+            serde_json::from_slice::<Vec<Call<'_>>>(request)
+        }
+        // Invalid requests are logged anyway, as they were still served.
+        .unwrap_or_default();
+
+        let methods = calls
+            .iter()
+            .map(|call| call.method.as_ref())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut hasher = DefaultHasher::new();
+        for call in &calls {
+            call.params.map(RawValue::get).hash(&mut hasher);
+        }
+        let params_hash = format!("{:016x}", hasher.finish());
+
+        let error_codes = match serde_json::from_slice::<Vec<CallResponse>>(response) {
+            Ok(batch) => batch,
+            Err(_) => serde_json::from_slice::<CallResponse>(response)
+                .map(|response| vec![response])
+                .unwrap_or_default(),
+        }
+        .into_iter()
+        .filter_map(|response| response.error)
+        .map(|error| error.code.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+        Self {
+            methods,
+            params_hash,
+            duration,
+            response_bytes: response.len(),
+            error_codes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Call<'a> {
+    #[serde(borrow)]
+    method: Cow<'a, str>,
+    #[serde(borrow, default)]
+    params: Option<&'a RawValue>,
+}
+
+#[derive(Deserialize)]
+struct CallResponse {
+    #[serde(default)]
+    error: Option<CallError>,
+}
+
+#[derive(Deserialize)]
+struct CallError {
+    code: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling() {
+        let layer = RequestLogLayer::new(
+            RequestLogConfig {
+                sample_every: NonZeroU64::new(3),
+                ..Default::default()
+            },
+            u32::MAX,
+        );
+        let sampled = (0..7).map(|_| layer.sample()).collect::<Vec<_>>();
+        assert_eq!(sampled, [true, false, false, true, false, false, true]);
+
+        let layer = RequestLogLayer::new(RequestLogConfig::default(), u32::MAX);
+        assert!(!(0..7).any(|_| layer.sample()));
+    }
+
+    mod record {
+        use super::*;
+
+        #[test]
+        fn single() {
+            let request = br#"{"jsonrpc":"2.0","id":0,"method":"v0.3_starknet_call","params":[1]}"#;
+            let response =
+                br#"{"jsonrpc":"2.0","id":0,"error":{"code":-32602,"message":"Invalid params"}}"#;
+            let record = Record::new(request, true, response, Duration::from_millis(7));
+
+            assert_eq!(record.methods, "v0.3_starknet_call");
+            assert_eq!(record.error_codes, "-32602");
+            assert_eq!(record.response_bytes, response.len());
+            assert_eq!(record.duration, Duration::from_millis(7));
+        }
+
+        #[test]
+        fn batch() {
+            let request = br#"[
+                {"jsonrpc":"2.0","id":0,"method":"v0.3_starknet_chainId"},
+                {"jsonrpc":"2.0","id":1,"method":"v0.3_starknet_getEvents","params":{}}
+            ]"#;
+            let response = br#"[
+                {"jsonrpc":"2.0","id":0,"result":"0x534e5f474f45524c49"},
+                {"jsonrpc":"2.0","id":1,"error":{"code":34,"message":"Too many keys"}}
+            ]"#;
+            let record = Record::new(request, false, response, Duration::ZERO);
+
+            assert_eq!(
+                record.methods,
+                "v0.3_starknet_chainId,v0.3_starknet_getEvents"
+            );
+            assert_eq!(record.error_codes, "34");
+        }
+
+        #[test]
+        fn params_hash() {
+            let record = |params: &str| {
+                let request = format!(
+                    r#"{{"jsonrpc":"2.0","id":0,"method":"starknet_call","params":{params}}}"#
+                );
+                Record::new(request.as_bytes(), true, b"", Duration::ZERO).params_hash
+            };
+
+            assert_eq!(record("[1, 2]"), record("[1, 2]"));
+            assert_ne!(record("[1, 2]"), record("[2, 1]"));
+        }
+
+        #[test]
+        fn invalid_request() {
+            let record = Record::new(b"not json", true, b"not json", Duration::ZERO);
+
+            assert_eq!(record.methods, "");
+            assert_eq!(record.error_codes, "");
+            assert_eq!(record.response_bytes, 8);
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_preserves_responses() {
+        use crate::context::RpcContext;
+        use crate::RpcServer;
+
+        let context = RpcContext::for_tests();
+        let (_server_handle, address) = RpcServer::new("127.0.0.1:0".parse().unwrap(), context)
+            .with_request_log(RequestLogConfig {
+                sample_every: NonZeroU64::new(1),
+                slow_threshold: Duration::ZERO,
+            })
+            .run()
+            .await
+            .unwrap();
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{address}/rpc/v0.3"))
+            .json(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "starknet_chainId",
+            }))
+            .send()
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+
+        assert_eq!(response["id"], 0);
+        assert!(response.get("result").is_some());
+    }
+}
@@ -0,0 +1,152 @@
+//! TLS configuration, so that small deployments can serve HTTPS without a reverse proxy.
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM file with the certificate chain of the server, starting with its own certificate.
+    pub cert: PathBuf,
+    /// PEM file with the private key of the server.
+    pub key: PathBuf,
+    /// PEM file with the certificate authorities whose client certificates are accepted. Clients
+    /// do not need a certificate if [None].
+    pub client_ca: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Loads the certificates and the key, failing if they are missing or invalid.
+    pub fn server_config(&self) -> anyhow::Result<ServerConfig> {
+        let certs = read_certs(&self.cert)?;
+        let key = read_key(&self.key)?;
+
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_ca {
+            Some(client_ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in read_certs(client_ca)? {
+                    roots.add(&cert).map_err(|e| {
+                        anyhow::anyhow!("Invalid client CA certificate in {client_ca:?}: {e:?}")
+                    })?;
+                }
+                builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .context("Invalid TLS certificate or key")?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(config)
+    }
+
+    pub(crate) fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        Ok(TlsAcceptor::from(Arc::new(self.server_config()?)))
+    }
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<Certificate>> {
+    let file = File::open(path).with_context(|| format!("Opening {path:?}"))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .with_context(|| format!("Reading certificates from {path:?}"))?;
+    anyhow::ensure!(!certs.is_empty(), "No certificates in {path:?}");
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> anyhow::Result<PrivateKey> {
+    let file = File::open(path).with_context(|| format!("Opening {path:?}"))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .with_context(|| format!("Reading private key from {path:?}"))?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .with_context(|| format!("No private key in {path:?}"))
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    /// A self-signed server certificate, and a client certificate signed by a certificate
+    /// authority. The files for a [TlsConfig] are written to a temporary directory.
+    pub struct Certificates {
+        pub dir: tempfile::TempDir,
+        /// The client certificate and its key, which the certificate authority signed.
+        pub client_cert: String,
+        pub client_key: String,
+    }
+
+    impl Certificates {
+        pub fn generate() -> Self {
+            let ca = {
+                let mut params = rcgen::CertificateParams::new(Vec::<String>::new());
+                params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+                rcgen::Certificate::from_params(params).unwrap()
+            };
+            let server = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+            let client = rcgen::generate_simple_self_signed(vec!["client".to_owned()]).unwrap();
+
+            let dir = tempfile::tempdir().unwrap();
+            let write = |name: &str, contents: String| {
+                std::fs::write(dir.path().join(name), contents).unwrap();
+            };
+            write("ca.pem", ca.serialize_pem().unwrap());
+            write("server.pem", server.serialize_pem().unwrap());
+            write("server.key", server.serialize_private_key_pem());
+
+            Self {
+                dir,
+                client_cert: client.serialize_pem_with_signer(&ca).unwrap(),
+                client_key: client.serialize_private_key_pem(),
+            }
+        }
+
+        pub fn config(&self, client_auth: bool) -> TlsConfig {
+            TlsConfig {
+                cert: self.dir.path().join("server.pem"),
+                key: self.dir.path().join("server.key"),
+                client_ca: client_auth.then(|| self.dir.path().join("ca.pem")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::Certificates;
+    use super::*;
+
+    #[test]
+    fn server_config() {
+        let certificates = Certificates::generate();
+        certificates.config(false).server_config().unwrap();
+        certificates.config(true).server_config().unwrap();
+    }
+
+    #[test]
+    fn missing_key() {
+        let certificates = Certificates::generate();
+        let config = TlsConfig {
+            key: certificates.dir.path().join("server.pem"),
+            ..certificates.config(false)
+        };
+
+        let error = config.server_config().unwrap_err();
+        assert!(error.to_string().starts_with("No private key"), "{error}");
+    }
+}
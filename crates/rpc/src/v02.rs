@@ -64,6 +64,14 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             "v0.2_pathfinder_getProof",
             crate::pathfinder::methods::get_proof,
         )?
+        .register_method(
+            "v0.2_pathfinder_getClassProof",
+            crate::pathfinder::methods::get_class_proof,
+        )?
+        .register_method(
+            "v0.2_pathfinder_verifyProof",
+            crate::pathfinder::methods::verify_proof,
+        )?
         .register_method(
             "v0.2_pathfinder_getTransactionStatus",
             crate::pathfinder::methods::get_transaction_status,
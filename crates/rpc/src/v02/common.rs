@@ -2,11 +2,13 @@
 use crate::v02::types::reply::BlockStatus;
 use anyhow::Context;
 use pathfinder_common::StarknetBlockNumber;
-use pathfinder_storage::RefsTable;
+use pathfinder_storage::{ReadAccess, RefsTable, StarknetBlocksBlockId};
+use starknet_gateway_types::pending::PendingSource;
+use starknet_gateway_types::reply::PendingStateUpdate;
 
 /// Determines block status based on the current L1-L2 stored in the DB.
 pub fn get_block_status(
-    db_tx: &rusqlite::Transaction<'_>,
+    db_tx: &impl ReadAccess,
     block_number: StarknetBlockNumber,
 ) -> anyhow::Result<BlockStatus> {
     // All our data is L2 accepted, check our L1-L2 head to see if this block has been accepted on L1.
@@ -19,3 +21,23 @@ pub fn get_block_status(
 
     Ok(block_status)
 }
+
+/// Looks up a value of the pending state using `find`.
+///
+/// If the pending state update does not contain the value, returns the block whose state the
+/// pending state update applies to. Reading that block instead of the latest one keeps the result
+/// consistent with the pending data, even if sync applied a new head in the meantime.
+pub async fn find_in_pending<T>(
+    pending: Option<&dyn PendingSource>,
+    find: impl FnOnce(&PendingStateUpdate) -> Option<T>,
+) -> Result<T, StarknetBlocksBlockId> {
+    let (parent_hash, _, state_update) = match pending {
+        Some(pending) => pending
+            .state_update_on_parent_block()
+            .await
+            .ok_or(StarknetBlocksBlockId::Latest)?,
+        None => return Err(StarknetBlocksBlockId::Latest),
+    };
+
+    find(&state_update).ok_or_else(|| parent_hash.into())
+}
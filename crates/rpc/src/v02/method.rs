@@ -16,7 +16,7 @@ mod get_state_update;
 mod get_storage_at;
 mod get_transaction_by_block_id_and_index;
 mod get_transaction_by_hash;
-mod get_transaction_receipt;
+pub(crate) mod get_transaction_receipt;
 mod pending_transactions;
 mod syncing;
 
@@ -33,6 +33,8 @@ pub(crate) use get_class::get_class;
 pub(crate) use get_class_at::get_class_at;
 pub(crate) use get_class_hash_at::get_class_hash_at;
 pub(super) use get_events::get_events;
+#[cfg(feature = "fuzzing")]
+pub(crate) use get_events::GetEventsInput;
 pub(crate) use get_nonce::get_nonce;
 pub(super) use get_state_update::get_state_update;
 pub(crate) use get_storage_at::get_storage_at;
@@ -8,15 +8,37 @@ use starknet_gateway_types::request::add_transaction::{
     CairoContractDefinition, ContractDefinition, SierraContractDefinition,
 };
 
-crate::error::generate_rpc_error_subset!(AddDeclareTransactionError: InvalidContractClass);
+crate::error::generate_rpc_error_subset!(
+    AddDeclareTransactionError: InvalidContractClass,
+    ClassAlreadyDeclared,
+    InvalidTransactionNonce,
+    InsufficientMaxFee,
+    InsufficientAccountBalance,
+    ValidationFailure,
+    CompilationFailed,
+    DuplicateTransaction,
+    CompiledClassHashMismatch,
+    UnsupportedTxVersion,
+    ReadOnly
+);
 
 impl From<SequencerError> for AddDeclareTransactionError {
     fn from(e: SequencerError) -> Self {
-        use starknet_gateway_types::error::StarknetErrorCode::InvalidProgram;
+        use starknet_gateway_types::error::StarknetErrorCode::*;
         match e {
-            SequencerError::StarknetError(e) if e.code == InvalidProgram => {
-                Self::InvalidContractClass
-            }
+            SequencerError::StarknetError(e) => match e.code {
+                InvalidProgram | InvalidContractDefinition => Self::InvalidContractClass,
+                ClassAlreadyDeclared => Self::ClassAlreadyDeclared,
+                InvalidTransactionNonce => Self::InvalidTransactionNonce,
+                InsufficientMaxFee => Self::InsufficientMaxFee,
+                InsufficientAccountBalance => Self::InsufficientAccountBalance,
+                ValidateFailure => Self::ValidationFailure,
+                CompilationFailed => Self::CompilationFailed,
+                DuplicatedTransaction => Self::DuplicateTransaction,
+                InvalidCompiledClassHash => Self::CompiledClassHashMismatch,
+                InvalidTransactionVersion => Self::UnsupportedTxVersion,
+                _ => Self::Internal(SequencerError::StarknetError(e).into()),
+            },
             _ => Self::Internal(e.into()),
         }
     }
@@ -51,6 +73,10 @@ pub async fn add_declare_transaction(
     context: RpcContext,
     input: AddDeclareTransactionInput,
 ) -> Result<AddDeclareTransactionOutput, AddDeclareTransactionError> {
+    if context.read_only {
+        return Err(AddDeclareTransactionError::ReadOnly);
+    }
+
     match input.declare_transaction {
         Transaction::Declare(BroadcastedDeclareTransaction::V0V1(tx)) => {
             let contract_definition: CairoContractDefinition = tx
@@ -359,14 +385,7 @@ mod tests {
             token: None,
         };
         let error = add_declare_transaction(context, input).await.unwrap_err();
-        // FIXME at least make sure a proper starknet error variant is returned from the gateway
-        // until a more meaningful rpc error code is introduced in the spec and we can use it
-        assert_matches::assert_matches!(error, AddDeclareTransactionError::Internal(error) => {
-            let error = error.downcast::<SequencerError>().unwrap();
-            assert_matches::assert_matches!(error, SequencerError::StarknetError(error) => {
-                assert_eq!(error.code, StarknetErrorCode::CompilationFailed);
-            })
-        });
+        assert_matches::assert_matches!(error, AddDeclareTransactionError::CompilationFailed);
     }
 
     #[test_log::test(tokio::test)]
@@ -440,4 +459,32 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn gateway_rejections() {
+        let rejection = |code| {
+            let error =
+                SequencerError::StarknetError(starknet_gateway_types::error::StarknetError {
+                    code,
+                    message: "rejected".to_owned(),
+                });
+            crate::error::RpcError::from(AddDeclareTransactionError::from(error)).code()
+        };
+
+        assert_eq!(rejection(StarknetErrorCode::InvalidProgram), 50);
+        assert_eq!(rejection(StarknetErrorCode::ClassAlreadyDeclared), 51);
+        assert_eq!(rejection(StarknetErrorCode::InvalidTransactionNonce), 52);
+        assert_eq!(rejection(StarknetErrorCode::InsufficientMaxFee), 53);
+        assert_eq!(rejection(StarknetErrorCode::InsufficientAccountBalance), 54);
+        assert_eq!(rejection(StarknetErrorCode::ValidateFailure), 55);
+        assert_eq!(rejection(StarknetErrorCode::CompilationFailed), 56);
+        assert_eq!(rejection(StarknetErrorCode::DuplicatedTransaction), 59);
+        assert_eq!(rejection(StarknetErrorCode::InvalidCompiledClassHash), 60);
+        assert_eq!(rejection(StarknetErrorCode::InvalidTransactionVersion), 61);
+        // Rejections without a specific error code are internal errors.
+        assert_eq!(
+            rejection(StarknetErrorCode::TransactionLimitExceeded),
+            -32603
+        );
+    }
 }
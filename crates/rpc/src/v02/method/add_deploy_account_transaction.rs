@@ -1,9 +1,9 @@
 use crate::context::RpcContext;
 use crate::felt::{RpcFelt, RpcFelt251};
 use crate::v02::types::request::BroadcastedDeployAccountTransaction;
-use anyhow::Context;
 use pathfinder_common::{ContractAddress, StarknetTransactionHash};
 use starknet_gateway_client::ClientApi;
+use starknet_gateway_types::error::SequencerError;
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 #[serde(tag = "type")]
@@ -26,12 +26,44 @@ pub struct AddDeployAccountTransactionOutput {
     contract_address: ContractAddress,
 }
 
-crate::error::generate_rpc_error_subset!(AddDeployAccountTransactionError: ClassHashNotFound);
+crate::error::generate_rpc_error_subset!(
+    AddDeployAccountTransactionError: ClassHashNotFound,
+    InvalidTransactionNonce,
+    InsufficientMaxFee,
+    InsufficientAccountBalance,
+    ValidationFailure,
+    DuplicateTransaction,
+    UnsupportedTxVersion,
+    ReadOnly
+);
+
+impl From<SequencerError> for AddDeployAccountTransactionError {
+    fn from(e: SequencerError) -> Self {
+        use starknet_gateway_types::error::StarknetErrorCode::*;
+        match e {
+            SequencerError::StarknetError(e) => match e.code {
+                UndeclaredClass => Self::ClassHashNotFound,
+                InvalidTransactionNonce => Self::InvalidTransactionNonce,
+                InsufficientMaxFee => Self::InsufficientMaxFee,
+                InsufficientAccountBalance => Self::InsufficientAccountBalance,
+                ValidateFailure => Self::ValidationFailure,
+                DuplicatedTransaction => Self::DuplicateTransaction,
+                InvalidTransactionVersion => Self::UnsupportedTxVersion,
+                _ => Self::Internal(SequencerError::StarknetError(e).into()),
+            },
+            _ => Self::Internal(e.into()),
+        }
+    }
+}
 
 pub async fn add_deploy_account_transaction(
     context: RpcContext,
     input: AddDeployAccountTransactionInput,
 ) -> Result<AddDeployAccountTransactionOutput, AddDeployAccountTransactionError> {
+    if context.read_only {
+        return Err(AddDeployAccountTransactionError::ReadOnly);
+    }
+
     let Transaction::DeployAccount(tx) = input.deploy_account_transaction;
     let response = context
         .sequencer
@@ -44,8 +76,7 @@ pub async fn add_deploy_account_transaction(
             tx.class_hash,
             tx.constructor_calldata,
         )
-        .await
-        .context("Sending Deploy Account Transaction to the gateway")?;
+        .await?;
 
     Ok(AddDeployAccountTransactionOutput {
         transaction_hash: response.transaction_hash,
@@ -147,4 +178,18 @@ mod tests {
 
         assert_eq!(response, expected);
     }
+
+    #[test]
+    fn undeclared_class() {
+        use starknet_gateway_types::error::{StarknetError, StarknetErrorCode};
+
+        let error = SequencerError::StarknetError(StarknetError {
+            code: StarknetErrorCode::UndeclaredClass,
+            message: "Class with hash 0x1 is not declared.".to_owned(),
+        });
+        assert_matches::assert_matches!(
+            AddDeployAccountTransactionError::from(error),
+            AddDeployAccountTransactionError::ClassHashNotFound
+        );
+    }
 }
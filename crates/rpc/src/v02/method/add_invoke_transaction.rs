@@ -1,11 +1,37 @@
 use crate::context::RpcContext;
 use crate::felt::RpcFelt;
 use crate::v02::types::request::BroadcastedInvokeTransaction;
-use anyhow::Context;
 use pathfinder_common::StarknetTransactionHash;
 use starknet_gateway_client::ClientApi;
+use starknet_gateway_types::error::SequencerError;
 
-crate::error::generate_rpc_error_subset!(AddInvokeTransactionError);
+crate::error::generate_rpc_error_subset!(
+    AddInvokeTransactionError: InvalidTransactionNonce,
+    InsufficientMaxFee,
+    InsufficientAccountBalance,
+    ValidationFailure,
+    DuplicateTransaction,
+    UnsupportedTxVersion,
+    ReadOnly
+);
+
+impl From<SequencerError> for AddInvokeTransactionError {
+    fn from(e: SequencerError) -> Self {
+        use starknet_gateway_types::error::StarknetErrorCode::*;
+        match e {
+            SequencerError::StarknetError(e) => match e.code {
+                InvalidTransactionNonce => Self::InvalidTransactionNonce,
+                InsufficientMaxFee => Self::InsufficientMaxFee,
+                InsufficientAccountBalance => Self::InsufficientAccountBalance,
+                ValidateFailure => Self::ValidationFailure,
+                DuplicatedTransaction => Self::DuplicateTransaction,
+                InvalidTransactionVersion => Self::UnsupportedTxVersion,
+                _ => Self::Internal(SequencerError::StarknetError(e).into()),
+            },
+            _ => Self::Internal(e.into()),
+        }
+    }
+}
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 #[serde(tag = "type")]
@@ -30,37 +56,43 @@ pub async fn add_invoke_transaction(
     context: RpcContext,
     input: AddInvokeTransactionInput,
 ) -> Result<AddInvokeTransactionOutput, AddInvokeTransactionError> {
+    if context.read_only {
+        return Err(AddInvokeTransactionError::ReadOnly);
+    }
+
     let Transaction::Invoke(tx) = input.invoke_transaction;
     let response = match tx {
-        BroadcastedInvokeTransaction::V0(v0) => context
-            .sequencer
-            .add_invoke_transaction(
-                v0.version,
-                v0.max_fee,
-                v0.signature,
-                // Nonce is part of the RPC specification for V0 but this
-                // is a bug in the spec. The gateway won't accept it, so
-                // we null it out.
-                None,
-                v0.contract_address,
-                Some(v0.entry_point_selector),
-                v0.calldata,
-            )
-            .await
-            .context("Sending V0 invoke transaction to gateway")?,
-        BroadcastedInvokeTransaction::V1(v1) => context
-            .sequencer
-            .add_invoke_transaction(
-                v1.version,
-                v1.max_fee,
-                v1.signature,
-                Some(v1.nonce),
-                v1.sender_address,
-                None,
-                v1.calldata,
-            )
-            .await
-            .context("Sending V1 invoke transaction to gateway")?,
+        BroadcastedInvokeTransaction::V0(v0) => {
+            context
+                .sequencer
+                .add_invoke_transaction(
+                    v0.version,
+                    v0.max_fee,
+                    v0.signature,
+                    // Nonce is part of the RPC specification for V0 but this
+                    // is a bug in the spec. The gateway won't accept it, so
+                    // we null it out.
+                    None,
+                    v0.contract_address,
+                    Some(v0.entry_point_selector),
+                    v0.calldata,
+                )
+                .await?
+        }
+        BroadcastedInvokeTransaction::V1(v1) => {
+            context
+                .sequencer
+                .add_invoke_transaction(
+                    v1.version,
+                    v1.max_fee,
+                    v1.signature,
+                    Some(v1.nonce),
+                    v1.sender_address,
+                    None,
+                    v1.calldata,
+                )
+                .await?
+        }
     };
 
     Ok(AddInvokeTransactionOutput {
@@ -258,4 +290,29 @@ mod tests {
         let result = add_invoke_transaction(context, input).await.unwrap();
         assert_eq!(result, expected);
     }
+
+    #[tokio::test]
+    async fn read_only() {
+        let context = RpcContext::for_tests().with_read_only();
+        let input = AddInvokeTransactionInput {
+            invoke_transaction: test_invoke_txn(),
+        };
+
+        let error = add_invoke_transaction(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, AddInvokeTransactionError::ReadOnly);
+    }
+
+    #[test]
+    fn insufficient_max_fee() {
+        use starknet_gateway_types::error::{StarknetError, StarknetErrorCode};
+
+        let error = SequencerError::StarknetError(StarknetError {
+            code: StarknetErrorCode::InsufficientMaxFee,
+            message: "Actual fee exceeded max fee.".to_owned(),
+        });
+        assert_matches::assert_matches!(
+            AddInvokeTransactionError::from(error),
+            AddInvokeTransactionError::InsufficientMaxFee
+        );
+    }
 }
@@ -2,7 +2,7 @@ use crate::context::RpcContext;
 use crate::felt::RpcFelt;
 use anyhow::Context;
 use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
-use pathfinder_storage::StarknetBlocksTable;
+use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable};
 
 #[serde_with::serde_as]
 #[derive(serde::Serialize)]
@@ -18,25 +18,31 @@ pub async fn block_hash_and_number(
     context: RpcContext,
 ) -> Result<BlockHashAndNumber, BlockNumberError> {
     let storage = context.storage.clone();
+
+    if let Some(latest) = storage.header_cache().get(StarknetBlocksBlockId::Latest) {
+        return Ok(BlockHashAndNumber {
+            block_hash: latest.hash,
+            block_number: latest.number,
+        });
+    }
+
     let span = tracing::Span::current();
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-        let tx = db.transaction().context("Creating database transaction")?;
-
-        StarknetBlocksTable::get_latest_hash_and_number(&tx)
-            .context("Reading latest block hash and number from database")?
-            .map(|(block_hash, block_number)| BlockHashAndNumber {
-                block_hash,
-                block_number,
-            })
-            .ok_or(BlockNumberError::NoBlocks)
+        storage.read(|tx| {
+            StarknetBlocksTable::get_latest_hash_and_number(tx)
+                .context("Reading latest block hash and number from database")
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    jh.await
+        .context("Database read panic or shutting down")??
+        .map(|(block_hash, block_number)| BlockHashAndNumber {
+            block_hash,
+            block_number,
+        })
+        .ok_or(BlockNumberError::NoBlocks)
 }
 
 pub async fn block_number(context: RpcContext) -> Result<StarknetBlockNumber, BlockNumberError> {
@@ -60,4 +66,24 @@ mod tests {
             StarknetBlockHash(pathfinder_common::felt_bytes!(b"latest"))
         );
     }
+
+    #[tokio::test]
+    async fn cached() {
+        let context = RpcContext::for_tests();
+
+        let mut latest = {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest)
+                .unwrap()
+                .unwrap()
+        };
+        latest.number += 1;
+        latest.hash = StarknetBlockHash(pathfinder_common::felt_bytes!(b"cached"));
+        context.storage.header_cache().insert(latest.clone());
+
+        let result = block_hash_and_number(context).await.unwrap();
+        assert_eq!(result.block_number, latest.number);
+        assert_eq!(result.block_hash, latest.hash);
+    }
 }
@@ -7,7 +7,8 @@ crate::error::generate_rpc_error_subset!(
     ContractNotFound,
     InvalidMessageSelector,
     InvalidCallData,
-    ContractError
+    ContractError,
+    StatePruned
 );
 
 impl From<crate::cairo::ext_py::CallFailure> for CallError {
@@ -15,6 +16,7 @@ impl From<crate::cairo::ext_py::CallFailure> for CallError {
         use crate::cairo::ext_py::CallFailure::*;
         match c {
             NoSuchBlock => Self::BlockNotFound,
+            StatePruned => Self::StatePruned,
             NoSuchContract => Self::ContractNotFound,
             InvalidEntryPoint => Self::InvalidMessageSelector,
             ExecutionFailed(e) => Self::Internal(anyhow::anyhow!("Internal error: {}", e)),
@@ -58,6 +60,11 @@ impl From<FunctionCall> for crate::v02::types::request::Call {
 #[derive(serde::Serialize, Debug)]
 pub struct CallOutput(#[serde_as(as = "Vec<RpcFelt>")] Vec<CallResultValue>);
 
+/// Executes the call against the locally synced state of the given block.
+///
+/// Calls never reach the gateway; they are executed by the pool of Python workers, which read
+/// contract classes and storage directly from the database. Throughput therefore scales with
+/// `--python-subprocesses`.
 pub async fn call(context: RpcContext, input: CallInput) -> Result<CallOutput, CallError> {
     let handle = context
         .call_handle
@@ -14,7 +14,8 @@ crate::error::generate_rpc_error_subset!(
     ContractNotFound,
     ContractError,
     InvalidMessageSelector,
-    InvalidCallData
+    InvalidCallData,
+    StatePruned
 );
 
 impl From<crate::cairo::ext_py::CallFailure> for EstimateFeeError {
@@ -22,6 +23,7 @@ impl From<crate::cairo::ext_py::CallFailure> for EstimateFeeError {
         use crate::cairo::ext_py::CallFailure::*;
         match c {
             NoSuchBlock => Self::BlockNotFound,
+            StatePruned => Self::StatePruned,
             NoSuchContract => Self::ContractNotFound,
             InvalidEntryPoint => Self::InvalidMessageSelector,
             ExecutionFailed(e) => Self::Internal(anyhow::anyhow!("Internal error: {}", e)),
@@ -31,6 +33,11 @@ impl From<crate::cairo::ext_py::CallFailure> for EstimateFeeError {
     }
 }
 
+/// Estimates the fee of the transaction against the locally synced state of the given block.
+///
+/// Estimates never reach the gateway. The transaction is executed by the pool of Python workers,
+/// and priced with the gas price stored with the block, or with the current `eth_gasPrice` of the
+/// Ethereum endpoint for the `latest` and `pending` tags.
 pub async fn estimate_fee(
     context: RpcContext,
     input: EstimateFeeInput,
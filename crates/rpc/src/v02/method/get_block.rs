@@ -1,8 +1,12 @@
 use crate::context::RpcContext;
 use crate::v02::common::get_block_status;
+use crate::v02::types::reply::BlockStatus;
 use anyhow::{anyhow, Context};
 use pathfinder_common::{BlockId, StarknetBlockHash, StarknetBlockNumber, StateCommitment};
-use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable};
+use pathfinder_storage::{
+    BlockHeaderCache, ReadAccess, StarknetBlocksBlockId, StarknetBlocksTable,
+    StarknetTransactionsTable,
+};
 use serde::Deserialize;
 use stark_hash::Felt;
 
@@ -41,24 +45,54 @@ pub async fn get_block_with_txs(
 }
 
 /// Get block information given the block id
+///
+/// Blocks requested by number which are not stored yet may be served by the
+/// [BlockPrefetch](crate::prefetch::BlockPrefetch).
 async fn get_block(
     context: RpcContext,
     block_id: BlockId,
     scope: types::BlockResponseScope,
+) -> Result<types::Block, GetBlockError> {
+    let prefetch = context.block_prefetch.clone().and_then(|prefetch| {
+        let number = prefetch.observe(block_id)?;
+        Some((prefetch, number))
+    });
+
+    let result = get_stored_block(context, block_id, scope).await;
+    match (result, prefetch) {
+        (Err(GetBlockError::BlockNotFound), Some((prefetch, number))) => {
+            match prefetch.block(number).await {
+                Some(block) => Ok(types::Block::from_sequencer_scoped(
+                    block.as_ref().clone().into(),
+                    scope,
+                )),
+                None => Err(GetBlockError::BlockNotFound),
+            }
+        }
+        (result, _) => result,
+    }
+}
+
+async fn get_stored_block(
+    context: RpcContext,
+    block_id: BlockId,
+    scope: types::BlockResponseScope,
 ) -> Result<types::Block, GetBlockError> {
     let block_id = match block_id {
         BlockId::Pending => {
             match context
                 .pending_data
                 .ok_or_else(|| anyhow!("Pending data not supported in this configuration"))?
-                .block()
+                .block_with_version()
                 .await
             {
-                Some(block) => {
-                    return Ok(types::Block::from_sequencer_scoped(
-                        block.as_ref().clone().into(),
-                        scope,
-                    ))
+                Some((block, version)) => {
+                    let block =
+                        types::Block::from_sequencer_scoped(block.as_ref().clone().into(), scope);
+                    return Ok(types::Block {
+                        pending_state_version: Some(version),
+                        ..block
+                    });
                 }
                 None => return Err(GetBlockError::BlockNotFound),
             }
@@ -68,38 +102,70 @@ async fn get_block(
         BlockId::Latest => StarknetBlocksBlockId::Latest,
     };
 
+    // Only blocks requested by hash are cached, as the latest block and block numbers change
+    // meaning on reorgs.
+    let cache_key = match block_id {
+        StarknetBlocksBlockId::Hash(hash) => Some((scope.cache_method(), hash.0)),
+        _ => None,
+    };
+    if let Some((method, hash)) = cache_key {
+        if let Some(block) = context.storage.response_cache().get(method, hash) {
+            return Ok(block);
+        }
+    }
+
     let storage = context.storage.clone();
+    let cache_generation = storage.response_cache().generation();
     let span = tracing::Span::current();
 
     tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut connection = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let transaction = connection
-            .transaction()
-            .context("Creating database transaction")?;
-
-        // Need to get the block status. This also tests that the block hash is valid.
-        let block = get_raw_block(&transaction, block_id)?;
+        storage.read(|tx| {
+            // Need to get the block status. This also tests that the block hash is valid.
+            let block = match get_raw_block(tx, storage.header_cache(), block_id)? {
+                Some(block) => block,
+                None => return Ok(None),
+            };
 
-        let transactions = get_block_transactions(&transaction, block.number, scope)?;
+            let block_number = block.number;
+            let transactions = get_block_transactions(tx, block_number, scope)?;
+            let block = types::Block::from_raw(block, transactions);
+
+            if let Some((method, hash)) = cache_key {
+                if block.status == BlockStatus::AcceptedOnL1 {
+                    storage.response_cache().insert(
+                        method,
+                        hash,
+                        block_number,
+                        cache_generation,
+                        block.clone(),
+                    );
+                }
+            }
 
-        Ok(types::Block::from_raw(block, transactions))
+            Ok(Some(block))
+        })
     })
     .await
-    .context("Database read panic or shutting down")?
+    .context("Database read panic or shutting down")??
+    .ok_or(GetBlockError::BlockNotFound)
 }
 
-/// Fetches a [RawBlock](types::RawBlock) from storage.
+/// Fetches a [RawBlock](types::RawBlock) from storage, or [None] if it does not exist.
 fn get_raw_block(
-    transaction: &rusqlite::Transaction<'_>,
+    transaction: &impl ReadAccess,
+    header_cache: &BlockHeaderCache,
     block_id: StarknetBlocksBlockId,
-) -> Result<types::RawBlock, GetBlockError> {
-    let block = StarknetBlocksTable::get(transaction, block_id)
-        .context("Read block from database")?
-        .ok_or(GetBlockError::BlockNotFound)?;
+) -> anyhow::Result<Option<types::RawBlock>> {
+    let block = match header_cache.get(block_id) {
+        Some(block) => block,
+        None => match StarknetBlocksTable::get(transaction, block_id)
+            .context("Read block from database")?
+        {
+            Some(block) => block,
+            None => return Ok(None),
+        },
+    };
 
     let block_status = get_block_status(transaction, block.number)?;
 
@@ -108,9 +174,12 @@ fn get_raw_block(
             (StarknetBlockHash(Felt::ZERO), StateCommitment(Felt::ZERO))
         }
         other => {
-            let parent_block = StarknetBlocksTable::get(transaction, (other - 1).into())
-                .context("Read parent block from database")?
-                .context("Parent block missing")?;
+            let parent_block = match header_cache.get((other - 1).into()) {
+                Some(parent_block) => parent_block,
+                None => StarknetBlocksTable::get(transaction, (other - 1).into())
+                    .context("Read parent block from database")?
+                    .context("Parent block missing")?,
+            };
 
             (parent_block.hash, parent_block.root)
         }
@@ -128,15 +197,15 @@ fn get_raw_block(
         sequencer: block.sequencer_address,
     };
 
-    Ok(block)
+    Ok(Some(block))
 }
 
 /// This function assumes that the block ID is valid i.e. it won't check if the block hash or number exist.
 fn get_block_transactions(
-    db_tx: &rusqlite::Transaction<'_>,
+    db_tx: &impl ReadAccess,
     block_number: StarknetBlockNumber,
     scope: types::BlockResponseScope,
-) -> Result<types::Transactions, GetBlockError> {
+) -> anyhow::Result<types::Transactions> {
     let transactions_receipts =
         StarknetTransactionsTable::get_transaction_data_for_block(db_tx, block_number.into())
             .context("Reading transactions from database")?;
@@ -168,6 +237,7 @@ mod types {
     use serde::Serialize;
     use serde_with::{serde_as, skip_serializing_none};
     use stark_hash::Felt;
+    use starknet_gateway_types::pending::PendingStateVersion;
 
     /// Determines the type of response to block related queries.
     #[derive(Copy, Clone, Debug)]
@@ -176,6 +246,17 @@ mod types {
         FullTransactions,
     }
 
+    impl BlockResponseScope {
+        /// The method under which blocks of this scope are stored in the
+        /// [ResponseCache](pathfinder_storage::ResponseCache).
+        pub fn cache_method(&self) -> &'static str {
+            match self {
+                BlockResponseScope::TransactionHashes => "starknet_getBlockWithTxHashes",
+                BlockResponseScope::FullTransactions => "starknet_getBlockWithTxs",
+            }
+        }
+    }
+
     /// Wrapper for transaction data returned in block related queries,
     /// chosen variant depends on [`BlockResponseScope`].
     #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
@@ -215,6 +296,9 @@ mod types {
         #[serde_as(as = "RpcFelt")]
         pub sequencer_address: SequencerAddress,
         pub transactions: Transactions,
+        /// Identifies the pending block, which clients can pass back as the
+        /// `pending_state_version` param of later queries to detect whether it changed.
+        pub pending_state_version: Option<PendingStateVersion>,
     }
 
     /// Convenience type for DB manipulation.
@@ -243,6 +327,7 @@ mod types {
                 timestamp: block.timestamp,
                 sequencer_address: block.sequencer,
                 transactions,
+                pending_state_version: None,
             }
         }
 
@@ -282,6 +367,7 @@ mod types {
                         // Default value for cairo <0.8.0 is 0
                         .unwrap_or(SequencerAddress(Felt::ZERO)),
                     transactions,
+                    pending_state_version: None,
                 },
                 MaybePendingBlock::Pending(pending) => Self {
                     status: pending.status.into(),
@@ -292,6 +378,7 @@ mod types {
                     timestamp: pending.timestamp,
                     sequencer_address: pending.sequencer_address,
                     transactions,
+                    pending_state_version: None,
                 },
             }
         }
@@ -303,7 +390,7 @@ mod tests {
     use super::*;
     use assert_matches::assert_matches;
     use jsonrpsee::types::Params;
-    use pathfinder_common::{felt, StarknetBlockHash, StarknetBlockNumber};
+    use pathfinder_common::{felt, felt_bytes, StarknetBlockHash, StarknetBlockNumber};
     use starknet_gateway_types::pending::PendingData;
 
     #[test]
@@ -401,11 +488,14 @@ mod tests {
                 ctx.clone(),
                 BlockId::Pending,
                 Box::new(|i, result| {
-                    assert_matches!(result, Ok(block) => assert_eq!(
-                        block.parent_hash,
-                        StarknetBlockHash(pathfinder_common::felt_bytes!(b"latest")),
-                        "test case {i}"
-                    ), "test case {i}")
+                    assert_matches!(result, Ok(block) => {
+                        assert_eq!(
+                            block.parent_hash,
+                            StarknetBlockHash(pathfinder_common::felt_bytes!(b"latest")),
+                            "test case {i}"
+                        );
+                        assert!(block.pending_state_version.is_some(), "test case {i}");
+                    }, "test case {i}")
                 }),
             ),
             (
@@ -452,4 +542,76 @@ mod tests {
             check(i, test_case).await;
         }
     }
+
+    #[tokio::test]
+    async fn caches_blocks_accepted_on_l1() {
+        let context = RpcContext::for_tests();
+        {
+            let mut connection = context.storage.connection().unwrap();
+            let transaction = connection.transaction().unwrap();
+            pathfinder_storage::RefsTable::set_l1_l2_head(
+                &transaction,
+                Some(StarknetBlockNumber::GENESIS),
+            )
+            .unwrap();
+            transaction.commit().unwrap();
+        }
+
+        let genesis = StarknetBlockHash(felt_bytes!(b"genesis"));
+        let block1 = StarknetBlockHash(felt_bytes!(b"block 1"));
+        for hash in [genesis, block1] {
+            let input = GetBlockInput {
+                block_id: BlockId::Hash(hash),
+            };
+            get_block_with_tx_hashes(context.clone(), input)
+                .await
+                .unwrap();
+        }
+
+        let cache = context.storage.response_cache();
+        let method = types::BlockResponseScope::TransactionHashes.cache_method();
+        let cached = cache.get::<types::Block>(method, genesis.0).unwrap();
+        assert_eq!(cached.status, BlockStatus::AcceptedOnL1);
+        // Only accepted on L2, so it could still change.
+        assert!(cache.get::<types::Block>(method, block1.0).is_none());
+        // Blocks with full transactions are cached separately.
+        let method = types::BlockResponseScope::FullTransactions.cache_method();
+        assert!(cache.get::<types::Block>(method, genesis.0).is_none());
+    }
+
+    #[tokio::test]
+    async fn serves_prefetched_blocks_beyond_storage() {
+        use crate::prefetch::{BlockPrefetch, PrefetchConfig};
+        use starknet_gateway_client::test_utils::{MockGateway, ScriptedChain};
+
+        let mut chain = ScriptedChain::default();
+        chain.push_empty_blocks(5);
+        let gateway = MockGateway::spawn(chain);
+        let prefetch = BlockPrefetch::new(
+            gateway.client(),
+            PrefetchConfig::new(std::num::NonZeroU64::new(1).unwrap()),
+        );
+        let context = RpcContext::for_tests().with_block_prefetch(prefetch);
+
+        // Blocks 0 to 2 are stored.
+        for number in 0..=3 {
+            let input = GetBlockInput {
+                block_id: BlockId::Number(StarknetBlockNumber::new_or_panic(number)),
+            };
+            let block = get_block_with_tx_hashes(context.clone(), input)
+                .await
+                .unwrap();
+            assert_eq!(
+                block.block_number,
+                Some(StarknetBlockNumber::new_or_panic(number))
+            );
+        }
+
+        // Only the blocks following sequential requests are fetched.
+        let input = GetBlockInput {
+            block_id: BlockId::Number(StarknetBlockNumber::new_or_panic(10)),
+        };
+        let error = get_block_with_tx_hashes(context, input).await.unwrap_err();
+        assert_matches!(error, GetBlockError::BlockNotFound);
+    }
 }
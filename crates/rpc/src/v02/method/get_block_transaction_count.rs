@@ -1,7 +1,7 @@
 use crate::context::RpcContext;
 use anyhow::Context;
 use pathfinder_common::BlockId;
-use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable};
+use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable};
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct GetBlockTransactionCountInput {
@@ -12,10 +12,17 @@ type BlockTransactionCount = u64;
 
 crate::error::generate_rpc_error_subset!(GetBlockTransactionCountError: BlockNotFound);
 
+/// Blocks requested by number which are not stored yet may be served by the
+/// [BlockPrefetch](crate::prefetch::BlockPrefetch).
 pub async fn get_block_transaction_count(
     context: RpcContext,
     input: GetBlockTransactionCountInput,
 ) -> Result<BlockTransactionCount, GetBlockTransactionCountError> {
+    let prefetch = context.block_prefetch.clone().and_then(|prefetch| {
+        let number = prefetch.observe(input.block_id)?;
+        Some((prefetch, number))
+    });
+
     let block_id = match input.block_id {
         BlockId::Hash(hash) => hash.into(),
         BlockId::Number(number) => number.into(),
@@ -36,30 +43,23 @@ pub async fn get_block_transaction_count(
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-        let tx = db.transaction().context("Creating database transaction")?;
-
-        let block_transaction_count =
-            StarknetTransactionsTable::get_transaction_count(&tx, block_id)
-                .context("Reading transaction count from database")?;
-
-        // Check if the value was 0 because there were no transactions, or because the block hash is invalid.
-        if block_transaction_count == 0 {
-            // get_storage_commitment is cheaper than querying the full block.
-            let storage_commitment = StarknetBlocksTable::get_storage_commitment(&tx, block_id)
-                .context("Reading storage commitment from database")?;
-            return if storage_commitment.is_some() {
-                Ok(0)
-            } else {
-                Err(GetBlockTransactionCountError::BlockNotFound)
-            };
-        }
-        Ok(block_transaction_count as BlockTransactionCount)
+        storage.read(|tx| {
+            StarknetBlocksTable::get_transaction_count(tx, block_id)
+                .context("Reading transaction count from database")
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    let block_transaction_count = jh.await.context("Database read panic or shutting down")??;
+
+    match (block_transaction_count, prefetch) {
+        (Some(count), _) => Ok(count as BlockTransactionCount),
+        (None, Some((prefetch, number))) => prefetch
+            .block(number)
+            .await
+            .map(|block| block.transactions.len() as BlockTransactionCount)
+            .ok_or(GetBlockTransactionCountError::BlockNotFound),
+        (None, None) => Err(GetBlockTransactionCountError::BlockNotFound),
+    }
 }
 
 #[cfg(test)]
@@ -2,12 +2,14 @@ use crate::context::RpcContext;
 use crate::v02::types::ContractClass;
 use anyhow::Context;
 use pathfinder_common::{BlockId, ClassHash};
+use pathfinder_storage::ReadTransaction;
 use rusqlite::OptionalExtension;
-use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::pending::PendingSource;
+use std::sync::Arc;
 
 crate::error::generate_rpc_error_subset!(GetClassError: BlockNotFound, ClassHashNotFound);
 
-#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct GetClassInput {
     block_id: BlockId,
     class_hash: ClassHash,
@@ -28,45 +30,78 @@ pub async fn get_class(
         other => other,
     };
 
+    // The class definition may not have been downloaded yet, see `--sync.lazy-class-download`.
+    if let Some(class) = read_class(context.clone(), block, input.class_hash).await? {
+        return Ok(class);
+    }
+
+    context
+        .download_deferred_class(input.class_hash)
+        .await
+        .context("Downloading deferred class definition")?;
+
+    let class = read_class(context, block, input.class_hash)
+        .await?
+        .context("Class definition is missing after download")?;
+
+    Ok(class)
+}
+
+/// Returns [None] if the class definition has not been downloaded yet.
+async fn read_class(
+    context: RpcContext,
+    block: BlockId,
+    class_hash: ClassHash,
+) -> Result<Option<ContractClass>, GetClassError> {
     let span = tracing::Span::current();
-    let jh = tokio::task::spawn_blocking(move || -> Result<ContractClass, GetClassError> {
+    let jh = tokio::task::spawn_blocking(move || -> Result<_, GetClassError> {
         let _g = span.enter();
-        let mut db = context
-            .storage
-            .connection()
-            .context("Opening database connection")?;
-        let tx = db.transaction().context("Creating database transaction")?;
-
-        let definition = match block {
-            BlockId::Pending => read_pending(&tx, input.class_hash),
-            BlockId::Number(number) => read_at_number(&tx, input.class_hash, number),
-            BlockId::Hash(hash) => read_at_hash(&tx, input.class_hash, hash),
-            BlockId::Latest => read_latest(&tx, input.class_hash),
-        }?;
+        let definition = context.storage.read(|tx| {
+            let definition = match block {
+                BlockId::Pending => read_pending(tx, class_hash),
+                BlockId::Number(number) => read_at_number(tx, class_hash, number),
+                BlockId::Hash(hash) => read_at_hash(tx, class_hash, hash),
+                BlockId::Latest => read_latest(tx, class_hash),
+            };
+
+            // Only internal errors fail the read, so that they are retried if the database is busy.
+            match definition {
+                Err(GetClassError::Internal(e)) => Err(e),
+                definition => Ok(definition),
+            }
+        })??;
+
+        let definition = match definition {
+            Some(definition) => definition,
+            None => return Ok(None),
+        };
 
         let definition =
             zstd::decode_all(&*definition).context("Decompressing class definition")?;
         let class = ContractClass::from_definition_bytes(&definition)
             .context("Parsing class definition")?;
 
-        Ok(class)
+        Ok(Some(class))
     });
 
     jh.await.context("Reading class from database")?
 }
 
-/// Returns the class definition data.
+/// Returns the class definition data, which is [None] if it has not been downloaded yet.
 ///
 /// This is useful only if you are already certain this class was declared.
 fn read_pending(
-    tx: &rusqlite::Transaction<'_>,
+    tx: &ReadTransaction<'_>,
     class: ClassHash,
-) -> Result<Vec<u8>, GetClassError> {
+) -> Result<Option<Vec<u8>>, GetClassError> {
     tx.query_row(
         "SELECT definition FROM class_definitions WHERE hash=?",
         rusqlite::params! { class },
         |row| {
-            let def = row.get_ref_unwrap(0).as_blob()?.to_owned();
+            let def = row
+                .get_ref_unwrap(0)
+                .as_blob_or_null()?
+                .map(ToOwned::to_owned);
             Ok(def)
         },
     )
@@ -76,13 +111,19 @@ fn read_pending(
 }
 
 /// Returns the class definition data iff it was declared on a canonical block.
-fn read_latest(tx: &rusqlite::Transaction<'_>, class: ClassHash) -> Result<Vec<u8>, GetClassError> {
+fn read_latest(
+    tx: &ReadTransaction<'_>,
+    class: ClassHash,
+) -> Result<Option<Vec<u8>>, GetClassError> {
     // This works because declared_on is only set if the class was declared in a canonical block.
     tx.query_row(
         "SELECT definition FROM class_definitions WHERE hash=? AND declared_on IS NOT NULL",
         rusqlite::params! { class },
         |row| {
-            let def = row.get_ref_unwrap(0).as_blob()?.to_owned();
+            let def = row
+                .get_ref_unwrap(0)
+                .as_blob_or_null()?
+                .map(ToOwned::to_owned);
             Ok(def)
         },
     )
@@ -94,10 +135,10 @@ fn read_latest(tx: &rusqlite::Transaction<'_>, class: ClassHash) -> Result<Vec<u
 /// Returns the class definition data iff it was declared on or before the given block hash.
 /// The block hash provided must also form part of the canonical chain.
 fn read_at_hash(
-    tx: &rusqlite::Transaction<'_>,
+    tx: &ReadTransaction<'_>,
     class: ClassHash,
     block: pathfinder_common::StarknetBlockHash,
-) -> Result<Vec<u8>, GetClassError> {
+) -> Result<Option<Vec<u8>>, GetClassError> {
     let number = tx
         .query_row(
             "SELECT number FROM canonical_blocks WHERE hash=?",
@@ -116,7 +157,7 @@ fn read_at_hash(
         WHERE code.hash=? AND blocks.number <= ?",
         rusqlite::params! { class, number },
         |row| {
-            let def = row.get_ref_unwrap(0).as_blob()?.to_owned();
+            let def = row.get_ref_unwrap(0).as_blob_or_null()?.map(ToOwned::to_owned);
             Ok(def)
         },
     )
@@ -127,10 +168,10 @@ fn read_at_hash(
 
 /// Returns the class definition data iff it was declared on or before the given block number.
 fn read_at_number(
-    tx: &rusqlite::Transaction<'_>,
+    tx: &ReadTransaction<'_>,
     class: ClassHash,
     block: pathfinder_common::StarknetBlockNumber,
-) -> Result<Vec<u8>, GetClassError> {
+) -> Result<Option<Vec<u8>>, GetClassError> {
     // Check that the block number exists. This has to happen first as the <= check
     // in the class selection query will work even if the block number exceeds what
     // is available in canonical_blocks.
@@ -149,7 +190,7 @@ fn read_at_number(
         WHERE code.hash=? AND blocks.number <= ?",
         rusqlite::params! { class, block },
         |row| {
-            let def = row.get_ref_unwrap(0).as_blob()?.to_owned();
+            let def = row.get_ref_unwrap(0).as_blob_or_null()?.map(ToOwned::to_owned);
             Ok(def)
         },
     )
@@ -159,7 +200,7 @@ fn read_at_number(
 }
 
 /// Returns true if the class is declared or deployed in the pending state.
-async fn is_pending_class(pending: &Option<PendingData>, hash: ClassHash) -> bool {
+async fn is_pending_class(pending: &Option<Arc<dyn PendingSource>>, hash: ClassHash) -> bool {
     let state_diff = match pending {
         Some(pending) => match pending.state_update().await {
             Some(pending) => pending,
@@ -487,4 +528,57 @@ mod tests {
         .unwrap_err();
         assert_matches!(error, GetClassError::BlockNotFound);
     }
+
+    #[tokio::test]
+    async fn deferred() {
+        use futures::FutureExt;
+
+        let context = RpcContext::for_tests();
+        let class_hash = ClassHash(felt_bytes!(b"class 0 hash"));
+
+        // Sync has deferred downloading the definition.
+        let definition = {
+            let connection = context.storage.connection().unwrap();
+            let definition: Vec<u8> = connection
+                .query_row(
+                    "SELECT definition FROM class_definitions WHERE hash = ?",
+                    [class_hash],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            connection
+                .execute(
+                    "UPDATE class_definitions SET definition = NULL WHERE hash = ?",
+                    [class_hash],
+                )
+                .unwrap();
+            definition
+        };
+
+        let input = GetClassInput {
+            block_id: BlockId::Latest,
+            class_hash,
+        };
+
+        let error = super::get_class(context.clone(), input.clone())
+            .await
+            .unwrap_err();
+        assert_matches!(error, GetClassError::Internal(_));
+
+        let storage = context.storage.clone();
+        let download: crate::context::DeferredClassDownload =
+            std::sync::Arc::new(move |hash: ClassHash| {
+                let connection = storage.connection().unwrap();
+                connection
+                    .execute(
+                        "UPDATE class_definitions SET definition = ? WHERE hash = ?",
+                        rusqlite::params![&definition, hash],
+                    )
+                    .unwrap();
+                futures::future::ready(Ok(())).boxed()
+            });
+        let context = context.with_deferred_class_download(download);
+
+        super::get_class(context, input).await.unwrap();
+    }
 }
@@ -1,13 +1,20 @@
 use crate::context::RpcContext;
+use crate::v02::common::find_in_pending;
 use crate::v02::types::ContractClass;
 use anyhow::Context;
 use pathfinder_common::{BlockId, ClassHash, ContractAddress};
 use pathfinder_merkle_tree::state_tree::StorageCommitmentTree;
-use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable};
+use pathfinder_storage::{
+    ReadTransaction, StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable,
+};
 use rusqlite::OptionalExtension;
-use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::pending::PendingSource;
 
-crate::error::generate_rpc_error_subset!(GetClassAtError: BlockNotFound, ContractNotFound);
+crate::error::generate_rpc_error_subset!(
+    GetClassAtError: BlockNotFound,
+    ContractNotFound,
+    StatePruned
+);
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct GetClassAtInput {
@@ -19,28 +26,51 @@ pub async fn get_class_at(
     context: RpcContext,
     input: GetClassAtInput,
 ) -> Result<ContractClass, GetClassAtError> {
+    // The class definition may not have been downloaded yet, see `--sync.lazy-class-download`.
+    let class_hash =
+        match read_class_at(context.clone(), input.block_id, input.contract_address).await? {
+            Ok(class) => return Ok(class),
+            Err(class_hash) => class_hash,
+        };
+
+    context
+        .download_deferred_class(class_hash)
+        .await
+        .context("Downloading deferred class definition")?;
+
+    match read_class_at(context, input.block_id, input.contract_address).await? {
+        Ok(class) => Ok(class),
+        Err(_) => Err(anyhow::anyhow!("Class definition is missing after download").into()),
+    }
+}
+
+/// Returns the [ClassHash] instead of the class if its definition has not been downloaded yet.
+async fn read_class_at(
+    context: RpcContext,
+    block_id: BlockId,
+    contract_address: ContractAddress,
+) -> Result<Result<ContractClass, ClassHash>, GetClassAtError> {
     let span = tracing::Span::current();
-    let block = match input.block_id {
+    let block = match block_id {
         BlockId::Number(number) => number.into(),
         BlockId::Hash(hash) => hash.into(),
         BlockId::Latest => StarknetBlocksBlockId::Latest,
         BlockId::Pending => {
-            match get_pending_class_hash(context.pending_data, input.contract_address).await {
-                Some(class) => {
+            let pending_class =
+                get_pending_class_hash(context.pending_data.as_deref(), contract_address).await;
+            match pending_class {
+                Ok(class) => {
                     let jh = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
                         let _g = span.enter();
-                        let mut db = context
-                            .storage
-                            .connection()
-                            .context("Opening database connection")?;
-
-                        let tx = db.transaction().context("Creating database transaction")?;
-
-                        let definition = get_definition(&tx, class)?;
+                        let definition =
+                            match context.storage.read(|tx| get_definition(tx, class))? {
+                                Some(definition) => definition,
+                                None => return Ok(Err(class)),
+                            };
                         let class = ContractClass::from_definition_bytes(&definition)
                             .context("Parsing class definition")?;
 
-                        Ok(class)
+                        Ok(Ok(class))
                     });
 
                     let class = jh
@@ -48,7 +78,7 @@ pub async fn get_class_at(
                         .context("Reading class definition from database")??;
                     return Ok(class);
                 }
-                None => StarknetBlocksBlockId::Latest,
+                Err(block_id) => block_id,
             }
         }
     };
@@ -61,26 +91,30 @@ pub async fn get_class_at(
             .context("Opening database connection")?;
 
         let tx = db.transaction().context("Creating database transaction")?;
-        let definition = get_definition_at(&tx, block, input.contract_address)?;
+        let definition = match get_definition_at(&tx, block, contract_address)? {
+            Ok(definition) => definition,
+            Err(class_hash) => return Ok(Err(class_hash)),
+        };
         let class = ContractClass::from_definition_bytes(&definition)
             .context("Parsing class definition")?;
 
-        Ok(class)
+        Ok(Ok(class))
     });
 
     jh.await.context("Reading class from database")?
 }
 
-/// Fetches the class's definition without checking any block requirements.
+/// Fetches the class's definition without checking any block requirements, which is [None] if it
+/// has not been downloaded yet.
 ///
 /// This is useful if you have previously already verified that the class should exist.
-fn get_definition(tx: &rusqlite::Transaction<'_>, class: ClassHash) -> anyhow::Result<Vec<u8>> {
+fn get_definition(tx: &ReadTransaction<'_>, class: ClassHash) -> anyhow::Result<Option<Vec<u8>>> {
     let definition = tx
         .query_row(
             "SELECT definition FROM class_definitions WHERE hash=?",
             [class],
             |row| {
-                let data = row.get_ref_unwrap(0).as_blob()?.to_vec();
+                let data = row.get_ref_unwrap(0).as_blob_or_null()?.map(<[u8]>::to_vec);
                 Ok(data)
             },
         )
@@ -91,15 +125,20 @@ fn get_definition(tx: &rusqlite::Transaction<'_>, class: ClassHash) -> anyhow::R
     Ok(definition)
 }
 
+/// Returns the [ClassHash] instead of the definition if it has not been downloaded yet.
 fn get_definition_at(
     tx: &rusqlite::Transaction<'_>,
     block: StarknetBlocksBlockId,
     contract: ContractAddress,
-) -> Result<Vec<u8>, GetClassAtError> {
+) -> Result<Result<Vec<u8>, ClassHash>, GetClassAtError> {
     let storage_commitment = StarknetBlocksTable::get_storage_commitment(tx, block)
         .context("Reading storage commitment from database")?
         .ok_or(GetClassAtError::BlockNotFound)?;
 
+    if TreePruningTable::is_pruned(tx, block).context("Query pruned state")? {
+        return Err(GetClassAtError::StatePruned);
+    }
+
     let tree = StorageCommitmentTree::load(tx, storage_commitment)
         .context("Loading storage commitment tree")?;
     let state_hash = tree
@@ -107,19 +146,25 @@ fn get_definition_at(
         .context("Fetching contract leaf in storage commitment tree")?
         .ok_or(GetClassAtError::ContractNotFound)?;
 
-    let definition = tx
+    let (class_hash, definition) = tx
         .query_row(
-            "SELECT definition FROM class_definitions code JOIN contract_states states ON (code.hash = states.hash) WHERE states.state_hash=?",
+            "SELECT code.hash, definition FROM class_definitions code JOIN contract_states states ON (code.hash = states.hash) WHERE states.state_hash=?",
             [state_hash],
             |row| {
-                let data = row.get_ref_unwrap(0).as_blob()?.to_vec();
-                Ok(data)
+                let class_hash: ClassHash = row.get(0)?;
+                let data = row.get_ref_unwrap(1).as_blob_or_null()?.map(<[u8]>::to_vec);
+                Ok((class_hash, data))
             }
         )
         .optional()
         .context("Reading definition from database")?
         .context("Class definition is missing")?;
 
+    let definition = match definition {
+        Some(definition) => definition,
+        None => return Ok(Err(class_hash)),
+    };
+
     let definition = zstd::decode_all(&*definition)
         .context("Decompressing contract definition")
         .map_err(|e| {
@@ -129,21 +174,23 @@ fn get_definition_at(
             ))
         })?;
 
-    Ok(definition)
+    Ok(Ok(definition))
 }
 
-/// Returns the [ClassHash] of the given [ContractAddress] if any is defined in the pending data.
+/// Returns the [ClassHash] of the given [ContractAddress] if any is defined in the pending data,
+/// or the block to read it from otherwise.
 async fn get_pending_class_hash(
-    pending: Option<PendingData>,
+    pending: Option<&dyn PendingSource>,
     address: ContractAddress,
-) -> Option<ClassHash> {
-    pending?.state_update().await.and_then(|state_update| {
+) -> Result<ClassHash, StarknetBlocksBlockId> {
+    find_in_pending(pending, |state_update| {
         state_update
             .state_diff
             .deployed_contracts
             .iter()
             .find_map(|contract| (contract.address == address).then_some(contract.class_hash))
     })
+    .await
 }
 
 #[cfg(test)]
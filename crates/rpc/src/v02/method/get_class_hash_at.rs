@@ -1,12 +1,17 @@
 use crate::context::RpcContext;
 use crate::felt::RpcFelt;
+use crate::v02::common::find_in_pending;
 use anyhow::Context;
 use pathfinder_common::{BlockId, ClassHash, ContractAddress, ContractStateHash};
 use pathfinder_merkle_tree::state_tree::StorageCommitmentTree;
-use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable};
-use starknet_gateway_types::pending::PendingData;
+use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable};
+use starknet_gateway_types::pending::PendingSource;
 
-crate::error::generate_rpc_error_subset!(GetClassHashAtError: BlockNotFound, ContractNotFound);
+crate::error::generate_rpc_error_subset!(
+    GetClassHashAtError: BlockNotFound,
+    ContractNotFound,
+    StatePruned
+);
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct GetClassHashAtInput {
@@ -27,9 +32,12 @@ pub async fn get_class_hash_at(
         BlockId::Number(number) => number.into(),
         BlockId::Latest => StarknetBlocksBlockId::Latest,
         BlockId::Pending => {
-            match get_pending_class_hash(context.pending_data, input.contract_address).await {
-                Some(class_hash) => return Ok(GetClassHashOutput(class_hash)),
-                None => StarknetBlocksBlockId::Latest,
+            let pending_class =
+                get_pending_class_hash(context.pending_data.as_deref(), input.contract_address)
+                    .await;
+            match pending_class {
+                Ok(class_hash) => return Ok(GetClassHashOutput(class_hash)),
+                Err(block_id) => block_id,
             }
         }
     };
@@ -54,6 +62,10 @@ pub async fn get_class_hash_at(
             .context("Reading storage commitment from database")?
             .ok_or(GetClassHashAtError::BlockNotFound)?;
 
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetClassHashAtError::StatePruned);
+        }
+
         let tree = StorageCommitmentTree::load(&tx, storage_commitment)
             .context("Loading storage commitment tree")?;
         let state_hash = tree
@@ -74,12 +86,13 @@ pub async fn get_class_hash_at(
     jh.await.context("Database read panic or shutting down")?
 }
 
-/// Returns the [ClassHash] of the given [ContractAddress] if any is defined in the pending data.
+/// Returns the [ClassHash] of the given [ContractAddress] if any is defined in the pending data,
+/// or the block to read it from otherwise.
 async fn get_pending_class_hash(
-    pending: Option<PendingData>,
+    pending: Option<&dyn PendingSource>,
     address: ContractAddress,
-) -> Option<ClassHash> {
-    pending?.state_update().await.and_then(|state_update| {
+) -> Result<ClassHash, StarknetBlocksBlockId> {
+    find_in_pending(pending, |state_update| {
         state_update
             .state_diff
             .deployed_contracts
@@ -91,6 +104,7 @@ async fn get_pending_class_hash(
                 .iter()
                 .find_map(|contract| (contract.address == address).then_some(contract.class_hash)))
     })
+    .await
 }
 
 /// Returns the [ClassHash] for the given [ContractStateHash] from the database.
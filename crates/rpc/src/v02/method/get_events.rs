@@ -2,16 +2,19 @@ use crate::context::RpcContext;
 use anyhow::Context;
 use pathfinder_common::{BlockId, ContractAddress, EventKey, StarknetBlockNumber};
 use pathfinder_storage::{
-    EventFilterError, StarknetBlocksTable, StarknetEventFilter, StarknetEventsTable, V02KeyFilter,
+    EventFilterError, ReadTransaction, StarknetBlocksTable, StarknetEventFilter,
+    StarknetEventsTable, V02KeyFilter,
 };
 use serde::Deserialize;
-use starknet_gateway_types::pending::PendingData;
+use starknet_gateway_types::pending::PendingSource;
+use std::sync::Arc;
 use tokio::task::JoinHandle;
 
 crate::error::generate_rpc_error_subset!(
     GetEventsError: BlockNotFound,
     PageSizeTooBig,
-    InvalidContinuationToken
+    InvalidContinuationToken,
+    EventsQueryTooExpensive { blocks: u64, events: u64, limit: u64 }
 );
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
@@ -20,6 +23,29 @@ pub struct GetEventsInput {
     filter: EventFilter,
 }
 
+impl GetEventsInput {
+    /// Checks the filter before any events are queried, returning the offset given by the
+    /// continuation token.
+    ///
+    /// The page size is checked here instead of only by the database query, as requests for
+    /// pending events alone never reach the database.
+    pub(crate) fn validate(&self) -> Result<Option<usize>, GetEventsError> {
+        if self.filter.chunk_size > StarknetEventsTable::PAGE_SIZE_LIMIT {
+            return Err(GetEventsError::PageSizeTooBig);
+        }
+
+        self.filter
+            .continuation_token
+            .as_ref()
+            .map(|token| {
+                token
+                    .parse::<usize>()
+                    .map_err(|_| GetEventsError::InvalidContinuationToken)
+            })
+            .transpose()
+    }
+}
+
 /// Contains event filter parameters passed to `starknet_getEvents`.
 #[serde_with::skip_serializing_none]
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
@@ -67,14 +93,8 @@ pub async fn get_events(
 
     use BlockId::*;
 
+    let requested_offset = input.validate()?;
     let request = input.filter;
-    let requested_offset = match request.continuation_token {
-        Some(s) => Some(
-            s.parse::<usize>()
-                .map_err(|_| GetEventsError::InvalidContinuationToken)?,
-        ),
-        None => None,
-    };
 
     // Handle the trivial (1) and (2) cases.
     match (request.from_block, request.to_block) {
@@ -86,10 +106,12 @@ pub async fn get_events(
         }
         (Some(Pending), Some(Pending)) => {
             let skip = requested_offset.unwrap_or_default();
+            let pending_generation = pending_generation(&context.pending_data);
 
             let mut events = Vec::new();
             let is_last_page = append_pending_events(
                 &context.pending_data,
+                pending_generation,
                 &mut events,
                 skip,
                 request.chunk_size,
@@ -111,76 +133,37 @@ pub async fn get_events(
         _ => {}
     }
 
+    // Pending events may only be appended to the database events if they are built on the latest
+    // block which the database query sees, so the generation of the pending data is recorded
+    // before the query. A new head written meanwhile advances it.
+    let pending_generation = pending_generation(&context.pending_data);
+
     let storage = context.storage.clone();
-    let keys = V02KeyFilter(request.keys.clone());
+    let max_cost = context.get_events_max_cost;
 
     // blocking task to perform database event query and optionally, the event count
     // required for (4d).
     let span = tracing::Span::current();
-    let db_events: JoinHandle<Result<_, GetEventsError>> = tokio::task::spawn_blocking(move || {
+    let db_request = request.clone();
+    let db_events: JoinHandle<anyhow::Result<_>> = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut connection = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let transaction = connection
-            .transaction()
-            .context("Creating database transaction")?;
-
-        let from_block = map_from_block_to_number(&transaction, request.from_block)?;
-        let to_block = map_to_block_to_number(&transaction, request.to_block)?;
-
-        let filter = StarknetEventFilter {
-            from_block,
-            to_block,
-            contract_address: request.address,
-            keys: keys.clone(),
-            page_size: request.chunk_size,
-            offset: requested_offset.unwrap_or_default(),
-        };
-        // We don't add context here, because [StarknetEventsTable::get_events] adds its
-        // own context to the errors. This way we get meaningful error information
-        // for errors related to query parameters.
-        let page = StarknetEventsTable::get_events(&transaction, &filter).map_err(|e| {
-            if e.downcast_ref::<EventFilterError>().is_some() {
-                GetEventsError::PageSizeTooBig
-            } else {
-                GetEventsError::from(e)
+        storage.read(|tx| {
+            // Only internal errors fail the read, so that they are retried if the database is busy.
+            match read_events(
+                tx,
+                &db_request,
+                max_cost,
+                requested_offset.unwrap_or_default(),
+            ) {
+                Err(GetEventsError::Internal(e)) => Err(e),
+                result => Ok(result),
             }
-        })?;
-
-        // Additional information is required if we need to append pending events.
-        // More specifically, we need some database event count in order to page through
-        // the pending events properly.
-        let event_count = if request.to_block == Some(Pending) && page.events.is_empty() {
-            let count = StarknetEventsTable::event_count(
-                &transaction,
-                from_block,
-                to_block,
-                request.address,
-                &keys,
-            )?;
-
-            Some(count)
-        } else {
-            None
-        };
-
-        let continuation_token =
-            next_continuation_token(filter.offset, filter.page_size, page.is_last_page);
-
-        Ok((
-            types::GetEventsResult {
-                events: page.events.into_iter().map(|e| e.into()).collect(),
-                continuation_token,
-            },
-            event_count,
-        ))
+        })
     });
 
     let (mut events, count) = db_events
         .await
-        .context("Database read panic or shutting down")??;
+        .context("Database read panic or shutting down")???;
 
     // Append pending data if required.
     if matches!(request.to_block, Some(Pending)) && events.events.len() < request.chunk_size {
@@ -203,6 +186,7 @@ pub async fn get_events(
 
         let is_last_page = append_pending_events(
             &context.pending_data,
+            pending_generation,
             &mut events.events,
             skip,
             amount,
@@ -223,12 +207,81 @@ pub async fn get_events(
     Ok(events)
 }
 
+/// Queries a page of database events, along with the number of events matching the filter if the
+/// page is empty and pending events are requested, which is needed to page through those.
+fn read_events(
+    tx: &ReadTransaction<'_>,
+    request: &EventFilter,
+    max_cost: Option<u64>,
+    requested_offset: usize,
+) -> Result<(types::GetEventsResult, Option<usize>), GetEventsError> {
+    use BlockId::*;
+
+    let keys = V02KeyFilter(request.keys.clone());
+    let from_block = map_from_block_to_number(tx, request.from_block)?;
+    let to_block = map_to_block_to_number(tx, request.to_block)?;
+
+    if let Some(max_cost) = max_cost {
+        let cost = StarknetEventsTable::estimate_query_cost(tx, from_block, to_block)
+            .context("Estimating events query cost")?;
+        if cost.events > max_cost {
+            return Err(GetEventsError::EventsQueryTooExpensive {
+                blocks: cost.blocks,
+                events: cost.events,
+                limit: max_cost,
+            });
+        }
+    }
+
+    let filter = StarknetEventFilter {
+        from_block,
+        to_block,
+        contract_address: request.address,
+        keys: keys.clone(),
+        page_size: request.chunk_size,
+        offset: requested_offset,
+    };
+    // We don't add context here, because [StarknetEventsTable::get_events] adds its
+    // own context to the errors. This way we get meaningful error information
+    // for errors related to query parameters.
+    let page = StarknetEventsTable::get_events(tx, &filter).map_err(|e| {
+        if e.downcast_ref::<EventFilterError>().is_some() {
+            GetEventsError::PageSizeTooBig
+        } else {
+            GetEventsError::from(e)
+        }
+    })?;
+
+    // Additional information is required if we need to append pending events.
+    // More specifically, we need some database event count in order to page through
+    // the pending events properly.
+    let event_count = if request.to_block == Some(Pending) && page.events.is_empty() {
+        let count =
+            StarknetEventsTable::event_count(tx, from_block, to_block, request.address, &keys)?;
+
+        Some(count)
+    } else {
+        None
+    };
+
+    let continuation_token =
+        next_continuation_token(filter.offset, filter.page_size, page.is_last_page);
+
+    Ok((
+        types::GetEventsResult {
+            events: page.events.into_iter().map(|e| e.into()).collect(),
+            continuation_token,
+        },
+        event_count,
+    ))
+}
+
 // Maps `to_block` BlockId to a block number which can be used by the events query.
 //
 // This block id specifies the upper end of the range, so pending/latest/None means
 // there's no upper limit.
 fn map_to_block_to_number(
-    tx: &rusqlite::Transaction<'_>,
+    tx: &ReadTransaction<'_>,
     block: Option<BlockId>,
 ) -> Result<Option<StarknetBlockNumber>, GetEventsError> {
     use BlockId::*;
@@ -250,7 +303,7 @@ fn map_to_block_to_number(
 // This block id specifies the lower end of the range, so pending/latest means
 // a lower limit here.
 fn map_from_block_to_number(
-    tx: &rusqlite::Transaction<'_>,
+    tx: &ReadTransaction<'_>,
     block: Option<BlockId>,
 ) -> Result<Option<StarknetBlockNumber>, GetEventsError> {
     use BlockId::*;
@@ -272,10 +325,21 @@ fn map_from_block_to_number(
     }
 }
 
+/// The current [PendingSource::generation], or zero if pending data is not supported.
+fn pending_generation(pending_data: &Option<Arc<dyn PendingSource>>) -> u64 {
+    pending_data
+        .as_ref()
+        .map(|pending| pending.generation())
+        .unwrap_or_default()
+}
+
 /// Append's pending events to `dst` based on the filter requirements and returns
 /// true if this was the last pending data i.e. `is_last_page`.
+///
+/// Only pending data of the given [generation](PendingSource::generation) is used.
 async fn append_pending_events(
-    pending_data: &Option<PendingData>,
+    pending_data: &Option<Arc<dyn PendingSource>>,
+    generation: u64,
     dst: &mut Vec<types::EmittedEvent>,
     skip: usize,
     amount: usize,
@@ -283,7 +347,7 @@ async fn append_pending_events(
     keys: std::collections::HashSet<EventKey>,
 ) -> bool {
     let pending_block = match pending_data.as_ref() {
-        Some(data) => match data.block().await {
+        Some(data) => match data.block_at(generation).await {
             Some(block) => block,
             None => return true,
         },
@@ -374,6 +438,7 @@ mod types {
         ContractAddress, EventData, EventKey, StarknetBlockHash, StarknetBlockNumber,
         StarknetTransactionHash,
     };
+    use pathfinder_serde::policy::{Numeric, RpcV02};
     use pathfinder_storage::StarknetEmittedEvent;
     use serde::Serialize;
 
@@ -382,9 +447,9 @@ mod types {
     #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
     #[serde(deny_unknown_fields)]
     pub struct EmittedEvent {
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub data: Vec<EventData>,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub keys: Vec<EventKey>,
         #[serde_as(as = "RpcFelt251")]
         pub from_address: ContractAddress,
@@ -498,6 +563,33 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn get_events_too_expensive() {
+        let (context, _) = setup();
+        let max_cost = test_utils::EVENTS_PER_BLOCK as u64;
+        let context = context.with_get_events_max_cost(max_cost);
+
+        let input = |to_block: u64| GetEventsInput {
+            filter: EventFilter {
+                from_block: Some(BlockId::Number(StarknetBlockNumber::GENESIS)),
+                to_block: Some(BlockId::Number(StarknetBlockNumber::new_or_panic(to_block))),
+                address: None,
+                keys: vec![],
+                chunk_size: 10,
+                continuation_token: None,
+            },
+        };
+
+        get_events(context.clone(), input(0)).await.unwrap();
+
+        let error = get_events(context, input(1)).await.unwrap_err();
+        assert_matches::assert_matches!(
+            error,
+            GetEventsError::EventsQueryTooExpensive { blocks: 2, events, limit }
+                if events == 2 * max_cost && limit == max_cost
+        );
+    }
+
     #[tokio::test]
     async fn get_events_with_empty_filter() {
         let (context, events) = setup();
@@ -731,6 +823,25 @@ mod tests {
             assert!(result.events.is_empty());
         }
 
+        #[tokio::test]
+        async fn invalid_page_size() {
+            let context = RpcContext::for_tests_with_pending().await;
+
+            let input = GetEventsInput {
+                filter: EventFilter {
+                    from_block: Some(BlockId::Pending),
+                    to_block: Some(BlockId::Pending),
+                    address: None,
+                    keys: vec![],
+                    chunk_size: usize::MAX,
+                    continuation_token: None,
+                },
+            };
+            let error = get_events(context, input).await.unwrap_err();
+
+            assert_eq!(GetEventsError::PageSizeTooBig, error);
+        }
+
         #[tokio::test]
         async fn all_events() {
             let context = RpcContext::for_tests_with_pending().await;
@@ -1,8 +1,10 @@
 use crate::context::RpcContext;
 use crate::felt::RpcFelt;
+use crate::v02::common::find_in_pending;
 use anyhow::Context;
 use pathfinder_common::{BlockId, ContractAddress, ContractNonce};
-use starknet_gateway_types::pending::PendingData;
+use pathfinder_storage::StarknetBlocksBlockId;
+use starknet_gateway_types::pending::PendingSource;
 
 #[derive(serde::Deserialize, Debug, PartialEq, Eq)]
 pub struct GetNonceInput {
@@ -14,21 +16,27 @@ pub struct GetNonceInput {
 #[derive(serde::Serialize, Debug, PartialEq)]
 pub struct GetNonceOutput(#[serde_as(as = "RpcFelt")] ContractNonce);
 
-crate::error::generate_rpc_error_subset!(GetNonceError: BlockNotFound, ContractNotFound);
+crate::error::generate_rpc_error_subset!(
+    GetNonceError: BlockNotFound,
+    ContractNotFound,
+    StatePruned
+);
 
 pub async fn get_nonce(
     context: RpcContext,
     input: GetNonceInput,
 ) -> Result<GetNonceOutput, GetNonceError> {
     use pathfinder_merkle_tree::state_tree::StorageCommitmentTree;
-    use pathfinder_storage::{ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable};
+    use pathfinder_storage::{ContractsStateTable, StarknetBlocksTable, TreePruningTable};
 
     // We can potentially read the nonce from pending without having to reach out to the database.
     let block_id = match input.block_id {
         BlockId::Pending => {
-            match get_pending_nonce(&context.pending_data, input.contract_address).await {
-                Some(nonce) => return Ok(GetNonceOutput(nonce)),
-                None => StarknetBlocksBlockId::Latest,
+            let pending_nonce =
+                get_pending_nonce(context.pending_data.as_deref(), input.contract_address).await;
+            match pending_nonce {
+                Ok(nonce) => return Ok(GetNonceOutput(nonce)),
+                Err(block_id) => block_id,
             }
         }
         BlockId::Latest => StarknetBlocksBlockId::Latest,
@@ -49,6 +57,10 @@ pub async fn get_nonce(
             .context("Fetching storage commitment")?
             .ok_or(GetNonceError::BlockNotFound)?;
 
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetNonceError::StatePruned);
+        }
+
         let storage_commitment_tree = StorageCommitmentTree::load(&tx, storage_commitment)
             .context("Loading storage commitment tree")?;
 
@@ -67,18 +79,15 @@ pub async fn get_nonce(
     jh.await.context("Database read panic or shutting down")?
 }
 
-/// Returns the contract's pending nonce.
+/// Returns the contract's pending nonce, or the block to read it from if it is not pending.
 async fn get_pending_nonce(
-    pending: &Option<PendingData>,
+    pending: Option<&dyn PendingSource>,
     contract_address: ContractAddress,
-) -> Option<ContractNonce> {
-    match pending {
-        Some(pending) => pending
-            .state_update()
-            .await
-            .and_then(|update| update.state_diff.nonces.get(&contract_address).copied()),
-        None => None,
-    }
+) -> Result<ContractNonce, StarknetBlocksBlockId> {
+    find_in_pending(pending, |update| {
+        update.state_diff.nonces.get(&contract_address).copied()
+    })
+    .await
 }
 
 #[cfg(test)]
@@ -264,18 +273,25 @@ mod tests {
 
         let pending_data = starknet_gateway_types::pending::PendingData::default();
         pending_data.set(block, state_update).await;
-        let pending_data = Some(pending_data);
-
-        let result = get_pending_nonce(&pending_data, valid_1).await;
-        assert_eq!(result, Some(nonce_1));
-
-        let result = get_pending_nonce(&pending_data, valid_2).await;
-        assert_eq!(result, Some(nonce_2));
-
-        let result = get_pending_nonce(&pending_data, invalid).await;
-        assert_eq!(result, None);
-
-        let result = get_pending_nonce(&None, valid_1).await;
-        assert_eq!(result, None);
+        let pending_data: Option<&dyn PendingSource> = Some(&pending_data);
+
+        let result = get_pending_nonce(pending_data, valid_1).await;
+        assert_eq!(result, Ok(nonce_1));
+
+        let result = get_pending_nonce(pending_data, valid_2).await;
+        assert_eq!(result, Ok(nonce_2));
+
+        // Nonces which are not pending are read from the pending block's parent.
+        let result = get_pending_nonce(pending_data, invalid).await;
+        assert_eq!(
+            result,
+            Err(StarknetBlockHash(felt_bytes!(b"dont care")).into())
+        );
+
+        let result = get_pending_nonce(None, valid_1).await;
+        assert_eq!(
+            result,
+            Err(pathfinder_storage::StarknetBlocksBlockId::Latest)
+        );
     }
 }
@@ -1,9 +1,12 @@
 use crate::context::RpcContext;
 use crate::felt::RpcFelt;
+use crate::v02::common::find_in_pending;
 use anyhow::{anyhow, Context};
 use pathfinder_common::{BlockId, ContractAddress, StorageAddress, StorageValue};
 use pathfinder_merkle_tree::state_tree::{ContractsStateTree, StorageCommitmentTree};
-use pathfinder_storage::{ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable};
+use pathfinder_storage::{
+    ContractsStateTable, StarknetBlocksBlockId, StarknetBlocksTable, TreePruningTable,
+};
 use serde::Deserialize;
 use stark_hash::Felt;
 
@@ -18,7 +21,11 @@ pub struct GetStorageAtInput {
 #[derive(serde::Serialize)]
 pub struct GetStorageOutput(#[serde_as(as = "RpcFelt")] StorageValue);
 
-crate::error::generate_rpc_error_subset!(GetStorageAtError: ContractNotFound, BlockNotFound);
+crate::error::generate_rpc_error_subset!(
+    GetStorageAtError: ContractNotFound,
+    BlockNotFound,
+    StatePruned
+);
 
 /// Get the value of the storage at the given address and key.
 pub async fn get_storage_at(
@@ -30,29 +37,26 @@ pub async fn get_storage_at(
         BlockId::Number(number) => number.into(),
         BlockId::Latest => StarknetBlocksBlockId::Latest,
         BlockId::Pending => {
-            match context
+            let pending = context
                 .pending_data
-                .ok_or_else(|| anyhow!("Pending data not supported in this configuration"))?
-                .state_update()
-                .await
-            {
-                Some(update) => {
-                    let pending_value = update
-                        .state_diff
-                        .storage_diffs
-                        .get(&input.contract_address)
-                        .and_then(|storage| {
-                            storage.iter().find_map(|update| {
-                                (update.key == input.key).then_some(update.value)
-                            })
-                        });
+                .as_deref()
+                .ok_or_else(|| anyhow!("Pending data not supported in this configuration"))?;
+            let pending_value = find_in_pending(Some(pending), |update| {
+                update
+                    .state_diff
+                    .storage_diffs
+                    .get(&input.contract_address)
+                    .and_then(|storage| {
+                        storage
+                            .iter()
+                            .find_map(|update| (update.key == input.key).then_some(update.value))
+                    })
+            })
+            .await;
 
-                    match pending_value {
-                        Some(value) => return Ok(GetStorageOutput(value)),
-                        None => StarknetBlocksBlockId::Latest,
-                    }
-                }
-                None => StarknetBlocksBlockId::Latest,
+            match pending_value {
+                Ok(value) => return Ok(GetStorageOutput(value)),
+                Err(block_id) => block_id,
             }
         }
     };
@@ -76,6 +80,10 @@ pub async fn get_storage_at(
             // by using a dedicated error code from the RPC API spec
             .ok_or(GetStorageAtError::BlockNotFound)?;
 
+        if TreePruningTable::is_pruned(&tx, block_id).context("Query pruned state")? {
+            return Err(GetStorageAtError::StatePruned);
+        }
+
         let storage_commitment_tree =
             StorageCommitmentTree::load(&tx, storage_commitment).context("Global state tree")?;
 
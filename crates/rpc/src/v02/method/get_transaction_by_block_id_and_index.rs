@@ -15,6 +15,8 @@ crate::error::generate_rpc_error_subset!(
     InvalidTxnIndex
 );
 
+/// Blocks requested by number which are not stored yet may be served by the
+/// [BlockPrefetch](crate::prefetch::BlockPrefetch).
 pub async fn get_transaction_by_block_id_and_index(
     context: RpcContext,
     input: GetTransactionByBlockIdAndIndexInput,
@@ -25,6 +27,11 @@ pub async fn get_transaction_by_block_id_and_index(
         .try_into()
         .map_err(|_| GetTransactionByBlockIdAndIndexError::InvalidTxnIndex)?;
 
+    let prefetch = context.block_prefetch.clone().and_then(|prefetch| {
+        let number = prefetch.observe(input.block_id)?;
+        Some((prefetch, number))
+    });
+
     let block_id = match input.block_id {
         BlockId::Hash(hash) => hash.into(),
         BlockId::Number(number) => number.into(),
@@ -39,37 +46,52 @@ pub async fn get_transaction_by_block_id_and_index(
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let db_tx = db.transaction().context("Creating database transaction")?;
-
-        // Get the transaction from storage.
-        match StarknetTransactionsTable::get_transaction_at_block(&db_tx, block_id, index)
-            .context("Reading transaction from database")?
-        {
-            Some(transaction) => Ok(transaction.into()),
-            None => {
-                // We now need to check whether it was the block hash or transaction index which were invalid. We do this by checking if the block exists
-                // at all. If no, then the block hash is invalid. If yes, then the index is invalid.
-                //
-                // get_storage_commitment is cheaper than querying the full block.
-                match StarknetBlocksTable::get_storage_commitment(&db_tx, block_id)
-                    .context("Reading block from database")?
-                {
-                    Some(_) => Err(GetTransactionByBlockIdAndIndexError::InvalidTxnIndex),
-                    None => Err(GetTransactionByBlockIdAndIndexError::BlockNotFound),
-                }
+        storage.read(|tx| {
+            // The block's transaction count tells apart an invalid block from an invalid index
+            // without looking up the transaction.
+            let count = match StarknetBlocksTable::get_transaction_count(tx, block_id)
+                .context("Reading transaction count from database")?
+            {
+                Some(count) => count,
+                None => return Ok(None),
+            };
+            if index >= count {
+                return Ok(Some(None));
             }
-        }
+
+            let transaction =
+                StarknetTransactionsTable::get_transaction_at_block(tx, block_id, index)
+                    .context("Reading transaction from database")?;
+            Ok(Some(transaction))
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    let transaction = match (
+        jh.await.context("Database read panic or shutting down")??,
+        prefetch,
+    ) {
+        (Some(transaction), _) => transaction,
+        (None, Some((prefetch, number))) => {
+            let block = prefetch
+                .block(number)
+                .await
+                .ok_or(GetTransactionByBlockIdAndIndexError::BlockNotFound)?;
+            return block
+                .transactions
+                .get(index)
+                .map(Into::into)
+                .ok_or(GetTransactionByBlockIdAndIndexError::InvalidTxnIndex);
+        }
+        (None, None) => return Err(GetTransactionByBlockIdAndIndexError::BlockNotFound),
+    };
+
+    transaction
+        .map(Into::into)
+        .ok_or(GetTransactionByBlockIdAndIndexError::InvalidTxnIndex)
 }
 
 async fn get_transaction_from_pending(
-    pending: &Option<starknet_gateway_types::pending::PendingData>,
+    pending: &Option<std::sync::Arc<dyn starknet_gateway_types::pending::PendingSource>>,
     index: usize,
 ) -> Result<Transaction, GetTransactionByBlockIdAndIndexError> {
     // We return InvalidTxnIndex even if the pending block is technically missing.
@@ -1,5 +1,6 @@
 use crate::context::RpcContext;
-use crate::v02::types::reply::Transaction;
+use crate::v02::common::get_block_status;
+use crate::v02::types::reply::{BlockStatus, Transaction};
 use anyhow::Context;
 use pathfinder_common::StarknetTransactionHash;
 use pathfinder_storage::StarknetTransactionsTable;
@@ -11,6 +12,9 @@ pub struct GetTransactionByHashInput {
 
 crate::error::generate_rpc_error_subset!(GetTransactionByHashError: TxnHashNotFound);
 
+/// The method under which transactions are stored in the [ResponseCache](pathfinder_storage::ResponseCache).
+const CACHE_METHOD: &str = "starknet_getTransactionByHash";
+
 pub async fn get_transaction_by_hash(
     context: RpcContext,
     input: GetTransactionByHashInput,
@@ -29,25 +33,53 @@ pub async fn get_transaction_by_hash(
         }
     }
 
+    if let Some(transaction) = context
+        .storage
+        .response_cache()
+        .get(CACHE_METHOD, input.transaction_hash.0)
+    {
+        return Ok(transaction);
+    }
+
     let storage = context.storage.clone();
+    let cache_generation = storage.response_cache().generation();
     let span = tracing::Span::current();
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let db_tx = db.transaction().context("Creating database transaction")?;
+        storage.read(|tx| {
+            // Get the transaction from storage.
+            let transaction =
+                match StarknetTransactionsTable::get_transaction(tx, input.transaction_hash)
+                    .context("Reading transaction from database")?
+                {
+                    Some(transaction) => Transaction::from(transaction),
+                    None => return Ok(None),
+                };
+
+            // The transaction itself never changes, but it may still be reorged away.
+            let block_number =
+                StarknetTransactionsTable::get_block_number(tx, input.transaction_hash)
+                    .context("Reading transaction block number from database")?;
+            if let Some(block_number) = block_number {
+                if get_block_status(tx, block_number)? == BlockStatus::AcceptedOnL1 {
+                    storage.response_cache().insert(
+                        CACHE_METHOD,
+                        input.transaction_hash.0,
+                        block_number,
+                        cache_generation,
+                        transaction.clone(),
+                    );
+                }
+            }
 
-        // Get the transaction from storage.
-        StarknetTransactionsTable::get_transaction(&db_tx, input.transaction_hash)
-            .context("Reading transaction from database")?
-            .ok_or(GetTransactionByHashError::TxnHashNotFound)
-            .map(|tx| tx.into())
+            Ok(Some(transaction))
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    jh.await
+        .context("Database read panic or shutting down")??
+        .ok_or(GetTransactionByHashError::TxnHashNotFound)
 }
 
 #[cfg(test)]
@@ -168,4 +200,42 @@ mod tests {
             }))
         )
     }
+
+    #[tokio::test]
+    async fn caches_transactions_accepted_on_l1() {
+        let context = RpcContext::for_tests();
+        let cache = context.storage.response_cache();
+
+        let input = GetTransactionByHashInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"txn 0")),
+        };
+        get_transaction_by_hash(context.clone(), input)
+            .await
+            .unwrap();
+        assert!(cache
+            .get::<Transaction>(CACHE_METHOD, felt_bytes!(b"txn 0"))
+            .is_none());
+
+        {
+            let mut db = context.storage.connection().unwrap();
+            let tx = db.transaction().unwrap();
+            pathfinder_storage::RefsTable::set_l1_l2_head(
+                &tx,
+                Some(pathfinder_common::StarknetBlockNumber::GENESIS),
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        let input = GetTransactionByHashInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"txn 0")),
+        };
+        let result = get_transaction_by_hash(context.clone(), input)
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get::<Transaction>(CACHE_METHOD, felt_bytes!(b"txn 0")),
+            Some(result)
+        );
+    }
 }
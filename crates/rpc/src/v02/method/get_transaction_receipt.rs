@@ -1,5 +1,6 @@
 use crate::context::RpcContext;
 use crate::v02::common::get_block_status;
+use crate::v02::types::reply::BlockStatus;
 use anyhow::Context;
 use pathfinder_common::StarknetTransactionHash;
 use pathfinder_storage::{StarknetBlocksTable, StarknetTransactionsTable};
@@ -11,6 +12,9 @@ pub struct GetTransactionReceiptInput {
 
 crate::error::generate_rpc_error_subset!(GetTransactionReceiptError: TxnHashNotFound);
 
+/// The method under which receipts are stored in the [ResponseCache](pathfinder_storage::ResponseCache).
+const CACHE_METHOD: &str = "starknet_getTransactionReceipt";
+
 pub async fn get_transaction_receipt(
     context: RpcContext,
     input: GetTransactionReceiptInput,
@@ -35,58 +39,81 @@ pub async fn get_transaction_receipt(
         };
     }
 
+    if let Some(receipt) = context
+        .storage
+        .response_cache()
+        .get(CACHE_METHOD, input.transaction_hash.0)
+    {
+        return Ok(receipt);
+    }
+
     let storage = context.storage.clone();
+    let cache_generation = storage.response_cache().generation();
     let span = tracing::Span::current();
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let db_tx = db.transaction().context("Creating database transaction")?;
+        storage.read(|tx| {
+            let (transaction, receipt, block_hash) =
+                match StarknetTransactionsTable::get_transaction_with_receipt(
+                    tx,
+                    input.transaction_hash,
+                )
+                .context("Reading transaction receipt from database")?
+                {
+                    Some(found) => found,
+                    None => return Ok(None),
+                };
+
+            // We require the block status here as well..
+            let block_number = StarknetBlocksTable::get_number(tx, block_hash)
+                .context("Reading block from database")?
+                .context("Block missing from database")?;
+            let block_status = get_block_status(tx, block_number)?;
+
+            let receipt = types::MaybePendingTransactionReceipt::Normal(
+                types::TransactionReceipt::with_block_data(
+                    receipt,
+                    block_status,
+                    block_hash,
+                    block_number,
+                    transaction,
+                ),
+            );
 
-        match StarknetTransactionsTable::get_transaction_with_receipt(
-            &db_tx,
-            input.transaction_hash,
-        )
-        .context("Reading transaction receipt from database")?
-        {
-            Some((transaction, receipt, block_hash)) => {
-                // We require the block status here as well..
-                let block_number = StarknetBlocksTable::get_number(&db_tx, block_hash)
-                    .context("Reading block from database")?
-                    .context("Block missing from database")?;
-                let block_status = get_block_status(&db_tx, block_number)?;
-
-                Ok(types::MaybePendingTransactionReceipt::Normal(
-                    types::TransactionReceipt::with_block_data(
-                        receipt,
-                        block_status,
-                        block_hash,
-                        block_number,
-                        transaction,
-                    ),
-                ))
+            if block_status == BlockStatus::AcceptedOnL1 {
+                storage.response_cache().insert(
+                    CACHE_METHOD,
+                    input.transaction_hash.0,
+                    block_number,
+                    cache_generation,
+                    receipt.clone(),
+                );
             }
-            None => Err(GetTransactionReceiptError::TxnHashNotFound),
-        }
+
+            Ok(Some(receipt))
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    jh.await
+        .context("Database read panic or shutting down")??
+        .ok_or(GetTransactionReceiptError::TxnHashNotFound)
 }
 
-mod types {
+pub(crate) mod types {
     use crate::felt::{RpcFelt, RpcFelt251};
     use crate::v02::types::reply::BlockStatus;
     use pathfinder_common::{
         ContractAddress, EthereumAddress, EventData, EventKey, Fee, L1ToL2MessagePayloadElem,
         L2ToL1MessagePayloadElem, StarknetBlockHash, StarknetBlockNumber, StarknetTransactionHash,
     };
+    use pathfinder_serde::policy::{Numeric, RpcV02};
     use pathfinder_serde::EthereumAddressAsHexStr;
     use serde::Serialize;
     use serde_with::serde_as;
-    use starknet_gateway_types::reply::transaction::{L1ToL2Message, L2ToL1Message};
+    use starknet_gateway_types::reply::transaction::{
+        ExecutionStatus, L1ToL2Message, L2ToL1Message,
+    };
 
     /// L2 transaction receipt as returned by the RPC API.
     #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
@@ -137,6 +164,11 @@ mod types {
         pub block_number: StarknetBlockNumber,
         pub messages_sent: Vec<MessageToL1>,
         pub events: Vec<Event>,
+        /// Only present for reverted transactions, which are not part of the specification.
+        #[serde(default, skip_serializing_if = "ExecutionStatus::is_succeeded")]
+        pub execution_status: ExecutionStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub revert_reason: Option<String>,
     }
 
     #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
@@ -195,6 +227,8 @@ mod types {
                     .map(MessageToL1::from)
                     .collect(),
                 events: receipt.events.into_iter().map(Event::from).collect(),
+                execution_status: receipt.execution_status,
+                revert_reason: receipt.revert_error,
             };
 
             use starknet_gateway_types::reply::transaction::Transaction::*;
@@ -248,6 +282,11 @@ mod types {
         pub actual_fee: Fee,
         pub messages_sent: Vec<MessageToL1>,
         pub events: Vec<Event>,
+        /// Only present for reverted transactions, which are not part of the specification.
+        #[serde(default, skip_serializing_if = "ExecutionStatus::is_succeeded")]
+        pub execution_status: ExecutionStatus,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub revert_reason: Option<String>,
     }
 
     #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
@@ -298,6 +337,8 @@ mod types {
                     .map(MessageToL1::from)
                     .collect(),
                 events: receipt.events.into_iter().map(Event::from).collect(),
+                execution_status: receipt.execution_status,
+                revert_reason: receipt.revert_error,
             };
 
             use starknet_gateway_types::reply::transaction::Transaction::*;
@@ -325,7 +366,7 @@ mod types {
     pub struct MessageToL1 {
         #[serde_as(as = "EthereumAddressAsHexStr")]
         pub to_address: EthereumAddress,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub payload: Vec<L2ToL1MessagePayloadElem>,
     }
 
@@ -346,7 +387,7 @@ mod types {
     pub struct MessageToL2 {
         #[serde_as(as = "EthereumAddressAsHexStr")]
         pub from_address: EthereumAddress,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub payload: Vec<L1ToL2MessagePayloadElem>,
     }
 
@@ -367,9 +408,9 @@ mod types {
     pub struct Event {
         #[serde_as(as = "RpcFelt251")]
         pub from_address: ContractAddress,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub keys: Vec<EventKey>,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub data: Vec<EventData>,
     }
 
@@ -435,6 +476,8 @@ mod types {
                             keys: vec![EventKey(felt!("0xe7"))],
                             data: vec![EventData(felt!("0xe8"))],
                         }],
+                        execution_status: ExecutionStatus::Succeeded,
+                        revert_reason: None,
                     }
                 }
             }
@@ -453,6 +496,8 @@ mod types {
                             keys: vec![EventKey(felt!("0xa7"))],
                             data: vec![EventData(felt!("0xa8"))],
                         }],
+                        execution_status: ExecutionStatus::Succeeded,
+                        revert_reason: None,
                     }
                 }
             }
@@ -535,6 +580,33 @@ mod types {
                 data
             );
         }
+
+        proptest::proptest! {
+            #[test]
+            fn receipt_round_trip(
+                receipt in starknet_gateway_types::test_utils::receipt(),
+                transaction in starknet_gateway_types::test_utils::transaction(),
+                status in proptest::sample::select(vec![
+                    BlockStatus::Pending,
+                    BlockStatus::AcceptedOnL2,
+                    BlockStatus::AcceptedOnL1,
+                    BlockStatus::Rejected,
+                ]),
+                block_hash in proptest::prelude::any::<StarknetBlockHash>(),
+                block_number in proptest::prelude::any::<StarknetBlockNumber>(),
+            ) {
+                let receipt = TransactionReceipt::with_block_data(
+                    receipt,
+                    status,
+                    block_hash,
+                    block_number,
+                    transaction,
+                );
+                let json = serde_json::to_value(&receipt).unwrap();
+                let decoded = serde_json::from_value::<TransactionReceipt>(json).unwrap();
+                proptest::prop_assert_eq!(decoded, receipt);
+            }
+        }
     }
 }
 
@@ -630,6 +702,8 @@ mod tests {
                             )),
                             keys: vec![EventKey(felt_bytes!(b"event 0 key"))],
                         }],
+                        execution_status: Default::default(),
+                        revert_reason: None,
                     }
                 }
             ))
@@ -669,6 +743,8 @@ mod tests {
                                 keys: vec![EventKey(felt_bytes!(b"pending key 2"))],
                             },
                         ],
+                        execution_status: Default::default(),
+                        revert_reason: None,
                     }
                 }
             ))
@@ -2,7 +2,7 @@
 
 pub(crate) mod class;
 pub use class::*;
-pub mod syncing;
+pub use starknet_gateway_types::sync_state::syncing;
 
 /// Groups all strictly input types of the RPC API.
 pub mod request {
@@ -10,7 +10,8 @@ pub mod request {
         CallParam, CasmHash, ClassHash, ContractAddress, ContractAddressSalt, EntryPoint, Fee,
         TransactionNonce, TransactionSignatureElem, TransactionVersion,
     };
-    use pathfinder_serde::{TransactionSignatureElemAsDecimalStr, TransactionVersionAsHexStr};
+    use pathfinder_serde::policy::{Numeric, RpcV02};
+    use pathfinder_serde::TransactionVersionAsHexStr;
     use serde::Deserialize;
     use serde_with::serde_as;
 
@@ -223,9 +224,8 @@ pub mod request {
         pub contract_address: ContractAddress,
         pub calldata: Vec<CallParam>,
         pub entry_point_selector: Option<EntryPoint>,
-        /// EstimateFee hurry: it doesn't make any sense to use decimal numbers for one field
         #[serde(default)]
-        #[serde_as(as = "Vec<TransactionSignatureElemAsDecimalStr>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub signature: Vec<TransactionSignatureElem>,
         /// EstimateFee hurry: max fee is needed if there's a signature
         #[serde(default = "call_default_max_fee")]
@@ -390,6 +390,7 @@ pub mod reply {
         EntryPoint, Fee, StarknetTransactionHash, TransactionNonce, TransactionSignatureElem,
         TransactionVersion,
     };
+    use pathfinder_serde::policy::{Numeric, RpcV02};
     use pathfinder_serde::TransactionVersionAsHexStr;
     use serde::{Deserialize, Serialize};
     use serde_with::serde_as;
@@ -441,7 +442,7 @@ pub mod reply {
         pub max_fee: Fee,
         #[serde_as(as = "TransactionVersionAsHexStr")]
         pub version: TransactionVersion,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub signature: Vec<TransactionSignatureElem>,
         #[serde_as(as = "RpcFelt")]
         pub nonce: TransactionNonce,
@@ -541,7 +542,7 @@ pub mod reply {
         // DEPLOY_ACCOUNT_TXN
         #[serde_as(as = "RpcFelt")]
         pub contract_address_salt: ContractAddressSalt,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub constructor_calldata: Vec<CallParam>,
         #[serde_as(as = "RpcFelt")]
         pub class_hash: ClassHash,
@@ -608,7 +609,7 @@ pub mod reply {
         pub contract_address: ContractAddress,
         #[serde_as(as = "RpcFelt")]
         pub entry_point_selector: EntryPoint,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub calldata: Vec<CallParam>,
     }
 
@@ -622,7 +623,7 @@ pub mod reply {
         // INVOKE_TXN_V1
         #[serde_as(as = "RpcFelt251")]
         pub sender_address: ContractAddress,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub calldata: Vec<CallParam>,
     }
 
@@ -637,7 +638,7 @@ pub mod reply {
         #[serde_as(as = "RpcFelt")]
         pub hash: StarknetTransactionHash,
         pub max_fee: Fee,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub signature: Vec<TransactionSignatureElem>,
         #[serde_as(as = "RpcFelt")]
         pub nonce: TransactionNonce,
@@ -659,7 +660,7 @@ pub mod reply {
         pub version: TransactionVersion,
         #[serde_as(as = "RpcFelt")]
         pub contract_address_salt: ContractAddressSalt,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub constructor_calldata: Vec<ConstructorParam>,
     }
 
@@ -681,7 +682,7 @@ pub mod reply {
         pub contract_address: ContractAddress,
         #[serde_as(as = "RpcFelt")]
         pub entry_point_selector: EntryPoint,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV02>>")]
         pub calldata: Vec<CallParam>,
     }
 
@@ -951,6 +952,18 @@ pub mod reply {
                     transactions
                 );
             }
+
+            proptest::proptest! {
+                #[test]
+                fn transaction_round_trip(
+                    transaction in starknet_gateway_types::test_utils::transaction()
+                ) {
+                    let transaction = Transaction::from(transaction);
+                    let json = serde_json::to_value(&transaction).unwrap();
+                    let decoded = serde_json::from_value::<Transaction>(json).unwrap();
+                    proptest::prop_assert_eq!(decoded, transaction);
+                }
+            }
         }
     }
 }
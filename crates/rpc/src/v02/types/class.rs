@@ -201,14 +201,24 @@ pub struct CairoContractClass {
 }
 
 impl CairoContractClass {
+    /// Limits the size of the decompressed program, so that a small compressed program cannot
+    /// exhaust our memory.
+    const MAX_PROGRAM_SIZE: u64 = 100 * 1024 * 1024;
+
     pub fn class_hash(&self) -> Result<ComputedClassHash, anyhow::Error> {
         // decode program
+        let compressed = base64::decode(&self.program).context("Decoding program")?;
         let mut decompressor =
-            flate2::read::GzDecoder::new(Cursor::new(base64::decode(&self.program).unwrap()));
+            flate2::read::GzDecoder::new(Cursor::new(compressed)).take(Self::MAX_PROGRAM_SIZE + 1);
         let mut program = Vec::new();
         decompressor
             .read_to_end(&mut program)
             .context("Decompressing program")?;
+        anyhow::ensure!(
+            program.len() as u64 <= Self::MAX_PROGRAM_SIZE,
+            "Decompressed program exceeds {} bytes",
+            Self::MAX_PROGRAM_SIZE
+        );
 
         let program: serde_json::Value =
             serde_json::from_slice(&program).context("Parsing program JSON")?;
@@ -509,5 +519,20 @@ mod tests {
             let class = ContractClass::from_definition_bytes(&contract_definition).unwrap();
             assert_eq!(class.class_hash().unwrap(), class_hash);
         }
+
+        #[test]
+        fn malformed_cairo_program_is_an_error() {
+            let contract_definition = zstd::decode_all(CONTRACT_DEFINITION).unwrap();
+            let mut class = ContractClass::from_definition_bytes(&contract_definition)
+                .unwrap()
+                .as_cairo()
+                .unwrap();
+
+            class.program = "not base64!".to_owned();
+            class.class_hash().unwrap_err();
+
+            class.program = base64::encode(b"not gzip");
+            class.class_hash().unwrap_err();
+        }
     }
 }
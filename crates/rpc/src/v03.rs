@@ -72,10 +72,27 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             "v0.3_starknet_simulateTransaction",
             method::simulate_transaction,
         )?
+        .register_method(
+            "v0.3_starknet_simulateTransactions",
+            method::simulate_transaction,
+        )?
+        .register_method(
+            "v0.3_starknet_traceBlockTransactions",
+            method::trace_block_transactions,
+        )?
+        .register_method("v0.3_starknet_traceTransaction", method::trace_transaction)?
         .register_method(
             "v0.3_pathfinder_getProof",
             crate::pathfinder::methods::get_proof,
         )?
+        .register_method(
+            "v0.3_pathfinder_getClassProof",
+            crate::pathfinder::methods::get_class_proof,
+        )?
+        .register_method(
+            "v0.3_pathfinder_verifyProof",
+            crate::pathfinder::methods::verify_proof,
+        )?
         .register_method(
             "v0.3_pathfinder_getTransactionStatus",
             crate::pathfinder::methods::get_transaction_status,
@@ -1,18 +1,24 @@
 mod estimate_fee;
-mod get_events;
+pub(crate) mod get_events;
 mod get_state_update;
 pub(crate) mod simulate_transaction;
+mod trace_block_transactions;
+mod trace_transaction;
 
 pub(super) use estimate_fee::estimate_fee;
 pub(super) use get_events::get_events;
+#[cfg(feature = "fuzzing")]
+pub(crate) use get_events::GetEventsInput;
 pub(super) use get_state_update::get_state_update;
 pub(crate) use simulate_transaction::simulate_transaction;
+pub(super) use trace_block_transactions::trace_block_transactions;
+pub(super) use trace_transaction::trace_transaction;
 
 pub(crate) mod common {
     use std::sync::Arc;
 
     use pathfinder_common::{BlockId, StarknetBlockTimestamp};
-    use starknet_gateway_types::{pending::PendingData, reply::PendingStateUpdate};
+    use starknet_gateway_types::{pending::PendingSource, reply::PendingStateUpdate};
 
     use crate::{
         cairo::ext_py::{BlockHashNumberOrLatest, GasPriceSource, Handle},
@@ -68,7 +74,7 @@ pub(crate) mod common {
     /// by [`crate::cairo::ext_py`] with the optional, latest pending data.
     pub async fn base_block_and_pending_for_call(
         at_block: BlockId,
-        pending_data: &Option<PendingData>,
+        pending_data: &Option<Arc<dyn PendingSource>>,
     ) -> Result<
         (
             BlockHashNumberOrLatest,
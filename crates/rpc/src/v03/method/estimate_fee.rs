@@ -17,7 +17,8 @@ crate::error::generate_rpc_error_subset!(
     ContractNotFound,
     ContractError,
     InvalidMessageSelector,
-    InvalidCallData
+    InvalidCallData,
+    StatePruned
 );
 
 impl From<crate::cairo::ext_py::CallFailure> for EstimateFeeError {
@@ -25,6 +26,7 @@ impl From<crate::cairo::ext_py::CallFailure> for EstimateFeeError {
         use crate::cairo::ext_py::CallFailure::*;
         match c {
             NoSuchBlock => Self::BlockNotFound,
+            StatePruned => Self::StatePruned,
             NoSuchContract => Self::ContractNotFound,
             InvalidEntryPoint => Self::InvalidMessageSelector,
             ExecutionFailed(e) => Self::Internal(anyhow::anyhow!("Internal error: {e}")),
@@ -10,10 +10,17 @@ pub struct GetStateUpdateInput {
 
 crate::error::generate_rpc_error_subset!(GetStateUpdateError: BlockNotFound);
 
+/// State updates of blocks requested by number which are not stored yet may be served by the
+/// [BlockPrefetch](crate::prefetch::BlockPrefetch).
 pub async fn get_state_update(
     context: RpcContext,
     input: GetStateUpdateInput,
 ) -> Result<types::StateUpdate, GetStateUpdateError> {
+    let prefetch = context.block_prefetch.clone().and_then(|prefetch| {
+        let number = prefetch.observe(input.block_id)?;
+        Some((prefetch, number))
+    });
+
     let block_id = match input.block_id {
         BlockId::Pending => {
             match &context
@@ -39,32 +46,39 @@ pub async fn get_state_update(
 
     let jh = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
-        let mut db = storage
-            .connection()
-            .context("Opening database connection")?;
-
-        let tx = db.transaction().context("Creating database transaction")?;
-
-        let block_hash = match block_id {
-            StarknetBlocksBlockId::Hash(h) => h,
-            StarknetBlocksBlockId::Number(_) | StarknetBlocksBlockId::Latest => {
-                StarknetBlocksTable::get_hash(
-                    &tx,
-                    block_id.try_into().expect("block_id is not a hash"),
-                )
-                .context("Read block from database")?
-                .ok_or(GetStateUpdateError::BlockNotFound)?
-            }
-        };
-
-        let state_update = StarknetStateUpdatesTable::get(&tx, block_hash)
-            .context("Read state update from database")?
-            .ok_or(GetStateUpdateError::BlockNotFound)?;
+        storage.read(|tx| {
+            let block_hash = match block_id {
+                StarknetBlocksBlockId::Hash(h) => h,
+                StarknetBlocksBlockId::Number(_) | StarknetBlocksBlockId::Latest => {
+                    match StarknetBlocksTable::get_hash(
+                        tx,
+                        block_id.try_into().expect("block_id is not a hash"),
+                    )
+                    .context("Read block from database")?
+                    {
+                        Some(hash) => hash,
+                        None => return Ok(None),
+                    }
+                }
+            };
 
-        Ok(state_update.into())
+            StarknetStateUpdatesTable::get(tx, block_hash)
+                .context("Read state update from database")
+        })
     });
 
-    jh.await.context("Database read panic or shutting down")?
+    match (
+        jh.await.context("Database read panic or shutting down")??,
+        prefetch,
+    ) {
+        (Some(update), _) => Ok(update.into()),
+        (None, Some((prefetch, number))) => prefetch
+            .state_update(number)
+            .await
+            .map(|update| update.as_ref().clone().into())
+            .ok_or(GetStateUpdateError::BlockNotFound),
+        (None, None) => Err(GetStateUpdateError::BlockNotFound),
+    }
 }
 
 mod types {
@@ -96,6 +110,17 @@ mod types {
         pub state_diff: StateDiff,
     }
 
+    impl From<starknet_gateway_types::reply::StateUpdate> for StateUpdate {
+        fn from(x: starknet_gateway_types::reply::StateUpdate) -> Self {
+            Self {
+                block_hash: Some(x.block_hash),
+                new_root: Some(x.new_root),
+                old_root: x.old_root,
+                state_diff: x.state_diff.into(),
+            }
+        }
+    }
+
     impl From<starknet_gateway_types::reply::PendingStateUpdate> for StateUpdate {
         fn from(x: starknet_gateway_types::reply::PendingStateUpdate) -> Self {
             Self {
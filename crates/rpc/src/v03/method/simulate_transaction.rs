@@ -17,11 +17,12 @@ use stark_hash::Felt;
 
 use super::common::prepare_handle_and_block;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[cfg_attr(any(test, feature = "rpc-full-serde"), derive(Serialize))]
 pub struct SimulateTrasactionInput {
-    block_id: BlockId,
-    transactions: Vec<BroadcastedTransaction>,
-    simulation_flags: dto::SimulationFlags,
+    pub(crate) block_id: BlockId,
+    pub(crate) transactions: Vec<BroadcastedTransaction>,
+    pub(crate) simulation_flags: dto::SimulationFlags,
 }
 
 #[derive(Debug, Serialize, Eq, PartialEq)]
@@ -30,13 +31,15 @@ pub struct SimulateTransactionOutput(pub Vec<dto::SimulatedTransaction>);
 crate::error::generate_rpc_error_subset!(
     SimulateTransactionError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    StatePruned
 );
 
 impl From<CallFailure> for SimulateTransactionError {
     fn from(value: CallFailure) -> Self {
         match value {
             CallFailure::NoSuchBlock => Self::BlockNotFound,
+            CallFailure::StatePruned => Self::StatePruned,
             CallFailure::NoSuchContract => Self::ContractNotFound,
             CallFailure::InvalidEntryPoint => Self::ContractError,
             CallFailure::ExecutionFailed(e) => Self::Internal(anyhow!("Execution failed: {e}")),
@@ -47,6 +50,12 @@ impl From<CallFailure> for SimulateTransactionError {
     }
 }
 
+/// Executes the transactions one after the other on top of the given block, returning the trace
+/// and fee estimate of each. Each transaction sees the state changes of the ones before it.
+///
+/// Served as both `starknet_simulateTransaction` and `starknet_simulateTransactions`, the name
+/// used by later versions of the specification. With `SKIP_VALIDATE`, the account's
+/// `__validate__` entry point is not called, so unsigned transactions can be simulated.
 pub async fn simulate_transaction(
     context: RpcContext,
     input: SimulateTrasactionInput,
@@ -92,7 +101,7 @@ fn map_fee(fee: FeeEstimate) -> reply::FeeEstimate {
     }
 }
 
-fn map_function_invocation(mut fi: FunctionInvocation) -> dto::FunctionInvocation {
+pub(crate) fn map_function_invocation(mut fi: FunctionInvocation) -> dto::FunctionInvocation {
     dto::FunctionInvocation {
         call_type: fi.call_type,
         caller_address: fi.caller_address,
@@ -113,9 +122,7 @@ fn map_function_invocation(mut fi: FunctionInvocation) -> dto::FunctionInvocatio
     }
 }
 
-fn map_trace(
-    mut trace: TransactionTrace,
-) -> Result<dto::TransactionTrace, SimulateTransactionError> {
+pub(crate) fn map_trace(mut trace: TransactionTrace) -> anyhow::Result<dto::TransactionTrace> {
     let invocations = (
         trace.validate_invocation.take(),
         trace.function_invocation.take(),
@@ -149,9 +156,7 @@ fn map_trace(
         (_, Some(fun), _) => Ok(dto::TransactionTrace::L1Handler(dto::L1HandlerTxnTrace {
             function_invocation: Some(map_function_invocation(fun)),
         })),
-        _ => Err(SimulateTransactionError::Internal(anyhow!(
-            "Unmatched transaction trace: '{trace:?}'"
-        ))),
+        _ => Err(anyhow!("Unmatched transaction trace: '{trace:?}'")),
     }
 }
 
@@ -163,6 +168,7 @@ pub mod dto {
     use crate::felt::RpcFelt251;
     use crate::v02::method::call::FunctionCall;
     use crate::v02::types::reply::FeeEstimate;
+    use pathfinder_serde::policy::{Numeric, RpcV03};
 
     use super::*;
 
@@ -179,7 +185,7 @@ pub mod dto {
 
     #[serde_with::serde_as]
     #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
-    pub struct Signature(#[serde_as(as = "Vec<RpcFelt>")] pub Vec<Felt>);
+    pub struct Signature(#[serde_as(as = "Vec<Numeric<RpcV03>>")] pub Vec<Felt>);
 
     #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
     pub enum CallType {
@@ -229,7 +235,7 @@ pub mod dto {
     #[serde_with::serde_as]
     #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
     pub struct MsgToL1 {
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV03>>")]
         pub payload: Vec<Felt>,
         #[serde_as(as = "RpcFelt")]
         pub to_address: Felt,
@@ -247,9 +253,9 @@ pub mod dto {
     #[serde_with::serde_as]
     #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
     pub struct EventContent {
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV03>>")]
         pub data: Vec<Felt>,
-        #[serde_as(as = "Vec<RpcFelt>")]
+        #[serde_as(as = "Vec<Numeric<RpcV03>>")]
         pub keys: Vec<Felt>,
     }
 
@@ -0,0 +1,327 @@
+use anyhow::Context;
+use pathfinder_common::{
+    BlockId, GasPrice, StarknetBlockHash, StarknetBlockNumber, StarknetBlockTimestamp,
+    StarknetTransactionHash,
+};
+use pathfinder_storage::{StarknetBlocksBlockId, StarknetBlocksTable, StarknetTransactionsTable};
+use serde::{Deserialize, Serialize};
+use starknet_gateway_client::ClientApi;
+use starknet_gateway_types::error::SequencerError;
+use starknet_gateway_types::reply::transaction::Transaction;
+
+use crate::cairo::ext_py::types::FunctionInvocation;
+use crate::cairo::ext_py::CallFailure;
+use crate::context::RpcContext;
+use crate::felt::RpcFelt;
+
+use super::simulate_transaction::{dto, map_function_invocation, map_trace};
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TraceBlockTransactionsInput {
+    block_hash: StarknetBlockHash,
+}
+
+#[serde_with::serde_as]
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Trace {
+    #[serde_as(as = "RpcFelt")]
+    transaction_hash: StarknetTransactionHash,
+    trace_root: dto::TransactionTrace,
+}
+
+crate::error::generate_rpc_error_subset!(
+    TraceBlockTransactionsError: BlockNotFound,
+    NoTraceAvailable
+);
+
+/// Returns the traces of all transactions of a block, replayed on top of its parent block.
+///
+/// Blocks containing declare, deploy or L1 handler transactions cannot be replayed, as skipping
+/// these would change the state seen by the transactions after them. Their traces are fetched
+/// from the feeder gateway instead, failing with `NoTraceAvailable` if it has none.
+pub async fn trace_block_transactions(
+    context: RpcContext,
+    input: TraceBlockTransactionsInput,
+) -> Result<Vec<Trace>, TraceBlockTransactionsError> {
+    let block = read_block(&context, input.block_hash)
+        .await?
+        .ok_or(TraceBlockTransactionsError::BlockNotFound)?;
+
+    let traces = match replay(&context, &block, block.transactions.len()).await? {
+        Some(traces) => traces,
+        None => gateway_traces(&context, input.block_hash, &block.transactions)
+            .await?
+            .ok_or(TraceBlockTransactionsError::NoTraceAvailable)?,
+    };
+
+    let traces = block
+        .transactions
+        .iter()
+        .zip(traces)
+        .map(|(transaction, trace_root)| Trace {
+            transaction_hash: transaction.hash(),
+            trace_root,
+        })
+        .collect();
+
+    Ok(traces)
+}
+
+/// A block, as needed to replay its transactions.
+pub(super) struct ReplayBlock {
+    /// [None] for the genesis block, which has no state to replay on.
+    parent_hash: Option<StarknetBlockHash>,
+    gas_price: GasPrice,
+    timestamp: StarknetBlockTimestamp,
+    pub transactions: Vec<Transaction>,
+}
+
+/// Reads the block with the given hash, or [None] if there is no such block.
+pub(super) async fn read_block(
+    context: &RpcContext,
+    block_hash: StarknetBlockHash,
+) -> anyhow::Result<Option<ReplayBlock>> {
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        storage.read(|tx| {
+            let block = match StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Hash(block_hash))
+                .context("Reading block")?
+            {
+                Some(block) => block,
+                None => return Ok(None),
+            };
+
+            let parent_hash = match block.number.get().checked_sub(1) {
+                Some(parent) => {
+                    let parent = StarknetBlockNumber::new_or_panic(parent);
+                    let parent_hash = StarknetBlocksTable::get_hash(tx, parent.into())
+                        .context("Reading parent block")?
+                        .with_context(|| format!("Parent block {parent} is missing"))?;
+                    Some(parent_hash)
+                }
+                None => None,
+            };
+
+            let transactions = StarknetTransactionsTable::get_transaction_data_for_block(
+                tx,
+                StarknetBlocksBlockId::Hash(block_hash),
+            )
+            .context("Reading transactions")?
+            .into_iter()
+            .map(|(transaction, _)| transaction)
+            .collect();
+
+            Ok(Some(ReplayBlock {
+                parent_hash,
+                gas_price: block.gas_price,
+                timestamp: block.timestamp,
+                transactions,
+            }))
+        })
+    });
+
+    jh.await.context("Database read panic or shutting down")?
+}
+
+/// Replays the first `count` transactions of `block`, returning their traces, or [None] if they
+/// cannot be replayed.
+pub(super) async fn replay(
+    context: &RpcContext,
+    block: &ReplayBlock,
+    count: usize,
+) -> anyhow::Result<Option<Vec<dto::TransactionTrace>>> {
+    let parent_hash = match block.parent_hash {
+        Some(parent_hash) => parent_hash,
+        None => return Ok(None),
+    };
+    let transactions = &block.transactions[..count];
+    let replayable = transactions.iter().all(|transaction| {
+        matches!(
+            transaction,
+            Transaction::Invoke(_) | Transaction::DeployAccount(_)
+        )
+    });
+    if !replayable {
+        return Ok(None);
+    }
+
+    let handle = context
+        .call_handle
+        .as_ref()
+        .context("Unsupported configuration")?;
+
+    let traces = handle
+        .trace_block(parent_hash, block.gas_price, block.timestamp, transactions)
+        .await
+        .map_err(|e| match e {
+            CallFailure::ExecutionFailed(e) => anyhow::anyhow!("Execution failed: {e}"),
+            other => anyhow::anyhow!("Replaying transactions failed: {other:?}"),
+        })?;
+
+    let traces = traces
+        .into_iter()
+        .map(map_trace)
+        .collect::<Result<_, _>>()?;
+
+    Ok(Some(traces))
+}
+
+#[derive(Deserialize)]
+struct GatewayBlockTraces {
+    traces: Vec<GatewayTrace>,
+}
+
+/// A transaction trace as reported by the feeder gateway.
+#[derive(Deserialize)]
+struct GatewayTrace {
+    transaction_hash: StarknetTransactionHash,
+    validate_invocation: Option<FunctionInvocation>,
+    function_invocation: Option<FunctionInvocation>,
+    fee_transfer_invocation: Option<FunctionInvocation>,
+}
+
+/// Fetches the traces of `transactions` of the block with the given hash from the feeder gateway,
+/// for transactions which cannot be replayed. Returns [None] if the gateway has no traces for
+/// them.
+pub(super) async fn gateway_traces(
+    context: &RpcContext,
+    block_hash: StarknetBlockHash,
+    transactions: &[Transaction],
+) -> anyhow::Result<Option<Vec<dto::TransactionTrace>>> {
+    let traces = match context
+        .sequencer
+        .block_traces(BlockId::Hash(block_hash))
+        .await
+    {
+        Ok(traces) => traces,
+        Err(SequencerError::StarknetError(_)) => return Ok(None),
+        Err(e) => return Err(e).context("Fetching block traces from the feeder gateway"),
+    };
+    let traces = serde_json::from_slice::<GatewayBlockTraces>(&traces)
+        .context("Parsing block traces of the feeder gateway")?;
+    let mut traces = traces
+        .traces
+        .into_iter()
+        .map(|trace| (trace.transaction_hash, trace))
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let traces = transactions
+        .iter()
+        .map(|transaction| {
+            traces
+                .remove(&transaction.hash())
+                .map(|trace| map_gateway_trace(transaction, trace))
+        })
+        .collect();
+
+    Ok(traces)
+}
+
+/// Maps a feeder gateway trace to the trace of the transaction's type.
+///
+/// Unlike [map_trace], this does not have to guess the type of the transaction from the
+/// invocations of its trace, which are the same for example for invoke v0 and L1 handler
+/// transactions.
+fn map_gateway_trace(transaction: &Transaction, trace: GatewayTrace) -> dto::TransactionTrace {
+    let validate_invocation = trace.validate_invocation.map(map_function_invocation);
+    let function_invocation = trace.function_invocation.map(map_function_invocation);
+    let fee_transfer_invocation = trace.fee_transfer_invocation.map(map_function_invocation);
+
+    match transaction {
+        Transaction::Invoke(_) => dto::TransactionTrace::Invoke(dto::InvokeTxnTrace {
+            validate_invocation,
+            execute_invocation: function_invocation,
+            fee_transfer_invocation,
+        }),
+        Transaction::Declare(_) => dto::TransactionTrace::Declare(dto::DeclareTxnTrace {
+            validate_invocation,
+            fee_transfer_invocation,
+        }),
+        // The specification has no trace of deploy transactions, which only run the constructor
+        // of the deployed contract like deploy account transactions do.
+        Transaction::DeployAccount(_) | Transaction::Deploy(_) => {
+            dto::TransactionTrace::DeployAccount(dto::DeployAccountTxnTrace {
+                validate_invocation,
+                constructor_invocation: function_invocation,
+                fee_transfer_invocation,
+            })
+        }
+        Transaction::L1Handler(_) => dto::TransactionTrace::L1Handler(dto::L1HandlerTxnTrace {
+            function_invocation,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt_bytes;
+    use starknet_gateway_client::test_utils::{MockGateway, ScriptedChain};
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = TraceBlockTransactionsInput {
+            block_hash: StarknetBlockHash(felt_bytes!(b"missing")),
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn genesis_has_no_trace() {
+        // The genesis block cannot be replayed, and the gateway has no traces for it either.
+        let gateway = MockGateway::spawn(ScriptedChain::default());
+        let context = RpcContext::for_tests().with_sequencer(gateway.client());
+        let input = TraceBlockTransactionsInput {
+            block_hash: StarknetBlockHash(felt_bytes!(b"genesis")),
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::NoTraceAvailable);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_gateway_traces() {
+        let block_hash = StarknetBlockHash(felt_bytes!(b"genesis"));
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"txn 0"));
+
+        let mut chain = ScriptedChain::default();
+        chain.add_block_traces(block_hash, gateway_traces_json(transaction_hash));
+        let gateway = MockGateway::spawn(chain);
+        let context = RpcContext::for_tests().with_sequencer(gateway.client());
+        let input = TraceBlockTransactionsInput { block_hash };
+
+        let traces = trace_block_transactions(context, input).await.unwrap();
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].transaction_hash, transaction_hash);
+        // The trace of an invoke v0 transaction, which has no validation.
+        assert_matches::assert_matches!(
+            &traces[0].trace_root,
+            dto::TransactionTrace::Invoke(dto::InvokeTxnTrace {
+                validate_invocation: None,
+                execute_invocation: Some(_),
+                fee_transfer_invocation: None,
+            })
+        );
+    }
+
+    /// Block traces of the feeder gateway, with the trace of an invoke v0 transaction.
+    fn gateway_traces_json(transaction_hash: StarknetTransactionHash) -> serde_json::Value {
+        serde_json::json!([{
+            "transaction_hash": transaction_hash,
+            "function_invocation": {
+                "calldata": [],
+                "contract_address": "0x1",
+                "selector": "0x2",
+                "entry_point_type": "EXTERNAL",
+            },
+            "signature": [],
+        }])
+    }
+}
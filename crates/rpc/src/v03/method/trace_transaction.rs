@@ -0,0 +1,125 @@
+use anyhow::Context;
+use pathfinder_common::StarknetTransactionHash;
+use pathfinder_storage::StarknetTransactionsTable;
+use serde::Deserialize;
+
+use crate::context::RpcContext;
+
+use super::simulate_transaction::dto;
+use super::trace_block_transactions::{gateway_traces, read_block, replay};
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TraceTransactionInput {
+    transaction_hash: StarknetTransactionHash,
+}
+
+crate::error::generate_rpc_error_subset!(
+    TraceTransactionError: TxnHashNotFound,
+    NoTraceAvailable
+);
+
+/// Returns the trace of a transaction, replayed on top of its parent block together with the
+/// transactions before it in its block.
+///
+/// If the transaction, or any transaction before it in its block, is a declare, deploy or L1
+/// handler transaction, the trace is fetched from the feeder gateway instead, failing with
+/// `NoTraceAvailable` if it has none.
+pub async fn trace_transaction(
+    context: RpcContext,
+    input: TraceTransactionInput,
+) -> Result<dto::TransactionTrace, TraceTransactionError> {
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        storage.read(|tx| {
+            let block_hash =
+                StarknetTransactionsTable::get_transaction_with_receipt(tx, input.transaction_hash)
+                    .context("Reading transaction")?
+                    .map(|(_, _, block_hash)| block_hash);
+            Ok(block_hash)
+        })
+    });
+    let block_hash = jh
+        .await
+        .context("Database read panic or shutting down")??
+        .ok_or(TraceTransactionError::TxnHashNotFound)?;
+
+    let block = read_block(&context, block_hash)
+        .await?
+        .context("Block of transaction is missing")?;
+    let index = block
+        .transactions
+        .iter()
+        .position(|transaction| transaction.hash() == input.transaction_hash)
+        .context("Transaction is missing from its block")?;
+
+    let transactions = &block.transactions[..index + 1];
+    let traces = match replay(&context, &block, transactions.len()).await? {
+        Some(traces) => Some(traces),
+        None => gateway_traces(&context, block_hash, &transactions[index..]).await?,
+    };
+    let trace = traces
+        .and_then(|mut traces| traces.pop())
+        .ok_or(TraceTransactionError::NoTraceAvailable)?;
+
+    Ok(trace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{felt_bytes, StarknetBlockHash};
+    use starknet_gateway_client::test_utils::{MockGateway, ScriptedChain};
+
+    #[tokio::test]
+    async fn transaction_not_found() {
+        let context = RpcContext::for_tests();
+        let input = TraceTransactionInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"missing")),
+        };
+
+        let error = trace_transaction(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceTransactionError::TxnHashNotFound);
+    }
+
+    #[tokio::test]
+    async fn genesis_transaction_has_no_trace() {
+        // The genesis block cannot be replayed, and the gateway has no traces for it either.
+        let gateway = MockGateway::spawn(ScriptedChain::default());
+        let context = RpcContext::for_tests().with_sequencer(gateway.client());
+        let input = TraceTransactionInput {
+            transaction_hash: StarknetTransactionHash(felt_bytes!(b"txn 0")),
+        };
+
+        let error = trace_transaction(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceTransactionError::NoTraceAvailable);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_gateway_trace() {
+        let transaction_hash = StarknetTransactionHash(felt_bytes!(b"txn 0"));
+
+        let mut chain = ScriptedChain::default();
+        chain.add_block_traces(
+            StarknetBlockHash(felt_bytes!(b"genesis")),
+            serde_json::json!([{
+                "transaction_hash": transaction_hash,
+                "function_invocation": {
+                    "calldata": [],
+                    "contract_address": "0x1",
+                    "selector": "0x2",
+                    "entry_point_type": "EXTERNAL",
+                },
+                "signature": [],
+            }]),
+        );
+        let gateway = MockGateway::spawn(chain);
+        let context = RpcContext::for_tests().with_sequencer(gateway.client());
+        let input = TraceTransactionInput { transaction_hash };
+
+        let trace = trace_transaction(context, input).await.unwrap();
+        assert_matches::assert_matches!(trace, dto::TransactionTrace::Invoke(_));
+    }
+}
@@ -35,15 +35,9 @@ pub async fn prefix_rpc_method_names_with_version(
     request: Request<Body>,
     max_request_body_size: u32,
 ) -> Result<Request<Body>, BoxError> {
-    let prefixes = match request.uri().path() {
-        // An empty path "" is treated the same as "/".
-        // However for a non-empty path adding a trailing slash
-        // makes it a different path from the original,
-        // that's why we have to account for those separately.
-        "/" | "/rpc/v0.2" | "/rpc/v0.2/" => &[("starknet_", "v0.2_"), ("pathfinder_", "v0.2_")][..],
-        "/rpc/v0.3" | "/rpc/v0.3/" => &[("starknet_", "v0.3_"), ("pathfinder_", "v0.3_")][..],
-        "/rpc/pathfinder/v0.1" | "/rpc/pathfinder/v0.1/" => &[("pathfinder_", "v0.1_")][..],
-        _ => {
+    let prefixes = match method_name_prefixes(request.uri().path()) {
+        Some(prefixes) => prefixes,
+        None => {
             return Err(BoxError::from(VersioningError::InvalidPath));
         }
     };
@@ -51,23 +45,66 @@ pub async fn prefix_rpc_method_names_with_version(
     // Retain the parts to then later recreate the request
     let (parts, body) = request.into_parts();
 
-    let (body, is_single) = match read_body(&parts.headers, body, max_request_body_size).await {
-        Ok(x) => x,
+    let (body, is_single) = read_request_body(&parts.headers, body, max_request_body_size).await?;
+
+    let body = match prefix_method_names(&body, is_single, prefixes) {
+        // Body was read and processed successfuly
+        Ok(Some(new_body)) => new_body,
+        // Body was read successfully but processing failed,
+        // pass the original payload to the inner service for proper error handling
+        Ok(None) => body,
+        // Reserialization failed
+        Err(_) => return Err(BoxError::from(VersioningError::Internal)),
+    };
+
+    let request: Request<Body> = Request::from_parts(parts, body.into());
+
+    Ok(request)
+}
+
+/// Reads the body of a request, and whether it is a single request rather than a batch.
+pub(crate) async fn read_request_body(
+    headers: &http::HeaderMap,
+    body: Body,
+    max_request_body_size: u32,
+) -> Result<(Vec<u8>, bool), VersioningError> {
+    match read_body(headers, body, max_request_body_size).await {
+        Ok(x) => Ok(x),
         Err(GenericTransportError::TooLarge) => {
-            return Err(BoxError::from(VersioningError::TooLarge(
-                max_request_body_size,
-            )))
-        }
-        Err(GenericTransportError::Malformed) => {
-            return Err(BoxError::from(VersioningError::Malformed))
+            Err(VersioningError::TooLarge(max_request_body_size))
         }
-        Err(GenericTransportError::Inner(_)) => {
-            return Err(BoxError::from(VersioningError::Internal))
+        Err(GenericTransportError::Malformed) => Err(VersioningError::Malformed),
+        Err(GenericTransportError::Inner(_)) => Err(VersioningError::Internal),
+    }
+}
+
+/// The method name prefixes for the RPC endpoint at `path`, in the form of `(old, new)`.
+pub(crate) fn method_name_prefixes(path: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    match path {
+        // An empty path "" is treated the same as "/".
+        // However for a non-empty path adding a trailing slash
+        // makes it a different path from the original,
+        // that's why we have to account for those separately.
+        "/" | "/rpc/v0.2" | "/rpc/v0.2/" => {
+            Some(&[("starknet_", "v0.2_"), ("pathfinder_", "v0.2_")])
         }
-    };
+        "/rpc/v0.3" | "/rpc/v0.3/" => Some(&[("starknet_", "v0.3_"), ("pathfinder_", "v0.3_")]),
+        "/rpc/pathfinder/v0.1" | "/rpc/pathfinder/v0.1/" => Some(&[("pathfinder_", "v0.1_")]),
+        _ => None,
+    }
+}
 
-    let new_body = if is_single {
-        match serde_json::from_slice::<jsonrpsee::types::Request<'_>>(&body) {
+/// Prefixes the method names of a single request or a batch of requests.
+///
+/// Returns [None] if the body is not a valid request, in which case it should be passed on
+/// unchanged so that jsonrpsee can respond with the proper error.
+pub(crate) fn prefix_method_names(
+    body: &[u8],
+    is_single: bool,
+    prefixes: &[(&str, &str)],
+) -> Result<Option<Vec<u8>>, serde_json::Error> {
+    if is_single {
+        match serde_json::from_slice::<jsonrpsee::types::Request<'_>>(body) {
             Ok(mut request) => {
                 prefix_method(&mut request, prefixes);
                 serde_json::to_vec(&request).map(Option::Some)
@@ -75,7 +112,7 @@ pub async fn prefix_rpc_method_names_with_version(
             Err(_) => Ok(None),
         }
     } else {
-        match serde_json::from_slice::<Vec<jsonrpsee::types::Request<'_>>>(&body) {
+        match serde_json::from_slice::<Vec<jsonrpsee::types::Request<'_>>>(body) {
             Ok(mut batch) => {
                 batch
                     .iter_mut()
@@ -84,21 +121,7 @@ pub async fn prefix_rpc_method_names_with_version(
             }
             Err(_) => Ok(None),
         }
-    };
-
-    let body = match new_body {
-        // Body was read and processed successfuly
-        Ok(Some(new_body)) => new_body,
-        // Body was read successfully but processing failed,
-        // pass the original payload to the inner service for proper error handling
-        Ok(None) => body,
-        // Reserialization failed
-        Err(_) => return Err(BoxError::from(VersioningError::Internal)),
-    };
-
-    let request: Request<Body> = Request::from_parts(parts, body.into());
-
-    Ok(request)
+    }
 }
 
 pub fn try_map_errors_to_responses(
@@ -106,10 +129,24 @@ pub fn try_map_errors_to_responses(
 ) -> Result<Response<Body>, BoxError> {
     match result {
         Ok(response) => Ok(response),
-        Err(error) => match error.downcast_ref::<VersioningError>() {
-            Some(error) => Ok(error.to_response()),
-            None => Err(error),
-        },
+        Err(error) => {
+            if let Some(error) = error.downcast_ref::<VersioningError>() {
+                return Ok(error.to_response());
+            }
+            if let Some(error) = error.downcast_ref::<crate::listener::Unproxied>() {
+                return Ok(error.to_response());
+            }
+            if let Some(error) = error.downcast_ref::<crate::ip_filter::ForbiddenIp>() {
+                return Ok(error.to_response());
+            }
+            if let Some(error) = error.downcast_ref::<crate::request_limits::LimitExceeded>() {
+                return Ok(error.to_response());
+            }
+            match error.downcast_ref::<crate::api_keys::ApiKeyError>() {
+                Some(error) => Ok(error.to_response()),
+                None => Err(error),
+            }
+        }
     }
 }
 
@@ -124,7 +161,7 @@ fn prefix_method(request: &mut jsonrpsee::types::Request<'_>, prefixes: &[(&str,
 }
 
 /// These responses are 1:1 to what jsonrpsee could have exported
-mod response {
+pub(crate) mod response {
     use jsonrpsee::types::ErrorObject;
 
     use super::*;
@@ -149,7 +186,10 @@ mod response {
         with_error(StatusCode::INTERNAL_SERVER_ERROR, ErrorCode::InternalError)
     }
 
-    fn with_error<'a>(code: StatusCode, error: impl Into<ErrorObject<'a>>) -> Response<Body> {
+    pub(crate) fn with_error<'a>(
+        code: StatusCode,
+        error: impl Into<ErrorObject<'a>>,
+    ) -> Response<Body> {
         let body = ErrorResponse::borrowed(error.into(), Id::Null);
         let body = serde_json::to_string(&body)
             .expect("error response is serializable")
@@ -162,7 +202,7 @@ mod response {
             .expect("response is properly formed")
     }
 
-    fn with_canonical_reason(code: StatusCode) -> Response<Body> {
+    pub(crate) fn with_canonical_reason(code: StatusCode) -> Response<Body> {
         Builder::new()
             .status(code)
             .header(CONTENT_TYPE, TEXT)
@@ -203,9 +243,31 @@ mod tests {
         "starknet_pendingTransactions",
         "starknet_syncing",
     ];
-    const COMMON_FOR_ALL: [&str; 2] = ["pathfinder_getProof", "pathfinder_getTransactionStatus"];
-    const V03_ONLY: [&str; 1] = ["starknet_simulateTransaction"];
-    const PATHFINDER_ONLY: [&str; 1] = ["pathfinder_version"];
+    const COMMON_FOR_ALL: [&str; 4] = [
+        "pathfinder_getProof",
+        "pathfinder_getClassProof",
+        "pathfinder_getTransactionStatus",
+        "pathfinder_verifyProof",
+    ];
+    const V03_ONLY: [&str; 4] = [
+        "starknet_simulateTransaction",
+        "starknet_simulateTransactions",
+        "starknet_traceBlockTransactions",
+        "starknet_traceTransaction",
+    ];
+    const PATHFINDER_ONLY: [&str; 11] = [
+        "pathfinder_version",
+        "pathfinder_syncStatus",
+        "pathfinder_getReorgHistory",
+        "pathfinder_getDatabaseStats",
+        "pathfinder_getNodeInfo",
+        "pathfinder_getRevertedTransactions",
+        "pathfinder_getTransactionByL1MessageHash",
+        "pathfinder_getContractStorageEntries",
+        "pathfinder_getTransactionInclusionProof",
+        "pathfinder_getReceiptsRange",
+        "pathfinder_getNonceHistory",
+    ];
 
     const V02_PATHS: &[&str] = &["", "/", "/rpc/v0.2", "/rpc/v0.2/"];
     const V03_PATHS: &[&str] = &["/rpc/v0.3", "/rpc/v0.3/"];
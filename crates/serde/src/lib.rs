@@ -2,65 +2,25 @@
 
 use ethers::types::{H160, H256};
 use num_bigint::BigUint;
-use pathfinder_common::{
-    CallParam, ConstructorParam, EthereumAddress, EventData, EventKey, GasPrice,
-    L1ToL2MessagePayloadElem, L2ToL1MessagePayloadElem, StarknetBlockNumber,
-    TransactionSignatureElem, TransactionVersion,
-};
+use pathfinder_common::{EthereumAddress, GasPrice, StarknetBlockNumber, TransactionVersion};
 use serde::de::Visitor;
-use serde_with::{serde_conv, DeserializeAs, SerializeAs};
+use serde_with::{DeserializeAs, SerializeAs};
 use stark_hash::{Felt, HexParseError, OverflowError};
 use std::borrow::Cow;
 use std::str::FromStr;
 
-serde_conv!(
-    pub CallParamAsDecimalStr,
-    CallParam,
-    |serialize_me: &CallParam| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(CallParam)
-);
-
-serde_conv!(
-    pub ConstructorParamAsDecimalStr,
-    ConstructorParam,
-    |serialize_me: &ConstructorParam| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(ConstructorParam)
-);
+pub mod policy;
 
-serde_conv!(
-    pub EventDataAsDecimalStr,
-    EventData,
-    |serialize_me: &EventData| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(EventData)
-);
-
-serde_conv!(
-    pub EventKeyAsDecimalStr,
-    EventKey,
-    |serialize_me: &EventKey| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(EventKey)
-);
+use policy::{Gateway, Numeric};
 
-serde_conv!(
-    pub L1ToL2MessagePayloadElemAsDecimalStr,
-    L1ToL2MessagePayloadElem,
-    |serialize_me: &L1ToL2MessagePayloadElem| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(L1ToL2MessagePayloadElem)
-);
-
-serde_conv!(
-    pub L2ToL1MessagePayloadElemAsDecimalStr,
-    L2ToL1MessagePayloadElem,
-    |serialize_me: &L2ToL1MessagePayloadElem| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(L2ToL1MessagePayloadElem)
-);
-
-serde_conv!(
-    pub TransactionSignatureElemAsDecimalStr,
-    TransactionSignatureElem,
-    |serialize_me: &TransactionSignatureElem| starkhash_to_dec_str(&serialize_me.0),
-    |s: &str| starkhash_from_dec_str(s).map(TransactionSignatureElem)
-);
+// The sequencer API encodings of felts, kept for the schema migrations which use them.
+pub type CallParamAsDecimalStr = Numeric<Gateway>;
+pub type ConstructorParamAsDecimalStr = Numeric<Gateway>;
+pub type EventDataAsDecimalStr = Numeric<Gateway>;
+pub type EventKeyAsDecimalStr = Numeric<Gateway>;
+pub type L1ToL2MessagePayloadElemAsDecimalStr = Numeric<Gateway>;
+pub type L2ToL1MessagePayloadElemAsDecimalStr = Numeric<Gateway>;
+pub type TransactionSignatureElemAsDecimalStr = Numeric<Gateway>;
 
 pub struct EthereumAddressAsHexStr;
 
@@ -0,0 +1,187 @@
+//! Encodings of the numeric fields whose representation differs between API surfaces.
+//!
+//! The sequencer API encodes felts such as calldata, signatures and event data as decimal strings,
+//! while the JSON-RPC specifications encode them as hex strings. Rather than each field type having
+//! a serializer per encoding, fields are annotated with the surface they belong to and the surface
+//! decides the encoding:
+//!
+//! ```ignore
+//! #[serde_as(as = "Vec<Numeric<Gateway>>")]
+//! pub calldata: Vec<CallParam>,
+//! ```
+use std::marker::PhantomData;
+
+use pathfinder_common::{
+    CallParam, ConstructorParam, EventData, EventKey, L1ToL2MessagePayloadElem,
+    L2ToL1MessagePayloadElem, TransactionSignatureElem,
+};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serializer};
+use serde_with::{DeserializeAs, SerializeAs};
+use stark_hash::Felt;
+
+use crate::{starkhash_from_dec_str, starkhash_to_dec_str};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// A "0x" prefixed hex string, whose prefix is required when deserializing.
+    Hex,
+    /// A decimal string. Hex strings are accepted as well when deserializing.
+    Decimal,
+}
+
+/// An API surface, which decides the encoding of the fields annotated with [Numeric].
+pub trait Surface {
+    const ENCODING: Encoding;
+}
+
+/// The sequencer (feeder) gateway API.
+pub struct Gateway;
+
+/// The v0.2 JSON-RPC specification.
+pub struct RpcV02;
+
+/// The v0.3 JSON-RPC specification.
+pub struct RpcV03;
+
+impl Surface for Gateway {
+    const ENCODING: Encoding = Encoding::Decimal;
+}
+
+impl Surface for RpcV02 {
+    const ENCODING: Encoding = Encoding::Hex;
+}
+
+impl Surface for RpcV03 {
+    const ENCODING: Encoding = Encoding::Hex;
+}
+
+/// A numeric type which is encoded according to the [Surface] it is annotated with.
+pub trait Number: Sized {
+    fn to_felt(&self) -> Felt;
+    fn from_felt(felt: Felt) -> Self;
+}
+
+impl Number for Felt {
+    fn to_felt(&self) -> Felt {
+        *self
+    }
+
+    fn from_felt(felt: Felt) -> Self {
+        felt
+    }
+}
+
+macro_rules! felt_number {
+    ($($target:ident),+ $(,)?) => {
+        $(
+            impl Number for $target {
+                fn to_felt(&self) -> Felt {
+                    self.0
+                }
+
+                fn from_felt(felt: Felt) -> Self {
+                    Self(felt)
+                }
+            }
+        )+
+    };
+}
+
+felt_number!(
+    CallParam,
+    ConstructorParam,
+    EventData,
+    EventKey,
+    L1ToL2MessagePayloadElem,
+    L2ToL1MessagePayloadElem,
+    TransactionSignatureElem,
+);
+
+/// Serializes a [Number] in the [Encoding] of the surface `S`.
+pub struct Numeric<S>(PhantomData<S>);
+
+impl<S: Surface, T: Number> SerializeAs<T> for Numeric<S> {
+    fn serialize_as<Ser>(source: &T, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Ser: Serializer,
+    {
+        let felt = source.to_felt();
+        match S::ENCODING {
+            Encoding::Hex => serializer.serialize_str(&felt.to_hex_str()),
+            Encoding::Decimal => serializer.serialize_str(&starkhash_to_dec_str(&felt)),
+        }
+    }
+}
+
+impl<'de, S: Surface, T: Number> DeserializeAs<'de, T> for Numeric<S> {
+    fn deserialize_as<D>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        let felt = match S::ENCODING {
+            Encoding::Hex if !s.starts_with("0x") => {
+                return Err(D::Error::custom("Missing '0x' prefix"))
+            }
+            Encoding::Hex => Felt::from_hex_str(&s).map_err(D::Error::custom)?,
+            Encoding::Decimal => starkhash_from_dec_str(&s).map_err(D::Error::custom)?,
+        };
+        Ok(T::from_felt(felt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+    use serde::Serialize;
+    use serde_with::serde_as;
+
+    #[serde_as]
+    #[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+    struct GatewayCall {
+        #[serde_as(as = "Vec<Numeric<Gateway>>")]
+        calldata: Vec<CallParam>,
+    }
+
+    #[serde_as]
+    #[derive(Debug, Deserialize, PartialEq, Eq, Serialize)]
+    struct RpcCall {
+        #[serde_as(as = "Vec<Numeric<RpcV03>>")]
+        calldata: Vec<CallParam>,
+    }
+
+    #[test]
+    fn surfaces() {
+        let calldata = vec![CallParam(felt!("0x1f")), CallParam(Felt::ZERO)];
+
+        let gateway = GatewayCall {
+            calldata: calldata.clone(),
+        };
+        let json = serde_json::to_value(&gateway).unwrap();
+        assert_eq!(json, serde_json::json!({"calldata": ["31", "0"]}));
+        assert_eq!(
+            serde_json::from_value::<GatewayCall>(json).unwrap(),
+            gateway
+        );
+
+        let rpc = RpcCall { calldata };
+        let json = serde_json::to_value(&rpc).unwrap();
+        assert_eq!(json, serde_json::json!({"calldata": ["0x1f", "0x0"]}));
+        assert_eq!(serde_json::from_value::<RpcCall>(json).unwrap(), rpc);
+    }
+
+    #[test]
+    fn hex_requires_prefix() {
+        let json = serde_json::json!({"calldata": ["1f"]});
+        serde_json::from_value::<RpcCall>(json).unwrap_err();
+    }
+
+    #[test]
+    fn decimal_accepts_hex() {
+        let json = serde_json::json!({"calldata": ["0x1f"]});
+        let gateway = serde_json::from_value::<GatewayCall>(json).unwrap();
+        assert_eq!(gateway.calldata, vec![CallParam(felt!("0x1f"))]);
+    }
+}
@@ -70,7 +70,7 @@ impl ContractCodeTable {
     }
 
     pub fn get_class(
-        transaction: &Transaction<'_>,
+        transaction: &impl crate::ReadAccess,
         hash: ClassHash,
     ) -> anyhow::Result<Option<ContractClass>> {
         let row = transaction
@@ -82,16 +82,17 @@ impl ContractCodeTable {
                     ":hash": &hash.0.to_be_bytes()
                 },
                 |row| {
-                    let definition: Vec<u8> = row.get("definition")?;
+                    let definition: Option<Vec<u8>> = row.get("definition")?;
 
                     Ok(definition)
                 },
             )
             .optional()?;
 
+        // Deferred classes have no definition yet.
         let definition = match row {
-            None => return Ok(None),
-            Some(definition) => definition,
+            Some(Some(definition)) => definition,
+            _ => return Ok(None),
         };
 
         let definition = zstd::decode_all(&*definition)
@@ -115,6 +116,30 @@ impl ContractCodeTable {
         }))
     }
 
+    /// Returns the decompressed definition of the class, as served by the gateway.
+    pub fn get_definition(
+        connection: &Connection,
+        hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let definition: Option<Option<Vec<u8>>> = connection
+            .query_row(
+                "SELECT definition FROM class_definitions WHERE hash = ?",
+                [&hash.0.to_be_bytes()[..]],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        // Deferred classes have no definition yet.
+        match definition.flatten() {
+            Some(definition) => {
+                let definition = zstd::decode_all(&*definition)
+                    .context("Corruption: invalid compressed column (definition)")?;
+                Ok(Some(definition))
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Returns true for each [ClassHash] if the class definition already exists in the table.
     pub fn exists(connection: &Connection, classes: &[ClassHash]) -> anyhow::Result<Vec<bool>> {
         let mut stmt = connection.prepare("SELECT 1 FROM class_definitions WHERE hash = ?")?;
@@ -214,6 +239,195 @@ impl CasmClassTable {
             .map(|hash| stmt.exists([&hash.0.to_be_bytes()[..]]))
             .collect::<Result<Vec<_>, _>>()?)
     }
+
+    /// Returns the compiled class hash of the CASM class, if it is stored.
+    pub fn get_compiled_class_hash(
+        connection: &Connection,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<CasmHash>> {
+        connection
+            .query_row(
+                "SELECT compiled_class_hash FROM casm_definitions WHERE hash = ?",
+                [class_hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying for compiled class hash")
+    }
+
+    /// Returns the decompressed CASM definition of the Sierra class, if it is stored.
+    pub fn get_definition(
+        connection: &Connection,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let definition: Option<Vec<u8>> = connection
+            .query_row(
+                "SELECT definition FROM casm_definitions WHERE hash = ?",
+                [class_hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying for CASM definition")?;
+
+        definition
+            .map(|definition| {
+                zstd::decode_all(&*definition)
+                    .context("Corruption: invalid compressed column (definition)")
+            })
+            .transpose()
+    }
+}
+
+/// Records the Sierra classes which could not be compiled to CASM.
+///
+/// These classes are stored in [CasmClassTable] with an empty definition.
+pub struct CasmCompilationFailuresTable {}
+
+impl CasmCompilationFailuresTable {
+    /// Records the compilation error of a class, replacing any earlier one.
+    pub fn upsert(
+        connection: &Connection,
+        class_hash: ClassHash,
+        casm_compiler_version: &str,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        let version_id = CasmCompilerVersions::intern(connection, casm_compiler_version)
+            .context("Fetching CASM compiler version id")?;
+
+        connection.execute(
+            r"INSERT OR REPLACE INTO casm_compilation_failures
+                (hash, compiler_version_id, error)
+            VALUES
+                (:hash, :compiler_version_id, :error)",
+            named_params! {
+                ":hash": class_hash,
+                ":compiler_version_id": version_id,
+                ":error": error,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Returns the compilation error of the class, if its compilation failed.
+    pub fn get(connection: &Connection, class_hash: ClassHash) -> anyhow::Result<Option<String>> {
+        connection
+            .query_row(
+                "SELECT error FROM casm_compilation_failures WHERE hash = ?",
+                [class_hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying for CASM compilation failure")
+    }
+
+    /// Removes the compilation error of a class, once its CASM definition has been provided.
+    pub fn remove(connection: &Connection, class_hash: ClassHash) -> anyhow::Result<()> {
+        connection.execute(
+            "DELETE FROM casm_compilation_failures WHERE hash = ?",
+            [class_hash],
+        )?;
+        Ok(())
+    }
+}
+
+/// Tracks the classes whose definitions have not been downloaded yet.
+///
+/// Each such class has a placeholder in [ContractCodeTable] without a definition, so that it
+/// can be marked as declared like any other class.
+pub struct DeferredClassesTable {}
+
+impl DeferredClassesTable {
+    /// Inserts placeholders for the classes, deferring the download of their definitions.
+    ///
+    /// Does nothing for classes which already exist.
+    pub fn insert(connection: &Connection, classes: &[ClassHash]) -> anyhow::Result<()> {
+        let mut placeholder = connection.prepare_cached(
+            "INSERT INTO class_definitions (hash) VALUES (?) ON CONFLICT DO NOTHING",
+        )?;
+        let mut deferred =
+            connection.prepare_cached("INSERT INTO deferred_classes (hash) VALUES (?)")?;
+
+        for class in classes {
+            if placeholder.execute([class])? == 1 {
+                deferred.execute([class])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the compiled class hash of a deferred Sierra class, which is only known once the
+    /// class has been declared.
+    pub fn set_compiled_class_hash(
+        connection: &Connection,
+        class: ClassHash,
+        compiled_class_hash: &CasmHash,
+    ) -> anyhow::Result<()> {
+        connection.execute(
+            "UPDATE deferred_classes SET compiled_class_hash = ? WHERE hash = ?",
+            rusqlite::params![compiled_class_hash, class],
+        )?;
+        Ok(())
+    }
+
+    /// Returns `Some` if the class is deferred, containing its compiled class hash if known.
+    pub fn get(
+        connection: &Connection,
+        class: ClassHash,
+    ) -> anyhow::Result<Option<Option<CasmHash>>> {
+        connection
+            .query_row(
+                "SELECT compiled_class_hash FROM deferred_classes WHERE hash = ?",
+                [class],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying for deferred class")
+    }
+
+    /// Returns up to `limit` deferred classes which have been declared, along with their compiled
+    /// class hash if they are Sierra classes.
+    pub fn next_declared(
+        connection: &Connection,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(ClassHash, Option<CasmHash>)>> {
+        let mut stmt = connection.prepare(
+            r"SELECT deferred.hash, deferred.compiled_class_hash FROM deferred_classes deferred
+            JOIN class_definitions classes ON (deferred.hash = classes.hash)
+            WHERE classes.declared_on IS NOT NULL
+            LIMIT ?",
+        )?;
+
+        let rows = stmt
+            .query_map([limit], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Querying for declared deferred classes")?;
+
+        Ok(rows)
+    }
+
+    /// Stores the definition of a deferred class in place of its placeholder.
+    pub fn resolve(connection: &Connection, contract: &CompressedContract) -> anyhow::Result<()> {
+        connection.execute(
+            "UPDATE class_definitions SET definition = ? WHERE hash = ? AND definition IS NULL",
+            rusqlite::params![&contract.definition[..], contract.hash],
+        )?;
+        connection.execute(
+            "DELETE FROM deferred_classes WHERE hash = ?",
+            [contract.hash],
+        )?;
+        Ok(())
+    }
+
+    /// Removes the placeholder of a deferred class, which turned out not to be declared.
+    pub fn remove(connection: &Connection, class: ClassHash) -> anyhow::Result<()> {
+        connection.execute("DELETE FROM deferred_classes WHERE hash = ?", [class])?;
+        connection.execute(
+            "DELETE FROM class_definitions WHERE hash = ? AND definition IS NULL",
+            [class],
+        )?;
+        Ok(())
+    }
 }
 
 /// Stores class commitment table leaf hash to data mapping.
@@ -245,6 +459,21 @@ impl ClassCommitmentLeavesTable {
 
         Ok(())
     }
+
+    /// Returns the compiled class hash the leaf hash was calculated from, if it exists.
+    pub fn get_compiled_class_hash(
+        transaction: &impl crate::ReadAccess,
+        hash: &ClassCommitmentLeafHash,
+    ) -> anyhow::Result<Option<CasmHash>> {
+        transaction
+            .query_row(
+                "SELECT compiled_class_hash FROM class_commitment_leaves WHERE hash = ?",
+                [hash],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying for class commitment leaf")
+    }
 }
 
 #[cfg(test)]
@@ -278,6 +507,101 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compilation_failures() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+
+        let hash = ClassHash(felt!("0x123"));
+        assert_eq!(
+            CasmCompilationFailuresTable::get(&connection, hash).unwrap(),
+            None
+        );
+
+        CasmCompilationFailuresTable::upsert(&connection, hash, "v1", "first").unwrap();
+        CasmCompilationFailuresTable::upsert(&connection, hash, "v2", "second").unwrap();
+
+        assert_eq!(
+            CasmCompilationFailuresTable::get(&connection, hash).unwrap(),
+            Some("second".to_owned())
+        );
+
+        CasmCompilationFailuresTable::remove(&connection, hash).unwrap();
+        assert_eq!(
+            CasmCompilationFailuresTable::get(&connection, hash).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn deferred_classes() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let transaction = connection.transaction().unwrap();
+
+        let (existing, _, _) = setup_class(&transaction);
+        let cairo = ClassHash(felt!("0x456"));
+        let sierra = ClassHash(felt!("0x789"));
+        let compiled_class_hash = CasmHash(felt!("0xabc"));
+
+        DeferredClassesTable::insert(&transaction, &[existing, cairo, sierra]).unwrap();
+        assert_eq!(
+            ContractCodeTable::exists(&transaction, &[cairo, sierra]).unwrap(),
+            vec![true, true]
+        );
+        assert_eq!(
+            DeferredClassesTable::get(&transaction, existing).unwrap(),
+            None
+        );
+        assert_eq!(
+            ContractCodeTable::get_class(&transaction, cairo).unwrap(),
+            None
+        );
+
+        // Only declared classes are ready to be downloaded.
+        assert_eq!(
+            DeferredClassesTable::next_declared(&transaction, 10).unwrap(),
+            vec![]
+        );
+
+        let block = StarknetBlockHash(felt!("0x1"));
+        ContractCodeTable::update_declared_on_if_null(&transaction, cairo, block).unwrap();
+        ContractCodeTable::update_declared_on_if_null(&transaction, sierra, block).unwrap();
+        DeferredClassesTable::set_compiled_class_hash(&transaction, sierra, &compiled_class_hash)
+            .unwrap();
+
+        let mut next = DeferredClassesTable::next_declared(&transaction, 10).unwrap();
+        next.sort_by_key(|(hash, _)| hash.0);
+        assert_eq!(
+            next,
+            vec![(cairo, None), (sierra, Some(compiled_class_hash))]
+        );
+
+        let definition = zstd::bulk::compress(b"{}", 10).unwrap();
+        DeferredClassesTable::resolve(
+            &transaction,
+            &CompressedContract {
+                definition,
+                hash: cairo,
+            },
+        )
+        .unwrap();
+        DeferredClassesTable::remove(&transaction, sierra).unwrap();
+
+        assert_eq!(
+            DeferredClassesTable::get(&transaction, cairo).unwrap(),
+            None
+        );
+        assert_eq!(
+            ContractCodeTable::exists(&transaction, &[cairo, sierra]).unwrap(),
+            vec![true, false]
+        );
+        assert_eq!(
+            DeferredClassesTable::next_declared(&transaction, 10).unwrap(),
+            vec![]
+        );
+    }
+
     fn setup_class(transaction: &Transaction<'_>) -> (ClassHash, &'static [u8], serde_json::Value) {
         let hash = ClassHash(felt!("0x123"));
 
@@ -0,0 +1,321 @@
+//! Per-block bloom filters over the emitting addresses and keys of events.
+//!
+//! Event queries restricted to an address or keys consult the filters of the blocks in their range
+//! first, so that blocks which can't contain a matching event are never scanned. Blocks without
+//! events have no filter.
+use anyhow::Context;
+use pathfinder_common::{ContractAddress, EventKey, StarknetBlockNumber};
+use rusqlite::{named_params, Transaction};
+use sha3::{Digest, Keccak256};
+use stark_hash::Felt;
+
+/// Stores the [EventBloom] of each block with events.
+pub(crate) struct EventBloomTable {}
+
+/// The blocks whose filters may match a query, see [EventBloomTable::candidate_blocks].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CandidateBlocks {
+    pub blocks: Vec<StarknetBlockNumber>,
+    /// Set if the range had more filters than are scanned at once, in which case the blocks after
+    /// this one were not ruled out.
+    pub unscanned_after: Option<StarknetBlockNumber>,
+}
+
+impl CandidateBlocks {
+    /// Returns true if no block in range can have a matching event.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty() && self.unscanned_after.is_none()
+    }
+}
+
+impl EventBloomTable {
+    pub fn upsert(
+        tx: &Transaction<'_>,
+        block_number: StarknetBlockNumber,
+        bloom: &EventBloom,
+    ) -> anyhow::Result<()> {
+        tx.execute(
+            "INSERT OR REPLACE INTO starknet_events_filters (block_number, bloom) VALUES (:block_number, :bloom)",
+            named_params![
+                ":block_number": block_number,
+                ":bloom": &bloom.to_compressed_bytes()?,
+            ],
+        )
+        .context("Inserting event bloom filter")?;
+
+        Ok(())
+    }
+
+    /// Rebuilds the filters of all blocks from their events.
+    pub fn rebuild(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute("DELETE FROM starknet_events_filters", [])
+            .context("Deleting event bloom filters")?;
+
+        let mut query = tx
+        .prepare(
+            "SELECT block_number, from_address, keys FROM starknet_events ORDER BY block_number",
+        )
+        .context("Preparing events query")?;
+        let mut rows = query.query([]).context("Querying events")?;
+
+        let mut current: Option<(StarknetBlockNumber, EventBloom)> = None;
+        while let Some(row) = rows.next().context("Fetching event")? {
+            let block_number: StarknetBlockNumber = row.get_unwrap("block_number");
+            let from_address: ContractAddress = row.get_unwrap("from_address");
+            let keys = row.get_ref_unwrap("keys").as_str()?;
+
+            if !matches!(&current, Some((number, _)) if *number == block_number) {
+                if let Some((number, bloom)) = current.take() {
+                    EventBloomTable::upsert(tx, number, &bloom)?;
+                }
+                current = Some((block_number, EventBloom::new()));
+            }
+            let bloom = &mut current.as_mut().expect("Set above").1;
+
+            bloom.set_address(&from_address);
+            let mut buffer = [0u8; 32];
+            for key in keys.split(' ').filter(|key| !key.is_empty()) {
+                let used = base64::decode_config_slice(key, base64::STANDARD, &mut buffer)
+                    .context("Decoding event key")?;
+                let key = Felt::from_be_slice(&buffer[..used]).context("Parsing event key")?;
+                bloom.set_key(&EventKey(key));
+            }
+        }
+
+        if let Some((number, bloom)) = current {
+            EventBloomTable::upsert(tx, number, &bloom)?;
+        }
+
+        Ok(())
+    }
+
+    /// Filters scanned by a single query, from the start of its range.
+    ///
+    /// Every filter is read and decompressed, which was measured at about 1s for a range of 200k
+    /// blocks with events, versus 20ms for 4096 of them. The blocks also end up in an `IN` list of
+    /// the events query, which takes 6ms to count the events of 4096 blocks and 100ms for 100k.
+    /// Pages of events are returned in block order, so the start of the range matters most.
+    pub const MAX_SCANNED_FILTERS: usize = 4096;
+
+    /// Returns the blocks in the (inclusive) range whose events may include some emitted by
+    /// `contract_address`, with one of the keys of each of the `key_groups`.
+    ///
+    /// At most [MAX_SCANNED_FILTERS](Self::MAX_SCANNED_FILTERS) filters are scanned, and the blocks
+    /// after them are left as they are.
+    ///
+    /// Returns [None] if neither restricts the events, as then every block with events matches.
+    pub fn candidate_blocks(
+        tx: &impl crate::ReadAccess,
+        from_block: Option<StarknetBlockNumber>,
+        to_block: Option<StarknetBlockNumber>,
+        contract_address: Option<&ContractAddress>,
+        key_groups: &[&[EventKey]],
+    ) -> anyhow::Result<Option<CandidateBlocks>> {
+        if contract_address.is_none() && key_groups.is_empty() {
+            return Ok(None);
+        }
+
+        let mut stmt = tx
+            .prepare_cached(
+                "SELECT block_number, bloom FROM starknet_events_filters
+                WHERE block_number BETWEEN :from_block AND :to_block
+                ORDER BY block_number LIMIT :limit",
+            )
+            .context("Preparing event bloom filter query")?;
+        let mut rows = stmt
+            .query(named_params![
+                ":from_block": from_block.unwrap_or(StarknetBlockNumber::GENESIS),
+                ":to_block": to_block.unwrap_or(StarknetBlockNumber::MAX),
+                ":limit": Self::MAX_SCANNED_FILTERS,
+            ])
+            .context("Querying event bloom filters")?;
+
+        let mut candidates = Vec::new();
+        let mut scanned = 0;
+        let mut last_scanned = None;
+        while let Some(row) = rows.next().context("Fetching event bloom filter")? {
+            let block_number = row.get_unwrap("block_number");
+            scanned += 1;
+            last_scanned = Some(block_number);
+
+            let bloom = row.get_ref_unwrap("bloom").as_blob()?;
+            let bloom = EventBloom::from_compressed_bytes(bloom)?;
+
+            let address_matches = contract_address.map_or(true, |a| bloom.check_address(a));
+            let keys_match = key_groups
+                .iter()
+                .all(|keys| keys.iter().any(|key| bloom.check_key(key)));
+
+            if address_matches && keys_match {
+                candidates.push(block_number);
+            }
+        }
+
+        Ok(Some(CandidateBlocks {
+            blocks: candidates,
+            unscanned_after: last_scanned.filter(|_| scanned == Self::MAX_SCANNED_FILTERS),
+        }))
+    }
+}
+
+/// A bloom filter over the emitting addresses and keys of a block's events.
+///
+/// Keys are added regardless of their position in the event, so the filter answers whether any
+/// event of the block has a key, not whether one has it at a particular position.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct EventBloom(Vec<u8>);
+
+impl EventBloom {
+    /// Number of bits in a filter, a power of two.
+    const BITS: usize = 16384;
+    /// Number of bits set for each item.
+    const HASHES: usize = 3;
+
+    const ADDRESS_TAG: u8 = 0;
+    const KEY_TAG: u8 = 1;
+
+    pub fn new() -> Self {
+        Self(vec![0; Self::BITS / 8])
+    }
+
+    pub fn from_compressed_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let bytes = zstd::bulk::decompress(bytes, Self::BITS / 8)
+            .context("Decompressing event bloom filter")?;
+        anyhow::ensure!(
+            bytes.len() == Self::BITS / 8,
+            "Event bloom filter has {} bytes",
+            bytes.len()
+        );
+        Ok(Self(bytes))
+    }
+
+    pub fn to_compressed_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        zstd::bulk::compress(&self.0, 10).context("Compressing event bloom filter")
+    }
+
+    pub fn set_address(&mut self, address: &ContractAddress) {
+        self.set(Self::ADDRESS_TAG, address.get())
+    }
+
+    pub fn set_key(&mut self, key: &EventKey) {
+        self.set(Self::KEY_TAG, &key.0)
+    }
+
+    /// Returns false if no event of the block was emitted by `address`.
+    pub fn check_address(&self, address: &ContractAddress) -> bool {
+        self.check(Self::ADDRESS_TAG, address.get())
+    }
+
+    /// Returns false if no event of the block has `key`.
+    pub fn check_key(&self, key: &EventKey) -> bool {
+        self.check(Self::KEY_TAG, &key.0)
+    }
+
+    fn set(&mut self, tag: u8, item: &Felt) {
+        for bit in Self::bits(tag, item) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    fn check(&self, tag: u8, item: &Felt) -> bool {
+        Self::bits(tag, item)
+            .into_iter()
+            .all(|bit| self.0[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// The bits of an item, taken from its hash two bytes at a time.
+    fn bits(tag: u8, item: &Felt) -> [usize; Self::HASHES] {
+        let mut hasher = Keccak256::new();
+        hasher.update([tag]);
+        hasher.update(item.as_be_bytes());
+        let hash = hasher.finalize();
+
+        let mut bits = [0; Self::HASHES];
+        for (i, bit) in bits.iter_mut().enumerate() {
+            let value = u16::from_be_bytes([hash[2 * i], hash[2 * i + 1]]) as usize;
+            *bit = value % Self::BITS;
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::felt;
+
+    #[test]
+    fn bloom() {
+        let address = ContractAddress::new_or_panic(felt!("0x1"));
+        let key = EventKey(felt!("0x2"));
+
+        let mut bloom = EventBloom::new();
+        bloom.set_address(&address);
+        bloom.set_key(&key);
+
+        let bloom =
+            EventBloom::from_compressed_bytes(&bloom.to_compressed_bytes().unwrap()).unwrap();
+        assert!(bloom.check_address(&address));
+        assert!(bloom.check_key(&key));
+        // Addresses and keys are told apart.
+        assert!(!bloom.check_key(&EventKey(felt!("0x1"))));
+        assert!(!bloom.check_address(&ContractAddress::new_or_panic(felt!("0x3"))));
+    }
+
+    #[test]
+    fn candidate_blocks_scan_is_bounded() {
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+        let tx = connection.transaction().unwrap();
+        tx.execute(
+            "CREATE TABLE starknet_events_filters (block_number INTEGER PRIMARY KEY, bloom BLOB)",
+            [],
+        )
+        .unwrap();
+
+        let key = EventKey(felt!("0x1"));
+        let mut matching = EventBloom::new();
+        matching.set_key(&key);
+        let other = EventBloom::new();
+
+        let blocks = EventBloomTable::MAX_SCANNED_FILTERS as u64 + 10;
+        for block in 0..blocks {
+            let bloom = if block % 2 == 0 { &matching } else { &other };
+            EventBloomTable::upsert(&tx, StarknetBlockNumber::new_or_panic(block), bloom).unwrap();
+        }
+
+        let candidates = |from: u64| {
+            EventBloomTable::candidate_blocks(
+                &tx,
+                Some(StarknetBlockNumber::new_or_panic(from)),
+                None,
+                None,
+                &[&[key]],
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        // The blocks after the scanned filters are not ruled out.
+        let bounded = candidates(0);
+        assert_eq!(
+            bounded.blocks.len(),
+            EventBloomTable::MAX_SCANNED_FILTERS / 2
+        );
+        assert_eq!(
+            bounded.unscanned_after,
+            Some(StarknetBlockNumber::new_or_panic(
+                EventBloomTable::MAX_SCANNED_FILTERS as u64 - 1
+            ))
+        );
+
+        let complete = candidates(blocks - 5);
+        assert_eq!(
+            complete.blocks,
+            vec![
+                StarknetBlockNumber::new_or_panic(blocks - 4),
+                StarknetBlockNumber::new_or_panic(blocks - 2),
+            ]
+        );
+        assert_eq!(complete.unscanned_after, None);
+    }
+}
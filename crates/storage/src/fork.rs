@@ -0,0 +1,348 @@
+use anyhow::Context;
+use pathfinder_common::{
+    ClassHash, ContractAddress, ContractNonce, GasPrice, SequencerAddress, StarknetBlockHash,
+    StarknetBlockNumber, StarknetBlockTimestamp, StorageAddress, StorageValue,
+};
+use rusqlite::{named_params, Connection, OptionalExtension};
+
+/// The remote block which fork mode executes against.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ForkBlock {
+    pub number: StarknetBlockNumber,
+    pub hash: StarknetBlockHash,
+    pub timestamp: StarknetBlockTimestamp,
+    pub gas_price: GasPrice,
+    pub sequencer_address: SequencerAddress,
+    pub starknet_version: Option<String>,
+}
+
+/// Caches the remote state fetched in fork mode.
+///
+/// Contracts and storage are cached per [ForkBlock], whereas classes are immutable and therefore
+/// shared by all of them. Definitions are stored zstd compressed.
+pub struct ForkStateTable;
+
+impl ForkStateTable {
+    pub fn insert_block(connection: &Connection, block: &ForkBlock) -> anyhow::Result<()> {
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO fork_blocks
+                    ( number,  hash,  timestamp,  gas_price,  sequencer_address,  starknet_version)
+                VALUES
+                    (:number, :hash, :timestamp, :gas_price, :sequencer_address, :starknet_version)",
+                named_params! {
+                    ":number": block.number,
+                    ":hash": block.hash,
+                    ":timestamp": block.timestamp,
+                    ":gas_price": &block.gas_price.to_be_bytes(),
+                    ":sequencer_address": block.sequencer_address,
+                    ":starknet_version": block.starknet_version,
+                },
+            )
+            .context("Inserting fork block")?;
+
+        Ok(())
+    }
+
+    pub fn get_block(
+        connection: &Connection,
+        number: StarknetBlockNumber,
+    ) -> anyhow::Result<Option<ForkBlock>> {
+        connection
+            .query_row(
+                r"SELECT hash, timestamp, gas_price, sequencer_address, starknet_version
+                FROM fork_blocks WHERE number = ?",
+                [number],
+                |row| {
+                    let gas_price: Vec<u8> = row.get("gas_price")?;
+                    let gas_price = GasPrice::from_be_slice(&gas_price).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            2,
+                            rusqlite::types::Type::Blob,
+                            Box::new(e),
+                        )
+                    })?;
+
+                    Ok(ForkBlock {
+                        number,
+                        hash: row.get("hash")?,
+                        timestamp: row.get("timestamp")?,
+                        gas_price,
+                        sequencer_address: row.get("sequencer_address")?,
+                        starknet_version: row.get("starknet_version")?,
+                    })
+                },
+            )
+            .optional()
+            .context("Querying fork block")
+    }
+
+    /// Stores the class hash and nonce of the contract, where a zero class hash marks a
+    /// contract which is not deployed at the block.
+    pub fn insert_contract(
+        connection: &Connection,
+        block: StarknetBlockNumber,
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+        nonce: ContractNonce,
+    ) -> anyhow::Result<()> {
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO fork_contracts
+                    (block_number, contract_address, class_hash, nonce)
+                VALUES (?, ?, ?, ?)",
+                rusqlite::params![block, contract_address, class_hash, nonce],
+            )
+            .context("Inserting fork contract")?;
+
+        Ok(())
+    }
+
+    /// Stores the class hash and nonce of a contract imported from a dump, and removes any of its
+    /// storage fetched before. The dump holds all of its storage, so storage which is not stored
+    /// for it afterwards is zero instead of being fetched.
+    pub fn insert_imported_contract(
+        connection: &Connection,
+        block: StarknetBlockNumber,
+        contract_address: ContractAddress,
+        class_hash: ClassHash,
+        nonce: ContractNonce,
+    ) -> anyhow::Result<()> {
+        connection
+            .execute(
+                "DELETE FROM fork_storage WHERE block_number = ? AND contract_address = ?",
+                rusqlite::params![block, contract_address],
+            )
+            .context("Deleting fetched fork storage")?;
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO fork_contracts
+                    (block_number, contract_address, class_hash, nonce, imported)
+                VALUES (?, ?, ?, ?, 1)",
+                rusqlite::params![block, contract_address, class_hash, nonce],
+            )
+            .context("Inserting imported fork contract")?;
+
+        Ok(())
+    }
+
+    pub fn insert_storage(
+        connection: &Connection,
+        block: StarknetBlockNumber,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+        value: StorageValue,
+    ) -> anyhow::Result<()> {
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO fork_storage
+                    (block_number, contract_address, key, value)
+                VALUES (?, ?, ?, ?)",
+                rusqlite::params![block, contract_address, key, value],
+            )
+            .context("Inserting fork storage")?;
+
+        Ok(())
+    }
+
+    /// Stores a class, with its compiled CASM if it is a Sierra class.
+    pub fn insert_class_compressed(
+        connection: &Connection,
+        class_hash: ClassHash,
+        definition: &[u8],
+        casm_definition: Option<&[u8]>,
+    ) -> anyhow::Result<()> {
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO fork_classes (hash, definition, casm_definition)
+                VALUES (?, ?, ?)",
+                rusqlite::params![class_hash, definition, casm_definition],
+            )
+            .context("Inserting fork class")?;
+
+        Ok(())
+    }
+
+    /// Returns the [ContractNonce] and [ClassHash] of the contract, if it has been cached.
+    pub fn get_contract(
+        connection: &Connection,
+        block: StarknetBlockNumber,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Option<(ClassHash, ContractNonce)>> {
+        connection
+            .query_row(
+                r"SELECT class_hash, nonce FROM fork_contracts
+                WHERE block_number = ? AND contract_address = ?",
+                rusqlite::params![block, contract_address],
+                |row| Ok((row.get("class_hash")?, row.get("nonce")?)),
+            )
+            .optional()
+            .context("Querying fork contract")
+    }
+
+    /// Returns the storage value, if it has been cached. Storage of imported contracts is zero
+    /// unless stored.
+    pub fn get_storage(
+        connection: &Connection,
+        block: StarknetBlockNumber,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+    ) -> anyhow::Result<Option<StorageValue>> {
+        let value = connection
+            .query_row(
+                r"SELECT value FROM fork_storage
+                WHERE block_number = ? AND contract_address = ? AND key = ?",
+                rusqlite::params![block, contract_address, key],
+                |row| row.get("value"),
+            )
+            .optional()
+            .context("Querying fork storage")?;
+        if value.is_some() {
+            return Ok(value);
+        }
+
+        let imported = connection
+            .query_row(
+                r"SELECT EXISTS(SELECT 1 FROM fork_contracts
+                WHERE block_number = ? AND contract_address = ? AND imported = 1)",
+                rusqlite::params![block, contract_address],
+                |row| row.get::<_, bool>(0),
+            )
+            .context("Querying imported fork contract")?;
+
+        Ok(imported.then_some(StorageValue(stark_hash::Felt::ZERO)))
+    }
+
+    pub fn class_exists(connection: &Connection, class_hash: ClassHash) -> anyhow::Result<bool> {
+        connection
+            .query_row(
+                "SELECT 1 FROM fork_classes WHERE hash = ?",
+                [class_hash],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .context("Querying fork class")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+    use pathfinder_common::felt;
+    use stark_hash::Felt;
+
+    fn block(number: u64) -> ForkBlock {
+        ForkBlock {
+            number: StarknetBlockNumber::new_or_panic(number),
+            hash: StarknetBlockHash(felt!("0xabcd")),
+            timestamp: StarknetBlockTimestamp::new_or_panic(1000),
+            gas_price: GasPrice(10),
+            sequencer_address: SequencerAddress(felt!("0x1234")),
+            starknet_version: Some("0.11.0".to_owned()),
+        }
+    }
+
+    #[test]
+    fn block_round_trip() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+
+        let block = block(5);
+        ForkStateTable::insert_block(&connection, &block).unwrap();
+
+        let result = ForkStateTable::get_block(&connection, block.number).unwrap();
+        assert_eq!(result, Some(block));
+
+        let missing = StarknetBlockNumber::new_or_panic(6);
+        let result = ForkStateTable::get_block(&connection, missing).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn state_is_per_block() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+
+        let first = block(1);
+        let second = block(2);
+        ForkStateTable::insert_block(&connection, &first).unwrap();
+        ForkStateTable::insert_block(&connection, &second).unwrap();
+
+        let contract = ContractAddress::new_or_panic(felt!("0x1"));
+        let key = StorageAddress::new_or_panic(felt!("0x2"));
+        let value = StorageValue(felt!("0x3"));
+        let class_hash = ClassHash(felt!("0x4"));
+        let nonce = ContractNonce(felt!("0x5"));
+
+        ForkStateTable::insert_storage(&connection, first.number, contract, key, value).unwrap();
+        ForkStateTable::insert_contract(&connection, first.number, contract, class_hash, nonce)
+            .unwrap();
+        // Inserting again is harmless, e.g. if two requests fetched the same state.
+        ForkStateTable::insert_storage(&connection, first.number, contract, key, value).unwrap();
+
+        let result = ForkStateTable::get_storage(&connection, first.number, contract, key);
+        assert_eq!(result.unwrap(), Some(value));
+        let result = ForkStateTable::get_storage(&connection, second.number, contract, key);
+        assert_eq!(result.unwrap(), None);
+
+        let result = ForkStateTable::get_contract(&connection, first.number, contract);
+        assert_eq!(result.unwrap(), Some((class_hash, nonce)));
+        let result = ForkStateTable::get_contract(&connection, second.number, contract);
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn imported_contract() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+
+        let block = block(1);
+        ForkStateTable::insert_block(&connection, &block).unwrap();
+
+        let contract = ContractAddress::new_or_panic(felt!("0x1"));
+        let fetched = StorageAddress::new_or_panic(felt!("0x2"));
+        let imported = StorageAddress::new_or_panic(felt!("0x3"));
+        let value = StorageValue(felt!("0x4"));
+        let class_hash = ClassHash(felt!("0x5"));
+        let nonce = ContractNonce(felt!("0x6"));
+
+        ForkStateTable::insert_storage(&connection, block.number, contract, fetched, value)
+            .unwrap();
+        let result = ForkStateTable::get_storage(&connection, block.number, contract, imported);
+        assert_eq!(result.unwrap(), None);
+
+        ForkStateTable::insert_imported_contract(
+            &connection,
+            block.number,
+            contract,
+            class_hash,
+            nonce,
+        )
+        .unwrap();
+        ForkStateTable::insert_storage(&connection, block.number, contract, imported, value)
+            .unwrap();
+
+        // Storage fetched before the import is replaced by the dump, which lacks zero values.
+        let result = ForkStateTable::get_storage(&connection, block.number, contract, fetched);
+        assert_eq!(result.unwrap(), Some(StorageValue(Felt::ZERO)));
+        let result = ForkStateTable::get_storage(&connection, block.number, contract, imported);
+        assert_eq!(result.unwrap(), Some(value));
+        let result = ForkStateTable::get_contract(&connection, block.number, contract);
+        assert_eq!(result.unwrap(), Some((class_hash, nonce)));
+    }
+
+    #[test]
+    fn class_exists() {
+        let storage = Storage::in_memory().unwrap();
+        let connection = storage.connection().unwrap();
+
+        let class_hash = ClassHash(felt!("0x4"));
+        assert!(!ForkStateTable::class_exists(&connection, class_hash).unwrap());
+
+        ForkStateTable::insert_class_compressed(&connection, class_hash, b"definition", None)
+            .unwrap();
+        assert!(ForkStateTable::class_exists(&connection, class_hash).unwrap());
+    }
+}
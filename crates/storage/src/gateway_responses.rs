@@ -0,0 +1,142 @@
+use anyhow::Context;
+use rusqlite::{named_params, OptionalExtension};
+use starknet_gateway_client::ResponseStore;
+
+use crate::Storage;
+
+/// Persists the feeder gateway responses which can never change, see [ResponseStore].
+///
+/// Responses are stored zstd compressed.
+pub struct GatewayResponsesTable;
+
+impl GatewayResponsesTable {
+    /// Stores the response, and then evicts the oldest responses until the compressed responses
+    /// take up at most `max_size` bytes.
+    pub fn insert(
+        connection: &rusqlite::Connection,
+        method: &str,
+        hash: &[u8],
+        response: &[u8],
+        max_size: u64,
+    ) -> anyhow::Result<()> {
+        let response = zstd::encode_all(response, 10).context("Compressing response")?;
+        connection
+            .execute(
+                r"INSERT OR REPLACE INTO gateway_responses (method, hash, response)
+                VALUES (:method, :hash, :response)",
+                named_params! {
+                    ":method": method,
+                    ":hash": hash,
+                    ":response": response,
+                },
+            )
+            .context("Inserting gateway response")?;
+
+        // Replaced responses are inserted anew, so the rowid orders responses by insertion.
+        connection
+            .execute(
+                r"DELETE FROM gateway_responses WHERE rowid IN (
+                    SELECT rowid FROM (
+                        SELECT rowid, SUM(length(response)) OVER (ORDER BY rowid DESC) AS total
+                        FROM gateway_responses
+                    )
+                    WHERE total > ?
+                )",
+                [i64::try_from(max_size).unwrap_or(i64::MAX)],
+            )
+            .context("Evicting gateway responses")?;
+
+        Ok(())
+    }
+
+    pub fn get(
+        connection: &impl crate::ReadAccess,
+        method: &str,
+        hash: &[u8],
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let response: Option<Vec<u8>> = connection
+            .query_row(
+                "SELECT response FROM gateway_responses WHERE method = ? AND hash = ?",
+                rusqlite::params![method, hash],
+                |row| row.get("response"),
+            )
+            .optional()
+            .context("Querying gateway response")?;
+
+        response
+            .map(|response| zstd::decode_all(response.as_slice()))
+            .transpose()
+            .context("Decompressing response")
+    }
+}
+
+/// Stores the responses in the [GatewayResponsesTable], evicting the oldest ones beyond a size.
+pub struct GatewayResponseStore {
+    storage: Storage,
+    max_size: u64,
+}
+
+impl GatewayResponseStore {
+    /// Stores responses in `storage`, keeping at most `max_size` bytes of compressed responses.
+    pub fn new(storage: Storage, max_size: u64) -> Self {
+        Self { storage, max_size }
+    }
+}
+
+impl ResponseStore for GatewayResponseStore {
+    fn get(&self, method: &'static str, hash: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        self.storage
+            .read(|tx| GatewayResponsesTable::get(tx, method, hash))
+    }
+
+    fn insert(&self, method: &'static str, hash: &[u8], response: &[u8]) -> anyhow::Result<()> {
+        self.storage
+            .write(|tx| GatewayResponsesTable::insert(tx, method, hash, response, self.max_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let store = GatewayResponseStore::new(Storage::in_memory().unwrap(), u64::MAX);
+
+        store.insert("get_block", &[1; 32], b"block").unwrap();
+        store
+            .insert("get_transaction", &[1; 32], b"transaction")
+            .unwrap();
+
+        assert_eq!(
+            store.get("get_block", &[1; 32]).unwrap(),
+            Some(b"block".to_vec())
+        );
+        assert_eq!(
+            store.get("get_transaction", &[1; 32]).unwrap(),
+            Some(b"transaction".to_vec())
+        );
+        assert_eq!(store.get("get_block", &[2; 32]).unwrap(), None);
+    }
+
+    #[test]
+    fn oldest_responses_are_evicted() {
+        let storage = Storage::in_memory().unwrap();
+        let compressed_size = zstd::encode_all(&[0u8; 100][..], 10).unwrap().len() as u64;
+        let store = GatewayResponseStore::new(storage, 2 * compressed_size);
+
+        store.insert("get_block", &[1; 32], &[0; 100]).unwrap();
+        store.insert("get_block", &[2; 32], &[0; 100]).unwrap();
+        store.insert("get_block", &[3; 32], &[0; 100]).unwrap();
+
+        assert_eq!(store.get("get_block", &[1; 32]).unwrap(), None);
+        assert!(store.get("get_block", &[2; 32]).unwrap().is_some());
+        assert!(store.get("get_block", &[3; 32]).unwrap().is_some());
+
+        // Storing a response again makes it the newest.
+        store.insert("get_block", &[2; 32], &[0; 100]).unwrap();
+        store.insert("get_block", &[4; 32], &[0; 100]).unwrap();
+        assert_eq!(store.get("get_block", &[3; 32]).unwrap(), None);
+        assert!(store.get("get_block", &[2; 32]).unwrap().is_some());
+    }
+}
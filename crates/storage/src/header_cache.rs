@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
+
+use crate::{StarknetBlock, StarknetBlocksBlockId};
+
+/// An in-memory cache of the most recent [block headers](StarknetBlock).
+///
+/// Serves the latest block and recent blocks by number or hash without a database query.
+/// Sync fills the cache with the stored head at startup and then with each block it stores, and
+/// read-only replicas with each block their writer stores. A miss means the caller should fall
+/// back to the [StarknetBlocksTable](crate::StarknetBlocksTable).
+///
+/// Cheap to clone, with all clones sharing the same cache.
+#[derive(Clone, Debug)]
+pub struct BlockHeaderCache(Arc<RwLock<Inner>>);
+
+#[derive(Debug)]
+struct Inner {
+    capacity: usize,
+    /// Contiguous headers, ordered by block number.
+    headers: VecDeque<StarknetBlock>,
+    numbers: HashMap<StarknetBlockHash, StarknetBlockNumber>,
+}
+
+impl BlockHeaderCache {
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(RwLock::new(Inner {
+            capacity,
+            headers: VecDeque::with_capacity(capacity),
+            numbers: HashMap::with_capacity(capacity),
+        })))
+    }
+
+    /// Adds a newly stored block. This must be called only once the block has been committed
+    /// to the database.
+    ///
+    /// Starts over if the block does not extend the cached headers.
+    pub fn insert(&self, block: StarknetBlock) {
+        let mut inner = self.0.write().unwrap_or_else(|e| e.into_inner());
+
+        if inner.capacity == 0 {
+            return;
+        }
+
+        let extends_latest = inner
+            .headers
+            .back()
+            .map(|latest| latest.number + 1 == block.number)
+            .unwrap_or(true);
+        if !extends_latest {
+            inner.headers.clear();
+            inner.numbers.clear();
+        }
+
+        if inner.headers.len() == inner.capacity {
+            if let Some(evicted) = inner.headers.pop_front() {
+                inner.numbers.remove(&evicted.hash);
+            }
+        }
+
+        inner.numbers.insert(block.hash, block.number);
+        inner.headers.push_back(block);
+    }
+
+    /// Removes all blocks from `reorg_tail` onwards. This must be called before the blocks are
+    /// removed from the database, so that reorged blocks are never served.
+    pub fn reorg(&self, reorg_tail: StarknetBlockNumber) {
+        let mut inner = self.0.write().unwrap_or_else(|e| e.into_inner());
+
+        while inner
+            .headers
+            .back()
+            .map(|latest| latest.number >= reorg_tail)
+            .unwrap_or_default()
+        {
+            if let Some(removed) = inner.headers.pop_back() {
+                inner.numbers.remove(&removed.hash);
+            }
+        }
+    }
+
+    /// Returns the block if it is cached.
+    pub fn get(&self, block: StarknetBlocksBlockId) -> Option<StarknetBlock> {
+        let inner = self.0.read().unwrap_or_else(|e| e.into_inner());
+
+        let number = match block {
+            StarknetBlocksBlockId::Latest => return inner.headers.back().cloned(),
+            StarknetBlocksBlockId::Number(number) => number,
+            StarknetBlocksBlockId::Hash(hash) => *inner.numbers.get(&hash)?,
+        };
+
+        let oldest = inner.headers.front()?.number;
+        let index = number.get().checked_sub(oldest.get())?;
+        inner.headers.get(index as usize).cloned()
+    }
+}
+
+impl Default for BlockHeaderCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::{GasPrice, SequencerAddress, StarknetBlockTimestamp, StateCommitment};
+    use stark_hash::Felt;
+
+    fn block(number: u64) -> StarknetBlock {
+        StarknetBlock {
+            number: StarknetBlockNumber::new_or_panic(number),
+            hash: StarknetBlockHash(Felt::from(number + 100)),
+            root: StateCommitment(Felt::ZERO),
+            timestamp: StarknetBlockTimestamp::new_or_panic(number),
+            gas_price: GasPrice::ZERO,
+            sequencer_address: SequencerAddress(Felt::ZERO),
+            transaction_commitment: None,
+            event_commitment: None,
+        }
+    }
+
+    #[test]
+    fn evicts_oldest() {
+        let cache = BlockHeaderCache::new(2);
+        cache.insert(block(0));
+        cache.insert(block(1));
+        cache.insert(block(2));
+
+        assert_eq!(cache.get(StarknetBlocksBlockId::Latest), Some(block(2)));
+        assert_eq!(cache.get(block(1).number.into()), Some(block(1)));
+        assert_eq!(cache.get(block(0).number.into()), None);
+        assert_eq!(cache.get(block(0).hash.into()), None);
+        assert_eq!(cache.get(block(2).hash.into()), Some(block(2)));
+    }
+
+    #[test]
+    fn gap_starts_over() {
+        let cache = BlockHeaderCache::new(10);
+        cache.insert(block(0));
+        cache.insert(block(5));
+
+        assert_eq!(cache.get(block(0).number.into()), None);
+        assert_eq!(cache.get(block(5).number.into()), Some(block(5)));
+    }
+
+    #[test]
+    fn reorg() {
+        let cache = BlockHeaderCache::new(10);
+        cache.insert(block(0));
+        cache.insert(block(1));
+        cache.insert(block(2));
+
+        cache.reorg(StarknetBlockNumber::new_or_panic(1));
+
+        assert_eq!(cache.get(StarknetBlocksBlockId::Latest), Some(block(0)));
+        assert_eq!(cache.get(block(1).number.into()), None);
+        assert_eq!(cache.get(block(2).hash.into()), None);
+
+        cache.insert(block(1));
+        assert_eq!(cache.get(StarknetBlocksBlockId::Latest), Some(block(1)));
+    }
+}
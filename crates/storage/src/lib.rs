@@ -4,26 +4,57 @@
 
 mod contract;
 mod ethereum;
+mod event_bloom;
+mod fork;
+mod gateway_responses;
+mod header_cache;
+mod lock;
 pub mod merkle_tree;
+mod metric_counters;
+mod migration_history;
+mod nonce_history;
+mod reorg;
+mod response_cache;
 mod schema;
+pub mod snapshot;
 mod state;
 #[cfg(any(feature = "test-utils", test))]
 pub mod test_fixtures;
 #[cfg(any(feature = "test-utils", test))]
 pub mod test_utils;
+mod transaction;
+mod tree_pruning;
+mod trie_node_cache;
 pub mod types;
+pub mod vacuum;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-pub use contract::{CasmClassTable, ClassCommitmentLeavesTable, ContractCodeTable};
+pub use contract::{
+    CasmClassTable, CasmCompilationFailuresTable, ClassCommitmentLeavesTable, ContractCodeTable,
+    DeferredClassesTable,
+};
 pub use ethereum::{EthereumBlocksTable, EthereumTransactionsTable};
+pub use fork::{ForkBlock, ForkStateTable};
+pub use gateway_responses::{GatewayResponseStore, GatewayResponsesTable};
+pub use header_cache::BlockHeaderCache;
+pub use lock::DatabaseLock;
+pub use metric_counters::MetricCountersTable;
+pub use migration_history::{MigrationHistoryTable, MigrationRecord};
+pub use nonce_history::{NonceHistoryTable, NonceUpdate};
+pub use reorg::{Reorg, ReorgHistoryTable};
+pub use response_cache::ResponseCache;
 use rusqlite::functions::FunctionFlags;
 pub use state::{
-    CanonicalBlocksTable, ContractsStateTable, EventFilterError, L1StateTable, L1TableBlockId,
-    RefsTable, StarknetBlock, StarknetBlocksBlockId, StarknetBlocksTable, StarknetEmittedEvent,
-    StarknetEventFilter, StarknetEventsTable, StarknetStateUpdatesTable, StarknetTransactionsTable,
-    V02KeyFilter, V03KeyFilter,
+    BlockTransaction, CanonicalBlocksTable, ContractsStateTable, EventFilterError, EventQueryCost,
+    L1StateTable, L1TableBlockId, RefsTable, RevertedTransaction, StarknetBlock,
+    StarknetBlocksBlockId, StarknetBlocksTable, StarknetEmittedEvent, StarknetEventFilter,
+    StarknetEventsTable, StarknetStateUpdatesTable, StarknetTransactionsTable, V02KeyFilter,
+    V03KeyFilter,
 };
+pub use transaction::{ReadAccess, ReadStatement, ReadTransaction, WriteTransaction};
+pub use tree_pruning::{PrunedTree, TreePruningTable};
+pub use trie_node_cache::TrieNodeCache;
 
 use anyhow::Context;
 use r2d2::Pool;
@@ -48,8 +79,8 @@ pub enum JournalMode {
 /// Intended usage:
 /// - Use [Storage::migrate] to create the app's database.
 /// - Pass the [Storage] (or clones thereof) to components which require database access.
-/// - Use [Storage::connection] to create connection's to the database, which can in turn
-///   be used to interact with the various [tables](self).
+/// - Use [Storage::read] and [Storage::write] to run transactions against the various
+///   [tables](self), or [Storage::connection] for long-lived connections.
 #[derive(Clone)]
 pub struct Storage(Inner);
 
@@ -58,6 +89,10 @@ struct Inner {
     /// Uses [`Arc`] to allow _shallow_ [Storage] cloning
     database_path: Arc<PathBuf>,
     pool: Pool<SqliteConnectionManager>,
+    header_cache: BlockHeaderCache,
+    response_cache: ResponseCache,
+    /// Set by [Storage::open_read_only], whose connections must stay `query_only`.
+    read_only: bool,
 }
 
 impl Storage {
@@ -67,17 +102,37 @@ impl Storage {
     /// and passed to the various components which require access to the database.
     ///
     /// May be cloned safely.
+    ///
+    /// Existing databases are backed up before applying destructive migrations, see
+    /// [Storage::migrate_with_backup].
     pub fn migrate(database_path: PathBuf, journal_mode: JournalMode) -> anyhow::Result<Self> {
+        Self::migrate_with_backup(database_path, journal_mode, true)
+    }
+
+    /// Like [Storage::migrate], but only backs up the database before destructive migrations
+    /// if `backup` is set.
+    ///
+    /// The backup is written next to the database, as `<database>.backup-v<version>` where
+    /// `version` is the schema version before migrating.
+    pub fn migrate_with_backup(
+        database_path: PathBuf,
+        journal_mode: JournalMode,
+        backup: bool,
+    ) -> anyhow::Result<Self> {
         let manager = SqliteConnectionManager::file(&database_path)
             .with_init(move |c| setup_connection(c, journal_mode));
         let pool = Pool::builder().build(manager)?;
 
         let mut conn = pool.get()?;
-        migrate_database(&mut conn).context("Migrate database")?;
+        let backup_path = backup.then_some(database_path.as_path());
+        migrate_database(&mut conn, backup_path).context("Migrate database")?;
 
         let inner = Inner {
             database_path: Arc::new(database_path),
             pool,
+            header_cache: BlockHeaderCache::default(),
+            response_cache: ResponseCache::default(),
+            read_only: false,
         };
 
         let storage = Storage(inner);
@@ -85,12 +140,107 @@ impl Storage {
         Ok(storage)
     }
 
+    /// Opens an existing database without migrating it, on connections which can only read.
+    ///
+    /// Takes no write locks, so that several read-only [Storage]s can serve the database which
+    /// another process syncs. The database must already be at the schema version of this
+    /// application, since only a writer can migrate it.
+    pub fn open_read_only(database_path: PathBuf) -> anyhow::Result<Self> {
+        use rusqlite::OpenFlags;
+
+        anyhow::ensure!(
+            database_path.exists(),
+            "Database {} does not exist",
+            database_path.display()
+        );
+
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        let manager = SqliteConnectionManager::file(&database_path)
+            .with_flags(flags)
+            .with_init(setup_read_only_connection);
+        let pool = Pool::builder().build(manager)?;
+
+        let conn = pool.get()?;
+        let version = schema_version(&*conn)?;
+        let expected = schema::migrations().len();
+        anyhow::ensure!(
+            version == expected,
+            "Database version {} does not match this application ({}), it must be migrated by a \
+            writer of the same version first",
+            version,
+            expected
+        );
+
+        let inner = Inner {
+            database_path: Arc::new(database_path),
+            pool,
+            header_cache: BlockHeaderCache::default(),
+            response_cache: ResponseCache::default(),
+            read_only: true,
+        };
+
+        Ok(Storage(inner))
+    }
+
     /// Returns a new Sqlite [Connection] to the database.
     pub fn connection(&self) -> anyhow::Result<PooledConnection> {
         let conn = self.0.pool.get()?;
+        // A failed read transaction may have left the pooled connection read-only.
+        if !self.0.read_only {
+            conn.pragma_update(None, "query_only", false)?;
+        }
         Ok(conn)
     }
 
+    /// Runs `f` in a [ReadTransaction], on a connection which rejects writes.
+    ///
+    /// `f` is run again if the database is busy, so it should have no side effects other than on
+    /// the database. Blocks the thread, so must be called from a blocking context.
+    pub fn read<T, F>(&self, mut f: F) -> anyhow::Result<T>
+    where
+        F: FnMut(&ReadTransaction<'_>) -> anyhow::Result<T>,
+    {
+        transaction::retry_busy(|| {
+            let mut connection = self.connection()?;
+            connection.pragma_update(None, "query_only", true)?;
+
+            let result = connection
+                .transaction()
+                .map_err(anyhow::Error::from)
+                .and_then(|tx| f(&ReadTransaction(tx)));
+
+            if !self.0.read_only {
+                connection.pragma_update(None, "query_only", false)?;
+            }
+            result
+        })
+    }
+
+    /// Runs `f` in a [WriteTransaction], which is committed if `f` succeeds and rolled back
+    /// otherwise.
+    ///
+    /// The write lock is taken when the transaction begins, so that a busy database is detected
+    /// before any work is done. `f` is run again if the database is busy, so it should have no
+    /// side effects other than on the database. Blocks the thread, so must be called from a
+    /// blocking context.
+    pub fn write<T, F>(&self, mut f: F) -> anyhow::Result<T>
+    where
+        F: FnMut(&WriteTransaction<'_>) -> anyhow::Result<T>,
+    {
+        transaction::retry_busy(|| {
+            let mut connection = self.connection()?;
+            let tx = WriteTransaction(
+                connection.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?,
+            );
+
+            let value = f(&tx)?;
+            tx.0.commit()?;
+            Ok(value)
+        })
+    }
+
     /// Convenience function for tests to create an in-memory database.
     /// Equivalent to [Storage::migrate] with an in-memory backed database.
     // No longer cfg(test) because needed in benchmarks
@@ -116,12 +266,49 @@ impl Storage {
     pub fn path(&self) -> &Path {
         &self.0.database_path
     }
+
+    /// Writes a consistent copy of the database to `destination`, which must not exist yet,
+    /// while the database stays in use.
+    ///
+    /// The copy holds the database as of when the backup started, since it is taken within a
+    /// single read transaction. With write-ahead logging this does not block writers. Blocks the
+    /// thread until the copy is written, so must be called from a blocking context.
+    pub fn backup(&self, destination: &Path) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !destination.exists(),
+            "Backup {} already exists",
+            destination.display()
+        );
+
+        let connection = self.connection()?;
+        vacuum_into(&connection, destination)
+    }
+
+    /// The cache of recent block headers, shared by all clones of this [Storage].
+    pub fn header_cache(&self) -> &BlockHeaderCache {
+        &self.0.header_cache
+    }
+
+    /// The cache of immutable RPC responses, shared by all clones of this [Storage].
+    pub fn response_cache(&self) -> &ResponseCache {
+        &self.0.response_cache
+    }
 }
 
 fn setup_connection(
     connection: &mut rusqlite::Connection,
     journal_mode: JournalMode,
 ) -> Result<(), rusqlite::Error> {
+    // Only new databases use incremental vacuum by default, since switching an existing one
+    // requires rebuilding it with a full vacuum.
+    let pages: u64 =
+        connection.query_row("SELECT page_count FROM pragma_page_count", [], |row| {
+            row.get(0)
+        })?;
+    if pages == 0 {
+        connection.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+    }
+
     // set journal mode related pragmas
     match journal_mode {
         JournalMode::Rollback => connection.pragma_update(None, "journal_mode", "DELETE")?,
@@ -144,6 +331,28 @@ fn setup_connection(
         true,
     )?;
 
+    // Storing a block takes more distinct statements than the default cache holds, which would
+    // then be parsed again for every block.
+    connection.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
+    register_functions(connection)
+}
+
+/// Number of prepared statements cached per connection.
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+/// Unlike [setup_connection], sets nothing which writes to the database, leaving the journal
+/// mode to the writer.
+fn setup_read_only_connection(
+    connection: &mut rusqlite::Connection,
+) -> Result<(), rusqlite::Error> {
+    connection.pragma_update(None, "query_only", true)?;
+
+    register_functions(connection)
+}
+
+/// Registers the custom functions used by the schema's triggers.
+fn register_functions(connection: &rusqlite::Connection) -> Result<(), rusqlite::Error> {
     connection.create_scalar_function(
         "base64_felts_to_index_prefixed_base32_felts",
         1,
@@ -182,8 +391,11 @@ fn base64_felts_to_index_prefixed_base32_felts(base64_felts: &str) -> String {
 
 /// Migrates the database to the latest version. This __MUST__ be called
 /// at the beginning of the application.
-fn migrate_database(connection: &mut Connection) -> anyhow::Result<()> {
-    let version = schema_version(connection)?;
+///
+/// If the path of the database is given, an existing database is [backed up](backup_database)
+/// before applying any [destructive](schema::DESTRUCTIVE) migrations.
+fn migrate_database(connection: &mut Connection, backup: Option<&Path>) -> anyhow::Result<()> {
+    let version = schema_version(&*connection)?;
     let migrations = schema::migrations();
 
     // Check that the database is not newer than this application knows of.
@@ -194,6 +406,18 @@ fn migrate_database(connection: &mut Connection) -> anyhow::Result<()> {
         migrations.len()
     );
 
+    if let Some(database_path) = backup {
+        let destructive = (version + 1..=migrations.len())
+            .any(|revision| schema::DESTRUCTIVE.contains(&revision));
+        // A new database has nothing worth backing up.
+        if destructive && version > 0 {
+            backup_database(connection, database_path, version)?;
+        }
+    }
+
+    // Migrations applied before the history table exists are recorded once it does.
+    let mut history = Vec::new();
+
     // Sequentially apply each missing migration.
     migrations
         .iter()
@@ -201,10 +425,29 @@ fn migrate_database(connection: &mut Connection) -> anyhow::Result<()> {
         .skip(version)
         .try_for_each(|(from, migration)| {
             let mut do_migration = || -> anyhow::Result<()> {
+                let applied_at = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let started = std::time::Instant::now();
+
                 let transaction = connection
                     .transaction()
                     .context("Create database transaction")?;
                 migration(&transaction)?;
+
+                history.push(MigrationRecord {
+                    revision: from + 1,
+                    applied_at,
+                    duration_ms: started.elapsed().as_millis() as u64,
+                });
+                if from + 1 >= schema::MIGRATION_HISTORY_REVISION {
+                    for record in history.drain(..) {
+                        MigrationHistoryTable::insert(&transaction, &record)
+                            .context("Recording migration")?;
+                    }
+                }
+
                 transaction
                     .pragma_update(None, VERSION_KEY, from + 1)
                     .context("Failed to update the schema version number")?;
@@ -221,9 +464,43 @@ fn migrate_database(connection: &mut Connection) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Writes a copy of the database at schema `version` to `<database_path>.backup-v<version>`.
+///
+/// An existing backup is kept as is, since it was made by a previous attempt at the same
+/// migration which must have failed.
+fn backup_database(
+    connection: &Connection,
+    database_path: &Path,
+    version: usize,
+) -> anyhow::Result<()> {
+    let mut backup = database_path.as_os_str().to_owned();
+    backup.push(format!(".backup-v{version}"));
+    let backup = PathBuf::from(backup);
+
+    if backup.exists() {
+        tracing::info!(path=%backup.display(), "Database backup already exists, skipping backup");
+        return Ok(());
+    }
+
+    tracing::info!(path=%backup.display(), "Backing up database before migrating, this may take a while");
+    vacuum_into(connection, &backup)
+}
+
+/// Writes a compacted copy of the database to `destination`.
+fn vacuum_into(connection: &Connection, destination: &Path) -> anyhow::Result<()> {
+    let path = destination
+        .to_str()
+        .context("Backup path is not valid UTF-8")?;
+    connection
+        .execute("VACUUM INTO ?", [path])
+        .context("Backing up database")?;
+
+    Ok(())
+}
+
 /// Returns the current schema version of the existing database,
 /// or `0` if database does not yet exist.
-fn schema_version(connection: &Connection) -> anyhow::Result<usize> {
+pub fn schema_version(connection: &impl ReadAccess) -> anyhow::Result<usize> {
     // We store the schema version in the Sqlite provided PRAGMA "user_version",
     // which stores an INTEGER and defaults to 0.
     let version = connection.query_row(
@@ -254,7 +531,7 @@ mod tests {
     fn full_migration() {
         let mut conn = rusqlite::Connection::open_in_memory().unwrap();
         setup_connection(&mut conn, JournalMode::Rollback).unwrap();
-        migrate_database(&mut conn).unwrap();
+        migrate_database(&mut conn, None).unwrap();
         let version = schema_version(&conn).unwrap();
         let expected = schema::migrations().len();
         assert_eq!(version, expected);
@@ -271,7 +548,116 @@ mod tests {
             .unwrap();
 
         // Migration should fail.
-        migrate_database(&mut conn).unwrap_err();
+        migrate_database(&mut conn, None).unwrap_err();
+    }
+
+    #[test]
+    fn read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("test.sqlite");
+
+        // Only a migrated database can be opened.
+        Storage::open_read_only(database_path.clone()).unwrap_err();
+        let conn = rusqlite::Connection::open(&database_path).unwrap();
+        conn.pragma_update(None, VERSION_KEY, 1).unwrap();
+        drop(conn);
+        Storage::open_read_only(database_path.clone()).unwrap_err();
+        std::fs::remove_file(&database_path).unwrap();
+
+        let writer = Storage::migrate(database_path.clone(), JournalMode::WAL).unwrap();
+        let replica = Storage::open_read_only(database_path).unwrap();
+
+        writer
+            .write(|tx| {
+                tx.execute("CREATE TABLE test(value INTEGER)", [])?;
+                tx.execute("INSERT INTO test VALUES (1)", [])?;
+                Ok(())
+            })
+            .unwrap();
+
+        // The replica sees the writer's changes, but cannot write itself.
+        let value: i64 = replica
+            .read(|tx| Ok(tx.query_row("SELECT value FROM test", [], |row| row.get(0))?))
+            .unwrap();
+        assert_eq!(value, 1);
+        replica
+            .write(|tx| Ok(tx.execute("INSERT INTO test VALUES (2)", [])?))
+            .unwrap_err();
+
+        // Neither reads nor checkouts switch the replica's connections back to writing.
+        let query_only: bool = replica
+            .connection()
+            .unwrap()
+            .pragma_query_value(None, "query_only", |row| row.get(0))
+            .unwrap();
+        assert!(query_only);
+    }
+
+    #[test]
+    fn backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("test.sqlite");
+
+        let conn = rusqlite::Connection::open(&database_path).unwrap();
+        conn.execute_batch("CREATE TABLE test(value INTEGER); INSERT INTO test VALUES (1);")
+            .unwrap();
+
+        backup_database(&conn, &database_path, 3).unwrap();
+
+        let backup = rusqlite::Connection::open(dir.path().join("test.sqlite.backup-v3")).unwrap();
+        let value: i64 = backup
+            .query_row("SELECT value FROM test", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, 1);
+
+        // A second attempt keeps the existing backup.
+        backup_database(&conn, &database_path, 3).unwrap();
+    }
+
+    #[test]
+    fn online_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::migrate(dir.path().join("test.sqlite"), JournalMode::WAL).unwrap();
+        storage
+            .write(|tx| {
+                tx.execute("CREATE TABLE test(value INTEGER)", [])?;
+                tx.execute("INSERT INTO test VALUES (1)", [])?;
+                Ok(())
+            })
+            .unwrap();
+
+        // A reader holding the database open does not stop the backup.
+        let mut reader = storage.connection().unwrap();
+        let reader = reader.transaction().unwrap();
+        reader
+            .query_row("SELECT value FROM test", [], |row| row.get::<_, i64>(0))
+            .unwrap();
+
+        let destination = dir.path().join("test.sqlite.backup");
+        storage.backup(&destination).unwrap();
+        drop(reader);
+
+        let backup = rusqlite::Connection::open(&destination).unwrap();
+        let value: i64 = backup
+            .query_row("SELECT value FROM test", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, 1);
+
+        // Existing files are not overwritten.
+        storage.backup(&destination).unwrap_err();
+    }
+
+    #[test]
+    fn new_database_is_not_backed_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("test.sqlite");
+
+        let mut conn = rusqlite::Connection::open(&database_path).unwrap();
+        setup_connection(&mut conn, JournalMode::Rollback).unwrap();
+        migrate_database(&mut conn, Some(&database_path)).unwrap();
+
+        let files = std::fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(files, 1);
     }
 
     #[test]
@@ -0,0 +1,136 @@
+//! Prevents multiple pathfinder instances from using the same database.
+//!
+//! SQLite's own locking only protects individual transactions, so two nodes syncing into the same
+//! database would interleave their writes and corrupt its state.
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// An exclusive lock on a database, held until this is dropped.
+///
+/// The lock is taken on an open file handle rather than on a path, so it also covers the database
+/// being reached through a symlink or, on case-insensitive file systems, a differently cased path.
+///
+/// The lock file is not removed on drop, as removing it would race against another instance
+/// acquiring it. The operating system releases the lock if the process exits without dropping it.
+#[derive(Debug)]
+pub struct DatabaseLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl DatabaseLock {
+    /// Acquires the lock for the database at `database_path`, failing if another process
+    /// already holds it.
+    pub fn acquire(database_path: &Path) -> anyhow::Result<Self> {
+        let mut path = database_path.as_os_str().to_owned();
+        path.push(".lock");
+        let path = PathBuf::from(path);
+
+        let mut file = match open_exclusive(&path) {
+            Ok(file) => file,
+            Err(e) if is_contended(&e) => {
+                let owner = std::fs::read_to_string(&path)
+                    .ok()
+                    .map(|pid| pid.trim().to_owned())
+                    .filter(|pid| !pid.is_empty())
+                    .map(|pid| format!(" by process {pid}"))
+                    .unwrap_or_default();
+
+                anyhow::bail!(
+                    "Database {} is already in use{owner}. Only one pathfinder instance may use a database at a time.",
+                    database_path.display()
+                );
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Opening lock file {}", path.display()))
+            }
+        };
+
+        // Record our pid to help identify the owner. This is purely informational.
+        file.set_len(0)
+            .and_then(|_| write!(file, "{}", std::process::id()))
+            .and_then(|_| file.flush())
+            .with_context(|| format!("Writing lock file {}", path.display()))?;
+
+        Ok(Self { _file: file, path })
+    }
+
+    /// Path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+fn open_exclusive(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)?;
+
+    // SAFETY: the file descriptor is valid for as long as `file` is alive.
+    let result = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn is_contended(error: &std::io::Error) -> bool {
+    error.kind() == std::io::ErrorKind::WouldBlock
+}
+
+#[cfg(windows)]
+fn open_exclusive(path: &Path) -> std::io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    // A share mode of zero denies all other handles to the file until ours is closed.
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(0)
+        .open(path)
+}
+
+#[cfg(windows)]
+fn is_contended(error: &std::io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    error.raw_os_error() == Some(ERROR_SHARING_VIOLATION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("test.sqlite");
+
+        let lock = DatabaseLock::acquire(&database).unwrap();
+        assert_eq!(lock.path(), dir.path().join("test.sqlite.lock"));
+
+        let error = DatabaseLock::acquire(&database).unwrap_err();
+        assert!(error.to_string().contains("already in use"), "{error}");
+
+        drop(lock);
+        DatabaseLock::acquire(&database).unwrap();
+    }
+
+    #[test]
+    fn other_databases_are_unaffected() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let _mainnet = DatabaseLock::acquire(&dir.path().join("mainnet.sqlite")).unwrap();
+        DatabaseLock::acquire(&dir.path().join("goerli.sqlite")).unwrap();
+    }
+}
@@ -44,6 +44,8 @@ use rusqlite::{params, OptionalExtension, Transaction};
 
 use stark_hash::Felt;
 
+use crate::TrieNodeCache;
+
 /// Backing storage for Starknet Binary Merkle Patricia Tree.
 ///
 /// Default implementation and persistent implementation is the `RcNodeStorage`. Testing/future
@@ -60,7 +62,6 @@ pub trait NodeStorage {
     fn upsert(&self, key: Felt, node: PersistedNode) -> anyhow::Result<()>;
 
     /// Decrement previously stored `key`'s reference count. This shouldn't fail for key not found.
-    #[cfg(any(feature = "test-utils", test))]
     fn decrement_ref_count(&self, key: Felt) -> anyhow::Result<()>;
 
     /// Increment previously stored `key`'s reference count. This shouldn't fail for key not found.
@@ -78,7 +79,6 @@ impl NodeStorage for () {
         Ok(())
     }
 
-    #[cfg(any(feature = "test-utils", test))]
     fn decrement_ref_count(&self, _key: Felt) -> anyhow::Result<()> {
         Ok(())
     }
@@ -112,7 +112,6 @@ impl NodeStorage for std::cell::RefCell<std::collections::HashMap<Felt, Persiste
         Ok(())
     }
 
-    #[cfg(any(feature = "test-utils", test))]
     fn decrement_ref_count(&self, _key: Felt) -> anyhow::Result<()> {
         Ok(())
     }
@@ -135,6 +134,9 @@ impl NodeStorage for std::cell::RefCell<std::collections::HashMap<Felt, Persiste
 pub struct RcNodeStorage<'tx, 'queries> {
     transaction: &'tx Transaction<'tx>,
     queries: Queries<'queries>,
+    /// The table of the trie if its nodes are kept in the [TrieNodeCache], which is only the
+    /// case for the tries of the StarkNet state.
+    cached_table: Option<&'static str>,
 }
 
 impl std::fmt::Debug for RcNodeStorage<'_, '_> {
@@ -156,12 +158,9 @@ struct Queries<'a> {
     insert: Cow<'a, str>,
     update: Cow<'a, str>,
     get: Cow<'a, str>,
-    #[cfg(any(feature = "test-utils", test))]
     delete_node: Cow<'a, str>,
-    #[cfg(any(feature = "test-utils", test))]
     set_ref_count: Cow<'a, str>,
     increment_ref_count: Cow<'a, str>,
-    #[cfg(any(feature = "test-utils", test))]
     get_ref_count: Cow<'a, str>,
 }
 
@@ -188,15 +187,12 @@ impl Queries<'static> {
             .into(),
             update: format!("UPDATE {table} SET data=?, ref_count=? WHERE hash=?").into(),
             get: format!("SELECT data FROM {table} WHERE hash = ?").into(),
-            #[cfg(any(feature = "test-utils", test))]
             delete_node: format!("DELETE FROM {table} WHERE hash = ?").into(),
-            #[cfg(any(feature = "test-utils", test))]
             set_ref_count: format!("UPDATE {table} SET ref_count = ? WHERE hash = ?").into(),
             increment_ref_count: format!(
                 "UPDATE {table} SET ref_count = ref_count + 1 WHERE hash = ?"
             )
             .into(),
-            #[cfg(any(feature = "test-utils", test))]
             get_ref_count: format!("SELECT ref_count FROM {table} WHERE hash = ?").into(),
         }
     }
@@ -220,12 +216,9 @@ impl Queries<'static> {
             insert: borrow_cow!(self.insert),
             update: borrow_cow!(self.update),
             get: borrow_cow!(self.get),
-            #[cfg(any(feature = "test-utils", test))]
             delete_node: borrow_cow!(self.delete_node),
-            #[cfg(any(feature = "test-utils", test))]
             set_ref_count: borrow_cow!(self.set_ref_count),
             increment_ref_count: borrow_cow!(self.increment_ref_count),
-            #[cfg(any(feature = "test-utils", test))]
             get_ref_count: borrow_cow!(self.get_ref_count),
         }
     }
@@ -240,7 +233,6 @@ impl<'a, 'queries> NodeStorage for RcNodeStorage<'a, 'queries> {
         self.upsert(key, node)
     }
 
-    #[cfg(any(feature = "test-utils", test))]
     fn decrement_ref_count(&self, key: Felt) -> anyhow::Result<()> {
         RcNodeStorage::decrement_ref_count(self, key)
     }
@@ -352,16 +344,16 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
     /// None of the [RcNodeStorage] functions rollback on failure. This means that if any error
     /// is encountered, the transaction should be rolled back to prevent database corruption.
     pub fn open(table: &str, transaction: &'tx Transaction<'tx>) -> anyhow::Result<Self> {
-        let queries = match table {
+        let (queries, cached_table) = match table {
             "tree_global" => {
                 let q = GLOBAL_STORAGE_TABLE.borrow();
                 // this assertion exists to prove that the reborrowing works.
                 debug_assert!(matches!(q.create, Cow::Borrowed(_)));
-                q
+                (q, Some("tree_global"))
             }
-            "tree_contracts" => CONTRACTS_STORAGE_TABLE.borrow(),
-            "tree_class" => CLASS_TREE_TABLE.borrow(),
-            other => Queries::format(other),
+            "tree_contracts" => (CONTRACTS_STORAGE_TABLE.borrow(), Some("tree_contracts")),
+            "tree_class" => (CLASS_TREE_TABLE.borrow(), Some("tree_class")),
+            other => (Queries::format(other), None),
         };
 
         // no need to prepare this, unless we get multiple tree openings in single transaction, but
@@ -371,6 +363,7 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
         Ok(Self {
             transaction,
             queries,
+            cached_table,
         })
     }
 
@@ -464,7 +457,29 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
     }
 
     /// Returns the node given by `key`, or [None] if it doesn't exist.
+    ///
+    /// Nodes of the StarkNet state tries are served from the [TrieNodeCache] if possible.
     pub fn get(&self, key: Felt) -> anyhow::Result<Option<PersistedNode>> {
+        if let Some(table) = self.cached_table {
+            if let Some(node) = TrieNodeCache::global().get(table, key) {
+                return Ok(Some(node));
+            }
+        }
+
+        let node = self.get_stored(key)?;
+
+        // Leaves are no longer stored, and the ones which still are may be overwritten.
+        if let (Some(table), Some(node @ (PersistedNode::Binary(_) | PersistedNode::Edge(_)))) =
+            (self.cached_table, &node)
+        {
+            TrieNodeCache::global().insert(table, key, node.clone());
+        }
+
+        Ok(node)
+    }
+
+    /// Returns the node given by `key` as stored in the database, bypassing the [TrieNodeCache].
+    fn get_stored(&self, key: Felt) -> anyhow::Result<Option<PersistedNode>> {
         let hash = key.to_be_bytes();
 
         let mut query = self.transaction.prepare_cached(&self.queries.get)?;
@@ -475,7 +490,6 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
                 Ok(PersistedNode::deserialize(data))
             })
             .optional()?;
-
         node.transpose()
     }
 
@@ -490,11 +504,11 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
     ///
     /// Does not perform rollback on failure. This implies that you should rollback the [RcNodeStorage's](RcNodeStorage) transaction
     /// if this call returns an error to prevent database corruption.
-    #[cfg(any(feature = "test-utils", test))]
     fn delete_node(&self, key: Felt) -> anyhow::Result<()> {
         let hash = key.to_be_bytes();
 
-        let node = match self.get(key)? {
+        // The cache may still hold nodes which an earlier transaction deleted.
+        let node = match self.get_stored(key)? {
             Some(node) => node,
             None => return Ok(()),
         };
@@ -503,6 +517,10 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
 
         stmt.execute([&hash[..]])?;
 
+        if let Some(table) = self.cached_table {
+            TrieNodeCache::remove_on_commit(self.transaction, table, key);
+        }
+
         match node {
             PersistedNode::Binary(binary) => {
                 self.decrement_ref_count(binary.left)?;
@@ -517,7 +535,6 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
 
     /// Decrements the reference count of the node and automatically deletes it
     /// if the count becomes zero.
-    #[cfg(any(feature = "test-utils", test))]
     pub fn decrement_ref_count(&self, key: Felt) -> anyhow::Result<()> {
         let hash = key.to_be_bytes();
 
@@ -527,8 +544,7 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
 
         let ref_count = query
             .query_row([&hash[..]], |row| {
-                // FIXME: u16 is not realistic for the counts
-                let ref_count: u16 = row.get("ref_count")?;
+                let ref_count: u64 = row.get("ref_count")?;
 
                 Ok(ref_count)
             })
@@ -537,8 +553,10 @@ impl<'tx, 'queries> RcNodeStorage<'tx, 'queries> {
         match ref_count {
             Some(0 | 1) => self.delete_node(key)?,
             Some(count) => {
-                self.transaction
-                    .execute(&self.queries.set_ref_count, params![count - 1, &hash[..]])?;
+                let mut stmt = self
+                    .transaction
+                    .prepare_cached(&self.queries.set_ref_count)?;
+                stmt.execute(params![count - 1, &hash[..]])?;
             }
             None => {}
         }
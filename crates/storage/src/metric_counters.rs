@@ -0,0 +1,72 @@
+use anyhow::Context;
+use rusqlite::{named_params, Transaction};
+
+/// Persists cumulative counters, so that their metrics continue from their previous values after
+/// a restart instead of starting from zero.
+pub struct MetricCountersTable;
+
+impl MetricCountersTable {
+    /// Adds `delta` to the counter `name`, which starts at zero if it does not exist yet.
+    pub fn add(transaction: &Transaction<'_>, name: &str, delta: u64) -> anyhow::Result<()> {
+        transaction
+            .execute(
+                r"INSERT INTO metric_counters (name, value) VALUES (:name, :delta)
+                ON CONFLICT(name) DO UPDATE SET value = value + excluded.value",
+                named_params! {
+                    ":name": name,
+                    ":delta": delta,
+                },
+            )
+            .with_context(|| format!("Adding to counter {name}"))?;
+
+        Ok(())
+    }
+
+    /// Returns the value of every persisted counter.
+    pub fn get_all(transaction: &impl crate::ReadAccess) -> anyhow::Result<Vec<(String, u64)>> {
+        let mut stmt = transaction
+            .prepare("SELECT name, value FROM metric_counters ORDER BY name")
+            .context("Preparing statement")?;
+
+        let counters = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .context("Querying metric counters")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over metric counters")?;
+
+        Ok(counters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn add() {
+        let storage = Storage::in_memory().unwrap();
+        let mut conn = storage.connection().unwrap();
+        let transaction = conn.transaction().unwrap();
+
+        // Seeded by the migration, from an empty database.
+        let seeded = vec![
+            ("sync_blocks_total".to_owned(), 0),
+            ("sync_reorgs_total".to_owned(), 0),
+        ];
+        assert_eq!(MetricCountersTable::get_all(&transaction).unwrap(), seeded);
+
+        MetricCountersTable::add(&transaction, "sync_blocks_total", 2).unwrap();
+        MetricCountersTable::add(&transaction, "sync_blocks_total", 3).unwrap();
+        MetricCountersTable::add(&transaction, "bytes", 10).unwrap();
+
+        assert_eq!(
+            MetricCountersTable::get_all(&transaction).unwrap(),
+            vec![
+                ("bytes".to_owned(), 10),
+                ("sync_blocks_total".to_owned(), 5),
+                ("sync_reorgs_total".to_owned(), 0),
+            ]
+        );
+    }
+}
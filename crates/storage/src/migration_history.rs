@@ -0,0 +1,81 @@
+use anyhow::Context;
+use rusqlite::{named_params, Transaction};
+
+/// A schema migration applied to the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MigrationRecord {
+    /// The schema version after the migration.
+    pub revision: usize,
+    /// Unix timestamp in seconds of when the migration was started.
+    pub applied_at: u64,
+    pub duration_ms: u64,
+}
+
+/// Stores the history of [migrations](MigrationRecord) applied to the database.
+///
+/// Migrations applied before the table was added are not recorded, except for new databases.
+pub struct MigrationHistoryTable;
+
+impl MigrationHistoryTable {
+    pub fn insert(transaction: &Transaction<'_>, record: &MigrationRecord) -> anyhow::Result<()> {
+        transaction
+            .execute(
+                r"INSERT INTO migration_history
+                    ( revision,  applied_at,  duration_ms)
+                VALUES
+                    (:revision, :applied_at, :duration_ms)",
+                named_params! {
+                    ":revision": record.revision,
+                    ":applied_at": record.applied_at,
+                    ":duration_ms": record.duration_ms,
+                },
+            )
+            .context("Inserting migration record")?;
+
+        Ok(())
+    }
+
+    /// Returns all recorded migrations, oldest first.
+    pub fn get_all(transaction: &impl crate::ReadAccess) -> anyhow::Result<Vec<MigrationRecord>> {
+        let mut stmt = transaction
+            .prepare(
+                r"SELECT revision, applied_at, duration_ms FROM migration_history ORDER BY revision",
+            )
+            .context("Preparing statement")?;
+
+        let records = stmt
+            .query_map([], |row| {
+                Ok(MigrationRecord {
+                    revision: row.get("revision")?,
+                    applied_at: row.get("applied_at")?,
+                    duration_ms: row.get("duration_ms")?,
+                })
+            })
+            .context("Querying migration history")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over migration history")?;
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn new_database_records_all_migrations() {
+        let storage = Storage::in_memory().unwrap();
+        let mut conn = storage.connection().unwrap();
+        let transaction = conn.transaction().unwrap();
+
+        let revisions = MigrationHistoryTable::get_all(&transaction)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.revision)
+            .collect::<Vec<_>>();
+        let expected = (1..=crate::schema::migrations().len()).collect::<Vec<_>>();
+        assert_eq!(revisions, expected);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Context;
+use pathfinder_common::{ContractAddress, ContractNonce, StarknetBlockNumber};
+use rusqlite::{named_params, Transaction};
+
+use crate::types::state_update::Nonce;
+
+/// A change of a contract's nonce.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NonceUpdate {
+    pub block_number: StarknetBlockNumber,
+    /// The nonce after the block.
+    pub nonce: ContractNonce,
+}
+
+/// Indexes the nonce changes of each contract by block.
+///
+/// Only canonical blocks are included; the nonce changes of a block are removed along with it
+/// when it is reorged away.
+pub struct NonceHistoryTable;
+
+impl NonceHistoryTable {
+    /// Records the nonce changes of the state update of a canonical block.
+    pub fn insert(
+        transaction: &Transaction<'_>,
+        block_number: StarknetBlockNumber,
+        nonces: &[Nonce],
+    ) -> anyhow::Result<()> {
+        let mut stmt = transaction
+            .prepare_cached(
+                r"INSERT INTO nonce_updates (contract_address, block_number, nonce)
+                VALUES (:contract_address, :block_number, :nonce)",
+            )
+            .context("Preparing statement")?;
+
+        for nonce in nonces {
+            stmt.execute(named_params! {
+                ":contract_address": nonce.contract_address,
+                ":block_number": block_number,
+                ":nonce": nonce.nonce,
+            })
+            .context("Inserting nonce update")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the nonce changes of the contract in the inclusive range of blocks, oldest first.
+    pub fn get(
+        transaction: &impl crate::ReadAccess,
+        contract_address: ContractAddress,
+        from: StarknetBlockNumber,
+        to: StarknetBlockNumber,
+    ) -> anyhow::Result<Vec<NonceUpdate>> {
+        let mut stmt = transaction
+            .prepare(
+                r"SELECT block_number, nonce FROM nonce_updates
+                WHERE contract_address = :contract_address AND block_number BETWEEN :from AND :to
+                ORDER BY block_number",
+            )
+            .context("Preparing statement")?;
+
+        let updates = stmt
+            .query_map(
+                named_params! {
+                    ":contract_address": contract_address,
+                    ":from": from,
+                    ":to": to,
+                },
+                |row| {
+                    Ok(NonceUpdate {
+                        block_number: row.get("block_number")?,
+                        nonce: row.get("nonce")?,
+                    })
+                },
+            )
+            .context("Querying nonce updates")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over nonce updates")?;
+
+        Ok(updates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils, CanonicalBlocksTable, StarknetBlocksTable, Storage};
+    use pathfinder_common::felt;
+    use stark_hash::Felt;
+
+    #[test]
+    fn history() {
+        let storage = Storage::in_memory().unwrap();
+        let mut conn = storage.connection().unwrap();
+        let transaction = conn.transaction().unwrap();
+
+        let contract = ContractAddress::new_or_panic(felt!("0x1"));
+        let other = ContractAddress::new_or_panic(felt!("0x2"));
+        let nonce = |value| ContractNonce(Felt::from_u64(value));
+
+        for (i, block) in test_utils::create_blocks().iter().enumerate() {
+            StarknetBlocksTable::insert(
+                &transaction,
+                &block.block,
+                None,
+                block.storage_commitment,
+                block.class_commitment,
+            )
+            .unwrap();
+            CanonicalBlocksTable::insert(&transaction, block.block.number, block.block.hash)
+                .unwrap();
+
+            let mut nonces = vec![Nonce {
+                contract_address: other,
+                nonce: nonce(i as u64),
+            }];
+            // The contract's nonce does not change in block 2.
+            if i != 2 {
+                nonces.push(Nonce {
+                    contract_address: contract,
+                    nonce: nonce(10 * i as u64),
+                });
+            }
+            NonceHistoryTable::insert(&transaction, block.block.number, &nonces).unwrap();
+        }
+
+        let update = |block, value| NonceUpdate {
+            block_number: StarknetBlockNumber::new_or_panic(block),
+            nonce: nonce(value),
+        };
+        let result = NonceHistoryTable::get(
+            &transaction,
+            contract,
+            StarknetBlockNumber::new_or_panic(1),
+            StarknetBlockNumber::new_or_panic(3),
+        )
+        .unwrap();
+        assert_eq!(result, vec![update(1, 10), update(3, 30)]);
+
+        // The changes are no longer part of the chain.
+        CanonicalBlocksTable::reorg(&transaction, StarknetBlockNumber::new_or_panic(3)).unwrap();
+        let result = NonceHistoryTable::get(
+            &transaction,
+            contract,
+            StarknetBlockNumber::new_or_panic(1),
+            StarknetBlockNumber::new_or_panic(3),
+        )
+        .unwrap();
+        assert_eq!(result, vec![update(1, 10)]);
+    }
+}
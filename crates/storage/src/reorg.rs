@@ -0,0 +1,117 @@
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber};
+use rusqlite::{named_params, Transaction};
+
+/// A reorg of the L2 chain processed by sync.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reorg {
+    /// Unix timestamp in seconds of when the reorg was processed.
+    pub timestamp: u64,
+    /// Number of blocks which were removed.
+    pub depth: u64,
+    pub old_head_number: StarknetBlockNumber,
+    pub old_head_hash: StarknetBlockHash,
+    /// The head the chain was rewound to, which is [None] if the genesis block was removed.
+    ///
+    /// Sync continues from this block on the new chain.
+    pub new_head: Option<(StarknetBlockNumber, StarknetBlockHash)>,
+}
+
+/// Stores the history of [reorgs](Reorg) processed by this node.
+pub struct ReorgHistoryTable;
+
+impl ReorgHistoryTable {
+    pub fn insert(transaction: &Transaction<'_>, reorg: &Reorg) -> anyhow::Result<()> {
+        transaction
+            .execute(
+                r"INSERT INTO reorg_history
+                    ( timestamp,  depth,  old_head_number,  old_head_hash,  new_head_number,  new_head_hash)
+                VALUES
+                    (:timestamp, :depth, :old_head_number, :old_head_hash, :new_head_number, :new_head_hash)",
+                named_params! {
+                    ":timestamp": reorg.timestamp,
+                    ":depth": reorg.depth,
+                    ":old_head_number": reorg.old_head_number,
+                    ":old_head_hash": reorg.old_head_hash,
+                    ":new_head_number": reorg.new_head.map(|(number, _)| number),
+                    ":new_head_hash": reorg.new_head.map(|(_, hash)| hash),
+                },
+            )
+            .context("Inserting reorg")?;
+
+        Ok(())
+    }
+
+    /// Returns up to `limit` of the most recent reorgs, newest first.
+    pub fn get_latest(
+        transaction: &impl crate::ReadAccess,
+        limit: u64,
+    ) -> anyhow::Result<Vec<Reorg>> {
+        let mut stmt = transaction
+            .prepare(
+                r"SELECT timestamp, depth, old_head_number, old_head_hash, new_head_number, new_head_hash
+                FROM reorg_history ORDER BY id DESC LIMIT ?",
+            )
+            .context("Preparing statement")?;
+
+        let reorgs = stmt
+            .query_map([limit], |row| {
+                let new_head_number: Option<StarknetBlockNumber> = row.get("new_head_number")?;
+                let new_head_hash: Option<StarknetBlockHash> = row.get("new_head_hash")?;
+
+                Ok(Reorg {
+                    timestamp: row.get("timestamp")?,
+                    depth: row.get("depth")?,
+                    old_head_number: row.get("old_head_number")?,
+                    old_head_hash: row.get("old_head_hash")?,
+                    new_head: new_head_number.zip(new_head_hash),
+                })
+            })
+            .context("Querying reorg history")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over reorg history")?;
+
+        Ok(reorgs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+    use pathfinder_common::felt;
+
+    #[test]
+    fn newest_first() {
+        let storage = Storage::in_memory().unwrap();
+        let mut conn = storage.connection().unwrap();
+        let transaction = conn.transaction().unwrap();
+
+        let first = Reorg {
+            timestamp: 1000,
+            depth: 1,
+            old_head_number: StarknetBlockNumber::GENESIS,
+            old_head_hash: StarknetBlockHash(felt!("0x1")),
+            new_head: None,
+        };
+        let second = Reorg {
+            timestamp: 2000,
+            depth: 2,
+            old_head_number: StarknetBlockNumber::new_or_panic(10),
+            old_head_hash: StarknetBlockHash(felt!("0xa")),
+            new_head: Some((
+                StarknetBlockNumber::new_or_panic(8),
+                StarknetBlockHash(felt!("0x8")),
+            )),
+        };
+
+        ReorgHistoryTable::insert(&transaction, &first).unwrap();
+        ReorgHistoryTable::insert(&transaction, &second).unwrap();
+
+        let result = ReorgHistoryTable::get_latest(&transaction, 10).unwrap();
+        assert_eq!(result, vec![second.clone(), first]);
+
+        let result = ReorgHistoryTable::get_latest(&transaction, 1).unwrap();
+        assert_eq!(result, vec![second]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::any::Any;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+use pathfinder_common::StarknetBlockNumber;
+use stark_hash::Felt;
+
+/// A size-limited cache of RPC responses which can no longer change, such as blocks, transactions
+/// and receipts that have been accepted on L1.
+///
+/// Entries are keyed by the RPC method and the hash identifying the requested item, and record the
+/// number of the block they belong to. This lets sync evict all entries affected by a reorg. The
+/// least recently used entry is evicted once the cache is full.
+///
+/// A response read from the database before a reorg is committed may only reach the cache after
+/// the reorged entries were evicted. To keep such stale responses out, every reorg starts a new
+/// cache generation: callers read the [generation](ResponseCache::generation) before they open
+/// their database transaction, and inserts of an earlier generation are discarded.
+///
+/// Cheap to clone, with all clones sharing the same cache.
+#[derive(Clone)]
+pub struct ResponseCache(Arc<Mutex<Inner>>);
+
+struct Inner {
+    cache: Option<LruCache<Key, Entry>>,
+    generation: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    method: &'static str,
+    hash: Felt,
+}
+
+struct Entry {
+    block: StarknetBlockNumber,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl ResponseCache {
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+
+    /// Creates a cache which holds up to `capacity` responses. A capacity of zero disables caching.
+    pub fn new(capacity: usize) -> Self {
+        let cache = NonZeroUsize::new(capacity).map(LruCache::new);
+        Self(Arc::new(Mutex::new(Inner {
+            cache,
+            generation: 0,
+        })))
+    }
+
+    /// The current cache generation, which must be read before the database transaction that
+    /// a response is read in is opened.
+    pub fn generation(&self) -> u64 {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).generation
+    }
+
+    /// Returns the cached response of `method` for the item identified by `hash`.
+    pub fn get<T: Clone + 'static>(&self, method: &'static str, hash: Felt) -> Option<T> {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = inner.cache.as_mut()?.get(&Key { method, hash })?;
+        entry.value.downcast_ref::<T>().cloned()
+    }
+
+    /// Caches the response of `method` for the item identified by `hash`, which is part of `block`
+    /// and was read in the cache `generation`. The response is discarded if a reorg has since
+    /// started a new generation.
+    ///
+    /// Only responses which will not change unless `block` is reorged may be cached.
+    pub fn insert<T: Send + Sync + 'static>(
+        &self,
+        method: &'static str,
+        hash: Felt,
+        block: StarknetBlockNumber,
+        generation: u64,
+        value: T,
+    ) {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.generation != generation {
+            return;
+        }
+        if let Some(cache) = inner.cache.as_mut() {
+            let entry = Entry {
+                block,
+                value: Arc::new(value),
+            };
+            cache.put(Key { method, hash }, entry);
+        }
+    }
+
+    /// Removes all responses for blocks from `reorg_tail` onwards, and starts a new generation.
+    ///
+    /// This must be called once the reorg has been committed, so that responses read before then
+    /// are discarded. Calling it before the reorg as well stops serving the reorged responses
+    /// while the reorg is being written.
+    pub fn reorg(&self, reorg_tail: StarknetBlockNumber) {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner.generation += 1;
+        if let Some(cache) = inner.cache.as_mut() {
+            let reorged = cache
+                .iter()
+                .filter(|(_, entry)| entry.block >= reorg_tail)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+
+            for key in reorged {
+                cache.pop(&key);
+            }
+        }
+    }
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        f.debug_struct("ResponseCache")
+            .field(
+                "len",
+                &inner.cache.as_ref().map(|c| c.len()).unwrap_or_default(),
+            )
+            .field("generation", &inner.generation)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METHOD: &str = "getTransactionByHash";
+    const GENESIS: StarknetBlockNumber = StarknetBlockNumber::GENESIS;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = ResponseCache::new(2);
+        cache.insert(METHOD, Felt::from(1u64), GENESIS, 0, 1u32);
+        cache.insert(METHOD, Felt::from(2u64), GENESIS, 0, 2u32);
+
+        // Touch the first entry so that the second one is evicted instead.
+        assert_eq!(cache.get::<u32>(METHOD, Felt::from(1u64)), Some(1));
+        cache.insert(METHOD, Felt::from(3u64), GENESIS, 0, 3u32);
+
+        assert_eq!(cache.get::<u32>(METHOD, Felt::from(1u64)), Some(1));
+        assert_eq!(cache.get::<u32>(METHOD, Felt::from(2u64)), None);
+        assert_eq!(cache.get::<u32>(METHOD, Felt::from(3u64)), Some(3));
+    }
+
+    #[test]
+    fn keyed_by_method() {
+        let cache = ResponseCache::new(10);
+        cache.insert(METHOD, Felt::from(1u64), GENESIS, 0, 1u32);
+
+        assert_eq!(cache.get::<u32>("other", Felt::from(1u64)), None);
+        // A different response type is a miss rather than a panic.
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(1u64)), None);
+    }
+
+    #[test]
+    fn reorg() {
+        let cache = ResponseCache::new(10);
+        for i in 0..3u64 {
+            let block = StarknetBlockNumber::new_or_panic(i);
+            cache.insert(METHOD, Felt::from(i), block, 0, i);
+        }
+
+        cache.reorg(StarknetBlockNumber::new_or_panic(1));
+
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(0u64)), Some(0));
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(1u64)), None);
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(2u64)), None);
+    }
+
+    #[test]
+    fn stale_insert_after_reorg_is_discarded() {
+        let cache = ResponseCache::new(10);
+        let block = StarknetBlockNumber::new_or_panic(1);
+
+        // A reader takes the generation and reads the block from the database, while a reorg
+        // of that block is committed and evicts the cache before the reader inserts.
+        let generation = cache.generation();
+        cache.reorg(block);
+        cache.insert(METHOD, Felt::from(1u64), block, generation, 1u64);
+
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(1u64)), None);
+
+        // Readers which start after the reorg cache as usual.
+        let generation = cache.generation();
+        cache.insert(METHOD, Felt::from(1u64), block, generation, 2u64);
+        assert_eq!(cache.get::<u64>(METHOD, Felt::from(1u64)), Some(2));
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = ResponseCache::new(0);
+        cache.insert(METHOD, Felt::from(1u64), GENESIS, 0, 1u32);
+
+        assert_eq!(cache.get::<u32>(METHOD, Felt::from(1u64)), None);
+    }
+}
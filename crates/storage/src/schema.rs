@@ -1,3 +1,4 @@
+mod parallel;
 mod revision_0001;
 mod revision_0002;
 mod revision_0003;
@@ -28,6 +29,30 @@ mod revision_0027;
 mod revision_0028;
 mod revision_0029;
 mod revision_0030;
+mod revision_0031;
+mod revision_0032;
+mod revision_0033;
+mod revision_0034;
+mod revision_0035;
+mod revision_0036;
+mod revision_0037;
+mod revision_0038;
+mod revision_0039;
+mod revision_0040;
+mod revision_0041;
+mod revision_0042;
+mod revision_0043;
+mod revision_0044;
+mod revision_0045;
+mod revision_0046;
+mod revision_0047;
+
+/// Revisions of the migrations which drop or rewrite existing data, and are therefore preceded
+/// by a database backup.
+pub(crate) const DESTRUCTIVE: &[usize] = &[2, 3, 5, 6, 10, 12, 18, 20, 26, 27];
+
+/// The revision which adds the `migration_history` table.
+pub(crate) const MIGRATION_HISTORY_REVISION: usize = 33;
 
 type MigrationFn = fn(&rusqlite::Transaction<'_>) -> anyhow::Result<()>;
 
@@ -65,5 +90,22 @@ pub fn migrations() -> &'static [MigrationFn] {
         revision_0028::migrate,
         revision_0029::migrate,
         revision_0030::migrate,
+        revision_0031::migrate,
+        revision_0032::migrate,
+        revision_0033::migrate,
+        revision_0034::migrate,
+        revision_0035::migrate,
+        revision_0036::migrate,
+        revision_0037::migrate,
+        revision_0038::migrate,
+        revision_0039::migrate,
+        revision_0040::migrate,
+        revision_0041::migrate,
+        revision_0042::migrate,
+        revision_0043::migrate,
+        revision_0044::migrate,
+        revision_0045::migrate,
+        revision_0046::migrate,
+        revision_0047::migrate,
     ]
 }
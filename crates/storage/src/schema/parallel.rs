@@ -0,0 +1,107 @@
+//! Spreads the CPU heavy parts of migrations, such as decompressing and re-encoding rows, over
+//! multiple threads.
+
+use std::num::NonZeroUsize;
+
+use anyhow::Context;
+
+/// Number of inputs handed to the worker threads at a time.
+const BATCH_SIZE: usize = 10_000;
+
+/// Applies `transform` to the `inputs` on one thread per CPU, and passes the outputs to `write` in
+/// the same order as the inputs.
+///
+/// Only `transform` runs in parallel. Reading the inputs and writing the outputs stays on the
+/// migration's transaction, since SQLite only permits a single writer and the migration must
+/// remain atomic.
+pub(crate) fn transform<I, O>(
+    inputs: impl Iterator<Item = anyhow::Result<I>>,
+    transform: fn(I) -> anyhow::Result<O>,
+    mut write: impl FnMut(O) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    let threads = std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1);
+    let mut inputs = inputs;
+
+    loop {
+        let batch = inputs
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let chunk_size = (batch.len() + threads - 1) / threads;
+        let mut batch = batch.into_iter();
+        let workers = (0..threads)
+            .map(|_| {
+                let chunk = batch.by_ref().take(chunk_size).collect::<Vec<_>>();
+                std::thread::spawn(move || {
+                    chunk
+                        .into_iter()
+                        .map(transform)
+                        .collect::<anyhow::Result<Vec<_>>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            let outputs = worker
+                .join()
+                .map_err(|_| anyhow::anyhow!("Migration worker thread panicked"))?
+                .context("Transforming rows")?;
+            for output in outputs {
+                write(output)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_order() {
+        let inputs = (0..3 * BATCH_SIZE as u64 + 7).map(Ok);
+
+        let mut outputs = Vec::new();
+        transform(
+            inputs,
+            |x| Ok(x * 2),
+            |x| {
+                outputs.push(x);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        let expected = (0..3 * BATCH_SIZE as u64 + 7)
+            .map(|x| x * 2)
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, expected);
+    }
+
+    #[test]
+    fn transform_error_is_returned() {
+        let inputs = (0..100u64).map(Ok);
+
+        let error = transform(
+            inputs,
+            |x| match x {
+                50 => anyhow::bail!("Bad input"),
+                x => Ok(x),
+            },
+            |_| Ok(()),
+        )
+        .unwrap_err();
+
+        assert_eq!(error.root_cause().to_string(), "Bad input");
+    }
+}
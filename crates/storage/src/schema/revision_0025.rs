@@ -29,15 +29,46 @@ pub(crate) fn migrate(tx: &Transaction<'_>) -> anyhow::Result<()> {
             keys,
             content='',
             tokenize='ascii'
-        );
+        );",
+    )
+    .context("Creating the starknet_events_key_03 FTS5 table")?;
+
+    // Re-populate the full text index with keys. Encoding the keys is the expensive part, so it
+    // is done in parallel instead of by the SQL function.
+    let mut query = tx
+        .prepare("SELECT id, keys FROM starknet_events")
+        .context("Preparing events query")?;
+    let mut insert = tx
+        .prepare("INSERT INTO starknet_events_keys_03 (rowid, keys) VALUES (?, ?)")
+        .context("Preparing event keys insert")?;
 
-        -- Re-populate the full text index with keys
-        INSERT INTO starknet_events_keys_03 (rowid, keys)
-            SELECT
-                starknet_events.id,
-                base64_felts_to_index_prefixed_base32_felts(starknet_events.keys)
-            FROM starknet_events;
+    let rows = query
+        .query_map([], |row| {
+            let id: i64 = row.get(0)?;
+            let keys: String = row.get(1)?;
+            Ok((id, keys))
+        })
+        .context("Querying events")?
+        .map(|row| row.context("Reading event"));
 
+    super::parallel::transform(
+        rows,
+        |(id, keys)| {
+            Ok((
+                id,
+                crate::base64_felts_to_index_prefixed_base32_felts(&keys),
+            ))
+        },
+        |(id, keys)| {
+            insert
+                .execute(rusqlite::params![id, keys])
+                .context("Inserting event keys")?;
+            Ok(())
+        },
+    )?;
+
+    tx.execute_batch(
+        r"
         -- Re-create triggers updating the FTS5 table
         CREATE TRIGGER starknet_events_03_ai
         AFTER INSERT ON starknet_events
@@ -76,7 +107,7 @@ pub(crate) fn migrate(tx: &Transaction<'_>) -> anyhow::Result<()> {
             );
         END;",
     )
-    .context("Creating the starknet_events_key_03 FTS5 table and related triggers")?;
+    .context("Creating triggers for the starknet_events_key_03 FTS5 table")?;
 
     tracing::info!("Created event key index with new lookup semantics for starknet_events");
 
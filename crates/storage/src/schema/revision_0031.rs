@@ -0,0 +1,21 @@
+use anyhow::Context;
+
+/// This migration adds the reorg_history table, which records every L2 reorg processed by sync.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE reorg_history (
+            id              INTEGER PRIMARY KEY,
+            -- Unix timestamp in seconds of when the reorg was processed.
+            timestamp       INTEGER NOT NULL,
+            depth           INTEGER NOT NULL,
+            old_head_number INTEGER NOT NULL,
+            old_head_hash   BLOB    NOT NULL,
+            -- NULL if the reorg removed the genesis block.
+            new_head_number INTEGER,
+            new_head_hash   BLOB
+        );
+        ",
+    )
+    .context("Adding reorg_history table")
+}
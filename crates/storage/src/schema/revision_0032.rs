@@ -0,0 +1,73 @@
+use anyhow::Context;
+use rusqlite::named_params;
+use starknet_gateway_types::reply::transaction::L1ToL2Message;
+
+/// This migration adds the l1_handler_messages table, which indexes L1 handler transactions by the
+/// hash of the L1 to L2 message they consumed.
+///
+/// The index is populated from the receipts of the existing transactions.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE l1_handler_messages (
+            message_hash     BLOB PRIMARY KEY NOT NULL,
+            transaction_hash BLOB NOT NULL
+        );
+        ",
+    )
+    .context("Adding l1_handler_messages table")?;
+
+    let mut query_statement = tx
+        .prepare(r"SELECT hash, receipt FROM starknet_transactions")
+        .context("Preparing statement for reading starknet_transactions table")?;
+    let mut insert_statement = tx
+        .prepare(
+            r"INSERT OR REPLACE INTO l1_handler_messages
+                (message_hash, transaction_hash)
+            VALUES
+                (:message_hash, :transaction_hash)",
+        )
+        .context("Preparing statement for adding a message")?;
+
+    let rows = query_statement
+        .query_map([], |row| {
+            let hash: Vec<u8> = row.get("hash")?;
+            let receipt: Vec<u8> = row.get("receipt")?;
+            Ok((hash, receipt))
+        })
+        .context("Executing query")?
+        .map(|row| row.context("Reading transaction"));
+
+    super::parallel::transform(rows, consumed_message, |message| {
+        if let Some((message_hash, transaction_hash)) = message {
+            insert_statement
+                .execute(named_params![
+                    ":message_hash": message_hash,
+                    ":transaction_hash": transaction_hash,
+                ])
+                .context("Inserting message")?;
+        }
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Returns the hash of the L1 to L2 message consumed by the transaction, if any, along with the
+/// transaction hash.
+fn consumed_message(
+    (transaction_hash, receipt): (Vec<u8>, Vec<u8>),
+) -> anyhow::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    /// The only part of the receipt we are interested in.
+    #[derive(serde::Deserialize)]
+    struct Receipt {
+        l1_to_l2_consumed_message: Option<L1ToL2Message>,
+    }
+
+    let receipt = zstd::decode_all(receipt.as_slice()).context("Decompressing receipt")?;
+    let receipt: Receipt = serde_json::from_slice(&receipt).context("Deserializing receipt")?;
+
+    Ok(receipt
+        .l1_to_l2_consumed_message
+        .map(|message| (message.hash().0.as_bytes().to_vec(), transaction_hash)))
+}
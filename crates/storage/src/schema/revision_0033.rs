@@ -0,0 +1,17 @@
+use anyhow::Context;
+
+/// This migration adds the migration_history table, which records when each schema migration was
+/// applied and how long it took.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE migration_history (
+            revision    INTEGER PRIMARY KEY,
+            -- Unix timestamp in seconds of when the migration was started.
+            applied_at  INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL
+        );
+        ",
+    )
+    .context("Adding migration_history table")
+}
@@ -0,0 +1,21 @@
+use anyhow::Context;
+
+/// This migration adds the reverted_transactions table, which indexes reverted transactions by
+/// block.
+///
+/// Transactions can only revert since StarkNet 0.12.1, and receipts stored before this migration
+/// do not contain their execution status. The table therefore starts out empty.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE reverted_transactions (
+            transaction_hash BLOB    PRIMARY KEY NOT NULL,
+            block_number     INTEGER NOT NULL REFERENCES canonical_blocks(number) ON DELETE CASCADE,
+            revert_error     TEXT
+        );
+
+        CREATE INDEX reverted_transactions_block_number ON reverted_transactions(block_number);
+        ",
+    )
+    .context("Adding reverted_transactions table")
+}
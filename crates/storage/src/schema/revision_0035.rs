@@ -0,0 +1,19 @@
+use anyhow::Context;
+
+/// This migration adds the casm_compilation_failures table, which records the Sierra classes
+/// which could not be compiled to CASM.
+///
+/// Such classes are stored with an empty CASM definition, so that a single pathological class
+/// does not stop sync.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE casm_compilation_failures (
+            hash                BLOB    PRIMARY KEY NOT NULL,
+            compiler_version_id INTEGER NOT NULL REFERENCES casm_compiler_versions(id),
+            error               TEXT    NOT NULL
+        );
+        ",
+    )
+    .context("Adding casm_compilation_failures table")
+}
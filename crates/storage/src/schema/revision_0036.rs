@@ -0,0 +1,20 @@
+use anyhow::Context;
+
+/// This migration adds the deferred_classes table, which tracks the classes whose definitions
+/// have not been downloaded yet.
+///
+/// Such classes are stored in class_definitions with a NULL definition, so that they can still
+/// be marked as declared.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE deferred_classes (
+            hash                BLOB    PRIMARY KEY NOT NULL,
+            -- Only known for Sierra classes, once they have been declared.
+            compiled_class_hash BLOB,
+            FOREIGN KEY(hash) REFERENCES class_definitions(hash) ON DELETE CASCADE
+        );
+        ",
+    )
+    .context("Adding deferred_classes table")
+}
@@ -0,0 +1,47 @@
+use anyhow::Context;
+
+/// This migration adds the tables which cache the remote state used in fork mode.
+///
+/// Fork mode executes against the state of a pinned block on a remote network, fetching each
+/// piece of state on first use. None of this is part of the synced chain, so it is kept apart
+/// from the regular state tables.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE fork_blocks (
+            number            INTEGER PRIMARY KEY NOT NULL,
+            hash              BLOB    NOT NULL,
+            timestamp         INTEGER NOT NULL,
+            gas_price         BLOB    NOT NULL,
+            sequencer_address BLOB    NOT NULL,
+            starknet_version  TEXT
+        );
+
+        CREATE TABLE fork_contracts (
+            block_number     INTEGER NOT NULL REFERENCES fork_blocks(number) ON DELETE CASCADE,
+            contract_address BLOB    NOT NULL,
+            -- Zero if the contract is not deployed at the block.
+            class_hash       BLOB    NOT NULL,
+            nonce            BLOB    NOT NULL,
+            PRIMARY KEY (block_number, contract_address)
+        );
+
+        CREATE TABLE fork_storage (
+            block_number     INTEGER NOT NULL REFERENCES fork_blocks(number) ON DELETE CASCADE,
+            contract_address BLOB    NOT NULL,
+            key              BLOB    NOT NULL,
+            value            BLOB    NOT NULL,
+            PRIMARY KEY (block_number, contract_address, key)
+        );
+
+        CREATE TABLE fork_classes (
+            hash             BLOB PRIMARY KEY NOT NULL,
+            -- zstd compressed
+            definition       BLOB NOT NULL,
+            -- zstd compressed, only present for Sierra classes
+            casm_definition  BLOB
+        );
+        ",
+    )
+    .context("Adding fork tables")
+}
@@ -0,0 +1,16 @@
+use anyhow::Context;
+
+/// This migration replaces the `starknet_transactions` block hash index with one over the block
+/// hash and transaction index.
+///
+/// This lets the transactions of a range of blocks be read in execution order without sorting
+/// them, which is what paging through the receipts of a range of blocks relies on. The old index
+/// is a prefix of the new one and is therefore redundant.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE INDEX starknet_transactions_block_hash_idx ON starknet_transactions(block_hash, idx);
+        DROP INDEX starknet_transactions_block_hash;",
+    )
+    .context("Replacing starknet_transactions block hash index")
+}
@@ -0,0 +1,81 @@
+use anyhow::Context;
+use rusqlite::named_params;
+
+use crate::types::state_update::Nonce;
+
+/// This migration adds the nonce_updates table, which indexes the nonce changes of each contract
+/// by block.
+///
+/// The index is populated from the state updates of the existing canonical blocks.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE nonce_updates (
+            contract_address BLOB    NOT NULL,
+            block_number     INTEGER NOT NULL REFERENCES canonical_blocks(number) ON DELETE CASCADE,
+            nonce            BLOB    NOT NULL,
+            PRIMARY KEY (contract_address, block_number)
+        );
+
+        CREATE INDEX nonce_updates_block_number ON nonce_updates(block_number);
+        ",
+    )
+    .context("Adding nonce_updates table")?;
+
+    let mut query_statement = tx
+        .prepare(
+            r"SELECT canonical_blocks.number, starknet_state_updates.data
+            FROM starknet_state_updates
+            JOIN canonical_blocks ON starknet_state_updates.block_hash = canonical_blocks.hash",
+        )
+        .context("Preparing statement for reading starknet_state_updates table")?;
+    let mut insert_statement = tx
+        .prepare(
+            r"INSERT INTO nonce_updates (contract_address, block_number, nonce)
+            VALUES (:contract_address, :block_number, :nonce)",
+        )
+        .context("Preparing statement for adding a nonce update")?;
+
+    let rows = query_statement
+        .query_map([], |row| {
+            let number: i64 = row.get("number")?;
+            let data: Vec<u8> = row.get("data")?;
+            Ok((number, data))
+        })
+        .context("Executing query")?
+        .map(|row| row.context("Reading state update"));
+
+    super::parallel::transform(rows, nonces, |(number, nonces)| {
+        for nonce in nonces {
+            insert_statement
+                .execute(named_params![
+                    ":contract_address": nonce.contract_address,
+                    ":block_number": number,
+                    ":nonce": nonce.nonce,
+                ])
+                .context("Inserting nonce update")?;
+        }
+        Ok(())
+    })?;
+
+    Ok(())
+}
+
+/// Returns the nonce changes of the state update, along with the block number.
+fn nonces((number, data): (i64, Vec<u8>)) -> anyhow::Result<(i64, Vec<Nonce>)> {
+    /// The only parts of the state update we are interested in.
+    #[derive(serde::Deserialize)]
+    struct StateUpdate {
+        state_diff: StateDiff,
+    }
+    #[derive(serde::Deserialize)]
+    struct StateDiff {
+        nonces: Vec<Nonce>,
+    }
+
+    let state_update = zstd::decode_all(data.as_slice()).context("Decompressing state update")?;
+    let state_update: StateUpdate =
+        serde_json::from_slice(&state_update).context("Deserializing state update")?;
+
+    Ok((number, state_update.state_diff.nonces))
+}
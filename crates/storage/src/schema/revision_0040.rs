@@ -0,0 +1,22 @@
+use anyhow::Context;
+
+/// This migration adds the metric_counters table, which persists cumulative counters across
+/// restarts.
+///
+/// The block and reorg counters are seeded from the already synced blocks and recorded reorgs.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE metric_counters (
+            name  TEXT    PRIMARY KEY,
+            value INTEGER NOT NULL
+        );
+
+        INSERT INTO metric_counters (name, value)
+            SELECT 'sync_blocks_total', COUNT(*) FROM canonical_blocks;
+        INSERT INTO metric_counters (name, value)
+            SELECT 'sync_reorgs_total', COUNT(*) FROM reorg_history;
+        ",
+    )
+    .context("Adding metric_counters table")
+}
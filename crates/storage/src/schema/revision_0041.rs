@@ -0,0 +1,18 @@
+use anyhow::Context;
+
+/// This migration adds the gateway_responses table, which persists the feeder gateway responses
+/// which can never change.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE gateway_responses (
+            method   TEXT NOT NULL,
+            hash     BLOB NOT NULL,
+            response BLOB NOT NULL,
+            PRIMARY KEY (method, hash)
+        )",
+        [],
+    )
+    .context("Adding gateway_responses table")?;
+
+    Ok(())
+}
@@ -0,0 +1,22 @@
+use anyhow::Context;
+
+/// This migration adds the transaction_count column to starknet_blocks, which caches the number
+/// of transactions of each block so that it need not be counted from starknet_transactions.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        "ALTER TABLE starknet_blocks ADD COLUMN transaction_count INTEGER",
+        [],
+    )
+    .context("Adding transaction_count column")?;
+
+    tx.execute(
+        r"UPDATE starknet_blocks SET transaction_count = (
+            SELECT COUNT(*) FROM starknet_transactions
+            WHERE starknet_transactions.block_hash = starknet_blocks.hash
+        )",
+        [],
+    )
+    .context("Counting transactions of existing blocks")?;
+
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use anyhow::Context;
+
+/// This migration adds the tables which track the pruning of historical state trie nodes.
+///
+/// tree_pruning holds the single row of settings, where a NULL `retained_blocks` keeps all
+/// historical state. tree_pruning_roots queues the trie roots superseded by each block, which are
+/// released once the block falls out of the retained range.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE tree_pruning (
+            idx             INTEGER PRIMARY KEY,
+            retained_blocks INTEGER,
+            -- The latest block whose state may have been pruned.
+            pruned_until    INTEGER
+        );
+        INSERT INTO tree_pruning (idx, retained_blocks, pruned_until) VALUES (1, NULL, NULL);
+
+        CREATE TABLE tree_pruning_roots (
+            block_number INTEGER NOT NULL,
+            -- The trie table of the root.
+            tree         TEXT    NOT NULL,
+            root         BLOB    NOT NULL
+        );
+        CREATE INDEX tree_pruning_roots_block_number ON tree_pruning_roots(block_number);
+        ",
+    )
+    .context("Adding tree pruning tables")
+}
@@ -0,0 +1,112 @@
+use anyhow::Context;
+use rusqlite::named_params;
+use sha3::{Digest, Keccak256};
+use stark_hash::Felt;
+
+/// This migration adds the per-block bloom filters over event addresses and keys, and builds them
+/// for the blocks already in the database.
+///
+/// The filters are built as they were defined at this revision, so that later changes to
+/// [EventBloom](crate::event_bloom::EventBloom) don't change what this migration writes.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE starknet_events_filters (
+            block_number INTEGER PRIMARY KEY NOT NULL,
+            bloom        BLOB    NOT NULL,
+            FOREIGN KEY(block_number) REFERENCES starknet_blocks(number)
+            ON DELETE CASCADE
+        )",
+        [],
+    )
+    .context("Creating starknet_events_filters table")?;
+
+    let row_count: usize = tx
+        .query_row("SELECT count(1) FROM starknet_events", [], |r| r.get(0))
+        .context("Count rows in starknet_events table")?;
+
+    if row_count > 0 {
+        tracing::info!(
+            %row_count,
+            "Building event bloom filters, this might take a while",
+        );
+    }
+
+    let mut query = tx
+        .prepare(
+            "SELECT block_number, from_address, keys FROM starknet_events ORDER BY block_number",
+        )
+        .context("Preparing events query")?;
+    let mut rows = query.query([]).context("Querying events")?;
+
+    let mut insert = tx
+        .prepare(
+            "INSERT INTO starknet_events_filters (block_number, bloom) VALUES (:block_number, :bloom)",
+        )
+        .context("Preparing event bloom filter insert")?;
+
+    let mut current: Option<(i64, Bloom)> = None;
+    while let Some(row) = rows.next().context("Fetching event")? {
+        let block_number: i64 = row.get_unwrap("block_number");
+        let from_address = row.get_ref_unwrap("from_address").as_blob()?;
+        let keys = row.get_ref_unwrap("keys").as_str()?;
+
+        if !matches!(&current, Some((number, _)) if *number == block_number) {
+            if let Some((number, bloom)) = current.take() {
+                insert
+                    .execute(named_params![":block_number": number, ":bloom": bloom.compress()?])
+                    .context("Inserting event bloom filter")?;
+            }
+            current = Some((block_number, Bloom::new()));
+        }
+        let bloom = &mut current.as_mut().expect("Set above").1;
+
+        let from_address = Felt::from_be_slice(from_address).context("Parsing event address")?;
+        bloom.set(Bloom::ADDRESS_TAG, &from_address);
+        let mut buffer = [0u8; 32];
+        for key in keys.split(' ').filter(|key| !key.is_empty()) {
+            let used = base64::decode_config_slice(key, base64::STANDARD, &mut buffer)
+                .context("Decoding event key")?;
+            let key = Felt::from_be_slice(&buffer[..used]).context("Parsing event key")?;
+            bloom.set(Bloom::KEY_TAG, &key);
+        }
+    }
+
+    if let Some((number, bloom)) = current {
+        insert
+            .execute(named_params![":block_number": number, ":bloom": bloom.compress()?])
+            .context("Inserting event bloom filter")?;
+    }
+
+    Ok(())
+}
+
+/// The event bloom filter as defined at this revision.
+struct Bloom(Vec<u8>);
+
+impl Bloom {
+    const BITS: usize = 16384;
+    const HASHES: usize = 3;
+
+    const ADDRESS_TAG: u8 = 0;
+    const KEY_TAG: u8 = 1;
+
+    fn new() -> Self {
+        Self(vec![0; Self::BITS / 8])
+    }
+
+    fn compress(&self) -> anyhow::Result<Vec<u8>> {
+        zstd::bulk::compress(&self.0, 10).context("Compressing event bloom filter")
+    }
+
+    fn set(&mut self, tag: u8, item: &Felt) {
+        let mut hasher = Keccak256::new();
+        hasher.update([tag]);
+        hasher.update(item.as_be_bytes());
+        let hash = hasher.finalize();
+
+        for i in 0..Self::HASHES {
+            let bit = u16::from_be_bytes([hash[2 * i], hash[2 * i + 1]]) as usize % Self::BITS;
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+/// This migration adds an index on the emitting address, first key and block number of events.
+///
+/// Most events are identified by their first key, such as the selector of an ERC-20 `Transfer`,
+/// so this resolves getEvents queries for one such key of one contract by an index lookup, instead
+/// of intersecting the full-text index of all keys with the events of the contract.
+///
+/// Keys are stored as space separated Base64 strings, and each key is 44 characters long, so the
+/// first key is indexed as the first 44 characters. Queries must use the same expression to use
+/// the index.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    use anyhow::Context;
+
+    tracing::info!("Adding event key index to starknet_events table, this might take a while");
+
+    tx.execute(
+        r"CREATE INDEX starknet_events_from_address_key0_block_number ON starknet_events(from_address, substr(keys, 1, 44), block_number)",
+        [],
+    )
+    .context("Adding 'starknet_events_from_address_key0_block_number' index")?;
+
+    Ok(())
+}
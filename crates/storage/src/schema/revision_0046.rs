@@ -0,0 +1,33 @@
+use anyhow::Context;
+
+/// This migration tracks which blocks' state is actually pruned, and the trie roots committed by
+/// the retained blocks.
+///
+/// tree_pruning_ranges replaces the `pruned_until` column, which also counted the blocks synced
+/// before pruning was enabled as pruned. As the state of those blocks cannot be told apart from
+/// pruned state anymore, the existing `pruned_until` carries over as a range from genesis.
+///
+/// tree_pruning_commits records the roots committed by each retained block, which are released
+/// if the block is reorged away.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+        CREATE TABLE tree_pruning_ranges (
+            first_block INTEGER NOT NULL,
+            last_block  INTEGER PRIMARY KEY
+        );
+        INSERT INTO tree_pruning_ranges (first_block, last_block)
+            SELECT 0, pruned_until FROM tree_pruning WHERE pruned_until IS NOT NULL;
+        ALTER TABLE tree_pruning DROP COLUMN pruned_until;
+
+        CREATE TABLE tree_pruning_commits (
+            block_number INTEGER NOT NULL,
+            -- The trie table of the root.
+            tree         TEXT    NOT NULL,
+            root         BLOB    NOT NULL
+        );
+        CREATE INDEX tree_pruning_commits_block_number ON tree_pruning_commits(block_number);
+        ",
+    )
+    .context("Adding tree pruning ranges and commits")
+}
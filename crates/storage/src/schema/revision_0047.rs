@@ -0,0 +1,15 @@
+use anyhow::Context;
+
+/// This migration marks the contracts whose state was imported into fork mode from a dump.
+///
+/// A dump holds all of a contract's non-zero storage, so storage of an imported contract which is
+/// missing from fork_storage is zero, instead of being fetched from the remote network.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        "ALTER TABLE fork_contracts ADD COLUMN imported INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .context("Adding imported column to fork_contracts")?;
+
+    Ok(())
+}
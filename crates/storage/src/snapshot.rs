@@ -0,0 +1,222 @@
+//! Database snapshots, which bootstrap a new node from a copy of a synced database instead of
+//! syncing from genesis.
+//!
+//! A snapshot is a zstd-compressed pathfinder database holding the headers, state tries and
+//! classes up to its head block. It is [staged](stage) next to the database first, so that it is
+//! only moved into place once it has been checked. A snapshot which fails to stage, or which is
+//! dropped without being [committed](StagedSnapshot::commit), leaves nothing behind.
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use pathfinder_common::{StarknetBlockHash, StarknetBlockNumber, StateCommitment};
+use pathfinder_ethereum::log::StateUpdateLog;
+use rusqlite::Connection;
+
+use crate::{JournalMode, L1StateTable, StarknetBlocksBlockId, StarknetBlocksTable, Storage};
+
+/// The latest block of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotHead {
+    pub number: StarknetBlockNumber,
+    pub hash: StarknetBlockHash,
+    pub state_commitment: StateCommitment,
+    /// The state update on L1 which covers the head, as recorded by the node which took the
+    /// snapshot. [None] if it recorded none.
+    pub l1_state_update: Option<StateUpdateLog>,
+}
+
+/// A snapshot which is decompressed and migrated next to the database, but not in place yet.
+pub struct StagedSnapshot {
+    /// [None] once committed.
+    storage: Option<Storage>,
+    path: PathBuf,
+    database: PathBuf,
+    head: SnapshotHead,
+}
+
+impl StagedSnapshot {
+    pub fn head(&self) -> &SnapshotHead {
+        &self.head
+    }
+
+    /// The staged database, for checks beyond those of [stage].
+    pub fn storage(&self) -> &Storage {
+        self.storage
+            .as_ref()
+            .expect("Storage is only taken on commit")
+    }
+
+    /// Moves the snapshot into place as the database.
+    pub fn commit(mut self) -> anyhow::Result<()> {
+        // Closes the connections, so that no journal is left behind.
+        self.storage.take();
+
+        std::fs::rename(&self.path, &self.database).with_context(|| {
+            format!(
+                "Moving {} to {}",
+                self.path.display(),
+                self.database.display()
+            )
+        })
+    }
+}
+
+impl Drop for StagedSnapshot {
+    fn drop(&mut self) {
+        self.storage.take();
+        // Already gone if committed.
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Where a snapshot for `database` is staged, which is `<database>.snapshot`.
+pub fn staging_path(database: &Path) -> PathBuf {
+    let mut path = database.as_os_str().to_owned();
+    path.push(".snapshot");
+    PathBuf::from(path)
+}
+
+/// Decompresses the `snapshot` next to `database`, checks its integrity, migrates it to the
+/// current schema and reads its head.
+///
+/// Fails if `database` already exists, since a snapshot cannot be merged into a database. This
+/// reads the whole snapshot several times, so it should be called from a blocking context.
+pub fn stage(snapshot: impl Read, database: &Path) -> anyhow::Result<StagedSnapshot> {
+    anyhow::ensure!(
+        !database.exists(),
+        "Database {} already exists, snapshots can only bootstrap a new database",
+        database.display()
+    );
+
+    let path = staging_path(database);
+    let (storage, head) = match decompress_and_migrate(snapshot, &path) {
+        Ok(staged) => staged,
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+    };
+
+    Ok(StagedSnapshot {
+        storage: Some(storage),
+        path,
+        database: database.to_owned(),
+        head,
+    })
+}
+
+fn decompress_and_migrate(
+    snapshot: impl Read,
+    path: &Path,
+) -> anyhow::Result<(Storage, SnapshotHead)> {
+    let mut file =
+        std::fs::File::create(path).with_context(|| format!("Creating {}", path.display()))?;
+    zstd::stream::copy_decode(snapshot, &mut file).context("Decompressing snapshot")?;
+    file.sync_all().context("Flushing snapshot")?;
+    drop(file);
+
+    check_integrity(path)?;
+
+    // The backup would only be a second copy of the snapshot.
+    let storage = Storage::migrate_with_backup(path.to_owned(), JournalMode::Rollback, false)
+        .context("Migrating snapshot")?;
+    let head = read_head(&storage)?;
+
+    Ok((storage, head))
+}
+
+fn check_integrity(path: &Path) -> anyhow::Result<()> {
+    let connection = Connection::open(path).context("Opening snapshot")?;
+    let result: String = connection
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .context("Checking snapshot integrity")?;
+    anyhow::ensure!(result == "ok", "Snapshot is corrupted: {result}");
+
+    Ok(())
+}
+
+fn read_head(storage: &Storage) -> anyhow::Result<SnapshotHead> {
+    let mut connection = storage.connection()?;
+    let tx = connection.transaction()?;
+
+    let head = StarknetBlocksTable::get(&tx, StarknetBlocksBlockId::Latest)
+        .context("Reading snapshot head")?
+        .context("Snapshot contains no blocks")?;
+    let l1_state_update = L1StateTable::get(&tx, head.number.into())
+        .context("Reading L1 state update of snapshot head")?;
+
+    Ok(SnapshotHead {
+        number: head.number,
+        hash: head.hash,
+        state_commitment: head.root,
+        l1_state_update,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::init;
+    use crate::StarknetBlock;
+
+    /// A compressed snapshot of a database with blocks 0 to 2.
+    fn snapshot(dir: &Path) -> Vec<u8> {
+        let source = dir.join("source.sqlite");
+        let storage = Storage::migrate(source.clone(), JournalMode::Rollback).unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        init::with_n_state_updates(&tx, 3);
+        tx.commit().unwrap();
+        drop(connection);
+        drop(storage);
+
+        let database = std::fs::read(source).unwrap();
+        zstd::encode_all(database.as_slice(), 0).unwrap()
+    }
+
+    #[test]
+    fn stage_and_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("mainnet.sqlite");
+
+        let compressed = snapshot(dir.path());
+
+        let staged = stage(compressed.as_slice(), &database).unwrap();
+        let expected = StarknetBlock::nth(2);
+        assert_eq!(
+            staged.head(),
+            &SnapshotHead {
+                number: expected.number,
+                hash: expected.hash,
+                state_commitment: expected.root,
+                l1_state_update: None,
+            }
+        );
+        assert!(!database.exists());
+
+        staged.commit().unwrap();
+        assert!(database.exists());
+        assert!(!staging_path(&database).exists());
+
+        // Existing databases are left alone.
+        stage(compressed.as_slice(), &database).unwrap_err();
+        assert!(!staging_path(&database).exists());
+    }
+
+    #[test]
+    fn dropped_and_failed_snapshots_are_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("mainnet.sqlite");
+
+        let compressed = snapshot(dir.path());
+
+        drop(stage(compressed.as_slice(), &database).unwrap());
+        assert!(!staging_path(&database).exists());
+
+        let truncated = &compressed[..compressed.len() / 2];
+        stage(truncated, &database).unwrap_err();
+        assert!(!staging_path(&database).exists());
+        assert!(!database.exists());
+    }
+}
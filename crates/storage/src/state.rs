@@ -1,3 +1,4 @@
+use crate::event_bloom::{CandidateBlocks, EventBloom, EventBloomTable};
 use crate::types::StateUpdate;
 use anyhow::Context;
 use ethers::types::H256;
@@ -8,8 +9,9 @@ use pathfinder_common::{
     Chain, ClassCommitment, ClassHash, ContractAddress, ContractNonce, ContractRoot,
     ContractStateHash, EthereumBlockHash, EthereumBlockNumber, EthereumLogIndex,
     EthereumTransactionHash, EthereumTransactionIndex, EventCommitment, EventData, EventKey,
-    GasPrice, SequencerAddress, StarknetBlockHash, StarknetBlockNumber, StarknetBlockTimestamp,
-    StarknetTransactionHash, StateCommitment, StorageCommitment, TransactionCommitment,
+    GasPrice, L1ToL2MessageHash, SequencerAddress, StarknetBlockHash, StarknetBlockNumber,
+    StarknetBlockTimestamp, StarknetTransactionHash, StateCommitment, StorageCommitment,
+    TransactionCommitment,
 };
 use pathfinder_ethereum::{log::StateUpdateLog, BlockOrigin, EthOrigin, TransactionOrigin};
 use rusqlite::{named_params, params, OptionalExtension, Transaction};
@@ -78,7 +80,7 @@ impl L1StateTable {
 
     /// Returns the [state commitment](StateCommitment) of the given block.
     pub fn get_state_commitment(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: L1TableBlockId,
     ) -> anyhow::Result<Option<StateCommitment>> {
         let mut statement = match block {
@@ -107,7 +109,7 @@ impl L1StateTable {
 
     /// Returns the [update](StateUpdateLog) of the given block.
     pub fn get(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: L1TableBlockId,
     ) -> anyhow::Result<Option<StateUpdateLog>> {
         let mut statement = match block {
@@ -189,13 +191,32 @@ impl L1StateTable {
             block_number: starknet_block_number,
         }))
     }
+
+    /// Returns the block of the latest [update](StateUpdateLog) which precedes `block`.
+    pub fn get_preceding_number(
+        tx: &impl crate::ReadAccess,
+        block: StarknetBlockNumber,
+    ) -> anyhow::Result<Option<StarknetBlockNumber>> {
+        let mut statement = tx.prepare(
+            "SELECT starknet_block_number FROM l1_state WHERE starknet_block_number < ?
+                ORDER BY starknet_block_number DESC LIMIT 1",
+        )?;
+        let mut rows = statement.query([block])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Some(row.get_unwrap("starknet_block_number"))),
+            None => Ok(None),
+        }
+    }
 }
 
 pub struct RefsTable {}
 
 impl RefsTable {
     /// Returns the current L1-L2 head. This indicates the latest block for which L1 and L2 agree.
-    pub fn get_l1_l2_head(tx: &Transaction<'_>) -> anyhow::Result<Option<StarknetBlockNumber>> {
+    pub fn get_l1_l2_head(
+        tx: &impl crate::ReadAccess,
+    ) -> anyhow::Result<Option<StarknetBlockNumber>> {
         // This table always contains exactly one row.
         tx.query_row("SELECT l1_l2_head FROM refs WHERE idx = 1", [], |row| {
             row.get::<_, Option<_>>(0)
@@ -262,7 +283,7 @@ impl StarknetBlocksTable {
 
     /// Returns the requested [StarknetBlock].
     pub fn get(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<Option<StarknetBlock>> {
         let mut statement = match block {
@@ -331,7 +352,7 @@ impl StarknetBlocksTable {
 
     /// Returns the [storage_commitment](StorageCommitment) of the given block.
     pub fn get_storage_commitment(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<Option<StorageCommitment>> {
         match block {
@@ -355,9 +376,51 @@ impl StarknetBlocksTable {
         .map_err(|e| e.into())
     }
 
+    /// Returns the number of transactions of the given block, or [None] if there is no such block.
+    ///
+    /// The count is stored with the block by [StarknetTransactionsTable::upsert], so that this is a
+    /// single row lookup. Transactions are only counted for blocks stored without a count.
+    pub fn get_transaction_count(
+        tx: &impl crate::ReadAccess,
+        block: StarknetBlocksBlockId,
+    ) -> anyhow::Result<Option<usize>> {
+        let row: Option<(StarknetBlockHash, Option<usize>)> = match block {
+            StarknetBlocksBlockId::Number(number) => tx.query_row(
+                "SELECT hash, transaction_count FROM starknet_blocks WHERE number = ?",
+                [number],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ),
+            StarknetBlocksBlockId::Hash(hash) => tx.query_row(
+                "SELECT hash, transaction_count FROM starknet_blocks WHERE hash = ?",
+                [hash],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ),
+            StarknetBlocksBlockId::Latest => tx.query_row(
+                "SELECT hash, transaction_count FROM starknet_blocks ORDER BY number DESC LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ),
+        }
+        .optional()
+        .context("Querying transaction count")?;
+
+        match row {
+            None => Ok(None),
+            Some((_, Some(count))) => Ok(Some(count)),
+            Some((hash, None)) => tx
+                .query_row(
+                    "SELECT COUNT(*) FROM starknet_transactions WHERE block_hash = ?",
+                    [hash],
+                    |row| row.get(0),
+                )
+                .map(Some)
+                .context("Counting transactions"),
+        }
+    }
+
     /// Returns the state commitment of the given block.
     pub fn get_state_commitment(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<Option<(StorageCommitment, ClassCommitment)>> {
         let mut statement = match block {
@@ -404,7 +467,9 @@ impl StarknetBlocksTable {
     }
 
     /// Returns the [number](StarknetBlockNumber) of the latest block.
-    pub fn get_latest_number(tx: &Transaction<'_>) -> anyhow::Result<Option<StarknetBlockNumber>> {
+    pub fn get_latest_number(
+        tx: &impl crate::ReadAccess,
+    ) -> anyhow::Result<Option<StarknetBlockNumber>> {
         let maybe = tx
             .query_row(
                 "SELECT number FROM starknet_blocks ORDER BY number DESC LIMIT 1",
@@ -417,7 +482,7 @@ impl StarknetBlocksTable {
 
     /// Returns the [hash](StarknetBlockHash) and [number](StarknetBlockNumber) of the latest block.
     pub fn get_latest_hash_and_number(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
     ) -> anyhow::Result<Option<(StarknetBlockHash, StarknetBlockNumber)>> {
         let maybe = tx
             .query_row(
@@ -434,7 +499,7 @@ impl StarknetBlocksTable {
     }
 
     pub fn get_number(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         hash: StarknetBlockHash,
     ) -> anyhow::Result<Option<StarknetBlockNumber>> {
         tx.query_row(
@@ -446,8 +511,26 @@ impl StarknetBlocksTable {
         .map_err(|e| e.into())
     }
 
+    /// Returns the [`starknet_gateway_types::reply::Block::starknet_version`] of the given block,
+    /// which is [None] if the block does not exist or predates versioning.
+    pub fn get_starknet_version(
+        tx: &impl crate::ReadAccess,
+        number: StarknetBlockNumber,
+    ) -> anyhow::Result<Option<String>> {
+        let version: Option<Option<String>> = tx
+            .query_row(
+                r"SELECT starknet_versions.version FROM starknet_blocks
+                LEFT JOIN starknet_versions ON starknet_blocks.version_id = starknet_versions.id
+                WHERE starknet_blocks.number = ?",
+                [number],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(version.flatten())
+    }
+
     /// Returns the [chain](pathfinder_common::Chain) based on genesis block hash stored in the DB.
-    pub fn get_chain(tx: &Transaction<'_>) -> anyhow::Result<Option<Chain>> {
+    pub fn get_chain(tx: &impl crate::ReadAccess) -> anyhow::Result<Option<Chain>> {
         let genesis = Self::get_hash(tx, StarknetBlockNumber::GENESIS.into())
             .context("Read genesis block from database")?;
 
@@ -463,7 +546,7 @@ impl StarknetBlocksTable {
 
     /// Returns hash of a given block number or `latest`
     pub fn get_hash(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksNumberOrLatest,
     ) -> anyhow::Result<Option<StarknetBlockHash>> {
         match block {
@@ -532,6 +615,25 @@ impl TryFrom<StarknetBlocksBlockId> for StarknetBlocksNumberOrLatest {
     }
 }
 
+/// A transaction whose execution was reverted, see [transaction::ExecutionStatus].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RevertedTransaction {
+    pub transaction_hash: StarknetTransactionHash,
+    pub block_number: StarknetBlockNumber,
+    pub revert_error: Option<String>,
+}
+
+/// A transaction and its receipt, along with their position in the chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockTransaction {
+    pub block_number: StarknetBlockNumber,
+    pub block_hash: StarknetBlockHash,
+    /// Index of the transaction within its block.
+    pub index: u64,
+    pub transaction: transaction::Transaction,
+    pub receipt: transaction::Receipt,
+}
+
 /// Stores all known starknet transactions
 pub struct StarknetTransactionsTable {}
 
@@ -539,17 +641,44 @@ impl StarknetTransactionsTable {
     /// Inserts a Starknet block's transactions and transaction receipts into the [StarknetTransactionsTable].
     ///
     /// overwrites existing data if the transaction hash already exists.
+    ///
+    /// Each statement is prepared once and then executed for every row of the block, which also
+    /// holds for the events across all of the block's receipts.
     pub fn upsert(
         tx: &Transaction<'_>,
         block_hash: StarknetBlockHash,
         block_number: StarknetBlockNumber,
         transaction_data: &[(transaction::Transaction, transaction::Receipt)],
     ) -> anyhow::Result<()> {
+        tx.execute(
+            "UPDATE starknet_blocks SET transaction_count = ? WHERE hash = ?",
+            params![transaction_data.len(), block_hash],
+        )
+        .context("Updating transaction count")?;
+
         if transaction_data.is_empty() {
             return Ok(());
         }
 
+        let mut insert_transaction = tx
+            .prepare_cached(r"INSERT OR REPLACE INTO starknet_transactions (hash, idx, block_hash, tx, receipt) VALUES (:hash, :idx, :block_hash, :tx, :receipt)")
+            .context("Preparing transaction insert")?;
+        let mut insert_message = tx
+            .prepare_cached(
+                r"INSERT OR REPLACE INTO l1_handler_messages (message_hash, transaction_hash)
+                VALUES (:message_hash, :transaction_hash)",
+            )
+            .context("Preparing L1 handler message insert")?;
+        let mut insert_reverted = tx
+            .prepare_cached(
+                r"INSERT OR REPLACE INTO reverted_transactions (transaction_hash, block_number, revert_error)
+                VALUES (:transaction_hash, :block_number, :revert_error)",
+            )
+            .context("Preparing reverted transaction insert")?;
+
         let mut compressor = zstd::bulk::Compressor::new(10).context("Create zstd compressor")?;
+        let mut bloom = EventBloom::new();
+        let mut has_events = false;
         for (i, (transaction, receipt)) in transaction_data.iter().enumerate() {
             // Serialize and compress transaction data.
             let tx_data =
@@ -564,14 +693,15 @@ impl StarknetTransactionsTable {
                 .compress(&serialized_receipt)
                 .context("Compress Starknet transaction receipt")?;
 
-            tx.execute(r"INSERT OR REPLACE INTO starknet_transactions (hash, idx, block_hash, tx, receipt) VALUES (:hash, :idx, :block_hash, :tx, :receipt)",
-                       named_params![
+            insert_transaction
+                .execute(named_params![
                     ":hash": transaction.hash(),
                     ":idx": i,
                     ":block_hash": block_hash,
                     ":tx": &tx_data,
                     ":receipt": &serialized_receipt,
-                ]).context("Insert transaction data into transactions table")?;
+                ])
+                .context("Insert transaction data into transactions table")?;
 
             // insert events from receipt
             StarknetEventsTable::insert_events(
@@ -581,6 +711,35 @@ impl StarknetTransactionsTable {
                 &receipt.events,
             )
             .context("Inserting events")?;
+
+            for event in &receipt.events {
+                has_events = true;
+                bloom.set_address(&event.from_address);
+                event.keys.iter().for_each(|key| bloom.set_key(key));
+            }
+
+            if let Some(message) = &receipt.l1_to_l2_consumed_message {
+                insert_message
+                    .execute(named_params![
+                        ":message_hash": message.hash().0.as_bytes(),
+                        ":transaction_hash": receipt.transaction_hash,
+                    ])
+                    .context("Insert L1 handler message")?;
+            }
+
+            if receipt.execution_status == transaction::ExecutionStatus::Reverted {
+                insert_reverted
+                    .execute(named_params![
+                        ":transaction_hash": receipt.transaction_hash,
+                        ":block_number": block_number,
+                        ":revert_error": receipt.revert_error,
+                    ])
+                    .context("Insert reverted transaction")?;
+            }
+        }
+
+        if has_events {
+            EventBloomTable::upsert(tx, block_number, &bloom)?;
         }
 
         Ok(())
@@ -615,7 +774,7 @@ impl StarknetTransactionsTable {
     }
 
     fn get_block_hash(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<Option<StarknetBlockHash>> {
         Ok(match block {
@@ -630,7 +789,7 @@ impl StarknetTransactionsTable {
     }
 
     pub fn get_transaction_data_for_block(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<Vec<(transaction::Transaction, transaction::Receipt)>> {
         let block_hash = match Self::get_block_hash(tx, block)? {
@@ -671,7 +830,7 @@ impl StarknetTransactionsTable {
     }
 
     pub fn get_transactions_for_latest_block(
-        sqlite_tx: &Transaction<'_>,
+        sqlite_tx: &impl crate::ReadAccess,
     ) -> anyhow::Result<Vec<transaction::Transaction>> {
         let mut stmt = sqlite_tx
             .prepare(
@@ -701,22 +860,22 @@ impl StarknetTransactionsTable {
     }
 
     pub fn get_transaction_at_block(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
         index: usize,
     ) -> anyhow::Result<Option<transaction::Transaction>> {
         // Identify block hash
         let block_hash = match block {
             StarknetBlocksBlockId::Number(number) => {
-                match StarknetBlocksTable::get(tx, number.into())? {
-                    Some(block) => block.hash,
+                match StarknetBlocksTable::get_hash(tx, number.into())? {
+                    Some(hash) => hash,
                     None => return Ok(None),
                 }
             }
             StarknetBlocksBlockId::Hash(hash) => hash,
             StarknetBlocksBlockId::Latest => {
-                match StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Latest)? {
-                    Some(block) => block.hash,
+                match StarknetBlocksTable::get_hash(tx, StarknetBlocksNumberOrLatest::Latest)? {
+                    Some(hash) => hash,
                     None => return Ok(None),
                 }
             }
@@ -748,7 +907,7 @@ impl StarknetTransactionsTable {
     }
 
     pub fn get_transaction(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         transaction: StarknetTransactionHash,
     ) -> anyhow::Result<Option<transaction::Transaction>> {
         let mut stmt = tx
@@ -770,8 +929,142 @@ impl StarknetTransactionsTable {
         Ok(Some(transaction))
     }
 
+    /// Returns the number of the block which contains the transaction.
+    pub fn get_block_number(
+        tx: &impl crate::ReadAccess,
+        transaction: StarknetTransactionHash,
+    ) -> anyhow::Result<Option<StarknetBlockNumber>> {
+        tx.query_row(
+            r"SELECT starknet_blocks.number FROM starknet_transactions
+            JOIN starknet_blocks ON starknet_transactions.block_hash = starknet_blocks.hash
+            WHERE starknet_transactions.hash = ?",
+            [transaction],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.into())
+    }
+
+    /// Returns the hash and block of the L1 handler transaction which consumed the L1 to L2
+    /// message, if that transaction is part of a stored block.
+    pub fn get_by_l1_to_l2_message(
+        tx: &impl crate::ReadAccess,
+        message: L1ToL2MessageHash,
+    ) -> anyhow::Result<
+        Option<(
+            StarknetTransactionHash,
+            StarknetBlockNumber,
+            StarknetBlockHash,
+        )>,
+    > {
+        tx.query_row(
+            r"SELECT l1_handler_messages.transaction_hash, starknet_blocks.number, starknet_blocks.hash
+            FROM l1_handler_messages
+            JOIN starknet_transactions ON l1_handler_messages.transaction_hash = starknet_transactions.hash
+            JOIN starknet_blocks ON starknet_transactions.block_hash = starknet_blocks.hash
+            WHERE l1_handler_messages.message_hash = ?",
+            [message.0.as_bytes()],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .context("Querying L1 handler message")
+    }
+
+    /// Returns the reverted transactions of the blocks in the inclusive range, in the order in
+    /// which they were executed.
+    pub fn get_reverted(
+        tx: &impl crate::ReadAccess,
+        from: StarknetBlockNumber,
+        to: StarknetBlockNumber,
+    ) -> anyhow::Result<Vec<RevertedTransaction>> {
+        let mut stmt = tx
+            .prepare(
+                r"SELECT reverted_transactions.transaction_hash, reverted_transactions.block_number, reverted_transactions.revert_error
+                FROM reverted_transactions
+                JOIN starknet_transactions ON reverted_transactions.transaction_hash = starknet_transactions.hash
+                WHERE reverted_transactions.block_number BETWEEN :from AND :to
+                ORDER BY reverted_transactions.block_number, starknet_transactions.idx",
+            )
+            .context("Preparing statement")?;
+
+        let reverted = stmt
+            .query_map(named_params![":from": from, ":to": to], |row| {
+                Ok(RevertedTransaction {
+                    transaction_hash: row.get(0)?,
+                    block_number: row.get(1)?,
+                    revert_error: row.get(2)?,
+                })
+            })
+            .context("Querying reverted transactions")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over reverted transactions")?;
+
+        Ok(reverted)
+    }
+
+    /// Returns up to `limit` transactions and their receipts, in the order in which they were
+    /// executed, starting with the transaction at `start_index` of block `start` and ending with
+    /// the last transaction of block `to`.
+    pub fn get_receipts_range(
+        tx: &impl crate::ReadAccess,
+        start: StarknetBlockNumber,
+        start_index: u64,
+        to: StarknetBlockNumber,
+        limit: usize,
+    ) -> anyhow::Result<Vec<BlockTransaction>> {
+        let mut stmt = tx
+            .prepare(
+                r"SELECT starknet_blocks.number, starknet_blocks.hash, starknet_transactions.idx, starknet_transactions.tx, starknet_transactions.receipt
+                FROM starknet_blocks
+                JOIN starknet_transactions ON starknet_transactions.block_hash = starknet_blocks.hash
+                WHERE starknet_blocks.number BETWEEN :start AND :to
+                    AND (starknet_blocks.number > :start OR starknet_transactions.idx >= :start_index)
+                ORDER BY starknet_blocks.number, starknet_transactions.idx
+                LIMIT :limit",
+            )
+            .context("Preparing statement")?;
+
+        let mut rows = stmt
+            .query(named_params![
+                ":start": start,
+                ":start_index": start_index,
+                ":to": to,
+                ":limit": limit,
+            ])
+            .context("Executing query")?;
+
+        let mut data = Vec::new();
+        while let Some(row) = rows.next()? {
+            let receipt = row
+                .get_ref_unwrap("receipt")
+                .as_blob_or_null()?
+                .context("Receipt data missing")?;
+            let receipt = zstd::decode_all(receipt).context("Decompressing transaction receipt")?;
+            let receipt =
+                serde_json::from_slice(&receipt).context("Deserializing transaction receipt")?;
+
+            let transaction = row
+                .get_ref_unwrap("tx")
+                .as_blob_or_null()?
+                .context("Transaction data missing")?;
+            let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
+            let transaction =
+                serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+
+            data.push(BlockTransaction {
+                block_number: row.get("number")?,
+                block_hash: row.get("hash")?,
+                index: row.get("idx")?,
+                transaction,
+                receipt,
+            });
+        }
+
+        Ok(data)
+    }
+
     pub fn get_transaction_with_receipt(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         txn_hash: StarknetTransactionHash,
     ) -> anyhow::Result<
         Option<(
@@ -808,37 +1101,13 @@ impl StarknetTransactionsTable {
         Ok(Some((transaction, receipt, block_hash)))
     }
 
+    /// Returns the number of transactions of the given block, which is zero if there is no such
+    /// block. See [StarknetBlocksTable::get_transaction_count].
     pub fn get_transaction_count(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block: StarknetBlocksBlockId,
     ) -> anyhow::Result<usize> {
-        match block {
-            StarknetBlocksBlockId::Number(number) => tx
-                .query_row(
-                    "SELECT COUNT(*) FROM starknet_transactions
-                    JOIN starknet_blocks ON starknet_transactions.block_hash = starknet_blocks.hash
-                    WHERE number = ?1",
-                    [number],
-                    |row| row.get(0),
-                )
-                .context("Counting transactions"),
-            StarknetBlocksBlockId::Hash(hash) => tx
-                .query_row(
-                    "SELECT COUNT(*) FROM starknet_transactions WHERE block_hash = ?1",
-                    [hash],
-                    |row| row.get(0),
-                )
-                .context("Counting transactions"),
-            StarknetBlocksBlockId::Latest => {
-                // First get the latest block
-                let block = match StarknetBlocksTable::get(tx, StarknetBlocksBlockId::Latest)? {
-                    Some(block) => block.number,
-                    None => return Ok(0),
-                };
-
-                Self::get_transaction_count(tx, block.into())
-            }
-        }
+        StarknetBlocksTable::get_transaction_count(tx, block).map(Option::unwrap_or_default)
     }
 }
 
@@ -867,6 +1136,18 @@ pub enum EventFilterError {
     PageSizeTooBig(usize),
 }
 
+/// Estimated cost of an events query, see [StarknetEventsTable::estimate_query_cost].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EventQueryCost {
+    /// Number of blocks in the queried range.
+    pub blocks: u64,
+    /// Number of events emitted in the queried range.
+    ///
+    /// This is an upper bound for the number of events a query has to scan and sort, as
+    /// the address and key filters can only narrow it down.
+    pub events: u64,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PageOfEvents {
     pub events: Vec<StarknetEmittedEvent>,
@@ -875,6 +1156,14 @@ pub struct PageOfEvents {
 
 pub trait KeyFilter {
     fn apply<'a>(&self, key_fts_expression: &'a mut String) -> Option<KeyFilterResult<'a>>;
+
+    /// The groups of keys of which a matching event must have at least one key each, regardless
+    /// of position. Used to skip blocks with the event bloom filters.
+    fn key_groups(&self) -> Vec<&[EventKey]>;
+
+    /// The keys of which a matching event must have one as its first key, and whether that is the
+    /// filter's only restriction. Used to look up the events of a contract by their first key.
+    fn first_keys(&self) -> Option<(&[EventKey], bool)>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -928,6 +1217,19 @@ impl KeyFilter for V02KeyFilter {
             None
         }
     }
+
+    fn key_groups(&self) -> Vec<&[EventKey]> {
+        if self.0.is_empty() {
+            vec![]
+        } else {
+            vec![&self.0]
+        }
+    }
+
+    fn first_keys(&self) -> Option<(&[EventKey], bool)> {
+        // Keys match in any position.
+        None
+    }
 }
 
 #[derive(Clone)]
@@ -977,6 +1279,23 @@ impl KeyFilter for V03KeyFilter {
             None
         }
     }
+
+    fn key_groups(&self) -> Vec<&[EventKey]> {
+        self.0
+            .iter()
+            .filter(|keys| !keys.is_empty())
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    fn first_keys(&self) -> Option<(&[EventKey], bool)> {
+        match self.0.split_first() {
+            Some((first, rest)) if !first.is_empty() => {
+                Some((first, rest.iter().all(Vec::is_empty)))
+            }
+            _ => None,
+        }
+    }
 }
 
 pub struct StarknetEventsTable {}
@@ -1017,13 +1336,24 @@ impl StarknetEventsTable {
         debug_assert_eq!(_capacity, out.capacity(), "pre-reservation was not enough");
     }
 
+    /// Rebuilds the index on the address and first key of events, and the event bloom filters of
+    /// all blocks.
+    ///
+    /// The migrations build both, so this is only needed to repair a database whose indexes are
+    /// suspected to be corrupt or out of date.
+    pub fn reindex(tx: &Transaction<'_>) -> anyhow::Result<()> {
+        tx.execute("REINDEX starknet_events_from_address_key0_block_number", [])
+            .context("Rebuilding event key index")?;
+        EventBloomTable::rebuild(tx).context("Rebuilding event bloom filters")
+    }
+
     pub fn insert_events(
         tx: &Transaction<'_>,
         block_number: StarknetBlockNumber,
         transaction_hash: StarknetTransactionHash,
         events: &[transaction::Event],
     ) -> anyhow::Result<()> {
-        let mut stmt = tx.prepare(
+        let mut stmt = tx.prepare_cached(
             r"INSERT INTO starknet_events ( block_number,  idx,  transaction_hash,  from_address,  keys,  data)
                                    VALUES (:block_number, :idx, :transaction_hash, :from_address, :keys, :data)"
         )?;
@@ -1061,6 +1391,8 @@ impl StarknetEventsTable {
         contract_address: Option<&'arg ContractAddress>,
         keys: &dyn KeyFilter,
         key_fts_expression: &'arg mut String,
+        first_keys_json: &'arg mut String,
+        candidate_blocks: Option<&'arg (String, Option<StarknetBlockNumber>)>,
     ) -> (
         std::borrow::Cow<'query, str>,
         Vec<(&'static str, &'arg dyn rusqlite::ToSql)>,
@@ -1088,16 +1420,56 @@ impl StarknetEventsTable {
             (None, None) => {}
         }
 
+        // on the blocks whose event bloom filters may match, or which were not scanned
+        if let Some((candidate_blocks, unscanned_after)) = candidate_blocks {
+            match unscanned_after {
+                None => where_statement_parts
+                    .push("block_number IN (SELECT value FROM json_each(:candidate_blocks))"),
+                Some(unscanned_after) => {
+                    where_statement_parts.push(
+                        "(block_number IN (SELECT value FROM json_each(:candidate_blocks)) \
+                        OR block_number > :unscanned_after)",
+                    );
+                    params.push((":unscanned_after", unscanned_after));
+                }
+            }
+            params.push((":candidate_blocks", candidate_blocks));
+        }
+
         // on contract address
         if let Some(contract_address) = contract_address {
             where_statement_parts.push("from_address = :contract_address");
             params.push((":contract_address", contract_address))
         }
 
+        // Filter on the first key of a contract's events: this is using the index on the address
+        // and the first key, which are the first 44 characters of the Base64 encoded keys.
+        let first_keys = contract_address.and(keys.first_keys());
+        if let Some((first_keys, _)) = first_keys {
+            first_keys_json.push('[');
+            first_keys.iter().enumerate().for_each(|(i, key)| {
+                first_keys_json.push('"');
+                StarknetEventsTable::encode_event_key_to_base64(key, first_keys_json);
+                first_keys_json.push('"');
+
+                if i != first_keys.len() - 1 {
+                    first_keys_json.push(',');
+                }
+            });
+            first_keys_json.push(']');
+
+            where_statement_parts.push(
+                "substr(starknet_events.keys, 1, 44) IN (SELECT value FROM json_each(:first_keys))",
+            );
+            params.push((":first_keys", first_keys_json));
+        }
+
         // Filter on keys: this is using an FTS5 full-text index (virtual table) on the keys.
         // The idea is that we convert keys to a space-separated list of Bas64 encoded string
         // representation and then use the full-text index to find events matching the events.
-        if let Some(result) = keys.apply(key_fts_expression) {
+        // This is not needed if the first key is the only restriction, and was filtered on above.
+        let only_first_keys = matches!(first_keys, Some((_, true)));
+        if let Some(result) = keys.apply(key_fts_expression).filter(|_| !only_first_keys) {
             base_query.to_mut().push_str(result.base_query);
             where_statement_parts.push(result.where_statement);
             params.push((result.param.0, key_fts_expression));
@@ -1136,13 +1508,26 @@ impl StarknetEventsTable {
     }
 
     pub fn event_count(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         from_block: Option<StarknetBlockNumber>,
         to_block: Option<StarknetBlockNumber>,
         contract_address: Option<ContractAddress>,
         keys: &dyn KeyFilter,
     ) -> anyhow::Result<usize> {
+        let candidate_blocks = match Self::candidate_blocks(
+            tx,
+            from_block,
+            to_block,
+            contract_address.as_ref(),
+            keys,
+        )? {
+            Some(blocks) if blocks.is_empty() => return Ok(0),
+            candidate_blocks => candidate_blocks
+                .map(|blocks| (Self::json_array(&blocks.blocks), blocks.unscanned_after)),
+        };
+
         let mut key_fts_expression = String::new();
+        let mut first_keys_json = String::new();
         let (query, params) = Self::event_query(
             "SELECT COUNT(1) FROM starknet_events",
             from_block.as_ref(),
@@ -1150,6 +1535,8 @@ impl StarknetEventsTable {
             contract_address.as_ref(),
             keys,
             &mut key_fts_expression,
+            &mut first_keys_json,
+            candidate_blocks.as_ref(),
         );
 
         let count: usize = tx.query_row(&query, params.as_slice(), |row| row.get(0))?;
@@ -1157,8 +1544,99 @@ impl StarknetEventsTable {
         Ok(count)
     }
 
-    pub fn get_events<K: KeyFilter>(
+    /// Returns the blocks in range whose event bloom filters may match, or [None] if the filter
+    /// doesn't restrict the events, or the events are found by an index on their address.
+    fn candidate_blocks(
         tx: &Transaction<'_>,
+        from_block: Option<StarknetBlockNumber>,
+        to_block: Option<StarknetBlockNumber>,
+        contract_address: Option<&ContractAddress>,
+        keys: &dyn KeyFilter,
+    ) -> anyhow::Result<Option<CandidateBlocks>> {
+        // The indexes on the address and block number, or on the address, first key and block
+        // number, lead straight to the events of the contract, so reading the filters of the
+        // blocks in range first would only slow the query down, as would probing the index once
+        // per candidate block.
+        if contract_address.is_some() {
+            return Ok(None);
+        }
+
+        EventBloomTable::candidate_blocks(
+            tx,
+            from_block,
+            to_block,
+            contract_address,
+            &keys.key_groups(),
+        )
+    }
+
+    /// Encodes block numbers as a JSON array, for the `json_each` in the query.
+    fn json_array(blocks: &[StarknetBlockNumber]) -> String {
+        let blocks = blocks
+            .iter()
+            .map(|block| block.get().to_string())
+            .collect::<Vec<_>>();
+        format!("[{}]", blocks.join(","))
+    }
+
+    /// Estimates the cost of querying events in the given block range, which are inclusive
+    /// and default to the whole chain.
+    ///
+    /// This is cheap as it relies on event rowids increasing with block number, so the number of
+    /// events in a range follows from the first and last rowid in it.
+    pub fn estimate_query_cost(
+        tx: &impl crate::ReadAccess,
+        from_block: Option<StarknetBlockNumber>,
+        to_block: Option<StarknetBlockNumber>,
+    ) -> anyhow::Result<EventQueryCost> {
+        let latest = match StarknetBlocksTable::get_latest_number(tx)? {
+            Some(latest) => latest,
+            None => {
+                return Ok(EventQueryCost {
+                    blocks: 0,
+                    events: 0,
+                })
+            }
+        };
+
+        let from_block = from_block.unwrap_or(StarknetBlockNumber::GENESIS);
+        let to_block = to_block.map(|to| to.min(latest)).unwrap_or(latest);
+        if from_block > to_block {
+            return Ok(EventQueryCost {
+                blocks: 0,
+                events: 0,
+            });
+        }
+
+        let blocks = to_block.get() - from_block.get() + 1;
+
+        let first: Option<i64> = tx
+            .query_row(
+                "SELECT rowid FROM starknet_events WHERE block_number >= ? ORDER BY block_number ASC, rowid ASC LIMIT 1",
+                [from_block],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying first event in range")?;
+        let last: Option<i64> = tx
+            .query_row(
+                "SELECT rowid FROM starknet_events WHERE block_number <= ? ORDER BY block_number DESC, rowid DESC LIMIT 1",
+                [to_block],
+                |row| row.get(0),
+            )
+            .optional()
+            .context("Querying last event in range")?;
+
+        let events = match (first, last) {
+            (Some(first), Some(last)) if last >= first => (last - first + 1) as u64,
+            _ => 0,
+        };
+
+        Ok(EventQueryCost { blocks, events })
+    }
+
+    pub fn get_events<K: KeyFilter>(
+        tx: &impl crate::ReadAccess,
         filter: &StarknetEventFilter<K>,
     ) -> anyhow::Result<PageOfEvents> {
         if filter.page_size > Self::PAGE_SIZE_LIMIT {
@@ -1181,7 +1659,26 @@ impl StarknetEventsTable {
                INNER JOIN starknet_transactions ON (starknet_transactions.hash = starknet_events.transaction_hash)
                INNER JOIN starknet_blocks ON (starknet_blocks.number = starknet_events.block_number)"#;
 
+        let candidate_blocks = match Self::candidate_blocks(
+            tx,
+            filter.from_block,
+            filter.to_block,
+            filter.contract_address.as_ref(),
+            &filter.keys,
+        )? {
+            // No block in range can have a matching event.
+            Some(blocks) if blocks.is_empty() => {
+                return Ok(PageOfEvents {
+                    events: vec![],
+                    is_last_page: true,
+                })
+            }
+            candidate_blocks => candidate_blocks
+                .map(|blocks| (Self::json_array(&blocks.blocks), blocks.unscanned_after)),
+        };
+
         let mut key_fts_expression = String::new();
+        let mut first_keys_json = String::new();
 
         let (mut base_query, mut params) = Self::event_query(
             base_query,
@@ -1190,6 +1687,8 @@ impl StarknetEventsTable {
             filter.contract_address.as_ref(),
             &filter.keys,
             &mut key_fts_expression,
+            &mut first_keys_json,
+            candidate_blocks.as_ref(),
         );
 
         // We have to be able to decide if there are more events. We request one extra event
@@ -1343,22 +1842,22 @@ impl ContractsStateTable {
         root: ContractRoot,
         nonce: ContractNonce,
     ) -> anyhow::Result<()> {
-        transaction.execute(
+        let mut stmt = transaction.prepare_cached(
             "INSERT OR IGNORE INTO contract_states (state_hash, hash, root, nonce) VALUES (:state_hash, :hash, :root, :nonce)",
-            named_params! {
-                ":state_hash": state_hash,
-                ":hash": hash,
-                ":root": root,
-                ":nonce": nonce,
-            },
         )?;
+        stmt.execute(named_params! {
+            ":state_hash": state_hash,
+            ":hash": hash,
+            ":root": root,
+            ":nonce": nonce,
+        })?;
         Ok(())
     }
 
     /// Gets the root associated with the given state hash, or [None]
     /// if it does not exist.
     pub fn get_root(
-        transaction: &Transaction<'_>,
+        transaction: &impl crate::ReadAccess,
         state_hash: ContractStateHash,
     ) -> anyhow::Result<Option<ContractRoot>> {
         transaction
@@ -1376,7 +1875,7 @@ impl ContractsStateTable {
     /// Gets the nonce associated with the given state hash, or [None]
     /// if it does not exist.
     pub fn get_nonce(
-        transaction: &Transaction<'_>,
+        transaction: &impl crate::ReadAccess,
         state_hash: ContractStateHash,
     ) -> anyhow::Result<Option<ContractNonce>> {
         transaction
@@ -1394,7 +1893,7 @@ impl ContractsStateTable {
     /// Gets the root and nonce associated with the given state hash, or [None]
     /// if it does not exist.
     pub fn get_root_class_hash_and_nonce(
-        transaction: &Transaction<'_>,
+        transaction: &impl crate::ReadAccess,
         state_hash: ContractStateHash,
     ) -> anyhow::Result<Option<(ContractRoot, ClassHash, ContractNonce)>> {
         transaction
@@ -1447,7 +1946,7 @@ impl StarknetStateUpdatesTable {
 
     /// Gets a StarkNet state update for block.
     pub fn get(
-        tx: &Transaction<'_>,
+        tx: &impl crate::ReadAccess,
         block_hash: StarknetBlockHash,
     ) -> anyhow::Result<Option<StateUpdate>> {
         let mut stmt = tx
@@ -1794,13 +2293,36 @@ mod tests {
                 );
             }
         }
-    }
 
-    mod starknet_blocks {
-        use super::*;
-        use crate::test_utils::{self, BlockWithCommitment};
+        #[test]
+        fn get_preceding_number() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
 
-        fn create_blocks() -> [BlockWithCommitment; test_utils::NUM_BLOCKS] {
+            let updates = create_updates();
+            for update in [&updates[0], &updates[2]] {
+                L1StateTable::upsert(&tx, update).unwrap();
+            }
+
+            let preceding = |block| L1StateTable::get_preceding_number(&tx, block).unwrap();
+            assert_eq!(preceding(updates[0].block_number), None);
+            assert_eq!(
+                preceding(updates[2].block_number),
+                Some(updates[0].block_number)
+            );
+            assert_eq!(
+                preceding(updates[2].block_number + 1),
+                Some(updates[2].block_number)
+            );
+        }
+    }
+
+    mod starknet_blocks {
+        use super::*;
+        use crate::test_utils::{self, BlockWithCommitment};
+
+        fn create_blocks() -> [BlockWithCommitment; test_utils::NUM_BLOCKS] {
             test_utils::create_blocks()
         }
 
@@ -2191,6 +2713,32 @@ mod tests {
                 // we should not have any nulls
                 assert_eq!(rows.len(), 2, "nulls were not expected in {rows:?}");
             }
+
+            #[test]
+            fn get_starknet_version() {
+                let storage = Storage::in_memory().unwrap();
+                let mut connection = storage.connection().unwrap();
+                let tx = connection.transaction().unwrap();
+
+                let blocks = super::create_blocks();
+                for (block, version) in blocks.iter().zip([None, Some("0.9.1")]) {
+                    StarknetBlocksTable::insert(
+                        &tx,
+                        &block.block,
+                        version,
+                        StorageCommitment::ZERO,
+                        ClassCommitment::ZERO,
+                    )
+                    .unwrap();
+                }
+
+                let version = |block: &super::BlockWithCommitment| {
+                    StarknetBlocksTable::get_starknet_version(&tx, block.block.number).unwrap()
+                };
+                assert_eq!(version(&blocks[0]), None);
+                assert_eq!(version(&blocks[1]), Some("0.9.1".to_owned()));
+                assert_eq!(version(&blocks[2]), None);
+            }
         }
 
         mod get_latest_number {
@@ -2460,6 +3008,8 @@ mod tests {
                     l2_to_l1_messages: Vec::new(),
                     transaction_hash: transactions[0].hash(),
                     transaction_index: pathfinder_common::StarknetTransactionIndex::new_or_panic(0),
+                    execution_status: Default::default(),
+                    revert_error: None,
                 },
                 transaction::Receipt {
                     actual_fee: None,
@@ -2476,6 +3026,8 @@ mod tests {
                     l2_to_l1_messages: Vec::new(),
                     transaction_hash: transactions[1].hash(),
                     transaction_index: pathfinder_common::StarknetTransactionIndex::new_or_panic(1),
+                    execution_status: Default::default(),
+                    revert_error: None,
                 },
             ];
 
@@ -2643,6 +3195,46 @@ mod tests {
             );
         }
 
+        #[test]
+        fn get_events_skips_blocks_by_bloom_filter() {
+            let (storage, test_data) = test_utils::setup_test_storage();
+            let emitted_events = test_data.events;
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let expected_event = &emitted_events[33];
+            let keys = V02KeyFilter(vec![expected_event.keys[0]]);
+
+            let filter = StarknetEventFilter {
+                from_block: None,
+                to_block: None,
+                contract_address: None,
+                keys: keys.clone(),
+                page_size: test_utils::NUM_EVENTS,
+                offset: 0,
+            };
+            let events = StarknetEventsTable::get_events(&tx, &filter).unwrap();
+            assert_eq!(events.events, vec![expected_event.clone()]);
+
+            // Without a filter, the block is treated as one without events.
+            tx.execute(
+                "DELETE FROM starknet_events_filters WHERE block_number = ?",
+                [expected_event.block_number],
+            )
+            .unwrap();
+
+            let events = StarknetEventsTable::get_events(&tx, &filter).unwrap();
+            assert_eq!(
+                events,
+                PageOfEvents {
+                    events: vec![],
+                    is_last_page: true,
+                }
+            );
+            let count = StarknetEventsTable::event_count(&tx, None, None, None, &keys).unwrap();
+            assert_eq!(count, 0);
+        }
+
         #[test]
         fn get_events_by_key_v02() {
             let (storage, test_data) = test_utils::setup_test_storage();
@@ -2717,6 +3309,119 @@ mod tests {
             );
         }
 
+        #[test]
+        fn get_events_by_first_key_of_contract() {
+            let (storage, test_data) = test_utils::setup_test_storage();
+            let emitted_events = test_data.events;
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let expected_event = &emitted_events[27];
+            let filter = StarknetEventFilter {
+                from_block: None,
+                to_block: None,
+                contract_address: Some(expected_event.from_address),
+                keys: V03KeyFilter(vec![vec![expected_event.keys[0]]]),
+                page_size: test_utils::NUM_EVENTS,
+                offset: 0,
+            };
+
+            let expected_events = emitted_events
+                .iter()
+                .filter(|event| {
+                    event.from_address == expected_event.from_address
+                        && event.keys[0] == expected_event.keys[0]
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            let events = StarknetEventsTable::get_events(&tx, &filter).unwrap();
+            assert_eq!(
+                events,
+                PageOfEvents {
+                    events: expected_events,
+                    is_last_page: true,
+                }
+            );
+
+            // combined with a restriction on the second key
+            let filter = StarknetEventFilter {
+                keys: V03KeyFilter(vec![
+                    vec![expected_event.keys[0]],
+                    vec![expected_event.keys[1]],
+                ]),
+                ..filter
+            };
+            let events = StarknetEventsTable::get_events(&tx, &filter).unwrap();
+            assert_eq!(
+                events,
+                PageOfEvents {
+                    events: vec![expected_event.clone()],
+                    is_last_page: true,
+                }
+            );
+
+            // the second key is not the first one
+            let filter = StarknetEventFilter {
+                keys: V03KeyFilter(vec![vec![expected_event.keys[1]]]),
+                ..filter
+            };
+            let events = StarknetEventsTable::get_events(&tx, &filter).unwrap();
+            assert_eq!(
+                events,
+                PageOfEvents {
+                    events: vec![],
+                    is_last_page: true,
+                }
+            );
+        }
+
+        #[test]
+        fn first_key_of_contract_is_looked_up_by_index() {
+            let (storage, test_data) = test_utils::setup_test_storage();
+            let expected_event = &test_data.events[27];
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let contract_address = Some(expected_event.from_address);
+            let keys = V03KeyFilter(vec![vec![expected_event.keys[0]]]);
+
+            // The filters of the blocks are not consulted.
+            let candidate_blocks = StarknetEventsTable::candidate_blocks(
+                &tx,
+                None,
+                None,
+                contract_address.as_ref(),
+                &keys,
+            )
+            .unwrap();
+            assert_eq!(candidate_blocks, None);
+
+            let mut key_fts_expression = String::new();
+            let mut first_keys_json = String::new();
+            let (query, params) = StarknetEventsTable::event_query(
+                "SELECT COUNT(1) FROM starknet_events",
+                None,
+                None,
+                contract_address.as_ref(),
+                &keys,
+                &mut key_fts_expression,
+                &mut first_keys_json,
+                None,
+            );
+
+            let mut statement = tx.prepare(&format!("EXPLAIN QUERY PLAN {query}")).unwrap();
+            let plan = statement
+                .query_map(params.as_slice(), |row| row.get::<_, String>("detail"))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert!(
+                plan.iter().any(|step| step
+                    .contains("USING INDEX starknet_events_from_address_key0_block_number")),
+                "{plan:?}"
+            );
+        }
+
         #[test]
         fn get_events_with_no_filter() {
             let (storage, test_data) = test_utils::setup_test_storage();
@@ -3004,6 +3709,62 @@ mod tests {
             assert_eq!(count, test_utils::EVENTS_PER_BLOCK);
         }
 
+        #[test]
+        fn estimate_query_cost() {
+            let (storage, _) = test_utils::setup_test_storage();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let cost = StarknetEventsTable::estimate_query_cost(&tx, None, None).unwrap();
+            assert_eq!(
+                cost,
+                EventQueryCost {
+                    blocks: test_utils::NUM_BLOCKS as u64,
+                    events: test_utils::NUM_EVENTS as u64,
+                }
+            );
+
+            let cost = StarknetEventsTable::estimate_query_cost(
+                &tx,
+                Some(StarknetBlockNumber::new_or_panic(1)),
+                Some(StarknetBlockNumber::new_or_panic(2)),
+            )
+            .unwrap();
+            assert_eq!(
+                cost,
+                EventQueryCost {
+                    blocks: 2,
+                    events: 2 * test_utils::EVENTS_PER_BLOCK as u64,
+                }
+            );
+
+            // The range is clamped to the latest block.
+            let cost = StarknetEventsTable::estimate_query_cost(
+                &tx,
+                Some(StarknetBlockNumber::new_or_panic(3)),
+                Some(StarknetBlockNumber::MAX),
+            )
+            .unwrap();
+            assert_eq!(
+                cost,
+                EventQueryCost {
+                    blocks: 1,
+                    events: test_utils::EVENTS_PER_BLOCK as u64,
+                }
+            );
+
+            let cost =
+                StarknetEventsTable::estimate_query_cost(&tx, Some(StarknetBlockNumber::MAX), None)
+                    .unwrap();
+            assert_eq!(
+                cost,
+                EventQueryCost {
+                    blocks: 0,
+                    events: 0,
+                }
+            );
+        }
+
         #[test]
         fn event_count_from_contract() {
             let (storage, test_data) = test_utils::setup_test_storage();
@@ -3115,4 +3876,258 @@ mod tests {
             }
         }
     }
+
+    mod starknet_transactions {
+        use super::*;
+        use crate::test_utils;
+        use pathfinder_common::{
+            felt, CallParam, EntryPoint, EthereumAddress, L1ToL2MessageNonce,
+            StarknetTransactionIndex, TransactionNonce, TransactionVersion,
+        };
+
+        #[test]
+        fn get_by_l1_to_l2_message() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let block = test_utils::create_blocks()[0].clone();
+            StarknetBlocksTable::insert(
+                &tx,
+                &block.block,
+                None,
+                block.storage_commitment,
+                block.class_commitment,
+            )
+            .unwrap();
+
+            let transaction_hash = StarknetTransactionHash(felt!("0x1234"));
+            let message = transaction::L1ToL2Message {
+                from_address: EthereumAddress(ethers::types::H160::from_low_u64_be(0xabc)),
+                payload: vec![],
+                selector: EntryPoint(felt!("0x1")),
+                to_address: ContractAddress::new_or_panic(felt!("0x2")),
+                nonce: Some(L1ToL2MessageNonce(felt!("0x3"))),
+            };
+            let transaction =
+                transaction::Transaction::L1Handler(transaction::L1HandlerTransaction {
+                    contract_address: message.to_address,
+                    entry_point_selector: message.selector,
+                    nonce: TransactionNonce(felt!("0x3")),
+                    calldata: vec![CallParam(felt!("0xabc"))],
+                    transaction_hash,
+                    version: TransactionVersion::ZERO,
+                });
+            let receipt = transaction::Receipt {
+                actual_fee: None,
+                events: vec![],
+                execution_resources: None,
+                l1_to_l2_consumed_message: Some(message.clone()),
+                l2_to_l1_messages: vec![],
+                transaction_hash,
+                transaction_index: StarknetTransactionIndex::new_or_panic(0),
+                execution_status: Default::default(),
+                revert_error: None,
+            };
+            StarknetTransactionsTable::upsert(
+                &tx,
+                block.block.hash,
+                block.block.number,
+                &[(transaction, receipt)],
+            )
+            .unwrap();
+
+            let result =
+                StarknetTransactionsTable::get_by_l1_to_l2_message(&tx, message.hash()).unwrap();
+            assert_eq!(
+                result,
+                Some((transaction_hash, block.block.number, block.block.hash))
+            );
+
+            let unknown = L1ToL2MessageHash(H256::from_low_u64_be(1));
+            let result = StarknetTransactionsTable::get_by_l1_to_l2_message(&tx, unknown).unwrap();
+            assert_eq!(result, None);
+
+            // The transaction is no longer part of the chain.
+            StarknetBlocksTable::reorg(&tx, block.block.number).unwrap();
+            let result =
+                StarknetTransactionsTable::get_by_l1_to_l2_message(&tx, message.hash()).unwrap();
+            assert_eq!(result, None);
+        }
+
+        #[test]
+        fn transaction_count() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let blocks = test_utils::create_blocks();
+            for block in &blocks[..2] {
+                StarknetBlocksTable::insert(
+                    &tx,
+                    &block.block,
+                    None,
+                    block.storage_commitment,
+                    block.class_commitment,
+                )
+                .unwrap();
+            }
+            let transactions = test_utils::create_transactions_and_receipts()
+                .into_iter()
+                .take(3)
+                .collect::<Vec<_>>();
+            StarknetTransactionsTable::upsert(
+                &tx,
+                blocks[0].block.hash,
+                blocks[0].block.number,
+                &transactions,
+            )
+            .unwrap();
+            StarknetTransactionsTable::upsert(
+                &tx,
+                blocks[1].block.hash,
+                blocks[1].block.number,
+                &[],
+            )
+            .unwrap();
+
+            let count = |block: StarknetBlocksBlockId| {
+                StarknetBlocksTable::get_transaction_count(&tx, block).unwrap()
+            };
+            assert_eq!(count(blocks[0].block.number.into()), Some(3));
+            assert_eq!(count(blocks[0].block.hash.into()), Some(3));
+            assert_eq!(count(StarknetBlocksBlockId::Latest), Some(0));
+            assert_eq!(count(blocks[2].block.number.into()), None);
+
+            // Blocks stored without a count fall back to counting their transactions.
+            tx.execute("UPDATE starknet_blocks SET transaction_count = NULL", [])
+                .unwrap();
+            assert_eq!(count(blocks[0].block.number.into()), Some(3));
+            assert_eq!(count(StarknetBlocksBlockId::Latest), Some(0));
+
+            let result = StarknetTransactionsTable::get_transaction_at_block(
+                &tx,
+                blocks[0].block.number.into(),
+                2,
+            )
+            .unwrap();
+            assert_eq!(result, Some(transactions[2].0.clone()));
+        }
+
+        #[test]
+        fn get_reverted() {
+            let storage = Storage::in_memory().unwrap();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let block = test_utils::create_blocks()[0].clone();
+            StarknetBlocksTable::insert(
+                &tx,
+                &block.block,
+                None,
+                block.storage_commitment,
+                block.class_commitment,
+            )
+            .unwrap();
+            CanonicalBlocksTable::insert(&tx, block.block.number, block.block.hash).unwrap();
+
+            let transactions = test_utils::create_transactions_and_receipts()
+                .into_iter()
+                .take(3)
+                .enumerate()
+                .map(|(i, (transaction, mut receipt))| {
+                    if i != 1 {
+                        receipt.execution_status = transaction::ExecutionStatus::Reverted;
+                        receipt.revert_error = Some(format!("Error {i}"));
+                    }
+                    (transaction, receipt)
+                })
+                .collect::<Vec<_>>();
+            StarknetTransactionsTable::upsert(
+                &tx,
+                block.block.hash,
+                block.block.number,
+                &transactions,
+            )
+            .unwrap();
+
+            let result = StarknetTransactionsTable::get_reverted(
+                &tx,
+                block.block.number,
+                block.block.number,
+            )
+            .unwrap();
+            let expected = [0, 2]
+                .into_iter()
+                .map(|i| RevertedTransaction {
+                    transaction_hash: transactions[i].1.transaction_hash,
+                    block_number: block.block.number,
+                    revert_error: Some(format!("Error {i}")),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(result, expected);
+
+            // The transactions are no longer part of the chain.
+            CanonicalBlocksTable::reorg(&tx, block.block.number).unwrap();
+            let result = StarknetTransactionsTable::get_reverted(
+                &tx,
+                block.block.number,
+                block.block.number,
+            )
+            .unwrap();
+            assert_eq!(result, vec![]);
+        }
+
+        #[test]
+        fn get_receipts_range() {
+            let (storage, test_data) = test_utils::setup_test_storage();
+            let mut connection = storage.connection().unwrap();
+            let tx = connection.transaction().unwrap();
+
+            let receipts = |start: usize, index: u64, to: usize, limit: usize| {
+                StarknetTransactionsTable::get_receipts_range(
+                    &tx,
+                    test_data.blocks[start].block.number,
+                    index,
+                    test_data.blocks[to].block.number,
+                    limit,
+                )
+                .unwrap()
+                .into_iter()
+                .map(|transaction| transaction.receipt)
+                .collect::<Vec<_>>()
+            };
+
+            // Continues into the next block.
+            let start = test_utils::TRANSACTIONS_PER_BLOCK + 10;
+            assert_eq!(
+                receipts(1, 10, 2, 10),
+                test_data.receipts[start..start + 10]
+            );
+
+            // Ends with the last block of the range.
+            let start = 2 * test_utils::TRANSACTIONS_PER_BLOCK + 10;
+            let end = 3 * test_utils::TRANSACTIONS_PER_BLOCK;
+            assert_eq!(receipts(2, 10, 2, 100), test_data.receipts[start..end]);
+
+            let result = StarknetTransactionsTable::get_receipts_range(
+                &tx,
+                test_data.blocks[3].block.number,
+                0,
+                test_data.blocks[3].block.number,
+                1,
+            )
+            .unwrap();
+            assert_eq!(
+                result,
+                vec![BlockTransaction {
+                    block_number: test_data.blocks[3].block.number,
+                    block_hash: test_data.blocks[3].block.hash,
+                    index: 0,
+                    transaction: test_data.transactions[end].clone(),
+                    receipt: test_data.receipts[end].clone(),
+                }]
+            );
+        }
+    }
 }
@@ -0,0 +1,296 @@
+//! Typed transaction guards, created by [Storage::read](crate::Storage::read) and
+//! [Storage::write](crate::Storage::write).
+//!
+//! Both retry the whole transaction if the database is busy, which otherwise surfaces as an
+//! intermittent "database is locked" error whenever a reader and a writer collide.
+//!
+//! Table functions which only read take any [ReadAccess], which a [ReadTransaction] is limited
+//! to, so that writing through a [ReadTransaction] does not compile. They name the trait by its
+//! path instead of importing it, as its methods would otherwise shadow those of
+//! [rusqlite::Transaction] for the writing table functions next to them.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::ops::Deref;
+use std::time::Duration;
+
+use rusqlite::{CachedStatement, ErrorCode, MappedRows, Params, Row, Rows};
+
+/// Number of attempts made before a busy error is returned.
+const BUSY_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, which doubles for each further retry.
+const BUSY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// A transaction which cannot be committed, and which only gives [ReadAccess] to the database.
+///
+/// The connection is also put into SQLite's `query_only` mode for the lifetime of the
+/// transaction, in case a table function which writes is passed the underlying connection. The
+/// transaction is rolled back when dropped.
+pub struct ReadTransaction<'a>(pub(crate) rusqlite::Transaction<'a>);
+
+/// A transaction holding the database's write lock, which is committed by
+/// [Storage::write](crate::Storage::write) if its closure succeeds.
+pub struct WriteTransaction<'a>(pub(crate) rusqlite::Transaction<'a>);
+
+/// Read-only access to the database, which table functions that only read accept.
+///
+/// Only read-only statements can be prepared, and the prepared [ReadStatement]s can only be
+/// queried. The connection itself is not exposed outside of this crate.
+pub trait ReadAccess: sealed::Connection {
+    /// Prepares a statement, failing with [rusqlite::Error::InvalidQuery] if it could write.
+    ///
+    /// Statements are cached by the connection.
+    fn prepare(&self, sql: &str) -> rusqlite::Result<ReadStatement<'_>> {
+        let statement = self.connection(sealed::Token(())).prepare_cached(sql)?;
+        if !statement.readonly() {
+            return Err(rusqlite::Error::InvalidQuery);
+        }
+        Ok(ReadStatement(statement))
+    }
+
+    /// The same as [ReadAccess::prepare], as statements are always cached.
+    fn prepare_cached(&self, sql: &str) -> rusqlite::Result<ReadStatement<'_>> {
+        self.prepare(sql)
+    }
+
+    /// Runs a read-only query which is expected to return at least one row, and maps the first.
+    fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> rusqlite::Result<T>
+    where
+        P: Params,
+        F: FnOnce(&Row<'_>) -> rusqlite::Result<T>,
+    {
+        self.prepare(sql)?.query_row(params, f)
+    }
+}
+
+impl ReadTransaction<'_> {
+    /// See [ReadAccess::prepare].
+    pub fn prepare(&self, sql: &str) -> rusqlite::Result<ReadStatement<'_>> {
+        ReadAccess::prepare(self, sql)
+    }
+
+    /// See [ReadAccess::query_row].
+    pub fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> rusqlite::Result<T>
+    where
+        P: Params,
+        F: FnOnce(&Row<'_>) -> rusqlite::Result<T>,
+    {
+        ReadAccess::query_row(self, sql, params, f)
+    }
+}
+
+impl ReadAccess for rusqlite::Connection {}
+impl ReadAccess for rusqlite::Transaction<'_> {}
+impl ReadAccess for ReadTransaction<'_> {}
+impl ReadAccess for WriteTransaction<'_> {}
+impl<T: ReadAccess + ?Sized> ReadAccess for &T {}
+
+/// A read-only statement prepared by [ReadAccess::prepare].
+pub struct ReadStatement<'conn>(CachedStatement<'conn>);
+
+impl ReadStatement<'_> {
+    /// See [rusqlite::Statement::query].
+    pub fn query<P: Params>(&mut self, params: P) -> rusqlite::Result<Rows<'_>> {
+        self.0.query(params)
+    }
+
+    /// See [rusqlite::Statement::query_map].
+    pub fn query_map<T, P, F>(&mut self, params: P, f: F) -> rusqlite::Result<MappedRows<'_, F>>
+    where
+        P: Params,
+        F: FnMut(&Row<'_>) -> rusqlite::Result<T>,
+    {
+        self.0.query_map(params, f)
+    }
+
+    /// See [rusqlite::Statement::query_row].
+    pub fn query_row<T, P, F>(&mut self, params: P, f: F) -> rusqlite::Result<T>
+    where
+        P: Params,
+        F: FnOnce(&Row<'_>) -> rusqlite::Result<T>,
+    {
+        self.0.query_row(params, f)
+    }
+
+    /// See [rusqlite::Statement::exists].
+    pub fn exists<P: Params>(&mut self, params: P) -> rusqlite::Result<bool> {
+        self.0.exists(params)
+    }
+}
+
+mod sealed {
+    /// Only this crate can construct a token, so that only it can reach the connection of a
+    /// [ReadAccess](super::ReadAccess), even where the trait's methods are in scope.
+    pub struct Token(pub(super) ());
+
+    pub trait Connection {
+        fn connection(&self, token: Token) -> &rusqlite::Connection;
+    }
+
+    impl Connection for rusqlite::Connection {
+        fn connection(&self, _: Token) -> &rusqlite::Connection {
+            self
+        }
+    }
+
+    impl Connection for rusqlite::Transaction<'_> {
+        fn connection(&self, _: Token) -> &rusqlite::Connection {
+            self
+        }
+    }
+
+    impl Connection for super::ReadTransaction<'_> {
+        fn connection(&self, _: Token) -> &rusqlite::Connection {
+            &self.0
+        }
+    }
+
+    impl Connection for super::WriteTransaction<'_> {
+        fn connection(&self, _: Token) -> &rusqlite::Connection {
+            &self.0
+        }
+    }
+
+    impl<T: Connection + ?Sized> Connection for &T {
+        fn connection(&self, token: Token) -> &rusqlite::Connection {
+            (**self).connection(token)
+        }
+    }
+}
+
+impl<'a> Deref for WriteTransaction<'a> {
+    type Target = rusqlite::Transaction<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Runs `f` until it succeeds, fails for another reason than the database being busy, or has been
+/// attempted [BUSY_ATTEMPTS] times.
+///
+/// Retries back off exponentially, with a random jitter so that colliding transactions do not
+/// collide again. This blocks the thread, so it must not be called from an async context.
+pub(crate) fn retry_busy<T>(mut f: impl FnMut() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let mut backoff = BUSY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < BUSY_ATTEMPTS && is_busy(&e) => {
+                let delay = backoff + jitter(backoff);
+                tracing::debug!(%attempt, ?delay, "Database is busy, retrying transaction");
+                std::thread::sleep(delay);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_busy(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(error, _))
+                if matches!(error.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        )
+    })
+}
+
+/// A random duration of at most `max`.
+fn jitter(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let nanos = max.as_nanos() as u64;
+    Duration::from_nanos(random % nanos.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    fn busy() -> anyhow::Error {
+        let error = rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY);
+        anyhow::Error::new(rusqlite::Error::SqliteFailure(error, None)).context("Reading block")
+    }
+
+    #[test]
+    fn retries_while_busy() {
+        let mut attempts = 0;
+        let result = retry_busy(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(busy())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result = retry_busy(|| -> anyhow::Result<()> {
+            attempts += 1;
+            Err(busy())
+        });
+        assert!(is_busy(&result.unwrap_err()));
+        assert_eq!(attempts, BUSY_ATTEMPTS);
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        let mut attempts = 0;
+        let result = retry_busy(|| -> anyhow::Result<()> {
+            attempts += 1;
+            anyhow::bail!("Not busy")
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn read_rejects_writes() {
+        let storage = Storage::in_memory().unwrap();
+
+        let result = storage.read(|tx| {
+            tx.query_row("CREATE TABLE t (x INTEGER)", [], |_| Ok(()))?;
+            Ok(())
+        });
+        assert!(matches!(
+            result.unwrap_err().downcast_ref(),
+            Some(rusqlite::Error::InvalidQuery)
+        ));
+
+        // The connection is writable again once returned to the pool.
+        storage
+            .write(|tx| {
+                tx.execute("CREATE TABLE t (x INTEGER)", [])?;
+                Ok(())
+            })
+            .unwrap();
+        let count = storage
+            .read(|tx| {
+                let count =
+                    tx.query_row("SELECT COUNT(*) FROM t", [], |row| row.get::<_, i64>(0))?;
+                Ok(count)
+            })
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn write_rolls_back_on_error() {
+        let storage = Storage::in_memory().unwrap();
+
+        let result = storage.write(|tx| {
+            tx.execute("CREATE TABLE t (x INTEGER)", [])?;
+            anyhow::bail!("Aborted")
+        });
+        assert!(result.is_err());
+
+        let result = storage.read(|tx| {
+            tx.query_row("SELECT COUNT(*) FROM t", [], |_| Ok(()))?;
+            Ok(())
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Pruning of historical state trie nodes.
+//!
+//! Every block commits new roots of the storage commitment, class commitment and contract
+//! storage tries, each of which holds a reference to its root node. The roots which a block
+//! supersedes are queued by [TreePruningTable::supersede], and once the block falls out of the
+//! retained range their references are released by [TreePruningTable::prune], which deletes the
+//! nodes no longer reachable from any retained root.
+//!
+//! The roots which a block commits are recorded by [TreePruningTable::commit] while the block is
+//! retained, so that [TreePruningTable::reorg] can release them if the block is reorged away.
+//!
+//! Only roots superseded while pruning is enabled are ever released, so that enabling it on an
+//! existing database keeps the state of the blocks synced before. The blocks whose state was
+//! released are tracked as ranges, see [TreePruningTable::is_pruned].
+use anyhow::Context;
+use pathfinder_common::StarknetBlockNumber;
+use rusqlite::{named_params, Transaction};
+use stark_hash::Felt;
+
+use crate::merkle_tree::RcNodeStorage;
+use crate::{StarknetBlocksBlockId, StarknetBlocksTable};
+
+/// The state tries whose nodes are pruned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrunedTree {
+    StorageCommitment,
+    ClassCommitment,
+    ContractStorage,
+}
+
+impl PrunedTree {
+    fn table(self) -> &'static str {
+        match self {
+            PrunedTree::StorageCommitment => "tree_global",
+            PrunedTree::ClassCommitment => "tree_class",
+            PrunedTree::ContractStorage => "tree_contracts",
+        }
+    }
+}
+
+/// Tracks the retained historical state and the trie roots which are pending pruning.
+pub struct TreePruningTable;
+
+impl TreePruningTable {
+    /// Sets the number of blocks before the latest one whose state is retained, or [None] to
+    /// retain the state of all blocks.
+    pub fn set_retention(tx: &Transaction<'_>, retained_blocks: Option<u64>) -> anyhow::Result<()> {
+        tx.execute(
+            "UPDATE tree_pruning SET retained_blocks = ? WHERE idx = 1",
+            [retained_blocks],
+        )
+        .context("Updating retained blocks")?;
+
+        if retained_blocks.is_none() {
+            // Nothing would release the queued roots anymore.
+            tx.execute("DELETE FROM tree_pruning_roots", [])
+                .context("Clearing superseded roots")?;
+            tx.execute("DELETE FROM tree_pruning_commits", [])
+                .context("Clearing committed roots")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of blocks before the latest one whose state is retained, which is
+    /// [None] if all state is retained.
+    pub fn get_retention(tx: &impl crate::ReadAccess) -> anyhow::Result<Option<u64>> {
+        tx.query_row(
+            "SELECT retained_blocks FROM tree_pruning WHERE idx = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Querying retained blocks")
+    }
+
+    /// Returns true if the state of the `block` was pruned. Blocks which do not exist are not
+    /// pruned, and neither are blocks synced before pruning was enabled.
+    pub fn is_pruned(
+        tx: &impl crate::ReadAccess,
+        block: StarknetBlocksBlockId,
+    ) -> anyhow::Result<bool> {
+        let number = match block {
+            StarknetBlocksBlockId::Number(number) => number,
+            StarknetBlocksBlockId::Hash(hash) => match StarknetBlocksTable::get_number(tx, hash)? {
+                Some(number) => number,
+                None => return Ok(false),
+            },
+            StarknetBlocksBlockId::Latest => return Ok(false),
+        };
+
+        tx.query_row(
+            r"SELECT EXISTS(
+                SELECT 1 FROM tree_pruning_ranges
+                WHERE first_block <= :block AND last_block >= :block
+            )",
+            named_params! { ":block": number },
+            |row| row.get(0),
+        )
+        .context("Querying pruned blocks")
+    }
+
+    /// Queues the `root` of the `tree`, which `block` superseded, for pruning once the state
+    /// of the block before it is no longer retained. Does nothing if pruning is disabled.
+    pub fn supersede(
+        tx: &Transaction<'_>,
+        block: StarknetBlockNumber,
+        tree: PrunedTree,
+        root: Felt,
+    ) -> anyhow::Result<()> {
+        Self::record(tx, "tree_pruning_roots", block, tree, root)
+            .context("Queueing superseded root")
+    }
+
+    /// Records the `root` of the `tree`, which `block` committed, so that it is released if the
+    /// block is reorged away. Does nothing if pruning is disabled.
+    pub fn commit(
+        tx: &Transaction<'_>,
+        block: StarknetBlockNumber,
+        tree: PrunedTree,
+        root: Felt,
+    ) -> anyhow::Result<()> {
+        Self::record(tx, "tree_pruning_commits", block, tree, root)
+            .context("Recording committed root")
+    }
+
+    fn record(
+        tx: &Transaction<'_>,
+        table: &str,
+        block: StarknetBlockNumber,
+        tree: PrunedTree,
+        root: Felt,
+    ) -> anyhow::Result<()> {
+        // Empty tries have no nodes.
+        if root == Felt::ZERO || Self::get_retention(tx)?.is_none() {
+            return Ok(());
+        }
+
+        tx.execute(
+            &format!(
+                r"INSERT INTO {table} (block_number, tree, root)
+                VALUES (:block_number, :tree, :root)"
+            ),
+            named_params! {
+                ":block_number": block,
+                ":tree": tree.table(),
+                ":root": &root.to_be_bytes()[..],
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the roots recorded in `table` for the blocks selected by `condition`, such as
+    /// `block_number <= ?`.
+    fn roots(
+        tx: &Transaction<'_>,
+        table: &str,
+        condition: &str,
+        block: StarknetBlockNumber,
+    ) -> anyhow::Result<Vec<(StarknetBlockNumber, String, Felt)>> {
+        let mut stmt = tx
+            .prepare_cached(&format!(
+                "SELECT block_number, tree, root FROM {table} WHERE {condition}"
+            ))
+            .context("Preparing roots query")?;
+        let roots = stmt
+            .query_map([block], |row| {
+                let block: StarknetBlockNumber = row.get(0)?;
+                let tree: String = row.get(1)?;
+                let root = row.get_ref_unwrap(2).as_blob()?;
+                let root = Felt::from_be_slice(root).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        2,
+                        rusqlite::types::Type::Blob,
+                        Box::new(e),
+                    )
+                })?;
+                Ok((block, tree, root))
+            })
+            .context("Querying roots")?
+            .collect::<Result<Vec<_>, _>>()
+            .context("Iterating over roots")?;
+
+        Ok(roots)
+    }
+
+    /// Releases one reference to each of the `roots`, deleting the nodes which are no longer
+    /// reachable.
+    fn release(
+        tx: &Transaction<'_>,
+        roots: &[(StarknetBlockNumber, String, Felt)],
+    ) -> anyhow::Result<()> {
+        for (_, tree, root) in roots {
+            RcNodeStorage::open(tree, tx)
+                .with_context(|| format!("Opening {tree}"))?
+                .decrement_ref_count(*root)
+                .with_context(|| format!("Releasing root {root} of {tree}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Marks the state of `block` as pruned, extending the range which ends at the block before
+    /// it if there is one.
+    fn mark_pruned(tx: &Transaction<'_>, block: u64) -> anyhow::Result<()> {
+        let extended = match block.checked_sub(1) {
+            Some(previous) => tx
+                .execute(
+                    "UPDATE tree_pruning_ranges SET last_block = ? WHERE last_block = ?",
+                    [block, previous],
+                )
+                .context("Extending pruned range")?,
+            None => 0,
+        };
+        if extended == 0 {
+            tx.execute(
+                r"INSERT INTO tree_pruning_ranges (first_block, last_block) VALUES (?1, ?1)
+                ON CONFLICT DO NOTHING",
+                [block],
+            )
+            .context("Inserting pruned range")?;
+        }
+
+        Ok(())
+    }
+
+    /// Prunes the trie nodes which are only reachable from the state of blocks which are no
+    /// longer retained now that `head` is the latest block. Returns the number of released roots.
+    pub fn prune(tx: &Transaction<'_>, head: StarknetBlockNumber) -> anyhow::Result<usize> {
+        let retained_blocks = match Self::get_retention(tx)? {
+            Some(retained_blocks) => retained_blocks,
+            None => return Ok(0),
+        };
+        // The roots superseded by this block and before belong to the state of blocks before
+        // the retained range.
+        let horizon = match head.get().checked_sub(retained_blocks) {
+            Some(horizon) => StarknetBlockNumber::new_or_panic(horizon),
+            None => return Ok(0),
+        };
+
+        let roots = Self::roots(tx, "tree_pruning_roots", "block_number <= ?", horizon)
+            .context("Querying superseded roots")?;
+        Self::release(tx, &roots)?;
+
+        tx.execute(
+            "DELETE FROM tree_pruning_roots WHERE block_number <= ?",
+            [horizon],
+        )
+        .context("Deleting released roots")?;
+        // The blocks up to the horizon can no longer be reorged away.
+        tx.execute(
+            "DELETE FROM tree_pruning_commits WHERE block_number <= ?",
+            [horizon],
+        )
+        .context("Deleting committed roots")?;
+
+        // Releasing a root superseded by a block prunes the state of the block before it.
+        let mut pruned = roots
+            .iter()
+            .filter_map(|(block, _, _)| block.get().checked_sub(1))
+            .collect::<Vec<_>>();
+        pruned.sort_unstable();
+        pruned.dedup();
+        for block in pruned {
+            Self::mark_pruned(tx, block)?;
+        }
+
+        Ok(roots.len())
+    }
+
+    /// Releases the roots committed by the blocks from `reorg_tail` onwards, which are being
+    /// reorged away, so that their nodes are deleted.
+    ///
+    /// The roots these blocks superseded are dequeued without being released, as they are either
+    /// the roots of the new head or were committed by the reorged blocks themselves.
+    ///
+    /// Fails if pruning is enabled and the reorg is deeper than the retained blocks, whose
+    /// committed roots are the only ones recorded.
+    pub fn reorg(tx: &Transaction<'_>, reorg_tail: StarknetBlockNumber) -> anyhow::Result<()> {
+        if let Some(retained_blocks) = Self::get_retention(tx)? {
+            let head = StarknetBlocksTable::get_latest_number(tx).context("Query latest block")?;
+            if let Some(head) = head {
+                anyhow::ensure!(
+                    reorg_tail.get() + retained_blocks > head.get(),
+                    "Reorg to block {reorg_tail} is deeper than the state retained for the \
+                    {retained_blocks} blocks before the latest block {head}"
+                );
+            }
+        }
+
+        let roots = Self::roots(tx, "tree_pruning_commits", "block_number >= ?", reorg_tail)
+            .context("Querying committed roots of reorged blocks")?;
+        Self::release(tx, &roots)?;
+
+        tx.execute(
+            "DELETE FROM tree_pruning_commits WHERE block_number >= ?",
+            [reorg_tail],
+        )
+        .context("Deleting committed roots of reorged blocks")?;
+        tx.execute(
+            "DELETE FROM tree_pruning_roots WHERE block_number >= ?",
+            [reorg_tail],
+        )
+        .context("Deleting superseded roots of reorged blocks")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::{PersistedBinaryNode, PersistedNode};
+    use crate::Storage;
+
+    /// Commits a root with a single binary node to the `tree`, like committing a trie does.
+    fn commit(tx: &Transaction<'_>, tree: PrunedTree, root: Felt) {
+        let storage = RcNodeStorage::open(tree.table(), tx).unwrap();
+        storage
+            .upsert(
+                root,
+                PersistedNode::Binary(PersistedBinaryNode {
+                    left: Felt::from_hex_str("0x1").unwrap(),
+                    right: Felt::from_hex_str("0x2").unwrap(),
+                }),
+            )
+            .unwrap();
+        storage.increment_ref_count(root).unwrap();
+    }
+
+    fn exists(tx: &Transaction<'_>, tree: PrunedTree, root: Felt) -> bool {
+        RcNodeStorage::open(tree.table(), tx)
+            .unwrap()
+            .get(root)
+            .unwrap()
+            .is_some()
+    }
+
+    #[test]
+    fn prune() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let tree = PrunedTree::StorageCommitment;
+        let roots = (0..4)
+            .map(|i| Felt::from_hex_str(&format!("0xabc{i}")).unwrap())
+            .collect::<Vec<_>>();
+
+        // The root of each block's state is superseded by the next block, where the root
+        // superseded by block 1 is not recorded without pruning.
+        commit(&tx, tree, roots[0]);
+        commit(&tx, tree, roots[1]);
+        TreePruningTable::supersede(&tx, StarknetBlockNumber::new_or_panic(1), tree, roots[0])
+            .unwrap();
+
+        TreePruningTable::set_retention(&tx, Some(1)).unwrap();
+        for i in 2..4 {
+            commit(&tx, tree, roots[i]);
+            let block = StarknetBlockNumber::new_or_panic(i as u64);
+            TreePruningTable::supersede(&tx, block, tree, roots[i - 1]).unwrap();
+        }
+
+        // Block 3 is the latest, so that the state of blocks 2 and 3 is retained.
+        let released = TreePruningTable::prune(&tx, StarknetBlockNumber::new_or_panic(3)).unwrap();
+        assert_eq!(released, 1);
+        assert!(exists(&tx, tree, roots[0]));
+        assert!(!exists(&tx, tree, roots[1]));
+        assert!(exists(&tx, tree, roots[2]));
+        assert!(exists(&tx, tree, roots[3]));
+
+        let block =
+            |number| StarknetBlocksBlockId::Number(StarknetBlockNumber::new_or_panic(number));
+        // The state of block 0 was synced before pruning was enabled, and is kept.
+        assert!(!TreePruningTable::is_pruned(&tx, block(0)).unwrap());
+        assert!(TreePruningTable::is_pruned(&tx, block(1)).unwrap());
+        assert!(!TreePruningTable::is_pruned(&tx, block(2)).unwrap());
+        assert!(!TreePruningTable::is_pruned(&tx, StarknetBlocksBlockId::Latest).unwrap());
+    }
+
+    #[test]
+    fn reorg() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let tree = PrunedTree::ContractStorage;
+        let old_root = Felt::from_hex_str("0xabc").unwrap();
+        let new_root = Felt::from_hex_str("0xdef").unwrap();
+        commit(&tx, tree, old_root);
+
+        // Block 5 supersedes the old root with the new one.
+        TreePruningTable::set_retention(&tx, Some(0)).unwrap();
+        commit(&tx, tree, new_root);
+        let block = StarknetBlockNumber::new_or_panic(5);
+        TreePruningTable::supersede(&tx, block, tree, old_root).unwrap();
+        TreePruningTable::commit(&tx, block, tree, new_root).unwrap();
+
+        // Reorging block 5 away releases the root it committed, but keeps the one it superseded.
+        TreePruningTable::reorg(&tx, block).unwrap();
+        assert!(exists(&tx, tree, old_root));
+        assert!(!exists(&tx, tree, new_root));
+
+        let released = TreePruningTable::prune(&tx, block).unwrap();
+        assert_eq!(released, 0);
+        assert!(exists(&tx, tree, old_root));
+    }
+}
@@ -0,0 +1,262 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use lru::LruCache;
+use stark_hash::Felt;
+
+use crate::merkle_tree::PersistedNode;
+
+const METRIC_HITS: &str = "trie_node_cache_hits_total";
+const METRIC_MISSES: &str = "trie_node_cache_misses_total";
+
+/// Number of independently locked parts of the process-wide cache, so that sync and the RPC API
+/// rarely wait for each other.
+const SHARDS: usize = 16;
+
+lazy_static::lazy_static! {
+    static ref GLOBAL: TrieNodeCache = TrieNodeCache::new(0, SHARDS);
+}
+
+thread_local! {
+    /// The nodes deleted by the transaction open on this thread, which are evicted once it commits.
+    /// They are not served to the transaction itself in the meantime.
+    static PENDING_EVICTIONS: RefCell<HashSet<Key>> = RefCell::new(HashSet::new());
+}
+
+/// A size-limited cache of the state trie nodes read from the database, which saves the repeated
+/// reads of the upper nodes of the tries when applying state updates and serving state queries.
+///
+/// A node is identified by its hash, which commits to its content, so a cached node is valid in
+/// every transaction and never has to be invalidated, although nodes are removed once their
+/// deletion from the database is committed. Until then, the transactions which can still read
+/// them keep being served from the cache. A reader of an older snapshot may cache a deleted node
+/// again, so nodes are only served for reads, while deleting nodes reads them from the database.
+/// The least recently used node is evicted once the cache is full.
+///
+/// Tries are opened from bare transactions, so [RcNodeStorage](crate::merkle_tree::RcNodeStorage)
+/// uses a single cache per process, which is shared by sync and the RPC API. It is disabled until
+/// sized with [TrieNodeCache::configure].
+///
+/// Lookups are counted by the `trie_node_cache_hits_total` and `trie_node_cache_misses_total`
+/// metrics, labelled with the table of the trie.
+pub struct TrieNodeCache(Vec<Mutex<Option<LruCache<Key, PersistedNode>>>>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    table: &'static str,
+    hash: Felt,
+}
+
+impl TrieNodeCache {
+    pub const DEFAULT_CAPACITY: usize = 100_000;
+
+    /// Creates a cache which holds up to `capacity` nodes, split evenly across `shards`. A
+    /// capacity of zero disables caching.
+    fn new(capacity: usize, shards: usize) -> Self {
+        let cache = Self((0..shards).map(|_| Mutex::new(None)).collect());
+        cache.resize(capacity);
+        cache
+    }
+
+    /// Sizes the process-wide cache to hold up to `capacity` nodes, dropping all cached nodes.
+    /// A capacity of zero disables caching.
+    pub fn configure(capacity: usize) {
+        GLOBAL.resize(capacity);
+    }
+
+    fn resize(&self, capacity: usize) {
+        let per_shard = (capacity + self.0.len() - 1) / self.0.len();
+        for shard in &self.0 {
+            let mut shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+            *shard = NonZeroUsize::new(per_shard).map(LruCache::new);
+        }
+    }
+
+    /// The process-wide cache.
+    pub(crate) fn global() -> &'static Self {
+        &GLOBAL
+    }
+
+    /// Returns true if the process-wide cache holds the node of the trie in `table` with `hash`.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn contains(table: &'static str, hash: Felt) -> bool {
+        let key = Key { table, hash };
+        let shard = GLOBAL.shard(&key).lock().unwrap_or_else(|e| e.into_inner());
+        shard.as_ref().map_or(false, |cache| cache.contains(&key))
+    }
+
+    fn shard(&self, key: &Key) -> &Mutex<Option<LruCache<Key, PersistedNode>>> {
+        let byte = key.hash.as_be_bytes()[31] as usize;
+        &self.0[byte % self.0.len()]
+    }
+
+    /// Returns the cached node of the trie in `table` with `hash`.
+    pub(crate) fn get(&self, table: &'static str, hash: Felt) -> Option<PersistedNode> {
+        let key = Key { table, hash };
+        let node = if PENDING_EVICTIONS.with(|pending| pending.borrow().contains(&key)) {
+            None
+        } else {
+            let mut cache = self.shard(&key).lock().unwrap_or_else(|e| e.into_inner());
+            cache.as_mut()?.get(&key).cloned()
+        };
+
+        match node {
+            Some(_) => metrics::increment_counter!(METRIC_HITS, "table" => table),
+            None => metrics::increment_counter!(METRIC_MISSES, "table" => table),
+        }
+
+        node
+    }
+
+    /// Caches a node of the trie in `table`, which was read from the database.
+    pub(crate) fn insert(&self, table: &'static str, hash: Felt, node: PersistedNode) {
+        let key = Key { table, hash };
+        let mut cache = self.shard(&key).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cache) = cache.as_mut() {
+            cache.put(key, node);
+        }
+    }
+
+    /// Removes a node of the trie in `table`.
+    fn remove(&self, key: &Key) {
+        let mut cache = self.shard(key).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cache) = cache.as_mut() {
+            cache.pop(key);
+        }
+    }
+
+    /// Removes a node of the trie in `table` from the process-wide cache once the transaction on
+    /// `connection` which deletes it from the database commits.
+    pub(crate) fn remove_on_commit(
+        connection: &rusqlite::Connection,
+        table: &'static str,
+        hash: Felt,
+    ) {
+        // The hooks only use thread locals and the process-wide cache, so installing them again
+        // for every deletion is as good as once per connection.
+        connection.commit_hook(Some(|| {
+            PENDING_EVICTIONS.with(|pending| {
+                pending
+                    .borrow_mut()
+                    .drain(..)
+                    .for_each(|key| GLOBAL.remove(&key))
+            });
+            // Let the transaction commit.
+            false
+        }));
+        connection.rollback_hook(Some(|| {
+            PENDING_EVICTIONS.with(|pending| pending.borrow_mut().clear())
+        }));
+
+        PENDING_EVICTIONS.with(|pending| pending.borrow_mut().insert(Key { table, hash }));
+    }
+}
+
+impl std::fmt::Debug for TrieNodeCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let len = self
+            .0
+            .iter()
+            .map(|shard| {
+                let shard = shard.lock().unwrap_or_else(|e| e.into_inner());
+                shard.as_ref().map(|c| c.len()).unwrap_or_default()
+            })
+            .sum::<usize>();
+        f.debug_struct("TrieNodeCache").field("len", &len).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle_tree::PersistedBinaryNode;
+
+    const TABLE: &str = "tree_global";
+
+    fn node(value: u64) -> PersistedNode {
+        PersistedNode::Binary(PersistedBinaryNode {
+            left: Felt::from(value),
+            right: Felt::from(value + 1),
+        })
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = TrieNodeCache::new(2, 1);
+        cache.insert(TABLE, Felt::from(1u64), node(1));
+        cache.insert(TABLE, Felt::from(2u64), node(2));
+
+        // Touch the first node so that the second one is evicted instead.
+        assert_eq!(cache.get(TABLE, Felt::from(1u64)), Some(node(1)));
+        cache.insert(TABLE, Felt::from(3u64), node(3));
+
+        assert_eq!(cache.get(TABLE, Felt::from(1u64)), Some(node(1)));
+        assert_eq!(cache.get(TABLE, Felt::from(2u64)), None);
+        assert_eq!(cache.get(TABLE, Felt::from(3u64)), Some(node(3)));
+
+        cache.remove(&Key {
+            table: TABLE,
+            hash: Felt::from(1u64),
+        });
+        assert_eq!(cache.get(TABLE, Felt::from(1u64)), None);
+    }
+
+    #[test]
+    fn evicts_once_deletion_commits() {
+        TrieNodeCache::configure(TrieNodeCache::DEFAULT_CAPACITY);
+        let hash = Felt::from(0xe71c7u64);
+        let cached = || TrieNodeCache::contains(TABLE, hash);
+
+        let mut connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute("CREATE TABLE nodes (hash BLOB)", [])
+            .unwrap();
+
+        // Rolled back deletions keep the node.
+        TrieNodeCache::global().insert(TABLE, hash, node(1));
+        let tx = connection.transaction().unwrap();
+        tx.execute("INSERT INTO nodes VALUES (1)", []).unwrap();
+        TrieNodeCache::remove_on_commit(&tx, TABLE, hash);
+        assert!(cached());
+        tx.rollback().unwrap();
+        assert!(cached());
+
+        let tx = connection.transaction().unwrap();
+        tx.execute("INSERT INTO nodes VALUES (1)", []).unwrap();
+        TrieNodeCache::remove_on_commit(&tx, TABLE, hash);
+        assert!(cached());
+        // Though the deleting transaction no longer reads it.
+        assert_eq!(TrieNodeCache::global().get(TABLE, hash), None);
+        tx.commit().unwrap();
+        assert!(!cached());
+    }
+
+    #[test]
+    fn shards_share_the_capacity() {
+        let cache = TrieNodeCache::new(32, SHARDS);
+        for i in 0..SHARDS as u64 {
+            cache.insert(TABLE, Felt::from(i), node(i));
+        }
+        for i in 0..SHARDS as u64 {
+            assert_eq!(cache.get(TABLE, Felt::from(i)), Some(node(i)));
+        }
+    }
+
+    #[test]
+    fn tables_are_kept_apart() {
+        let cache = TrieNodeCache::new(2, 1);
+        cache.insert(TABLE, Felt::from(1u64), node(1));
+
+        assert_eq!(cache.get("tree_contracts", Felt::from(1u64)), None);
+    }
+
+    #[test]
+    fn zero_capacity_disables_cache() {
+        let cache = TrieNodeCache::new(0, 1);
+        cache.insert(TABLE, Felt::from(1u64), node(1));
+
+        assert_eq!(cache.get(TABLE, Felt::from(1u64)), None);
+    }
+}
@@ -0,0 +1,146 @@
+//! Returning the free space of the database to the filesystem.
+//!
+//! Deleted rows leave free pages behind, which Sqlite reuses for new rows but does not release
+//! by itself. Databases created by pathfinder use
+//! [incremental vacuum](https://sqlite.org/pragma.html#pragma_incremental_vacuum), which
+//! releases them in steps of any size. Older databases can be switched to it by
+//! [enable_incremental_vacuum].
+
+use anyhow::Context;
+use rusqlite::Connection;
+
+/// The size of the database file, in pages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DatabaseSize {
+    /// Size of a page in bytes.
+    pub page_size: u64,
+    pub pages: u64,
+    /// Pages which are not used by any table or index.
+    pub free_pages: u64,
+}
+
+impl DatabaseSize {
+    pub fn bytes(&self) -> u64 {
+        self.pages * self.page_size
+    }
+
+    pub fn free_bytes(&self) -> u64 {
+        self.free_pages * self.page_size
+    }
+}
+
+/// Returns true if free pages can be released with [incremental_vacuum].
+pub fn incremental_vacuum_enabled(connection: &Connection) -> anyhow::Result<bool> {
+    // 2 is INCREMENTAL.
+    let mode: u8 = connection
+        .query_row("SELECT auto_vacuum FROM pragma_auto_vacuum", [], |row| {
+            row.get(0)
+        })
+        .context("Reading auto vacuum mode")?;
+    Ok(mode == 2)
+}
+
+/// Switches a database which was created without incremental vacuum to it, returning false if
+/// it already uses it.
+///
+/// This rebuilds the whole database, which takes a while for large databases and temporarily
+/// needs up to twice its size in disk space. It holds the write lock of the database until done,
+/// and cannot run within a transaction.
+pub fn enable_incremental_vacuum(connection: &Connection) -> anyhow::Result<bool> {
+    if incremental_vacuum_enabled(connection)? {
+        return Ok(false);
+    }
+
+    // The mode of an existing database only changes once it is vacuumed.
+    connection
+        .pragma_update(None, "auto_vacuum", "INCREMENTAL")
+        .context("Setting auto vacuum mode")?;
+    connection
+        .execute_batch("VACUUM")
+        .context("Vacuuming database")?;
+
+    Ok(true)
+}
+
+pub fn database_size(connection: &Connection) -> anyhow::Result<DatabaseSize> {
+    connection
+        .query_row(
+            "SELECT page_size, page_count, freelist_count
+            FROM pragma_page_size, pragma_page_count, pragma_freelist_count",
+            [],
+            |row| {
+                Ok(DatabaseSize {
+                    page_size: row.get(0)?,
+                    pages: row.get(1)?,
+                    free_pages: row.get(2)?,
+                })
+            },
+        )
+        .context("Reading database size")
+}
+
+/// Releases up to `pages` free pages to the filesystem.
+///
+/// This holds the write lock of the database until done, so `pages` should be kept small enough
+/// not to stall other writers.
+pub fn incremental_vacuum(connection: &Connection, pages: u64) -> anyhow::Result<()> {
+    // Sqlite frees the pages as the statement is stepped through.
+    let mut stmt = connection
+        .prepare(&format!("PRAGMA incremental_vacuum({pages})"))
+        .context("Preparing incremental vacuum")?;
+    let mut rows = stmt.query([]).context("Running incremental vacuum")?;
+    while rows.next().context("Running incremental vacuum")?.is_some() {}
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn frees_pages() {
+        let storage = Storage::in_memory().unwrap();
+        let conn = storage.connection().unwrap();
+        assert!(incremental_vacuum_enabled(&conn).unwrap());
+
+        conn.execute_batch(
+            r"CREATE TABLE test(data BLOB);
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100)
+            INSERT INTO test SELECT zeroblob(10000) FROM n;
+            DELETE FROM test;",
+        )
+        .unwrap();
+
+        let before = database_size(&conn).unwrap();
+        assert!(before.free_pages > 10);
+
+        incremental_vacuum(&conn, 10).unwrap();
+        let after = database_size(&conn).unwrap();
+        assert_eq!(after.free_pages, before.free_pages - 10);
+        assert!(after.pages < before.pages);
+
+        incremental_vacuum(&conn, after.free_pages).unwrap();
+        let after = database_size(&conn).unwrap();
+        assert_eq!(after.free_pages, 0);
+    }
+
+    #[test]
+    fn enables_incremental_vacuum() {
+        // A database created before incremental vacuum was enabled.
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE test(data BLOB); INSERT INTO test VALUES (x'1234');")
+            .unwrap();
+        assert!(!incremental_vacuum_enabled(&conn).unwrap());
+
+        assert!(enable_incremental_vacuum(&conn).unwrap());
+        assert!(incremental_vacuum_enabled(&conn).unwrap());
+        let data: Vec<u8> = conn
+            .query_row("SELECT data FROM test", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(data, vec![0x12, 0x34]);
+
+        assert!(!enable_incremental_vacuum(&conn).unwrap());
+    }
+}
@@ -306,3 +306,5 @@ rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_chainId"}'
 rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_getNonce","params":["latest", "0x019245f0f49d23f2379d3e3f20d1f3f46207d1c4a1d09cac8dd50e8d528aabe1"]}'
 rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_syncing"}'
 rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_pendingTransactions"}'
+rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_traceTransaction","params":{"transaction_hash":"0x74ec6667e6057becd3faff77d9ab14aecf5dde46edb7c599ee771f70f9e80ba"}}'
+rpc_call '{"jsonrpc":"2.0","id":"0","method":"starknet_traceBlockTransactions","params":{"block_hash":"0x3871c8a0c3555687515a07f365f6f5b1d8c2ae953f7844575b8bde2b2efed27"}}'
@@ -1,5 +1,6 @@
 import asyncio
 import dataclasses
+import gc
 import itertools
 import json
 import os
@@ -9,6 +10,7 @@ import sqlite3
 import sys
 import time
 import traceback
+import tracemalloc
 from abc import abstractmethod
 from dataclasses import dataclass, field
 from enum import Enum
@@ -59,6 +61,7 @@ try:
         PatriciaStateReader,
     )
     from starkware.starknet.business_logic.state.state import BlockInfo, CachedState
+    from starkware.starknet.business_logic.state.state_api import StateReader
     from starkware.starknet.definitions import fields, constants
     from starkware.starknet.definitions.constants import GasCost
     from starkware.starknet.definitions.error_codes import StarknetErrorCode
@@ -73,6 +76,8 @@ try:
     )
     from starkware.starknet.services.api.contract_class.contract_class import (
         CompiledClass,
+        CompiledClassBase,
+        DeprecatedCompiledClass,
         EntryPointType,
     )
     from starkware.starknet.services.api.contract_class.contract_class_utils import (
@@ -91,6 +96,9 @@ try:
     )
     from starkware.starkware_utils.error_handling import StarkException
     from starkware.storage.storage import FactFetchingContext, Storage
+    from starkware.starknet.core.os.contract_class.compiled_class_hash import (
+        compute_compiled_class_hash,
+    )
     from starkware.starknet.core.os.contract_class.utils import (
         ClassHashType,
         class_hash_cache_ctx_var,
@@ -112,7 +120,7 @@ except ModuleNotFoundError:
 
 
 # used from tests, and the query which asserts that the schema is of expected version.
-EXPECTED_SCHEMA_REVISION = 30
+EXPECTED_SCHEMA_REVISION = 47
 EXPECTED_CAIRO_VERSION = "0.11.0"
 
 # used by the sqlite adapter to communicate "contract state not found, nor was the patricia tree key"
@@ -126,6 +134,7 @@ class Verb(Enum):
     CALL = 0
     ESTIMATE_FEE = 1
     SIMULATE_TX = 2
+    EXECUTE_TX = 3
 
 
 class Chain(Enum):
@@ -260,12 +269,23 @@ class SimulateTx(Command):
     skip_validate: bool
 
 
+@marshmallow_dataclass.dataclass(frozen=True)
+class ExecuteTx(Command):
+    verb: ClassVar[Verb] = Verb.EXECUTE_TX
+
+    # zero means to use the gas price from the current block.
+    gas_price: int = field(metadata=fields.gas_price_metadata)
+
+    transactions: List[TransactionAndClassHashHint]
+
+
 class CommandSchema(marshmallow_oneofschema.OneOfSchema):
     type_field = "verb"
     type_schemas: Dict[str, Type[Schema]] = {
         Verb.CALL.name: Call.Schema,
         Verb.ESTIMATE_FEE.name: EstimateFee.Schema,
         Verb.SIMULATE_TX.name: SimulateTx.Schema,
+        Verb.EXECUTE_TX.name: ExecuteTx.Schema,
     }
 
     at_block = mfields.Str()
@@ -281,11 +301,15 @@ def main():
     """
     Loops on stdin, reads json commands from lines, outputs single json as a response.
     Starts by outputting "ready"
+
+    With the optional fork block number, commands are executed against the state of that
+    block cached in the fork tables instead of the synced state.
     """
-    if len(sys.argv) != 2:
-        print("usage: call.py [sqlite.db]")
+    if len(sys.argv) not in (2, 3):
+        print("usage: call.py [sqlite.db] [fork block number]")
         sys.exit(1)
     database_path = sys.argv[1]
+    fork_block = int(sys.argv[2]) if len(sys.argv) == 3 else None
 
     # make sure that regardless of the interesting platform we communicate sanely to pathfinder
     sys.stdin.reconfigure(encoding="utf-8")
@@ -319,7 +343,7 @@ def main():
         # even though "the general wisdom" is to flush on '\n', python seems to only do it
         # if it didn't add the newline to the written out string.
         print("ready", flush=True)
-        do_loop(connection, sys.stdin, sys.stdout)
+        do_loop(connection, sys.stdin, sys.stdout, fork_block)
 
 
 def check_cairolang_version():
@@ -330,7 +354,12 @@ def check_cairolang_version():
         return False
 
 
-def do_loop(connection: sqlite3.Connection, input_gen, output_file):
+def do_loop(
+    connection: sqlite3.Connection,
+    input_gen,
+    output_file,
+    fork_block: Optional[int] = None,
+):
     logger = Logger()
 
     if DEV_MODE:
@@ -340,12 +369,20 @@ def do_loop(connection: sqlite3.Connection, input_gen, output_file):
 
     contract_class_cache = LRUCache(maxsize=128)
 
+    # measures the memory each command takes, including the hint processor's allocations and the
+    # returned builtin segments, so that pathfinder can estimate the memory of the next commands
+    if not tracemalloc.is_tracing():
+        tracemalloc.start()
+
     for line in input_gen:
         if line == "" or line.startswith("#"):
             continue
 
         out = {"status": "ok"}
 
+        tracemalloc.reset_peak()
+        allocated_before = tracemalloc.get_traced_memory()[0]
+
         started_at = time.time()
         parsed_at = None
         # make the first this available for failed cases
@@ -357,11 +394,10 @@ def do_loop(connection: sqlite3.Connection, input_gen, output_file):
 
             parsed_at = time.time()
 
-            connection.execute("BEGIN")
-
-            [verb, output, inner_timings] = loop_inner(
-                connection, command, contract_class_cache
-            )
+            with Snapshot(connection):
+                [verb, output, inner_timings] = loop_inner(
+                    connection, command, contract_class_cache, fork_block
+                )
 
             # this is more backwards compatible dictionary union
             timings = {**timings, **inner_timings}
@@ -369,6 +405,20 @@ def do_loop(connection: sqlite3.Connection, input_gen, output_file):
             out["output"] = render(verb, output)
         except NoSuchBlock:
             out = {"status": "error", "kind": "NO_SUCH_BLOCK"}
+        except StatePruned:
+            out = {"status": "error", "kind": "STATE_PRUNED"}
+        except DeferredClass as exc:
+            out = {
+                "status": "error",
+                "kind": "DEFERRED_CLASS",
+                "class_hash": "0x" + exc.class_hash.hex(),
+            }
+        except ForkStateMissing as exc:
+            out = {
+                "status": "error",
+                "kind": "FORK_STATE_MISSING",
+                "missing": exc.missing,
+            }
         except UnexpectedSchemaVersion:
             out = {"status": "error", "kind": "INVALID_SCHEMA_VERSION"}
         except marshmallow.exceptions.MarshmallowError as exc:
@@ -399,8 +449,6 @@ def do_loop(connection: sqlite3.Connection, input_gen, output_file):
             report_failed(logger, command, exc)
             out = {"status": "failed", "exception": stringified}
         finally:
-            connection.rollback()
-
             completed_at = time.time()
 
             if parsed_at is not None and started_at < parsed_at:
@@ -410,6 +458,15 @@ def do_loop(connection: sqlite3.Connection, input_gen, output_file):
                 timings["execution"] = completed_at - parsed_at
 
             logger.trace(json.dumps(timings))
+
+            out["memory"] = max(
+                0, tracemalloc.get_traced_memory()[1] - allocated_before
+            )
+            # the traces and execution state reference each other, so they would otherwise
+            # only be freed by some later command, after pathfinder released the memory
+            # reserved for them
+            gc.collect()
+
             print(json.dumps(out), file=output_file, flush=True)
 
 
@@ -425,7 +482,10 @@ def report_failed(logger, command, e):
 
 
 def loop_inner(
-    connection: sqlite3.Connection, command: Command, contract_class_cache=None
+    connection: sqlite3.Connection,
+    command: Command,
+    contract_class_cache=None,
+    fork_block: Optional[int] = None,
 ):
     logger = Logger()
 
@@ -453,11 +513,16 @@ def loop_inner(
 
     # the later parts will have access to gas_price through this block_info
     try:
-        (block_info, storage_commitment, class_commitment) = resolve_block(
-            connection, at_block, command.gas_price
-        )
+        if fork_block is not None:
+            block_info = resolve_fork_block(
+                connection, fork_block, at_block, command.gas_price
+            )
+        else:
+            (block_info, storage_commitment, class_commitment) = resolve_block(
+                connection, at_block, command.gas_price
+            )
     except NoSuchBlock:
-        if fallback_to_latest:
+        if fallback_to_latest and fork_block is None:
             pending_updates = {}
             pending_deployed = []
             pending_nonces = {}
@@ -478,19 +543,26 @@ def loop_inner(
 
     general_config = create_general_config(command.chain.value)
 
-    if class_commitment == 0:
-        class_commitment = None
+    if fork_block is not None:
+        adapter = None
+        state_reader = ForkStateReader(connection, fork_block)
+    else:
+        if class_commitment == 0:
+            class_commitment = None
+
+        adapter = SqliteAdapter(connection)
+        # hook up the sqlite adapter
+        ffc = FactFetchingContext(storage=adapter, hash_func=pedersen_hash_func)
+        global_state_root = PatriciaTree(storage_commitment, 251)
+        contract_class_root = (
+            PatriciaTree(class_commitment, 251)
+            if class_commitment is not None
+            else None
+        )
+        state_reader = PatriciaStateReader(
+            global_state_root, contract_class_root, ffc, contract_class_storage=adapter
+        )
 
-    adapter = SqliteAdapter(connection)
-    # hook up the sqlite adapter
-    ffc = FactFetchingContext(storage=adapter, hash_func=pedersen_hash_func)
-    global_state_root = PatriciaTree(storage_commitment, 251)
-    contract_class_root = (
-        PatriciaTree(class_commitment, 251) if class_commitment is not None else None
-    )
-    state_reader = PatriciaStateReader(
-        global_state_root, contract_class_root, ffc, contract_class_storage=adapter
-    )
     async_state = CachedState(
         block_info=block_info,
         state_reader=state_reader,
@@ -499,6 +571,34 @@ def loop_inner(
 
     apply_pending(async_state, pending_updates, pending_deployed, pending_nonces)
 
+    try:
+        ret = execute(logger, command, async_state, general_config, block_info, timings)
+    except Exception as exc:
+        # cairo-lang wraps the errors raised while executing hints, so report the class
+        # or fork state which could not be read no matter how the error got here
+        if adapter is not None and adapter.deferred_class is not None:
+            raise DeferredClass(adapter.deferred_class) from exc
+        if (
+            isinstance(state_reader, ForkStateReader)
+            and state_reader.missing is not None
+        ):
+            raise ForkStateMissing(state_reader.missing) from exc
+        raise
+
+    if adapter is not None:
+        timings["sql"] = {
+            "timings": adapter.elapsed,
+            "counts": adapter.counts,
+            "cache": adapter.cache,
+        }
+    timings["cairo-lang"] = time.time() - started_at
+
+    return ret
+
+
+def execute(logger, command, async_state, general_config, block_info, timings):
+    ret = None
+
     if isinstance(command, Call):
         result = asyncio.run(
             do_call(
@@ -531,16 +631,18 @@ def loop_inner(
             )
         )
         ret = (command.verb, simulated_transactions, timings)
+    elif isinstance(command, ExecuteTx):
+        executed_transactions = asyncio.run(
+            do_execute_tx(
+                async_state,
+                general_config,
+                command.transactions,
+            )
+        )
+        ret = (command.verb, executed_transactions, timings)
     else:
         logger.error(f"Unrecognised command: {command}")
 
-    timings["sql"] = {
-        "timings": adapter.elapsed,
-        "counts": adapter.counts,
-        "cache": adapter.cache,
-    }
-    timings["cairo-lang"] = time.time() - started_at
-
     return ret
 
 
@@ -551,6 +653,8 @@ def render(verb, vals):
         return FeeEstimation.Schema(many=True).dump(vals)
     elif verb == Verb.SIMULATE_TX:
         return TransactionSimulation.Schema(many=True).dump(vals)
+    elif verb == Verb.EXECUTE_TX:
+        return TransactionExecution.Schema(many=True).dump(vals)
 
 
 def as_hex(x):
@@ -571,6 +675,38 @@ class TransactionSimulation(BaseResponseObject):
     fee_estimation: FeeEstimation
 
 
+felt_list_metadata = dict(
+    marshmallow_field=mfields.List(
+        everest_fields.FeltField.get_marshmallow_field(required=True)
+    )
+)
+
+
+@marshmallow_dataclass.dataclass(frozen=True)
+class ExecutedEvent(BaseResponseObject):
+    from_address: int = field(metadata=felt_metadata)
+    keys: List[int] = field(metadata=felt_list_metadata)
+    data: List[int] = field(metadata=felt_list_metadata)
+
+
+@marshmallow_dataclass.dataclass(frozen=True)
+class ExecutedMessage(BaseResponseObject):
+    from_address: int = field(metadata=felt_metadata)
+    to_address: int = field(metadata=felt_metadata)
+    payload: List[int] = field(metadata=felt_list_metadata)
+
+
+@marshmallow_dataclass.dataclass(frozen=True)
+class TransactionExecution(BaseResponseObject):
+    """
+    The parts of a transaction receipt which executing the transaction produces.
+    """
+
+    actual_fee: int = field(metadata=felt_metadata)
+    events: List[ExecutedEvent]
+    l2_to_l1_messages: List[ExecutedMessage]
+
+
 def int_hash_or_latest(s: str):
     if s == "latest":
         return s
@@ -646,6 +782,14 @@ def resolve_block(
         # zero rows, or wrong number of columns (unlikely)
         raise NoSuchBlock(at_block) from exc
 
+    # the tries of pruned blocks are partially deleted
+    pruned = connection.execute(
+        "select 1 from tree_pruning_ranges where first_block <= ? and last_block >= ?",
+        [block_number, block_number],
+    ).fetchone()
+    if pruned is not None:
+        raise StatePruned(block_number)
+
     gas_price = int.from_bytes(gas_price, "big")
 
     if forced_gas_price != 0:
@@ -663,11 +807,68 @@ def resolve_block(
     )
 
 
+def resolve_fork_block(
+    connection: sqlite3.Connection, fork_block: int, at_block, forced_gas_price: int
+) -> BlockInfo:
+    """
+    Fork mode has only the one block, which at_block must refer to either by
+    its number, its hash or as the latest block.
+    """
+
+    cursor = connection.execute(
+        "select hash, timestamp, gas_price, sequencer_address, starknet_version from fork_blocks where number = ?",
+        [fork_block],
+    )
+    row = cursor.fetchone()
+
+    if row is None:
+        raise NoSuchBlock(at_block)
+
+    [block_hash, block_time, gas_price, sequencer_address, starknet_version] = row
+
+    if isinstance(at_block, int):
+        if at_block != fork_block:
+            raise NoSuchBlock(at_block)
+    elif isinstance(at_block, bytes):
+        if at_block.rjust(32, b"\x00") != block_hash:
+            raise NoSuchBlock(at_block)
+    else:
+        assert at_block == "latest", f"unexpected block: {at_block}"
+
+    gas_price = int.from_bytes(gas_price, "big")
+
+    if forced_gas_price != 0:
+        gas_price = forced_gas_price
+
+    sequencer_address = int.from_bytes(sequencer_address, "big")
+
+    return BlockInfo(
+        fork_block, block_time, gas_price, sequencer_address, starknet_version
+    )
+
+
 class NoSuchBlock(Exception):
     def __init__(self, at_block):
         super().__init__(f"Could not find the block by: {at_block}")
 
 
+class StatePruned(Exception):
+    def __init__(self, block_number):
+        super().__init__(f"State of block {block_number} has been pruned")
+
+
+class DeferredClass(Exception):
+    def __init__(self, class_hash):
+        super().__init__(f"Class definition not downloaded yet: 0x{class_hash.hex()}")
+        self.class_hash = class_hash
+
+
+class ForkStateMissing(Exception):
+    def __init__(self, missing):
+        super().__init__(f"Fork state not fetched yet: {missing}")
+        self.missing = missing
+
+
 class UnexpectedSchemaVersion(Exception):
     def __init__(self):
         super().__init__("Schema mismatch, is this pathfinders database file?")
@@ -700,15 +901,42 @@ class Logger:
         print(f"{level}{json.dumps(message)}", file=sys.stderr, flush=True)
 
 
+class Snapshot:
+    """
+    A point-in-time view of pathfinder's database for executing a single command.
+
+    All reads made while the snapshot is open see the database as it was when the
+    snapshot was taken, even while pathfinder keeps syncing new blocks. This needs
+    the database to be in WAL mode, otherwise the open snapshot blocks pathfinder
+    from writing until it is closed.
+    """
+
+    def __init__(self, connection: sqlite3.Connection):
+        self.connection = connection
+
+    def __enter__(self):
+        self.connection.execute("BEGIN")
+        # BEGIN is deferred, which would take the snapshot only at the first read,
+        # so read right away to take it before the command starts executing.
+        self.connection.execute("select 1 from starknet_blocks limit 1").fetchall()
+        return self
+
+    def __exit__(self, exc_type, exc_value, traceback):
+        self.connection.rollback()
+        return False
+
+
 class SqliteAdapter(Storage):
     """
     Reads from pathfinders' database to give cairo-lang call implementation the nodes as needed
-    however using a single transaction.
+    however using a single transaction, which is the [Snapshot] of the command.
     """
 
     def __init__(self, connection: sqlite3.Connection):
         assert connection.in_transaction, "first query should had started a transaction"
         self.connection = connection
+        # the class whose definition was found missing, if any
+        self.deferred_class = None
         self.elapsed = {
             "total": 0,
             "patricia_node": 0,
@@ -859,11 +1087,19 @@ class SqliteAdapter(Storage):
                 casm_definitions.compiled_class_hash is null""",
             [suffix],
         )
-        [only] = next(cursor, [None])
+        row = cursor.fetchone()
 
-        if only is None:
+        if row is None:
             return None
 
+        [only] = row
+
+        if only is None:
+            # pathfinder has deferred downloading the definition, which needs to be
+            # downloaded before executing the command again
+            self.deferred_class = suffix
+            raise DeferredClass(suffix)
+
         # pathfinder stores zstd compressed json blobs
         decompressor = zstandard.ZstdDecompressor()
         only = decompressor.decompress(only)
@@ -927,6 +1163,119 @@ class SqliteAdapter(Storage):
         return suffix
 
 
+class ForkStateReader(StateReader):
+    """
+    Reads the state of the forked block from the fork tables, which pathfinder fills
+    from the remote network on demand.
+
+    Reading anything which has not been fetched yet raises ForkStateMissing, after
+    which pathfinder fetches it and executes the command again.
+    """
+
+    def __init__(self, connection: sqlite3.Connection, block_number: int):
+        self.connection = connection
+        self.block_number = block_number
+        # the state which was found missing, if any
+        self.missing = None
+        # Sierra classes are looked up by their compiled class hash, which is
+        # computed from the CASM while resolving it for the class hash
+        self.compiled_classes = {}
+
+    def report_missing(self, missing):
+        self.missing = missing
+        raise ForkStateMissing(missing)
+
+    async def get_storage_at(self, contract_address: int, key: int) -> int:
+        cursor = self.connection.execute(
+            "select value from fork_storage where block_number = ? and contract_address = ? and key = ?",
+            [self.block_number, felt_to_bytes(contract_address), felt_to_bytes(key)],
+        )
+        row = cursor.fetchone()
+
+        if row is None and self.is_imported(contract_address):
+            # imported contracts hold all of their non-zero storage
+            return 0
+
+        if row is None:
+            self.report_missing(
+                {
+                    "kind": "storage",
+                    "contract_address": as_hex(contract_address),
+                    "key": as_hex(key),
+                }
+            )
+
+        return int.from_bytes(row[0], "big")
+
+    async def get_nonce_at(self, contract_address: int) -> int:
+        [_, nonce] = self.fetch_contract(contract_address)
+        return nonce
+
+    async def get_class_hash_at(self, contract_address: int) -> int:
+        [class_hash, _] = self.fetch_contract(contract_address)
+        return class_hash
+
+    async def get_compiled_class_hash(self, class_hash: int) -> int:
+        [_, casm_definition] = self.fetch_class(class_hash)
+
+        if casm_definition is None:
+            # deprecated classes have no compiled class hash
+            return 0
+
+        compiled_class = CompiledClass.loads(zstandard.decompress(casm_definition))
+        compiled_class_hash = compute_compiled_class_hash(compiled_class)
+        self.compiled_classes[compiled_class_hash] = compiled_class
+
+        return compiled_class_hash
+
+    async def get_compiled_class(self, compiled_class_hash: int) -> CompiledClassBase:
+        compiled_class = self.compiled_classes.get(compiled_class_hash, None)
+        if compiled_class is not None:
+            return compiled_class
+
+        # deprecated classes are looked up by their class hash instead
+        [definition, _] = self.fetch_class(compiled_class_hash)
+        return DeprecatedCompiledClass.loads(zstandard.decompress(definition))
+
+    def is_imported(self, contract_address: int) -> bool:
+        cursor = self.connection.execute(
+            "select 1 from fork_contracts where block_number = ? and contract_address = ? and imported = 1",
+            [self.block_number, felt_to_bytes(contract_address)],
+        )
+        return cursor.fetchone() is not None
+
+    def fetch_contract(self, contract_address: int) -> Tuple[int, int]:
+        cursor = self.connection.execute(
+            "select class_hash, nonce from fork_contracts where block_number = ? and contract_address = ?",
+            [self.block_number, felt_to_bytes(contract_address)],
+        )
+        row = cursor.fetchone()
+
+        if row is None:
+            self.report_missing(
+                {"kind": "contract", "contract_address": as_hex(contract_address)}
+            )
+
+        [class_hash, nonce] = row
+        return (int.from_bytes(class_hash, "big"), int.from_bytes(nonce, "big"))
+
+    def fetch_class(self, class_hash: int) -> Tuple[bytes, Optional[bytes]]:
+        cursor = self.connection.execute(
+            "select definition, casm_definition from fork_classes where hash = ?",
+            [felt_to_bytes(class_hash)],
+        )
+        row = cursor.fetchone()
+
+        if row is None:
+            self.report_missing({"kind": "class", "class_hash": as_hex(class_hash)})
+
+        return row
+
+
+def felt_to_bytes(x: int) -> bytes:
+    return x.to_bytes(32, "big")
+
+
 async def do_call(
     async_state: CachedState,
     general_config,
@@ -1097,6 +1446,49 @@ async def do_simulate_tx(
     return simulated_transactions
 
 
+async def do_execute_tx(
+    state: CachedState,
+    general_config: StarknetGeneralConfig,
+    transactions: List[TransactionAndClassHashHint],
+):
+    """
+    Executes the transactions one after the other, as they would be executed in a block.
+    """
+    executed_transactions = []
+
+    class_hash_cache = LRUCache(maxsize=128)
+    with set_class_hash_cache(class_hash_cache):
+        for transaction in transactions:
+            tx_info = await simulate_account_tx(
+                state, general_config, transaction, skip_validate=False
+            )
+
+            events = [
+                ExecutedEvent(
+                    from_address=event.from_address, keys=event.keys, data=event.data
+                )
+                for event in tx_info.get_sorted_events()
+            ]
+            messages = [
+                ExecutedMessage(
+                    from_address=message.from_address,
+                    to_address=message.to_address,
+                    payload=message.payload,
+                )
+                for message in tx_info.get_sorted_l2_to_l1_messages()
+            ]
+
+            executed_transactions.append(
+                TransactionExecution(
+                    actual_fee=tx_info.actual_fee,
+                    events=events,
+                    l2_to_l1_messages=messages,
+                )
+            )
+
+    return executed_transactions
+
+
 def apply_pending(
     state: CachedState,
     updates: Dict[int, List[StorageDiff]],
@@ -32,6 +32,8 @@ from pathfinder_worker.call import (
     Command,
     EstimateFee,
     FeeEstimation,
+    Snapshot,
+    TransactionExecution,
     TransactionSimulation,
     TransactionAndClassHashHint,
     check_cairolang_version,
@@ -163,8 +165,8 @@ def test_relative_path(path):
 
 
 # This only contains the tables required for call.
-def inmemory_with_tables():
-    con = sqlite3.connect(":memory:")
+def inmemory_with_tables(database=":memory:"):
+    con = sqlite3.connect(database)
     con.isolation_level = None
 
     cur = con.execute("BEGIN")
@@ -246,6 +248,50 @@ def inmemory_with_tables():
             hash                BLOB    PRIMARY KEY NOT NULL,
             compiled_class_hash BLOB    NOT NULL
         );
+
+        -- Remote state cached in fork mode.
+        CREATE TABLE fork_blocks (
+            number            INTEGER PRIMARY KEY NOT NULL,
+            hash              BLOB    NOT NULL,
+            timestamp         INTEGER NOT NULL,
+            gas_price         BLOB    NOT NULL,
+            sequencer_address BLOB    NOT NULL,
+            starknet_version  TEXT
+        );
+
+        CREATE TABLE fork_contracts (
+            block_number     INTEGER NOT NULL,
+            contract_address BLOB    NOT NULL,
+            class_hash       BLOB    NOT NULL,
+            nonce            BLOB    NOT NULL,
+            imported         INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (block_number, contract_address)
+        );
+
+        CREATE TABLE fork_storage (
+            block_number     INTEGER NOT NULL,
+            contract_address BLOB    NOT NULL,
+            key              BLOB    NOT NULL,
+            value            BLOB    NOT NULL,
+            PRIMARY KEY (block_number, contract_address, key)
+        );
+
+        CREATE TABLE fork_classes (
+            hash             BLOB PRIMARY KEY NOT NULL,
+            definition       BLOB NOT NULL,
+            casm_definition  BLOB
+        );
+
+        CREATE TABLE tree_pruning (
+            idx             INTEGER PRIMARY KEY,
+            retained_blocks INTEGER
+        );
+        INSERT INTO tree_pruning (idx, retained_blocks) VALUES (1, NULL);
+
+        CREATE TABLE tree_pruning_ranges (
+            first_block INTEGER NOT NULL,
+            last_block  INTEGER PRIMARY KEY
+        );
         """
     )
 
@@ -440,7 +486,7 @@ def default_132_on_3_scenario(con, input_jsons):
 
     print(output)
 
-    output = [json.loads(line) for line in output.splitlines()]
+    output = [without_memory(json.loads(line)) for line in output.splitlines()]
 
     if len(output) == 1:
         output = output[0]
@@ -448,6 +494,12 @@ def default_132_on_3_scenario(con, input_jsons):
     return output
 
 
+def without_memory(output):
+    # the measured memory varies between runs, see test_memory_is_measured
+    assert isinstance(output.pop("memory"), int)
+    return output
+
+
 def test_success():
     con = inmemory_with_tables()
     (contract_address, _) = populate_test_contract_with_132_on_3(con)
@@ -471,6 +523,161 @@ def test_success():
     assert number == expected == block_hash == latest
 
 
+def test_memory_is_measured():
+    con = inmemory_with_tables()
+    (contract_address, _) = populate_test_contract_with_132_on_3(con)
+    contract_address = hex(contract_address)
+    entry_point = hex(get_selector_from_name("get_value"))
+
+    command = f'{{ "verb": "CALL", "at_block": "1", "contract_address": "{contract_address}", "entry_point_selector": "{entry_point}", "calldata": ["0x84"], "gas_price": 0, "chain": "TESTNET", "pending_updates": {{}}, "pending_deployed": [], "pending_nonces": {{}}, "pending_timestamp": 0 }}'
+
+    output_catcher = io.StringIO()
+    do_loop(con, [command, "not json"], output_catcher)
+    [executed, invalid] = [
+        json.loads(line) for line in output_catcher.getvalue().splitlines()
+    ]
+
+    # executing the class takes far more than failing to parse the command
+    assert executed["status"] == "ok"
+    assert invalid["status"] == "error"
+    assert executed["memory"] > invalid["memory"]
+
+
+def test_deferred_class():
+    con = inmemory_with_tables()
+    (contract_address, _) = populate_test_contract_with_132_on_3(con)
+    class_hash = 0x050B2148C0D782914E0B12A1A32ABE5E398930B7E914F82C65CB7AFCE0A0AB9B
+
+    # pathfinder has not downloaded the definition yet
+    con.execute(
+        "update class_definitions set definition = null where hash = ?",
+        [felt_to_bytes(class_hash)],
+    )
+
+    contract_address = hex(contract_address)
+    entry_point = hex(get_selector_from_name("get_value"))
+
+    command = f'{{ "verb": "CALL", "at_block": "1", "contract_address": "{contract_address}", "entry_point_selector": "{entry_point}", "calldata": ["0x84"], "gas_price": 0, "chain": "TESTNET", "pending_updates": {{}}, "pending_deployed": [], "pending_nonces": {{}}, "pending_timestamp": 0 }}'
+
+    output = default_132_on_3_scenario(con, [command])
+
+    assert output == {
+        "status": "error",
+        "kind": "DEFERRED_CLASS",
+        "class_hash": "0x" + felt_to_bytes(class_hash).hex(),
+    }
+
+
+def test_fork_state_is_fetched_on_demand():
+    con = inmemory_with_tables()
+    contract_address = 0x57DDE83C18C0EFE7123C36A52D704CF27D5C38CDF0B1E1EDC3B0DAE3EE4E374
+    class_hash = 0x050B2148C0D782914E0B12A1A32ABE5E398930B7E914F82C65CB7AFCE0A0AB9B
+    block_hash = b"some forked blockhash".rjust(32, b"\x00")
+    fork_block = 5
+
+    con.execute(
+        "insert into fork_blocks (number, hash, timestamp, gas_price, sequencer_address) values (?, ?, 1, ?, ?)",
+        [fork_block, block_hash, b"\x00" * 16, b"\x00" * 32],
+    )
+
+    entry_point = hex(get_selector_from_name("get_value"))
+    common_command_data = f'"contract_address": "{hex(contract_address)}", "entry_point_selector": "{entry_point}", "calldata": ["0x84"], "gas_price": 0, "chain": "TESTNET", "pending_updates": {{}}, "pending_deployed": [], "pending_nonces": {{}}, "pending_timestamp": 0'
+
+    def execute(at_block="latest"):
+        output_catcher = io.StringIO()
+        command = f'{{ "verb": "CALL", "at_block": "{at_block}", {common_command_data} }}'
+        do_loop(con, [command], output_catcher, fork_block)
+        return without_memory(json.loads(output_catcher.getvalue()))
+
+    def missing(missing):
+        return {"status": "error", "kind": "FORK_STATE_MISSING", "missing": missing}
+
+    # each piece of state is reported missing until pathfinder has fetched it
+    assert execute() == missing(
+        {"kind": "contract", "contract_address": call.as_hex(contract_address)}
+    )
+    con.execute(
+        "insert into fork_contracts (block_number, contract_address, class_hash, nonce) values (?, ?, ?, ?)",
+        [
+            fork_block,
+            felt_to_bytes(contract_address),
+            felt_to_bytes(class_hash),
+            felt_to_bytes(0),
+        ],
+    )
+
+    assert execute() == missing({"kind": "class", "class_hash": call.as_hex(class_hash)})
+    path = test_relative_path(
+        "../../../crates/gateway-test-fixtures/fixtures/contracts/contract_definition.json.zst"
+    )
+    with open(path, "rb") as f:
+        con.execute(
+            "insert into fork_classes (hash, definition) values (?, ?)",
+            [felt_to_bytes(class_hash), f.read()],
+        )
+
+    assert execute() == missing(
+        {
+            "kind": "storage",
+            "contract_address": call.as_hex(contract_address),
+            "key": call.as_hex(132),
+        }
+    )
+    con.execute(
+        "insert into fork_storage (block_number, contract_address, key, value) values (?, ?, ?, ?)",
+        [
+            fork_block,
+            felt_to_bytes(contract_address),
+            felt_to_bytes(132),
+            felt_to_bytes(3),
+        ],
+    )
+
+    expected = {"status": "ok", "output": ["0x03"]}
+    assert execute() == expected
+    assert execute(str(fork_block)) == expected
+    assert execute("0x" + block_hash.hex()) == expected
+
+    # only the forked block exists
+    assert execute(str(fork_block + 1)) == {"status": "error", "kind": "NO_SUCH_BLOCK"}
+
+    # the storage of an imported contract is zero unless it was part of the dump
+    con.execute(
+        "delete from fork_storage where block_number = ? and contract_address = ?",
+        [fork_block, felt_to_bytes(contract_address)],
+    )
+    con.execute(
+        "update fork_contracts set imported = 1 where block_number = ? and contract_address = ?",
+        [fork_block, felt_to_bytes(contract_address)],
+    )
+    assert execute() == {"status": "ok", "output": ["0x0"]}
+
+
+def test_snapshot_does_not_see_later_blocks(tmp_path):
+    database = tmp_path / "snapshot.sqlite"
+    con = inmemory_with_tables(str(database))
+    con.execute("PRAGMA journal_mode=WAL")
+    populate_test_contract_with_132_on_3(con)
+
+    reader = sqlite3.connect(f"file:{database}?mode=ro", uri=True)
+    reader.isolation_level = None
+
+    with Snapshot(reader):
+        # sync stores a new block while the command is executing
+        con.execute(
+            """insert into starknet_blocks (hash, number, timestamp, root, gas_price, sequencer_address, class_commitment)
+            select ?, 2, 2, root, gas_price, sequencer_address, class_commitment from starknet_blocks where number = 1""",
+            [b"another blockhash".rjust(32, b"\x00")],
+        )
+
+        (block_info, _, _) = resolve_block(reader, "latest", 0)
+        assert block_info.block_number == 1
+
+    with Snapshot(reader):
+        (block_info, _, _) = resolve_block(reader, "latest", 0)
+        assert block_info.block_number == 2
+
+
 def test_positive_directly():
     """
     this is like test_success but does it directly with the do_call, instead of the json wrapping, which hides exceptions which come from upgrading.
@@ -606,6 +813,28 @@ def test_no_such_block():
     assert latest == expected
 
 
+def test_state_pruned():
+    con = inmemory_with_tables()
+    (contract_address, _) = populate_test_contract_with_132_on_3(con)
+    contract_address = hex(contract_address)
+    entry_point = hex(get_selector_from_name("get_value"))
+
+    common_command_data = f'"contract_address": "{contract_address}", "entry_point_selector": "{entry_point}", "calldata": ["0x84"], "gas_price": 0, "chain": "TESTNET", "pending_updates": {{}}, "pending_deployed": [], "pending_nonces": {{}}, "pending_timestamp": 0'
+
+    con.execute("update tree_pruning set retained_blocks = 0")
+    con.execute(
+        "insert into tree_pruning_ranges (first_block, last_block) values (1, 1)"
+    )
+    con.commit()
+
+    [output] = default_132_on_3_scenario(
+        con,
+        [f'{{ "verb": "CALL", "at_block": "1", {common_command_data} }}'],
+    )
+
+    assert output == {"status": "error", "kind": "STATE_PRUNED"}
+
+
 def test_check_cairolang_version():
     # run this here as well so that we get earlier than CI feedback
     # of another constant that needs to be upgraded
@@ -2235,3 +2464,66 @@ def test_simulate_transaction_succeeds():
     expected = TransactionSimulation.Schema().loads(expected_json)
 
     assert output == [expected]
+
+
+def test_execute_transaction_succeeds():
+    con = inmemory_with_tables()
+
+    dummy_account_contract_path = test_relative_path(
+        "../../../crates/gateway-test-fixtures/fixtures/contracts/dummy_account.json.zst"
+    )
+    dummy_account_contract_class_hash = (
+        0x00AF5F6EE1C2AD961F0B1CD3FA4285CEFAD65A418DD105719FAA5D47583EB0A8
+    )
+    cur = con.execute("BEGIN")
+    declare_class(cur, dummy_account_contract_class_hash, dummy_account_contract_path)
+
+    con.execute(
+        """insert into starknet_blocks (hash, number, timestamp, root, gas_price, sequencer_address) values (?, 1, 1, ?, ?, ?)""",
+        [
+            b"some blockhash somewhere".rjust(32, b"\x00"),
+            b"\x00" * 32,
+            b"\x00" * 16,
+            b"\x00" * 32,
+        ],
+    )
+    con.commit()
+
+    command_json = """
+    {
+        "verb": "EXECUTE_TX",
+        "at_block": "latest",
+        "chain": "TESTNET",
+        "pending_updates": {},
+        "pending_deployed": [],
+        "pending_nonces": {},
+        "pending_timestamp": 42,
+        "gas_price": "0x1",
+        "transactions": [{
+            "transaction": {
+                "contract_address_salt": "0x46c0d4abf0192a788aca261e58d7031576f7d8ea5229f452b0f23e691dd5971",
+                "max_fee": "0x0",
+                "signature": [
+                    "0x296ab4b0b7cb0c6929c4fb1e04b782511dffb049f72a90efe5d53f0515eab88",
+                    "0x4e80d8bb98a9baf47f6f0459c2329a5401538576e76436acaf5f56c573c7d77"
+                ],
+                "class_hash": "0xaf5f6ee1c2ad961f0b1cd3fa4285cefad65a418dd105719faa5d47583eb0a8",
+                "nonce": "0x0",
+                "version": "0x100000000000000000000000000000001",
+                "constructor_calldata": [],
+                "type": "DEPLOY_ACCOUNT"
+            },
+            "class_hash_hint": null
+        }]
+    }
+    """
+
+    command = Command.Schema().loads(command_json)
+
+    con.execute("BEGIN")
+
+    (_verb, output, _timings) = loop_inner(con, command)
+
+    assert output == [
+        TransactionExecution(actual_fee=0xC18, events=[], l2_to_l1_messages=[])
+    ]